@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+
+use kube::{core::DynamicObject, ResourceExt};
+
+/// Field manager kubemc applies objects as
+pub const FIELD_MANAGER: &str = "kubemc";
+
+// Kinds other objects depend on, applied right after namespaces and CRDs
+const FOUNDATION_KINDS: &[&str] = &[
+    "PriorityClass",
+    "StorageClass",
+    "ServiceAccount",
+    "ClusterRole",
+    "ClusterRoleBinding",
+    "Role",
+    "RoleBinding",
+    "ConfigMap",
+    "Secret",
+    "PersistentVolume",
+    "PersistentVolumeClaim",
+    "ResourceQuota",
+    "LimitRange",
+];
+
+// Webhooks go last, so they cannot reject the objects of the manifest they are served by
+const WEBHOOK_KINDS: &[&str] = &[
+    "MutatingWebhookConfiguration",
+    "ValidatingWebhookConfiguration",
+];
+
+/// A document of a manifest being applied, along with its place in the file
+#[derive(Clone, Debug)]
+pub struct ManifestDocument {
+    /// 1-based position among the objects of the manifest
+    pub position: usize,
+    pub object: DynamicObject,
+}
+
+fn kind(object: &DynamicObject) -> &str {
+    object
+        .types
+        .as_ref()
+        .map(|t| t.kind.as_str())
+        .unwrap_or_default()
+}
+
+fn group(object: &DynamicObject) -> &str {
+    object
+        .types
+        .as_ref()
+        .and_then(|t| t.api_version.split_once('/'))
+        .map(|(group, _)| group)
+        .unwrap_or_default()
+}
+
+// Where a kind goes in the apply order, lower first
+fn apply_rank(object: &DynamicObject) -> u8 {
+    match kind(object) {
+        "Namespace" => 0,
+        "CustomResourceDefinition" => 1,
+        kind if FOUNDATION_KINDS.contains(&kind) => 2,
+        kind if WEBHOOK_KINDS.contains(&kind) => 4,
+        _ => 3,
+    }
+}
+
+/// Puts the objects of a manifest in the order they can be applied in: namespaces and CRDs
+/// first, then what workloads depend on, then everything else and webhooks last. Objects of the
+/// same rank keep their order in the manifest.
+pub fn apply_order(objects: Vec<DynamicObject>) -> Vec<ManifestDocument> {
+    let mut documents: Vec<ManifestDocument> = objects
+        .into_iter()
+        .enumerate()
+        .map(|(i, object)| ManifestDocument {
+            position: i + 1,
+            object,
+        })
+        .collect();
+    documents.sort_by_key(|document| apply_rank(&document.object));
+    documents
+}
+
+/// The CRDs of a manifest by the group and kind they define, for holding back their instances
+/// until each cluster established them
+pub fn manifest_crds(documents: &[ManifestDocument]) -> BTreeMap<(String, String), String> {
+    documents
+        .iter()
+        .filter(|document| kind(&document.object) == "CustomResourceDefinition")
+        .filter_map(|document| {
+            let spec = &document.object.data["spec"];
+            let group = spec["group"].as_str()?;
+            let kind = spec["names"]["kind"].as_str()?;
+            Some((
+                (group.to_owned(), kind.to_owned()),
+                document.object.name_any(),
+            ))
+        })
+        .collect()
+}
+
+/// Name of the manifest CRD defining the object, None for objects of kinds served already
+pub fn defining_crd<'a>(
+    object: &DynamicObject,
+    crds: &'a BTreeMap<(String, String), String>,
+) -> Option<&'a String> {
+    crds.get(&(group(object).to_owned(), kind(object).to_owned()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn object(api_version: &str, kind: &str, name: &str) -> DynamicObject {
+        serde_json::from_value(serde_json::json!({
+            "apiVersion": api_version,
+            "kind": kind,
+            "metadata": {"name": name},
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn order_manifest_documents() {
+        let mut crd = object(
+            "apiextensions.k8s.io/v1",
+            "CustomResourceDefinition",
+            "certificates.cert-manager.io",
+        );
+        crd.data = serde_json::json!({
+            "spec": {"group": "cert-manager.io", "names": {"kind": "Certificate"}},
+        });
+        let objects = vec![
+            object(
+                "admissionregistration.k8s.io/v1",
+                "ValidatingWebhookConfiguration",
+                "cert-manager-webhook",
+            ),
+            object("apps/v1", "Deployment", "cert-manager"),
+            object("cert-manager.io/v1", "Certificate", "web-tls"),
+            crd,
+            object("v1", "ServiceAccount", "cert-manager"),
+            object("v1", "Namespace", "cert-manager"),
+            object("apps/v1", "Deployment", "cert-manager-webhook"),
+        ];
+        let documents = apply_order(objects);
+        let order: Vec<(usize, String)> = documents
+            .iter()
+            .map(|d| (d.position, kind(&d.object).to_owned()))
+            .collect();
+        assert_eq!(
+            order,
+            [
+                (6, "Namespace".into()),
+                (4, "CustomResourceDefinition".into()),
+                (5, "ServiceAccount".into()),
+                (2, "Deployment".into()),
+                (3, "Certificate".into()),
+                (7, "Deployment".into()),
+                (1, "ValidatingWebhookConfiguration".into()),
+            ]
+        );
+
+        let crds = manifest_crds(&documents);
+        let certificate = &documents[4].object;
+        assert_eq!(
+            defining_crd(certificate, &crds).map(String::as_str),
+            Some("certificates.cert-manager.io")
+        );
+        assert_eq!(defining_crd(&documents[3].object, &crds), None);
+    }
+}
@@ -0,0 +1,313 @@
+use std::{fmt::Display, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use k8s_openapi::chrono::{DateTime, Duration, Utc};
+
+use crate::{config::is_dns1123_label, index::names_kind, output::format_duration};
+
+/// Parses an age such as `45s`, `90m`, `1h30m` or `30d` into seconds, the short form ages are
+/// shown in. A bare number has no unit and is rejected.
+pub fn parse_age(age: &str) -> Option<i64> {
+    let mut seconds: i64 = 0;
+    let mut number = String::new();
+    for c in age.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            'd' => 86400,
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return None,
+        };
+        seconds = seconds.checked_add(number.parse::<i64>().ok()?.checked_mul(unit)?)?;
+        number.clear();
+    }
+    (number.is_empty() && !age.is_empty()).then_some(seconds)
+}
+
+/// Parses an age flag such as `--since 1h`
+pub fn parse_age_arg(age: &str) -> Result<Duration> {
+    parse_age(age)
+        .map(Duration::seconds)
+        .ok_or_else(|| anyhow!("invalid age {}, expected e.g. 45s, 90m, 1h30m or 30d", age))
+}
+
+/// Limits on how long ago objects were created
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct AgeWindow {
+    /// Only objects created at most this long ago
+    pub since: Option<Duration>,
+    /// Only objects created at least this long ago
+    pub older_than: Option<Duration>,
+}
+
+impl AgeWindow {
+    pub fn is_empty(&self) -> bool {
+        self.since.is_none() && self.older_than.is_none()
+    }
+
+    /// Fails for windows no object can fall in, such as `--since 1h --older-than 1d`
+    pub fn validate(&self) -> Result<()> {
+        match (self.since, self.older_than) {
+            (Some(since), Some(older_than)) if since < older_than => Err(anyhow!(
+                "--since must be longer than --older-than, no object is both younger than {} and \
+                 older than {}",
+                format_duration(since),
+                format_duration(older_than)
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether an object created at `created` falls in the window. Objects without a creation
+    /// time only match an empty window.
+    pub fn contains(&self, created: Option<DateTime<Utc>>, now: DateTime<Utc>) -> bool {
+        if self.is_empty() {
+            return true;
+        }
+        let Some(created) = created else {
+            return false;
+        };
+        let age = now - created;
+        self.since.is_none_or(|since| age <= since)
+            && self.older_than.is_none_or(|older_than| age >= older_than)
+    }
+}
+
+/// A single object, written as `KIND/NAME`, `KIND.GROUP/NAME` or `NAMESPACE/KIND/NAME`, or
+/// given as separate `KIND NAME` arguments
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjectRef {
+    pub namespace: Option<String>,
+    /// Kind, plural or short name, optionally qualified with the API group as in `cronjobs.batch`
+    pub resource: String,
+    pub name: String,
+}
+
+impl ObjectRef {
+    /// Parses the resource and optional name arguments of commands acting on one object
+    pub fn parse(resource: &str, name: Option<&str>) -> Result<Self> {
+        match name {
+            Some(name) if resource.contains('/') => Err(anyhow!(
+                "name given twice, use either {} or {} {}",
+                resource,
+                resource,
+                name
+            )),
+            Some(name) => Self::new(None, resource, name, resource),
+            None => resource.parse(),
+        }
+    }
+
+    /// Whether the reference names an object of `kind`, by kind, plural or short name
+    pub fn is_kind(&self, kind: &str) -> bool {
+        names_kind(&self.resource, kind)
+    }
+
+    /// The namespace to look the object up in, `default` unless the reference names one
+    pub fn namespace_or(&self, default: &str) -> String {
+        self.namespace.clone().unwrap_or_else(|| default.to_owned())
+    }
+
+    fn new(namespace: Option<&str>, resource: &str, name: &str, input: &str) -> Result<Self> {
+        if let Some(namespace) = namespace {
+            if !is_dns1123_label(namespace) {
+                return Err(anyhow!("invalid namespace {:?} in {}", namespace, input));
+            }
+        }
+        if resource.contains(',') {
+            return Err(anyhow!(
+                "{} cannot name an object of several resources",
+                input
+            ));
+        }
+        let valid_resource = !resource.is_empty()
+            && resource
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '.' || c == '-')
+            && !resource.starts_with('.')
+            && !resource.ends_with('.');
+        if !valid_resource {
+            return Err(anyhow!("invalid resource {:?} in {}", resource, input));
+        }
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            return Err(anyhow!("invalid name {:?} in {}", name, input));
+        }
+        Ok(Self {
+            namespace: namespace.map(str::to_owned),
+            resource: resource.to_owned(),
+            name: name.to_owned(),
+        })
+    }
+}
+
+impl FromStr for ObjectRef {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parts: Vec<&str> = s.split('/').collect();
+        match parts[..] {
+            [resource, name] => Self::new(None, resource, name, s),
+            [namespace, resource, name] => Self::new(Some(namespace), resource, name, s),
+            [_] => Err(anyhow!(
+                "{} is missing a name, expected KIND/NAME or NAMESPACE/KIND/NAME",
+                s
+            )),
+            _ => Err(anyhow!(
+                "invalid object {}, expected KIND/NAME or NAMESPACE/KIND/NAME",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for ObjectRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(namespace) = &self.namespace {
+            write!(f, "{}/", namespace)?;
+        }
+        write!(f, "{}/{}", self.resource, self.name)
+    }
+}
+
+/// Splits a resource argument given as `KIND/NAME`, e.g. `deployment/app`, the way kubectl
+/// accepts it. `KIND NAME` is passed through as is.
+pub fn resource_and_name(resource: &str, name: Option<&str>) -> Result<(String, Option<String>)> {
+    let Some((kind, inline_name)) = resource.split_once('/') else {
+        return Ok((resource.to_owned(), name.map(str::to_owned)));
+    };
+    if name.is_some() {
+        return Err(anyhow!(
+            "name given twice, use either {} or {} {}",
+            resource,
+            kind,
+            inline_name
+        ));
+    }
+    if kind.contains(',') {
+        return Err(anyhow!(
+            "{} cannot name an object of several resources",
+            resource
+        ));
+    }
+    if kind.is_empty() || inline_name.is_empty() || inline_name.contains('/') {
+        return Err(anyhow!("invalid resource {}, expected KIND/NAME", resource));
+    }
+    Ok((kind.to_owned(), Some(inline_name.to_owned())))
+}
+
+/// Parses a pod argument given as `NAME` or, like kubectl, as `pod/NAME`
+pub fn parse_pod_arg(pod: &str) -> Result<String> {
+    let name = match pod.split_once('/') {
+        None => pod,
+        Some((kind, name)) if names_kind(kind, "Pod") => name,
+        Some((kind, _)) => return Err(anyhow!("{} is not a pod, expected NAME or pod/NAME", kind)),
+    };
+    if name.is_empty() || name.contains('/') {
+        return Err(anyhow!("invalid pod {}, expected NAME or pod/NAME", pod));
+    }
+    Ok(name.to_owned())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn split_kind_and_name() {
+        assert_eq!(
+            resource_and_name("deployment/app", None).unwrap(),
+            ("deployment".to_string(), Some("app".to_string()))
+        );
+        assert_eq!(
+            resource_and_name("deployment", Some("app")).unwrap(),
+            ("deployment".to_string(), Some("app".to_string()))
+        );
+        assert_eq!(
+            resource_and_name("pods,services", None).unwrap(),
+            ("pods,services".to_string(), None)
+        );
+        assert!(resource_and_name("deployment/app", Some("app")).is_err());
+        assert!(resource_and_name("pods,services/app", None).is_err());
+        assert!(resource_and_name("deployment/", None).is_err());
+
+        assert_eq!(parse_pod_arg("web-0").unwrap(), "web-0");
+        assert_eq!(parse_pod_arg("pod/web-0").unwrap(), "web-0");
+        assert_eq!(parse_pod_arg("po/web-0").unwrap(), "web-0");
+        assert!(parse_pod_arg("deployment/web").is_err());
+        assert!(parse_pod_arg("pod/").is_err());
+    }
+
+    #[test]
+    fn parse_object_refs() {
+        let deployment = ObjectRef::parse("deployment/app", None).unwrap();
+        assert_eq!(
+            deployment,
+            ObjectRef {
+                namespace: None,
+                resource: "deployment".into(),
+                name: "app".into()
+            }
+        );
+        assert_eq!(
+            ObjectRef::parse("deployment", Some("app")).unwrap(),
+            deployment
+        );
+
+        let cronjob: ObjectRef = "team-a/cronjobs.batch/backup".parse().unwrap();
+        assert_eq!(cronjob.namespace.as_deref(), Some("team-a"));
+        assert_eq!(cronjob.resource, "cronjobs.batch");
+        assert_eq!(cronjob.namespace_or("default"), "team-a");
+        assert_eq!(cronjob.to_string(), "team-a/cronjobs.batch/backup");
+
+        let error = |s: &str| s.parse::<ObjectRef>().unwrap_err().to_string();
+        assert!(error("deployment").contains("missing a name"));
+        assert!(error("Team/deployment/app").contains("invalid namespace"));
+        assert!(error("pods,services/app").contains("several resources"));
+        assert!(error("deployment/").contains("invalid name"));
+        assert!(error("a/b/c/d").contains("expected KIND/NAME"));
+        assert!(ObjectRef::parse("deployment/app", Some("app")).is_err());
+    }
+
+    #[test]
+    fn age_windows() {
+        assert_eq!(parse_age("1h30m"), Some(5400));
+        assert_eq!(parse_age("30d"), Some(30 * 86400));
+        assert_eq!(parse_age("5"), None);
+        assert_eq!(parse_age("1w"), None);
+        assert!(parse_age_arg("soon").is_err());
+
+        let now: DateTime<Utc> = "2030-01-31T00:00:00Z".parse().unwrap();
+        let created = |age: &str| Some(now - parse_age_arg(age).unwrap());
+        let recent = AgeWindow {
+            since: Some(parse_age_arg("1h").unwrap()),
+            older_than: None,
+        };
+        assert!(recent.contains(created("10m"), now));
+        assert!(!recent.contains(created("2h"), now));
+        assert!(!recent.contains(None, now));
+
+        let forgotten = AgeWindow {
+            since: None,
+            older_than: Some(parse_age_arg("30d").unwrap()),
+        };
+        assert!(forgotten.contains(created("31d"), now));
+        assert!(!forgotten.contains(created("29d"), now));
+        assert!(AgeWindow::default().contains(None, now));
+
+        let between = AgeWindow {
+            since: Some(parse_age_arg("7d").unwrap()),
+            older_than: Some(parse_age_arg("1d").unwrap()),
+        };
+        assert!(between.validate().is_ok());
+        assert!(between.contains(created("2d"), now));
+        let inverted = AgeWindow {
+            since: between.older_than,
+            older_than: between.since,
+        };
+        assert!(inverted.validate().is_err());
+    }
+}
@@ -0,0 +1,668 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+};
+
+use anyhow::Result;
+use k8s_openapi::{
+    api::{
+        apps::v1::{DaemonSet, Deployment, StatefulSet},
+        batch::v1::{CronJob, Job},
+        core::v1::{
+            Container, Node, NodeSelectorRequirement, NodeSelectorTerm, Pod, PodSpec,
+            ServiceAccount, Taint, Toleration,
+        },
+    },
+    NamespaceResourceScope,
+};
+use kube::{api::ListParams, Api, Client as KubeClient, Resource, ResourceExt};
+use serde::{de::DeserializeOwned, Serialize};
+
+/// Registries serving public images that need no pull secret. Images from any other registry
+/// are assumed to be private, so clusters whose nodes carry registry credentials of their own
+/// will see findings for those registries.
+const PUBLIC_REGISTRIES: &[&str] = &[
+    "docker.io",
+    "index.docker.io",
+    "registry-1.docker.io",
+    "registry.k8s.io",
+    "k8s.gcr.io",
+    "quay.io",
+    "ghcr.io",
+    "public.ecr.aws",
+    "mcr.microsoft.com",
+];
+
+/// An image pull hygiene problem of a single container
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImageIssue {
+    /// `imagePullPolicy: Always` on an image pinned by digest, which can never change
+    AlwaysPullDigest,
+    /// The image uses the `latest` tag, or no tag at all
+    LatestTag,
+    /// The image comes from a private registry without any pull secret on the pod or its
+    /// service account
+    MissingPullSecret,
+}
+
+impl Display for ImageIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageIssue::AlwaysPullDigest => write!(f, "pull Always on digest"),
+            ImageIssue::LatestTag => write!(f, "latest tag"),
+            ImageIssue::MissingPullSecret => write!(f, "no pull secret"),
+        }
+    }
+}
+
+/// A workload and the pod spec it runs, named as KIND/NAME
+#[derive(Clone, Debug)]
+pub struct Workload {
+    pub namespace: String,
+    pub name: String,
+    pub spec: PodSpec,
+}
+
+/// An issue found on one container of a workload
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImageFinding {
+    pub namespace: String,
+    pub workload: String,
+    pub container: String,
+    pub image: String,
+    pub issue: ImageIssue,
+}
+
+/// A security posture problem of a workload, or of one of its containers
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SecurityIssue {
+    /// The container runs privileged
+    Privileged,
+    /// The pod shares the node's network namespace
+    HostNetwork,
+    /// The pod shares the node's process namespace
+    HostPid,
+    /// Neither the container nor its pod sets `runAsNonRoot: true`
+    RunAsRoot,
+}
+
+impl Display for SecurityIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SecurityIssue::Privileged => write!(f, "privileged"),
+            SecurityIssue::HostNetwork => write!(f, "hostNetwork"),
+            SecurityIssue::HostPid => write!(f, "hostPID"),
+            SecurityIssue::RunAsRoot => write!(f, "no runAsNonRoot"),
+        }
+    }
+}
+
+/// A security issue of a workload. Issues of the whole pod have no container.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SecurityFinding {
+    pub namespace: String,
+    pub workload: String,
+    pub container: Option<String>,
+    pub issue: SecurityIssue,
+}
+
+/// Audits the image pulls of a cluster, returning how many workloads were audited along with
+/// the findings
+pub async fn image_pull_audit(
+    client: KubeClient,
+    namespace: Option<String>,
+) -> Result<(usize, Vec<ImageFinding>)> {
+    let workloads = fetch_workloads(client.clone(), namespace.as_deref()).await?;
+    let accounts = fetch_pull_secret_accounts(client, namespace.as_deref()).await?;
+    Ok((workloads.len(), audit_image_pull(&workloads, &accounts)))
+}
+
+/// Audits the security contexts of a cluster, returning how many workloads were audited per
+/// namespace along with the findings
+pub async fn security_audit(
+    client: KubeClient,
+    namespace: Option<String>,
+) -> Result<(BTreeMap<String, usize>, Vec<SecurityFinding>)> {
+    let workloads = fetch_workloads(client, namespace.as_deref()).await?;
+    let mut namespaces: BTreeMap<String, usize> = BTreeMap::new();
+    for workload in &workloads {
+        *namespaces.entry(workload.namespace.clone()).or_default() += 1;
+    }
+    Ok((namespaces, audit_security(&workloads)))
+}
+
+/// Whether the nodes of a cluster can run a workload. `blockers` counts the nodes ruled out
+/// by each reason, a node may be ruled out for several.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SchedulingReport {
+    pub nodes: usize,
+    pub schedulable: usize,
+    pub blockers: BTreeMap<String, usize>,
+}
+
+/// Checks the pod template of a deployment against the nodes of a cluster, or None when the
+/// cluster has no such deployment
+pub async fn scheduling_audit(
+    client: KubeClient,
+    namespace: String,
+    name: String,
+) -> Result<Option<SchedulingReport>> {
+    let deployments: Api<Deployment> = Api::namespaced(client.clone(), &namespace);
+    let Some(deployment) = deployments.get_opt(&name).await? else {
+        return Ok(None);
+    };
+    let spec = deployment
+        .spec
+        .and_then(|s| s.template.spec)
+        .unwrap_or_default();
+    let nodes: Api<Node> = Api::all(client);
+    let nodes = nodes.list(&ListParams::default()).await?.items;
+    Ok(Some(check_scheduling(&spec, &nodes)))
+}
+
+/// Checks which nodes a pod could be scheduled on going by taints, the node selector and
+/// required node affinity. Resources and pod affinity are left to the scheduler.
+pub fn check_scheduling(spec: &PodSpec, nodes: &[Node]) -> SchedulingReport {
+    let mut report = SchedulingReport {
+        nodes: nodes.len(),
+        ..Default::default()
+    };
+    for node in nodes {
+        let blockers = node_blockers(spec, node);
+        if blockers.is_empty() {
+            report.schedulable += 1;
+        }
+        for blocker in blockers {
+            *report.blockers.entry(blocker).or_default() += 1;
+        }
+    }
+    report
+}
+
+// Reasons the pod cannot run on the node, empty when it can
+fn node_blockers(spec: &PodSpec, node: &Node) -> Vec<String> {
+    let tolerations = spec.tolerations.as_deref().unwrap_or_default();
+    let labels = node.labels();
+    let mut blockers = Vec::new();
+    let cordoned = node.spec.as_ref().and_then(|s| s.unschedulable) == Some(true);
+    let cordon_taint = Taint {
+        key: "node.kubernetes.io/unschedulable".into(),
+        effect: "NoSchedule".into(),
+        ..Default::default()
+    };
+    if cordoned && !tolerations.iter().any(|t| tolerates(t, &cordon_taint)) {
+        blockers.push("cordoned".into());
+    }
+    let taints = node.spec.as_ref().and_then(|s| s.taints.as_deref());
+    for taint in taints.unwrap_or_default() {
+        let blocking = taint.effect == "NoSchedule" || taint.effect == "NoExecute";
+        if blocking && !tolerations.iter().any(|t| tolerates(t, taint)) {
+            blockers.push(format!("taint {}", display_taint(taint)));
+        }
+    }
+    let selector = spec.node_selector.iter().flatten();
+    if selector
+        .into_iter()
+        .any(|(key, value)| labels.get(key) != Some(value))
+    {
+        blockers.push("nodeSelector".into());
+    }
+    let required = spec
+        .affinity
+        .as_ref()
+        .and_then(|a| a.node_affinity.as_ref())
+        .and_then(|a| {
+            a.required_during_scheduling_ignored_during_execution
+                .as_ref()
+        });
+    if let Some(required) = required {
+        let name = node.name_any();
+        if !required
+            .node_selector_terms
+            .iter()
+            .any(|term| term_matches(term, labels, &name))
+        {
+            blockers.push("node affinity".into());
+        }
+    }
+    blockers
+}
+
+// Same rules as the scheduler: an empty key with Exists tolerates every taint and an empty
+// effect tolerates every effect
+fn tolerates(toleration: &Toleration, taint: &Taint) -> bool {
+    let effect = toleration.effect.as_deref().unwrap_or_default();
+    if !effect.is_empty() && effect != taint.effect {
+        return false;
+    }
+    let key = toleration.key.as_deref().unwrap_or_default();
+    let exists = toleration.operator.as_deref() == Some("Exists");
+    if key.is_empty() {
+        return exists;
+    }
+    key == taint.key
+        && (exists
+            || toleration.value.as_deref().unwrap_or_default()
+                == taint.value.as_deref().unwrap_or_default())
+}
+
+fn display_taint(taint: &Taint) -> String {
+    match &taint.value {
+        Some(value) if !value.is_empty() => format!("{}={}:{}", taint.key, value, taint.effect),
+        _ => format!("{}:{}", taint.key, taint.effect),
+    }
+}
+
+// A term matches when all of its requirements do. Terms without requirements match nothing.
+fn term_matches(term: &NodeSelectorTerm, labels: &BTreeMap<String, String>, name: &str) -> bool {
+    let expressions = term.match_expressions.as_deref().unwrap_or_default();
+    let fields = term.match_fields.as_deref().unwrap_or_default();
+    if expressions.is_empty() && fields.is_empty() {
+        return false;
+    }
+    let node_fields = BTreeMap::from([("metadata.name".to_owned(), name.to_owned())]);
+    expressions.iter().all(|r| requirement_matches(r, labels))
+        && fields.iter().all(|r| requirement_matches(r, &node_fields))
+}
+
+fn requirement_matches(
+    requirement: &NodeSelectorRequirement,
+    labels: &BTreeMap<String, String>,
+) -> bool {
+    let value = labels.get(&requirement.key);
+    let values = requirement.values.as_deref().unwrap_or_default();
+    let compare = |ordering: std::cmp::Ordering| {
+        let wanted = values.first().and_then(|v| v.parse::<i64>().ok());
+        let actual = value.and_then(|v| v.parse::<i64>().ok());
+        matches!((actual, wanted), (Some(a), Some(w)) if a.cmp(&w) == ordering)
+    };
+    match requirement.operator.as_str() {
+        "In" => value.is_some_and(|v| values.contains(v)),
+        "NotIn" => value.is_none_or(|v| !values.contains(v)),
+        "Exists" => value.is_some(),
+        "DoesNotExist" => value.is_none(),
+        "Gt" => compare(std::cmp::Ordering::Greater),
+        "Lt" => compare(std::cmp::Ordering::Less),
+        _ => false,
+    }
+}
+
+/// Lists the pod running workloads of a cluster, leaving out jobs and pods created by another
+/// workload so each container is only audited once
+pub async fn fetch_workloads(client: KubeClient, namespace: Option<&str>) -> Result<Vec<Workload>> {
+    let mut workloads = Vec::new();
+    for deployment in list::<Deployment>(&client, namespace).await? {
+        let spec = deployment.spec.clone().and_then(|s| s.template.spec);
+        workloads.extend(workload(&deployment, spec));
+    }
+    for statefulset in list::<StatefulSet>(&client, namespace).await? {
+        let spec = statefulset.spec.clone().and_then(|s| s.template.spec);
+        workloads.extend(workload(&statefulset, spec));
+    }
+    for daemonset in list::<DaemonSet>(&client, namespace).await? {
+        let spec = daemonset.spec.clone().and_then(|s| s.template.spec);
+        workloads.extend(workload(&daemonset, spec));
+    }
+    for cronjob in list::<CronJob>(&client, namespace).await? {
+        let spec = cronjob
+            .spec
+            .clone()
+            .and_then(|s| s.job_template.spec)
+            .and_then(|s| s.template.spec);
+        workloads.extend(workload(&cronjob, spec));
+    }
+    for job in list::<Job>(&client, namespace).await? {
+        if job.owner_references().is_empty() {
+            let spec = job.spec.clone().and_then(|s| s.template.spec);
+            workloads.extend(workload(&job, spec));
+        }
+    }
+    for pod in list::<Pod>(&client, namespace).await? {
+        if pod.owner_references().is_empty() {
+            let spec = pod.spec.clone();
+            workloads.extend(workload(&pod, spec));
+        }
+    }
+    Ok(workloads)
+}
+
+/// Namespace and name of every service account with image pull secrets
+pub async fn fetch_pull_secret_accounts(
+    client: KubeClient,
+    namespace: Option<&str>,
+) -> Result<BTreeSet<(String, String)>> {
+    Ok(list::<ServiceAccount>(&client, namespace)
+        .await?
+        .into_iter()
+        .filter(|sa| {
+            sa.image_pull_secrets
+                .as_ref()
+                .is_some_and(|s| !s.is_empty())
+        })
+        .map(|sa| (sa.namespace().unwrap_or_default(), sa.name_any()))
+        .collect())
+}
+
+/// Checks every container of the workloads for image pull issues. `pull_secret_accounts` holds
+/// the service accounts that provide pull secrets to their pods.
+pub fn audit_image_pull(
+    workloads: &[Workload],
+    pull_secret_accounts: &BTreeSet<(String, String)>,
+) -> Vec<ImageFinding> {
+    let mut findings = Vec::new();
+    for workload in workloads {
+        let spec = &workload.spec;
+        let service_account = spec.service_account_name.as_deref().unwrap_or("default");
+        let has_pull_secret = spec
+            .image_pull_secrets
+            .as_ref()
+            .is_some_and(|s| !s.is_empty())
+            || pull_secret_accounts
+                .contains(&(workload.namespace.clone(), service_account.to_owned()));
+        let containers = spec
+            .init_containers
+            .iter()
+            .flatten()
+            .chain(spec.containers.iter());
+        for container in containers {
+            for issue in container_issues(container, has_pull_secret) {
+                findings.push(ImageFinding {
+                    namespace: workload.namespace.clone(),
+                    workload: workload.name.clone(),
+                    container: container.name.clone(),
+                    image: container.image.clone().unwrap_or_default(),
+                    issue,
+                });
+            }
+        }
+    }
+    findings
+}
+
+/// Checks the workloads for privileged containers, host namespaces and containers that may run
+/// as root
+pub fn audit_security(workloads: &[Workload]) -> Vec<SecurityFinding> {
+    let mut findings = Vec::new();
+    for workload in workloads {
+        let spec = &workload.spec;
+        let finding = |container: Option<&Container>, issue| SecurityFinding {
+            namespace: workload.namespace.clone(),
+            workload: workload.name.clone(),
+            container: container.map(|c| c.name.clone()),
+            issue,
+        };
+        if spec.host_network == Some(true) {
+            findings.push(finding(None, SecurityIssue::HostNetwork));
+        }
+        if spec.host_pid == Some(true) {
+            findings.push(finding(None, SecurityIssue::HostPid));
+        }
+        let pod_non_root = spec
+            .security_context
+            .as_ref()
+            .and_then(|sc| sc.run_as_non_root);
+        let containers = spec
+            .init_containers
+            .iter()
+            .flatten()
+            .chain(spec.containers.iter());
+        for container in containers {
+            let context = container.security_context.as_ref();
+            if context.and_then(|sc| sc.privileged) == Some(true) {
+                findings.push(finding(Some(container), SecurityIssue::Privileged));
+            }
+            if context.and_then(|sc| sc.run_as_non_root).or(pod_non_root) != Some(true) {
+                findings.push(finding(Some(container), SecurityIssue::RunAsRoot));
+            }
+        }
+    }
+    findings
+}
+
+fn container_issues(container: &Container, has_pull_secret: bool) -> Vec<ImageIssue> {
+    let Some(image) = container.image.as_deref() else {
+        return Vec::new();
+    };
+    let image = ImageName::parse(image);
+    let mut issues = Vec::new();
+    if image.digest.is_some() && container.image_pull_policy.as_deref() == Some("Always") {
+        issues.push(ImageIssue::AlwaysPullDigest);
+    }
+    if image.digest.is_none() && image.tag.is_none_or(|tag| tag == "latest") {
+        issues.push(ImageIssue::LatestTag);
+    }
+    if !has_pull_secret && !PUBLIC_REGISTRIES.contains(&image.registry) {
+        issues.push(ImageIssue::MissingPullSecret);
+    }
+    issues
+}
+
+// The parts of an image reference such as `registry.example.com:5000/team/app:1.2@sha256:...`
+#[derive(Debug, PartialEq)]
+struct ImageName<'a> {
+    registry: &'a str,
+    tag: Option<&'a str>,
+    digest: Option<&'a str>,
+}
+
+impl<'a> ImageName<'a> {
+    fn parse(image: &'a str) -> Self {
+        let (name, digest) = match image.split_once('@') {
+            Some((name, digest)) => (name, Some(digest)),
+            None => (image, None),
+        };
+        // the first component is only a registry if it looks like a host, otherwise the image
+        // comes from docker hub
+        let (registry, path) = match name.split_once('/') {
+            Some((host, path)) if host.contains(['.', ':']) || host == "localhost" => (host, path),
+            _ => ("docker.io", name),
+        };
+        let tag = path
+            .rsplit_once(':')
+            .map(|(_, tag)| tag)
+            .filter(|tag| !tag.contains('/'));
+        Self {
+            registry,
+            tag,
+            digest,
+        }
+    }
+}
+
+fn workload<K>(object: &K, spec: Option<PodSpec>) -> Option<Workload>
+where
+    K: Resource<DynamicType = ()>,
+{
+    Some(Workload {
+        namespace: object.meta().namespace.clone().unwrap_or_default(),
+        name: format!(
+            "{}/{}",
+            K::kind(&()).to_lowercase(),
+            object.meta().name.as_deref()?
+        ),
+        spec: spec?,
+    })
+}
+
+async fn list<K>(client: &KubeClient, namespace: Option<&str>) -> Result<Vec<K>>
+where
+    K: Resource<Scope = NamespaceResourceScope, DynamicType = ()>
+        + Clone
+        + DeserializeOwned
+        + std::fmt::Debug,
+{
+    let api: Api<K> = match namespace {
+        Some(ns) => Api::namespaced(client.clone(), ns),
+        None => Api::all(client.clone()),
+    };
+    Ok(api.list(&ListParams::default()).await?.items)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use k8s_openapi::api::core::v1::{
+        Affinity, NodeAffinity, NodeSelector, NodeSpec, PodSecurityContext, SecurityContext,
+    };
+
+    fn workload(namespace: &str, image: &str, policy: Option<&str>) -> Workload {
+        Workload {
+            namespace: namespace.into(),
+            name: "deployment/app".into(),
+            spec: PodSpec {
+                containers: vec![Container {
+                    name: "app".into(),
+                    image: Some(image.into()),
+                    image_pull_policy: policy.map(Into::into),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            },
+        }
+    }
+
+    fn node(name: &str, labels: &[(&str, &str)], taints: Vec<Taint>) -> Node {
+        let mut node = Node::default();
+        node.metadata.name = Some(name.into());
+        node.metadata.labels = Some(
+            labels
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect(),
+        );
+        node.spec = Some(NodeSpec {
+            taints: Some(taints),
+            ..Default::default()
+        });
+        node
+    }
+
+    #[test]
+    fn schedulable_nodes() {
+        let gpu = Taint {
+            key: "dedicated".into(),
+            value: Some("gpu".into()),
+            effect: "NoSchedule".into(),
+            ..Default::default()
+        };
+        let nodes = vec![
+            node("gpu-1", &[("zone", "a")], vec![gpu.clone()]),
+            node("cpu-1", &[("zone", "b")], Vec::new()),
+            node("cpu-2", &[("zone", "c")], Vec::new()),
+        ];
+        let mut spec = PodSpec {
+            node_selector: Some(BTreeMap::from([("zone".into(), "a".into())])),
+            ..Default::default()
+        };
+        let report = check_scheduling(&spec, &nodes);
+        assert_eq!(report.schedulable, 0);
+        assert_eq!(
+            report.blockers,
+            BTreeMap::from([
+                ("nodeSelector".to_owned(), 2),
+                ("taint dedicated=gpu:NoSchedule".to_owned(), 1),
+            ])
+        );
+
+        spec.tolerations = Some(vec![Toleration {
+            key: Some("dedicated".into()),
+            operator: Some("Exists".into()),
+            ..Default::default()
+        }]);
+        assert_eq!(check_scheduling(&spec, &nodes).schedulable, 1);
+
+        spec.node_selector = None;
+        spec.affinity = Some(Affinity {
+            node_affinity: Some(NodeAffinity {
+                required_during_scheduling_ignored_during_execution: Some(NodeSelector {
+                    node_selector_terms: vec![NodeSelectorTerm {
+                        match_expressions: Some(vec![NodeSelectorRequirement {
+                            key: "zone".into(),
+                            operator: "NotIn".into(),
+                            values: Some(vec!["a".into(), "b".into()]),
+                        }]),
+                        ..Default::default()
+                    }],
+                }),
+                ..Default::default()
+            }),
+            ..Default::default()
+        });
+        let report = check_scheduling(&spec, &nodes);
+        assert_eq!(report.schedulable, 1);
+        assert_eq!(report.blockers.get("node affinity"), Some(&2));
+    }
+
+    #[test]
+    fn security_issues() {
+        let mut privileged = workload("default", "nginx:1.25", None);
+        privileged.spec.host_network = Some(true);
+        privileged.spec.containers[0].security_context = Some(SecurityContext {
+            privileged: Some(true),
+            ..Default::default()
+        });
+        let mut non_root = workload("default", "nginx:1.25", None);
+        non_root.spec.security_context = Some(PodSecurityContext {
+            run_as_non_root: Some(true),
+            ..Default::default()
+        });
+        let findings = audit_security(&[privileged, non_root]);
+        let issues: Vec<(Option<&str>, SecurityIssue)> = findings
+            .iter()
+            .map(|f| (f.container.as_deref(), f.issue))
+            .collect();
+        assert_eq!(
+            issues,
+            vec![
+                (None, SecurityIssue::HostNetwork),
+                (Some("app"), SecurityIssue::Privileged),
+                (Some("app"), SecurityIssue::RunAsRoot),
+            ]
+        );
+    }
+
+    #[test]
+    fn image_pull_issues() {
+        assert_eq!(
+            ImageName::parse("registry.example.com:5000/team/app:1.2@sha256:abc"),
+            ImageName {
+                registry: "registry.example.com:5000",
+                tag: Some("1.2"),
+                digest: Some("sha256:abc"),
+            }
+        );
+        assert_eq!(ImageName::parse("nginx").registry, "docker.io");
+        assert_eq!(ImageName::parse("localhost:5000/app").tag, None);
+
+        let accounts = BTreeSet::from([("private".to_owned(), "default".to_owned())]);
+        let issues = |w: Workload| -> Vec<ImageIssue> {
+            audit_image_pull(&[w], &accounts)
+                .into_iter()
+                .map(|f| f.issue)
+                .collect()
+        };
+        assert_eq!(issues(workload("default", "nginx:1.25", None)), vec![]);
+        assert_eq!(
+            issues(workload("default", "nginx", None)),
+            vec![ImageIssue::LatestTag]
+        );
+        assert_eq!(
+            issues(workload(
+                "default",
+                "quay.io/app@sha256:abc",
+                Some("Always")
+            )),
+            vec![ImageIssue::AlwaysPullDigest]
+        );
+        assert_eq!(
+            issues(workload("default", "registry.example.com/app:latest", None)),
+            vec![ImageIssue::LatestTag, ImageIssue::MissingPullSecret]
+        );
+        assert_eq!(
+            issues(workload("private", "registry.example.com/app:1.0", None)),
+            vec![]
+        );
+    }
+}
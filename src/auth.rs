@@ -0,0 +1,401 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    hash::{Hash, Hasher},
+    io::IsTerminal,
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use k8s_openapi::chrono::{DateTime, Duration, Utc};
+use kube::config::{ExecConfig, ExecInteractiveMode, Kubeconfig};
+use secrecy::SecretString;
+use serde::{Deserialize, Serialize};
+use tokio::{process::Command, sync::Mutex};
+use tracing::log::{debug, warn};
+
+use crate::serial;
+
+/// Credentials expiring within this window are treated as already expired
+const EXPIRY_MARGIN_SECS: i64 = 60;
+
+// Held by the exec plugin that has the terminal, so that prompts of different clusters never mix
+static PROMPT: Mutex<()> = Mutex::const_new(());
+
+// What plugins run without input say on stderr when they fail for the lack of it
+const INPUT_HINTS: &[&str] = &["interactive", "terminal", "tty", "stdin", "input", "prompt"];
+
+/// An exec plugin that ran and exited unsuccessfully, with what it said on stderr when it was run
+/// without the terminal
+#[derive(Debug)]
+struct ExecFailure {
+    command: String,
+    status: std::process::ExitStatus,
+    stderr: String,
+}
+
+impl ExecFailure {
+    // Whether the plugin failed because it wanted to ask for something, such as an MFA code
+    fn needs_input(&self) -> bool {
+        let stderr = self.stderr.to_lowercase();
+        INPUT_HINTS.iter().any(|hint| stderr.contains(hint))
+    }
+}
+
+impl std::fmt::Display for ExecFailure {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} exited with {}", self.command, self.status)?;
+        match self.stderr.trim() {
+            "" => Ok(()),
+            stderr => write!(f, ": {}", stderr),
+        }
+    }
+}
+
+impl std::error::Error for ExecFailure {}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExecCredential {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api_version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    kind: Option<String>,
+    status: Option<ExecCredentialStatus>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExecCredentialStatus {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expiration_timestamp: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_certificate_data: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    client_key_data: Option<String>,
+}
+
+impl ExecCredentialStatus {
+    fn is_fresh(&self) -> bool {
+        match self.expiration_timestamp {
+            Some(expiration) => expiration - Duration::seconds(EXPIRY_MARGIN_SECS) > Utc::now(),
+            None => false,
+        }
+    }
+}
+
+/// Runs the exec credential plugins of the given kubeconfig users, paired with the clusters
+/// using them, concurrently and replaces the exec config of each user with the resulting static
+/// credentials. Credentials carrying an `expirationTimestamp` are cached on disk and reused until
+/// they expire. Users whose plugin fails are left untouched so the kube client can still attempt
+/// the exec itself.
+///
+/// Plugins run side by side without input. Those with `interactiveMode: Always`, and those that
+/// failed asking for input such as an MFA code or an OIDC login when there is a terminal, get it
+/// one at a time after naming the clusters they log in to, while the others carry on.
+pub async fn resolve_exec_credentials(kubeconfig: &mut Kubeconfig, users: &[(String, String)]) {
+    let mut clusters: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for (user, clustername) in users {
+        clusters.entry(user).or_default().push(clustername);
+    }
+    let execs: Vec<(String, String, ExecConfig)> = clusters
+        .into_iter()
+        .filter_map(|(user, clusters)| {
+            let exec = exec_config_for_user(kubeconfig, user)?;
+            Some((user.to_owned(), prompt_label(user, &clusters), exec))
+        })
+        .collect();
+
+    let terminal = std::io::stdin().is_terminal();
+    let handles = serial::join_all(execs.into_iter().map(|(user, label, exec)| {
+        tokio::spawn(async move {
+            let credential = serial::phase(
+                &format!("user {}", user),
+                "exec",
+                get_credential(&user, &label, &exec, terminal),
+            )
+            .await;
+            (user, credential)
+        })
+    }))
+    .await;
+
+    for handle in handles {
+        match handle {
+            Ok((user, Ok(status))) => apply_credential(kubeconfig, &user, status),
+            Ok((user, Err(e))) => warn!("failed to run exec plugin for user {}: {}", user, e),
+            Err(e) => debug!("join failed {}", e),
+        }
+    }
+}
+
+fn exec_config_for_user(kubeconfig: &Kubeconfig, user: &str) -> Option<ExecConfig> {
+    kubeconfig
+        .auth_infos
+        .iter()
+        .find(|named_auth| named_auth.name == user)
+        .and_then(|named_auth| named_auth.auth_info.as_ref())
+        .and_then(|auth_info| auth_info.exec.clone())
+}
+
+fn apply_credential(kubeconfig: &mut Kubeconfig, user: &str, status: ExecCredentialStatus) {
+    let Some(auth_info) = kubeconfig
+        .auth_infos
+        .iter_mut()
+        .find(|named_auth| named_auth.name == user)
+        .and_then(|named_auth| named_auth.auth_info.as_mut())
+    else {
+        return;
+    };
+    auth_info.exec = None;
+    if let Some(token) = status.token {
+        auth_info.token = Some(SecretString::new(token));
+    }
+    // kubeconfig stores certificate data base64 encoded while exec plugins return plain PEM
+    if let (Some(cert), Some(key)) = (status.client_certificate_data, status.client_key_data) {
+        auth_info.client_certificate_data = Some(STANDARD.encode(cert));
+        auth_info.client_key_data = Some(SecretString::new(STANDARD.encode(key)));
+    }
+}
+
+// Names the clusters a plugin logs in to when it asks for input
+fn prompt_label(user: &str, clusters: &[&str]) -> String {
+    let noun = if clusters.len() == 1 {
+        "cluster"
+    } else {
+        "clusters"
+    };
+    format!("{} {} (user {})", noun, clusters.join(", "), user)
+}
+
+async fn get_credential(
+    user: &str,
+    label: &str,
+    exec: &ExecConfig,
+    terminal: bool,
+) -> Result<ExecCredentialStatus> {
+    let cache_path = cache_file_path(user, exec);
+    if let Some(path) = &cache_path {
+        if let Some(status) = read_cached_credential(path) {
+            debug!("using cached exec credential for user {}", user);
+            return Ok(status);
+        }
+    }
+
+    // plugins that do not say otherwise run without input so that they never wait on each other
+    // for the terminal, and only get it when they failed for the lack of input
+    let status = match exec.interactive_mode {
+        Some(ExecInteractiveMode::Never) => run_exec_plugin(exec, false).await?,
+        Some(ExecInteractiveMode::Always) => run_prompted(exec, label).await?,
+        _ => match run_exec_plugin(exec, false).await {
+            Ok(status) => status,
+            Err(e)
+                if terminal
+                    && e.downcast_ref::<ExecFailure>()
+                        .is_some_and(ExecFailure::needs_input) =>
+            {
+                debug!("exec plugin for {} needs input: {}", label, e);
+                run_prompted(exec, label).await?
+            }
+            Err(e) => return Err(e),
+        },
+    };
+    if let Some(path) = &cache_path {
+        if status.expiration_timestamp.is_some() {
+            if let Err(e) = write_cached_credential(path, &status) {
+                debug!("failed to cache exec credential for user {}: {}", user, e);
+            }
+        }
+    }
+    Ok(status)
+}
+
+// Runs a plugin with the terminal to itself once no other plugin has it
+async fn run_prompted(exec: &ExecConfig, label: &str) -> Result<ExecCredentialStatus> {
+    let _prompt = PROMPT.lock().await;
+    eprintln!("Logging in to {}", label);
+    run_exec_plugin(exec, true).await
+}
+
+async fn run_exec_plugin(exec: &ExecConfig, interactive: bool) -> Result<ExecCredentialStatus> {
+    let command = exec
+        .command
+        .as_ref()
+        .ok_or_else(|| anyhow!("exec plugin has no command"))?;
+    let api_version = exec
+        .api_version
+        .clone()
+        .unwrap_or_else(|| "client.authentication.k8s.io/v1beta1".into());
+    let exec_info = serde_json::json!({
+        "apiVersion": api_version,
+        "kind": "ExecCredential",
+        "spec": { "interactive": interactive },
+    });
+
+    let mut cmd = Command::new(command);
+    if let Some(args) = &exec.args {
+        cmd.args(args);
+    }
+    if let Some(envs) = &exec.env {
+        for env in envs {
+            if let (Some(name), Some(value)) = (env.get("name"), env.get("value")) {
+                cmd.env(name, value);
+            }
+        }
+    }
+    // without the terminal stderr is kept to tell whether the plugin asked for input
+    let (stdin, stderr) = if interactive {
+        (Stdio::inherit(), Stdio::inherit())
+    } else {
+        (Stdio::null(), Stdio::piped())
+    };
+    cmd.env("KUBERNETES_EXEC_INFO", exec_info.to_string())
+        .stdin(stdin)
+        .stderr(stderr);
+
+    let output = cmd
+        .output()
+        .await
+        .with_context(|| format!("failed to run {}", command))?;
+    if !output.status.success() {
+        return Err(ExecFailure {
+            command: command.clone(),
+            status: output.status,
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        }
+        .into());
+    }
+    let credential: ExecCredential =
+        serde_json::from_slice(&output.stdout).context("failed to parse ExecCredential")?;
+    credential
+        .status
+        .ok_or_else(|| anyhow!("ExecCredential from {} has no status", command))
+}
+
+// Cache entries are keyed on the user name and the full exec config so that changing the plugin
+// arguments or environment never reuses a stale credential.
+fn cache_file_path(user: &str, exec: &ExecConfig) -> Option<PathBuf> {
+    let mut hasher = DefaultHasher::new();
+    user.hash(&mut hasher);
+    exec.command.hash(&mut hasher);
+    exec.args.hash(&mut hasher);
+    if let Some(envs) = &exec.env {
+        for env in envs {
+            let mut pairs: Vec<(&String, &String)> = env.iter().collect();
+            pairs.sort();
+            pairs.hash(&mut hasher);
+        }
+    }
+    credentials_cache_dir().map(|dir| dir.join(format!("{:016x}.json", hasher.finish())))
+}
+
+fn credentials_cache_dir() -> Option<PathBuf> {
+    dirs::home_dir().map(|h| {
+        h.join(".kube")
+            .join("cache")
+            .join("kubemc")
+            .join("credentials")
+    })
+}
+
+fn read_cached_credential(path: &Path) -> Option<ExecCredentialStatus> {
+    let data = std::fs::read_to_string(path).ok()?;
+    let credential: ExecCredential = serde_json::from_str(&data).ok()?;
+    credential.status.filter(|status| status.is_fresh())
+}
+
+fn write_cached_credential(path: &PathBuf, status: &ExecCredentialStatus) -> Result<()> {
+    use std::io::Write;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let credential = ExecCredential {
+        api_version: None,
+        kind: Some("ExecCredential".into()),
+        status: Some(status.clone()),
+    };
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(serde_json::to_string(&credential)?.as_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn credential_freshness() {
+        let credential: ExecCredential = serde_json::from_str(
+            r#"{"apiVersion":"client.authentication.k8s.io/v1beta1","kind":"ExecCredential",
+            "status":{"token":"abc","expirationTimestamp":"2000-01-01T00:00:00Z"}}"#,
+        )
+        .unwrap();
+        let mut status = credential.status.unwrap();
+        assert_eq!(status.token.as_deref(), Some("abc"));
+        assert!(!status.is_fresh());
+
+        status.expiration_timestamp = Some(Utc::now() + Duration::hours(1));
+        assert!(status.is_fresh());
+
+        status.expiration_timestamp = None;
+        assert!(!status.is_fresh());
+    }
+
+    #[tokio::test]
+    async fn prompt_for_input() {
+        assert_eq!(prompt_label("sso", &["prod"]), "cluster prod (user sso)");
+        assert_eq!(
+            prompt_label("sso", &["prod-us", "prod-eu"]),
+            "clusters prod-us, prod-eu (user sso)"
+        );
+
+        // a plugin that only logs in when it may ask for a one-time code
+        let script = r#"case "$KUBERNETES_EXEC_INFO" in
+            *'"interactive":true'*) echo '{"status":{"token":"mfa"}}' ;;
+            *) echo 'cannot prompt for a one-time code without a terminal' >&2; exit 1 ;;
+        esac"#;
+        let mut exec = ExecConfig {
+            api_version: None,
+            command: Some("sh".into()),
+            args: Some(vec!["-c".into(), script.into()]),
+            env: None,
+            drop_env: None,
+            interactive_mode: None,
+        };
+        let label = prompt_label("mfa", &["prod"]);
+        let status = get_credential("mfa", &label, &exec, true).await.unwrap();
+        assert_eq!(status.token.as_deref(), Some("mfa"));
+        assert!(get_credential("mfa", &label, &exec, false).await.is_err());
+
+        exec.interactive_mode = Some(ExecInteractiveMode::Never);
+        assert!(get_credential("mfa", &label, &exec, true).await.is_err());
+        exec.interactive_mode = Some(ExecInteractiveMode::Always);
+        assert!(get_credential("mfa", &label, &exec, false).await.is_ok());
+
+        // a plugin failing for another reason is not run again with the terminal
+        let script = r#"case "$KUBERNETES_EXEC_INFO" in
+            *'"interactive":true'*) echo '{"status":{"token":"again"}}' ;;
+            *) echo 'token has been revoked' >&2; exit 1 ;;
+        esac"#;
+        exec.args = Some(vec!["-c".into(), script.into()]);
+        exec.interactive_mode = None;
+        let e = get_credential("revoked", &label, &exec, true)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "sh exited with exit status: 1: token has been revoked"
+        );
+    }
+}
@@ -0,0 +1,307 @@
+use std::{
+    fmt::Display,
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, Context, Result};
+use tracing::log::debug;
+
+/// Size the caches are pruned back to, oldest entries first, by the automatic cleanup
+pub const MAX_CACHE_SIZE: u64 = 256 * 1024 * 1024;
+
+/// How often the automatic cleanup looks at the caches
+const PRUNE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// File whose modification time records the last automatic cleanup
+const PRUNE_MARKER: &str = "last-prune";
+
+/// The caches kubemc keeps under ~/.kube/cache
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CacheKind {
+    /// API resources of a cluster, per API server
+    Discovery,
+    /// Object names of `kubemc index build`, per cluster
+    Index,
+    /// Object names listed for shell completion, per cluster
+    Completion,
+    /// Lists reused by `get` within --cache-ttl, per cluster
+    Responses,
+    /// Exec plugin credentials, per kubeconfig user
+    Credentials,
+    /// When `pre-command`s last ran
+    Hooks,
+}
+
+impl Display for CacheKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            CacheKind::Discovery => "discovery",
+            CacheKind::Index => "index",
+            CacheKind::Completion => "completion",
+            CacheKind::Responses => "responses",
+            CacheKind::Credentials => "credentials",
+            CacheKind::Hooks => "hooks",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A file or directory of a cache, removed as a whole
+#[derive(Clone, Debug, PartialEq)]
+pub struct CacheEntry {
+    pub kind: CacheKind,
+    /// API server host of discovery, cluster of the index, completion and responses, empty
+    /// otherwise
+    pub owner: String,
+    pub path: PathBuf,
+    pub size: u64,
+    /// When anything in the entry was last written
+    pub modified: SystemTime,
+}
+
+impl CacheEntry {
+    pub fn age(&self, now: SystemTime) -> Duration {
+        now.duration_since(self.modified).unwrap_or_default()
+    }
+
+    pub fn remove(&self) -> Result<()> {
+        let removed = if self.path.is_dir() {
+            fs::remove_dir_all(&self.path)
+        } else {
+            fs::remove_file(&self.path)
+        };
+        removed.with_context(|| format!("failed to remove {}", self.path.display()))
+    }
+}
+
+/// Where the caches live
+#[derive(Clone, Debug)]
+pub struct CacheDirs {
+    /// Discovery, kept apart from the rest next to the discovery cache of kubectl
+    pub discovery: PathBuf,
+    pub kubemc: PathBuf,
+}
+
+impl CacheDirs {
+    pub fn home() -> Result<Self> {
+        let cache = dirs::home_dir()
+            .map(|h| h.join(".kube").join("cache"))
+            .ok_or_else(|| anyhow!("failed to find home directory"))?;
+        Ok(Self {
+            discovery: cache.join("kubemc-discovery"),
+            kubemc: cache.join("kubemc"),
+        })
+    }
+
+    /// Every entry of every cache, missing caches have none
+    pub fn scan(&self) -> Vec<CacheEntry> {
+        let mut entries = Vec::new();
+        let layout = [
+            (CacheKind::Discovery, self.discovery.clone()),
+            (CacheKind::Index, self.kubemc.join("index")),
+            (CacheKind::Completion, self.kubemc.join("completion")),
+            (CacheKind::Responses, self.kubemc.join("responses")),
+            (CacheKind::Credentials, self.kubemc.join("credentials")),
+            (CacheKind::Hooks, self.kubemc.join("hooks")),
+        ];
+        for (kind, dir) in layout {
+            let Ok(children) = fs::read_dir(&dir) else {
+                continue;
+            };
+            for child in children.flatten() {
+                let path = child.path();
+                let Some((size, modified)) = usage(&path) else {
+                    continue;
+                };
+                let owner = match kind {
+                    CacheKind::Discovery | CacheKind::Completion | CacheKind::Responses => {
+                        child.file_name().to_string_lossy().into_owned()
+                    }
+                    CacheKind::Index => path
+                        .file_stem()
+                        .map(|stem| stem.to_string_lossy().into_owned())
+                        .unwrap_or_default(),
+                    CacheKind::Credentials | CacheKind::Hooks => String::new(),
+                };
+                entries.push(CacheEntry {
+                    kind,
+                    owner,
+                    path,
+                    size,
+                    modified,
+                });
+            }
+        }
+        entries
+    }
+
+    /// Prunes the caches back to `MAX_CACHE_SIZE` when the last cleanup is a day old, so a
+    /// fleet of short lived clusters does not grow them forever
+    pub fn auto_prune(&self) -> Result<()> {
+        let marker = self.kubemc.join(PRUNE_MARKER);
+        let last = fs::metadata(&marker).and_then(|m| m.modified()).ok();
+        let now = SystemTime::now();
+        if last.is_some_and(|last| now.duration_since(last).unwrap_or_default() < PRUNE_INTERVAL) {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.kubemc)?;
+        fs::write(&marker, "")?;
+        for entry in over_budget(self.scan(), MAX_CACHE_SIZE) {
+            debug!("pruning {} cache {}", entry.kind, entry.path.display());
+            entry.remove()?;
+        }
+        Ok(())
+    }
+}
+
+// Total size of a file or directory and when anything in it was last modified
+fn usage(path: &Path) -> Option<(u64, SystemTime)> {
+    let metadata = fs::symlink_metadata(path).ok()?;
+    let mut size = metadata.len();
+    let mut modified = metadata.modified().ok()?;
+    if metadata.is_dir() {
+        for child in fs::read_dir(path).ok()?.flatten() {
+            if let Some((child_size, child_modified)) = usage(&child.path()) {
+                size += child_size;
+                modified = modified.max(child_modified);
+            }
+        }
+    }
+    Some((size, modified))
+}
+
+/// Entries last written at least `age` ago
+pub fn entries_older_than(
+    entries: Vec<CacheEntry>,
+    age: Duration,
+    now: SystemTime,
+) -> Vec<CacheEntry> {
+    entries
+        .into_iter()
+        .filter(|entry| entry.age(now) >= age)
+        .collect()
+}
+
+/// The oldest entries that have to go for the rest to fit in `max_size` bytes
+pub fn over_budget(mut entries: Vec<CacheEntry>, max_size: u64) -> Vec<CacheEntry> {
+    let mut total: u64 = entries.iter().map(|entry| entry.size).sum();
+    entries.sort_by_key(|entry| entry.modified);
+    entries
+        .into_iter()
+        .take_while(|entry| {
+            let over = total > max_size;
+            total -= entry.size;
+            over
+        })
+        .collect()
+}
+
+/// Formats a size in bytes with a binary unit, e.g. 12.5MiB
+pub fn format_size(size: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = size as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{}B", size)
+    } else {
+        format!("{:.1}{}", value, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn scan_and_prune_caches() {
+        let root = std::env::temp_dir().join(format!("kubemc-cache-{}", std::process::id()));
+        let dirs = CacheDirs {
+            discovery: root.join("kubemc-discovery"),
+            kubemc: root.join("kubemc"),
+        };
+        let write = |path: PathBuf, size: usize| {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, vec![b'x'; size]).unwrap();
+        };
+        write(
+            dirs.discovery
+                .join("prod.example.com_443/v1/serverresources.json"),
+            300,
+        );
+        write(
+            dirs.discovery
+                .join("prod.example.com_443/servergroups.json"),
+            100,
+        );
+        write(dirs.kubemc.join("index/prod-us.json"), 50);
+        write(dirs.kubemc.join("completion/prod-us/pods-shop.json"), 20);
+        write(
+            dirs.kubemc.join("responses/prod-us/0123456789abcdef.jsonl"),
+            40,
+        );
+        write(dirs.kubemc.join("credentials/0123456789abcdef.json"), 10);
+
+        let mut entries = dirs.scan();
+        entries.sort_by_key(|entry| entry.kind);
+        let found: Vec<(CacheKind, &str)> = entries
+            .iter()
+            .map(|entry| (entry.kind, entry.owner.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (CacheKind::Discovery, "prod.example.com_443"),
+                (CacheKind::Index, "prod-us"),
+                (CacheKind::Completion, "prod-us"),
+                (CacheKind::Responses, "prod-us"),
+                (CacheKind::Credentials, ""),
+            ]
+        );
+        // directories count everything below them
+        assert!(entries[0].size >= 400);
+
+        let now = SystemTime::now();
+        assert!(entries_older_than(entries.clone(), Duration::from_secs(3600), now).is_empty());
+        let later = now + Duration::from_secs(8 * 24 * 3600);
+        assert_eq!(
+            entries_older_than(entries.clone(), Duration::from_secs(7 * 24 * 3600), later).len(),
+            5
+        );
+
+        let entry = |owner: &str, size: u64, age: u64| CacheEntry {
+            kind: CacheKind::Index,
+            owner: owner.into(),
+            path: root.join(owner),
+            size,
+            modified: now - Duration::from_secs(age),
+        };
+        let pruned = over_budget(
+            vec![
+                entry("new", 100, 10),
+                entry("old", 100, 30),
+                entry("mid", 100, 20),
+            ],
+            150,
+        );
+        let owners: Vec<&str> = pruned.iter().map(|e| e.owner.as_str()).collect();
+        assert_eq!(owners, ["old", "mid"]);
+        assert!(over_budget(vec![entry("new", 100, 10)], 150).is_empty());
+
+        // the automatic cleanup runs once per interval
+        dirs.auto_prune().unwrap();
+        assert!(dirs.kubemc.join(PRUNE_MARKER).exists());
+        entries[1].remove().unwrap();
+        assert!(!dirs.kubemc.join("index/prod-us.json").exists());
+        fs::remove_dir_all(&root).unwrap();
+
+        assert_eq!(format_size(512), "512B");
+        assert_eq!(format_size(1536), "1.5KiB");
+        assert_eq!(format_size(3 * 1024 * 1024), "3.0MiB");
+    }
+}
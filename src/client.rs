@@ -1,109 +1,1236 @@
 use anyhow::{anyhow, Context, Result};
+use futures::{stream::BoxStream, Stream, StreamExt};
+use hyper::{
+    client::HttpConnector,
+    header::{HeaderValue, ACCEPT},
+};
+use hyper_rustls::HttpsConnectorBuilder;
+use hyper_timeout::TimeoutConnector;
+use k8s_openapi::apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceColumnDefinition;
 use kube::{
-    api::ListParams,
-    config::{KubeConfigOptions, Kubeconfig},
-    core::{DynamicObject, ObjectList},
+    api::{ListParams, Patch, PatchParams, PostParams},
+    client::ConfigExt,
+    config::{AuthInfo, KubeConfigOptions, Kubeconfig, NamedAuthInfo, NamedCluster},
+    core::{DynamicObject, ObjectList, Request},
     discovery::{ApiCapabilities, ApiResource, Scope},
-    Api, Client as KubeClient, Discovery as KubeDiscovery,
+    runtime::watcher,
+    Api, Client as KubeClient, Discovery as KubeDiscovery, ResourceExt,
+};
+use serde::de::DeserializeOwned;
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tower::ServiceBuilder;
+use tracing::{
+    debug_span,
+    log::{debug, warn},
+    Instrument,
 };
-use std::sync::Arc;
-use tracing::log::{debug, warn};
 
-use crate::{config::Cluster, discovery::Discovery};
+use crate::{
+    auth::resolve_exec_credentials,
+    config::{read_kubeconfig, Cluster, Connection},
+    discovery::{
+        nearest_resources, parse_kube_url_to_discovery, unknown_resource_message, Discovery,
+        Suggestions, UnknownResource, CACHE_TTL,
+    },
+    errors::{describe_error, describe_kube_error, is_auth_error},
+    hooks::run_pre_commands,
+    paging::{
+        is_expired_continue, trim_object, ObjectCap, PageTracker, Truncated, MAX_LIST_RESTARTS,
+    },
+    printer::fetch_printer_columns,
+    profile,
+    responses::ResponseCache,
+    retry::{with_retries, RetryPolicy},
+    serial,
+    table::{ServerTable, TABLE_ACCEPT},
+};
+
+/// Number of objects requested per page when listing
+pub const DEFAULT_PAGE_SIZE: u32 = 500;
+
+/// Number of objects listed from a cluster before the rest of its list is left out
+pub const DEFAULT_MAX_OBJECTS_PER_CLUSTER: u32 = 50_000;
+
+/// API groups Kubernetes serves itself, whose kinds accept strategic merge patches. CRDs and
+/// aggregated APIs, even under `k8s.io` groups, only accept merge patches.
+const BUILT_IN_GROUPS: &[&str] = &[
+    "",
+    "admissionregistration.k8s.io",
+    "apiextensions.k8s.io",
+    "apiregistration.k8s.io",
+    "apps",
+    "authentication.k8s.io",
+    "authorization.k8s.io",
+    "autoscaling",
+    "batch",
+    "certificates.k8s.io",
+    "coordination.k8s.io",
+    "discovery.k8s.io",
+    "events.k8s.io",
+    "flowcontrol.apiserver.k8s.io",
+    "internal.apiserver.k8s.io",
+    "networking.k8s.io",
+    "node.k8s.io",
+    "policy",
+    "rbac.authorization.k8s.io",
+    "resource.k8s.io",
+    "scheduling.k8s.io",
+    "storage.k8s.io",
+    "storagemigration.k8s.io",
+];
 
 type ClusterName = String;
 type Kind = String;
-type MCCluster = (ClusterName, Api<DynamicObject>, Kind);
+// A cluster's client for a kind along with the resource discovery resolved the kind to
+type MCCluster = (ClusterName, Api<DynamicObject>, ApiResource);
+
+/// A kind along with the API group serving it, the core group being empty. Kinds of the same
+/// name in different groups, such as core and Knative services, are told apart by it.
+pub type GroupKind = (String, String);
 
+/// The group and kind of a kind served under an apiVersion such as `apps/v1`
+pub fn group_kind(api_version: &str, kind: &str) -> GroupKind {
+    let group = api_version.split_once('/').map_or("", |(group, _)| group);
+    (group.to_owned(), kind.to_owned())
+}
+// A kubeconfig user along with a cluster relying on it
+type UserOf = (String, ClusterName);
+
+#[derive(Clone)]
 pub struct Client {
-    pub kind: String,
+    /// Kinds resolved from the requested resources, in the order they were requested
+    pub kinds: Vec<Kind>,
     kubeclients: Vec<MCCluster>,
+    // resources the kinds were resolved to, once per apiVersion and kind
+    resources: Vec<ApiResource>,
+    // the group and kind of each requested resource, as the first cluster serving it resolved it
+    group_kinds: Vec<GroupKind>,
+    /// Clusters that cannot serve a kind, with the reason
+    failed: Vec<(ClusterName, GroupKind, String)>,
+    retry: HashMap<ClusterName, RetryPolicy>,
+    // what each cluster connects to, keeping apart the lists cached for it
+    targets: HashMap<ClusterName, u64>,
+    // selectors narrowing listings and whether listed objects are trimmed
+    query: ListQuery,
+    // objects listed from each cluster and kind before the rest is left out
+    max_objects: Option<usize>,
+    // how long listings are reused from the response cache, None to leave the cache alone
+    cache_ttl: Option<Duration>,
+    // clusters answered by another cluster reaching the same target, along with that cluster
+    shared: Vec<(ClusterName, ClusterName)>,
+}
+
+/// What a listing asks the API servers for besides the kind, and what is kept of the answer
+#[derive(Clone, Debug, Default, Hash)]
+struct ListQuery {
+    labels: Option<String>,
+    fields: Option<String>,
+    // whether listed objects are trimmed down to what tables show
+    trim: bool,
+}
+
+impl ListQuery {
+    fn params(&self, page_size: u32) -> ListParams {
+        let mut lp = ListParams::default().limit(page_size);
+        if let Some(labels) = &self.labels {
+            lp = lp.labels(labels);
+        }
+        if let Some(fields) = &self.fields {
+            lp = lp.fields(fields);
+        }
+        lp
+    }
+
+    fn watcher_config(&self) -> watcher::Config {
+        let mut config = watcher::Config::default();
+        if let Some(labels) = &self.labels {
+            config = config.labels(labels);
+        }
+        if let Some(fields) = &self.fields {
+            config = config.fields(fields);
+        }
+        config
+    }
 }
 
 pub struct ListResponse {
     pub clustername: String,
     pub kind: String,
+    /// The apiVersion of the listed kind, such as `apps/v1`
+    pub api_version: String,
     pub object_list: ObjectList<DynamicObject>,
+    /// Set on the last page of a list stopped at the object cap
+    pub truncated: Option<Truncated>,
+    /// Set on the first page of a list reused from the response cache, how long ago it was listed
+    pub cached: Option<Duration>,
+}
+
+impl ListResponse {
+    pub fn group_kind(&self) -> GroupKind {
+        group_kind(&self.api_version, &self.kind)
+    }
+}
+
+// ObjectList is not Clone
+impl Clone for ListResponse {
+    fn clone(&self) -> Self {
+        Self {
+            clustername: self.clustername.clone(),
+            kind: self.kind.clone(),
+            api_version: self.api_version.clone(),
+            object_list: ObjectList {
+                metadata: self.object_list.metadata.clone(),
+                items: self.object_list.items.clone(),
+            },
+            truncated: self.truncated,
+            cached: self.cached,
+        }
+    }
+}
+
+/// A page of a list rendered by the API server of a cluster as a table
+#[derive(Clone)]
+pub struct TableResponse {
+    pub clustername: String,
+    /// The API group of the kind, empty for the core group
+    pub group: String,
+    pub kind: String,
+    pub table: ServerTable,
+    /// Set on the last page of a list stopped at the object cap
+    pub truncated: Option<Truncated>,
+}
+
+/// A cluster that could not list a kind, with a user facing reason
+#[derive(Clone)]
+pub struct ListFailure {
+    pub clustername: String,
+    /// The API group of the kind, empty for the core group
+    pub group: String,
+    pub kind: String,
+    pub error: String,
+}
+
+impl ListFailure {
+    pub fn group_kind(&self) -> GroupKind {
+        (self.group.clone(), self.kind.clone())
+    }
+}
+
+/// Results of listing a kind across all clusters
+pub struct Listing {
+    pub responses: Vec<ListResponse>,
+    pub failures: Vec<ListFailure>,
+}
+
+impl Listing {
+    /// The objects listed from one cluster as typed resources, skipping those that do not parse
+    pub fn objects<K: DeserializeOwned>(&self, clustername: &str) -> Vec<K> {
+        self.responses
+            .iter()
+            .filter(|lr| lr.clustername == clustername)
+            .flat_map(|lr| &lr.object_list.items)
+            .filter_map(|object| {
+                serde_json::to_value(object)
+                    .and_then(serde_json::from_value)
+                    .ok()
+            })
+            .collect()
+    }
+}
+
+/// Outcome of fetching a named object from a single cluster
+#[derive(Clone)]
+pub enum GetResult {
+    Found(Box<DynamicObject>),
+    NotFound,
+    Failed(String),
+}
+
+#[derive(Clone)]
+pub struct GetResponse {
+    pub clustername: String,
+    pub kind: String,
+    pub result: GetResult,
+}
+
+/// A merge patch for one object of a kind on one cluster
+pub struct ObjectPatch {
+    pub clustername: String,
+    pub kind: String,
+    pub name: String,
+    pub patch: serde_json::Value,
+}
+
+pub struct WatchResponse {
+    pub clustername: String,
+    pub kind: String,
+    pub event: watcher::Result<watcher::Event<DynamicObject>>,
+}
+
+/// Outcome of scaling a workload on a single cluster
+#[derive(Clone)]
+pub enum ScaleResult {
+    Scaled {
+        old: i32,
+        new: i32,
+    },
+    /// The `--current-replicas` precondition did not hold
+    Skipped {
+        current: i32,
+    },
+    Failed(String),
+}
+
+#[derive(Clone)]
+pub struct ScaleResponse {
+    pub clustername: String,
+    pub result: ScaleResult,
 }
 
 impl Client {
-    pub async fn try_new(clusters: &[Cluster], namespace: &str, resource: &str) -> Result<Self> {
-        let kubeconfig = Kubeconfig::read()?;
-        let handles = futures::future::join_all(clusters.iter().map(|cluster| {
-            let kubeconfig = kubeconfig.clone();
-            let cluster = cluster.clone();
-            let ns = Arc::new(namespace.to_owned());
-            let r = Arc::new(resource.to_owned());
+    /// Creates clients for every cluster and resource. `resources` may be a single resource or a
+    /// comma separated list such as `pods,services`.
+    pub async fn try_new(clusters: &[Cluster], namespace: &str, resources: &str) -> Result<Self> {
+        let resources: Vec<String> = resources
+            .split(',')
+            .map(|r| r.trim().to_owned())
+            .filter(|r| !r.is_empty())
+            .collect();
+        if resources.is_empty() {
+            return Err(anyhow!("no resource specified"));
+        }
+        let clusters: Vec<Cluster> = clusters.iter().filter(|c| !c.disabled).cloned().collect();
+        if clusters.is_empty() {
+            return Err(anyhow!("no enabled cluster to connect to"));
+        }
+        // clusters sharing the target of another cluster are not connected on their own
+        let shared: Vec<(ClusterName, ClusterName)> = clusters
+            .iter()
+            .filter_map(|cluster| {
+                let target = cluster.same_as.as_ref()?;
+                clusters
+                    .iter()
+                    .any(|c| &c.name == target && c.same_as.is_none())
+                    .then(|| (cluster.name.clone(), target.clone()))
+            })
+            .collect();
+        let clusters: Vec<Cluster> = clusters
+            .into_iter()
+            .filter(|cluster| !shared.iter().any(|(name, _)| name == &cluster.name))
+            .collect();
+        let kubeconfigs = load_kubeconfigs(&clusters).await?;
+        let mut results = spawn_create_clients(kubeconfigs, &clusters, namespace, &resources).await;
+
+        // clusters rejecting our credentials get their pre-command run once, then a second try
+        let retry: Vec<Cluster> = clusters
+            .iter()
+            .filter(|cluster| cluster.pre_command.is_some())
+            .filter(|cluster| {
+                results.iter().any(|(clustername, result)| {
+                    clustername == &cluster.name && matches!(result, Err(e) if is_auth_error(e))
+                })
+            })
+            .cloned()
+            .collect();
+        if !retry.is_empty() {
+            let commands: Vec<String> =
+                retry.iter().filter_map(|c| c.pre_command.clone()).collect();
+            run_pre_commands(&commands).await;
+            let kubeconfigs = load_kubeconfigs(&retry).await?;
+            let retried = spawn_create_clients(kubeconfigs, &retry, namespace, &resources).await;
+            results.retain(|(clustername, _)| !retry.iter().any(|c| &c.name == clustername));
+            results.extend(retried);
+        }
+
+        // a kind is only known once some cluster resolved it, so failures are collected by
+        // resource index first and named afterwards
+        let mut kinds: Vec<Option<GroupKind>> = vec![None; resources.len()];
+        let mut suggestions: Vec<Suggestions> = vec![Suggestions::new(); resources.len()];
+        let mut kubeclients: Vec<MCCluster> = Vec::new();
+        let mut resolved: Vec<ApiResource> = Vec::new();
+        let mut failed: Vec<(ClusterName, usize, String)> = Vec::new();
+        for result in results {
+            match result {
+                (clustername, Ok(clients)) => {
+                    for (i, client) in clients.into_iter().enumerate() {
+                        match client {
+                            Ok(mcclient) => {
+                                let resource = &mcclient.2;
+                                kinds[i].get_or_insert_with(|| {
+                                    (resource.group.clone(), resource.kind.clone())
+                                });
+                                if !resolved.iter().any(|r| {
+                                    r.api_version == resource.api_version && r.kind == resource.kind
+                                }) {
+                                    resolved.push(resource.clone());
+                                }
+                                kubeclients.push(mcclient)
+                            }
+                            Err(e) => {
+                                warn!("failed to create client {}", e);
+                                if let Some(unknown) = e.downcast_ref::<UnknownResource>() {
+                                    for suggestion in &unknown.suggestions {
+                                        suggestions[i]
+                                            .entry(suggestion.clone())
+                                            .or_default()
+                                            .insert(clustername.clone());
+                                    }
+                                }
+                                failed.push((clustername.clone(), i, describe_error(&e)))
+                            }
+                        }
+                    }
+                }
+                (clustername, Err(e)) => {
+                    warn!("failed to create client {}", e);
+                    for i in 0..resources.len() {
+                        failed.push((clustername.clone(), i, describe_error(&e)))
+                    }
+                }
+            }
+        }
+        // a resource no cluster knows is most likely a typo, worth failing on with suggestions
+        // rather than reporting it as a failure of every cluster
+        for ((kind, resource), suggestions) in kinds.iter().zip(&resources).zip(&suggestions) {
+            if kind.is_none() && !suggestions.is_empty() {
+                return Err(anyhow!(unknown_resource_message(resource, suggestions)));
+            }
+        }
+        let group_kinds: Vec<GroupKind> = kinds
+            .into_iter()
+            .zip(&resources)
+            .map(|(kind, resource)| kind.unwrap_or_else(|| (String::new(), resource.clone())))
+            .collect();
+        let kinds: Vec<Kind> = group_kinds.iter().map(|(_, kind)| kind.clone()).collect();
+        let failed = fan_out(
+            &shared,
+            failed
+                .into_iter()
+                .map(|(clustername, i, e)| (clustername, group_kinds[i].clone(), e))
+                .collect(),
+        );
+        let retry = clusters
+            .iter()
+            .map(|cluster| {
+                let policy = RetryPolicy::new(cluster.connection.as_ref());
+                (cluster.name.clone(), policy)
+            })
+            .collect();
+        let targets = clusters
+            .iter()
+            .map(|cluster| (cluster.name.clone(), cluster.target_key()))
+            .collect();
+        Ok(Client {
+            kinds,
+            kubeclients,
+            resources: resolved,
+            group_kinds,
+            failed,
+            retry,
+            targets,
+            query: ListQuery::default(),
+            max_objects: None,
+            cache_ttl: None,
+            shared,
+        })
+    }
+
+    /// Narrows listings and watches to the objects matching a label selector such as `app=web`
+    pub fn with_selector(mut self, selector: &str) -> Self {
+        self.query.labels = Some(selector.to_owned());
+        self
+    }
+
+    /// Narrows listings and watches to the objects matching a field selector such as
+    /// `status.phase=Running`
+    pub fn with_field_selector(mut self, selector: &str) -> Self {
+        self.query.fields = Some(selector.to_owned());
+        self
+    }
+
+    /// Trims listed objects down to what table columns show as each page arrives, so that only
+    /// a fraction of huge objects is held on to
+    pub fn with_trimming(mut self) -> Self {
+        self.query.trim = true;
+        self
+    }
+
+    /// Stops listing a kind on a cluster once `max` objects were listed, leaving out the rest
+    pub fn with_max_objects(mut self, max: usize) -> Self {
+        self.max_objects = Some(max);
+        self
+    }
+
+    /// Reuses a complete list of a kind on a cluster listed less than `ttl` ago with the same
+    /// namespace, selector and credentials, and keeps the lists made for the next time. A zero
+    /// `ttl` always lists again while still keeping the lists. Secrets are never cached.
+    pub fn with_response_cache(mut self, ttl: Duration) -> Self {
+        self.cache_ttl = Some(ttl);
+        self
+    }
+
+    pub async fn list(self) -> Result<Listing> {
+        Ok(list_resources(self, DEFAULT_PAGE_SIZE).await)
+    }
+
+    /// Lists resources from all clusters in pages of `page_size`, yielding each page as soon as
+    /// it arrives so that callers never need to hold a full listing in memory. Clusters that
+    /// could not be listed, including those without a client, are yielded as failures.
+    pub fn list_paged(
+        self,
+        page_size: u32,
+    ) -> impl Stream<Item = std::result::Result<ListResponse, ListFailure>> {
+        let failed = self
+            .failed
+            .into_iter()
+            .map(|(clustername, (group, kind), error)| {
+                Err(ListFailure {
+                    clustername,
+                    group,
+                    kind,
+                    error,
+                })
+            });
+        let (retry, query, cache_ttl) = (self.retry, self.query, self.cache_ttl);
+        let targets = self.targets;
+        let cap = ObjectCap::new(self.max_objects);
+        let clusters = self.kubeclients.into_iter().map(move |mcclient| {
+            let policy = retry.get(&mcclient.0).copied().unwrap_or_default();
+            let cache =
+                cache_ttl.map(|ttl| (ttl, targets.get(&mcclient.0).copied().unwrap_or_default()));
+            Box::pin(list_pages(
+                mcclient,
+                page_size,
+                cap,
+                query.clone(),
+                policy,
+                cache,
+            ))
+        });
+        let shared = self.shared;
+        let pages = serial::interleave(clusters)
+            .flat_map(move |page| futures::stream::iter(fan_out(&shared, vec![page])));
+        futures::stream::iter(failed).chain(pages)
+    }
+
+    /// Like `list_paged`, asking the API servers to render every page as a table through the
+    /// server-side Table protocol
+    pub fn list_tables_paged(
+        self,
+        page_size: u32,
+    ) -> impl Stream<Item = std::result::Result<TableResponse, ListFailure>> {
+        let failed = self
+            .failed
+            .into_iter()
+            .map(|(clustername, (group, kind), error)| {
+                Err(ListFailure {
+                    clustername,
+                    group,
+                    kind,
+                    error,
+                })
+            });
+        let (retry, query) = (self.retry, self.query);
+        let cap = ObjectCap::new(self.max_objects);
+        let clusters = self.kubeclients.into_iter().map(move |mcclient| {
+            let policy = retry.get(&mcclient.0).copied().unwrap_or_default();
+            Box::pin(list_table_pages(
+                mcclient,
+                page_size,
+                cap,
+                query.clone(),
+                policy,
+            ))
+        });
+        let shared = self.shared;
+        let pages = serial::interleave(clusters)
+            .flat_map(move |page| futures::stream::iter(fan_out(&shared, vec![page])));
+        futures::stream::iter(failed).chain(pages)
+    }
+
+    /// Watches all clusters, yielding watcher events tagged with the cluster and kind they came
+    /// from. Watches restart on their own after errors, which are yielded as they occur.
+    pub fn watch(self) -> impl Stream<Item = WatchResponse> {
+        futures::stream::select_all(self.watch_each().into_iter().map(|(_, watch)| watch))
+    }
+
+    /// Like `watch`, with a stream per cluster and kind so that the watches of a cluster can be
+    /// stopped on their own. Clusters sharing a target share its client but are watched apart.
+    pub fn watch_each(self) -> Vec<(ClusterName, BoxStream<'static, WatchResponse>)> {
+        let config = self.query.watcher_config();
+        let mut watches = Vec::new();
+        for (clustername, api, resource) in &self.kubeclients {
+            for name in std::iter::once(clustername).chain(shared_with(&self.shared, clustername)) {
+                let (name, kind) = (name.clone(), resource.kind.clone());
+                let clustername = name.clone();
+                let watch = watcher::watcher(api.clone(), config.clone())
+                    .map(move |event| WatchResponse {
+                        clustername: clustername.clone(),
+                        kind: kind.clone(),
+                        event,
+                    })
+                    .boxed();
+                watches.push((name, watch));
+            }
+        }
+        watches
+    }
+
+    /// The group and kind of each requested resource, in the order they were requested
+    pub fn group_kinds(&self) -> &[GroupKind] {
+        &self.group_kinds
+    }
+
+    /// The resources the kinds were resolved to, in the order clusters resolved them. A kind
+    /// served under different groups, such as core and Knative services, appears once per group
+    pub fn resources(&self) -> &[ApiResource] {
+        &self.resources
+    }
+
+    /// Clusters that could not serve a kind, with the kind and the reason
+    pub fn failures(&self) -> &[(ClusterName, GroupKind, String)] {
+        &self.failed
+    }
+
+    /// Sets the replicas of a named workload through its scale subresource on every cluster. When
+    /// `current_replicas` is set, clusters whose workload has a different replica count are
+    /// skipped and the patch is made conditional on the version that was checked.
+    pub async fn scale(
+        self,
+        name: &str,
+        replicas: i32,
+        current_replicas: Option<i32>,
+    ) -> Vec<ScaleResponse> {
+        scale_resource(self, name, replicas, current_replicas).await
+    }
+
+    /// Printer columns of the kinds served by CRDs, read from the CRD on one of the clusters
+    /// serving each kind. Kinds whose CRD cannot be read are left out and keep the default
+    /// columns.
+    pub async fn printer_columns(
+        &self,
+        wide: bool,
+    ) -> BTreeMap<GroupKind, Vec<CustomResourceColumnDefinition>> {
+        let lookups = self.group_kinds.iter().filter_map(|group_kind| {
+            let (clustername, api, _) = self
+                .kubeclients
+                .iter()
+                .find(|c| (&c.2.group, &c.2.kind) == (&group_kind.0, &group_kind.1))?;
+            let client = api.clone().into_client();
+            let kind = &group_kind.1;
+            Some(async move {
+                let columns = fetch_printer_columns(client, api.resource_url(), wide);
+                match serial::phase(clustername, &format!("read {} columns", kind), columns).await {
+                    Ok(columns) => columns.map(|columns| (group_kind.clone(), columns)),
+                    Err(e) => {
+                        debug!(
+                            "failed to read printer columns of {} on {}: {}",
+                            kind, clustername, e
+                        );
+                        None
+                    }
+                }
+            })
+        });
+        serial::join_all(lookups)
+            .await
+            .into_iter()
+            .flatten()
+            .collect()
+    }
+
+    /// Fetches a named object from every cluster. Clusters without a client are reported as
+    /// failed so that every configured cluster appears in the result exactly once per kind.
+    pub async fn get(&self, name: &str) -> Vec<GetResponse> {
+        get_resource(self, name).await
+    }
+
+    /// Applies a merge patch to the named object on every cluster, returning the patched objects.
+    /// Clusters without the object report it as not found.
+    pub async fn patch(&self, name: &str, patch: &serde_json::Value) -> Vec<GetResponse> {
+        let patch = Patch::Merge(patch.clone());
+        patch_resource(self, name, PatchParams::default(), patch, &[]).await
+    }
+
+    /// Applies a strategic merge patch to the named object on every cluster, which only built in
+    /// kinds accept, returning the patched objects
+    pub async fn patch_strategic(&self, name: &str, patch: &serde_json::Value) -> Vec<GetResponse> {
+        let patch = Patch::Strategic(patch.clone());
+        patch_resource(self, name, PatchParams::default(), patch, &[]).await
+    }
+
+    /// Whether the kinds are built into Kubernetes rather than served by a CRD or an aggregated
+    /// API, going by the API groups they were resolved to
+    pub fn built_in(&self) -> bool {
+        self.resources
+            .iter()
+            .all(|resource| BUILT_IN_GROUPS.contains(&resource.group.as_str()))
+    }
+
+    /// Applies merge patches to single objects through the client of their cluster and kind in
+    /// parallel, returning the patched objects in the order of the patches
+    pub async fn patch_objects(&self, patches: &[ObjectPatch]) -> Vec<GetResponse> {
+        let handles = serial::join_all(patches.iter().map(|patch| {
+            let clustername = self
+                .shared
+                .iter()
+                .find(|(name, _)| name == &patch.clustername)
+                .map_or(&patch.clustername, |(_, target)| target);
+            let client = self
+                .kubeclients
+                .iter()
+                .find(|client| &client.0 == clustername && client.2.kind == patch.kind)
+                .cloned();
+            let name = patch.name.clone();
+            let body = Patch::Merge(patch.patch.clone());
             tokio::spawn(async move {
-                create_client(kubeconfig, cluster, &ns.clone(), &r.clone()).await
+                let client = client?;
+                let span = debug_span!("patch", cluster = %client.0, kind = %client.2.kind);
+                let phase = format!("patch {} {}", client.2.kind, name);
+                let params = PatchParams::default();
+                let patch = client.1.patch(&name, &params, &body);
+                Some(
+                    serial::phase(&client.0, &phase, patch)
+                        .instrument(span)
+                        .await,
+                )
             })
         }))
         .await;
-        let mut kind = String::new();
-        let mut kubeclients: Vec<MCCluster> = Vec::new();
+        patches
+            .iter()
+            .zip(handles)
+            .map(|(patch, handle)| {
+                let result = match handle {
+                    Ok(Some(response)) => patch_result(&patch.clustername, response),
+                    Ok(None) => GetResult::Failed(format!("no client for {}", patch.kind)),
+                    Err(e) => GetResult::Failed(e.to_string()),
+                };
+                GetResponse {
+                    clustername: patch.clustername.clone(),
+                    kind: patch.kind.clone(),
+                    result,
+                }
+            })
+            .collect()
+    }
+
+    /// Applies the object server side as `field_manager` on every cluster but those in `skip`,
+    /// returning the applied objects
+    pub async fn apply(
+        &self,
+        object: &DynamicObject,
+        field_manager: &str,
+        skip: &[String],
+    ) -> Result<Vec<GetResponse>> {
+        let patch = Patch::Apply(serde_json::to_value(object)?);
+        let params = PatchParams::apply(field_manager);
+        Ok(patch_resource(self, &object.name_any(), params, patch, skip).await)
+    }
+
+    /// Creates the object on the named clusters, returning the outcome per cluster
+    pub async fn create(
+        &self,
+        object: &DynamicObject,
+        clusternames: &[String],
+    ) -> Vec<(ClusterName, Result<()>)> {
+        let named = |clustername: &String| {
+            std::iter::once(clustername)
+                .chain(shared_with(&self.shared, clustername))
+                .filter(|name| clusternames.contains(name))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        let handles = serial::join_all(
+            self.kubeclients
+                .iter()
+                .filter(|client| !named(&client.0).is_empty())
+                .map(|client| {
+                    let (clustername, api) = (client.0.clone(), client.1.clone());
+                    let object = object.clone();
+                    let phase = format!("create {}", client.2.kind);
+                    tokio::spawn(async move {
+                        let response = serial::phase(
+                            &clustername,
+                            &phase,
+                            api.create(&PostParams::default(), &object),
+                        )
+                        .await;
+                        (
+                            clustername,
+                            response.map(|_| ()).map_err(anyhow::Error::from),
+                        )
+                    })
+                }),
+        )
+        .await;
+
+        let mut responses = Vec::new();
         for handle in handles {
             match handle {
-                Ok(Ok(mcclient)) => {
-                    kind = mcclient.2.clone();
-                    kubeclients.push(mcclient)
+                Ok((clustername, response)) => {
+                    for name in named(&clustername) {
+                        let response = match &response {
+                            Ok(()) => Ok(()),
+                            Err(e) => Err(anyhow!(e.to_string())),
+                        };
+                        responses.push((name, response))
+                    }
                 }
-                Ok(Err(e)) => warn!("failed to create client {}", e),
-                Err(e) => debug!("join failed {}", e),
+                Err(e) => debug!("join handle failed {}", e),
             }
         }
-        Ok(Client { kind, kubeclients })
+        responses
+    }
+}
+
+/// A result tagged with the cluster it came from
+trait ClusterResult: Clone {
+    fn clustername_mut(&mut self) -> &mut ClusterName;
+}
+
+macro_rules! cluster_result {
+    ($($result:ty),*) => {
+        $(impl ClusterResult for $result {
+            fn clustername_mut(&mut self) -> &mut ClusterName {
+                &mut self.clustername
+            }
+        })*
+    };
+}
+
+cluster_result!(
+    ListResponse,
+    TableResponse,
+    ListFailure,
+    GetResponse,
+    ScaleResponse
+);
+
+impl ClusterResult for (ClusterName, GroupKind, String) {
+    fn clustername_mut(&mut self) -> &mut ClusterName {
+        &mut self.0
+    }
+}
+
+impl<T: ClusterResult, E: ClusterResult> ClusterResult for std::result::Result<T, E> {
+    fn clustername_mut(&mut self) -> &mut ClusterName {
+        match self {
+            Ok(result) => result.clustername_mut(),
+            Err(result) => result.clustername_mut(),
+        }
+    }
+}
+
+// Clusters answered by the given cluster since they share its target
+fn shared_with<'a>(
+    shared: &'a [(ClusterName, ClusterName)],
+    clustername: &'a str,
+) -> impl Iterator<Item = &'a ClusterName> {
+    shared
+        .iter()
+        .filter(move |(_, target)| target == clustername)
+        .map(|(name, _)| name)
+}
+
+// Copies the results of every cluster for the clusters sharing its target, under their own names
+fn fan_out<T: ClusterResult>(shared: &[(ClusterName, ClusterName)], results: Vec<T>) -> Vec<T> {
+    let mut fanned = Vec::with_capacity(results.len());
+    for mut result in results {
+        let clustername = result.clustername_mut().clone();
+        for name in shared_with(shared, &clustername) {
+            let mut copy = result.clone();
+            *copy.clustername_mut() = name.clone();
+            fanned.push(copy);
+        }
+        fanned.push(result);
+    }
+    fanned
+}
+
+type ClusterClients = (ClusterName, Result<Vec<Result<MCCluster>>>);
+
+// Creates clients for all clusters in parallel
+async fn spawn_create_clients(
+    kubeconfigs: Vec<Kubeconfig>,
+    clusters: &[Cluster],
+    namespace: &str,
+    resources: &[String],
+) -> Vec<ClusterClients> {
+    let handles = serial::join_all(clusters.iter().zip(kubeconfigs).map(
+        |(cluster, kubeconfig)| {
+            let cluster = cluster.clone();
+            let ns = Arc::new(cluster.namespace_or(namespace));
+            let r = Arc::new(resources.to_vec());
+            tokio::spawn(async move {
+                let clustername = cluster.name.clone();
+                let span = debug_span!("create_clients", cluster = %clustername);
+                let start = Instant::now();
+                let clients = serial::phase(
+                    &clustername,
+                    "connect",
+                    create_clients(kubeconfig, cluster, &ns.clone(), &r.clone()),
+                )
+                .instrument(span.clone())
+                .await;
+                let elapsed = start.elapsed();
+                profile::record_connect(&clustername, elapsed);
+                span.in_scope(|| {
+                    tracing::debug!(
+                        duration_ms = elapsed.as_millis() as u64,
+                        ok = clients.is_ok(),
+                        "created clients"
+                    )
+                });
+                (clustername, clients)
+            })
+        },
+    ))
+    .await;
+
+    let mut results = Vec::new();
+    for handle in handles {
+        match handle {
+            Ok(result) => results.push(result),
+            Err(e) => debug!("join failed {}", e),
+        }
+    }
+    results
+}
+
+/// Whether a cluster's discovery came from the kubemc cache or was fetched from the cluster
+pub enum DiscoverySource {
+    Cache,
+    Refreshed,
+}
+
+/// Loads discovery for every cluster from the kubemc cache, refreshing it from the cluster when
+/// it is stale or when `refresh` is set.
+pub async fn discover(
+    clusters: &[Cluster],
+    refresh: bool,
+) -> Result<Vec<(ClusterName, Result<(Discovery, DiscoverySource)>)>> {
+    let kubeconfigs = load_kubeconfigs(clusters).await?;
+    let handles = serial::join_all(clusters.iter().zip(kubeconfigs).map(
+        |(cluster, kubeconfig)| {
+            let cluster = cluster.clone();
+            tokio::spawn(async move {
+                let clustername = cluster.name.clone();
+                let discovery = async {
+                    let (client, endpoint) = connect(kubeconfig, cluster).await?;
+                    if !refresh {
+                        if let Ok(discovery) =
+                            Discovery::new_from_kubemc_cache(endpoint.clone(), CACHE_TTL)
+                        {
+                            return Ok((discovery, DiscoverySource::Cache));
+                        }
+                    }
+                    let discovery = Discovery::refresh(&client, endpoint).await?;
+                    Ok((discovery, DiscoverySource::Refreshed))
+                };
+                let discovery = serial::phase(&clustername, "discover", discovery).await;
+                (clustername, discovery)
+            })
+        },
+    ))
+    .await;
+
+    let mut discoveries = Vec::new();
+    for handle in handles {
+        match handle {
+            Ok(discovery) => discoveries.push(discovery),
+            Err(e) => debug!("join failed {}", e),
+        }
+    }
+    Ok(discoveries)
+}
+
+/// Connects to every cluster in parallel, for commands working with typed resources directly
+pub async fn connect_all(clusters: &[Cluster]) -> Result<Vec<(ClusterName, Result<KubeClient>)>> {
+    let kubeconfigs = load_kubeconfigs(clusters).await?;
+    let handles = serial::join_all(clusters.iter().zip(kubeconfigs).map(
+        |(cluster, kubeconfig)| {
+            let cluster = cluster.clone();
+            tokio::spawn(async move {
+                let clustername = cluster.name.clone();
+                let client = serial::phase(&clustername, "connect", connect(kubeconfig, cluster))
+                    .await
+                    .map(|(client, _)| client);
+                (clustername, client)
+            })
+        },
+    ))
+    .await;
+
+    let mut clients = Vec::new();
+    for handle in handles {
+        match handle {
+            Ok(client) => clients.push(client),
+            Err(e) => debug!("join failed {}", e),
+        }
     }
+    Ok(clients)
+}
 
-    pub async fn list(self) -> Result<Vec<ListResponse>> {
-        Ok(list_resources(self, &ListParams::default()).await)
+// Reads the kubeconfig of every cluster, in the same order as the clusters, and resolves exec
+// credentials for the users they rely on. Clusters using the same kubeconfig files share a read.
+async fn load_kubeconfigs(clusters: &[Cluster]) -> Result<Vec<Kubeconfig>> {
+    let mut sources: Vec<(Option<String>, Kubeconfig, Vec<UserOf>)> = Vec::new();
+    for cluster in clusters.iter().filter(|cluster| cluster.uses_kubeconfig()) {
+        let i = match sources
+            .iter()
+            .position(|(source, _, _)| source == &cluster.kubeconfig)
+        {
+            Some(i) => i,
+            None => {
+                let kubeconfig = read_kubeconfig(cluster.kubeconfig.as_deref())?;
+                sources.push((cluster.kubeconfig.clone(), kubeconfig, Vec::new()));
+                sources.len() - 1
+            }
+        };
+        let (_, kubeconfig, users) = &mut sources[i];
+        if let Some(user) = get_cluster_user(kubeconfig, cluster) {
+            users.push((user, cluster.name.clone()));
+        }
+    }
+    for (_, kubeconfig, users) in &mut sources {
+        resolve_exec_credentials(kubeconfig, users).await;
     }
+    Ok(clusters
+        .iter()
+        .map(|cluster| {
+            if !cluster.uses_kubeconfig() {
+                return Kubeconfig::default();
+            }
+            sources
+                .iter()
+                .find(|(source, _, _)| source == &cluster.kubeconfig)
+                .map(|(_, kubeconfig, _)| kubeconfig.clone())
+                .unwrap_or_default()
+        })
+        .collect())
 }
 
-async fn create_client(
+// Creates a client for each requested resource on a single cluster. The outer error means the
+// cluster itself is unusable, the inner ones that a single resource could not be resolved.
+async fn create_clients(
     kubeconfig: Kubeconfig,
     cluster: Cluster,
     namespace: &str,
-    resource: &str,
-) -> Result<MCCluster> {
+    resources: &[String],
+) -> Result<Vec<Result<MCCluster>>> {
     let clustername = cluster.name.clone();
-    let options = cluster.into();
+    let has_pre_command = cluster.pre_command.is_some();
+    let (client, endpoint) = connect(kubeconfig, cluster).await?;
+    // discovery may be served from cache, so check the credentials up front when a failure
+    // could be fixed by the cluster's pre-command
+    if has_pre_command {
+        client.apiserver_version().await?;
+    }
 
-    let discovery = Discovery::new_from_default_cache(get_cluster_endpoint(&kubeconfig, &options)?);
-    let config = kube::config::Config::from_custom_kubeconfig(kubeconfig, &options).await?;
-    let client = KubeClient::try_from(config)?;
+    // kubemc's own cache is preferred as it is refreshed on demand, down to the group versions
+    // it resolved one at a time, and kubectl's is used when kubemc has not discovered this
+    // cluster yet
+    let mut discoveries: Vec<Discovery> = [
+        Discovery::new_from_kubemc_cache(endpoint.clone(), CACHE_TTL)
+            .or_else(|_| Discovery::new_from_resolved_cache(endpoint.clone(), CACHE_TTL)),
+        Discovery::new_from_default_cache(endpoint.clone()),
+    ]
+    .into_iter()
+    .filter_map(|discovery| discovery.ok())
+    .collect();
+    let mut groups = None;
+    let mut refreshed = false;
+    let mut refresh_succeeded = false;
+    let mut kube_discovery: Option<KubeDiscovery> = None;
+    let mut clients = Vec::new();
+    for resource in resources {
+        let mut found = discoveries
+            .iter()
+            .find_map(|discovery| discovery.get_resource_from_name(resource).ok());
 
-    // if cached discovery succeeded and the requested resource is present, use it to make the
-    // request. Otherwise fall back to discovery via k8s api.
-    if let Ok(discovery) = discovery {
-        if let Ok((resource, scope)) = discovery.get_resource_from_name(resource) {
+        // the group version serving the resource is usually enough, which is a request or two
+        // rather than one per group of the cluster
+        if found.is_none() {
+            match Discovery::resolve(&client, endpoint.clone(), &mut groups, resource).await {
+                Ok(discovery) => {
+                    found = discovery.get_resource_from_name(resource).ok();
+                    discoveries.push(discovery);
+                }
+                Err(e) => debug!("failed to resolve {} for {}: {}", resource, clustername, e),
+            }
+        }
+
+        // a cache miss may be a newly installed CRD, so refresh the kubemc cache once
+        if found.is_none() && !refreshed {
+            refreshed = true;
+            match Discovery::refresh(&client, endpoint.clone()).await {
+                Ok(discovery) => {
+                    found = discovery.get_resource_from_name(resource).ok();
+                    discoveries.insert(0, discovery);
+                    refresh_succeeded = true;
+                }
+                Err(e) => debug!("failed to refresh discovery for {}: {}", clustername, e),
+            }
+        }
+
+        if let Some((resource, scope)) = found {
             debug!(
                 "creating client for cluster {} for resource {} with scope {:?}",
                 &clustername, &resource.kind, &scope
             );
-            let kind = resource.kind.clone();
-            let client = create_typed_kubeclient(client, resource, scope, namespace);
-            return Ok((clustername, client, kind));
+            let api = create_typed_kubeclient(client.clone(), resource.clone(), scope, namespace);
+            clients.push(Ok((clustername.clone(), api, resource)));
+            continue;
+        }
+
+        // live discovery is only needed when the cache could not be refreshed, otherwise the
+        // resource simply does not exist on this cluster
+        let ar_cap = if refresh_succeeded {
+            None
+        } else {
+            if kube_discovery.is_none() {
+                kube_discovery = Some(
+                    KubeDiscovery::new(client.clone())
+                        .run()
+                        .await
+                        .context("failed to discover api resources")?,
+                );
+            }
+            kube_discovery
+                .as_ref()
+                .and_then(|kube_discovery| resolve_api_resource(kube_discovery, resource))
+        };
+
+        if let Some((ar, cap)) = ar_cap {
+            let api = create_typed_kubeclient(client.clone(), ar.clone(), cap.scope, namespace);
+            clients.push(Ok((clustername.clone(), api, ar)))
+        } else {
+            let mut suggestions: Vec<String> = discoveries
+                .iter()
+                .flat_map(|discovery| discovery.suggestions(resource))
+                .collect();
+            if let Some(kube_discovery) = &kube_discovery {
+                suggestions.extend(live_suggestions(kube_discovery, resource));
+            }
+            suggestions.sort();
+            suggestions.dedup();
+            clients.push(Err(UnknownResource {
+                resource: resource.clone(),
+                clustername: clustername.clone(),
+                suggestions,
+            }
+            .into()))
         }
     }
+    Ok(clients)
+}
 
-    let kube_discovery = KubeDiscovery::new(client.clone())
-        .run()
-        .await
-        .context("failed to discover api resources")?;
+// Builds a kube client for the cluster, returning it along with the cluster's server endpoint
+async fn connect(kubeconfig: Kubeconfig, cluster: Cluster) -> Result<(KubeClient, String)> {
+    let connection = cluster.connection.clone();
+    let impersonate = cluster.impersonate.clone();
+    let clustername = cluster.name.clone();
+    let (mut config, endpoint) = if cluster.in_cluster {
+        let config = kube::Config::incluster().with_context(|| {
+            format!(
+                "failed to load the in-cluster config for cluster {}",
+                clustername
+            )
+        })?;
+        let endpoint = config.cluster_url.to_string();
+        (config, endpoint)
+    } else {
+        let (kubeconfig, options) = match &cluster.server {
+            Some(server) => direct_kubeconfig(&cluster, server),
+            None => (kubeconfig, cluster.into()),
+        };
+        let endpoint = get_cluster_endpoint(&kubeconfig, &options)?;
+        let config = kube::config::Config::from_custom_kubeconfig(kubeconfig, &options).await?;
+        (config, endpoint)
+    };
+    if let Some(impersonate) = impersonate {
+        if impersonate.user.is_none() && !impersonate.groups.is_empty() {
+            return Err(anyhow!(
+                "cannot impersonate groups on cluster {} without a user, set --as as well",
+                clustername
+            ));
+        }
+        config.auth_info.impersonate = impersonate.user;
+        config.auth_info.impersonate_groups =
+            Some(impersonate.groups).filter(|groups| !groups.is_empty());
+    }
+    let client = build_kube_client(config, connection.as_ref())?;
+    Ok((client, endpoint))
+}
 
-    let ar_cap = resolve_api_resource(&kube_discovery, resource);
+// Builds the kube client, applying any per-cluster connection tuning. Timeouts are handled by
+// kube itself, but keep-alive and pool settings need a hand-built hyper client.
+fn build_kube_client(
+    mut config: kube::Config,
+    connection: Option<&Connection>,
+) -> Result<KubeClient> {
+    let Some(connection) = connection else {
+        return Ok(KubeClient::try_from(config)?);
+    };
+    if let Some(secs) = connection.connect_timeout {
+        config.connect_timeout = Some(Duration::from_secs(secs));
+    }
+    if let Some(secs) = connection.read_timeout {
+        config.read_timeout = Some(Duration::from_secs(secs));
+    }
+    if !connection.requires_custom_transport() {
+        return Ok(KubeClient::try_from(config)?);
+    }
+    if config.proxy_url.is_some() {
+        warn!("proxy-url is not supported together with connection tuning and will be ignored");
+    }
+
+    let mut http = HttpConnector::new();
+    http.enforce_http(false);
+    http.set_keepalive(connection.tcp_keepalive.map(Duration::from_secs));
 
-    if let Some((ar, cap)) = ar_cap {
-        let kind = ar.kind.clone();
-        let client = create_typed_kubeclient(client, ar, cap.scope, namespace);
-        Ok((clustername, client, kind))
+    let mut tls = HttpsConnectorBuilder::new()
+        .with_tls_config(config.rustls_client_config()?)
+        .https_or_http();
+    if let Some(server_name) = &config.tls_server_name {
+        tls = tls.with_server_name(server_name.clone());
+    }
+    let https = if connection.http2 {
+        tls.enable_http1().enable_http2().wrap_connector(http)
     } else {
-        Err(anyhow!(
-            "discovery of resource {} failed for cluster {}",
-            resource,
-            clustername
-        ))
+        tls.enable_http1().wrap_connector(http)
+    };
+
+    let mut connector = TimeoutConnector::new(https);
+    connector.set_connect_timeout(config.connect_timeout);
+    connector.set_read_timeout(config.read_timeout);
+    connector.set_write_timeout(config.write_timeout);
+
+    let mut builder = hyper::Client::builder();
+    if let Some(secs) = connection.pool_idle_timeout {
+        builder.pool_idle_timeout(Duration::from_secs(secs));
+    }
+    if let Some(secs) = connection.http2_keep_alive_interval {
+        builder
+            .http2_keep_alive_interval(Duration::from_secs(secs))
+            .http2_keep_alive_while_idle(true);
+    }
+    if let Some(secs) = connection.http2_keep_alive_timeout {
+        builder.http2_keep_alive_timeout(Duration::from_secs(secs));
     }
+    let hyper_client: hyper::Client<_, hyper::Body> = builder.build(connector);
+
+    let service = ServiceBuilder::new()
+        .layer(config.base_uri_layer())
+        .option_layer(config.auth_layer()?)
+        .layer(config.extra_headers_layer()?)
+        .service(hyper_client);
+    Ok(KubeClient::new(service, config.default_namespace))
 }
 
 fn get_cluster_endpoint(kubeconfig: &Kubeconfig, options: &KubeConfigOptions) -> Result<String> {
@@ -117,6 +1244,62 @@ fn get_cluster_endpoint(kubeconfig: &Kubeconfig, options: &KubeConfigOptions) ->
     }
 }
 
+// A kubeconfig holding only the cluster given by `server`, sending the token of its token file
+fn direct_kubeconfig(cluster: &Cluster, server: &str) -> (Kubeconfig, KubeConfigOptions) {
+    let name = cluster.name.clone();
+    let kubeconfig = Kubeconfig {
+        clusters: vec![NamedCluster {
+            name: name.clone(),
+            cluster: Some(kube::config::Cluster {
+                server: Some(server.to_owned()),
+                certificate_authority: cluster.certificate_authority.clone(),
+                ..Default::default()
+            }),
+        }],
+        auth_infos: vec![NamedAuthInfo {
+            name: name.clone(),
+            auth_info: Some(AuthInfo {
+                token_file: cluster.token_file.clone(),
+                ..Default::default()
+            }),
+        }],
+        ..Default::default()
+    };
+    let options = KubeConfigOptions {
+        context: None,
+        cluster: Some(name.clone()),
+        user: Some(name),
+    };
+    (kubeconfig, options)
+}
+
+/// Name of the directory the discovery of a cluster is cached under, derived from its API server
+pub fn discovery_cache_name(cluster: &Cluster) -> Result<String> {
+    let endpoint = if cluster.in_cluster {
+        kube::Config::incluster()?.cluster_url.to_string()
+    } else if let Some(server) = &cluster.server {
+        server.clone()
+    } else {
+        let kubeconfig = read_kubeconfig(cluster.kubeconfig.as_deref())?;
+        get_cluster_endpoint(&kubeconfig, &cluster.into())?
+    };
+    parse_kube_url_to_discovery(endpoint)
+}
+
+// Returns the kubeconfig user a cluster authenticates as, either set directly or via its context
+fn get_cluster_user(kubeconfig: &Kubeconfig, cluster: &Cluster) -> Option<String> {
+    if let Some(user) = &cluster.user {
+        return Some(user.clone());
+    }
+    let ctx = cluster.context.as_ref()?;
+    kubeconfig
+        .contexts
+        .iter()
+        .find(|named_context| named_context.name == *ctx)
+        .and_then(|named_context| named_context.context.as_ref())
+        .map(|context| context.user.clone())
+}
+
 // Returns the cluster name from the specified context
 fn get_cluster_from_context(kubeconfig: &Kubeconfig, ctx: &str) -> Result<String> {
     kubeconfig
@@ -147,37 +1330,510 @@ fn get_server_endpoint_from_kubeconfig(
 }
 
 // Fetch resources using all clients in parallel
-async fn list_resources(client: Client, lp: &ListParams) -> Vec<ListResponse> {
-    let kind = client.kind;
-    let handles = futures::future::join_all(client.kubeclients.into_iter().map(|client| {
-        let lp = lp.clone();
+// Lists all clusters page by page, merging the pages of each cluster into a single response.
+// Clusters failing part way through are only reported as failures.
+async fn list_resources(client: Client, page_size: u32) -> Listing {
+    let start = Instant::now();
+    let mut responses: Vec<ListResponse> = Vec::new();
+    let mut failures: Vec<ListFailure> = Vec::new();
+    let mut pages = client.list_paged(page_size);
+    while let Some(page) = pages.next().await {
+        match page {
+            Ok(page) => match responses
+                .iter_mut()
+                .find(|lr| lr.clustername == page.clustername && lr.kind == page.kind)
+            {
+                Some(lr) => {
+                    lr.object_list.items.extend(page.object_list.items);
+                    lr.truncated = lr.truncated.or(page.truncated);
+                }
+                None => responses.push(page),
+            },
+            Err(failure) => failures.push(failure),
+        }
+    }
+    responses.retain(|lr| {
+        !failures
+            .iter()
+            .any(|f| f.clustername == lr.clustername && f.kind == lr.kind)
+    });
+    failures.sort_by(|a, b| a.clustername.cmp(&b.clustername));
+    tracing::debug!(
+        duration_ms = start.elapsed().as_millis() as u64,
+        clusters = responses.len(),
+        failures = failures.len(),
+        objects = responses
+            .iter()
+            .map(|lr| lr.object_list.items.len())
+            .sum::<usize>(),
+        "listed resources"
+    );
+    Listing {
+        responses,
+        failures,
+    }
+}
+
+// Lists a single cluster page by page, following continue tokens until the listing is complete
+// or reaches the object cap. A failed page is yielded as a failure and ends the stream for that
+// cluster. With a cache TTL and the cluster's target the pages of a list cached within it are
+// yielded instead, and a complete list is cached for the next time. Secrets are never cached.
+fn list_pages(
+    mcclient: MCCluster,
+    page_size: u32,
+    cap: ObjectCap,
+    query: ListQuery,
+    policy: RetryPolicy,
+    cache: Option<(Duration, u64)>,
+) -> impl Stream<Item = std::result::Result<ListResponse, ListFailure>> {
+    let (clustername, api, resource) = mcclient;
+    let (kind, group, api_version) = (resource.kind, resource.group, resource.api_version);
+    let cache =
+        cache
+            .filter(|_| kind != "Secret")
+            .and_then(|(ttl, target)| {
+                match ResponseCache::new(&clustername, target, api.resource_url(), &query) {
+                    Ok(cache) => Some((cache, ttl)),
+                    Err(e) => {
+                        debug!("no response cache for {}: {}", clustername, e);
+                        None
+                    }
+                }
+            });
+    if let Some((pages, age)) = cache.as_ref().and_then(|(cache, ttl)| cache.read(*ttl)) {
+        debug!(
+            "reusing {} list of cluster {} from {:?} ago",
+            kind, clustername, age
+        );
+        // the cached pages still only yield as many objects as the cap allows
+        let (mut cap, mut remaining) = (cap, pages.iter().map(|p| p.items.len()).sum::<usize>());
+        let mut responses = Vec::new();
+        for (i, mut object_list) in pages.into_iter().enumerate() {
+            remaining -= object_list.items.len();
+            let truncated = cap.take(&mut object_list.items, remaining);
+            let done = truncated.is_some();
+            responses.push(Ok(ListResponse {
+                clustername: clustername.clone(),
+                kind: kind.clone(),
+                api_version: api_version.clone(),
+                object_list,
+                truncated,
+                cached: (i == 0).then_some(age),
+            }));
+            if done {
+                break;
+            }
+        }
+        return futures::stream::iter(responses).left_stream();
+    }
+    let writer = cache.and_then(|(cache, _)| match cache.writer() {
+        Ok(writer) => Some(writer),
+        Err(e) => {
+            debug!(
+                "failed to cache {} list of cluster {}: {}",
+                kind, clustername, e
+            );
+            None
+        }
+    });
+    // the continue token of the next page, the objects yielded so far, how often the list
+    // started over, how many objects it may still yield and where the pages are cached, None
+    // once the last page was listed
+    let state = Some((None::<String>, PageTracker::default(), 0, cap, writer));
+    futures::stream::unfold(state, move |state| {
+        let api = api.clone();
+        let clustername = clustername.clone();
+        let (kind, group, api_version) = (kind.clone(), group.clone(), api_version.clone());
+        let query = query.clone();
+        async move {
+            let (mut continue_token, mut tracker, mut restarts, mut cap, mut writer) = state?;
+            let result = loop {
+                let mut lp = query.params(cap.limit(page_size));
+                if let Some(token) = &continue_token {
+                    lp = lp.continue_token(token);
+                }
+                let span = debug_span!("list_page", cluster = %clustername, kind = %kind);
+                let start = Instant::now();
+                let list = with_retries(&clustername, policy, || api.list(&lp));
+                let result = serial::phase(&clustername, &format!("list {}", kind), list)
+                    .instrument(span.clone())
+                    .await;
+                let elapsed = start.elapsed();
+                let objects = result.as_ref().map_or(0, |list| list.items.len());
+                profile::record_page(&clustername, elapsed, objects);
+                span.in_scope(|| {
+                    tracing::debug!(
+                        duration_ms = elapsed.as_millis() as u64,
+                        objects,
+                        "listed page"
+                    )
+                });
+                match result {
+                    // objects already yielded are dropped from the pages of the new list
+                    Err(e)
+                        if continue_token.is_some()
+                            && is_expired_continue(&e)
+                            && restarts < MAX_LIST_RESTARTS =>
+                    {
+                        debug!(
+                            "continue token for {} on cluster {} expired, listing again",
+                            kind, clustername
+                        );
+                        continue_token = None;
+                        restarts += 1;
+                    }
+                    result => break result,
+                }
+            };
+            match result {
+                Ok(mut object_list) => {
+                    let dropped = tracker.reconcile(&mut object_list.items);
+                    if dropped > 0 {
+                        debug!(
+                            "dropped {} {} objects of cluster {} listed before",
+                            dropped, kind, clustername
+                        );
+                    }
+                    if query.trim {
+                        object_list.items.iter_mut().for_each(trim_object);
+                    }
+                    let mut next = object_list
+                        .metadata
+                        .continue_
+                        .clone()
+                        .filter(|token| !token.is_empty());
+                    let truncated = cap.count(
+                        object_list.items.len(),
+                        next.is_some(),
+                        object_list.metadata.remaining_item_count,
+                    );
+                    if truncated.is_some() {
+                        next = None;
+                    }
+                    // only complete lists are cached, a truncated one is dropped unfinished
+                    if let Some(mut cached) = writer.take() {
+                        let written = cached.page(&object_list).and_then(|()| {
+                            if next.is_none() && truncated.is_none() {
+                                cached.finish()?;
+                            } else {
+                                writer = Some(cached);
+                            }
+                            Ok(())
+                        });
+                        if let Err(e) = written {
+                            debug!(
+                                "failed to cache {} list of cluster {}: {}",
+                                kind, clustername, e
+                            );
+                        }
+                    }
+                    let response = ListResponse {
+                        clustername,
+                        kind,
+                        api_version,
+                        object_list,
+                        truncated,
+                        cached: None,
+                    };
+                    Some((
+                        Ok(response),
+                        next.map(|token| (Some(token), tracker, restarts, cap, writer)),
+                    ))
+                }
+                Err(e) => {
+                    debug!("failed request to cluster {}: {}", clustername, e);
+                    let failure = ListFailure {
+                        clustername,
+                        group,
+                        kind,
+                        error: describe_kube_error(&e),
+                    };
+                    Some((Err(failure), None))
+                }
+            }
+        }
+    })
+    .right_stream()
+}
+
+fn list_table_pages(
+    mcclient: MCCluster,
+    page_size: u32,
+    cap: ObjectCap,
+    query: ListQuery,
+    policy: RetryPolicy,
+) -> impl Stream<Item = std::result::Result<TableResponse, ListFailure>> {
+    let (clustername, api, resource) = mcclient;
+    let (kind, group) = (resource.kind, resource.group);
+    let client = api.clone().into_client();
+    let url = api.resource_url().to_owned();
+    futures::stream::unfold(Some((None::<String>, cap)), move |state| {
+        let client = client.clone();
+        let request = Request::new(url.clone());
+        let clustername = clustername.clone();
+        let (kind, group) = (kind.clone(), group.clone());
+        let query = query.clone();
+        async move {
+            let (continue_token, mut cap) = state?;
+            let mut lp = query.params(cap.limit(page_size));
+            if let Some(token) = &continue_token {
+                lp = lp.continue_token(token);
+            }
+            let start = Instant::now();
+            let list = with_retries(&clustername, policy, || async {
+                let mut table_request = request.list(&lp).map_err(kube::Error::BuildRequest)?;
+                table_request
+                    .headers_mut()
+                    .insert(ACCEPT, HeaderValue::from_static(TABLE_ACCEPT));
+                client.request::<serde_json::Value>(table_request).await
+            });
+            let result = serial::phase(&clustername, &format!("list {} as table", kind), list)
+                .instrument(debug_span!("list_table_page", cluster = %clustername, kind = %kind))
+                .await
+                .map_err(|e| describe_kube_error(&e))
+                .and_then(|response| {
+                    ServerTable::from_response(response)
+                        .map_err(|e| format!("invalid table: {}", e))
+                });
+            let rows = result.as_ref().map_or(0, |table| table.rows.len());
+            profile::record_page(&clustername, start.elapsed(), rows);
+            match result {
+                Ok(table) => {
+                    let mut next = table
+                        .metadata
+                        .continue_
+                        .clone()
+                        .filter(|token| !token.is_empty());
+                    let truncated = cap.count(
+                        table.rows.len(),
+                        next.is_some(),
+                        table.metadata.remaining_item_count,
+                    );
+                    if truncated.is_some() {
+                        next = None;
+                    }
+                    let response = TableResponse {
+                        clustername,
+                        group,
+                        kind,
+                        table,
+                        truncated,
+                    };
+                    Some((Ok(response), next.map(|token| (Some(token), cap))))
+                }
+                Err(error) => {
+                    debug!("failed table request to cluster {}: {}", clustername, error);
+                    let failure = ListFailure {
+                        clustername,
+                        group,
+                        kind,
+                        error,
+                    };
+                    Some((Err(failure), None))
+                }
+            }
+        }
+    })
+}
+
+// Fetch a named resource using all clients in parallel
+async fn get_resource(client: &Client, name: &str) -> Vec<GetResponse> {
+    let handles = serial::join_all(client.kubeclients.iter().cloned().map(|mcclient| {
+        let name = name.to_owned();
+        let policy = client.retry.get(&mcclient.0).copied().unwrap_or_default();
+        let span = debug_span!("get", cluster = %mcclient.0, kind = %mcclient.2.kind);
+        tokio::spawn(async move {
+            let get = with_retries(&mcclient.0, policy, || mcclient.1.get_opt(&name));
+            let phase = format!("get {} {}", mcclient.2.kind, name);
+            serial::phase(&mcclient.0, &phase, get)
+                .instrument(span)
+                .await
+        })
+    }))
+    .await;
+
+    let mut responses: Vec<GetResponse> = client
+        .failed
+        .iter()
+        .cloned()
+        .map(|(clustername, (_, kind), e)| GetResponse {
+            clustername,
+            kind,
+            result: GetResult::Failed(e),
+        })
+        .collect();
+    let mut fetched = Vec::new();
+    for ((clustername, _, resource), handle) in client.kubeclients.iter().zip(handles) {
+        let result = match handle {
+            Ok(Ok(Some(object))) => GetResult::Found(Box::new(object)),
+            Ok(Ok(None)) => GetResult::NotFound,
+            Ok(Err(e)) => {
+                debug!("failed request to cluster {}: {}", clustername, e);
+                GetResult::Failed(describe_kube_error(&e))
+            }
+            // a cluster whose request panicked still counts as failed
+            Err(e) => {
+                debug!("join handle failed {}", e);
+                GetResult::Failed(e.to_string())
+            }
+        };
+        fetched.push(GetResponse {
+            clustername: clustername.clone(),
+            kind: resource.kind.clone(),
+            result,
+        })
+    }
+    responses.extend(fan_out(&client.shared, fetched));
+    responses
+}
+
+// Patch a named resource using all clients but those of the clusters in `skip` in parallel
+async fn patch_resource(
+    client: &Client,
+    name: &str,
+    params: PatchParams,
+    patch: Patch<serde_json::Value>,
+    skip: &[String],
+) -> Vec<GetResponse> {
+    let clients = client
+        .kubeclients
+        .iter()
+        .filter(|mcclient| {
+            std::iter::once(&mcclient.0)
+                .chain(shared_with(&client.shared, &mcclient.0))
+                .any(|name| !skip.contains(name))
+        })
+        .cloned();
+    let handles = serial::join_all(clients.map(|client| {
+        let name = name.to_owned();
+        let (params, patch) = (params.clone(), patch.clone());
+        let span = debug_span!("patch", cluster = %client.0, kind = %client.2.kind);
         tokio::spawn(async move {
-            let response = client.1.list(&lp).await;
-            (client.0, response)
+            let phase = format!("patch {} {}", client.2.kind, name);
+            let response = serial::phase(&client.0, &phase, client.1.patch(&name, &params, &patch))
+                .instrument(span)
+                .await;
+            (client.0, response, client.2.kind)
         })
     }))
     .await;
 
-    let mut lr: Vec<ListResponse> = Vec::new();
+    let mut responses: Vec<GetResponse> = client
+        .failed
+        .iter()
+        .filter(|(clustername, _, _)| !skip.contains(clustername))
+        .cloned()
+        .map(|(clustername, (_, kind), e)| GetResponse {
+            clustername,
+            kind,
+            result: GetResult::Failed(e),
+        })
+        .collect();
+    let mut patched = Vec::new();
     for handle in handles {
         match handle {
-            Ok(h) => {
-                if let Ok(object_list) = h.1 {
-                    lr.push(ListResponse {
-                        clustername: h.0,
-                        kind: kind.clone(),
-                        object_list,
-                    })
-                } else {
-                    warn!("failed request to cluster {}", h.0)
-                }
+            Ok((clustername, response, kind)) => {
+                let result = patch_result(&clustername, response);
+                patched.push(GetResponse {
+                    clustername,
+                    kind,
+                    result,
+                })
             }
             Err(e) => {
                 debug!("join handle failed {}", e)
             }
         }
     }
-    lr
+    responses.extend(
+        fan_out(&client.shared, patched)
+            .into_iter()
+            .filter(|response| !skip.contains(&response.clustername)),
+    );
+    responses
+}
+
+fn patch_result(clustername: &str, response: kube::Result<DynamicObject>) -> GetResult {
+    match response {
+        Ok(object) => GetResult::Found(Box::new(object)),
+        Err(kube::Error::Api(e)) if e.code == 404 => GetResult::NotFound,
+        Err(e) => {
+            debug!("failed request to cluster {}: {}", clustername, e);
+            GetResult::Failed(describe_kube_error(&e))
+        }
+    }
+}
+
+// Scale a named resource using all clients in parallel
+async fn scale_resource(
+    client: Client,
+    name: &str,
+    replicas: i32,
+    current_replicas: Option<i32>,
+) -> Vec<ScaleResponse> {
+    let handles = serial::join_all(client.kubeclients.into_iter().map(|client| {
+        let name = name.to_owned();
+        tokio::spawn(async move {
+            let scale = scale_one(&client.1, &name, replicas, current_replicas);
+            let result = serial::phase(&client.0, &format!("scale {}", name), scale).await;
+            (client.0, result)
+        })
+    }))
+    .await;
+
+    let mut responses: Vec<ScaleResponse> = client
+        .failed
+        .into_iter()
+        .map(|(clustername, _, e)| ScaleResponse {
+            clustername,
+            result: ScaleResult::Failed(e),
+        })
+        .collect();
+    let mut scaled = Vec::new();
+    for handle in handles {
+        match handle {
+            Ok((clustername, result)) => scaled.push(ScaleResponse {
+                clustername,
+                result,
+            }),
+            Err(e) => {
+                debug!("join handle failed {}", e)
+            }
+        }
+    }
+    responses.extend(fan_out(&client.shared, scaled));
+    responses
+}
+
+async fn scale_one(
+    api: &Api<DynamicObject>,
+    name: &str,
+    replicas: i32,
+    current_replicas: Option<i32>,
+) -> ScaleResult {
+    let scale = match api.get_scale(name).await {
+        Ok(scale) => scale,
+        Err(e) => return ScaleResult::Failed(describe_kube_error(&e)),
+    };
+    let old = scale
+        .spec
+        .and_then(|spec| spec.replicas)
+        .unwrap_or_default();
+    let mut patch = serde_json::json!({ "spec": { "replicas": replicas } });
+    if let Some(expected) = current_replicas {
+        if expected != old {
+            return ScaleResult::Skipped { current: old };
+        }
+        patch["metadata"] =
+            serde_json::json!({ "resourceVersion": scale.metadata.resource_version });
+    }
+    match api
+        .patch_scale(name, &PatchParams::default(), &Patch::Merge(&patch))
+        .await
+    {
+        Ok(_) => ScaleResult::Scaled { old, new: replicas },
+        Err(e) => ScaleResult::Failed(describe_kube_error(&e)),
+    }
 }
 
 fn create_typed_kubeclient(
@@ -193,6 +1849,22 @@ fn create_typed_kubeclient(
     }
 }
 
+// Suggestions for a resource name out of the kinds and plurals of live discovery
+fn live_suggestions(discovery: &KubeDiscovery, name: &str) -> Vec<String> {
+    let resources: Vec<ApiResource> = discovery
+        .groups()
+        .flat_map(|group| group.resources_by_stability())
+        .map(|(resource, _)| resource)
+        .collect();
+    nearest_resources(
+        name,
+        resources.iter().flat_map(|resource| {
+            [resource.kind.as_str(), resource.plural.as_str()]
+                .map(|candidate| (resource.plural.as_str(), candidate))
+        }),
+    )
+}
+
 fn resolve_api_resource(
     discovery: &KubeDiscovery,
     name: &str,
@@ -208,10 +1880,16 @@ fn resolve_api_resource(
                 .into_iter()
                 .map(move |res| (group, res))
         })
-        .filter(|(_, (res, _))| {
-            // match on both resource name and kind name
+        .filter(|(group, (res, _))| {
+            // match on both resource name and kind name, optionally qualified with the group
             // ideally we should allow shortname matches as well
-            name.eq_ignore_ascii_case(&res.kind) || name.eq_ignore_ascii_case(&res.plural)
+            let matches = |name: &str| {
+                name.eq_ignore_ascii_case(&res.kind) || name.eq_ignore_ascii_case(&res.plural)
+            };
+            matches(name)
+                || name.split_once('.').is_some_and(|(name, qualifier)| {
+                    qualifier.eq_ignore_ascii_case(group.name()) && matches(name)
+                })
         })
         .min_by_key(|(group, _res)| group.name())
         .map(|(_, res)| res)
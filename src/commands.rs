@@ -1,14 +1,118 @@
-use std::io::{self, Write};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::{Duration, Instant, SystemTime},
+};
+
+use anyhow::{anyhow, Context, Result};
+use clap::{parser::ValueSource, ArgMatches, Args, CommandFactory, Parser, Subcommand};
+use futures::{
+    stream::{AbortHandle, Abortable, BoxStream, SelectAll},
+    Future, Stream, StreamExt,
+};
+use k8s_openapi::{
+    api::{
+        apps::v1::{Deployment, ReplicaSet},
+        batch::v1::{CronJob, Job},
+        coordination::v1::Lease,
+        core::v1::{Endpoints, Namespace, Node, Pod},
+        discovery::v1::EndpointSlice,
+    },
+    chrono::{self, Utc},
+    kube_aggregator::pkg::apis::apiregistration::v1::APIService,
+};
+use kube::{
+    api::{AttachParams, EvictParams, ListParams, LogParams, Patch, PatchParams},
+    core::DynamicObject,
+    discovery::ApiResource,
+    runtime::{watcher, WatchStreamExt},
+    Api, Client as KubeClient, ResourceExt,
+};
+use serde::Serialize;
+use tabled::Tabled;
+use tokio::io::AsyncWrite;
+use tracing::log::{debug, warn};
 
-use anyhow::Result;
-use clap::{Parser, Subcommand};
+#[cfg(feature = "dev")]
+use crate::e2e::{Environment, KIND_PREFIX};
 
 use crate::{
-    client::Client,
-    config::Config,
-    output::{convert_list_response_to_table, create_table},
+    apply::{apply_order, defining_crd, manifest_crds, FIELD_MANAGER},
+    args::{parse_age_arg, parse_pod_arg, resource_and_name, AgeWindow, ObjectRef},
+    audit::{image_pull_audit, scheduling_audit, security_audit},
+    cache::{entries_older_than, format_size, CacheDirs, CacheKind},
+    client::{
+        connect_all, discover, discovery_cache_name, Client, DiscoverySource, GetResult, GroupKind,
+        ListFailure, ListResponse, ObjectPatch, WatchResponse, DEFAULT_MAX_OBJECTS_PER_CLUSTER,
+        DEFAULT_PAGE_SIZE,
+    },
+    completion::bash_completion,
+    config::{
+        config_path, read_kubeconfig, Cluster, Clusterset, Config, ConfigFile, Impersonation,
+        OutputSettings, API_VERSION,
+    },
+    copy::{Copy, CopyPath},
+    describe::{
+        node_requests, node_taints, wide_columns, wide_headers, NodeDescription, PodResources,
+        TAINTS_HEADER,
+    },
+    diff::{apply_merge_patch, apply_strategic_patch, diff, merge_patch, strategic_merge_patch},
+    discovery::CACHE_TTL,
+    drain::{cordon_patch, matches_pattern, Disposition, DrainPolicy},
+    edit::{cleaned, edit_in_editor, editable},
+    errors::{describe_error, describe_kube_error},
+    failover::{BackendUpdate, ServiceBackends, SERVICE_NAME_LABEL},
+    history::{self, Entry},
+    index::{self, completion_names, indexed_kind, ClusterIndex, COMPLETION_TTL, INDEXED_KINDS},
+    job::{job_from_cronjob, job_state, manual_job_name},
+    logs::{open_logs, LogMultiplexer, PodSelector},
+    manifest::{object_name, read_manifest, resource_name},
+    metadata::{metadata_patch, parse_metadata_args, MetadataField},
+    otlp,
+    output::{
+        api_resource_union, convert_list_response_to_table, create_table, format_duration,
+        namespace_union, object_line, print_grouped_rows, print_grouped_wide_rows, print_rows,
+        print_rows_with_footer, print_wide_rows, set_table_settings, table_string,
+        ApiServiceOutput, ApplyOutput, CacheOutput, CheckOutput, ClusterList, ClusterNameOutput,
+        ClusterOutput, CreateJobOutput, EditOutput, ErrorOutput, FailoverOutput, FindOutput,
+        Formatter, Health, HistoryOutput, ImageAuditOutput, ImageFindingOutput, IndexOutput,
+        IpFamily, KubeOutput, LeaderOutput, ManifestOutput, ManifestState, MetadataOutput,
+        NamedGetReport, NamespaceOutput, NodeMaintenanceOutput, OutputFormat, ReadinessOutput,
+        RevisionOutput, RolloutOutput, RowGroup, ScaleOutput, SchedulingOutput,
+        SecurityAuditOutput, SecurityFindingOutput, StatsCounts, StatsOutput, StatsReport,
+        StatusCell, SummaryOutput, TableSettings, TimelineOutput, WaitOutput,
+    },
+    ownership::{pod_owner, Owner, CHILD_RESOURCES},
+    paging::{sort_key, Truncated},
+    plugin::{parse_output_format, plugin_name, print_plugin, set_output_plugin, OutputPlugin},
+    portforward::{Forward, PortMapping, Target},
+    printer::{column_value, reads_annotations},
+    redact::{redact, redact_changes},
+    reload::{cluster_kubeconfigs, FileWatcher, Reload},
+    report::{parse_report, Report, TestCase},
+    rollout::{
+        deployment_rollout_state, median, newest_replicaset, replicaset_readiness, restart_patch,
+        revisions, slowdown, timeline_bar, RolloutState, RESTARTABLE_KINDS,
+    },
+    serial,
+    session::{run_session, CastRecorder, RawMode, SessionInfo, SessionKind},
+    stats::ClusterStats,
+    table::ServerTable,
+    telemetry,
+    verify::Checklist,
+    wait::{WaitFor, WaitState},
+    watch::{ObjectEvent, WatchState},
+    wizard::{config_wizard, context_wizard},
+    writer::{JsonArrayWriter, JsonLinesWriter, YamlDocumentWriter},
 };
 
+type Labels = BTreeMap<String, String>;
+
+/// How often rollout status, wait and drain poll the clusters
+const ROLLOUT_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
 #[derive(Debug, Parser)]
 #[clap(version, about, long_about = None)]
 pub struct Cli {
@@ -23,56 +127,5394 @@ pub struct Cli {
     /// Namespace to fetch resources from
     #[arg(long, short, global = true)]
     pub namespace: Option<String>,
+
+    /// Kubeconfig files to use for clusters without their own, separated like KUBECONFIG
+    #[arg(long, global = true)]
+    pub kubeconfig: Option<String>,
+
+    /// User to impersonate on clusters without an impersonation of their own in the config
+    #[arg(long = "as", global = true)]
+    pub as_user: Option<String>,
+
+    /// Group to impersonate along with --as, may be given more than once
+    #[arg(long, global = true)]
+    pub as_group: Vec<String>,
+
+    /// Also target clusters marked as under maintenance in the config
+    #[arg(long, global = true)]
+    pub include_maintenance: bool,
+
+    /// Target the clusters of every clusterset instead of only the current one
+    #[arg(long, global = true)]
+    pub all_clustersets: bool,
+
+    /// Target these kubeconfig contexts, comma separated, instead of a clusterset from the config.
+    /// The config file is not needed and is left untouched
+    #[arg(
+        long,
+        global = true,
+        value_delimiter = ',',
+        conflicts_with = "all_clustersets"
+    )]
+    pub contexts: Vec<String>,
+
+    /// Output format: table, wide, json, yaml, csv, jsonl, or plugin:<name> to pipe the JSON output
+    /// to a kubemc-format-<name> executable on PATH. Defaults to the format in the config, then
+    /// table
+    #[arg(long, short, global = true, value_parser = parse_output_format, default_value = "table")]
+    pub output: OutputFormat,
+
+    /// Column to sort table and csv rows by, such as `age`
+    #[arg(long, global = true)]
+    pub sort_by: Option<String>,
+
+    /// Table and csv columns to leave out, comma separated
+    #[arg(long, global = true, value_delimiter = ',')]
+    pub hide_columns: Vec<String>,
+
+    /// Labels to show as extra columns when listing resources, comma separated
+    #[arg(long, short = 'L', global = true, value_delimiter = ',')]
+    pub label_columns: Vec<String>,
+
+    /// Disable colored output. Setting the NO_COLOR environment variable does the same
+    #[arg(long, global = true)]
+    pub no_color: bool,
+
+    /// Report how many requests were retried per cluster on stderr
+    #[arg(long, short, global = true)]
+    pub verbose: bool,
+
+    /// Print how long connecting to and listing from each cluster took on stderr, slowest
+    /// cluster first
+    #[arg(long, global = true)]
+    pub profile: bool,
+
+    /// Work on one cluster at a time instead of all at once, reporting every phase per cluster
+    /// on stderr. Helps to find the cluster that hangs or misbehaves
+    #[arg(long, global = true)]
+    pub serial: bool,
+
+    /// Print the values of Secrets in yaml, json and diffs instead of masking them
+    #[arg(long, global = true)]
+    pub show_secret_values: bool,
+
+    /// Also write what verify-cluster, wait and get --filename or --against check per cluster
+    /// as test cases for CI, given as FORMAT=PATH. Only junit is supported, as in
+    /// junit=report.xml. Other commands reject it
+    #[arg(long, global = true, value_parser = parse_report)]
+    pub report: Option<Report>,
 }
 
 #[derive(Clone, Debug, Subcommand)]
 pub enum Action {
     /// Get/List Kubernetes resources
+    ///
+    /// When a name is given the exit code reports where the object was found: 0 in every
+    /// cluster, 1 if any cluster failed or there were no clusters to ask, 2 in a subset of
+    /// clusters and 3 in no cluster. With
+    /// --filename it is 0 only when every object matches the manifest on every cluster.
     #[command(arg_required_else_help = true)]
-    Get {
-        /// Kubernetes resource (pod, node, etc)
-        resource: String,
+    Get(GetArgs),
+
+    /// Show details of an object on every cluster. Only nodes are supported so far
+    #[command(arg_required_else_help = true)]
+    Describe(DescribeArgs),
+
+    /// Scale a deployment or statefulset on every cluster
+    #[command(arg_required_else_help = true)]
+    Scale(ScaleArgs),
+
+    /// Set or remove labels of an object, or of the objects matching a selector, on every
+    /// cluster
+    #[command(arg_required_else_help = true)]
+    Label(MetadataArgs),
+
+    /// Set or remove annotations of an object, or of the objects matching a selector, on every
+    /// cluster
+    #[command(arg_required_else_help = true)]
+    Annotate(MetadataArgs),
+
+    /// Mark the matching nodes unschedulable on every cluster
+    #[command(arg_required_else_help = true)]
+    Cordon(NodeArgs),
+
+    /// Mark the matching nodes schedulable again on every cluster
+    #[command(arg_required_else_help = true)]
+    Uncordon(NodeArgs),
+
+    /// Cordon the matching nodes on every cluster and evict their pods through the eviction API,
+    /// waiting on PodDisruptionBudgets, one node of a cluster after another. Daemonset and static
+    /// pods stay, pods without a controller or with emptyDir volumes stop the drain of their
+    /// node unless allowed.
+    ///
+    /// Exits with 1 when any node was not drained.
+    #[command(arg_required_else_help = true)]
+    Drain(DrainArgs),
+
+    /// Apply the objects of a manifest to every cluster server side. Namespaces and CRDs go
+    /// first and webhooks last, and objects of a CRD in the manifest wait for each cluster to
+    /// establish it.
+    ///
+    /// Exits with 1 when any document failed on any cluster.
+    #[command(arg_required_else_help = true)]
+    Apply(ApplyArgs),
+
+    /// Edit an object from one cluster in $EDITOR and apply the change to that cluster or to
+    /// every cluster holding the object, showing a diff per cluster first
+    #[command(arg_required_else_help = true)]
+    Edit(EditArgs),
+
+    /// Print one object as yaml without status and server set metadata, asking which cluster
+    /// to take it from when several hold it
+    #[command(arg_required_else_help = true)]
+    Yaml(YamlArgs),
+
+    /// Forward local ports to a pod or service
+    ///
+    /// The target must exist on exactly one cluster unless --cluster picks one or
+    /// --per-cluster-offset forwards every match on its own local ports.
+    #[command(arg_required_else_help = true)]
+    PortForward(PortForwardArgs),
+
+    /// Show the logs of a pod, or of the pods matching a selector, on every cluster. Lines are
+    /// prefixed with their cluster, pod and container
+    #[command(arg_required_else_help = true)]
+    Logs(LogsArgs),
+
+    /// Run a command in a container, e.g. `kubemc exec web-1 -- ls /tmp`. Exits with the exit
+    /// code of the command
+    ///
+    /// The pod must exist on exactly one cluster unless --cluster picks one.
+    #[command(arg_required_else_help = true)]
+    Exec(ExecArgs),
+
+    /// Attach to the main process of a running container, showing its output and forwarding
+    /// stdin with -i
+    ///
+    /// The pod must exist on exactly one cluster unless --cluster picks one.
+    #[command(arg_required_else_help = true)]
+    Attach(SessionArgs),
+
+    /// Copy files and directories to and from a container, e.g. `kubemc cp web-1:/tmp/dump .`
+    ///
+    /// The pod must exist on exactly one cluster unless --cluster picks one. The container
+    /// needs a tar binary, as with kubectl cp.
+    #[command(arg_required_else_help = true)]
+    Cp(CpArgs),
+
+    /// Manage the rollout of a deployment on every cluster
+    #[command(subcommand)]
+    Rollout(RolloutAction),
+
+    /// Create objects on every cluster
+    #[command(subcommand)]
+    Create(CreateAction),
+
+    /// Wait until an object meets a condition on every cluster, e.g. `kubemc wait
+    /// deployment/web --for=condition=Available --timeout=5m`, showing progress per cluster
+    ///
+    /// Exits with 0 once every cluster, or with --any one of them, meets the condition and with
+    /// 1 when the timeout passes first.
+    #[command(arg_required_else_help = true)]
+    Wait(WaitArgs),
+
+    /// Audit workloads across the clusterset for common mistakes
+    #[command(subcommand)]
+    Audit(AuditAction),
+
+    /// Show per-cluster counts of nodes, namespaces, pods and deployments along with allocatable
+    /// capacity, followed by the fleet total
+    Stats {
+        #[command(subcommand)]
+        action: Option<StatsAction>,
+    },
+
+    /// Show the changes kubemc made to clusters, such as applies, scales and drains, with their
+    /// outcome per cluster. They are recorded in ~/.kube/kubemc-history.jsonl
+    History {
+        /// Only changes made within this long, e.g. 1h or 7d
+        #[arg(long, value_parser = parse_age_arg)]
+        since: Option<chrono::Duration>,
+
+        /// Only changes made to this cluster
+        #[arg(long)]
+        cluster: Option<String>,
+    },
+
+    /// Show the API resources served across the clusterset and which clusters lack them
+    ApiResources {
+        /// Ignore the kubemc discovery cache and re-run discovery against every cluster
+        #[arg(long)]
+        refresh: bool,
+
+        /// Only show resources missing from at least one cluster
+        #[arg(long)]
+        missing: bool,
+    },
+
+    /// Show or clean the discovery, index, completion and credential caches kept under
+    /// ~/.kube/cache. They are also pruned to 256MiB, oldest first, once a day
+    #[command(subcommand)]
+    Cache(CacheAction),
+
+    /// Show the current holders of the leader election leases in the namespace on every
+    /// cluster, such as those of controllers in kube-system, along with when they last renewed
+    Leaders,
+
+    /// List the API services of every cluster with whether they are available, showing which
+    /// cluster has a broken aggregated API such as metrics. Exits with 1 when any is unavailable
+    ApiServices {
+        /// Only show API services that are not available
+        #[arg(long)]
+        unavailable: bool,
+    },
+
+    /// Watch the backends of a service on every cluster, keeping a count of its ready, not ready
+    /// and terminating endpoints per cluster up to date, to follow traffic failing over between
+    /// regions during a drill. Runs until interrupted
+    FailoverWatch {
+        /// Service to watch, as NAME, service/NAME or NAMESPACE/service/NAME
+        service: String,
+    },
+
+    /// Run the checks of a checklist against a cluster before it joins the fleet, reporting
+    /// pass or fail per check. Exits with 1 when a check fails
+    VerifyCluster {
+        /// Cluster of the current clusterset, or a kubeconfig context
+        cluster: String,
+
+        /// Checklist of checks to run
+        #[arg(long, short)]
+        filename: PathBuf,
+    },
+
+    /// Keep a local index of object names for `kubemc find` and shell completion
+    #[command(subcommand)]
+    Index(IndexAction),
+
+    /// Search the names in the local index without contacting the clusters. Exits with 1 when
+    /// nothing matches
+    #[command(arg_required_else_help = true)]
+    Find {
+        /// Text the object names contain
+        pattern: String,
+
+        /// Only objects of this kind, such as pods or deploy
+        #[arg(long, short)]
+        kind: Option<String>,
+    },
+
+    /// Print a shell completion script, e.g. `source <(kubemc completion bash)`. Object names
+    /// are completed from the clusters of the active clusterset and namespace
+    Completion {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+
+    /// Build a config interactively by picking kubeconfig contexts for each clusterset, then
+    /// write it to the config path
+    GenerateConfig {
+        /// Print the config instead of writing it
+        #[arg(long)]
+        dry_run: bool,
 
-        /// Name of resource
-        name: Option<String>,
+        /// Print an example config without asking anything
+        #[arg(long, conflicts_with = "dry_run")]
+        template: bool,
     },
 
-    /// Generates an example config
-    GenerateConfig,
+    /// Inspect the kubemc config
+    #[command(subcommand)]
+    Config(ConfigAction),
+
+    /// Pins commands to a single cluster of the active clusterset until the pin is cleared.
+    /// Without arguments the pinned cluster is shown
+    Pin {
+        /// Cluster to pin
+        cluster: Option<String>,
+
+        /// Remove the pin so commands target the whole clusterset again
+        #[arg(long, conflicts_with = "cluster")]
+        clear: bool,
+    },
+
+    /// Disable clusters of the current clusterset for a while without removing them from the
+    /// config, or list the clusters and whether they are enabled
+    #[command(subcommand)]
+    Cluster(ClusterAction),
+
+    /// Changes the configured namespace in kubemc config. Without a namespace, lists the
+    /// namespaces on the clusterset and which clusters have them
+    Namespace {
+        namespace: Option<String>,
+
+        /// Choose the namespace from those on the clusterset
+        #[arg(long, short, conflicts_with = "namespace")]
+        interactive: bool,
+
+        /// Offer to create the namespace on clusters where it does not exist
+        #[arg(long)]
+        create_missing: bool,
+
+        /// Create missing namespaces without prompting
+        #[arg(long, short, requires = "create_missing")]
+        yes: bool,
+    },
+
+    /// Commands for working on kubemc itself
+    #[cfg(feature = "dev")]
+    #[command(subcommand)]
+    Dev(DevAction),
+}
+
+#[cfg(feature = "dev")]
+#[derive(Clone, Debug, Subcommand)]
+pub enum DevAction {
+    /// Run the end-to-end suite: create two kind clusters and a clusterset of them, then check
+    /// apply, get, list, watch and deletions across both. Needs kind and a container runtime
+    E2e {
+        /// Leave the kind clusters running after the suite. The next run reuses them
+        #[arg(long)]
+        keep_clusters: bool,
+    },
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum IndexAction {
+    /// List the names of workloads, pods, services, config, namespaces and nodes on every
+    /// cluster, metadata only, and store them locally
+    Build {
+        /// Keep running and rebuild the index this often, e.g. 15m
+        #[arg(long, value_parser = parse_age_arg)]
+        every: Option<chrono::Duration>,
+    },
 
+    /// Print the names of a resource in the namespace that start with a prefix, one per line,
+    /// for shell completion. Names are listed from the clusters when the last listing is over a
+    /// minute old, falling back to the index for clusters that do not answer quickly
     #[command(arg_required_else_help = true)]
-    /// Changes the configured namespace in kubemc config
-    Namespace { namespace: String },
+    Names {
+        /// Resource such as pods or deploy
+        resource: String,
+
+        /// Start of the names to print
+        #[arg(default_value = "")]
+        prefix: String,
+    },
 }
 
-impl Cli {
-    pub async fn get(&self, resource: &str, _name: &Option<String>) -> Result<()> {
-        let config = Config::load_config(self.config_file.as_ref())?;
-        let clusterset = config.active_clusterset()?;
-        let mut ns = config.active_namespace()?;
-        if let Some(namespace) = &self.namespace {
-            ns = namespace.to_owned()
+#[derive(Clone, Debug, Subcommand)]
+pub enum ClusterAction {
+    /// Show the clusters of the current clusterset and whether commands target them
+    List,
+
+    /// Leave a cluster out of every command until it is enabled again
+    Disable {
+        /// Cluster of the current clusterset
+        cluster: String,
+    },
+
+    /// Target a disabled cluster again
+    Enable {
+        /// Cluster of the current clusterset
+        cluster: String,
+    },
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum CacheAction {
+    /// Show what is cached per cluster, its size and how stale it is
+    Info,
+
+    /// Remove cached entries, all of them unless --older-than is given
+    Clean {
+        /// Only remove entries last written at least this long ago, e.g. 7d
+        #[arg(long, value_parser = parse_age_arg)]
+        older_than: Option<chrono::Duration>,
+    },
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum StatsAction {
+    /// Show daily latency percentiles of kubemc commands, recorded when `telemetry: true` is set
+    /// in the config
+    #[command(name = "self")]
+    Telemetry {
+        /// Number of days to show
+        #[arg(long, default_value_t = 30)]
+        days: i64,
+    },
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum AuditAction {
+    /// Report containers pulling by digest with `imagePullPolicy: Always`, using `latest` or
+    /// untagged images, or pulling from a private registry without any pull secret on the pod
+    /// or its service account. Exits with 1 when anything is found
+    ImagePull {
+        /// Audit every namespace instead of the configured one
+        #[arg(long, short = 'A')]
+        all_namespaces: bool,
+    },
+
+    /// Report privileged containers, pods using the host network or PID namespace and
+    /// containers without `runAsNonRoot`, summarized per namespace. Exits with 1 when anything
+    /// is found
+    Security {
+        /// Audit every namespace instead of the configured one
+        #[arg(long, short = 'A')]
+        all_namespaces: bool,
+    },
+
+    /// Check on which clusters a deployment's tolerations, node selector and required node
+    /// affinity leave no node to run on. Exits with 1 when some cluster has no such node or no
+    /// such deployment
+    Scheduling {
+        /// Deployment as NAME, deployment/NAME or NAMESPACE/deployment/NAME
+        deployment: String,
+    },
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum ConfigAction {
+    /// Check the config for mistakes and that it matches the kubeconfig
+    Validate,
+
+    /// Rewrite the config in the current apiVersion, keeping the original as <config>.bak
+    Migrate,
+
+    /// Add a cluster to a clusterset once it connects, reports its server version and serves
+    /// discovery. Without --context or --server the kubeconfig contexts are listed to pick from
+    ///
+    /// Exits with 1, leaving the config unchanged, when any of the checks fails.
+    AddCluster(AddClusterArgs),
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct AddClusterArgs {
+    /// Name of the cluster in kubemc output, defaults to its context
+    pub name: Option<String>,
+
+    /// Kubeconfig context to connect to the cluster through
+    #[arg(long)]
+    pub context: Option<String>,
+
+    /// API server to connect to directly instead of through a kubeconfig
+    #[arg(long, conflicts_with = "context", requires_all = ["name", "token_file"])]
+    pub server: Option<String>,
+
+    /// File holding the bearer token sent to --server
+    #[arg(long, requires = "server")]
+    pub token_file: Option<String>,
+
+    /// CA certificate file --server is verified with, defaults to the system roots
+    #[arg(long, requires = "server")]
+    pub certificate_authority: Option<String>,
+
+    /// Clusterset to add the cluster to, defaults to the current one
+    #[arg(long)]
+    pub clusterset: Option<String>,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct GetArgs {
+    /// Kubernetes resource (pod, node, etc). Multiple resources may be comma separated, or a
+    /// single object may be named as KIND/NAME
+    #[arg(required_unless_present = "filename")]
+    pub resource: Option<String>,
+
+    /// Name of resource
+    pub name: Option<String>,
+
+    /// Write yaml output to this file instead of stdout. Only applies to listing with -o yaml
+    #[arg(long, conflicts_with_all = ["name", "watch"])]
+    pub output_file: Option<PathBuf>,
+
+    /// Fail once more than this many objects have been written in yaml output. Only applies to
+    /// listing with -o yaml
+    #[arg(
+        long,
+        conflicts_with_all = ["name", "watch", "summarize", "group_by", "against", "filename"]
+    )]
+    pub max_objects: Option<usize>,
+
+    /// Number of objects requested per page from each cluster when listing
+    #[arg(long, default_value_t = DEFAULT_PAGE_SIZE)]
+    pub page_size: u32,
+
+    /// Stop listing a kind on a cluster after this many objects, noting how many were left out
+    #[arg(
+        long,
+        default_value_t = DEFAULT_MAX_OBJECTS_PER_CLUSTER,
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    pub max_objects_per_cluster: u32,
+
+    /// Cache lists and reuse the objects a cluster listed within this long, e.g. 30s or 5m,
+    /// instead of listing them again. Caching is opt-in and replaces a --no-cache flag: without
+    /// --cache-ttl every cluster is listed again and nothing is cached. Secrets are never cached
+    /// and --exit-on-notready always lists again
+    #[arg(long, value_parser = parse_age_arg)]
+    pub cache_ttl: Option<chrono::Duration>,
+
+    /// Only list objects matching this label selector, e.g. app=web
+    #[arg(long, short = 'l')]
+    pub selector: Option<String>,
+
+    /// Only list objects matching this field selector, which the API servers apply before
+    /// sending anything, e.g. status.phase=Running or spec.nodeName=ip-10-0-3-17
+    #[arg(long)]
+    pub field_selector: Option<String>,
+
+    /// After listing, watch for changes and print them as they happen. Changes to the kubemc
+    /// config and kubeconfig files are picked up without restarting
+    #[arg(long, short)]
+    pub watch: bool,
+
+    /// When watching, print the changed fields of modified objects instead of the whole row
+    #[arg(long, requires = "watch")]
+    pub show_changes: bool,
+
+    /// Also show objects owned by the named object, such as the ReplicaSets and Pods of a
+    /// Deployment
+    #[arg(long)]
+    pub with_children: bool,
+
+    /// Only objects created within this long, e.g. 1h or 2d
+    #[arg(long, value_parser = parse_age_arg, conflicts_with = "watch")]
+    pub since: Option<chrono::Duration>,
+
+    /// Only objects created at least this long ago, e.g. 30d
+    #[arg(long, value_parser = parse_age_arg, conflicts_with = "watch")]
+    pub older_than: Option<chrono::Duration>,
+
+    /// Group pods under the workload owning them, such as their Deployment, per cluster
+    #[arg(
+        long,
+        value_enum,
+        conflicts_with_all = ["name", "watch", "with_children", "output_file"]
+    )]
+    pub group_by: Option<GroupBy>,
+
+    /// Only show addresses of this IP family in the address columns of pods, services and
+    /// ingresses
+    #[arg(long, value_enum)]
+    pub ip_family: Option<IpFamily>,
+
+    /// List the taints of nodes in an extra column of wide output
+    #[arg(long)]
+    pub show_taints: bool,
+
+    /// Split tables into a section per cluster, headed by the cluster and its object count,
+    /// instead of interleaving the rows of all clusters
+    #[arg(
+        long,
+        conflicts_with_all = ["watch", "group_by", "summarize", "server_columns"]
+    )]
+    pub chunk_output: bool,
+
+    /// Print per-cluster counts grouped by status instead of every object
+    #[arg(
+        long,
+        conflicts_with_all = ["watch", "with_children", "output_file", "group_by"]
+    )]
+    pub summarize: bool,
+
+    /// Let the API servers render the table columns through the server-side Table protocol,
+    /// giving every kind the columns kubectl shows. Only applies to table, wide and csv output
+    #[arg(
+        long,
+        conflicts_with_all = ["watch", "with_children", "output_file", "group_by", "summarize"]
+    )]
+    pub server_columns: bool,
+
+    /// Compare the objects to a baseline on each cluster instead of listing them, reporting
+    /// which fields were changed out of band. Exits with 1 when any object drifted
+    #[arg(
+        long,
+        value_enum,
+        conflicts_with_all = [
+            "watch", "with_children", "output_file", "group_by", "summarize", "server_columns"
+        ]
+    )]
+    pub against: Option<Baseline>,
+
+    /// Exit with 1 when a listed pod is not ready and has not completed, a listed node is not
+    /// Ready or a cluster could not be listed, for health checks in scripts and CI
+    #[arg(
+        long,
+        conflicts_with_all = [
+            "watch", "with_children", "group_by", "server_columns", "against", "filename"
+        ]
+    )]
+    pub exit_on_notready: bool,
+
+    /// Check every object of this manifest against the clusters, reporting whether it is
+    /// missing, differs from the manifest or matches it. Nothing is changed
+    #[arg(
+        long,
+        short,
+        conflicts_with_all = [
+            "resource", "output_file", "watch", "with_children", "since", "older_than", "summarize",
+            "group_by"
+        ]
+    )]
+    pub filename: Option<PathBuf>,
+}
+
+impl GetArgs {
+    fn age_window(&self) -> AgeWindow {
+        AgeWindow {
+            since: self.since,
+            older_than: self.older_than,
+        }
+    }
+
+    // Table rows of a list page, with addresses of other IP families dropped when filtering
+    fn rows(&self, lr: ListResponse) -> Vec<KubeOutput> {
+        let mut rows = convert_list_response_to_table(lr);
+        if let Some(family) = self.ip_family {
+            rows.iter_mut().for_each(|row| row.retain_ip_family(family));
         }
-        let client = Client::try_new(&clusterset.clusters, &ns, resource).await?;
-        let lrs = client.list().await?;
+        rows
+    }
 
-        let mut outputs = Vec::new();
+    // Whether a page holds an object that is not ready, only checked with --exit-on-notready
+    fn not_ready(&self, lr: &ListResponse) -> bool {
+        self.exit_on_notready
+            && lr.object_list.iter().any(|object| {
+                let formatter = Formatter::of(&lr.api_version, &lr.kind);
+                formatter.format(&lr.clustername, object.clone()).ready() == Some(false)
+            })
+    }
 
-        for lr in lrs {
-            outputs.append(&mut convert_list_response_to_table(lr))
+    // Exit code of a listing, failing with --exit-on-notready when something was not ready or
+    // a cluster could not be listed
+    fn list_exit_code(&self, not_ready: bool, failed: bool) -> ExitCode {
+        if self.exit_on_notready && (not_ready || failed) {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
         }
-        create_table(outputs);
-        Ok(())
     }
 
-    pub async fn generate_config(&self) -> Result<()> {
-        let config_yaml = Config::yaml()?;
-        io::stdout().write(config_yaml.as_bytes()).map(|_| Ok(()))?
+    // How long lists are reused from the response cache, None to leave it alone. Health checks
+    // never reuse lists
+    fn cache_ttl(&self) -> Option<Duration> {
+        let ttl = self.cache_ttl?.to_std().unwrap_or_default();
+        Some(if self.exit_on_notready {
+            Duration::ZERO
+        } else {
+            ttl
+        })
+    }
+
+    // Narrows the listings and watches of a client to the objects matching the selectors
+    fn select(&self, mut client: Client) -> Client {
+        if let Some(selector) = &self.selector {
+            client = client.with_selector(selector);
+        }
+        if let Some(selector) = &self.field_selector {
+            client = client.with_field_selector(selector);
+        }
+        client
+    }
+
+    // Lists the requested objects page by page up to the object cap, dropping objects outside
+    // the age window. Objects only shown as tables are trimmed of what tables never show
+    fn list(
+        &self,
+        client: Client,
+        trim: bool,
+    ) -> impl Stream<Item = std::result::Result<ListResponse, ListFailure>> {
+        let window = self.age_window();
+        let now = Utc::now();
+        let mut client = client.with_max_objects(self.max_objects_per_cluster as usize);
+        if let Some(ttl) = self.cache_ttl() {
+            client = client.with_response_cache(ttl);
+        }
+        if trim {
+            client = client.with_trimming();
+        }
+        client.list_paged(self.page_size).map(move |page| {
+            page.map(|mut lr| {
+                if let Some(age) = lr.cached {
+                    note_cached(&lr.clustername, &lr.kind, age);
+                }
+                if let Some(truncated) = lr.truncated {
+                    note_truncated(&lr.clustername, &lr.kind, truncated);
+                }
+                if !window.is_empty() {
+                    lr.object_list.items.retain(|object| {
+                        let created = object.creation_timestamp().map(|time| time.0);
+                        window.contains(created, now)
+                    });
+                }
+                lr
+            })
+        })
+    }
+
+    /// The resource and optional name, accepting both `KIND NAME` and `KIND/NAME`
+    pub fn resource_and_name(&self) -> Result<(String, Option<String>)> {
+        let resource = self
+            .resource
+            .as_deref()
+            .ok_or_else(|| anyhow!("a resource is required"))?;
+        let (resource, name) = resource_and_name(resource, self.name.as_deref())?;
+        if name.is_some() && self.watch {
+            return Err(anyhow!("--watch cannot be used with a name"));
+        }
+        if name.is_none() && self.with_children {
+            return Err(anyhow!("--with-children requires a name"));
+        }
+        if name.is_some() && self.summarize {
+            return Err(anyhow!("--summarize cannot be used with a name"));
+        }
+        if name.is_some() && self.exit_on_notready {
+            return Err(anyhow!("--exit-on-notready cannot be used with a name"));
+        }
+        if name.is_some() && (self.output_file.is_some() || self.max_objects.is_some()) {
+            return Err(anyhow!(
+                "--output-file and --max-objects cannot be used with a name"
+            ));
+        }
+        if name.is_some() && (self.selector.is_some() || self.field_selector.is_some()) {
+            return Err(anyhow!(
+                "--selector and --field-selector cannot be used with a name"
+            ));
+        }
+        if name.is_some() && !self.age_window().is_empty() {
+            return Err(anyhow!(
+                "--since and --older-than cannot be used with a name"
+            ));
+        }
+        self.age_window().validate()?;
+        Ok((resource, name))
+    }
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct DescribeArgs {
+    /// Kubernetes resource to describe, optionally as KIND/NAME
+    pub resource: String,
+
+    /// Name of resource
+    pub name: Option<String>,
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum CreateAction {
+    /// Run a cronjob now by creating a job from it on every cluster, e.g. `kubemc create job
+    /// --from=cronjob/backup --wait`
+    ///
+    /// Exits with 1 when the job could not be created on a cluster or, with --wait, did not
+    /// complete on every cluster in time.
+    #[command(arg_required_else_help = true)]
+    Job(CreateJobArgs),
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct CreateJobArgs {
+    /// Name of the job. Defaults to <cronjob>-manual-<unix time>
+    pub name: Option<String>,
+
+    /// Cronjob to create the job from, as cronjob/NAME
+    #[arg(long)]
+    pub from: String,
+
+    /// Only create the job on this cluster
+    #[arg(long)]
+    pub cluster: Option<String>,
+
+    /// Wait for the job to complete on every cluster, showing progress
+    #[arg(long)]
+    pub wait: bool,
+
+    /// How long to wait with --wait before giving up, e.g. 30m
+    #[arg(long, value_parser = parse_age_arg, default_value = "10m")]
+    pub timeout: chrono::Duration,
+}
+
+#[derive(Clone, Debug, Subcommand)]
+pub enum RolloutAction {
+    /// Wait for the rollout of a deployment to finish on every cluster, showing progress
+    #[command(arg_required_else_help = true)]
+    Status {
+        #[command(flatten)]
+        target: RolloutTarget,
+
+        /// Seconds to wait before giving up on clusters that are still progressing
+        #[arg(long, default_value_t = 300)]
+        timeout: u64,
+    },
+
+    /// Restart the pods of a deployment, statefulset or daemonset on every cluster
+    #[command(arg_required_else_help = true)]
+    Restart {
+        #[command(flatten)]
+        target: RolloutTarget,
+
+        #[command(flatten)]
+        rolling: RollingArgs,
+    },
+
+    /// Show the revisions of a deployment on every cluster
+    #[command(arg_required_else_help = true)]
+    History {
+        #[command(flatten)]
+        target: RolloutTarget,
+    },
+
+    /// Show when each pod of the latest rollout of a deployment became ready on every cluster,
+    /// on a timeline shared by all clusters, and flag clusters where pods take much longer to
+    /// become ready than across the fleet
+    #[command(arg_required_else_help = true)]
+    Timeline {
+        #[command(flatten)]
+        target: RolloutTarget,
+
+        /// Width of the timeline column in characters
+        #[arg(long, default_value_t = 40)]
+        width: usize,
+    },
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct RolloutTarget {
+    /// Kubernetes resource, optionally as KIND/NAME or NAMESPACE/KIND/NAME
+    pub resource: String,
+
+    /// Name of resource
+    pub name: Option<String>,
+}
+
+impl RolloutTarget {
+    fn object(&self) -> Result<ObjectRef> {
+        ObjectRef::parse(&self.resource, self.name.as_deref())
+    }
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct WaitArgs {
+    /// Kubernetes resource to wait on, optionally as KIND/NAME or NAMESPACE/KIND/NAME
+    pub resource: String,
+
+    /// Name of resource
+    pub name: Option<String>,
+
+    /// Condition to wait for: condition=TYPE, condition=TYPE=STATUS or delete
+    #[arg(long = "for")]
+    pub condition: WaitFor,
+
+    /// How long to wait before giving up, e.g. 30s or 5m
+    #[arg(long, value_parser = parse_age_arg, default_value = "30s")]
+    pub timeout: chrono::Duration,
+
+    /// Return as soon as one cluster meets the condition
+    #[arg(long)]
+    pub any: bool,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct ScaleArgs {
+    /// Kubernetes resource to scale (deployment, statefulset), optionally as KIND/NAME or
+    /// NAMESPACE/KIND/NAME
+    pub resource: String,
+
+    /// Name of resource
+    pub name: Option<String>,
+
+    /// Desired number of replicas
+    #[arg(long)]
+    pub replicas: i32,
+
+    /// Only scale on clusters where the current replica count matches
+    #[arg(long)]
+    pub current_replicas: Option<i32>,
+
+    /// Comma separated subset of clusters in the clusterset to scale on
+    #[arg(long, value_delimiter = ',')]
+    pub clusters: Vec<String>,
+
+    #[command(flatten)]
+    pub rolling: RollingArgs,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct MetadataArgs {
+    /// Kubernetes resource, optionally as KIND/NAME or NAMESPACE/KIND/NAME
+    pub resource: String,
+
+    /// Name of the object unless given with the resource or replaced by --selector, followed by
+    /// KEY=VALUE to set a key and KEY- to remove it
+    #[arg(required = true, value_name = "[NAME] CHANGES")]
+    pub changes: Vec<String>,
+
+    /// Change the objects matching this label selector instead of a named object, e.g. app=web
+    #[arg(long, short = 'l')]
+    pub selector: Option<String>,
+
+    /// Replace keys that are already set to another value
+    #[arg(long)]
+    pub overwrite: bool,
+
+    /// Comma separated subset of clusters in the clusterset to change objects on
+    #[arg(long, value_delimiter = ',')]
+    pub clusters: Vec<String>,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct NodeArgs {
+    /// Names of the nodes, in which `*` matches any run of characters as in `ip-10-0-*`
+    #[arg(required_unless_present = "selector")]
+    pub nodes: Vec<String>,
+
+    /// Select the nodes by label selector, e.g. node.kubernetes.io/instance-type=m5.large
+    #[arg(long, short = 'l')]
+    pub selector: Option<String>,
+
+    /// Comma separated subset of clusters in the clusterset to change nodes on
+    #[arg(long, value_delimiter = ',')]
+    pub clusters: Vec<String>,
+
+    #[command(flatten)]
+    pub rolling: RollingArgs,
+}
+
+impl NodeArgs {
+    // Names of the nodes of a cluster matching both the names and the selector
+    async fn matching_nodes(&self, client: KubeClient) -> Result<Vec<String>> {
+        let mut params = ListParams::default();
+        if let Some(selector) = &self.selector {
+            params = params.labels(selector);
+        }
+        Ok(Api::<Node>::all(client)
+            .list(&params)
+            .await?
+            .items
+            .iter()
+            .map(ResourceExt::name_any)
+            .filter(|name| {
+                self.nodes.is_empty()
+                    || self
+                        .nodes
+                        .iter()
+                        .any(|pattern| matches_pattern(pattern, name))
+            })
+            .collect())
+    }
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct DrainArgs {
+    #[command(flatten)]
+    pub nodes: NodeArgs,
+
+    /// Evict pods not managed by a controller, which are not recreated anywhere
+    #[arg(long)]
+    pub force: bool,
+
+    /// Evict pods with emptyDir volumes, losing their data
+    #[arg(long)]
+    pub delete_emptydir_data: bool,
+
+    /// How long to wait for the pods of each node to be evicted, e.g. 90s or 10m
+    #[arg(long, value_parser = parse_age_arg, default_value = "5m")]
+    pub timeout: chrono::Duration,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct ApplyArgs {
+    /// Manifest of one or more yaml documents to apply
+    #[arg(long, short)]
+    pub filename: PathBuf,
+
+    /// How long to wait for each cluster to establish the CRDs of the manifest, e.g. 30s or 2m
+    #[arg(long, value_parser = parse_age_arg, default_value = "60s")]
+    pub crd_timeout: chrono::Duration,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct EditArgs {
+    /// Kubernetes resource to edit, optionally as KIND/NAME or NAMESPACE/KIND/NAME
+    pub resource: String,
+
+    /// Name of resource
+    pub name: Option<String>,
+
+    /// Cluster to fetch the object from. Defaults to the first cluster holding it
+    #[arg(long)]
+    pub cluster: Option<String>,
+
+    /// Where to apply the change instead of asking
+    #[arg(long, value_enum)]
+    pub propagate: Option<Propagation>,
+
+    /// Apply the change without confirming the diff
+    #[arg(long, short)]
+    pub yes: bool,
+
+    #[command(flatten)]
+    pub rolling: RollingArgs,
+}
+
+/// How a change is rolled out across the clusters
+#[derive(Clone, Debug, Default, Args)]
+pub struct RollingArgs {
+    /// Change one cluster after another in config order instead of all at once, stopping at
+    /// the first cluster where the change fails
+    #[arg(long)]
+    pub one_cluster_at_a_time: bool,
+
+    /// Ask before moving on to the next cluster. Requires --one-cluster-at-a-time
+    #[arg(long, requires = "one_cluster_at_a_time")]
+    pub confirm_each: bool,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct YamlArgs {
+    /// Kubernetes resource to print, optionally as KIND/NAME or NAMESPACE/KIND/NAME
+    pub resource: String,
+
+    /// Name of resource
+    pub name: Option<String>,
+
+    /// Cluster to take the object from instead of asking
+    #[arg(long)]
+    pub cluster: Option<String>,
+}
+
+/// Shells `kubemc completion` writes scripts for
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shell {
+    Bash,
+}
+
+/// How `get --group-by` groups rows
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GroupBy {
+    /// The Deployment, StatefulSet, DaemonSet or other controller owning a pod
+    Owner,
+}
+
+/// What `get --against` compares objects to
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Baseline {
+    /// The configuration recorded by `kubectl apply` in the
+    /// kubectl.kubernetes.io/last-applied-configuration annotation
+    LastApplied,
+}
+
+/// Which clusters an edit is applied to
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Propagation {
+    /// Only the cluster the object was edited from
+    Cluster,
+    /// Every cluster holding the object
+    All,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct PortForwardArgs {
+    /// Pod or service to forward to, as pod/NAME, svc/NAME or a pod name
+    pub target: Target,
+
+    /// Ports to forward as LOCAL:REMOTE, or PORT to use the same port locally. Service targets
+    /// take service ports
+    #[arg(required = true)]
+    pub ports: Vec<PortMapping>,
+
+    /// Cluster to forward from when the target exists on several clusters
+    #[arg(long, conflicts_with = "per_cluster_offset")]
+    pub cluster: Option<String>,
+
+    /// Forward from every cluster with the target, shifting local ports by this much per
+    /// cluster in cluster name order
+    #[arg(long)]
+    pub per_cluster_offset: Option<u16>,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct LogsArgs {
+    /// Pod to show the logs of, as NAME or pod/NAME
+    #[arg(
+        required_unless_present = "selector",
+        conflicts_with = "selector",
+        value_parser = parse_pod_arg
+    )]
+    pub pod: Option<String>,
+
+    /// Show the logs of the pods matching this label selector, e.g. app=web
+    #[arg(long, short = 'l')]
+    pub selector: Option<String>,
+
+    /// Only show the logs of this container
+    #[arg(long)]
+    pub container: Option<String>,
+
+    /// Keep streaming new lines until interrupted
+    #[arg(long, short)]
+    pub follow: bool,
+
+    /// Number of recent lines to show per container, all lines by default
+    #[arg(long)]
+    pub tail: Option<i64>,
+
+    /// Most lines per second shown per container. Further lines are dropped and counted per
+    /// cluster, with the counts printed on exit
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    pub rate_limit: Option<u32>,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct ExecArgs {
+    #[command(flatten)]
+    pub session: SessionArgs,
+
+    /// Command to run and its arguments, given after --
+    #[arg(last = true, required = true)]
+    pub command: Vec<String>,
+}
+
+/// The container of an exec or attach session and how the session is run
+#[derive(Clone, Debug, Args)]
+pub struct SessionArgs {
+    /// Pod to run in, as NAME or pod/NAME
+    #[arg(value_parser = parse_pod_arg)]
+    pub pod: String,
+
+    /// Cluster of the pod, required when the pod exists on several clusters
+    #[arg(long)]
+    pub cluster: Option<String>,
+
+    /// Container to run in, defaults to the first container of the pod
+    #[arg(long)]
+    pub container: Option<String>,
+
+    /// Pass stdin to the container
+    #[arg(long, short = 'i')]
+    pub stdin: bool,
+
+    /// Allocate a TTY in the container and put the local terminal in raw mode, for shells and
+    /// other interactive programs. Requires -i
+    #[arg(long, short = 't', requires = "stdin")]
+    pub tty: bool,
+
+    /// Record the session to this new file in the asciicast v2 format of asciinema, along with
+    /// the cluster, namespace and pod it ran in. Only output is recorded unless --record-input
+    /// is given
+    #[arg(long)]
+    pub record_session: Option<PathBuf>,
+
+    /// Also record what is typed into the session, which may include passwords
+    #[arg(long, requires = "record_session")]
+    pub record_input: bool,
+}
+
+#[derive(Clone, Debug, Args)]
+pub struct CpArgs {
+    /// Local path or POD:PATH to copy from
+    pub source: CopyPath,
+
+    /// Local path or POD:PATH to copy to
+    pub destination: CopyPath,
+
+    /// Cluster of the pod, required when the pod exists on several clusters
+    #[arg(long)]
+    pub cluster: Option<String>,
+
+    /// Container to copy to or from, defaults to the first container of the pod
+    #[arg(long)]
+    pub container: Option<String>,
+}
+
+impl Cli {
+    /// Whether statuses should be colored, only when writing to a terminal and not disabled
+    pub fn color_enabled(&self) -> bool {
+        let no_color = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+        !self.no_color && !no_color && io::stdout().is_terminal()
+    }
+
+    /// Applies the output settings from the config to whatever was not given on the command line
+    pub fn apply_output_settings(&mut self, matches: &ArgMatches) {
+        let config = match Config::load_config(self.config_file.as_ref()) {
+            Ok(config) => config.output_settings(),
+            Err(e) => {
+                debug!("no output settings from config: {}", e);
+                OutputSettings::default()
+            }
+        };
+        let flags = OutputSettings {
+            format: (matches.value_source("output") == Some(ValueSource::CommandLine))
+                .then_some(self.output),
+            label_columns: self.label_columns.clone(),
+            hide_columns: self.hide_columns.clone(),
+            sort_by: self.sort_by.clone(),
+        };
+        let settings = flags.or(&config);
+        self.output = settings.format.unwrap_or_default();
+        if let Some(name) = matches
+            .get_raw("output")
+            .and_then(|mut raw| raw.next())
+            .and_then(|raw| plugin_name(raw.to_str()?))
+        {
+            set_output_plugin(name);
+        }
+        set_table_settings(TableSettings {
+            label_columns: settings.label_columns,
+            hide_columns: settings.hide_columns,
+            sort_by: settings.sort_by,
+        });
+    }
+
+    // Loads the active clusterset along with the namespace to use, preferring the --namespace flag
+    fn active_clusterset(&self) -> Result<(Clusterset, String)> {
+        // --contexts builds its clusterset from the kubeconfig alone
+        let config = if self.contexts.is_empty() {
+            Config::load_config(self.config_file.as_ref())?
+        } else {
+            Config::default()
+        };
+        let mut clusterset = self.clusterset(&config)?;
+        let mut ns = clusterset.namespace.clone();
+        if let Some(namespace) = &self.namespace {
+            ns = namespace.to_owned();
+            clusterset.clear_cluster_namespaces();
+        }
+        Ok((clusterset, ns))
+    }
+
+    // Loads the active clusterset for a command on a single object, along with the namespace to
+    // use, preferring the one named by the object
+    fn object_clusterset(&self, object: ObjectRef) -> Result<(Clusterset, String, ObjectRef)> {
+        if let (Some(flag), Some(inline)) = (&self.namespace, &object.namespace) {
+            if flag != inline {
+                return Err(anyhow!(
+                    "{} names namespace {} but --namespace is {}",
+                    object,
+                    inline,
+                    flag
+                ));
+            }
+        }
+        let (mut clusterset, ns) = self.active_clusterset()?;
+        if object.namespace.is_some() {
+            clusterset.clear_cluster_namespaces();
+        }
+        let ns = object.namespace_or(&ns);
+        Ok((clusterset, ns, object))
+    }
+
+    // The clusterset commands target, narrowed to the clusters they should touch
+    fn clusterset(&self, config: &Config) -> Result<Clusterset> {
+        let mut clusterset = if !self.contexts.is_empty() {
+            Clusterset::from_contexts(&self.contexts)?
+        } else if self.all_clustersets {
+            config.all_clustersets()?
+        } else {
+            config.active_clusterset()?.clone()
+        };
+        self.prepare_clusterset(&mut clusterset)?;
+        telemetry::set_cluster_count(clusterset.clusters.len());
+        Ok(clusterset)
+    }
+
+    // Narrows the clusterset to the clusters commands should touch, points clusters without a
+    // kubeconfig of their own at the --kubeconfig files and, across clustersets, marks clusters
+    // that are the same
+    fn prepare_clusterset(&self, clusterset: &mut Clusterset) -> Result<()> {
+        clusterset.apply_pin()?;
+        if let Some(pinned) = &clusterset.pinned_cluster {
+            eprintln!(
+                "{}: pinned, other clusters skipped until `kubemc pin --clear`",
+                pinned
+            );
+        }
+        for cluster in clusterset.skip_disabled() {
+            eprintln!("{}: skipped <disabled>", cluster);
+        }
+        if clusterset.clusters.is_empty() {
+            return Err(anyhow!(
+                "every cluster is disabled, enable one with `kubemc cluster enable`"
+            ));
+        }
+        if !self.include_maintenance {
+            for cluster in clusterset.skip_maintenance(Utc::now()) {
+                eprintln!("{}: skipped <maintenance>", cluster);
+            }
+            if clusterset.clusters.is_empty() {
+                return Err(anyhow!(
+                    "every cluster is under maintenance, use --include-maintenance to target them"
+                ));
+            }
+        }
+        for cluster in &mut clusterset.clusters {
+            self.apply_cluster_flags(cluster);
+        }
+        if self.all_clustersets {
+            clusterset.coalesce();
+        }
+        Ok(())
+    }
+
+    /// Fails for a --report given to a command that checks nothing to report on, rather than
+    /// leaving CI without the report it asked for
+    pub fn check_report(&self) -> Result<()> {
+        let reports = match &self.action {
+            Action::Get(args) => args.filename.is_some() || args.against.is_some(),
+            Action::Wait(_) | Action::VerifyCluster { .. } => true,
+            _ => false,
+        };
+        if self.report.is_some() && !reports {
+            return Err(anyhow!(
+                "--report only applies to verify-cluster, wait and get --filename or --against"
+            ));
+        }
+        Ok(())
+    }
+
+    // Writes what a command checked to the --report file, when one is given
+    fn write_report(&self, command: &str, cases: &[TestCase]) -> Result<()> {
+        match &self.report {
+            Some(report) => report.write(command, cases),
+            None => Ok(()),
+        }
+    }
+
+    // Points a cluster without a kubeconfig or impersonation of its own at the ones given by
+    // flags
+    fn apply_cluster_flags(&self, cluster: &mut Cluster) {
+        if cluster.kubeconfig.is_none() {
+            cluster.kubeconfig = self.kubeconfig.clone();
+        }
+        if cluster.impersonate.is_none() && (self.as_user.is_some() || !self.as_group.is_empty()) {
+            cluster.impersonate = Some(Impersonation {
+                user: self.as_user.clone(),
+                groups: self.as_group.clone(),
+            });
+        }
+    }
+
+    pub async fn get(&self, args: &GetArgs) -> Result<ExitCode> {
+        if let Some(path) = &args.filename {
+            return self.check_manifest(path).await;
+        }
+        if (args.output_file.is_some() || args.max_objects.is_some())
+            && self.output != OutputFormat::Yaml
+        {
+            return Err(anyhow!(
+                "--output-file and --max-objects only apply to yaml output"
+            ));
+        }
+        let (resource, name) = args.resource_and_name()?;
+        let (clusterset, ns) = self.active_clusterset()?;
+        let client = args.select(Client::try_new(&clusterset.clusters, &ns, &resource).await?);
+        if let Some(Baseline::LastApplied) = args.against {
+            return self
+                .check_last_applied(client, name.as_deref(), &ns, args)
+                .await;
+        }
+        let kinds = client.kinds.clone();
+        if let Some(name) = &name {
+            let mut responses = client.get(name).await;
+            let mut reports = Vec::new();
+            for kind in &kinds {
+                let (matching, rest) = responses.into_iter().partition(|r| &r.kind == kind);
+                responses = rest;
+                reports.push(NamedGetReport::new(name, kind, matching));
+            }
+            if args.with_children {
+                attach_children(&mut reports, &clusterset.clusters, &ns).await?;
+            }
+            self.print_named_get(&reports)?;
+            return Ok(ExitCode::from(NamedGetReport::combined_exit_code(&reports)));
+        }
+        if args.chunk_output && !matches!(self.output, OutputFormat::Table | OutputFormat::Wide) {
+            return Err(anyhow!(
+                "--chunk-output only applies to table and wide output"
+            ));
+        }
+        if args.watch {
+            return self
+                .watch(client, args, clusterset.clusters, ns, &resource)
+                .await;
+        }
+        if args.summarize {
+            return self.summarize(client, args).await;
+        }
+        if args.group_by.is_some() {
+            return self.group_by_owner(client, args).await;
+        }
+        if args.server_columns
+            && matches!(
+                self.output,
+                OutputFormat::Table | OutputFormat::Wide | OutputFormat::Csv
+            )
+        {
+            return self.get_server_tables(client, args).await;
+        }
+        if self.output == OutputFormat::Yaml {
+            return self.stream_yaml(client, args).await;
+        }
+        let wide = self.output == OutputFormat::Wide;
+        if args.show_taints && !wide {
+            return Err(anyhow!("--show-taints requires -o wide"));
+        }
+        let tabular = matches!(
+            self.output,
+            OutputFormat::Table | OutputFormat::Wide | OutputFormat::Csv
+        );
+        // one table per group and kind, so kinds of the same name in other groups keep apart
+        let mut group_kinds = table_kinds(&client);
+        // pods per node feed the pod counts of node rows and their requests in wide output
+        let node_requests = if tabular && group_kinds.iter().any(is_node) {
+            self.node_requests(&clusterset).await?
+        } else {
+            BTreeMap::new()
+        };
+        // objects of CRDs get the columns their CRD asks for instead of just their name and age
+        let printer_columns = match self.output {
+            OutputFormat::Table | OutputFormat::Wide | OutputFormat::Csv => {
+                client.printer_columns(wide).await
+            }
+            _ => BTreeMap::new(),
+        };
+        // annotations printer columns read must survive trimming
+        let trim = tabular
+            && !printer_columns
+                .values()
+                .any(|columns| reads_annotations(columns));
+        let mut pages = args.list(client, trim);
+        let mut not_ready = false;
+        let mut failed = false;
+        match self.output {
+            OutputFormat::Table | OutputFormat::Wide | OutputFormat::Csv => {
+                // pages are converted to rows as they arrive, keyed by group, kind and cluster so
+                // rows of clusters failing part way through can be dropped
+                // a row with its sort key, labels and wide columns
+                type Row = ((String, String), KubeOutput, Labels, Vec<String>);
+                let mut rows: BTreeMap<(GroupKind, String), Vec<Row>> = BTreeMap::new();
+                let mut failures = Vec::new();
+                while let Some(page) = pages.next().await {
+                    match page {
+                        Ok(lr) => {
+                            not_ready |= args.not_ready(&lr);
+                            let requested = node_requests.get(&lr.clustername);
+                            let group_kind = lr.group_kind();
+                            let keys: Vec<(String, String)> =
+                                lr.object_list.iter().map(sort_key).collect();
+                            let (labels, wide): (Vec<Labels>, Vec<Vec<String>>) = lr
+                                .object_list
+                                .iter()
+                                .map(|o| {
+                                    let columns = match printer_columns.get(&group_kind) {
+                                        Some(columns) => columns
+                                            .iter()
+                                            .map(|column| column_value(column, o))
+                                            .collect(),
+                                        None if wide => {
+                                            let (group, kind) = &group_kind;
+                                            let mut columns =
+                                                wide_columns(group, kind, o, requested);
+                                            if args.show_taints && is_node(&group_kind) {
+                                                columns.push(node_taints(o));
+                                            }
+                                            columns
+                                        }
+                                        None => Vec::new(),
+                                    };
+                                    (o.labels().clone(), columns)
+                                })
+                                .unzip();
+                            rows.entry((group_kind, lr.clustername.clone()))
+                                .or_default()
+                                .extend(
+                                    keys.into_iter()
+                                        .zip(args.rows(lr).into_iter().map(|mut row| {
+                                            if let (KubeOutput::Node(node), Some(requested)) =
+                                                (&mut row, requested)
+                                            {
+                                                let pods = requested
+                                                    .get(&node.name)
+                                                    .map_or(0, |resources| resources.pods);
+                                                node.set_pod_count(pods);
+                                            }
+                                            row
+                                        }))
+                                        .zip(labels)
+                                        .zip(wide)
+                                        .map(|(((key, row), labels), wide)| {
+                                            (key, row, labels, wide)
+                                        }),
+                                )
+                        }
+                        Err(failure) => failures.push(failure),
+                    }
+                }
+                failures.sort_by(|a, b| a.clustername.cmp(&b.clustername));
+                // a resource some cluster resolved to another group still gets its table
+                for (group_kind, _) in rows.keys() {
+                    if !group_kinds.contains(group_kind) {
+                        group_kinds.push(group_kind.clone());
+                    }
+                }
+
+                // one table per kind since each kind has its own columns
+                for (i, group_kind) in group_kinds.iter().enumerate() {
+                    let kind = &group_kind.1;
+                    let failed = |clustername: &str| {
+                        failures
+                            .iter()
+                            .any(|f| &f.group_kind() == group_kind && f.clustername == clustername)
+                    };
+                    let mut groups = Vec::new();
+                    for ((_, clustername), rows) in rows
+                        .iter_mut()
+                        .filter(|((k, clustername), _)| k == group_kind && !failed(clustername))
+                    {
+                        // a list that had to start over yields the objects it missed out of order
+                        let mut rows = std::mem::take(rows);
+                        rows.sort_by(|a, b| a.0.cmp(&b.0));
+                        let header = format!(
+                            "{} ({} {})",
+                            clustername,
+                            rows.len(),
+                            pluralize(&kind.to_lowercase(), rows.len())
+                        );
+                        let mut group = RowGroup {
+                            name: clustername.clone(),
+                            header,
+                            rows: Vec::new(),
+                            labels: Vec::new(),
+                            wide: Vec::new(),
+                        };
+                        for (_, output, labels, wide) in rows {
+                            group.rows.push(output);
+                            group.labels.push(labels);
+                            group.wide.push(wide);
+                        }
+                        groups.push(group);
+                    }
+                    if i > 0 {
+                        println!();
+                    }
+                    let headers: Vec<String> = match printer_columns.get(group_kind) {
+                        Some(columns) => columns.iter().map(|c| c.name.to_uppercase()).collect(),
+                        None if wide => {
+                            let mut headers: Vec<String> = wide_headers(&group_kind.0, kind)
+                                .iter()
+                                .map(|h| h.to_string())
+                                .collect();
+                            if args.show_taints && is_node(group_kind) {
+                                headers.push(TAINTS_HEADER.into());
+                            }
+                            headers
+                        }
+                        None => Vec::new(),
+                    };
+                    if args.chunk_output {
+                        print_grouped_wide_rows(self.output, "cluster", groups, headers);
+                    } else {
+                        let (mut outputs, mut labels, mut columns) = (vec![], vec![], vec![]);
+                        for group in groups {
+                            outputs.extend(group.rows);
+                            labels.extend(group.labels);
+                            columns.extend(group.wide);
+                        }
+                        print_wide_rows(self.output, outputs, labels, headers, columns);
+                    }
+                    self.print_list_failures(group_kind, &failures);
+                }
+                failed = !failures.is_empty();
+            }
+            OutputFormat::Json | OutputFormat::Plugin => {
+                // plugins read the same array of objects and their clusters as json output
+                let mut plugin = match self.output {
+                    OutputFormat::Plugin => Some(OutputPlugin::spawn()?),
+                    _ => None,
+                };
+                let out: Box<dyn Write + '_> = match plugin.as_mut() {
+                    Some(plugin) => Box::new(plugin.stdin()),
+                    None => Box::new(io::stdout()),
+                };
+                let mut writer = JsonArrayWriter::new(out);
+                while let Some(page) = pages.next().await {
+                    match page {
+                        Ok(lr) => {
+                            not_ready |= args.not_ready(&lr);
+                            for object in ClusterList::from(lr).objects() {
+                                writer.write(&object)?;
+                            }
+                        }
+                        Err(failure) => {
+                            failed = true;
+                            eprintln!(
+                                "{}: failed to list {}: {}",
+                                failure.clustername, failure.kind, failure.error
+                            )
+                        }
+                    }
+                }
+                writer.finish()?;
+                if let Some(plugin) = plugin {
+                    plugin.finish()?;
+                }
+            }
+            OutputFormat::Jsonl => {
+                let mut writer = JsonLinesWriter::new(io::stdout());
+                while let Some(page) = pages.next().await {
+                    match page {
+                        Ok(lr) => {
+                            not_ready |= args.not_ready(&lr);
+                            for object in lr.object_list.items {
+                                writer.write(&object_line(
+                                    &lr.clustername,
+                                    &lr.kind,
+                                    &lr.api_version,
+                                    object,
+                                ))?;
+                            }
+                            writer.flush()?;
+                        }
+                        Err(failure) => {
+                            failed = true;
+                            eprintln!(
+                                "{}: failed to list {}: {}",
+                                failure.clustername, failure.kind, failure.error
+                            )
+                        }
+                    }
+                }
+                writer.finish()?;
+            }
+            OutputFormat::Yaml => unreachable!("yaml output is streamed"),
+        }
+        Ok(args.list_exit_code(not_ready, failed))
+    }
+
+    // Prints the clusters that failed to list a kind below its table, or on stderr for csv
+    fn print_list_failures(&self, group_kind: &GroupKind, failures: &[ListFailure]) {
+        let errors: Vec<ErrorOutput> = failures
+            .iter()
+            .filter(|failure| &failure.group_kind() == group_kind)
+            .map(ErrorOutput::from)
+            .collect();
+        if errors.is_empty() {
+            return;
+        }
+        if self.output == OutputFormat::Csv {
+            for error in errors {
+                eprintln!(
+                    "{}: failed to list {}: {}",
+                    error.clustername, group_kind.1, error.error
+                );
+            }
+        } else {
+            println!();
+            create_table(errors);
+        }
+    }
+
+    // Lists through the server-side Table protocol. Each kind gets the columns of the first
+    // cluster answering for it, rows of clusters serving other columns fill in what they have.
+    async fn get_server_tables(&self, client: Client, args: &GetArgs) -> Result<ExitCode> {
+        let group_kinds = table_kinds(&client);
+        let wide = self.output == OutputFormat::Wide;
+        let window = args.age_window();
+        let now = Utc::now();
+        let mut tables: BTreeMap<(GroupKind, String), Vec<ServerTable>> = BTreeMap::new();
+        let mut failures = Vec::new();
+        let client = client.with_max_objects(args.max_objects_per_cluster as usize);
+        let mut pages = client.list_tables_paged(args.page_size);
+        while let Some(page) = pages.next().await {
+            match page {
+                Ok(response) => {
+                    if let Some(truncated) = response.truncated {
+                        note_truncated(&response.clustername, &response.kind, truncated);
+                    }
+                    tables
+                        .entry(((response.group, response.kind), response.clustername))
+                        .or_default()
+                        .push(response.table)
+                }
+                Err(failure) => failures.push(failure),
+            }
+        }
+        failures.sort_by(|a, b| a.clustername.cmp(&b.clustername));
+
+        for (i, group_kind) in group_kinds.iter().enumerate() {
+            let failed = |clustername: &str| {
+                failures
+                    .iter()
+                    .any(|f| &f.group_kind() == group_kind && f.clustername == clustername)
+            };
+            let kind_tables: Vec<(&String, &ServerTable)> = tables
+                .iter()
+                .filter(|((k, clustername), _)| k == group_kind && !failed(clustername))
+                .flat_map(|((_, clustername), pages)| pages.iter().map(move |t| (clustername, t)))
+                .collect();
+            let columns = kind_tables
+                .first()
+                .map(|(_, table)| table.columns(wide))
+                .unwrap_or_default();
+            let mut outputs = Vec::new();
+            let mut labels = Vec::new();
+            let mut cells = Vec::new();
+            for (clustername, table) in kind_tables {
+                for row in &table.rows {
+                    let metadata = row.metadata();
+                    let created = metadata.and_then(|m| m.creation_timestamp.as_ref());
+                    if !window.contains(created.map(|time| time.0), now) {
+                        continue;
+                    }
+                    outputs.push(ClusterNameOutput {
+                        clustername: clustername.clone(),
+                    });
+                    labels.push(metadata.and_then(|m| m.labels.clone()).unwrap_or_default());
+                    cells.push(table.cells(row, &columns));
+                }
+            }
+            if i > 0 {
+                println!();
+            }
+            let headers = columns.iter().map(|c| c.to_uppercase()).collect();
+            print_wide_rows(self.output, outputs, labels, headers, cells);
+            self.print_list_failures(group_kind, &failures);
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+
+    // Prints a line per change to watched objects until interrupted. The initial state of each
+    // cluster is printed as additions. Changes to the kubemc config and kubeconfig files are
+    // picked up along the way: clusters added, changed or given new credentials are watched
+    // again and clusters removed from the clusterset stop being watched.
+    async fn watch(
+        &self,
+        client: Client,
+        args: &GetArgs,
+        mut clusters: Vec<Cluster>,
+        mut ns: String,
+        resource: &str,
+    ) -> Result<ExitCode> {
+        let mut state = WatchState::default();
+        let formatters: BTreeMap<(String, String), Formatter> = client
+            .resources()
+            .iter()
+            .map(|ar| {
+                let key = (ar.api_version.clone(), ar.kind.clone());
+                (key, Formatter::for_resource(ar))
+            })
+            .collect();
+        let mut watches = Watches::default();
+        watches.add(client);
+        let mut files = self.watch_files(&clusters);
+        loop {
+            let changed = async {
+                match files.as_mut() {
+                    Some(files) => files.changed().await,
+                    None => std::future::pending().await,
+                }
+            };
+            let response = tokio::select! {
+                response = watches.events.next() => response,
+                changed = changed => {
+                    let (clusters, ns) = (&mut clusters, &mut ns);
+                    if self
+                        .reload_watches(&changed, clusters, ns, resource, &mut state, &mut watches)
+                        .await
+                    {
+                        files = self.watch_files(clusters);
+                    }
+                    continue;
+                }
+            };
+            let Some(response) = response else {
+                break;
+            };
+            let event = match response.event {
+                Ok(event) => event,
+                Err(e) => {
+                    warn!(
+                        "watch failed on cluster {}: {}",
+                        response.clustername,
+                        describe_error(&e.into())
+                    );
+                    continue;
+                }
+            };
+            for change in state.apply(&response.clustername, &response.kind, event) {
+                print_watch_event(&change, &formatters, args.show_changes)?;
+            }
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+
+    // Brings the watches in line with the changed files: clusters removed from the clusterset
+    // stop being watched and clusters added, changed or given new credentials are watched
+    // again. Returns whether anything was reloaded.
+    async fn reload_watches(
+        &self,
+        changed: &BTreeSet<PathBuf>,
+        clusters: &mut Vec<Cluster>,
+        ns: &mut String,
+        resource: &str,
+        state: &mut WatchState,
+        watches: &mut Watches,
+    ) -> bool {
+        let (current, current_ns) = match self.active_clusterset() {
+            Ok((clusterset, ns)) => (clusterset.clusters, ns),
+            Err(e) => {
+                warn!("not reloading the changed config: {}", e);
+                return false;
+            }
+        };
+        let mut reload = Reload::plan(clusters, &current, changed);
+        if &current_ns != ns {
+            reload.reconnect = current.clone();
+        }
+        for clustername in &reload.removed {
+            watches.stop(clustername);
+            state.forget(clustername);
+            eprintln!(
+                "{}: removed from the clusterset, no longer watched",
+                clustername
+            );
+        }
+        if !reload.reconnect.is_empty() {
+            for cluster in &reload.reconnect {
+                watches.stop(&cluster.name);
+            }
+            match Client::try_new(&reload.reconnect, &current_ns, resource).await {
+                Ok(client) => {
+                    for (clustername, (_, kind), e) in client.failures() {
+                        warn!("failed to watch {} on cluster {}: {}", kind, clustername, e);
+                    }
+                    for cluster in &reload.reconnect {
+                        eprintln!("{}: reloaded", cluster.name);
+                    }
+                    watches.add(client);
+                }
+                Err(e) => warn!("failed to reconnect after the config changed: {}", e),
+            }
+        }
+        (*clusters, *ns) = (current, current_ns);
+        !reload.is_empty()
+    }
+
+    // Watches the kubemc config and the kubeconfig files of the clusters, None when they cannot
+    // be watched, which leaves the command running without reloading
+    fn watch_files(&self, clusters: &[Cluster]) -> Option<FileWatcher> {
+        let mut files: Vec<PathBuf> = cluster_kubeconfigs(clusters).into_iter().collect();
+        // --contexts does not read the config
+        if self.contexts.is_empty() {
+            files.extend(config_path(self.config_file.as_ref()).ok());
+        }
+        match FileWatcher::new(&files) {
+            Ok(watcher) => Some(watcher),
+            Err(e) => {
+                debug!("not watching config files for changes: {}", e);
+                None
+            }
+        }
+    }
+
+    // Streams every listed object as its own yaml document as pages arrive from the clusters
+    async fn stream_yaml(&self, client: Client, args: &GetArgs) -> Result<ExitCode> {
+        let out: Box<dyn AsyncWrite + Unpin + Send> = match &args.output_file {
+            Some(path) => Box::new(tokio::fs::File::create(path).await?),
+            None => Box::new(tokio::io::stdout()),
+        };
+        let mut writer = YamlDocumentWriter::new(out, args.max_objects);
+        let mut pages = args.list(client, false);
+        let mut not_ready = false;
+        let mut failed = false;
+        while let Some(page) = pages.next().await {
+            let page = match page {
+                Ok(page) => page,
+                Err(failure) => {
+                    failed = true;
+                    eprintln!(
+                        "{}: failed to list {}: {}",
+                        failure.clustername, failure.kind, failure.error
+                    );
+                    continue;
+                }
+            };
+            not_ready |= args.not_ready(&page);
+            for document in ClusterList::from(page).objects() {
+                if let Err(e) = writer.write(&document).await {
+                    writer.finish().await?;
+                    return Err(e);
+                }
+            }
+        }
+        writer.finish().await?;
+        Ok(args.list_exit_code(not_ready, failed))
+    }
+
+    // Counts the listed objects by status per kind and cluster, followed by a total per kind
+    async fn summarize(&self, client: Client, args: &GetArgs) -> Result<ExitCode> {
+        let kinds = client.kinds.clone();
+        let mut summaries: BTreeMap<(String, String), SummaryOutput> = BTreeMap::new();
+        let mut failures = Vec::new();
+        let mut not_ready = false;
+        let mut pages = args.list(client, true);
+        while let Some(page) = pages.next().await {
+            match page {
+                Ok(lr) => {
+                    not_ready |= args.not_ready(&lr);
+                    let summary = summaries
+                        .entry((lr.kind.clone(), lr.clustername.clone()))
+                        .or_insert_with(|| SummaryOutput::new(&lr.clustername, &lr.kind));
+                    for output in convert_list_response_to_table(lr) {
+                        summary.count(output.summary_status());
+                    }
+                }
+                Err(failure) => failures.push(failure),
+            }
+        }
+        failures.sort_by(|a, b| a.clustername.cmp(&b.clustername));
+
+        let mut rows = Vec::new();
+        for kind in &kinds {
+            let mut total = SummaryOutput::new("total", kind);
+            for ((k, clustername), summary) in &summaries {
+                let failed = failures
+                    .iter()
+                    .any(|f| &f.kind == kind && &f.clustername == clustername);
+                if k == kind && !failed {
+                    total.add(summary);
+                    rows.push(summary.clone());
+                }
+            }
+            rows.push(total);
+        }
+        self.print_structured(rows)?;
+        for failure in &failures {
+            eprintln!(
+                "{}: failed to list {}: {}",
+                failure.clustername, failure.kind, failure.error
+            );
+        }
+        Ok(if failures.is_empty() && !not_ready {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        })
+    }
+
+    // Lists pods and prints them below a header row per cluster and owning workload. Pods
+    // without an owner come last in their cluster.
+    async fn group_by_owner(&self, client: Client, args: &GetArgs) -> Result<ExitCode> {
+        if client.kinds != ["Pod"] {
+            return Err(anyhow!("--group-by owner only supports pods"));
+        }
+        if !matches!(self.output, OutputFormat::Table | OutputFormat::Csv) {
+            return Err(anyhow!("--group-by only applies to table and csv output"));
+        }
+        type Group = (String, bool, Option<Owner>);
+        let mut groups: BTreeMap<Group, Vec<(KubeOutput, Labels)>> = BTreeMap::new();
+        let mut failures = Vec::new();
+        let mut pages = args.list(client, true);
+        while let Some(page) = pages.next().await {
+            match page {
+                Ok(lr) => {
+                    let keys: Vec<(Group, Labels)> = lr
+                        .object_list
+                        .iter()
+                        .map(|pod| {
+                            let owner = pod_owner(pod);
+                            let key = (lr.clustername.clone(), owner.is_none(), owner);
+                            (key, pod.labels().clone())
+                        })
+                        .collect();
+                    for ((key, labels), row) in keys.into_iter().zip(args.rows(lr)) {
+                        groups.entry(key).or_default().push((row, labels));
+                    }
+                }
+                Err(failure) => failures.push(failure),
+            }
+        }
+        groups
+            .retain(|(clustername, ..), _| !failures.iter().any(|f| &f.clustername == clustername));
+
+        let groups: Vec<RowGroup<KubeOutput>> = groups
+            .into_iter()
+            .map(|((clustername, _, owner), rows)| {
+                let name = owner.map_or_else(|| "<none>".to_owned(), |owner| owner.to_string());
+                let header = format!(
+                    "{} {} ({} {})",
+                    clustername,
+                    name,
+                    rows.len(),
+                    pluralize("pod", rows.len())
+                );
+                let (rows, labels) = rows.into_iter().unzip();
+                RowGroup {
+                    name,
+                    header,
+                    rows,
+                    labels,
+                    wide: Vec::new(),
+                }
+            })
+            .collect();
+        print_grouped_rows(self.output, "owner", groups);
+        for failure in &failures {
+            eprintln!(
+                "{}: failed to list {}: {}",
+                failure.clustername, failure.kind, failure.error
+            );
+        }
+        Ok(if failures.is_empty() {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        })
+    }
+
+    // Fetches every object of the manifest from every cluster and compares it to the manifest.
+    // Succeeds only when every object matches on every cluster.
+    async fn check_manifest(&self, path: &Path) -> Result<ExitCode> {
+        let objects = read_manifest(path)?;
+        let (clusterset, ns) = self.active_clusterset()?;
+        // objects of the same kind and namespace share a client
+        let mut clients: BTreeMap<(String, String), Client> = BTreeMap::new();
+        let mut outputs = Vec::new();
+        for object in &objects {
+            let namespace = object.namespace().unwrap_or_else(|| ns.clone());
+            let key = (resource_name(object), namespace.clone());
+            if !clients.contains_key(&key) {
+                let client = Client::try_new(&clusterset.clusters, &key.1, &key.0).await?;
+                clients.insert(key.clone(), client);
+            }
+            let mut responses = clients[&key].get(&object.name_any()).await;
+            responses.sort_by(|a, b| a.clustername.cmp(&b.clustername));
+            outputs.extend(
+                responses
+                    .into_iter()
+                    .map(|response| ManifestOutput::new(&namespace, object, response)),
+            );
+        }
+        let matches = outputs.iter().all(|o| o.state == ManifestState::Matches);
+        let cases: Vec<TestCase> = outputs
+            .iter()
+            .map(|o| o.test_case(o.state == ManifestState::Matches))
+            .collect();
+        self.write_report("get", &cases)?;
+        self.print_structured(outputs)?;
+        if matches {
+            Ok(ExitCode::SUCCESS)
+        } else {
+            Ok(ExitCode::FAILURE)
+        }
+    }
+
+    // Compares objects to the configuration last applied to them with `kubectl apply`, showing
+    // what was changed out of band on each cluster. Succeeds only when nothing drifted.
+    async fn check_last_applied(
+        &self,
+        client: Client,
+        name: Option<&str>,
+        ns: &str,
+        args: &GetArgs,
+    ) -> Result<ExitCode> {
+        let mut outputs = Vec::new();
+        let mut failures = Vec::new();
+        if let Some(name) = name {
+            outputs.extend(client.get(name).await.into_iter().map(|response| {
+                ManifestOutput::against_last_applied(
+                    response.clustername,
+                    &response.kind,
+                    ns,
+                    name,
+                    response.result,
+                )
+            }));
+        } else {
+            // the last applied configuration is what the objects are checked against
+            let mut pages = args.list(client, false);
+            while let Some(page) = pages.next().await {
+                match page {
+                    Ok(lr) => outputs.extend(lr.object_list.items.into_iter().map(|object| {
+                        let name = object.name_any();
+                        ManifestOutput::against_last_applied(
+                            lr.clustername.clone(),
+                            &lr.kind,
+                            ns,
+                            &name,
+                            GetResult::Found(Box::new(object)),
+                        )
+                    })),
+                    Err(failure) => failures.push(failure),
+                }
+            }
+        }
+        outputs.sort_by(|a, b| {
+            (&a.clustername, &a.namespace, &a.object).cmp(&(
+                &b.clustername,
+                &b.namespace,
+                &b.object,
+            ))
+        });
+        let is_drift = |state: &ManifestState| {
+            matches!(
+                state,
+                ManifestState::Differs | ManifestState::Missing | ManifestState::Failed
+            )
+        };
+        let drifted = outputs.iter().any(|o| is_drift(&o.state));
+        let cases: Vec<TestCase> = outputs
+            .iter()
+            .map(|o| o.test_case(!is_drift(&o.state)))
+            .collect();
+        self.write_report("get", &cases)?;
+        self.print_structured(outputs)?;
+        for failure in &failures {
+            eprintln!(
+                "{}: failed to list {}: {}",
+                failure.clustername, failure.kind, failure.error
+            );
+        }
+        if drifted || !failures.is_empty() {
+            Ok(ExitCode::FAILURE)
+        } else {
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+
+    // Prints rows in the output format, as a table or csv or as a list of structured rows
+    fn print_structured<T: Tabled + Serialize>(&self, rows: Vec<T>) -> Result<()> {
+        match self.output {
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&rows)?),
+            OutputFormat::Plugin => print_plugin(&rows)?,
+            OutputFormat::Jsonl => print_json_lines(&rows)?,
+            OutputFormat::Yaml => print!("{}", serde_yaml::to_string(&rows)?),
+            OutputFormat::Table | OutputFormat::Wide | OutputFormat::Csv => {
+                print_rows(self.output, rows)
+            }
+        }
+        Ok(())
+    }
+
+    fn print_named_get(&self, reports: &[NamedGetReport]) -> Result<()> {
+        match self.output {
+            OutputFormat::Table | OutputFormat::Wide | OutputFormat::Csv => {
+                for report in reports {
+                    let outputs = report
+                        .found
+                        .iter()
+                        .map(|found| {
+                            Formatter::for_object(&found.object)
+                                .format(&found.cluster, found.object.clone())
+                        })
+                        .collect::<Vec<_>>();
+                    if !report.children.is_empty() {
+                        for (i, rows) in report.ownership_tables().into_iter().enumerate() {
+                            if i > 0 {
+                                println!();
+                            }
+                            print_rows(self.output, rows);
+                        }
+                    } else if !outputs.is_empty() {
+                        print_rows(self.output, outputs);
+                    }
+                    if report.found.len() != report.total() {
+                        eprintln!("{}", report.summary());
+                    }
+                    for failure in &report.child_failures {
+                        eprintln!("{}: {}", failure.cluster, failure.error);
+                    }
+                }
+            }
+            OutputFormat::Json => match reports {
+                [report] => println!("{}", serde_json::to_string_pretty(report)?),
+                reports => println!("{}", serde_json::to_string_pretty(reports)?),
+            },
+            OutputFormat::Plugin => match reports {
+                [report] => print_plugin(report)?,
+                reports => print_plugin(&reports)?,
+            },
+            OutputFormat::Jsonl => print_json_lines(reports)?,
+            OutputFormat::Yaml => match reports {
+                [report] => print!("{}", serde_yaml::to_string(report)?),
+                reports => print!("{}", serde_yaml::to_string(reports)?),
+            },
+        }
+        Ok(())
+    }
+
+    pub async fn describe(&self, args: &DescribeArgs) -> Result<ExitCode> {
+        let object = ObjectRef::parse(&args.resource, args.name.as_deref())?;
+        if !object.is_kind("Node") {
+            return Err(anyhow!(
+                "describe only supports nodes, not {}",
+                object.resource
+            ));
+        }
+        let ObjectRef {
+            namespace, name, ..
+        } = object;
+        if namespace.is_some() {
+            return Err(anyhow!("nodes are not namespaced"));
+        }
+        let (clusterset, _) = self.active_clusterset()?;
+        let handles = serial::join_all(connect_all(&clusterset.clusters).await?.into_iter().map(
+            |(clustername, client)| {
+                let name = name.clone();
+                tokio::spawn(async move {
+                    let description = match client {
+                        Ok(client) => {
+                            let describe = NodeDescription::fetch(&clustername, client, &name);
+                            serial::phase(&clustername, "describe node", describe).await
+                        }
+                        Err(e) => Err(e),
+                    };
+                    (clustername, description)
+                })
+            },
+        ))
+        .await;
+
+        let mut descriptions = Vec::new();
+        let mut not_found = Vec::new();
+        let mut failed = false;
+        for handle in handles {
+            match handle {
+                Ok((_, Ok(Some(description)))) => descriptions.push(description),
+                Ok((clustername, Ok(None))) => not_found.push(clustername),
+                Ok((clustername, Err(e))) => {
+                    failed = true;
+                    eprintln!("{}: {}", clustername, describe_error(&e))
+                }
+                Err(e) => debug!("join failed {}", e),
+            }
+        }
+        descriptions.sort_by(|a, b| a.clustername.cmp(&b.clustername));
+        for (i, description) in descriptions.iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+            description.print();
+        }
+        if !not_found.is_empty() {
+            not_found.sort();
+            eprintln!("node {} not found in: {}", name, not_found.join(", "));
+        }
+        if descriptions.is_empty() || failed {
+            Ok(ExitCode::FAILURE)
+        } else {
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+
+    pub async fn apply(&self, args: &ApplyArgs) -> Result<ExitCode> {
+        let documents = apply_order(read_manifest(&args.filename)?);
+        let crds = manifest_crds(&documents);
+        let (clusterset, ns) = self.active_clusterset()?;
+        let crd_timeout = args
+            .crd_timeout
+            .to_std()
+            .map_err(|_| anyhow!("the CRD timeout cannot be negative"))?;
+        // clusters that did not establish a CRD of the manifest, and why
+        let mut unestablished: BTreeMap<String, Vec<(String, String)>> = BTreeMap::new();
+        // clients are built once per namespace and kind, not for every document
+        let mut clients: HashMap<(String, String), std::result::Result<Client, String>> =
+            HashMap::new();
+        let mut rows = Vec::new();
+        for document in &documents {
+            let object = &document.object;
+            let row = |clustername: String, result: StatusCell| ApplyOutput {
+                clustername,
+                document: document.position,
+                object: object_name(object),
+                result,
+            };
+            let mut skip = Vec::new();
+            let mut document_rows = Vec::new();
+            if let Some(crd) = defining_crd(object, &crds) {
+                if !unestablished.contains_key(crd) {
+                    let pending = await_established(&clusterset.clusters, crd, crd_timeout).await?;
+                    unestablished.insert(crd.clone(), pending);
+                }
+                for (clustername, reason) in &unestablished[crd] {
+                    let message = format!("skipped: CRD {} not established ({})", crd, reason);
+                    document_rows.push(row(
+                        clustername.clone(),
+                        StatusCell::new(message, Health::Unhealthy),
+                    ));
+                    skip.push(clustername.clone());
+                }
+            }
+            let namespace = object.namespace().unwrap_or_else(|| ns.clone());
+            let key = (namespace, resource_name(object));
+            if !clients.contains_key(&key) {
+                let client = Client::try_new(&clusterset.clusters, &key.0, &key.1)
+                    .await
+                    .map_err(|e| e.to_string());
+                clients.insert(key.clone(), client);
+            }
+            match &clients[&key] {
+                Ok(client) => {
+                    let responses = client.apply(object, FIELD_MANAGER, &skip).await?;
+                    document_rows.extend(responses.into_iter().map(|response| {
+                        row(
+                            response.clustername,
+                            patch_status(response.result, "applied"),
+                        )
+                    }));
+                }
+                Err(e) => {
+                    let message = format!("failed: {}", e);
+                    document_rows.extend(
+                        clusterset
+                            .clusters
+                            .iter()
+                            .filter(|cluster| !skip.contains(&cluster.name))
+                            .map(|cluster| {
+                                row(
+                                    cluster.name.clone(),
+                                    StatusCell::new(&message, Health::Unhealthy),
+                                )
+                            }),
+                    );
+                }
+            }
+            document_rows.sort_by(|a, b| a.clustername.cmp(&b.clustername));
+            rows.extend(document_rows);
+        }
+        record_history(
+            rows.iter()
+                .map(|row| history_entry("apply", &row.clustername, &row.object, &row.result))
+                .collect(),
+        );
+        let applied = rows.iter().all(|row| row.result.health == Health::Healthy);
+        self.print_structured(rows)?;
+        Ok(if applied {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        })
+    }
+
+    pub async fn scale(&self, args: &ScaleArgs) -> Result<ExitCode> {
+        let object = ObjectRef::parse(&args.resource, args.name.as_deref())?;
+        let (clusterset, ns, ObjectRef { resource, name, .. }) = self.object_clusterset(object)?;
+        let clusters = clusterset.select_clusters(&args.clusters)?;
+        let (ns, resource, name) = (&ns, &resource, &name);
+        let (mut outputs, all_scaled) = roll_out(
+            &args.rolling,
+            &clusters,
+            |output: &ScaleOutput| output.result == "scaled",
+            |clusters| async move {
+                let client = Client::try_new(&clusters, ns, resource).await?;
+                let responses = client
+                    .scale(name, args.replicas, args.current_replicas)
+                    .await;
+                Ok(responses
+                    .into_iter()
+                    .map(|response| ScaleOutput::new(name, response))
+                    .collect())
+            },
+        )
+        .await?;
+        outputs.sort_by(|a, b| a.clustername.cmp(&b.clustername));
+        let object = format!("{}/{}", resource, name);
+        record_history(
+            outputs
+                .iter()
+                .map(|output| {
+                    let scaled = output.result == "scaled";
+                    let outcome = if scaled {
+                        format!("scaled {}", output.replicas)
+                    } else {
+                        output.result.clone()
+                    };
+                    Entry::new("scale", &output.clustername, &object, &outcome, scaled)
+                })
+                .collect(),
+        );
+        self.print_structured(outputs)?;
+        if all_scaled {
+            Ok(ExitCode::SUCCESS)
+        } else {
+            Ok(ExitCode::FAILURE)
+        }
+    }
+
+    /// Marks the matching nodes unschedulable, or schedulable again, with one merge patch per
+    /// node
+    pub async fn cordon(&self, args: &NodeArgs, unschedulable: bool) -> Result<ExitCode> {
+        let (clusterset, _) = self.active_clusterset()?;
+        let clusters = clusterset.select_clusters(&args.clusters)?;
+        let done = if unschedulable {
+            "cordoned"
+        } else {
+            "uncordoned"
+        };
+        let patch = &Patch::Merge(cordon_patch(unschedulable));
+        let (mut outputs, all_done) = roll_out(
+            &args.rolling,
+            &clusters,
+            NodeMaintenanceOutput::succeeded,
+            |clusters| async move {
+                on_nodes(&clusters, args, |client, clustername, node| async move {
+                    let nodes: Api<Node> = Api::all(client);
+                    match nodes.patch(&node, &PatchParams::default(), patch).await {
+                        Ok(_) => NodeMaintenanceOutput::new(
+                            &clustername,
+                            &node,
+                            StatusCell::new(done, Health::Healthy),
+                        ),
+                        Err(e) => NodeMaintenanceOutput::failed(
+                            &clustername,
+                            &node,
+                            describe_kube_error(&e),
+                        ),
+                    }
+                })
+                .await
+            },
+        )
+        .await?;
+        outputs.sort_by(|a, b| a.clustername.cmp(&b.clustername));
+        let command = if unschedulable { "cordon" } else { "uncordon" };
+        record_history(node_history(command, &outputs));
+        let code = node_exit_code(&outputs, all_done);
+        self.print_structured(outputs)?;
+        Ok(code)
+    }
+
+    /// Cordons the matching nodes and evicts their pods, one node of a cluster at a time
+    pub async fn drain(&self, args: &DrainArgs) -> Result<ExitCode> {
+        let timeout = args
+            .timeout
+            .to_std()
+            .map_err(|_| anyhow!("the timeout cannot be negative"))?;
+        let policy = DrainPolicy {
+            force: args.force,
+            delete_emptydir_data: args.delete_emptydir_data,
+        };
+        let (clusterset, _) = self.active_clusterset()?;
+        let clusters = clusterset.select_clusters(&args.nodes.clusters)?;
+        let (mut outputs, all_drained) = roll_out(
+            &args.nodes.rolling,
+            &clusters,
+            NodeMaintenanceOutput::succeeded,
+            |clusters| async move {
+                on_nodes(
+                    &clusters,
+                    &args.nodes,
+                    |client, clustername, node| async move {
+                        drain_node(client, &clustername, &node, policy, timeout).await
+                    },
+                )
+                .await
+            },
+        )
+        .await?;
+        outputs.sort_by(|a, b| a.clustername.cmp(&b.clustername));
+        record_history(node_history("drain", &outputs));
+        let code = node_exit_code(&outputs, all_drained);
+        self.print_structured(outputs)?;
+        Ok(code)
+    }
+
+    /// Sets and removes labels or annotations of the named object, or of the objects matching
+    /// the selector, on every cluster with one merge patch per object
+    pub async fn change_metadata(
+        &self,
+        field: MetadataField,
+        args: &MetadataArgs,
+    ) -> Result<ExitCode> {
+        let (name, changes) = parse_metadata_args(field, &args.changes)?;
+        let (clusterset, ns, resource, name) = match (&args.selector, name) {
+            (Some(_), Some(name)) => {
+                return Err(anyhow!(
+                    "give either the name {} or --selector, not both",
+                    name
+                ))
+            }
+            (Some(_), None) if args.resource.contains('/') => {
+                return Err(anyhow!(
+                    "give either {} or --selector, not both",
+                    args.resource
+                ))
+            }
+            (Some(_), None) => {
+                let (clusterset, ns) = self.active_clusterset()?;
+                (clusterset, ns, args.resource.clone(), None)
+            }
+            (None, name) => {
+                let object = ObjectRef::parse(&args.resource, name.as_deref())?;
+                let (clusterset, ns, ObjectRef { resource, name, .. }) =
+                    self.object_clusterset(object)?;
+                (clusterset, ns, resource, Some(name))
+            }
+        };
+        let clusters = clusterset.select_clusters(&args.clusters)?;
+        let client = Client::try_new(&clusters, &ns, &resource).await?;
+        let row = |clustername: &str, kind: &str, name: &str, result: StatusCell| MetadataOutput {
+            clustername: clustername.to_owned(),
+            object: format!("{}/{}", kind.to_lowercase(), name),
+            result,
+        };
+
+        // the objects are read first, to check the changes against what they have
+        let mut rows = Vec::new();
+        let mut objects: Vec<(String, String, DynamicObject)> = Vec::new();
+        match (&args.selector, &name) {
+            (Some(selector), _) => {
+                let listing = client.clone().with_selector(selector).list().await?;
+                for response in listing.responses {
+                    for object in response.object_list.items {
+                        objects.push((response.clustername.clone(), response.kind.clone(), object));
+                    }
+                }
+                for failure in listing.failures {
+                    let message = format!("failed: {}", failure.error);
+                    rows.push(row(
+                        &failure.clustername,
+                        &failure.kind,
+                        "*",
+                        StatusCell::new(message, Health::Unhealthy),
+                    ));
+                }
+            }
+            (None, Some(name)) => {
+                for response in client.get(name).await {
+                    match response.result {
+                        GetResult::Found(object) => {
+                            objects.push((response.clustername, response.kind, *object))
+                        }
+                        result => rows.push(row(
+                            &response.clustername,
+                            &response.kind,
+                            name,
+                            patch_status(result, ""),
+                        )),
+                    }
+                }
+            }
+            (None, None) => unreachable!("objects are named or selected"),
+        }
+        if objects.is_empty() && rows.is_empty() {
+            let selector = args.selector.as_deref().unwrap_or_default();
+            eprintln!("no {} matches {} on any cluster", resource, selector);
+            return Ok(ExitCode::FAILURE);
+        }
+
+        let mut patches = Vec::new();
+        for (clustername, kind, object) in objects {
+            let name = object.name_any();
+            match metadata_patch(field, &object, &changes, args.overwrite) {
+                Ok(Some(patch)) => patches.push(ObjectPatch {
+                    clustername,
+                    kind,
+                    name,
+                    patch,
+                }),
+                Ok(None) => rows.push(row(
+                    &clustername,
+                    &kind,
+                    &name,
+                    StatusCell::new("unchanged", Health::Healthy),
+                )),
+                Err(e) => rows.push(row(
+                    &clustername,
+                    &kind,
+                    &name,
+                    StatusCell::new(format!("skipped: {}", e), Health::Unhealthy),
+                )),
+            }
+        }
+        let done = match field {
+            MetadataField::Labels => "labeled",
+            MetadataField::Annotations => "annotated",
+        };
+        let responses = client.patch_objects(&patches).await;
+        for (patch, response) in patches.iter().zip(responses) {
+            let status = patch_status(response.result, done);
+            rows.push(row(&patch.clustername, &patch.kind, &patch.name, status));
+        }
+        rows.sort_by(|a, b| (&a.clustername, &a.object).cmp(&(&b.clustername, &b.object)));
+        let command = match field {
+            MetadataField::Labels => "label",
+            MetadataField::Annotations => "annotate",
+        };
+        record_history(
+            rows.iter()
+                .map(|row| history_entry(command, &row.clustername, &row.object, &row.result))
+                .collect(),
+        );
+
+        let changed = rows.iter().all(|row| row.result.health == Health::Healthy);
+        self.print_structured(rows)?;
+        Ok(if changed {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        })
+    }
+
+    pub async fn edit(&self, args: &EditArgs) -> Result<ExitCode> {
+        let object = ObjectRef::parse(&args.resource, args.name.as_deref())?;
+        let (clusterset, ns, ObjectRef { resource, name, .. }) = self.object_clusterset(object)?;
+        let client = Client::try_new(&clusterset.clusters, &ns, &resource).await?;
+        let kind = client.kinds.join(",");
+        let mut found: BTreeMap<String, DynamicObject> = BTreeMap::new();
+        for response in client.get(&name).await {
+            match response.result {
+                GetResult::Found(object) => {
+                    found.insert(response.clustername, *object);
+                }
+                GetResult::NotFound => {}
+                GetResult::Failed(e) => eprintln!("{}: {}", response.clustername, e),
+            }
+        }
+        let (source, object) = match &args.cluster {
+            Some(cluster) => found
+                .get_key_value(cluster)
+                .ok_or_else(|| anyhow!("{} {} not found on cluster {}", kind, name, cluster))?,
+            None => found
+                .iter()
+                .next()
+                .ok_or_else(|| anyhow!("{} {} not found on any cluster", kind, name))?,
+        };
+
+        let original = editable(object)?;
+        let edited = edit_in_editor(
+            &format!("{}-{}", source, name),
+            &serde_yaml::to_string(&original)?,
+        )
+        .await?;
+        let edited: serde_json::Value =
+            serde_yaml::from_str(&edited).context("edited object is not valid yaml")?;
+        // built in kinds get a strategic merge patch like kubectl edit sends, so lists such as
+        // containers are merged by name instead of replacing what other clusters have
+        let strategic = client.built_in();
+        let patch = if strategic {
+            strategic_merge_patch(&original, &edited)
+        } else {
+            merge_patch(&original, &edited)
+        };
+        let Some(patch) = patch else {
+            eprintln!("edit cancelled, no changes made");
+            return Ok(ExitCode::SUCCESS);
+        };
+
+        let propagation = match args.propagate {
+            Some(propagation) => propagation,
+            None if found.len() == 1 => Propagation::Cluster,
+            None => choose_propagation(source, found.len())?,
+        };
+        let targets: Vec<String> = match propagation {
+            Propagation::Cluster => vec![source.clone()],
+            Propagation::All => clusterset
+                .clusters
+                .iter()
+                .filter(|cluster| found.contains_key(&cluster.name))
+                .map(|cluster| cluster.name.clone())
+                .collect(),
+        };
+        for target in &targets {
+            let current = serde_json::to_value(&found[target])?;
+            let patched = if strategic {
+                apply_strategic_patch(&current, &patch)
+            } else {
+                apply_merge_patch(&current, &patch)
+            };
+            let mut changes = diff(&current, &patched);
+            redact_changes(&kind, &mut changes);
+            println!("{}:", target);
+            if changes.is_empty() {
+                println!("    no changes");
+            }
+            for change in changes {
+                println!("    {}", change);
+            }
+        }
+        let prompt = format!(
+            "apply to {} {}?",
+            targets.len(),
+            pluralize("cluster", targets.len())
+        );
+        if !args.yes && !confirm(&prompt)? {
+            return Ok(ExitCode::SUCCESS);
+        }
+
+        let (ns, resource, name, patch) = (&ns, &resource, &name, &patch);
+        let (mut rows, edited) = roll_out(
+            &args.rolling,
+            &clusterset.select_clusters(&targets)?,
+            |row: &EditOutput| row.result.health == Health::Healthy,
+            |clusters| async move {
+                let client = Client::try_new(&clusters, ns, resource).await?;
+                let responses = if strategic {
+                    client.patch_strategic(name, patch).await
+                } else {
+                    client.patch(name, patch).await
+                };
+                Ok(responses
+                    .into_iter()
+                    .map(|response| EditOutput {
+                        clustername: response.clustername,
+                        name: name.clone(),
+                        result: patch_status(response.result, "edited"),
+                    })
+                    .collect())
+            },
+        )
+        .await?;
+        rows.sort_by(|a, b| a.clustername.cmp(&b.clustername));
+        let object = format!("{}/{}", resource, name);
+        record_history(
+            rows.iter()
+                .map(|row| history_entry("edit", &row.clustername, &object, &row.result))
+                .collect(),
+        );
+        self.print_structured(rows)?;
+        Ok(if edited {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        })
+    }
+
+    pub async fn yaml(&self, args: &YamlArgs) -> Result<ExitCode> {
+        let object = ObjectRef::parse(&args.resource, args.name.as_deref())?;
+        let (clusterset, ns, ObjectRef { resource, name, .. }) = self.object_clusterset(object)?;
+        let client = Client::try_new(&clusterset.clusters, &ns, &resource).await?;
+        let kind = client.kinds.join(",");
+        let mut found: BTreeMap<String, DynamicObject> = BTreeMap::new();
+        for response in client.get(&name).await {
+            match response.result {
+                GetResult::Found(object) => {
+                    found.insert(response.clustername, *object);
+                }
+                GetResult::NotFound => {}
+                GetResult::Failed(e) => eprintln!("{}: {}", response.clustername, e),
+            }
+        }
+        let object = match &args.cluster {
+            Some(cluster) => found
+                .get(cluster)
+                .ok_or_else(|| anyhow!("{} {} not found on cluster {}", kind, name, cluster))?,
+            None => {
+                let clusters: Vec<&String> = found.keys().collect();
+                match clusters[..] {
+                    [] => return Err(anyhow!("{} {} not found on any cluster", kind, name)),
+                    [cluster] => &found[cluster],
+                    _ if io::stdin().is_terminal() => &found[choose_cluster(&clusters)?],
+                    _ => {
+                        return Err(anyhow!(
+                            "{} {} exists on {}, pick one with --cluster",
+                            kind,
+                            name,
+                            clusters
+                                .iter()
+                                .map(|c| c.as_str())
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ))
+                    }
+                }
+            }
+        };
+        let mut object = cleaned(object)?;
+        redact(&kind, &mut object);
+        print!("{}", serde_yaml::to_string(&object)?);
+        Ok(ExitCode::SUCCESS)
+    }
+
+    pub async fn logs(&self, args: &LogsArgs) -> Result<ExitCode> {
+        let (clusterset, ns) = self.active_clusterset()?;
+        let selector = match (&args.pod, &args.selector) {
+            (Some(pod), _) => PodSelector::Name(pod.clone()),
+            (None, Some(labels)) => PodSelector::Labels(labels.clone()),
+            (None, None) => return Err(anyhow!("a pod or --selector is required")),
+        };
+        let params = LogParams {
+            follow: args.follow,
+            tail_lines: args.tail,
+            ..Default::default()
+        };
+        let handles = serial::join_all(connect_all(&clusterset.clusters).await?.into_iter().map(
+            |(clustername, client)| {
+                let ns = clusterset.cluster_namespace(&clustername, &ns);
+                let selector = selector.clone();
+                let container = args.container.clone();
+                let params = params.clone();
+                tokio::spawn(async move {
+                    let streams = match client {
+                        Ok(client) => {
+                            let open = open_logs(
+                                &clustername,
+                                client,
+                                &ns,
+                                &selector,
+                                container.as_deref(),
+                                &params,
+                            );
+                            serial::phase(&clustername, "open logs", open).await
+                        }
+                        Err(e) => Err(e),
+                    };
+                    (clustername, streams)
+                })
+            },
+        ))
+        .await;
+
+        let mut streams = Vec::new();
+        let mut failed = false;
+        for handle in handles {
+            match handle {
+                Ok((_, Ok(opened))) => streams.extend(opened),
+                Ok((clustername, Err(e))) => {
+                    failed = true;
+                    eprintln!("{}: {}", clustername, describe_error(&e));
+                }
+                Err(e) => debug!("join failed {}", e),
+            }
+        }
+        if streams.is_empty() {
+            return Err(anyhow!("no matching pods found on any cluster"));
+        }
+
+        let mut lines = futures::stream::select_all(streams);
+        let mut mux = LogMultiplexer::new(args.rate_limit);
+        let mut stdout = io::stdout().lock();
+        loop {
+            let line = tokio::select! {
+                line = lines.next() => line,
+                _ = tokio::signal::ctrl_c() => None,
+            };
+            let Some(line) = line else {
+                break;
+            };
+            if mux.admit(&line.source, Instant::now()) {
+                writeln!(stdout, "{} {}", line.source, line.line)?;
+            }
+        }
+        drop(stdout);
+        for (clustername, dropped) in mux.dropped() {
+            eprintln!(
+                "{}: dropped {} lines over the rate limit of {} lines per second",
+                clustername,
+                dropped,
+                args.rate_limit.unwrap_or_default()
+            );
+        }
+        Ok(if failed {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
+        })
+    }
+
+    pub async fn exec(&self, args: &ExecArgs) -> Result<ExitCode> {
+        self.session(&args.session, SessionKind::Exec(args.command.clone()))
+            .await
+    }
+
+    pub async fn attach(&self, args: &SessionArgs) -> Result<ExitCode> {
+        self.session(args, SessionKind::Attach).await
+    }
+
+    async fn session(&self, args: &SessionArgs, kind: SessionKind) -> Result<ExitCode> {
+        let (clustername, client, ns) = self.find_pod(&args.pod, args.cluster.as_ref()).await?;
+        let recorder = match &args.record_session {
+            Some(path) => {
+                let info = SessionInfo {
+                    cluster: clustername.clone(),
+                    namespace: ns.clone(),
+                    pod: args.pod.clone(),
+                    container: args.container.clone(),
+                    user: std::env::var("USER").unwrap_or_default(),
+                };
+                Some(CastRecorder::create(path, &info, &kind)?)
+            }
+            None => None,
+        };
+        let tty = args.tty && io::stdin().is_terminal();
+        if args.tty && !tty {
+            eprintln!("not allocating a TTY, stdin is not a terminal");
+        }
+        // a TTY carries stderr along with stdout
+        let mut params = AttachParams::default()
+            .stdin(args.stdin)
+            .tty(tty)
+            .stderr(!tty);
+        if let Some(container) = &args.container {
+            params = params.container(container);
+        }
+        let pods: Api<Pod> = Api::namespaced(client, &ns);
+        let raw_mode = match tty {
+            true => Some(RawMode::enable()?),
+            false => None,
+        };
+        let session =
+            run_session(pods, &args.pod, &kind, params, recorder, args.record_input).await;
+        drop(raw_mode);
+        let code = session.with_context(|| format!("cluster {}", clustername))?;
+        Ok(ExitCode::from(u8::try_from(code).unwrap_or(1)))
+    }
+
+    pub async fn cp(&self, args: &CpArgs) -> Result<()> {
+        let copy = Copy::new(&args.source, &args.destination)?;
+        let (clustername, client, ns) = self.find_pod(copy.pod(), args.cluster.as_ref()).await?;
+        copy.run(client, &ns, args.container.as_deref())
+            .await
+            .with_context(|| format!("cluster {}", clustername))
+    }
+
+    // Finds the one cluster holding a pod, or the cluster given, returning its name, a client
+    // and the namespace of the pod
+    async fn find_pod(
+        &self,
+        pod: &str,
+        cluster: Option<&String>,
+    ) -> Result<(String, KubeClient, String)> {
+        let (clusterset, ns) = self.active_clusterset()?;
+        let names: Vec<String> = cluster.into_iter().cloned().collect();
+        let clusters = clusterset.select_clusters(&names)?;
+        let handles = serial::join_all(connect_all(&clusters).await?.into_iter().map(
+            |(clustername, client)| {
+                let ns = clusterset.cluster_namespace(&clustername, &ns);
+                let pod = pod.to_owned();
+                tokio::spawn(async move {
+                    let found = match client {
+                        Ok(client) => {
+                            let pods: Api<Pod> = Api::namespaced(client.clone(), &ns);
+                            serial::phase(&clustername, "find pod", pods.get_opt(&pod))
+                                .await
+                                .map(|found| found.map(|_| client))
+                                .map_err(anyhow::Error::from)
+                        }
+                        Err(e) => Err(e),
+                    };
+                    (clustername, found)
+                })
+            },
+        ))
+        .await;
+
+        let mut found = Vec::new();
+        for handle in handles {
+            match handle {
+                Ok((clustername, Ok(Some(client)))) => found.push((clustername, client)),
+                Ok((_, Ok(None))) => {}
+                Ok((clustername, Err(e))) => {
+                    warn!("cluster {}: {}", clustername, describe_error(&e))
+                }
+                Err(e) => debug!("join failed {}", e),
+            }
+        }
+        found.sort_by(|a, b| a.0.cmp(&b.0));
+        let (clustername, client) = match found.len() {
+            0 => return Err(anyhow!("pod {} not found on any cluster", pod)),
+            1 => found.remove(0),
+            _ => {
+                let clusters: Vec<&str> = found.iter().map(|(name, _)| name.as_str()).collect();
+                return Err(anyhow!(
+                    "pod {} found on clusters {}, pick one with --cluster",
+                    pod,
+                    clusters.join(", ")
+                ));
+            }
+        };
+        let ns = clusterset.cluster_namespace(&clustername, &ns);
+        Ok((clustername, client, ns))
+    }
+
+    pub async fn port_forward(&self, args: &PortForwardArgs) -> Result<()> {
+        let (clusterset, ns) = self.active_clusterset()?;
+        let names: Vec<String> = args.cluster.iter().cloned().collect();
+        let clusters = clusterset.select_clusters(&names)?;
+        let handles = serial::join_all(connect_all(&clusters).await?.into_iter().map(
+            |(clustername, client)| {
+                let ns = clusterset.cluster_namespace(&clustername, &ns);
+                let target = args.target.clone();
+                let ports = args.ports.clone();
+                tokio::spawn(async move {
+                    let forward = match client {
+                        Ok(client) => {
+                            let resolve =
+                                Forward::resolve(&clustername, client, &ns, &target, &ports);
+                            serial::phase(&clustername, "resolve target", resolve).await
+                        }
+                        Err(e) => Err(e),
+                    };
+                    (clustername, forward)
+                })
+            },
+        ))
+        .await;
+
+        let mut forwards = Vec::new();
+        for handle in handles {
+            match handle {
+                Ok((_, Ok(Some(forward)))) => forwards.push(forward),
+                Ok((_, Ok(None))) => {}
+                Ok((clustername, Err(e))) => {
+                    warn!("cluster {}: {}", clustername, describe_error(&e))
+                }
+                Err(e) => debug!("join failed {}", e),
+            }
+        }
+        forwards.sort_by(|a, b| a.clustername.cmp(&b.clustername));
+        match (forwards.len(), args.per_cluster_offset) {
+            (0, _) => return Err(anyhow!("{} not found on any cluster", args.target)),
+            (1, _) => {}
+            (_, Some(offset)) => {
+                for (i, forward) in forwards.iter_mut().enumerate() {
+                    let offset = u16::try_from(i)
+                        .ok()
+                        .and_then(|i| i.checked_mul(offset))
+                        .ok_or_else(|| anyhow!("--per-cluster-offset is too large"))?;
+                    forward.ports = forward
+                        .ports
+                        .iter()
+                        .map(|mapping| mapping.offset(offset))
+                        .collect::<Result<_>>()?;
+                }
+            }
+            (_, None) => {
+                let clusters: Vec<&str> = forwards.iter().map(|f| f.clustername.as_str()).collect();
+                return Err(anyhow!(
+                    "{} found on clusters {}, pick one with --cluster or use --per-cluster-offset",
+                    args.target,
+                    clusters.join(", ")
+                ));
+            }
+        }
+
+        for forward in &forwards {
+            forward.start().await?;
+        }
+        tokio::signal::ctrl_c().await?;
+        Ok(())
+    }
+
+    pub async fn rollout(&self, action: &RolloutAction) -> Result<ExitCode> {
+        match action {
+            RolloutAction::Status { target, timeout } => {
+                self.rollout_status(target, Duration::from_secs(*timeout))
+                    .await
+            }
+            RolloutAction::Restart { target, rolling } => {
+                self.rollout_restart(target, rolling).await
+            }
+            RolloutAction::History { target } => self.rollout_history(target).await,
+            RolloutAction::Timeline { target, width } => {
+                self.rollout_timeline(target, (*width).max(2)).await
+            }
+        }
+    }
+
+    // Polls the deployment on every cluster until each rollout finished or the timeout passed.
+    // On a terminal the table is redrawn in place after every poll, other output formats only
+    // print the final state.
+    async fn rollout_status(&self, target: &RolloutTarget, timeout: Duration) -> Result<ExitCode> {
+        let (clusterset, ns, ObjectRef { resource, name, .. }) =
+            self.object_clusterset(target.object()?)?;
+        let client = Client::try_new(&clusterset.clusters, &ns, &resource).await?;
+        if client.kinds != ["Deployment"] {
+            return Err(anyhow!("rollout status only supports deployments"));
+        }
+        let deadline = Instant::now() + timeout;
+        let live = io::stdout().is_terminal()
+            && matches!(self.output, OutputFormat::Table | OutputFormat::Wide);
+        let mut drawn_lines = 0;
+        loop {
+            let mut states: Vec<(String, RolloutState)> = client
+                .get(&name)
+                .await
+                .into_iter()
+                .map(|response| {
+                    let state = match response.result {
+                        GetResult::Found(object) => serde_json::to_value(*object)
+                            .and_then(serde_json::from_value::<Deployment>)
+                            .map_or_else(
+                                |e| RolloutState::Failed(e.to_string()),
+                                |deployment| deployment_rollout_state(&deployment),
+                            ),
+                        GetResult::NotFound => RolloutState::Failed("not found".into()),
+                        GetResult::Failed(e) => RolloutState::Failed(e),
+                    };
+                    (response.clustername, state)
+                })
+                .collect();
+            states.sort_by(|a, b| a.0.cmp(&b.0));
+            let finished = states.iter().all(|(_, state)| state.is_finished());
+            let done = finished || Instant::now() >= deadline;
+            let rows: Vec<RolloutOutput> = states
+                .iter()
+                .map(|(clustername, state)| RolloutOutput {
+                    clustername: clustername.clone(),
+                    name: name.clone(),
+                    status: state.status(),
+                })
+                .collect();
+            if live {
+                redraw_table(rows, &mut drawn_lines);
+            } else if done {
+                self.print_structured(rows)?;
+            }
+            if done {
+                let complete = states
+                    .iter()
+                    .all(|(_, state)| matches!(state, RolloutState::Complete(_)));
+                return Ok(if complete {
+                    ExitCode::SUCCESS
+                } else {
+                    ExitCode::FAILURE
+                });
+            }
+            tokio::time::sleep(ROLLOUT_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Creates objects from existing ones on the selected clusters, so far jobs from cronjobs
+    pub async fn create(&self, action: &CreateAction) -> Result<ExitCode> {
+        match action {
+            CreateAction::Job(args) => self.create_job(args).await,
+        }
+    }
+
+    async fn create_job(&self, args: &CreateJobArgs) -> Result<ExitCode> {
+        let from = ObjectRef::parse(&args.from, None)?;
+        if !from.is_kind("CronJob") {
+            return Err(anyhow!(
+                "jobs can only be created from cronjobs, expected --from=cronjob/NAME"
+            ));
+        }
+        let (clusterset, ns, ObjectRef { name: cronjob, .. }) = self.object_clusterset(from)?;
+        let names: Vec<String> = args.cluster.iter().cloned().collect();
+        let clusters = clusterset.select_clusters(&names)?;
+        let timeout = args
+            .timeout
+            .to_std()
+            .map_err(|_| anyhow!("the timeout cannot be negative"))?;
+        let job_name = args
+            .name
+            .clone()
+            .unwrap_or_else(|| manual_job_name(&cronjob, Utc::now()));
+        let cronjobs = Client::try_new(&clusters, &ns, "cronjobs.batch").await?;
+        let jobs = Client::try_new(&clusters, &ns, "jobs.batch").await?;
+
+        // every cluster runs the job of its own cronjob, which owns it there
+        let creations = cronjobs.get(&cronjob).await.into_iter().map(|response| {
+            let (jobs, job_name, cronjob) = (&jobs, &job_name, &cronjob);
+            async move {
+                let job = match response.result {
+                    GetResult::Found(object) => serde_json::to_value(*object)
+                        .and_then(serde_json::from_value::<CronJob>)
+                        .map_err(anyhow::Error::from)
+                        .and_then(|cronjob| job_from_cronjob(&cronjob, job_name))
+                        .and_then(|job| Ok(serde_json::from_value(serde_json::to_value(job)?)?)),
+                    GetResult::NotFound => Err(anyhow!("cronjob {} not found", cronjob)),
+                    GetResult::Failed(e) => Err(anyhow!(e)),
+                };
+                let result = match job {
+                    Ok(job) => jobs
+                        .create(&job, std::slice::from_ref(&response.clustername))
+                        .await
+                        .pop()
+                        .map_or_else(|| Err(anyhow!("cluster did not answer")), |r| r.1),
+                    Err(e) => Err(e),
+                };
+                (response.clustername, result)
+            }
+        });
+        let mut failed = Vec::new();
+        let mut created = Vec::new();
+        for (clustername, result) in serial::join_all(creations).await {
+            match result {
+                Ok(()) => created.push(clustername),
+                Err(e) => failed.push(CreateJobOutput {
+                    clustername,
+                    job: job_name.clone(),
+                    status: StatusCell::new(format!("failed: {}", e), Health::Unhealthy),
+                }),
+            }
+        }
+        let job = format!("job/{}", job_name);
+        record_history(
+            created
+                .iter()
+                .map(|clustername| Entry::new("create job", clustername, &job, "created", true))
+                .chain(
+                    failed.iter().map(|row| {
+                        history_entry("create job", &row.clustername, &job, &row.status)
+                    }),
+                )
+                .collect(),
+        );
+
+        if !args.wait {
+            let mut rows = failed.clone();
+            rows.extend(created.into_iter().map(|clustername| CreateJobOutput {
+                clustername,
+                job: job_name.clone(),
+                status: StatusCell::new("created", Health::Healthy),
+            }));
+            rows.sort_by(|a, b| a.clustername.cmp(&b.clustername));
+            self.print_structured(rows)?;
+            return Ok(if failed.is_empty() {
+                ExitCode::SUCCESS
+            } else {
+                ExitCode::FAILURE
+            });
+        }
+
+        let deadline = Instant::now() + timeout;
+        let live = io::stdout().is_terminal();
+        let mut drawn_lines = 0;
+        loop {
+            let states: Vec<(String, RolloutState)> = jobs
+                .get(&job_name)
+                .await
+                .into_iter()
+                .filter(|response| created.contains(&response.clustername))
+                .map(|response| {
+                    let state = match response.result {
+                        GetResult::Found(object) => serde_json::to_value(*object)
+                            .and_then(serde_json::from_value::<Job>)
+                            .map_or_else(
+                                |e| RolloutState::Failed(e.to_string()),
+                                |job| job_state(&job),
+                            ),
+                        GetResult::NotFound => RolloutState::Failed("deleted".into()),
+                        GetResult::Failed(e) => RolloutState::Progressing(e),
+                    };
+                    (response.clustername, state)
+                })
+                .collect();
+            let finished = states.iter().all(|(_, state)| state.is_finished());
+            let done = finished || Instant::now() >= deadline;
+            if live || done {
+                let mut rows = failed.clone();
+                rows.extend(states.iter().map(|(clustername, state)| CreateJobOutput {
+                    clustername: clustername.clone(),
+                    job: job_name.clone(),
+                    status: state.status(),
+                }));
+                rows.sort_by(|a, b| a.clustername.cmp(&b.clustername));
+                redraw_table(rows, &mut drawn_lines);
+            }
+            if done {
+                if !finished {
+                    eprintln!(
+                        "timed out after {}s waiting for job {}",
+                        timeout.as_secs(),
+                        job_name
+                    );
+                }
+                let complete = states
+                    .iter()
+                    .all(|(_, state)| matches!(state, RolloutState::Complete(_)));
+                return Ok(if failed.is_empty() && finished && complete {
+                    ExitCode::SUCCESS
+                } else {
+                    ExitCode::FAILURE
+                });
+            }
+            tokio::time::sleep(ROLLOUT_POLL_INTERVAL.min(deadline - Instant::now())).await;
+        }
+    }
+
+    /// Polls the object on every cluster until enough clusters meet the condition or the timeout
+    /// passed, redrawing the progress table in place on a terminal like rollout status
+    pub async fn wait(&self, args: &WaitArgs) -> Result<ExitCode> {
+        let object = ObjectRef::parse(&args.resource, args.name.as_deref())?;
+        let (clusterset, ns, ObjectRef { resource, name, .. }) = self.object_clusterset(object)?;
+        let client = Client::try_new(&clusterset.clusters, &ns, &resource).await?;
+        let timeout = args
+            .timeout
+            .to_std()
+            .map_err(|_| anyhow!("the timeout cannot be negative"))?;
+        let deadline = Instant::now() + timeout;
+        let live = io::stdout().is_terminal();
+        let mut drawn_lines = 0;
+        loop {
+            let mut states: Vec<(String, WaitState)> = client
+                .get(&name)
+                .await
+                .into_iter()
+                .map(|response| (response.clustername, args.condition.state(response.result)))
+                .collect();
+            states.sort_by(|a, b| a.0.cmp(&b.0));
+            let met = if args.any {
+                states.iter().any(|(_, state)| state.is_met())
+            } else {
+                states.iter().all(|(_, state)| state.is_met())
+            };
+            let done = met || Instant::now() >= deadline;
+            if live || done {
+                let rows: Vec<WaitOutput> = states
+                    .iter()
+                    .map(|(clustername, state)| WaitOutput {
+                        clustername: clustername.clone(),
+                        name: name.clone(),
+                        status: state.status(),
+                    })
+                    .collect();
+                redraw_table(rows, &mut drawn_lines);
+            }
+            if done {
+                let cases: Vec<TestCase> = states
+                    .iter()
+                    .map(|(clustername, state)| {
+                        let failure = (!state.is_met()).then(|| state.status().text);
+                        TestCase::new(clustername, &name, failure)
+                    })
+                    .collect();
+                self.write_report("wait", &cases)?;
+                if !met {
+                    eprintln!(
+                        "timed out after {}s waiting for {}",
+                        timeout.as_secs(),
+                        name
+                    );
+                }
+                return Ok(if met {
+                    ExitCode::SUCCESS
+                } else {
+                    ExitCode::FAILURE
+                });
+            }
+            tokio::time::sleep(ROLLOUT_POLL_INTERVAL.min(deadline - Instant::now())).await;
+        }
+    }
+
+    async fn rollout_restart(
+        &self,
+        target: &RolloutTarget,
+        rolling: &RollingArgs,
+    ) -> Result<ExitCode> {
+        let (clusterset, ns, ObjectRef { resource, name, .. }) =
+            self.object_clusterset(target.object()?)?;
+        let patch = &restart_patch(Utc::now());
+        let (ns, resource, name) = (&ns, &resource, &name);
+        let (mut rows, restarted) = roll_out(
+            rolling,
+            &clusterset.clusters,
+            |row: &RolloutOutput| row.status.health == Health::Healthy,
+            |clusters| async move {
+                let client = Client::try_new(&clusters, ns, resource).await?;
+                if let Some(kind) = client
+                    .kinds
+                    .iter()
+                    .find(|kind| !RESTARTABLE_KINDS.contains(&kind.as_str()))
+                {
+                    return Err(anyhow!("rollout restart does not support {}", kind));
+                }
+                Ok(client
+                    .patch(name, patch)
+                    .await
+                    .into_iter()
+                    .map(|response| RolloutOutput {
+                        clustername: response.clustername,
+                        name: name.clone(),
+                        status: patch_status(response.result, "restarted"),
+                    })
+                    .collect())
+            },
+        )
+        .await?;
+        rows.sort_by(|a, b| a.clustername.cmp(&b.clustername));
+        let object = format!("{}/{}", resource, name);
+        record_history(
+            rows.iter()
+                .map(|row| history_entry("rollout restart", &row.clustername, &object, &row.status))
+                .collect(),
+        );
+        self.print_structured(rows)?;
+        Ok(if restarted {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        })
+    }
+
+    async fn rollout_history(&self, target: &RolloutTarget) -> Result<ExitCode> {
+        let (clusterset, ns, ObjectRef { resource, name, .. }) =
+            self.object_clusterset(target.object()?)?;
+        let client = Client::try_new(&clusterset.clusters, &ns, &resource).await?;
+        if client.kinds != ["Deployment"] {
+            return Err(anyhow!("rollout history only supports deployments"));
+        }
+        let mut uids = Vec::new();
+        let mut failed = false;
+        for response in client.get(&name).await {
+            match response.result {
+                GetResult::Found(object) => {
+                    if let Some(uid) = object.uid() {
+                        uids.push((response.clustername, uid));
+                    }
+                }
+                GetResult::NotFound => {
+                    eprintln!("{}: deployment {} not found", response.clustername, name)
+                }
+                GetResult::Failed(e) => {
+                    failed = true;
+                    eprintln!("{}: {}", response.clustername, e)
+                }
+            }
+        }
+        let clusters = clusterset.select_clusters(
+            &uids
+                .iter()
+                .map(|(clustername, _)| clustername.clone())
+                .collect::<Vec<_>>(),
+        )?;
+        if clusters.is_empty() {
+            return Ok(ExitCode::FAILURE);
+        }
+        let listing = Client::try_new(&clusters, &ns, "replicasets")
+            .await?
+            .list()
+            .await?;
+        for failure in &listing.failures {
+            failed = true;
+            eprintln!("{}: {}", failure.clustername, failure.error);
+        }
+        let mut rows = Vec::new();
+        for (clustername, uid) in &uids {
+            let replicasets: Vec<ReplicaSet> = listing.objects(clustername);
+            for revision in revisions(uid, &replicasets) {
+                rows.push(RevisionOutput::new(clustername, &revision));
+            }
+        }
+        rows.sort_by(|a, b| (&a.clustername, a.revision).cmp(&(&b.clustername, b.revision)));
+        self.print_structured(rows)?;
+        Ok(if failed {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
+        })
+    }
+
+    async fn rollout_timeline(&self, target: &RolloutTarget, width: usize) -> Result<ExitCode> {
+        let (clusterset, ns, ObjectRef { resource, name, .. }) =
+            self.object_clusterset(target.object()?)?;
+        let client = Client::try_new(&clusterset.clusters, &ns, &resource).await?;
+        if client.kinds != ["Deployment"] {
+            return Err(anyhow!("rollout timeline only supports deployments"));
+        }
+        let mut uids = Vec::new();
+        let mut failed = false;
+        for response in client.get(&name).await {
+            match response.result {
+                GetResult::Found(object) => {
+                    if let Some(uid) = object.uid() {
+                        uids.push((response.clustername, uid));
+                    }
+                }
+                GetResult::NotFound => {
+                    eprintln!("{}: deployment {} not found", response.clustername, name)
+                }
+                GetResult::Failed(e) => {
+                    failed = true;
+                    eprintln!("{}: {}", response.clustername, e)
+                }
+            }
+        }
+        let clusters = clusterset.select_clusters(
+            &uids
+                .iter()
+                .map(|(clustername, _)| clustername.clone())
+                .collect::<Vec<_>>(),
+        )?;
+        if clusters.is_empty() {
+            return Ok(ExitCode::FAILURE);
+        }
+        let replicasets = Client::try_new(&clusters, &ns, "replicasets")
+            .await?
+            .list()
+            .await?;
+        let pods = Client::try_new(&clusters, &ns, "pods")
+            .await?
+            .list()
+            .await?;
+        for failure in replicasets.failures.iter().chain(&pods.failures) {
+            failed = true;
+            eprintln!("{}: {}", failure.clustername, failure.error);
+        }
+
+        // the pods of the newest ReplicaSet per cluster, timed from when the rollout created it
+        let mut timelines = Vec::new();
+        for (clustername, uid) in &uids {
+            let cluster_replicasets: Vec<ReplicaSet> = replicasets.objects(clustername);
+            let Some(newest) = newest_replicaset(uid, &cluster_replicasets) else {
+                eprintln!("{}: deployment {} has no replicaset", clustername, name);
+                continue;
+            };
+            let readiness = replicaset_readiness(newest, &pods.objects::<Pod>(clustername));
+            let started = newest
+                .creation_timestamp()
+                .map(|time| time.0)
+                .or_else(|| readiness.first().map(|pod| pod.created))
+                .unwrap_or_else(Utc::now);
+            timelines.push((clustername.clone(), started, readiness));
+        }
+        let now = Utc::now();
+        let span = timelines
+            .iter()
+            .flat_map(|(_, started, readiness)| {
+                readiness
+                    .iter()
+                    .map(move |pod| pod.ready.unwrap_or(now) - *started)
+            })
+            .max()
+            .unwrap_or_else(chrono::Duration::zero);
+        let fleet: Vec<chrono::Duration> = timelines
+            .iter()
+            .flat_map(|(_, _, readiness)| readiness.iter().filter_map(|p| p.time_to_ready()))
+            .collect();
+        let fleet_median = median(&fleet);
+
+        let mut rows = Vec::new();
+        let mut summary = Vec::new();
+        for (clustername, started, readiness) in &timelines {
+            for pod in readiness {
+                let created = pod.created - *started;
+                let ready = pod.ready.map(|ready| ready - *started);
+                rows.push(TimelineOutput {
+                    clustername: clustername.clone(),
+                    pod: pod.pod.clone(),
+                    created: format!("+{}", format_duration(created)),
+                    ready: ready.map_or_else(
+                        || "<not ready>".into(),
+                        |ready| format!("+{}", format_duration(ready)),
+                    ),
+                    timeline: timeline_bar(created, ready, span, width),
+                });
+            }
+            let to_ready: Vec<chrono::Duration> =
+                readiness.iter().filter_map(|p| p.time_to_ready()).collect();
+            let cluster_median = median(&to_ready);
+            let slow = cluster_median
+                .zip(fleet_median)
+                .and_then(|(cluster, fleet)| slowdown(cluster, fleet));
+            let status = match slow {
+                Some(factor) => StatusCell::new(
+                    format!("slow: {:.1}x the fleet median", factor),
+                    Health::Unhealthy,
+                ),
+                None if to_ready.len() < readiness.len() => StatusCell::new(
+                    format!("{} not ready", readiness.len() - to_ready.len()),
+                    Health::Progressing,
+                ),
+                None => StatusCell::new("ok", Health::Healthy),
+            };
+            summary.push(ReadinessOutput {
+                clustername: clustername.clone(),
+                ready: format!("{}/{}", to_ready.len(), readiness.len()),
+                median: cluster_median.map(format_duration).unwrap_or_default(),
+                max: to_ready
+                    .iter()
+                    .max()
+                    .copied()
+                    .map(format_duration)
+                    .unwrap_or_default(),
+                status,
+            });
+        }
+        rows.sort_by(|a, b| a.clustername.cmp(&b.clustername));
+        summary.sort_by(|a, b| a.clustername.cmp(&b.clustername));
+        if !self.output.is_tabular() {
+            self.print_structured(rows)?;
+        } else {
+            print_rows(self.output, rows);
+            println!();
+            print_rows(self.output, summary);
+        }
+        Ok(if failed {
+            ExitCode::FAILURE
+        } else {
+            ExitCode::SUCCESS
+        })
+    }
+
+    /// Whether the config opts in to recording command latencies
+    pub fn telemetry_enabled(&self) -> bool {
+        Config::load_config(self.config_file.as_ref()).is_ok_and(|config| config.telemetry)
+    }
+
+    /// Where to send a trace of the command, None unless a collector is configured
+    pub fn otlp_endpoint(&self) -> Option<String> {
+        let configured = Config::load_config(self.config_file.as_ref())
+            .ok()
+            .and_then(|config| config.otlp_endpoint);
+        otlp::traces_endpoint(configured.as_deref())
+    }
+
+    pub fn telemetry_stats(&self, days: i64) -> Result<()> {
+        let records = telemetry::read()?;
+        if records.is_empty() && !self.telemetry_enabled() {
+            eprintln!("no telemetry recorded, set `telemetry: true` in the kubemc config to record command latencies");
+            return Ok(());
+        }
+        let rows = telemetry::summarize(&records, Utc::now() - chrono::Duration::days(days));
+        self.print_structured(rows)?;
+        Ok(())
+    }
+
+    pub fn history(&self, since: Option<chrono::Duration>, cluster: Option<&str>) -> Result<()> {
+        let entries = history::select(
+            history::read()?,
+            since.map(|since| Utc::now() - since),
+            cluster,
+        );
+        if entries.is_empty() {
+            eprintln!("no changes recorded");
+            return Ok(());
+        }
+        let rows: Vec<HistoryOutput> = entries.iter().map(HistoryOutput::from).collect();
+        self.print_structured(rows)?;
+        Ok(())
+    }
+
+    pub async fn stats(&self) -> Result<ExitCode> {
+        let (clusterset, _) = self.active_clusterset()?;
+        let handles = serial::join_all(connect_all(&clusterset.clusters).await?.into_iter().map(
+            |(clustername, client)| {
+                tokio::spawn(async move {
+                    let stats = match client {
+                        Ok(client) => {
+                            serial::phase(&clustername, "read stats", ClusterStats::fetch(client))
+                                .await
+                        }
+                        Err(e) => Err(e),
+                    };
+                    (clustername, stats)
+                })
+            },
+        ))
+        .await;
+
+        let mut stats = Vec::new();
+        let mut errors = Vec::new();
+        for handle in handles {
+            match handle {
+                Ok((clustername, Ok(cluster_stats))) => stats.push((clustername, cluster_stats)),
+                Ok((clustername, Err(e))) => errors.push(ErrorOutput {
+                    clustername,
+                    error: describe_error(&e),
+                }),
+                Err(e) => debug!("join failed {}", e),
+            }
+        }
+        stats.sort_by(|a, b| a.0.cmp(&b.0));
+        errors.sort_by(|a, b| a.clustername.cmp(&b.clustername));
+
+        let total = stats
+            .iter()
+            .fold(ClusterStats::default(), |total, (_, s)| total.add(s));
+        let outputs: Vec<StatsOutput> = stats
+            .iter()
+            .map(|(clustername, s)| StatsOutput::new(clustername, s))
+            .collect();
+        // the total is a footer in tables and a field of its own in structured output, never a
+        // row that could be taken for a cluster
+        let report = StatsReport {
+            clusters: outputs.clone(),
+            total: StatsCounts::new(&total),
+        };
+        match self.output {
+            OutputFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+            OutputFormat::Plugin => print_plugin(&report)?,
+            OutputFormat::Jsonl => print_json_lines(&outputs)?,
+            OutputFormat::Yaml => print!("{}", serde_yaml::to_string(&report)?),
+            OutputFormat::Table | OutputFormat::Wide | OutputFormat::Csv => print_rows_with_footer(
+                self.output,
+                outputs,
+                StatsOutput::new(StatsOutput::TOTAL, &total),
+            ),
+        }
+        for error in &errors {
+            eprintln!("{}: {}", error.clustername, error.error);
+        }
+        if errors.is_empty() {
+            Ok(ExitCode::SUCCESS)
+        } else {
+            Ok(ExitCode::FAILURE)
+        }
+    }
+
+    pub async fn audit(&self, action: &AuditAction) -> Result<ExitCode> {
+        match action {
+            AuditAction::ImagePull { all_namespaces } => {
+                let (clusterset, namespace) = self.audit_scope(*all_namespaces)?;
+                let (audits, errors) =
+                    audit_clusters(&clusterset, namespace, image_pull_audit).await?;
+                let summary: Vec<ImageAuditOutput> = audits
+                    .iter()
+                    .map(|(clustername, (workloads, findings))| {
+                        ImageAuditOutput::new(clustername, *workloads, findings)
+                    })
+                    .collect();
+                let findings: Vec<ImageFindingOutput> = audits
+                    .into_iter()
+                    .flat_map(|(clustername, (_, findings))| {
+                        findings
+                            .into_iter()
+                            .map(move |finding| ImageFindingOutput::new(&clustername, finding))
+                    })
+                    .collect();
+                self.print_audit(findings, summary, &errors)
+            }
+            AuditAction::Security { all_namespaces } => {
+                let (clusterset, namespace) = self.audit_scope(*all_namespaces)?;
+                let (audits, errors) =
+                    audit_clusters(&clusterset, namespace, security_audit).await?;
+                let summary: Vec<SecurityAuditOutput> = audits
+                    .iter()
+                    .flat_map(|(clustername, (workloads, findings))| {
+                        SecurityAuditOutput::by_namespace(clustername, workloads, findings)
+                    })
+                    .collect();
+                let mut findings: Vec<SecurityFindingOutput> = audits
+                    .into_iter()
+                    .flat_map(|(clustername, (_, findings))| {
+                        findings
+                            .into_iter()
+                            .map(move |finding| SecurityFindingOutput::new(&clustername, finding))
+                    })
+                    .collect();
+                findings.sort_by(|a, b| {
+                    (&a.clustername, &a.namespace).cmp(&(&b.clustername, &b.namespace))
+                });
+                self.print_audit(findings, summary, &errors)
+            }
+            AuditAction::Scheduling { deployment } => self.audit_scheduling(deployment).await,
+        }
+    }
+
+    async fn audit_scheduling(&self, deployment: &str) -> Result<ExitCode> {
+        let object = if deployment.contains('/') {
+            deployment.parse()?
+        } else {
+            ObjectRef::parse("deployment", Some(deployment))?
+        };
+        let (clusterset, ns, ObjectRef { resource, name, .. }) = self.object_clusterset(object)?;
+        let client = Client::try_new(&clusterset.clusters, &ns, &resource).await?;
+        if client.kinds != ["Deployment"] {
+            return Err(anyhow!("audit scheduling only supports deployments"));
+        }
+        let audit = move |client, _| scheduling_audit(client, ns.clone(), name.clone());
+        let (reports, errors) = audit_clusters(&clusterset, None, audit).await?;
+        // a cluster without the deployment has nothing that could be scheduled
+        let schedulable = reports
+            .iter()
+            .all(|(_, report)| report.as_ref().is_some_and(|r| r.schedulable > 0));
+        let outputs: Vec<SchedulingOutput> = reports
+            .into_iter()
+            .map(|(clustername, report)| SchedulingOutput::new(&clustername, report))
+            .collect();
+        self.print_structured(outputs)?;
+        for error in &errors {
+            eprintln!("{}: {}", error.clustername, error.error);
+        }
+        if schedulable && errors.is_empty() {
+            Ok(ExitCode::SUCCESS)
+        } else {
+            Ok(ExitCode::FAILURE)
+        }
+    }
+
+    // The clusterset to audit along with the namespace, None when auditing every namespace
+    fn audit_scope(&self, all_namespaces: bool) -> Result<(Clusterset, Option<String>)> {
+        let (clusterset, ns) = self.active_clusterset()?;
+        Ok((clusterset, (!all_namespaces).then_some(ns)))
+    }
+
+    // Prints audit findings followed by the summary table, failing when anything was found or
+    // a cluster could not be audited. Structured output only holds the findings.
+    fn print_audit<F, S>(
+        &self,
+        findings: Vec<F>,
+        summary: Vec<S>,
+        errors: &[ErrorOutput],
+    ) -> Result<ExitCode>
+    where
+        F: Tabled + Serialize,
+        S: Tabled,
+    {
+        let found = !findings.is_empty();
+        if !self.output.is_tabular() {
+            self.print_structured(findings)?;
+        } else {
+            if found {
+                print_rows(self.output, findings);
+                println!();
+            }
+            print_rows(self.output, summary);
+        }
+        for error in errors {
+            eprintln!("{}: {}", error.clustername, error.error);
+        }
+        if found || !errors.is_empty() {
+            Ok(ExitCode::FAILURE)
+        } else {
+            Ok(ExitCode::SUCCESS)
+        }
+    }
+
+    // What the pods on each node reserve per cluster, for the pod counts of node rows and the
+    // node columns of wide output. Clusters whose pods cannot be listed are left out and show
+    // their nodes' pods and requests as unknown
+    async fn node_requests(
+        &self,
+        clusterset: &Clusterset,
+    ) -> Result<BTreeMap<String, BTreeMap<String, PodResources>>> {
+        let (requests, errors) = audit_clusters(clusterset, None, node_requests).await?;
+        for error in errors {
+            eprintln!(
+                "{}: failed to list pods for node requests: {}",
+                error.clustername, error.error
+            );
+        }
+        Ok(requests.into_iter().collect())
+    }
+
+    pub fn cache(&self, action: &CacheAction) -> Result<()> {
+        let dirs = CacheDirs::home()?;
+        let now = SystemTime::now();
+        match action {
+            CacheAction::Info => {
+                let owners = self.cache_owners();
+                let mut rows: Vec<CacheOutput> = dirs
+                    .scan()
+                    .into_iter()
+                    .map(|entry| {
+                        let age = entry.age(now);
+                        let ttl = match entry.kind {
+                            CacheKind::Discovery => Some(CACHE_TTL),
+                            CacheKind::Completion => Some(COMPLETION_TTL),
+                            _ => None,
+                        };
+                        let status = match ttl {
+                            Some(ttl) if age > ttl => StatusCell::new("stale", Health::Progressing),
+                            Some(_) => StatusCell::new("fresh", Health::Healthy),
+                            None => StatusCell::default(),
+                        };
+                        let clustername = owners
+                            .get(&(entry.kind == CacheKind::Discovery, entry.owner.clone()))
+                            .map(|names| names.join(","))
+                            .unwrap_or_else(|| "-".into());
+                        CacheOutput {
+                            clustername,
+                            cache: entry.kind.to_string(),
+                            name: entry.owner,
+                            size: format_size(entry.size),
+                            age: chrono::Duration::from_std(age)
+                                .map(format_duration)
+                                .unwrap_or_default(),
+                            status,
+                        }
+                    })
+                    .collect();
+                rows.sort_by(|a, b| (&a.clustername, &a.cache).cmp(&(&b.clustername, &b.cache)));
+                self.print_structured(rows)?;
+            }
+            CacheAction::Clean { older_than } => {
+                let mut entries = dirs.scan();
+                if let Some(older_than) = older_than {
+                    let age = older_than
+                        .to_std()
+                        .map_err(|_| anyhow!("--older-than cannot be negative"))?;
+                    entries = entries_older_than(entries, age, now);
+                }
+                let (mut removed, mut freed) = (0, 0);
+                for entry in entries {
+                    match entry.remove() {
+                        Ok(()) => {
+                            removed += 1;
+                            freed += entry.size;
+                        }
+                        Err(e) => eprintln!("{:#}", e),
+                    }
+                }
+                println!(
+                    "removed {} cache {}, freeing {}",
+                    removed,
+                    if removed == 1 { "entry" } else { "entries" },
+                    format_size(freed)
+                );
+            }
+        }
+        Ok(())
+    }
+
+    // Names of the configured clusters by what their cache entries are named after: the API
+    // server for discovery, the cluster name for everything else
+    fn cache_owners(&self) -> BTreeMap<(bool, String), Vec<String>> {
+        let mut owners: BTreeMap<(bool, String), Vec<String>> = BTreeMap::new();
+        let Ok(config) = Config::load_config(self.config_file.as_ref()) else {
+            return owners;
+        };
+        let mut clusters: Vec<Cluster> = Vec::new();
+        for cluster in config.clustersets.iter().flat_map(|cs| &cs.clusters) {
+            if !clusters.iter().any(|c| c.name == cluster.name) {
+                clusters.push(cluster.clone());
+            }
+        }
+        for mut cluster in clusters {
+            self.apply_cluster_flags(&mut cluster);
+            if let Ok(host) = discovery_cache_name(&cluster) {
+                let names = owners.entry((true, host)).or_default();
+                if !names.contains(&cluster.name) {
+                    names.push(cluster.name.clone());
+                }
+            }
+            owners
+                .entry((false, index::file_name(&cluster.name)))
+                .or_default()
+                .push(cluster.name);
+        }
+        owners
+    }
+
+    pub async fn leaders(&self) -> Result<ExitCode> {
+        let (clusterset, ns) = self.active_clusterset()?;
+        let (leases, errors) = audit_clusters(&clusterset, Some(ns), |client, ns| async move {
+            let leases: Api<Lease> = Api::namespaced(client, ns.as_deref().unwrap_or_default());
+            Ok(leases.list(&ListParams::default()).await?.items)
+        })
+        .await?;
+        let now = Utc::now();
+        let outputs: Vec<LeaderOutput> = leases
+            .into_iter()
+            .flat_map(|(clustername, leases)| {
+                leases
+                    .into_iter()
+                    .map(move |lease| LeaderOutput::new(&clustername, lease, now))
+            })
+            .collect();
+        self.print_structured(outputs)?;
+        for error in &errors {
+            eprintln!("{}: {}", error.clustername, error.error);
+        }
+        if errors.is_empty() {
+            Ok(ExitCode::SUCCESS)
+        } else {
+            Ok(ExitCode::FAILURE)
+        }
+    }
+
+    // Watches the EndpointSlices and Endpoints of a service on every cluster. On a terminal the
+    // table is redrawn in place every second so the time since the last change keeps counting,
+    // otherwise it is printed again on every change.
+    pub async fn failover_watch(&self, service: &str) -> Result<ExitCode> {
+        let object = if service.contains('/') {
+            service.parse()?
+        } else {
+            ObjectRef {
+                namespace: None,
+                resource: "services".into(),
+                name: service.to_owned(),
+            }
+        };
+        if !object.is_kind("Service") {
+            return Err(anyhow!("failover-watch only supports services"));
+        }
+        let (clusterset, ns, ObjectRef { name, .. }) = self.object_clusterset(object)?;
+        let mut backends: BTreeMap<String, ServiceBackends> = BTreeMap::new();
+        let mut watches = Vec::new();
+        for (clustername, client) in connect_all(&clusterset.clusters).await? {
+            let state = backends.entry(clustername.clone()).or_default();
+            let client = match client {
+                Ok(client) => client,
+                Err(e) => {
+                    state.error = Some(describe_error(&e));
+                    continue;
+                }
+            };
+            let ns = clusterset.cluster_namespace(&clustername, &ns);
+            let slices = watcher::watcher(
+                Api::<EndpointSlice>::namespaced(client.clone(), &ns),
+                watcher::Config::default().labels(&format!("{}={}", SERVICE_NAME_LABEL, name)),
+            )
+            .default_backoff()
+            .map(BackendUpdate::Slices);
+            let endpoints = watcher::watcher(
+                Api::<Endpoints>::namespaced(client, &ns),
+                watcher::Config::default().fields(&format!("metadata.name={}", name)),
+            )
+            .default_backoff()
+            .map(BackendUpdate::Endpoints);
+            watches.push(
+                futures::stream::select(slices, endpoints)
+                    .map(move |update| (clustername.clone(), update))
+                    .boxed(),
+            );
+        }
+
+        // like rollout status only a table on a terminal is redrawn, anything else is printed
+        // again on every change
+        let live = io::stdout().is_terminal()
+            && matches!(self.output, OutputFormat::Table | OutputFormat::Wide);
+        let mut drawn_lines = 0;
+        let mut updates = futures::stream::select_all(watches);
+        let mut tick = tokio::time::interval(Duration::from_secs(1));
+        let mut changed = true;
+        loop {
+            if changed {
+                let now = Utc::now();
+                let rows: Vec<FailoverOutput> = backends
+                    .iter()
+                    .map(|(clustername, state)| FailoverOutput::new(clustername, &name, state, now))
+                    .collect();
+                if live {
+                    redraw_table(rows, &mut drawn_lines);
+                } else {
+                    self.print_structured(rows)?;
+                }
+            }
+            changed = tokio::select! {
+                update = updates.next(), if !updates.is_empty() => match update {
+                    Some((clustername, update)) => backends
+                        .entry(clustername)
+                        .or_default()
+                        .apply(update, Utc::now()),
+                    None => false,
+                },
+                _ = tick.tick(), if live => true,
+                _ = tokio::signal::ctrl_c() => break,
+            };
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+
+    pub async fn api_services(&self, unavailable: bool) -> Result<ExitCode> {
+        let (clusterset, _) = self.active_clusterset()?;
+        let (api_services, errors) = audit_clusters(&clusterset, None, |client, _| async move {
+            let api_services = Api::<APIService>::all(client)
+                .list(&ListParams::default())
+                .await?;
+            Ok(api_services.items)
+        })
+        .await?;
+        let outputs: Vec<ApiServiceOutput> = api_services
+            .into_iter()
+            .flat_map(|(clustername, api_services)| {
+                api_services
+                    .into_iter()
+                    .map(move |api_service| ApiServiceOutput::new(&clustername, api_service))
+            })
+            .filter(|output| !unavailable || !output.is_available())
+            .collect();
+        let available = outputs.iter().all(ApiServiceOutput::is_available);
+        self.print_structured(outputs)?;
+        for error in &errors {
+            eprintln!("{}: {}", error.clustername, error.error);
+        }
+        if available && errors.is_empty() {
+            Ok(ExitCode::SUCCESS)
+        } else {
+            Ok(ExitCode::FAILURE)
+        }
+    }
+
+    pub async fn api_resources(&self, refresh: bool, missing: bool) -> Result<()> {
+        let (clusterset, _) = self.active_clusterset()?;
+        let mut discoveries = Vec::new();
+        let mut errors = Vec::new();
+        for (clustername, discovery) in discover(&clusterset.clusters, refresh).await? {
+            match discovery {
+                Ok((discovery, source)) => {
+                    if let DiscoverySource::Refreshed = source {
+                        debug!("refreshed discovery for cluster {}", clustername);
+                    }
+                    discoveries.push((clustername, discovery))
+                }
+                Err(e) => errors.push(ErrorOutput {
+                    clustername,
+                    error: describe_error(&e),
+                }),
+            }
+        }
+        discoveries.sort_by(|a, b| a.0.cmp(&b.0));
+        errors.sort_by(|a, b| a.clustername.cmp(&b.clustername));
+
+        let mut outputs = api_resource_union(&discoveries);
+        if missing {
+            outputs.retain(|output| !output.missing.is_empty());
+        }
+        self.print_structured(outputs)?;
+        if !errors.is_empty() {
+            println!();
+            create_table(errors);
+        }
+        Ok(())
+    }
+
+    pub async fn verify_cluster(&self, name: &str, checklist: &Path) -> Result<ExitCode> {
+        let checklist = Checklist::load(checklist)?;
+        // clusters under maintenance or disabled are still checked, they may be waiting on this
+        // very check
+        let config = Config::load_config(self.config_file.as_ref())?;
+        let mut cluster = config
+            .active_clusterset()?
+            .clusters
+            .iter()
+            .find(|cluster| cluster.name == name)
+            .cloned()
+            .unwrap_or_else(|| Cluster {
+                name: name.to_owned(),
+                context: Some(name.to_owned()),
+                ..Default::default()
+            });
+        self.apply_cluster_flags(&mut cluster);
+        cluster.disabled = false;
+        let clusters = [cluster];
+
+        // resources are resolved before any check runs, so that a checklist asking for the
+        // readiness of a kind without one is rejected up front. A resource the cluster does not
+        // serve fails its check only.
+        let mut clients = Vec::new();
+        for check in &checklist.checks {
+            let ns = check.namespace.as_deref().unwrap_or("default");
+            let client = match Client::try_new(&clusters, ns, &check.resource).await {
+                Ok(client) => client,
+                Err(e) => {
+                    clients.push(Err(e.to_string()));
+                    continue;
+                }
+            };
+            let formatter = client
+                .resources()
+                .first()
+                .map_or(Formatter::Default, Formatter::for_resource);
+            if check.ready && !formatter.has_health() {
+                return Err(anyhow!(
+                    "check {}: {} have no readiness to check, remove `ready`",
+                    check.name,
+                    check.resource
+                ));
+            }
+            clients.push(Ok((client, formatter)));
+        }
+
+        let mut outputs = Vec::new();
+        for (check, client) in checklist.checks.iter().zip(clients) {
+            let (client, formatter) = match client {
+                Ok(client) => client,
+                Err(e) => {
+                    outputs.push(CheckOutput::new(&check.name, false, e));
+                    continue;
+                }
+            };
+            let objects = match &check.object {
+                Some(object) => match client.get(object).await.pop().map(|r| r.result) {
+                    Some(GetResult::Found(object)) => Ok(vec![*object]),
+                    Some(GetResult::NotFound) | None => Ok(Vec::new()),
+                    Some(GetResult::Failed(e)) => Err(e),
+                },
+                None => {
+                    let listing = client.list().await?;
+                    match listing.failures.into_iter().next() {
+                        Some(failure) => Err(failure.error),
+                        None => Ok(listing
+                            .responses
+                            .into_iter()
+                            .flat_map(|lr| lr.object_list.items)
+                            .collect()),
+                    }
+                }
+            };
+            let (passed, detail) = match objects {
+                Ok(objects) => check.evaluate(formatter, objects),
+                Err(e) => (false, e),
+            };
+            outputs.push(CheckOutput::new(&check.name, passed, detail));
+        }
+        let cases: Vec<TestCase> = outputs
+            .iter()
+            .map(|o| {
+                let failure = (o.result.health != Health::Healthy).then(|| o.detail.clone());
+                TestCase::new(name, &o.check, failure)
+            })
+            .collect();
+        self.write_report("verify-cluster", &cases)?;
+
+        let passed = outputs.iter().all(|o| o.result.health == Health::Healthy);
+        self.print_structured(outputs)?;
+        if passed {
+            Ok(ExitCode::SUCCESS)
+        } else {
+            Ok(ExitCode::FAILURE)
+        }
+    }
+
+    pub async fn index(&self, action: &IndexAction) -> Result<ExitCode> {
+        match action {
+            IndexAction::Build { every } => self.build_index(*every).await,
+            IndexAction::Names { resource, prefix } => self.index_names(resource, prefix).await,
+        }
+    }
+
+    // Indexes every cluster in parallel, then again after `every` until interrupted
+    async fn build_index(&self, every: Option<chrono::Duration>) -> Result<ExitCode> {
+        let (clusterset, _) = self.active_clusterset()?;
+        loop {
+            let (indexes, errors) =
+                audit_clusters(&clusterset, None, |client, _| ClusterIndex::build(client)).await?;
+            let mut rows = Vec::new();
+            for (clustername, index) in &indexes {
+                index.save(clustername)?;
+                rows.push(IndexOutput::new(clustername, index));
+            }
+            self.print_structured(rows)?;
+            for error in &errors {
+                eprintln!("{}: {}", error.clustername, error.error);
+            }
+            match every.and_then(|every| every.to_std().ok()) {
+                Some(every) => tokio::time::sleep(every).await,
+                None if errors.is_empty() => return Ok(ExitCode::SUCCESS),
+                None => return Ok(ExitCode::FAILURE),
+            }
+        }
+    }
+
+    // Prints matching names of every cluster once. Clusters are asked in parallel and never
+    // fail the command, completion should print what it can
+    async fn index_names(&self, resource: &str, prefix: &str) -> Result<ExitCode> {
+        let Some(indexed) = indexed_kind(resource) else {
+            return Ok(ExitCode::FAILURE);
+        };
+        let (clusterset, ns) = self.active_clusterset()?;
+        let lookups = clusterset
+            .clusters
+            .iter()
+            .map(|cluster| completion_names(cluster, indexed, &ns));
+        let names: BTreeSet<String> = serial::join_all(lookups)
+            .await
+            .into_iter()
+            .flatten()
+            .filter(|name| name.starts_with(prefix))
+            .collect();
+        for name in names {
+            println!("{}", name);
+        }
+        Ok(ExitCode::SUCCESS)
+    }
+
+    pub async fn find(&self, pattern: &str, kind: Option<&str>) -> Result<ExitCode> {
+        let kind = match kind {
+            Some(kind) => Some(indexed_kind(kind).map(|indexed| indexed.kind).ok_or_else(
+                || {
+                    let kinds: Vec<&str> = INDEXED_KINDS.iter().map(|k| k.plural).collect();
+                    anyhow!("{} is not indexed, indexed are {}", kind, kinds.join(", "))
+                },
+            )?),
+            None => None,
+        };
+        let (clusterset, _) = self.active_clusterset()?;
+        let mut rows = Vec::new();
+        for cluster in &clusterset.clusters {
+            let Some(index) = ClusterIndex::load(&cluster.name)? else {
+                eprintln!("{}: no index, run `kubemc index build`", cluster.name);
+                continue;
+            };
+            rows.extend(
+                index
+                    .find(pattern, kind)
+                    .filter(|entry| self.namespace.is_none() || entry.namespace == self.namespace)
+                    .map(|entry| FindOutput::new(&cluster.name, entry, index.built)),
+            );
+        }
+        let found = !rows.is_empty();
+        self.print_structured(rows)?;
+        Ok(if found {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        })
+    }
+
+    pub async fn completion(&self, shell: Shell) -> Result<()> {
+        let command = Cli::command();
+        let subcommands: Vec<&str> = command
+            .get_subcommands()
+            .map(|subcommand| subcommand.get_name())
+            .collect();
+        match shell {
+            Shell::Bash => print!("{}", bash_completion(&subcommands)),
+        }
+        Ok(())
+    }
+
+    pub async fn generate_config(&self, dry_run: bool, template: bool) -> Result<()> {
+        if template {
+            let config_yaml = Config::yaml()?;
+            return io::stdout().write(config_yaml.as_bytes()).map(|_| Ok(()))?;
+        }
+        let kubeconfig = read_kubeconfig(self.kubeconfig.as_deref())?;
+        let config = config_wizard(&kubeconfig, &mut io::stdin().lock(), &mut io::stderr())?;
+        if dry_run {
+            print!("{}", serde_yaml::to_string(&config)?);
+            return Ok(());
+        }
+        let path = config_path(self.config_file.as_ref())?;
+        if path.exists() && !confirm(&format!("replace the existing config {}?", path.display()))? {
+            eprintln!("left {} unchanged", path.display());
+            return Ok(());
+        }
+        Config::create_file(&path, &config).await?;
+        println!("wrote {}", path.display());
+        Ok(())
+    }
+
+    pub async fn namespace(
+        &self,
+        ns: Option<&str>,
+        interactive: bool,
+        create_missing: bool,
+        yes: bool,
+    ) -> Result<()> {
+        let mut file = ConfigFile::load(config_path(self.config_file.as_ref())?)?;
+        let selected;
+        let ns = match ns {
+            Some(ns) => ns,
+            None => {
+                let rows = self.list_namespaces(&file).await?;
+                if !interactive {
+                    self.print_structured(rows)?;
+                    return Ok(());
+                }
+                let Some(choice) = choose_namespace(&rows)? else {
+                    return Ok(());
+                };
+                selected = choice;
+                &selected
+            }
+        };
+        if create_missing {
+            let clusterset = self.clusterset(&file.config)?;
+            create_missing_namespace(&clusterset.clusters, ns, yes).await?;
+        }
+        file.config.set_namespace(ns)?;
+        file.save().await
+    }
+
+    // Lists the namespaces of every cluster in the active clusterset
+    async fn list_namespaces(&self, file: &ConfigFile) -> Result<Vec<NamespaceOutput>> {
+        let clusterset = self.clusterset(&file.config)?;
+        let listing = Client::try_new(&clusterset.clusters, "", "namespaces")
+            .await?
+            .list()
+            .await?;
+        for failure in &listing.failures {
+            eprintln!("{}: {}", failure.clustername, failure.error);
+        }
+        let clusternames: Vec<String> = clusterset
+            .clusters
+            .iter()
+            .map(|cluster| cluster.name.clone())
+            .collect();
+        Ok(namespace_union(
+            &clusternames,
+            &listing.responses,
+            &clusterset.namespace,
+        ))
+    }
+
+    pub async fn validate_config(&self) -> Result<ExitCode> {
+        let config = Config::load_config(self.config_file.as_ref())?;
+        // a pod connecting only to servers given in the config may have no kubeconfig at all
+        let uses_kubeconfig = config
+            .clustersets
+            .iter()
+            .flat_map(|cs| &cs.clusters)
+            .any(|cluster| cluster.uses_kubeconfig() && cluster.kubeconfig.is_none());
+        let kubeconfig = if uses_kubeconfig {
+            read_kubeconfig(self.kubeconfig.as_deref())?
+        } else {
+            Default::default()
+        };
+        let errors = config.validate(&kubeconfig);
+        if errors.is_empty() {
+            println!("config is valid");
+            return Ok(ExitCode::SUCCESS);
+        }
+        for error in &errors {
+            eprintln!("{}", error);
+        }
+        Ok(ExitCode::FAILURE)
+    }
+
+    pub async fn migrate_config(&self) -> Result<()> {
+        match Config::migrate_file(self.config_file.as_ref()).await? {
+            (path, Some(version)) => println!(
+                "migrated {} from {} to {}, the original is kept as {}.bak",
+                path.display(),
+                version,
+                API_VERSION,
+                path.display()
+            ),
+            (path, None) => println!("{} is already at {}", path.display(), API_VERSION),
+        }
+        Ok(())
+    }
+
+    // Checks a cluster connects, reports its version and serves discovery before adding it to
+    // the config, which is left alone when any check fails
+    pub async fn add_cluster(&self, args: &AddClusterArgs) -> Result<ExitCode> {
+        let mut file = ConfigFile::load(config_path(self.config_file.as_ref())?)?;
+        let context = match (&args.server, &args.context) {
+            (Some(_), _) => None,
+            (None, Some(context)) => Some(context.clone()),
+            (None, None) if io::stdin().is_terminal() => {
+                let kubeconfig = read_kubeconfig(self.kubeconfig.as_deref())?;
+                let context =
+                    context_wizard(&kubeconfig, &mut io::stdin().lock(), &mut io::stderr())?;
+                Some(context)
+            }
+            (None, None) => return Err(anyhow!("pick the cluster with --context or --server")),
+        };
+        let name = args
+            .name
+            .clone()
+            .or_else(|| context.clone())
+            .unwrap_or_default();
+        let cluster = Cluster {
+            name,
+            kubeconfig: context.as_ref().and(self.kubeconfig.clone()),
+            context,
+            server: args.server.clone(),
+            token_file: args.token_file.clone(),
+            certificate_authority: args.certificate_authority.clone(),
+            ..Default::default()
+        };
+        let clusterset = args
+            .clusterset
+            .clone()
+            .unwrap_or_else(|| file.config.current_clusterset.clone());
+        // a clash of names is found before connecting
+        file.config
+            .add_cluster(Some(&clusterset), cluster.clone())?;
+
+        let mut tested = cluster.clone();
+        self.apply_cluster_flags(&mut tested);
+        let tested = [tested];
+        let mut checks = Vec::new();
+        match connect_all(&tested).await?.pop().map(|(_, client)| client) {
+            Some(Ok(client)) => {
+                checks.push(CheckOutput::new("connect", true, String::new()));
+                let (passed, detail) = match client.apiserver_version().await {
+                    Ok(version) => (true, version.git_version),
+                    Err(e) => (false, describe_error(&e.into())),
+                };
+                checks.push(CheckOutput::new("server version", passed, detail));
+                let (passed, detail) = match discover(&tested, true).await?.pop() {
+                    Some((_, Ok((discovery, _)))) => {
+                        (true, format!("{} resources", discovery.len()))
+                    }
+                    Some((_, Err(e))) => (false, describe_error(&e)),
+                    None => (false, String::new()),
+                };
+                checks.push(CheckOutput::new("discovery", passed, detail));
+            }
+            Some(Err(e)) => checks.push(CheckOutput::new("connect", false, describe_error(&e))),
+            None => checks.push(CheckOutput::new("connect", false, String::new())),
+        }
+
+        let passed = checks.iter().all(|c| c.result.health == Health::Healthy);
+        self.print_structured(checks)?;
+        if !passed {
+            eprintln!("{}: not added, the config is unchanged", cluster.name);
+            return Ok(ExitCode::FAILURE);
+        }
+        file.save().await?;
+        eprintln!("{}: added to clusterset {}", cluster.name, clusterset);
+        Ok(ExitCode::SUCCESS)
+    }
+
+    pub async fn cluster(&self, action: &ClusterAction) -> Result<()> {
+        let mut file = ConfigFile::load(config_path(self.config_file.as_ref())?)?;
+        let (cluster, disabled) = match action {
+            ClusterAction::List => {
+                let mut config = file.config.clone();
+                config.resolve_includes()?;
+                let clusterset = config.active_clusterset()?;
+                let now = Utc::now();
+                let rows: Vec<ClusterOutput> = clusterset
+                    .clusters
+                    .iter()
+                    .map(|cluster| ClusterOutput::new(cluster, clusterset, now))
+                    .collect();
+                self.print_structured(rows)?;
+                return Ok(());
+            }
+            ClusterAction::Disable { cluster } => (cluster, true),
+            ClusterAction::Enable { cluster } => (cluster, false),
+        };
+        file.config.set_cluster_disabled(cluster, disabled)?;
+        file.save().await?;
+        let state = if disabled { "disabled" } else { "enabled" };
+        eprintln!("cluster {} {}", cluster, state);
+        Ok(())
+    }
+
+    #[cfg(feature = "dev")]
+    pub async fn dev(&self, action: &DevAction) -> Result<ExitCode> {
+        let DevAction::E2e { keep_clusters } = action;
+        let environment = Environment::create(&std::env::temp_dir().join(KIND_PREFIX)).await?;
+        let rows = environment.run_suite().await;
+        let passed = rows.iter().all(|row| row.result.health == Health::Healthy);
+        if *keep_clusters {
+            eprintln!(
+                "kind clusters kept, target them with --config-file {}",
+                environment.config().display()
+            );
+        } else {
+            environment.delete().await?;
+        }
+        self.print_structured(rows)?;
+        Ok(if passed {
+            ExitCode::SUCCESS
+        } else {
+            ExitCode::FAILURE
+        })
+    }
+
+    pub async fn pin(&self, cluster: Option<&str>, clear: bool) -> Result<()> {
+        let mut file = ConfigFile::load(config_path(self.config_file.as_ref())?)?;
+        if cluster.is_none() && !clear {
+            match &file.config.active_clusterset()?.pinned_cluster {
+                Some(pinned) => println!("{}", pinned),
+                None => println!("no cluster pinned"),
+            }
+            return Ok(());
+        }
+        file.config.set_pinned_cluster(cluster)?;
+        file.save().await
+    }
+}
+
+// Runs an audit against every cluster in parallel. Returns the results sorted by cluster along
+// with the clusters that could not be audited.
+async fn audit_clusters<T, F, Fut>(
+    clusterset: &Clusterset,
+    namespace: Option<String>,
+    audit: F,
+) -> Result<(Vec<(String, T)>, Vec<ErrorOutput>)>
+where
+    T: Send + 'static,
+    F: Fn(KubeClient, Option<String>) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = Result<T>> + Send,
+{
+    let handles = serial::join_all(connect_all(&clusterset.clusters).await?.into_iter().map(
+        |(clustername, client)| {
+            let namespace = namespace
+                .as_deref()
+                .map(|ns| clusterset.cluster_namespace(&clustername, ns));
+            let audit = audit.clone();
+            tokio::spawn(async move {
+                let result = match client {
+                    Ok(client) => {
+                        serial::phase(&clustername, "inspect", audit(client, namespace)).await
+                    }
+                    Err(e) => Err(e),
+                };
+                (clustername, result)
+            })
+        },
+    ))
+    .await;
+
+    let mut audits = Vec::new();
+    let mut errors = Vec::new();
+    for handle in handles {
+        match handle {
+            Ok((clustername, Ok(audit))) => audits.push((clustername, audit)),
+            Ok((clustername, Err(e))) => errors.push(ErrorOutput {
+                clustername,
+                error: describe_error(&e),
+            }),
+            Err(e) => debug!("join failed {}", e),
+        }
+    }
+    audits.sort_by(|a, b| a.0.cmp(&b.0));
+    errors.sort_by(|a, b| a.clustername.cmp(&b.clustername));
+    Ok((audits, errors))
+}
+
+// The running watches of `get --watch`, which can be stopped per cluster
+#[derive(Default)]
+struct Watches {
+    events: SelectAll<Abortable<BoxStream<'static, WatchResponse>>>,
+    handles: HashMap<String, Vec<AbortHandle>>,
+}
+
+impl Watches {
+    fn add(&mut self, client: Client) {
+        for (clustername, watch) in client.watch_each() {
+            let (watch, handle) = futures::stream::abortable(watch);
+            self.events.push(watch);
+            self.handles.entry(clustername).or_default().push(handle);
+        }
+    }
+
+    fn stop(&mut self, clustername: &str) {
+        for handle in self.handles.remove(clustername).into_iter().flatten() {
+            handle.abort();
+        }
+    }
+}
+
+fn print_watch_event(
+    event: &ObjectEvent,
+    formatters: &BTreeMap<(String, String), Formatter>,
+    show_changes: bool,
+) -> Result<()> {
+    if let (true, Some(previous)) = (show_changes, &event.previous) {
+        let mut changes = diff(
+            &serde_json::to_value(previous)?,
+            &serde_json::to_value(&event.object)?,
+        );
+        redact_changes(&event.kind, &mut changes);
+        if changes.is_empty() {
+            return Ok(());
+        }
+        println!(
+            "{:<10}{}   {}/{}",
+            event.change,
+            event.clustername,
+            event.kind,
+            event.object.name_any()
+        );
+        for change in changes {
+            println!("    {}", change);
+        }
+        return Ok(());
+    }
+    // kinds of the same name in different groups are told apart by the apiVersion of their
+    // objects, and kinds first watched after a reload by the objects alone
+    let formatter = event
+        .object
+        .types
+        .as_ref()
+        .and_then(|types| {
+            formatters
+                .get(&(types.api_version.clone(), event.kind.clone()))
+                .copied()
+        })
+        .unwrap_or_else(|| Formatter::for_object(&event.object));
+    let output = formatter.format(&event.clustername, event.object.clone());
+    println!("{:<10}{}", event.change, output.fields().join("   "));
+    Ok(())
+}
+
+// Lists candidate child resources on the clusters where a named object was found and attaches
+// the objects they own to each report
+async fn attach_children(
+    reports: &mut [NamedGetReport],
+    clusters: &[Cluster],
+    ns: &str,
+) -> Result<()> {
+    let clusters: Vec<Cluster> = clusters
+        .iter()
+        .filter(|cluster| {
+            reports
+                .iter()
+                .any(|r| r.found.iter().any(|found| found.cluster == cluster.name))
+        })
+        .cloned()
+        .collect();
+    if clusters.is_empty() {
+        return Ok(());
+    }
+    let client = Client::try_new(&clusters, ns, CHILD_RESOURCES).await?;
+    let candidates = client.list().await?;
+    for report in reports {
+        report.attach_children(&candidates);
+    }
+    Ok(())
+}
+
+// Applies a change to the clusters, all at once or with --one-cluster-at-a-time one cluster
+// after another, showing each cluster's rows on stderr as it finishes. Returns the rows and
+// whether the change was made on every cluster: rolling stops at the first cluster where a row
+// did not succeed, or when moving on to the next cluster is declined.
+async fn roll_out<R, F, Fut>(
+    rolling: &RollingArgs,
+    clusters: &[Cluster],
+    succeeded: impl Fn(&R) -> bool,
+    mut change: F,
+) -> Result<(Vec<R>, bool)>
+where
+    R: Tabled,
+    F: FnMut(Vec<Cluster>) -> Fut,
+    Fut: Future<Output = Result<Vec<R>>>,
+{
+    if !rolling.one_cluster_at_a_time {
+        let rows = change(clusters.to_vec()).await?;
+        let all_succeeded = rows.iter().all(&succeeded);
+        return Ok((rows, all_succeeded));
+    }
+    let mut rows = Vec::new();
+    for (i, cluster) in clusters.iter().enumerate() {
+        if i > 0 && rolling.confirm_each {
+            let prompt = format!(
+                "continue with cluster {} ({} of {})?",
+                cluster.name,
+                i + 1,
+                clusters.len()
+            );
+            if !confirm(&prompt)? {
+                eprintln!(
+                    "stopped, clusters left unchanged: {}",
+                    cluster_names(&clusters[i..])
+                );
+                return Ok((rows, false));
+            }
+        }
+        let cluster_rows = change(vec![cluster.clone()]).await?;
+        let cluster_succeeded = cluster_rows.iter().all(&succeeded);
+        eprintln!("{}", table_string(cluster_rows.iter().collect()));
+        rows.extend(cluster_rows);
+        if !cluster_succeeded {
+            if i + 1 < clusters.len() {
+                eprintln!(
+                    "change failed on cluster {}, stopping. Clusters left unchanged: {}",
+                    cluster.name,
+                    cluster_names(&clusters[i + 1..])
+                );
+            }
+            return Ok((rows, false));
+        }
+    }
+    Ok((rows, true))
+}
+
+// History of a node action, leaving out clusters without matching nodes
+fn node_history(command: &str, outputs: &[NodeMaintenanceOutput]) -> Vec<Entry> {
+    outputs
+        .iter()
+        .filter(|output| output.result.health != Health::Unknown)
+        .map(|output| {
+            let node = match output.node.as_str() {
+                "" => "nodes".to_owned(),
+                node => format!("node/{}", node),
+            };
+            let outcome = match output.detail.as_str() {
+                "" => output.result.text.clone(),
+                detail => format!("{} ({})", output.result.text, detail),
+            };
+            Entry::new(
+                command,
+                &output.clustername,
+                &node,
+                &outcome,
+                output.succeeded(),
+            )
+        })
+        .collect()
+}
+
+// Exit code of a node action, failing as well when a cluster had no matching nodes since the
+// nodes meant were most likely misspelled
+fn node_exit_code(outputs: &[NodeMaintenanceOutput], all_done: bool) -> ExitCode {
+    let matched = outputs
+        .iter()
+        .all(|output| output.result.health != Health::Unknown);
+    if all_done && matched {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+// Runs a node action on each matching node of the clusters, the clusters at once and the nodes
+// of a cluster one after another so that each cluster keeps most of its capacity
+async fn on_nodes<F, Fut>(
+    clusters: &[Cluster],
+    target: &NodeArgs,
+    action: F,
+) -> Result<Vec<NodeMaintenanceOutput>>
+where
+    F: Fn(KubeClient, String, String) -> Fut,
+    Fut: Future<Output = NodeMaintenanceOutput>,
+{
+    let action = &action;
+    let outputs = serial::join_all(connect_all(clusters).await?.into_iter().map(
+        |(clustername, client)| async move {
+            let work = async {
+                let client = client?;
+                let nodes = target.matching_nodes(client.clone()).await?;
+                if nodes.is_empty() {
+                    let result = StatusCell::new("no matching nodes", Health::Unknown);
+                    return Ok(vec![NodeMaintenanceOutput::new(&clustername, "", result)]);
+                }
+                let mut outputs = Vec::new();
+                for node in nodes {
+                    outputs.push(action(client.clone(), clustername.clone(), node).await);
+                }
+                Ok::<_, anyhow::Error>(outputs)
+            };
+            match serial::phase(&clustername, "nodes", work).await {
+                Ok(outputs) => outputs,
+                Err(e) => vec![NodeMaintenanceOutput::failed(
+                    &clustername,
+                    "",
+                    describe_error(&e),
+                )],
+            }
+        },
+    ))
+    .await;
+    Ok(outputs.into_iter().flatten().collect())
+}
+
+// Cordons a node and evicts its pods, retrying the evictions a PodDisruptionBudget does not
+// allow yet until the timeout, then waits for the evicted pods to be gone
+async fn drain_node(
+    client: KubeClient,
+    clustername: &str,
+    node: &str,
+    policy: DrainPolicy,
+    timeout: Duration,
+) -> NodeMaintenanceOutput {
+    let failed =
+        |e: &kube::Error| NodeMaintenanceOutput::failed(clustername, node, describe_kube_error(e));
+    let pod_name =
+        |pod: &Pod| format!("{}/{}", pod.namespace().unwrap_or_default(), pod.name_any());
+    let cordon = Patch::Merge(cordon_patch(true));
+    let nodes: Api<Node> = Api::all(client.clone());
+    if let Err(e) = nodes.patch(node, &PatchParams::default(), &cordon).await {
+        return failed(&e);
+    }
+    let params = ListParams::default().fields(&format!("spec.nodeName={}", node));
+    let pods = match Api::<Pod>::all(client.clone()).list(&params).await {
+        Ok(pods) => pods.items,
+        Err(e) => return failed(&e),
+    };
+    let mut pending = Vec::new();
+    let mut skipped = 0;
+    let mut blocked = Vec::new();
+    for pod in pods {
+        match policy.disposition(&pod) {
+            Disposition::Evict => pending.push(pod),
+            Disposition::Skip => skipped += 1,
+            Disposition::Block(flag) => blocked.push(format!("{} needs {}", pod_name(&pod), flag)),
+        }
+    }
+    let mut output = NodeMaintenanceOutput::new(
+        clustername,
+        node,
+        StatusCell::new("drained", Health::Healthy),
+    );
+    output.skipped = skipped.to_string();
+    if !blocked.is_empty() {
+        output.result = StatusCell::new("cordoned, not drained", Health::Unhealthy);
+        output.detail = blocked.join(", ");
+        return output;
+    }
+
+    eprintln!(
+        "{}: evicting {} pods from {}",
+        clustername,
+        pending.len(),
+        node
+    );
+    let deadline = Instant::now() + timeout;
+    let mut evicted = Vec::new();
+    let mut waiting = false;
+    while !pending.is_empty() {
+        let mut disrupted = Vec::new();
+        for pod in pending {
+            let pods: Api<Pod> =
+                Api::namespaced(client.clone(), &pod.namespace().unwrap_or_default());
+            match pods.evict(&pod.name_any(), &EvictParams::default()).await {
+                Ok(_) => evicted.push(pod),
+                Err(kube::Error::Api(e)) if e.code == 404 => evicted.push(pod),
+                // a PodDisruptionBudget allows no more disruption until other pods are ready
+                Err(kube::Error::Api(e)) if e.code == 429 => disrupted.push(pod),
+                Err(e) => return failed(&e),
+            }
+        }
+        pending = disrupted;
+        if pending.is_empty() {
+            break;
+        }
+        if Instant::now() >= deadline {
+            let names: Vec<String> = pending.iter().map(pod_name).collect();
+            output.result = StatusCell::new("timed out", Health::Unhealthy);
+            output.evicted = evicted.len().to_string();
+            output.detail = format!("PodDisruptionBudgets block evicting {}", names.join(", "));
+            return output;
+        }
+        if !waiting {
+            waiting = true;
+            eprintln!(
+                "{}: {} pods on {} wait for their PodDisruptionBudgets",
+                clustername,
+                pending.len(),
+                node
+            );
+        }
+        tokio::time::sleep(ROLLOUT_POLL_INTERVAL.min(deadline - Instant::now())).await;
+    }
+
+    // evicted pods shut down gracefully, the node is drained once they are gone
+    loop {
+        let mut remaining = Vec::new();
+        for pod in &evicted {
+            let pods: Api<Pod> =
+                Api::namespaced(client.clone(), &pod.namespace().unwrap_or_default());
+            match pods.get_opt(&pod.name_any()).await {
+                Ok(Some(current)) if current.uid() == pod.uid() => remaining.push(pod_name(pod)),
+                Ok(_) => {}
+                Err(e) => return failed(&e),
+            }
+        }
+        if remaining.is_empty() {
+            break;
+        }
+        if Instant::now() >= deadline {
+            output.result = StatusCell::new("timed out", Health::Unhealthy);
+            output.detail = format!("still terminating: {}", remaining.join(", "));
+            break;
+        }
+        tokio::time::sleep(ROLLOUT_POLL_INTERVAL.min(deadline - Instant::now())).await;
+    }
+    output.evicted = evicted.len().to_string();
+    output
+}
+
+// Prints a table over the one printed before, moving back over the previous table and clearing
+// it first
+fn redraw_table<T: Tabled>(rows: Vec<T>, drawn_lines: &mut usize) {
+    if *drawn_lines > 0 {
+        print!("\x1b[{}A\x1b[J", drawn_lines);
+    }
+    let table = table_string(rows);
+    println!("{}", table);
+    *drawn_lines = table.lines().count();
+}
+
+// Records what a mutating command did on each cluster, only warning when the history cannot be
+// written
+fn record_history(entries: Vec<Entry>) {
+    if let Err(e) = history::record(&entries) {
+        warn!("failed to record history: {}", e);
+    }
+}
+
+fn history_entry(command: &str, clustername: &str, resource: &str, result: &StatusCell) -> Entry {
+    let success = result.health == Health::Healthy;
+    Entry::new(command, clustername, resource, &result.text, success)
+}
+
+// Notes on stderr that the list of a kind on a cluster stopped at --max-objects-per-cluster
+// The group and kind of each requested resource once, in the order they were requested
+fn table_kinds(client: &Client) -> Vec<GroupKind> {
+    let mut group_kinds: Vec<GroupKind> = Vec::new();
+    for group_kind in client.group_kinds() {
+        if !group_kinds.contains(group_kind) {
+            group_kinds.push(group_kind.clone());
+        }
+    }
+    group_kinds
+}
+
+fn is_node((group, kind): &GroupKind) -> bool {
+    group.is_empty() && kind == "Node"
+}
+
+fn note_truncated(clustername: &str, kind: &str, truncated: Truncated) {
+    eprintln!("{}: {} list {}", clustername, kind, truncated)
+}
+
+fn note_cached(clustername: &str, kind: &str, age: Duration) {
+    let age = chrono::Duration::from_std(age)
+        .map(format_duration)
+        .unwrap_or_default();
+    eprintln!(
+        "{}: {} listed {} ago, run without --cache-ttl to list again",
+        clustername, kind, age
+    )
+}
+
+fn cluster_names(clusters: &[Cluster]) -> String {
+    clusters
+        .iter()
+        .map(|cluster| cluster.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Status of a patch on one cluster, `done` being the word for a successful patch
+fn patch_status(result: GetResult, done: &str) -> StatusCell {
+    match result {
+        GetResult::Found(_) => StatusCell::new(done, Health::Healthy),
+        GetResult::NotFound => StatusCell::new("not found", Health::Unknown),
+        GetResult::Failed(e) => StatusCell::new(format!("failed: {}", e), Health::Unhealthy),
+    }
+}
+
+// Polls the CRD on every cluster until each established it or the timeout passes, returning the
+// clusters that did not along with where they stand
+async fn await_established(
+    clusters: &[Cluster],
+    crd: &str,
+    timeout: Duration,
+) -> Result<Vec<(String, String)>> {
+    let client = Client::try_new(
+        clusters,
+        "default",
+        "customresourcedefinitions.apiextensions.k8s.io",
+    )
+    .await?;
+    let established = WaitFor::Condition {
+        type_: "Established".into(),
+        status: "True".into(),
+    };
+    let deadline = Instant::now() + timeout;
+    loop {
+        let mut pending = Vec::new();
+        for response in client.get(crd).await {
+            match established.state(response.result) {
+                WaitState::Met(_) => {}
+                WaitState::Waiting(message) | WaitState::Failed(message) => {
+                    pending.push((response.clustername, message))
+                }
+            }
+        }
+        if pending.is_empty() || Instant::now() >= deadline {
+            return Ok(pending);
+        }
+        debug!("waiting for CRD {} on {} clusters", crd, pending.len());
+        tokio::time::sleep(ROLLOUT_POLL_INTERVAL.min(deadline - Instant::now())).await;
+    }
+}
+
+// Creates the namespace on every cluster where it is missing, after confirmation unless `yes`
+async fn create_missing_namespace(clusters: &[Cluster], ns: &str, yes: bool) -> Result<()> {
+    let client = Client::try_new(clusters, ns, "namespaces").await?;
+    let mut missing = Vec::new();
+    for response in client.get(ns).await {
+        match response.result {
+            GetResult::NotFound => missing.push(response.clustername),
+            GetResult::Failed(e) => {
+                return Err(anyhow!(
+                    "failed to check namespace {} on cluster {}: {}",
+                    ns,
+                    response.clustername,
+                    e
+                ))
+            }
+            GetResult::Found(_) => {}
+        }
+    }
+    if missing.is_empty() {
+        return Ok(());
+    }
+    missing.sort();
+
+    let prompt = format!(
+        "namespace {} does not exist on clusters: {}. Create it?",
+        ns,
+        missing.join(", ")
+    );
+    if !yes && !confirm(&prompt)? {
+        return Ok(());
+    }
+    let namespace = DynamicObject::new(ns, &ApiResource::erase::<Namespace>(&()));
+    let created = client.create(&namespace, &missing).await;
+    let object = format!("namespace/{}", ns);
+    record_history(
+        created
+            .iter()
+            .map(|(clustername, result)| match result {
+                Ok(()) => Entry::new("namespace", clustername, &object, "created", true),
+                Err(e) => {
+                    let outcome = format!("failed: {}", describe_error(e));
+                    Entry::new("namespace", clustername, &object, &outcome, false)
+                }
+            })
+            .collect(),
+    );
+    for (clustername, result) in created {
+        match result {
+            Ok(()) => eprintln!("created namespace {} on cluster {}", ns, clustername),
+            Err(e) => {
+                return Err(e.context(format!(
+                    "failed to create namespace {} on cluster {}",
+                    ns, clustername
+                )))
+            }
+        }
+    }
+    Ok(())
+}
+
+// Lists the namespaces on stderr and reads a number or name from stdin. An empty answer keeps
+// the configured namespace.
+fn choose_namespace(rows: &[NamespaceOutput]) -> Result<Option<String>> {
+    if rows.is_empty() {
+        return Err(anyhow!("no namespaces found on the clusterset"));
+    }
+    for (i, row) in rows.iter().enumerate() {
+        let marker = if row.current.is_empty() { " " } else { "*" };
+        if row.missing.is_empty() {
+            eprintln!("{}{:>3}) {}", marker, i + 1, row.name);
+        } else {
+            eprintln!(
+                "{}{:>3}) {} (missing on {})",
+                marker,
+                i + 1,
+                row.name,
+                row.missing
+            );
+        }
+    }
+    eprint!("namespace [1-{}]: ", rows.len());
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim();
+    if answer.is_empty() {
+        return Ok(None);
+    }
+    let chosen = match answer.parse::<usize>() {
+        Ok(i) => rows.get(i.wrapping_sub(1)),
+        Err(_) => rows.iter().find(|row| row.name == answer),
+    };
+    chosen
+        .map(|row| Some(row.name.clone()))
+        .ok_or_else(|| anyhow!("{} is not one of the listed namespaces", answer))
+}
+
+// Prints every row as a compact JSON object on its own line
+fn print_json_lines<T: Serialize>(rows: &[T]) -> Result<()> {
+    let mut writer = JsonLinesWriter::new(io::stdout());
+    for row in rows {
+        writer.write(row)?;
+    }
+    writer.finish()?;
+    Ok(())
+}
+
+// Asks which of the clusters holding an object to take it from
+fn choose_cluster<'a>(clusters: &[&'a String]) -> Result<&'a String> {
+    for (i, cluster) in clusters.iter().enumerate() {
+        eprintln!("{:>3}) {}", i + 1, cluster);
+    }
+    eprint!("cluster [1-{}]: ", clusters.len());
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    let answer = answer.trim();
+    let chosen = match answer.parse::<usize>() {
+        Ok(i) => clusters.get(i.wrapping_sub(1)),
+        Err(_) => clusters.iter().find(|cluster| cluster.as_str() == answer),
+    };
+    chosen
+        .copied()
+        .ok_or_else(|| anyhow!("{} is not one of the listed clusters", answer))
+}
+
+// Asks whether an edit goes to the cluster it was made on or to every cluster with the object
+fn choose_propagation(source: &str, clusters: usize) -> Result<Propagation> {
+    eprint!(
+        "apply the change to [1] {} only or [2] all {} clusters holding the object? [1/2] ",
+        source, clusters
+    );
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    match answer.trim() {
+        "1" => Ok(Propagation::Cluster),
+        "2" => Ok(Propagation::All),
+        answer => Err(anyhow!("{:?} is neither 1 nor 2", answer)),
+    }
+}
+
+fn pluralize(word: &str, count: usize) -> String {
+    if count == 1 {
+        word.to_owned()
+    } else {
+        format!("{}s", word)
+    }
+}
+
+// Asks a yes/no question on stderr and reads the answer from stdin. Anything but y/yes is a no.
+fn confirm(prompt: &str) -> Result<bool> {
+    eprint!("{} [y/N] ", prompt);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[derive(Tabled)]
+    struct Row {
+        cluster: String,
+        ok: bool,
+    }
+
+    fn clusters(names: &[&str]) -> Vec<Cluster> {
+        names
+            .iter()
+            .map(|name| Cluster {
+                name: name.to_string(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    // Changes every cluster but `failing`, recording the batches of clusters it was called with
+    async fn run(rolling: RollingArgs, failing: &str) -> (Vec<String>, bool, Vec<Vec<String>>) {
+        let mut batches = Vec::new();
+        let (rows, all) = roll_out(
+            &rolling,
+            &clusters(&["a", "b", "c"]),
+            |row: &Row| row.ok,
+            |clusters| {
+                batches.push(clusters.iter().map(|c| c.name.clone()).collect());
+                async move {
+                    Ok(clusters
+                        .into_iter()
+                        .map(|c| Row {
+                            ok: c.name != failing,
+                            cluster: c.name,
+                        })
+                        .collect())
+                }
+            },
+        )
+        .await
+        .unwrap();
+        let rows = rows.into_iter().map(|row| row.cluster).collect();
+        (rows, all, batches)
+    }
+
+    #[tokio::test]
+    async fn roll_out_batches() {
+        let at_once = RollingArgs {
+            one_cluster_at_a_time: false,
+            confirm_each: false,
+        };
+        let (rows, all, batches) = run(at_once.clone(), "").await;
+        assert_eq!(rows, ["a", "b", "c"]);
+        assert!(all);
+        assert_eq!(batches, [["a", "b", "c"]]);
+
+        // a failure still changes every cluster when they are changed at once
+        let (rows, all, _) = run(at_once, "b").await;
+        assert_eq!(rows, ["a", "b", "c"]);
+        assert!(!all);
+
+        let rolling = RollingArgs {
+            one_cluster_at_a_time: true,
+            confirm_each: false,
+        };
+        let (rows, all, batches) = run(rolling.clone(), "").await;
+        assert_eq!(rows, ["a", "b", "c"]);
+        assert!(all);
+        assert_eq!(batches, [["a"], ["b"], ["c"]]);
+
+        // rolling stops at the first cluster where the change failed
+        let (rows, all, batches) = run(rolling, "b").await;
+        assert_eq!(rows, ["a", "b"]);
+        assert!(!all);
+        assert_eq!(batches, [["a"], ["b"]]);
+    }
+
+    #[test]
+    fn contexts_apart_from_cluster_subsets() {
+        Cli::command().debug_assert();
+        let cli = Cli::try_parse_from([
+            "kubemc",
+            "--contexts",
+            "kind-a,kind-b",
+            "scale",
+            "deployment",
+            "web",
+            "--replicas",
+            "2",
+            "--clusters",
+            "prod-eu",
+        ])
+        .unwrap();
+        assert_eq!(cli.contexts, ["kind-a", "kind-b"]);
+        let Action::Scale(args) = cli.action else {
+            panic!("expected scale");
+        };
+        assert_eq!(args.clusters, ["prod-eu"]);
+
+        let cli = Cli::try_parse_from(["kubemc", "cordon", "node-1", "--clusters", "a,b"]).unwrap();
+        assert!(cli.contexts.is_empty());
+        let Action::Cordon(args) = cli.action else {
+            panic!("expected cordon");
+        };
+        assert_eq!(args.clusters, ["a", "b"]);
+
+        let cli = Cli::try_parse_from([
+            "kubemc",
+            "label",
+            "pods",
+            "web",
+            "app=web",
+            "--clusters",
+            "a",
+            "--contexts",
+            "ctx",
+        ])
+        .unwrap();
+        assert_eq!(cli.contexts, ["ctx"]);
+        let Action::Label(args) = cli.action else {
+            panic!("expected label");
+        };
+        assert_eq!(args.clusters, ["a"]);
+    }
+
+    #[test]
+    fn report_only_where_checked() {
+        let parse = |args: &[&str]| {
+            Cli::try_parse_from(
+                ["kubemc", "--report", "junit=report.xml"]
+                    .iter()
+                    .chain(args),
+            )
+            .unwrap()
+        };
+        assert!(parse(&["get", "-f", "app.yaml"]).check_report().is_ok());
+        assert!(parse(&["verify-cluster", "new", "-f", "checks.yaml"])
+            .check_report()
+            .is_ok());
+        assert!(parse(&["get", "pods"]).check_report().is_err());
+        assert!(parse(&["scale", "deployment", "web", "--replicas", "2"])
+            .check_report()
+            .is_err());
     }
 
-    pub async fn namespace(&self, ns: &str) -> Result<()> {
-        let mut config = Config::load_config_from_default_file()?;
-        config.set_namespace(ns)?;
-        Config::write_config_to_defaul(serde_yaml::to_string(&config)?)
+    #[test]
+    fn yaml_output_flags_only_when_listing() {
+        let get_args = |args: &[&str]| {
+            let cli = Cli::try_parse_from(["kubemc", "get"].iter().chain(args))?;
+            let Action::Get(args) = cli.action else {
+                panic!("expected get");
+            };
+            Ok::<_, clap::Error>(args)
+        };
+        let args = get_args(&["pods", "--max-objects", "10"]).unwrap();
+        assert!(args.resource_and_name().is_ok());
+        assert!(get_args(&["pods", "web", "--output-file", "web.yaml"]).is_err());
+        assert!(get_args(&["pods", "--watch", "--max-objects", "10"]).is_err());
+        let args = get_args(&["pods/web", "--max-objects", "10"]).unwrap();
+        assert!(args.resource_and_name().is_err());
     }
 }
@@ -0,0 +1,86 @@
+use crate::index::INDEXED_KINDS;
+
+/// Subcommands taking a resource followed by an object name
+const NAMED_COMMANDS: &[&str] = &["get", "describe", "scale", "edit", "yaml"];
+
+/// Subcommands taking a pod name, or a KIND/NAME such as svc/NAME
+const POD_COMMANDS: &[&str] = &["logs", "exec", "attach", "port-forward"];
+
+/// Bash completion for kubemc. Subcommands and resources are completed from fixed lists, object
+/// names by running `kubemc index names` with the namespace and config given on the command line.
+pub fn bash_completion(subcommands: &[&str]) -> String {
+    let resources: Vec<&str> = INDEXED_KINDS
+        .iter()
+        .flat_map(|indexed| std::iter::once(indexed.plural).chain(indexed.short))
+        .collect();
+    BASH_TEMPLATE
+        .replace("@SUBCOMMANDS@", &subcommands.join(" "))
+        .replace("@NAMED_COMMANDS@", &NAMED_COMMANDS.join("|"))
+        .replace("@POD_COMMANDS@", &POD_COMMANDS.join("|"))
+        .replace("@RESOURCES@", &resources.join(" "))
+}
+
+const BASH_TEMPLATE: &str = r#"_kubemc() {
+    local cur=${COMP_WORDS[COMP_CWORD]} words=() flags=() i word
+    for ((i = 1; i < COMP_CWORD; i++)); do
+        word=${COMP_WORDS[i]}
+        case $word in
+            -n|--namespace|-c|--config-file|--kubeconfig)
+                flags+=("$word" "${COMP_WORDS[i+1]}")
+                ((i++))
+                ;;
+            -*) ;;
+            *) words+=("$word") ;;
+        esac
+    done
+    if [[ ${#words[@]} -eq 0 ]]; then
+        COMPREPLY=($(compgen -W "@SUBCOMMANDS@" -- "$cur"))
+        return
+    fi
+    case ${words[0]} in
+        @NAMED_COMMANDS@) words=("${words[@]:1}") ;;
+        @POD_COMMANDS@)
+            [[ ${#words[@]} -eq 1 ]] || return
+            if [[ $cur != */* ]]; then
+                COMPREPLY=($(kubemc "${flags[@]}" index names pods "$cur" 2>/dev/null))
+                return
+            fi
+            words=()
+            ;;
+        rollout)
+            if [[ ${#words[@]} -eq 1 ]]; then
+                COMPREPLY=($(compgen -W "status restart history" -- "$cur"))
+                return
+            fi
+            words=("${words[@]:2}")
+            ;;
+        *) return ;;
+    esac
+    if [[ ${#words[@]} -eq 0 && $cur == */* ]]; then
+        local resource=${cur%%/*}
+        COMPREPLY=($(kubemc "${flags[@]}" index names "$resource" "${cur#*/}" 2>/dev/null))
+        COMPREPLY=("${COMPREPLY[@]/#/$resource/}")
+    elif [[ ${#words[@]} -eq 0 ]]; then
+        COMPREPLY=($(compgen -W "@RESOURCES@" -- "$cur"))
+    elif [[ ${#words[@]} -eq 1 ]]; then
+        COMPREPLY=($(kubemc "${flags[@]}" index names "${words[0]}" "$cur" 2>/dev/null))
+    fi
+}
+complete -F _kubemc kubemc
+"#;
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn bash_script() {
+        let script = bash_completion(&["get", "scale"]);
+        assert!(script.contains(r#"compgen -W "get scale""#));
+        assert!(script.contains("get|describe|scale|edit|yaml)"));
+        assert!(script.contains("logs|exec|attach|port-forward)"));
+        assert!(script.contains("pods po deployments deploy"));
+        assert!(!script.contains("@RESOURCES@"));
+        assert!(script.ends_with("complete -F _kubemc kubemc\n"));
+    }
+}
@@ -1,11 +1,37 @@
 use anyhow::Context;
 use anyhow::{anyhow, Result};
 use dirs::home_dir;
-use kube::config::KubeConfigOptions;
+use k8s_openapi::chrono::{DateTime, Utc};
+use kube::config::{KubeConfigOptions, Kubeconfig};
 use serde::Deserialize;
 use serde::Serialize;
+use std::collections::{hash_map::DefaultHasher, HashSet};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
 use std::path::PathBuf;
-use std::{fs, path::Path};
+use std::time::{Duration, Instant, SystemTime};
+use std::{fmt::Display, fs, path::Path};
+use tracing::log::debug;
+
+use crate::output::OutputFormat;
+
+/// How long to wait for another kubemc to finish writing the config
+const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Locks older than this were left behind by a kubemc that exited while writing
+const STALE_LOCK_AGE: Duration = Duration::from_secs(30);
+
+/// Config version written by this kubemc
+pub const API_VERSION: &str = "kubemc/v1beta1";
+
+/// Upgrades a parsed config by one version
+type Migration = fn(&mut serde_yaml::Value);
+
+/// Older config versions with the version they upgrade to and the step doing it, oldest first.
+/// v1beta1 is the version defining per-cluster overrides (kubeconfig, connection, pre-command,
+/// impersonate) with kebab-case keys throughout
+const MIGRATIONS: &[(&str, &str, Migration)] =
+    &[("kubemc/v1alpha1", "kubemc/v1beta1", v1alpha1_to_v1beta1)];
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Config {
@@ -19,6 +45,24 @@ pub struct Config {
 
     /// Clustersets available to use
     pub clustersets: Vec<Clusterset>,
+
+    /// Record command latencies to a local file for `kubemc stats self`. Nothing leaves the
+    /// machine and no cluster or resource names are kept
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub telemetry: bool,
+
+    /// OTLP/HTTP collector receiving a trace of every command, with a span per request to each
+    /// cluster, e.g. http://localhost:4318. OTEL_EXPORTER_OTLP_ENDPOINT takes precedence
+    #[serde(
+        default,
+        rename = "otlp-endpoint",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub otlp_endpoint: Option<String>,
+
+    /// Default output settings for every clusterset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<OutputSettings>,
 }
 
 impl Config {
@@ -29,18 +73,36 @@ impl Config {
             cluster: Some("CLUSTER".into()),
             user: Some("USER".into()),
             context: None,
+            namespace: None,
+            kubeconfig: None,
+            maintenance: None,
+            disabled: false,
+            connection: None,
+            pre_command: None,
+            impersonate: None,
+            server: None,
+            token_file: None,
+            certificate_authority: None,
+            in_cluster: false,
+            same_as: None,
         };
 
         let clusterset = Clusterset {
             name: "clusterset1".into(),
             namespace: "default".into(),
             clusters: vec![cluster],
+            include: Vec::new(),
+            pinned_cluster: None,
+            output: None,
         };
 
         let config = Config {
-            api_version: "kubemc/v1alpha1".into(),
+            api_version: API_VERSION.into(),
             current_clusterset: "clusterset1".into(),
             clustersets: vec![clusterset],
+            telemetry: false,
+            otlp_endpoint: None,
+            output: None,
         };
 
         let config_yaml = serde_yaml::to_string(&config)?;
@@ -48,13 +110,107 @@ impl Config {
     }
 
     pub fn active_clusterset(&self) -> Result<&Clusterset> {
-        self
-            .clustersets
+        self.clustersets
             .iter()
             .find(|clusterset| clusterset.name == self.current_clusterset)
             .ok_or_else(|| anyhow!("clusterset {} not found", self.current_clusterset))
     }
 
+    /// The active clusterset with the clusters of every other clusterset added to it. A cluster
+    /// listed in several clustersets is only included once.
+    pub fn all_clustersets(&self) -> Result<Clusterset> {
+        let mut merged = self.active_clusterset()?.clone();
+        for clusterset in &self.clustersets {
+            for cluster in &clusterset.clusters {
+                match merged.clusters.iter().find(|c| c.name == cluster.name) {
+                    Some(existing) if existing.target() == cluster.target() => {}
+                    Some(_) => {
+                        return Err(anyhow!(
+                        "cluster {} in clusterset {} differs from another cluster of the same name",
+                        cluster.name,
+                        clusterset.name
+                    ))
+                    }
+                    None => merged.clusters.push(cluster.clone()),
+                }
+            }
+        }
+        Ok(merged)
+    }
+
+    /// Adds the clusters of the clustersets each clusterset includes to it, after its own
+    /// clusters. A cluster reached through several includes is only added once.
+    pub fn resolve_includes(&mut self) -> Result<()> {
+        let resolved = self
+            .clustersets
+            .iter()
+            .map(|clusterset| self.included_clusters(clusterset, &mut Vec::new()))
+            .collect::<Result<Vec<_>>>()?;
+        for (clusterset, clusters) in self.clustersets.iter_mut().zip(resolved) {
+            clusterset.clusters = clusters;
+        }
+        Ok(())
+    }
+
+    // The clusters of a clusterset along with those of the clustersets it includes. `path` holds
+    // the clustersets being resolved, to catch clustersets including each other.
+    fn included_clusters(
+        &self,
+        clusterset: &Clusterset,
+        path: &mut Vec<String>,
+    ) -> Result<Vec<Cluster>> {
+        path.push(clusterset.name.clone());
+        if path[..path.len() - 1].contains(&clusterset.name) {
+            return Err(anyhow!(
+                "clustersets include each other: {}",
+                path.join(" -> ")
+            ));
+        }
+        let mut clusters = clusterset.clusters.clone();
+        for name in &clusterset.include {
+            let included = self
+                .clustersets
+                .iter()
+                .find(|cs| &cs.name == name)
+                .ok_or_else(|| {
+                    anyhow!(
+                        "clusterset {} includes clusterset {} which does not exist",
+                        clusterset.name,
+                        name
+                    )
+                })?;
+            for cluster in self.included_clusters(included, path)? {
+                match clusters.iter().find(|c| c.name == cluster.name) {
+                    Some(existing) if existing.target() == cluster.target() => {}
+                    Some(_) => {
+                        return Err(anyhow!(
+                            "cluster {} included from clusterset {} differs from another cluster of the same name in clusterset {}",
+                            cluster.name,
+                            name,
+                            clusterset.name
+                        ))
+                    }
+                    None => clusters.push(cluster),
+                }
+            }
+        }
+        path.pop();
+        Ok(clusters)
+    }
+
+    /// Output settings of the active clusterset, falling back to the config wide settings
+    pub fn output_settings(&self) -> OutputSettings {
+        let clusterset = self
+            .active_clusterset()
+            .ok()
+            .and_then(|clusterset| clusterset.output.clone())
+            .unwrap_or_default();
+        match &self.output {
+            Some(defaults) => clusterset.or(defaults),
+            None => clusterset,
+        }
+    }
+
     pub fn active_namespace(&self) -> Result<String> {
         match self.active_clusterset() {
             Ok(cs) => Ok(cs.namespace.clone()),
@@ -75,32 +231,285 @@ impl Config {
         }
     }
 
+    /// Disables or enables a cluster of the active clusterset. A cluster it includes from another
+    /// clusterset is left alone, since that would change the other clusterset as well, and the
+    /// error names the clusterset to do it in.
+    pub fn set_cluster_disabled(&mut self, cluster: &str, disabled: bool) -> Result<()> {
+        let name = self.current_clusterset.clone();
+        let clusterset = self
+            .clustersets
+            .iter()
+            .find(|clusterset| clusterset.name == name)
+            .ok_or_else(|| anyhow!("failed to find active cluster"))?;
+        if !clusterset.clusters.iter().any(|c| c.name == cluster) {
+            return match self.cluster_owner(clusterset, cluster, &mut Vec::new()) {
+                Some(owner) => Err(anyhow!(
+                    "cluster {} is included from clusterset {}, switch to it to change the cluster there",
+                    cluster,
+                    owner
+                )),
+                None => Err(anyhow!("cluster {} not found in clusterset {}", cluster, name)),
+            };
+        }
+        for clusterset in self.clustersets.iter_mut().filter(|cs| cs.name == name) {
+            for c in clusterset.clusters.iter_mut().filter(|c| c.name == cluster) {
+                c.disabled = disabled;
+            }
+        }
+        Ok(())
+    }
+
+    // The clusterset a cluster comes from when `clusterset` includes it from another one, `path`
+    // holding the clustersets searched so far
+    fn cluster_owner(
+        &self,
+        clusterset: &Clusterset,
+        cluster: &str,
+        path: &mut Vec<String>,
+    ) -> Option<String> {
+        if path.contains(&clusterset.name) {
+            return None;
+        }
+        path.push(clusterset.name.clone());
+        clusterset.include.iter().find_map(|name| {
+            let included = self.clustersets.iter().find(|cs| &cs.name == name)?;
+            if included.clusters.iter().any(|c| c.name == cluster) {
+                Some(included.name.clone())
+            } else {
+                self.cluster_owner(included, cluster, path)
+            }
+        })
+    }
+
+    /// Adds a cluster to a clusterset, the active one when `clusterset` is None. Names must be
+    /// unique within the clusterset, including the clusters it includes from others, and the
+    /// clustersets including it must still resolve with the cluster added.
+    pub fn add_cluster(&mut self, clusterset: Option<&str>, cluster: Cluster) -> Result<()> {
+        let name = clusterset.unwrap_or(&self.current_clusterset).to_owned();
+        let mut resolved = self.clone();
+        resolved.resolve_includes()?;
+        let existing = resolved
+            .clustersets
+            .iter()
+            .find(|cs| cs.name == name)
+            .ok_or_else(|| anyhow!("clusterset {} not found", name))?;
+        if existing.clusters.iter().any(|c| c.name == cluster.name) {
+            return Err(anyhow!(
+                "clusterset {} already has a cluster named {}",
+                name,
+                cluster.name
+            ));
+        }
+        let mut added = self.clone();
+        added
+            .clustersets
+            .iter_mut()
+            .find(|cs| cs.name == name)
+            .ok_or_else(|| anyhow!("clusterset {} not found", name))?
+            .clusters
+            .push(cluster);
+        // a config that does not resolve would fail every command once saved
+        added.clone().resolve_includes()?;
+        *self = added;
+        Ok(())
+    }
+
+    /// Pins the active clusterset to one of its clusters, or clears the pin when `cluster` is None
+    pub fn set_pinned_cluster(&mut self, cluster: Option<&str>) -> Result<()> {
+        if let Some(cluster) = cluster {
+            // the clusters of included clustersets can be pinned as well
+            let mut resolved = self.clone();
+            resolved.resolve_includes()?;
+            resolved
+                .active_clusterset()?
+                .select_clusters(&[cluster.to_owned()])?;
+        }
+        let clusterset = self
+            .clustersets
+            .iter_mut()
+            .find(|clusterset| clusterset.name == self.current_clusterset)
+            .ok_or_else(|| anyhow!("failed to find active cluster"))?;
+        clusterset.pinned_cluster = cluster.map(str::to_owned);
+        Ok(())
+    }
+
     /// Load from specified path, then environment variable, or finally default location
     pub fn load_config<P: AsRef<Path>>(path: Option<P>) -> Result<Config> {
-        if let Some(path) = path {
-            let data = fs::read_to_string(path).context("failed to load file")?;
-            parse_config(&data)
-        } else if let Some(path) = env_config_path() {
-            let data = fs::read_to_string(path).context("failed to load file")?;
-            parse_config(&data)
-        } else if let Some(path) = default_config_path() {
-            let data = fs::read_to_string(path).context("failed to load file")?;
-            parse_config(&data)
-        } else {
-            Err(anyhow!("failed to load config"))
+        let path = config_path(path)?;
+        let (_, mut config) = read_config(&path)?;
+        config.resolve_includes()?;
+        Ok(config)
+    }
+
+    /// Rewrites the config in the current version, keeping the original next to it as
+    /// `<config>.bak`. Returns the version it was migrated from, or None if it already was
+    /// current
+    pub async fn migrate_file<P: AsRef<Path>>(
+        path: Option<P>,
+    ) -> Result<(PathBuf, Option<String>)> {
+        let path = config_path(path)?;
+        let _lock = ConfigLock::acquire(&path).await?;
+        let data = fs::read_to_string(&path)
+            .with_context(|| format!("failed to read kubemc config {}", path.display()))?;
+        let (config, version) = parse_versioned_config(&path, &data)?;
+        if version == API_VERSION {
+            return Ok((path, None));
         }
+        let backup = sibling_path(&path, "bak");
+        write_atomic_like(&backup, &data, &path)
+            .with_context(|| format!("failed to back up config to {}", backup.display()))?;
+        write_atomic(&path, &serde_yaml::to_string(&config)?)?;
+        Ok((path, Some(version)))
     }
 
     pub fn load_config_from_default_file() -> Result<Config> {
         let path = default_config_path().unwrap_or_default();
-        let data = fs::read_to_string(path).context("failed to load file")?;
-        parse_config(&data)
+        let (_, mut config) = read_config(&path)?;
+        config.resolve_includes()?;
+        Ok(config)
     }
 
-    pub fn write_config_to_defaul(config: String) -> Result<()> {
+    /// Writes a new config to `path`, replacing any config there and creating its directory
+    pub async fn create_file(path: &Path, config: &Config) -> Result<()> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+        let _lock = ConfigLock::acquire(path).await?;
+        write_atomic(path, &serde_yaml::to_string(config)?)
+    }
+
+    pub async fn write_config_to_defaul(config: String) -> Result<()> {
         let path = default_config_path().unwrap_or_default();
-        fs::write(path, config).context("failed to write kubemc config")
+        let _lock = ConfigLock::acquire(&path).await?;
+        write_atomic(&path, &config)
+    }
+}
+
+/// A config read from disk for modification. The original contents are kept so saving can
+/// detect another kubemc changing the file in the meantime rather than losing its change.
+pub struct ConfigFile {
+    path: PathBuf,
+    original: String,
+    pub config: Config,
+}
+
+impl ConfigFile {
+    pub fn load(path: PathBuf) -> Result<Self> {
+        let (original, config) = read_config(&path)?;
+        Ok(Self {
+            path,
+            original,
+            config,
+        })
     }
+
+    /// Writes the config back, failing if the file changed since it was loaded. The previous
+    /// config is kept as `<config>.bak`
+    pub async fn save(&self) -> Result<()> {
+        let _lock = ConfigLock::acquire(&self.path).await?;
+        let current = fs::read_to_string(&self.path).context("failed to load file")?;
+        if current != self.original {
+            return Err(anyhow!(
+                "{} was changed by another kubemc command, run the command again",
+                self.path.display()
+            ));
+        }
+        let backup = sibling_path(&self.path, "bak");
+        write_atomic_like(&backup, &current, &self.path)
+            .with_context(|| format!("failed to back up config to {}", backup.display()))?;
+        write_atomic(&self.path, &serde_yaml::to_string(&self.config)?)
+    }
+}
+
+// Exclusive lock on a config file, held by creating `<config>.lock` and released on drop
+struct ConfigLock {
+    path: PathBuf,
+}
+
+impl ConfigLock {
+    async fn acquire(config_path: &Path) -> Result<Self> {
+        let path = sibling_path(config_path, "lock");
+        let start = Instant::now();
+        loop {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(mut file) => {
+                    let _ = write!(file, "{}", std::process::id());
+                    return Ok(Self { path });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if is_stale_lock(&path) {
+                        let _ = fs::remove_file(&path);
+                        continue;
+                    }
+                    if start.elapsed() > LOCK_TIMEOUT {
+                        return Err(anyhow!(
+                            "config is locked by another kubemc command, remove {} if none is running",
+                            path.display()
+                        ));
+                    }
+                    tokio::time::sleep(Duration::from_millis(50)).await;
+                }
+                Err(e) => {
+                    return Err(e).with_context(|| format!("failed to lock {}", path.display()))
+                }
+            }
+        }
+    }
+}
+
+impl Drop for ConfigLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn is_stale_lock(path: &Path) -> bool {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age > STALE_LOCK_AGE)
+}
+
+// Writes to a temporary file next to the target and renames it over the target, so readers
+// never see a partially written config. A symlinked config stays a symlink, the file it points
+// to is replaced, and the config keeps its permissions.
+fn write_atomic(path: &Path, contents: &str) -> Result<()> {
+    write_atomic_like(path, contents, path)
+}
+
+// Like `write_atomic`, giving the file the permissions of `like`, such as the config a backup is
+// made of
+fn write_atomic_like(path: &Path, contents: &str, like: &Path) -> Result<()> {
+    let path = fs::canonicalize(path).unwrap_or_else(|_| path.to_owned());
+    let permissions = fs::metadata(like).map(|m| m.permissions()).ok();
+    let tmp = sibling_path(&path, &format!("tmp-{}", std::process::id()));
+    let write = || -> io::Result<()> {
+        let mut file = fs::File::create(&tmp)?;
+        if let Some(permissions) = permissions {
+            file.set_permissions(permissions)?;
+        }
+        file.write_all(contents.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&tmp, &path)
+    };
+    write().map_err(|e| {
+        let _ = fs::remove_file(&tmp);
+        anyhow!(e).context("failed to write kubemc config")
+    })
+}
+
+// `~/.kube/kubemc` with extension `lock` becomes `~/.kube/kubemc.lock`
+fn sibling_path(path: &Path, extension: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(extension);
+    path.with_file_name(name)
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -112,10 +521,147 @@ pub struct Clusterset {
     pub namespace: String,
 
     /// Clusters to query as part of the clusterset
+    #[serde(default)]
     pub clusters: Vec<Cluster>,
+
+    /// Other clustersets whose clusters are part of this one, e.g. all-prod including prod-us
+    /// and prod-eu. Resolved when the config is loaded
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+
+    /// Cluster that commands are limited to until the pin is cleared
+    #[serde(
+        rename = "pinned-cluster",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub pinned_cluster: Option<String>,
+
+    /// Output settings for this clusterset, taking precedence over the config wide ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output: Option<OutputSettings>,
 }
 
-#[derive(Debug, Default, Clone, Deserialize, Serialize)]
+impl Clusterset {
+    /// A clusterset that only lives for one command, holding a cluster named after each of the
+    /// kubeconfig contexts and using the default namespace
+    pub fn from_contexts(contexts: &[String]) -> Result<Self> {
+        let mut clusters: Vec<Cluster> = Vec::new();
+        for context in contexts.iter().map(|c| c.trim()).filter(|c| !c.is_empty()) {
+            if clusters.iter().any(|cluster| cluster.name == context) {
+                return Err(anyhow!("context {} is given more than once", context));
+            }
+            clusters.push(Cluster {
+                name: context.to_owned(),
+                context: Some(context.to_owned()),
+                ..Default::default()
+            });
+        }
+        if clusters.is_empty() {
+            return Err(anyhow!("no kubeconfig context given"));
+        }
+        Ok(Self {
+            name: "<temporary>".into(),
+            namespace: "default".into(),
+            clusters,
+            include: Vec::new(),
+            pinned_cluster: None,
+            output: None,
+        })
+    }
+
+    /// Drops clusters under maintenance, returning their names
+    pub fn skip_maintenance(&mut self, now: DateTime<Utc>) -> Vec<String> {
+        let (skipped, clusters) = self
+            .clusters
+            .drain(..)
+            .partition(|cluster| cluster.in_maintenance(now));
+        self.clusters = clusters;
+        skipped
+            .into_iter()
+            .map(|cluster: Cluster| cluster.name)
+            .collect()
+    }
+
+    /// Drops disabled clusters, returning their names
+    pub fn skip_disabled(&mut self) -> Vec<String> {
+        let (skipped, clusters) = self
+            .clusters
+            .drain(..)
+            .partition(|cluster| cluster.disabled);
+        self.clusters = clusters;
+        skipped
+            .into_iter()
+            .map(|cluster: Cluster| cluster.name)
+            .collect()
+    }
+
+    /// Drops every cluster but the pinned one, if a cluster is pinned
+    pub fn apply_pin(&mut self) -> Result<()> {
+        if let Some(pinned) = &self.pinned_cluster {
+            self.clusters = self.select_clusters(std::slice::from_ref(pinned)).context(
+                "pinned cluster is no longer in the clusterset, clear it with `kubemc pin --clear`",
+            )?;
+        }
+        Ok(())
+    }
+
+    /// Marks clusters reaching the same target as an earlier cluster, so that clients are only
+    /// created and requests only made once for them while each keeps its own name in the output
+    pub fn coalesce(&mut self) {
+        for i in 0..self.clusters.len() {
+            let (earlier, rest) = self.clusters.split_at_mut(i);
+            let cluster = &mut rest[0];
+            if let Some(existing) = earlier
+                .iter()
+                .find(|c| c.same_as.is_none() && c.target() == cluster.target())
+            {
+                debug!("{} coalesced with {}", cluster.name, existing.name);
+                cluster.same_as = Some(existing.name.clone());
+            }
+        }
+    }
+
+    /// Drops the namespaces of single clusters so all of them use the namespace given on the
+    /// command line
+    pub fn clear_cluster_namespaces(&mut self) {
+        for cluster in &mut self.clusters {
+            cluster.namespace = None;
+        }
+    }
+
+    /// The namespace commands use on the named cluster, `default` unless it has its own
+    pub fn cluster_namespace(&self, clustername: &str, default: &str) -> String {
+        self.clusters
+            .iter()
+            .find(|cluster| cluster.name == clustername)
+            .map_or_else(
+                || default.to_owned(),
+                |cluster| cluster.namespace_or(default),
+            )
+    }
+
+    /// Returns the clusters matching the given names, or all clusters when no names are given
+    pub fn select_clusters(&self, names: &[String]) -> Result<Vec<Cluster>> {
+        if names.is_empty() {
+            return Ok(self.clusters.clone());
+        }
+        names
+            .iter()
+            .map(|name| {
+                self.clusters
+                    .iter()
+                    .find(|cluster| &cluster.name == name)
+                    .cloned()
+                    .ok_or_else(|| {
+                        anyhow!("cluster {} not found in clusterset {}", name, self.name)
+                    })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
 pub struct Cluster {
     /// The name used to associate cluster output with
     pub name: String,
@@ -131,6 +677,277 @@ pub struct Cluster {
     /// Allow users to specify a context rather than both the cluster and user
     #[serde(skip_serializing_if = "Option::is_none")]
     pub context: Option<String>,
+
+    /// Namespace to use on this cluster instead of the clusterset namespace, for fleets naming
+    /// the same application's namespace differently per cluster. --namespace overrides it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+
+    /// Marks the cluster as under maintenance, either `true` or until an RFC 3339 timestamp.
+    /// Commands skip it unless --include-maintenance is given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub maintenance: Option<Maintenance>,
+
+    /// Leaves the cluster out of every command until `kubemc cluster enable` is run for it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub disabled: bool,
+
+    /// Connection tuning applied when building the client for this cluster
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connection: Option<Connection>,
+
+    /// Kubeconfig files to read this cluster from, separated like KUBECONFIG. Defaults to the
+    /// --kubeconfig flag, then KUBECONFIG, then ~/.kube/config
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kubeconfig: Option<String>,
+
+    /// Shell command, such as `tsh kube login foo`, run when the cluster rejects the current
+    /// credentials before connecting again
+    #[serde(rename = "pre-command", skip_serializing_if = "Option::is_none")]
+    pub pre_command: Option<String>,
+
+    /// Identity to impersonate on this cluster, taking precedence over --as and --as-group
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub impersonate: Option<Impersonation>,
+
+    /// API server to connect to directly instead of through a kubeconfig, e.g. a spoke cluster
+    /// reached from a pod in a management cluster
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub server: Option<String>,
+
+    /// File holding the bearer token sent to `server`, such as a mounted service account token.
+    /// It is read again periodically so rotated tokens are picked up
+    #[serde(rename = "token-file", skip_serializing_if = "Option::is_none")]
+    pub token_file: Option<String>,
+
+    /// CA certificate file `server` is verified with, defaults to the system roots
+    #[serde(
+        rename = "certificate-authority",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub certificate_authority: Option<String>,
+
+    /// Connect to the cluster kubemc runs in with the service account of its pod
+    #[serde(
+        rename = "in-cluster",
+        default,
+        skip_serializing_if = "std::ops::Not::not"
+    )]
+    pub in_cluster: bool,
+
+    /// Set on clusters of merged clustersets reaching the same target as an earlier cluster,
+    /// which answers for both
+    #[serde(skip)]
+    pub same_as: Option<String>,
+}
+
+/// A user and groups to act as through Kubernetes impersonation
+#[derive(Debug, Default, Clone, PartialEq, Hash, Deserialize, Serialize)]
+pub struct Impersonation {
+    /// User to impersonate
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+
+    /// Groups to impersonate
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+}
+
+type ClusterTarget<'a> = (
+    Option<&'a str>,
+    Option<&'a str>,
+    Option<&'a str>,
+    Option<&'a str>,
+    Option<&'a Impersonation>,
+    Option<&'a str>,
+    Option<&'a str>,
+    bool,
+);
+
+/// A maintenance marker, either on/off or lasting until a point in time
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Maintenance {
+    Enabled(bool),
+    Until(DateTime<Utc>),
+}
+
+impl Cluster {
+    /// Whether the cluster is under maintenance at `now`
+    pub fn in_maintenance(&self, now: DateTime<Utc>) -> bool {
+        match self.maintenance {
+            Some(Maintenance::Enabled(enabled)) => enabled,
+            Some(Maintenance::Until(until)) => now < until,
+            None => false,
+        }
+    }
+
+    /// The namespace commands use on this cluster, its own or else `default`
+    pub fn namespace_or(&self, default: &str) -> String {
+        self.namespace.as_deref().unwrap_or(default).to_owned()
+    }
+
+    // What the cluster connects to. Clusters with the same target reach the same API server
+    // as the same user and look at the same namespace.
+    fn target(&self) -> ClusterTarget<'_> {
+        (
+            self.kubeconfig.as_deref(),
+            self.context.as_deref(),
+            self.cluster.as_deref(),
+            self.user.as_deref(),
+            self.impersonate.as_ref(),
+            self.namespace.as_deref(),
+            self.server.as_deref(),
+            self.in_cluster,
+        )
+    }
+
+    /// A hash of what the cluster connects to, keeping apart what was fetched through other
+    /// kubeconfigs, contexts or impersonated users under the same cluster name
+    pub fn target_key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.target().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Whether the cluster is looked up in a kubeconfig, rather than given by `server` or
+    /// `in-cluster`
+    pub fn uses_kubeconfig(&self) -> bool {
+        self.server.is_none() && !self.in_cluster
+    }
+
+    // Settings that make no sense together with `server` or `in-cluster`
+    fn direct_connection_errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let kubeconfig_fields = [
+            ("context", self.context.is_some()),
+            ("cluster", self.cluster.is_some()),
+            ("user", self.user.is_some()),
+        ];
+        let conflicting = |with: &str| {
+            kubeconfig_fields
+                .iter()
+                .filter(|(_, set)| *set)
+                .map(|(field, _)| format!("{} cannot be set together with {}", field, with))
+                .collect::<Vec<_>>()
+        };
+        if self.in_cluster {
+            errors.extend(conflicting("in-cluster"));
+            if self.server.is_some() {
+                errors.push("server cannot be set together with in-cluster".into());
+            }
+        } else if self.server.is_some() {
+            errors.extend(conflicting("server"));
+        }
+        if self.server.is_none() {
+            for (field, set) in [
+                ("token-file", self.token_file.is_some()),
+                (
+                    "certificate-authority",
+                    self.certificate_authority.is_some(),
+                ),
+            ] {
+                if set {
+                    errors.push(format!("{} is only used together with server", field));
+                }
+            }
+        }
+        errors
+    }
+}
+
+/// Defaults for how results are printed. Command line flags take precedence.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct OutputSettings {
+    /// Output format used when --output is not given
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<OutputFormat>,
+
+    /// Labels shown as extra columns when listing resources
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub label_columns: Vec<String>,
+
+    /// Table and csv columns to leave out, such as `age`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub hide_columns: Vec<String>,
+
+    /// Table and csv column to sort rows by
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort_by: Option<String>,
+}
+
+impl OutputSettings {
+    /// Fills in whatever is unset from `defaults`
+    pub fn or(self, defaults: &OutputSettings) -> Self {
+        let or_vec = |values: Vec<String>, defaults: &Vec<String>| {
+            if values.is_empty() {
+                defaults.clone()
+            } else {
+                values
+            }
+        };
+        Self {
+            format: self.format.or(defaults.format),
+            label_columns: or_vec(self.label_columns, &defaults.label_columns),
+            hide_columns: or_vec(self.hide_columns, &defaults.hide_columns),
+            sort_by: self.sort_by.or_else(|| defaults.sort_by.clone()),
+        }
+    }
+}
+
+/// Transport settings for a single cluster. All durations are in seconds.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Connection {
+    /// Timeout for establishing the TCP and TLS connection
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connect_timeout: Option<u64>,
+
+    /// Timeout for reading a response from the API server
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub read_timeout: Option<u64>,
+
+    /// How long an idle connection is kept in the pool before being closed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pool_idle_timeout: Option<u64>,
+
+    /// Interval for TCP keep-alive probes on the underlying socket
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tcp_keepalive: Option<u64>,
+
+    /// Negotiate HTTP/2 with the API server
+    #[serde(default)]
+    pub http2: bool,
+
+    /// Interval for HTTP/2 PING frames, sent even when the connection is idle
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http2_keep_alive_interval: Option<u64>,
+
+    /// How long to wait for an HTTP/2 PING acknowledgement before closing the connection
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http2_keep_alive_timeout: Option<u64>,
+
+    /// How many times list and get requests are retried after throttling, server errors or
+    /// failed connections. Defaults to 3, 0 disables retries
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+
+    /// Delay before the first retry, doubled on every further retry. Fractions are allowed,
+    /// defaults to 0.2
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub retry_backoff: Option<f64>,
+}
+
+impl Connection {
+    /// Whether the settings require a hand-built transport rather than the kube default one
+    pub fn requires_custom_transport(&self) -> bool {
+        self.pool_idle_timeout.is_some()
+            || self.tcp_keepalive.is_some()
+            || self.http2
+            || self.http2_keep_alive_interval.is_some()
+            || self.http2_keep_alive_timeout.is_some()
+    }
 }
 
 impl From<Cluster> for KubeConfigOptions {
@@ -153,8 +970,357 @@ impl From<&Cluster> for KubeConfigOptions {
     }
 }
 
-fn parse_config(c: &str) -> Result<Config> {
-    Ok(serde_yaml::from_str(c)?)
+/// Reads and merges kubeconfig files from a list of paths separated like KUBECONFIG, where earlier
+/// files take precedence. Without paths the KUBECONFIG variable or the default file is used.
+pub fn read_kubeconfig(paths: Option<&str>) -> Result<Kubeconfig> {
+    if paths.is_none() {
+        return Ok(Kubeconfig::read()?);
+    }
+    kubeconfig_paths(paths)
+        .into_iter()
+        .try_fold(Kubeconfig::default(), |merged, path| {
+            let kubeconfig = Kubeconfig::read_from(&path)
+                .with_context(|| format!("failed to read kubeconfig {}", path.display()))?;
+            Ok(merged.merge(kubeconfig)?)
+        })
+}
+
+/// The kubeconfig files `read_kubeconfig` reads for `paths`
+pub fn kubeconfig_paths(paths: Option<&str>) -> Vec<PathBuf> {
+    let Some(paths) = paths
+        .map(str::to_owned)
+        .or_else(|| std::env::var("KUBECONFIG").ok())
+    else {
+        return home_dir()
+            .map(|home| home.join(".kube").join("config"))
+            .into_iter()
+            .collect();
+    };
+    std::env::split_paths(&paths)
+        .filter(|path| !path.as_os_str().is_empty())
+        .map(|path| match (path.strip_prefix("~"), home_dir()) {
+            (Ok(rest), Some(home)) => home.join(rest),
+            _ => path,
+        })
+        .collect()
+}
+
+/// A problem found while validating the config, along with where it was found
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationError {
+    pub location: String,
+    pub message: String,
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+impl Config {
+    /// Checks the config is consistent and that every cluster, user and context it refers to
+    /// exists in the kubeconfig. Clusters with their own kubeconfig are checked against it rather
+    /// than `kubeconfig`.
+    pub fn validate(&self, kubeconfig: &Kubeconfig) -> Vec<ValidationError> {
+        let mut errors = Vec::new();
+        let mut error =
+            |location: String, message: String| errors.push(ValidationError { location, message });
+        if !self
+            .clustersets
+            .iter()
+            .any(|cs| cs.name == self.current_clusterset)
+        {
+            error(
+                "current-clusterset".into(),
+                format!(
+                    "clusterset {:?} does not exist, expected one of: {}",
+                    self.current_clusterset,
+                    self.clustersets
+                        .iter()
+                        .map(|cs| cs.name.as_str())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            );
+        }
+
+        let mut clusterset_names = HashSet::new();
+        for clusterset in &self.clustersets {
+            let location = format!("clusterset {}", clusterset.name);
+            if !clusterset_names.insert(&clusterset.name) {
+                error(
+                    location.clone(),
+                    "clusterset name is used more than once".into(),
+                );
+            }
+            if !is_dns1123_label(&clusterset.namespace) {
+                error(
+                    location.clone(),
+                    format!(
+                        "namespace {:?} must be a lowercase RFC 1123 label of at most 63 \
+                         characters",
+                        clusterset.namespace
+                    ),
+                );
+            }
+            if let Some(pinned) = &clusterset.pinned_cluster {
+                if !clusterset.clusters.iter().any(|c| &c.name == pinned) {
+                    error(
+                        location.clone(),
+                        format!(
+                            "pinned cluster {} is not in the clusterset, clear it with `kubemc pin --clear`",
+                            pinned
+                        ),
+                    );
+                }
+            }
+
+            let mut cluster_names = HashSet::new();
+            for cluster in &clusterset.clusters {
+                let location = format!("{}, cluster {}", location, cluster.name);
+                if !cluster_names.insert(&cluster.name) {
+                    error(
+                        location.clone(),
+                        "cluster name is used more than once, output could not tell them apart"
+                            .into(),
+                    );
+                }
+                if cluster
+                    .impersonate
+                    .as_ref()
+                    .is_some_and(|i| i.user.is_none() && !i.groups.is_empty())
+                {
+                    error(
+                        location.clone(),
+                        "impersonate sets groups without a user, Kubernetes only impersonates \
+                         groups along with a user"
+                            .into(),
+                    );
+                }
+                for message in cluster.direct_connection_errors() {
+                    error(location.clone(), message);
+                }
+                if !cluster.uses_kubeconfig() {
+                    continue;
+                }
+                let own_kubeconfig = match cluster
+                    .kubeconfig
+                    .as_deref()
+                    .map(|paths| read_kubeconfig(Some(paths)))
+                {
+                    Some(Ok(own)) => Some(own),
+                    Some(Err(e)) => {
+                        error(location, format!("{:#}", e));
+                        continue;
+                    }
+                    None => None,
+                };
+                let kubeconfig = own_kubeconfig.as_ref().unwrap_or(kubeconfig);
+                let contexts: Vec<&str> = kubeconfig
+                    .contexts
+                    .iter()
+                    .map(|c| c.name.as_str())
+                    .collect();
+                let clusters: Vec<&str> = kubeconfig
+                    .clusters
+                    .iter()
+                    .map(|c| c.name.as_str())
+                    .collect();
+                let users: Vec<&str> = kubeconfig
+                    .auth_infos
+                    .iter()
+                    .map(|u| u.name.as_str())
+                    .collect();
+                let references = [
+                    ("context", &cluster.context, &contexts),
+                    ("cluster", &cluster.cluster, &clusters),
+                    ("user", &cluster.user, &users),
+                ];
+                for (field, reference, available) in references {
+                    if let Some(name) = reference {
+                        if !available.contains(&name.as_str()) {
+                            error(
+                                location.clone(),
+                                format!(
+                                    "{} {:?} not found in kubeconfig, available: {}",
+                                    field,
+                                    name,
+                                    available.join(", ")
+                                ),
+                            );
+                        }
+                    }
+                }
+                if cluster.context.is_none() && cluster.cluster.is_none() {
+                    error(
+                        location,
+                        "neither context nor cluster is set, one is needed to pick a kubeconfig \
+                         cluster"
+                            .into(),
+                    );
+                }
+            }
+        }
+        errors
+    }
+}
+
+// Namespaces must be RFC 1123 labels: lowercase alphanumerics and '-', starting and ending with
+// an alphanumeric
+pub(crate) fn is_dns1123_label(name: &str) -> bool {
+    let alphanumeric = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit();
+    !name.is_empty()
+        && name.len() <= 63
+        && name.chars().all(|c| alphanumeric(c) || c == '-')
+        && name.starts_with(alphanumeric)
+        && name.ends_with(alphanumeric)
+}
+
+// Reads and parses the config at `path`, returning the raw contents along with it
+fn read_config(path: &Path) -> Result<(String, Config)> {
+    let data = fs::read_to_string(path)
+        .with_context(|| format!("failed to read kubemc config {}", path.display()))?;
+    let (config, version) = parse_versioned_config(path, &data)?;
+    if version != API_VERSION {
+        debug!(
+            "{} uses {}, run `kubemc config migrate` to upgrade it to {}",
+            path.display(),
+            version,
+            API_VERSION
+        );
+    }
+    Ok((data, config))
+}
+
+// Parses a config of any supported version, upgrading older versions in memory. Returns the
+// version the config was written in along with it
+fn parse_versioned_config(path: &Path, data: &str) -> Result<(Config, String)> {
+    let diagnostic = |e: serde_yaml::Error| anyhow!("{}", config_diagnostic(path, data, &e));
+    let mut value: serde_yaml::Value = serde_yaml::from_str(data).map_err(diagnostic)?;
+    let version = config_version(&value).map_err(|e| {
+        anyhow!(
+            "{}",
+            with_kubeconfig_hint(
+                format!("invalid kubemc config {}: {}", path.display(), e),
+                data
+            )
+        )
+    })?;
+    if version == API_VERSION {
+        // parsing the text rather than the value keeps line numbers in errors
+        let config = serde_yaml::from_str(data).map_err(diagnostic)?;
+        return Ok((config, version));
+    }
+    migrate(&mut value, &version);
+    let config = serde_yaml::from_value(value).map_err(diagnostic)?;
+    Ok((config, version))
+}
+
+fn config_version(value: &serde_yaml::Value) -> Result<String> {
+    let version = value
+        .get("apiVersion")
+        .and_then(|version| version.as_str())
+        .ok_or_else(|| anyhow!("missing apiVersion, expected {}", API_VERSION))?;
+    let known = version == API_VERSION || MIGRATIONS.iter().any(|(from, ..)| *from == version);
+    if !known {
+        let supported: Vec<&str> = MIGRATIONS
+            .iter()
+            .map(|(from, ..)| *from)
+            .chain([API_VERSION])
+            .collect();
+        return Err(anyhow!(
+            "unsupported apiVersion {}, this kubemc reads {}. The config may need a newer kubemc",
+            version,
+            supported.join(", ")
+        ));
+    }
+    Ok(version.to_owned())
+}
+
+// Upgrades a config one version at a time from `version` to the current version
+fn migrate(value: &mut serde_yaml::Value, version: &str) {
+    let mut version = version.to_owned();
+    while let Some((_, to, step)) = MIGRATIONS.iter().find(|(from, ..)| *from == version) {
+        step(value);
+        version = to.to_string();
+    }
+    value["apiVersion"] = version.into();
+}
+
+// v1alpha1 also accepted `preCommand` for clusters, v1beta1 only `pre-command`
+fn v1alpha1_to_v1beta1(value: &mut serde_yaml::Value) {
+    let clustersets = value
+        .get_mut("clustersets")
+        .and_then(|clustersets| clustersets.as_sequence_mut());
+    for clusterset in clustersets.into_iter().flatten() {
+        let clusters = clusterset
+            .get_mut("clusters")
+            .and_then(|clusters| clusters.as_sequence_mut());
+        for cluster in clusters.into_iter().flatten() {
+            let Some(cluster) = cluster.as_mapping_mut() else {
+                continue;
+            };
+            if let Some(command) = cluster.remove("preCommand") {
+                if !cluster.contains_key("pre-command") {
+                    cluster.insert("pre-command".into(), command);
+                }
+            }
+        }
+    }
+}
+
+// Explains a config that failed to parse: the file, the error with the offending line marked,
+// and a hint when the file is a kubeconfig rather than a kubemc config
+fn config_diagnostic(path: &Path, data: &str, error: &serde_yaml::Error) -> String {
+    let mut message = format!(
+        "failed to parse kubemc config {}: {}",
+        path.display(),
+        error
+    );
+    if let Some(location) = error.location() {
+        if let Some(line) = data.lines().nth(location.line().saturating_sub(1)) {
+            let number = location.line().to_string();
+            message.push_str(&format!(
+                "\n {} | {}\n {} | {}^",
+                number,
+                line,
+                " ".repeat(number.len()),
+                " ".repeat(location.column().saturating_sub(1))
+            ));
+        }
+    }
+    with_kubeconfig_hint(message, data)
+}
+
+// Adds a hint when the file is a kubeconfig rather than a kubemc config
+fn with_kubeconfig_hint(mut message: String, data: &str) -> String {
+    if is_kubeconfig(data) {
+        message.push_str(
+            "\nthis is a kubeconfig, not a kubemc config. Point KUBEMC_CONFIG or --config-file at \
+             a kubemc config (see `kubemc generate-config`) and pass kubeconfigs with \
+             --kubeconfig or KUBECONFIG",
+        );
+    }
+    message
+}
+
+fn is_kubeconfig(data: &str) -> bool {
+    let Ok(value) = serde_yaml::from_str::<serde_yaml::Value>(data) else {
+        return false;
+    };
+    value.get("kind").and_then(|kind| kind.as_str()) == Some("Config")
+        || (value.get("contexts").is_some() && value.get("clustersets").is_none())
+}
+
+/// The specified path, then the environment variable, or finally the default location
+pub fn config_path<P: AsRef<Path>>(path: Option<P>) -> Result<PathBuf> {
+    match path {
+        Some(path) => Ok(path.as_ref().to_path_buf()),
+        None => env_config_path()
+            .or_else(default_config_path)
+            .ok_or_else(|| anyhow!("failed to load config")),
+    }
 }
 
 fn default_config_path() -> Option<PathBuf> {
@@ -173,9 +1339,672 @@ fn env_config_path() -> Option<PathBuf> {
 impl Default for Config {
     fn default() -> Self {
         Self {
-            api_version: "kubemc/v1alpha1".into(),
+            api_version: API_VERSION.into(),
             current_clusterset: "".into(),
             clustersets: Default::default(),
+            telemetry: false,
+            otlp_endpoint: None,
+            output: None,
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn parse_config(c: &str) -> Result<Config> {
+        Ok(serde_yaml::from_str(c)?)
+    }
+
+    #[test]
+    fn parse_cluster_connection() {
+        let config = parse_config(
+            r#"
+apiVersion: kubemc/v1alpha1
+current-clusterset: cs1
+clustersets:
+- name: cs1
+  namespace: default
+  clusters:
+  - name: cluster1
+    context: ctx1
+    connection:
+      connect-timeout: 5
+      http2: true
+      http2-keep-alive-interval: 30
+  - name: cluster2
+    context: ctx2
+    connection:
+      read-timeout: 10
+"#,
+        )
+        .unwrap();
+        let clusters = &config.active_clusterset().unwrap().clusters;
+        let first = clusters[0].connection.as_ref().unwrap();
+        assert_eq!(first.connect_timeout, Some(5));
+        assert_eq!(first.http2_keep_alive_interval, Some(30));
+        assert!(first.requires_custom_transport());
+        assert!(!clusters[1]
+            .connection
+            .as_ref()
+            .unwrap()
+            .requires_custom_transport());
+    }
+
+    #[test]
+    fn pin_cluster() {
+        let mut config = parse_config(
+            r#"
+apiVersion: kubemc/v1alpha1
+current-clusterset: cs1
+clustersets:
+- name: cs1
+  namespace: default
+  clusters:
+  - name: cluster1
+  - name: cluster2
+"#,
+        )
+        .unwrap();
+        assert!(config.set_pinned_cluster(Some("cluster3")).is_err());
+        config.set_pinned_cluster(Some("cluster2")).unwrap();
+
+        let mut clusterset = config.active_clusterset().unwrap().clone();
+        clusterset.apply_pin().unwrap();
+        let names: Vec<&str> = clusterset
+            .clusters
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["cluster2"]);
+
+        config.set_pinned_cluster(None).unwrap();
+        let yaml = serde_yaml::to_string(&config).unwrap();
+        assert!(!yaml.contains("pinned-cluster"));
+    }
+
+    #[test]
+    fn direct_cluster_connections() {
+        let config = parse_config(
+            r#"
+apiVersion: kubemc/v1beta1
+current-clusterset: spokes
+clustersets:
+- name: spokes
+  namespace: default
+  clusters:
+  - name: spoke1
+    server: https://spoke1.example.com:6443
+    token-file: /var/run/secrets/spoke1/token
+    certificate-authority: /var/run/secrets/spoke1/ca.crt
+  - name: hub
+    in-cluster: true
+  - name: mixed
+    in-cluster: true
+    server: https://spoke2.example.com
+    context: spoke2
+  - name: stray
+    context: east
+    token-file: /token
+"#,
+        )
+        .unwrap();
+        let clusters = &config.clustersets[0].clusters;
+        assert!(!clusters[0].uses_kubeconfig());
+        assert!(!clusters[1].uses_kubeconfig());
+        assert!(clusters[3].uses_kubeconfig());
+        let kubeconfig = Kubeconfig::from_yaml(
+            r#"
+apiVersion: v1
+kind: Config
+contexts:
+- name: east
+  context:
+    cluster: east
+    user: admin
+"#,
+        )
+        .unwrap();
+        let errors: Vec<String> = config
+            .validate(&kubeconfig)
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "clusterset spokes, cluster mixed: context cannot be set together with in-cluster",
+                "clusterset spokes, cluster mixed: server cannot be set together with in-cluster",
+                "clusterset spokes, cluster stray: token-file is only used together with server",
+            ]
+        );
+        let yaml = serde_yaml::to_string(&config).unwrap();
+        assert!(yaml.contains("in-cluster: true"));
+        assert!(yaml.contains("token-file: /var/run/secrets/spoke1/token"));
+    }
+
+    #[test]
+    fn validate_against_kubeconfig() {
+        let config = parse_config(
+            r#"
+apiVersion: kubemc/v1alpha1
+current-clusterset: cs1
+clustersets:
+- name: cs1
+  namespace: Team_A
+  clusters:
+  - name: east
+    context: east
+  - name: east
+    cluster: west
+    user: admin
+  - name: readonly
+    context: east
+    impersonate:
+      groups: [viewers]
+"#,
+        )
+        .unwrap();
+        let kubeconfig = Kubeconfig::from_yaml(
+            r#"
+apiVersion: v1
+kind: Config
+clusters:
+- name: east
+  cluster:
+    server: https://east
+contexts:
+- name: east
+  context:
+    cluster: east
+    user: admin
+users:
+- name: admin
+  user:
+    token: abc
+"#,
+        )
+        .unwrap();
+        let errors: Vec<String> = config
+            .validate(&kubeconfig)
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                r#"clusterset cs1: namespace "Team_A" must be a lowercase RFC 1123 label of at most 63 characters"#,
+                "clusterset cs1, cluster east: cluster name is used more than once, output could not tell them apart",
+                r#"clusterset cs1, cluster east: cluster "west" not found in kubeconfig, available: east"#,
+                "clusterset cs1, cluster readonly: impersonate sets groups without a user, Kubernetes only impersonates groups along with a user",
+            ]
+        );
+        assert!(is_dns1123_label("team-a"));
+        assert!(!is_dns1123_label("-team"));
+    }
+
+    #[test]
+    fn merge_kubeconfig_paths() {
+        let dir = std::env::temp_dir().join(format!("kubemc-kubeconfig-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let write = |name: &str, server: &str| {
+            let path = dir.join(name);
+            let kubeconfig = format!(
+                "apiVersion: v1\nkind: Config\nclusters:\n- name: shared\n  cluster:\n    server: {}\n- name: {}\n  cluster:\n    server: {}\n",
+                server, name, server
+            );
+            fs::write(&path, kubeconfig).unwrap();
+            path
+        };
+        let first = write("first", "https://first");
+        let second = write("second", "https://second");
+        let paths = std::env::join_paths([&first, &second]).unwrap();
+
+        let merged = read_kubeconfig(paths.to_str()).unwrap();
+        let clusters: Vec<(&str, Option<&str>)> = merged
+            .clusters
+            .iter()
+            .map(|c| {
+                let server = c.cluster.as_ref().and_then(|c| c.server.as_deref());
+                (c.name.as_str(), server)
+            })
+            .collect();
+        assert_eq!(
+            clusters,
+            vec![
+                ("shared", Some("https://first")),
+                ("first", Some("https://first")),
+                ("second", Some("https://second")),
+            ]
+        );
+        assert!(read_kubeconfig(dir.join("missing").to_str()).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn detect_concurrent_config_writes() {
+        let dir = std::env::temp_dir().join(format!("kubemc-config-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("kubemc");
+        fs::write(&path, Config::yaml().unwrap()).unwrap();
+
+        let mut first = ConfigFile::load(path.clone()).unwrap();
+        let mut second = ConfigFile::load(path.clone()).unwrap();
+        first.config.set_namespace("team-a").unwrap();
+        first.save().await.unwrap();
+        second.config.set_namespace("team-b").unwrap();
+        assert!(second.save().await.is_err());
+
+        let saved = ConfigFile::load(path.clone()).unwrap();
+        assert_eq!(saved.config.active_namespace().unwrap(), "team-a");
+        assert!(!sibling_path(&path, "lock").exists());
+        assert_eq!(
+            fs::read_to_string(sibling_path(&path, "bak")).unwrap(),
+            Config::yaml().unwrap()
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn keep_config_links_and_permissions() {
+        use std::os::unix::fs::{symlink, PermissionsExt};
+
+        let dir = std::env::temp_dir().join(format!("kubemc-config-link-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let target = dir.join("kubemc-real");
+        fs::write(&target, Config::yaml().unwrap()).unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o600)).unwrap();
+        let link = dir.join("kubemc");
+        symlink(&target, &link).unwrap();
+
+        let mut file = ConfigFile::load(link.clone()).unwrap();
+        file.config.set_namespace("team-a").unwrap();
+        file.save().await.unwrap();
+
+        let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert!(fs::symlink_metadata(&link).unwrap().is_symlink());
+        assert_eq!(mode(&target), 0o600);
+        assert_eq!(mode(&sibling_path(&link, "bak")), 0o600);
+        let saved = ConfigFile::load(target).unwrap();
+        assert_eq!(saved.config.active_namespace().unwrap(), "team-a");
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn skip_clusters_in_maintenance() {
+        let config = parse_config(
+            r#"
+apiVersion: kubemc/v1alpha1
+current-clusterset: cs1
+clustersets:
+- name: cs1
+  namespace: default
+  clusters:
+  - name: upgrading
+    maintenance: true
+  - name: done
+    maintenance: false
+  - name: window
+    maintenance: 2030-01-01T06:00:00Z
+  - name: plain
+"#,
+        )
+        .unwrap();
+        let mut clusterset = config.active_clusterset().unwrap().clone();
+        let now: DateTime<Utc> = "2030-01-01T05:00:00Z".parse().unwrap();
+        assert_eq!(
+            clusterset.skip_maintenance(now),
+            vec!["upgrading", "window"]
+        );
+        let names: Vec<&str> = clusterset
+            .clusters
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["done", "plain"]);
+
+        let later: DateTime<Utc> = "2030-01-01T07:00:00Z".parse().unwrap();
+        let mut clusterset = config.active_clusterset().unwrap().clone();
+        assert_eq!(clusterset.skip_maintenance(later), vec!["upgrading"]);
+    }
+
+    #[test]
+    fn temporary_clusterset() {
+        let contexts = vec!["kind-a".to_string(), " kind-b".to_string()];
+        let clusterset = Clusterset::from_contexts(&contexts).unwrap();
+        assert_eq!(clusterset.namespace, "default");
+        let names: Vec<(&str, Option<&str>)> = clusterset
+            .clusters
+            .iter()
+            .map(|c| (c.name.as_str(), c.context.as_deref()))
+            .collect();
+        assert_eq!(
+            names,
+            [("kind-a", Some("kind-a")), ("kind-b", Some("kind-b"))]
+        );
+        assert!(Clusterset::from_contexts(&["a".into(), "a".into()]).is_err());
+        assert!(Clusterset::from_contexts(&["".into()]).is_err());
+    }
+
+    #[test]
+    fn cluster_namespace_overrides() {
+        let config = parse_config(
+            r#"
+apiVersion: kubemc/v1beta1
+current-clusterset: prod
+clustersets:
+- name: prod
+  namespace: app
+  clusters:
+  - name: prod-us
+    context: prod-ctx
+    namespace: app-prod-us
+  - name: prod-eu
+    context: prod-ctx
+    namespace: app-prod-eu
+  - name: prod-ap
+    context: prod-ap-ctx
+"#,
+        )
+        .unwrap();
+        let mut clusterset = config.active_clusterset().unwrap().clone();
+        assert_eq!(
+            clusterset.cluster_namespace("prod-us", "app"),
+            "app-prod-us"
+        );
+        assert_eq!(clusterset.cluster_namespace("prod-ap", "app"), "app");
+
+        // the same context looking at different namespaces is not coalesced
+        clusterset.coalesce();
+        assert!(clusterset.clusters.iter().all(|c| c.same_as.is_none()));
+
+        clusterset.clear_cluster_namespaces();
+        assert_eq!(clusterset.cluster_namespace("prod-eu", "other"), "other");
+    }
+
+    #[test]
+    fn disable_clusters() {
+        let mut config = parse_config(
+            r#"
+apiVersion: kubemc/v1beta1
+current-clusterset: cs1
+clustersets:
+- name: cs1
+  namespace: default
+  clusters:
+  - name: prod-eu
+  - name: prod-us
+"#,
+        )
+        .unwrap();
+        config.set_cluster_disabled("prod-eu", true).unwrap();
+        assert!(config.set_cluster_disabled("prod-ap", true).is_err());
+        let yaml = serde_yaml::to_string(&config).unwrap();
+        assert_eq!(yaml.matches("disabled: true").count(), 1);
+
+        let mut clusterset = parse_config(&yaml)
+            .unwrap()
+            .active_clusterset()
+            .unwrap()
+            .clone();
+        assert_eq!(clusterset.skip_disabled(), vec!["prod-eu"]);
+        assert_eq!(clusterset.clusters[0].name, "prod-us");
+
+        config.set_cluster_disabled("prod-eu", false).unwrap();
+        assert!(!serde_yaml::to_string(&config).unwrap().contains("disabled"));
+        let mut config = parse_config(
+            r#"
+apiVersion: kubemc/v1beta1
+current-clusterset: all
+clustersets:
+- name: prod
+  namespace: default
+  clusters:
+  - name: prod-eu
+- name: all
+  namespace: default
+  include: [prod]
+"#,
+        )
+        .unwrap();
+        let error = config
+            .set_cluster_disabled("prod-eu", true)
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("included from clusterset prod"), "{}", error);
+    }
+
+    #[test]
+    fn add_clusters() {
+        let mut config = parse_config(
+            r#"
+apiVersion: kubemc/v1beta1
+current-clusterset: prod
+clustersets:
+- name: prod
+  namespace: default
+  clusters:
+  - name: prod-eu
+- name: all
+  namespace: default
+  include: [prod]
+  clusters:
+  - name: staging
+"#,
+        )
+        .unwrap();
+        let cluster = |name: &str| Cluster {
+            name: name.to_owned(),
+            context: Some(name.to_owned()),
+            ..Default::default()
+        };
+        config.add_cluster(None, cluster("prod-us")).unwrap();
+        assert!(config.add_cluster(None, cluster("prod-us")).is_err());
+        // included clusters count as well
+        assert!(config.add_cluster(Some("all"), cluster("prod-eu")).is_err());
+        assert!(config.add_cluster(Some("dev"), cluster("dev-1")).is_err());
+        config.add_cluster(Some("all"), cluster("qa")).unwrap();
+        // all includes prod and has its own staging, which must not differ from prod's
+        let other_staging = Cluster {
+            context: Some("other".into()),
+            ..cluster("staging")
+        };
+        assert!(config.add_cluster(Some("prod"), other_staging).is_err());
+
+        let names = |cs: &Clusterset| -> Vec<String> {
+            cs.clusters.iter().map(|c| c.name.clone()).collect()
+        };
+        assert_eq!(names(&config.clustersets[0]), ["prod-eu", "prod-us"]);
+        assert_eq!(names(&config.clustersets[1]), ["staging", "qa"]);
+        let yaml = serde_yaml::to_string(&config).unwrap();
+        assert!(yaml.contains("context: prod-us"));
+    }
+
+    #[test]
+    fn coalesce_clusters_across_clustersets() {
+        let config = parse_config(
+            r#"
+apiVersion: kubemc/v1alpha1
+current-clusterset: cs1
+clustersets:
+- name: cs1
+  namespace: default
+  clusters:
+  - name: prod
+    context: prod-ctx
+  - name: staging
+    context: staging-ctx
+- name: cs2
+  namespace: default
+  clusters:
+  - name: prod
+    context: prod-ctx
+  - name: prod-eu
+    context: prod-ctx
+  - name: dev
+    context: dev-ctx
+"#,
+        )
+        .unwrap();
+        let mut clusterset = config.all_clustersets().unwrap();
+        assert_eq!(clusterset.clusters.len(), 4);
+        clusterset.coalesce();
+        let names: Vec<(&str, Option<&str>)> = clusterset
+            .clusters
+            .iter()
+            .map(|c| (c.name.as_str(), c.same_as.as_deref()))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("prod", None),
+                ("staging", None),
+                ("prod-eu", Some("prod")),
+                ("dev", None)
+            ]
+        );
+
+        let mut impersonating = config.clone();
+        impersonating.clustersets[1].clusters[1].impersonate = Some(Impersonation {
+            user: Some("reader".into()),
+            groups: Vec::new(),
+        });
+        let mut clusterset = impersonating.all_clustersets().unwrap();
+        clusterset.coalesce();
+        assert!(clusterset.clusters.iter().all(|c| c.same_as.is_none()));
+
+        let mut conflicting = config.clone();
+        conflicting.clustersets[1].clusters[0].context = Some("other-ctx".into());
+        assert!(conflicting.all_clustersets().is_err());
+    }
+
+    #[test]
+    fn include_clustersets() {
+        let config = parse_config(
+            r#"
+apiVersion: kubemc/v1beta1
+current-clusterset: all-prod
+clustersets:
+- name: prod-us
+  namespace: default
+  clusters:
+  - name: us-east
+    context: us-east-ctx
+  - name: shared
+    context: shared-ctx
+- name: prod-eu
+  namespace: default
+  clusters:
+  - name: eu-west
+    context: eu-west-ctx
+  - name: shared
+    context: shared-ctx
+- name: all-prod
+  namespace: shop
+  include: [prod-us, prod-eu]
+  clusters:
+  - name: canary
+    context: canary-ctx
+"#,
+        )
+        .unwrap();
+        let mut resolved = config.clone();
+        resolved.resolve_includes().unwrap();
+        let names: Vec<&str> = resolved
+            .active_clusterset()
+            .unwrap()
+            .clusters
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(names, ["canary", "us-east", "shared", "eu-west"]);
+        assert_eq!(resolved.clustersets[0].clusters.len(), 2);
+        // included clusters can be pinned, and saving keeps the references rather than the
+        // clusters they stand for
+        let mut pinned = config.clone();
+        pinned.set_pinned_cluster(Some("eu-west")).unwrap();
+        assert_eq!(pinned.clustersets[2].clusters.len(), 1);
+        let saved: Config = parse_config(&serde_yaml::to_string(&pinned).unwrap()).unwrap();
+        assert_eq!(saved.clustersets[2].include, ["prod-us", "prod-eu"]);
+
+        let mut missing = config.clone();
+        missing.clustersets[2].include.push("staging".into());
+        assert!(missing.resolve_includes().is_err());
+
+        let mut circular = config.clone();
+        circular.clustersets[0].include.push("all-prod".into());
+        let e = circular.resolve_includes().unwrap_err().to_string();
+        assert!(e.contains("prod-us -> all-prod -> prod-us"), "{}", e);
+
+        let mut conflicting = config.clone();
+        conflicting.clustersets[1].clusters[1].context = Some("other-ctx".into());
+        assert!(conflicting.resolve_includes().is_err());
+    }
+
+    #[test]
+    fn explain_config_parse_errors() {
+        let path = Path::new("/tmp/kubemc");
+        let data = "apiVersion: kubemc/v1alpha1\ncurrent-clusterset: cs1\nclustersets: [\n";
+        let error = serde_yaml::from_str::<Config>(data).unwrap_err();
+        let message = config_diagnostic(path, data, &error);
+        assert!(message.starts_with("failed to parse kubemc config /tmp/kubemc: "));
+        assert!(!message.contains("kubeconfig"));
+
+        let data = "apiVersion: kubemc/v1beta1\ncurrent-clusterset: [cs1\n";
+        let error = serde_yaml::from_str::<Config>(data).unwrap_err();
+        let location = error.location().unwrap();
+        let message = config_diagnostic(path, data, &error);
+        let caret = format!(" {}^", " ".repeat(location.column() - 1));
+        assert!(message.lines().any(|line| line.ends_with(&caret)));
+
+        let kubeconfig = r#"
+apiVersion: v1
+kind: Config
+current-context: prod
+contexts:
+- name: prod
+  context: {cluster: prod, user: admin}
+"#;
+        let error = serde_yaml::from_str::<Config>(kubeconfig).unwrap_err();
+        assert!(config_diagnostic(path, kubeconfig, &error).contains("this is a kubeconfig"));
+    }
+
+    #[test]
+    fn migrate_config_versions() {
+        let path = Path::new("/tmp/kubemc");
+        let data = r#"
+apiVersion: kubemc/v1alpha1
+current-clusterset: cs1
+clustersets:
+- name: cs1
+  namespace: default
+  clusters:
+  - name: c1
+    context: c1
+    preCommand: tsh kube login c1
+"#;
+        let (config, version) = parse_versioned_config(path, data).unwrap();
+        assert_eq!(version, "kubemc/v1alpha1");
+        assert_eq!(config.api_version, API_VERSION);
+        let cluster = &config.clustersets[0].clusters[0];
+        assert_eq!(cluster.pre_command.as_deref(), Some("tsh kube login c1"));
+
+        let migrated = serde_yaml::to_string(&config).unwrap();
+        let (config, version) = parse_versioned_config(path, &migrated).unwrap();
+        assert_eq!(version, API_VERSION);
+        assert!(config.clustersets[0].clusters[0].pre_command.is_some());
+
+        let error = parse_versioned_config(path, &data.replace("v1alpha1", "v2")).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("unsupported apiVersion kubemc/v2"));
+        let error = parse_versioned_config(path, "current-clusterset: cs1\n").unwrap_err();
+        assert!(error.to_string().contains("missing apiVersion"));
+
+        let kubeconfig = "apiVersion: v1\nkind: Config\ncontexts: []\n";
+        let error = parse_versioned_config(path, kubeconfig).unwrap_err();
+        assert!(error.to_string().contains("this is a kubeconfig"));
+    }
+}
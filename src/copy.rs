@@ -0,0 +1,464 @@
+use std::{
+    io::{self, BufWriter, Read, Write},
+    path::{Component, Path, PathBuf},
+    str::FromStr,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use k8s_openapi::api::core::v1::Pod;
+use kube::{api::AttachParams, Api, Client as KubeClient};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    sync::mpsc,
+};
+use tracing::log::warn;
+
+/// Size of the chunks an archive is streamed in
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Chunks of an archive held between tar on a blocking thread and the container, bounding the
+/// memory a copy takes whatever the size of what is copied
+const CHUNKS_IN_FLIGHT: usize = 16;
+
+/// One side of a copy, a local path or `POD:PATH` for a path in a container
+#[derive(Clone, Debug, PartialEq)]
+pub enum CopyPath {
+    Local(PathBuf),
+    Pod { pod: String, path: String },
+}
+
+impl FromStr for CopyPath {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once(':') {
+            // a path of its own such as ./a:b stays local
+            Some((pod, path)) if !pod.is_empty() && !pod.contains('/') => {
+                if path.is_empty() {
+                    bail!("no path given in the pod {} in {}", pod, s);
+                }
+                Ok(CopyPath::Pod {
+                    pod: pod.to_owned(),
+                    path: path.to_owned(),
+                })
+            }
+            _ => Ok(CopyPath::Local(PathBuf::from(s))),
+        }
+    }
+}
+
+impl std::fmt::Display for CopyPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CopyPath::Local(path) => write!(f, "{}", path.display()),
+            CopyPath::Pod { pod, path } => write!(f, "{}:{}", pod, path),
+        }
+    }
+}
+
+/// A copy between the local filesystem and a container, in either direction
+#[derive(Clone, Debug, PartialEq)]
+pub enum Copy {
+    ToPod {
+        local: PathBuf,
+        pod: String,
+        path: String,
+    },
+    FromPod {
+        pod: String,
+        path: String,
+        local: PathBuf,
+    },
+}
+
+impl Copy {
+    pub fn new(source: &CopyPath, destination: &CopyPath) -> Result<Self> {
+        match (source, destination) {
+            (CopyPath::Local(local), CopyPath::Pod { pod, path }) => Ok(Copy::ToPod {
+                local: local.clone(),
+                pod: pod.clone(),
+                path: path.clone(),
+            }),
+            (CopyPath::Pod { pod, path }, CopyPath::Local(local)) => Ok(Copy::FromPod {
+                pod: pod.clone(),
+                path: path.clone(),
+                local: local.clone(),
+            }),
+            (CopyPath::Local(_), CopyPath::Local(_)) => Err(anyhow!(
+                "one of the paths must be in a pod, written as POD:PATH"
+            )),
+            (CopyPath::Pod { .. }, CopyPath::Pod { .. }) => {
+                Err(anyhow!("copying directly between pods is not supported"))
+            }
+        }
+    }
+
+    pub fn pod(&self) -> &str {
+        match self {
+            Copy::ToPod { pod, .. } | Copy::FromPod { pod, .. } => pod,
+        }
+    }
+
+    /// Runs the copy against the pod on one cluster, streaming a tar archive through `tar` in
+    /// the container the same way kubectl does, so the container image needs a tar binary
+    pub async fn run(&self, client: KubeClient, ns: &str, container: Option<&str>) -> Result<()> {
+        let pods: Api<Pod> = Api::namespaced(client, ns);
+        match self {
+            Copy::ToPod { local, pod, path } => {
+                let into_dir = path.ends_with('/') || is_dir(&pods, pod, path, container).await?;
+                let (dir, name) = if into_dir {
+                    (path.clone(), local_name(local)?)
+                } else {
+                    split_remote(path)?
+                };
+                let command = ["tar", "xmf", "-", "-C", &dir];
+                let mut process = pods
+                    .exec(
+                        pod,
+                        command,
+                        &attach_params(container).stdin(true).stdout(false),
+                    )
+                    .await?;
+                let (chunks, mut archive) = mpsc::channel(CHUNKS_IN_FLIGHT);
+                let local = local.clone();
+                let packing = tokio::task::spawn_blocking(move || {
+                    pack(
+                        &local,
+                        &name,
+                        BufWriter::with_capacity(CHUNK_SIZE, ChannelWriter(chunks)),
+                    )
+                });
+                let mut stdin = process.stdin().expect("stdin is attached");
+                while let Some(chunk) = archive.recv().await {
+                    stdin.write_all(&chunk).await?;
+                }
+                packing.await??;
+                stdin.shutdown().await?;
+                drop(stdin);
+                finish(process, "extract", path).await
+            }
+            Copy::FromPod { pod, path, local } => {
+                let (dir, name) = split_remote(path)?;
+                let command = ["tar", "cf", "-", "-C", &dir, &name];
+                let mut process = pods.exec(pod, command, &attach_params(container)).await?;
+                let (chunks, archive) = mpsc::channel(CHUNKS_IN_FLIGHT);
+                let local = local_destination(local, &name);
+                let unpacking = tokio::task::spawn_blocking(move || {
+                    unpack(ChannelReader::new(archive), &name, &local)
+                });
+                let mut stdout = process.stdout().expect("stdout is attached");
+                let mut buf = vec![0; CHUNK_SIZE];
+                loop {
+                    let read = stdout.read(&mut buf).await?;
+                    // a failed unpack stops taking chunks and reports why below
+                    if read == 0 || chunks.send(buf[..read].to_vec()).await.is_err() {
+                        break;
+                    }
+                }
+                drop((stdout, chunks));
+                let finished = finish(process, "archive", path).await;
+                let unpacked = unpacking.await?;
+                finished.and(unpacked)
+            }
+        }
+    }
+}
+
+// Whether a path in the container is a directory, going by `test -d`
+async fn is_dir(pods: &Api<Pod>, pod: &str, path: &str, container: Option<&str>) -> Result<bool> {
+    let mut process = pods
+        .exec(
+            pod,
+            ["test", "-d", path],
+            &attach_params(container).stdout(false),
+        )
+        .await?;
+    if let Some(mut stderr) = process.stderr() {
+        stderr.read_to_end(&mut Vec::new()).await?;
+    }
+    let status = match process.take_status() {
+        Some(status) => status.await,
+        None => None,
+    };
+    process.join().await?;
+    Ok(status.is_some_and(|status| status.status.as_deref() == Some("Success")))
+}
+
+// The name a local file or directory is copied into a directory under
+fn local_name(local: &Path) -> Result<String> {
+    let local = std::fs::canonicalize(local)
+        .with_context(|| format!("failed to read {}", local.display()))?;
+    local
+        .file_name()
+        .and_then(|name| name.to_str())
+        .map(str::to_owned)
+        .ok_or_else(|| anyhow!("{} does not name a file or directory", local.display()))
+}
+
+// Where a file or directory named `name` in the container is copied to: into `local` when it
+// is a directory, like cp does, otherwise `local` itself
+fn local_destination(local: &Path, name: &str) -> PathBuf {
+    if local.is_dir() {
+        local.join(name)
+    } else {
+        local.to_path_buf()
+    }
+}
+
+// Passes the archive written by tar on a blocking thread on to the async side of the copy
+struct ChannelWriter(mpsc::Sender<Vec<u8>>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0
+            .blocking_send(buf.to_vec())
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "copy stopped"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Hands the archive read from the container to tar on a blocking thread
+struct ChannelReader {
+    chunks: mpsc::Receiver<Vec<u8>>,
+    chunk: Vec<u8>,
+    read: usize,
+}
+
+impl ChannelReader {
+    fn new(chunks: mpsc::Receiver<Vec<u8>>) -> Self {
+        Self {
+            chunks,
+            chunk: Vec::new(),
+            read: 0,
+        }
+    }
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.read == self.chunk.len() {
+            match self.chunks.blocking_recv() {
+                Some(chunk) => {
+                    self.chunk = chunk;
+                    self.read = 0;
+                }
+                None => return Ok(0),
+            }
+        }
+        let len = buf.len().min(self.chunk.len() - self.read);
+        buf[..len].copy_from_slice(&self.chunk[self.read..self.read + len]);
+        self.read += len;
+        Ok(len)
+    }
+}
+
+fn attach_params(container: Option<&str>) -> AttachParams {
+    let params = AttachParams::default().stderr(true);
+    match container {
+        Some(container) => params.container(container),
+        None => params,
+    }
+}
+
+// Waits for the command in the container, failing with its stderr when it did not succeed
+async fn finish(mut process: kube::api::AttachedProcess, action: &str, path: &str) -> Result<()> {
+    let mut stderr = String::new();
+    if let Some(mut output) = process.stderr() {
+        output.read_to_string(&mut stderr).await?;
+    }
+    let status = match process.take_status() {
+        Some(status) => status.await,
+        None => None,
+    };
+    process.join().await?;
+    match status {
+        Some(status) if status.status.as_deref() != Some("Success") => Err(anyhow!(
+            "failed to {} {} in the container: {}",
+            action,
+            path,
+            stderr
+                .lines()
+                .next()
+                .or(status.message.as_deref())
+                .unwrap_or("tar failed")
+        )),
+        _ => Ok(()),
+    }
+}
+
+// Splits a container path into its directory and final name, which tar is run against
+fn split_remote(path: &str) -> Result<(String, String)> {
+    let trimmed = path.trim_end_matches('/');
+    let (dir, name) = match trimmed.rsplit_once('/') {
+        Some(("", name)) => ("/", name),
+        Some((dir, name)) => (dir, name),
+        None => (".", trimmed),
+    };
+    if name.is_empty() || name == "." || name == ".." {
+        bail!("{} does not name a file or directory", path);
+    }
+    Ok((dir.to_owned(), name.to_owned()))
+}
+
+/// Archives a local file or directory with `name` as its path in the archive, writing the
+/// archive to `out` as it goes
+pub fn pack(local: &Path, name: &str, out: impl Write) -> Result<()> {
+    let metadata =
+        std::fs::metadata(local).with_context(|| format!("failed to read {}", local.display()))?;
+    let mut builder = tar::Builder::new(out);
+    builder.follow_symlinks(false);
+    if metadata.is_dir() {
+        builder.append_dir_all(name, local)?;
+    } else {
+        builder.append_path_with_name(local, name)?;
+    }
+    builder
+        .into_inner()
+        .and_then(|mut out| out.flush())
+        .with_context(|| format!("failed to archive {}", local.display()))
+}
+
+/// Extracts an archive whose entries sit under `name` to `local`, so `name` itself becomes
+/// `local`. Entries escaping `local` and anything but files and directories are skipped.
+pub fn unpack(archive: impl Read, name: &str, local: &Path) -> Result<()> {
+    let mut archive = tar::Archive::new(archive);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let Some(target) = target_path(&path, name, local) else {
+            warn!("skipping {}, it is outside of {}", path.display(), name);
+            continue;
+        };
+        let kind = entry.header().entry_type();
+        if kind.is_dir() {
+            std::fs::create_dir_all(&target)
+                .with_context(|| format!("failed to create {}", target.display()))?;
+        } else if kind.is_file() {
+            if let Some(parent) = target.parent().filter(|p| !p.as_os_str().is_empty()) {
+                std::fs::create_dir_all(parent)
+                    .with_context(|| format!("failed to create {}", parent.display()))?;
+            }
+            entry
+                .unpack(&target)
+                .with_context(|| format!("failed to write {}", target.display()))?;
+        } else {
+            warn!(
+                "skipping {}, only files and directories are copied",
+                path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+// Where an archive entry is written, None for entries not under `name` or leaving it
+fn target_path(path: &Path, name: &str, local: &Path) -> Option<PathBuf> {
+    let rest = path.strip_prefix(name).ok()?;
+    if rest
+        .components()
+        .any(|c| !matches!(c, Component::Normal(_) | Component::CurDir))
+    {
+        return None;
+    }
+    if rest.as_os_str().is_empty() {
+        return Some(local.to_path_buf());
+    }
+    Some(local.join(rest))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn copy_paths() {
+        assert_eq!(
+            "web-1:/tmp/dump".parse::<CopyPath>().unwrap(),
+            CopyPath::Pod {
+                pod: "web-1".into(),
+                path: "/tmp/dump".into()
+            }
+        );
+        assert_eq!(
+            "./a:b".parse::<CopyPath>().unwrap(),
+            CopyPath::Local("./a:b".into())
+        );
+        assert!("web-1:".parse::<CopyPath>().is_err());
+        let local = CopyPath::Local("dump".into());
+        assert!(Copy::new(&local, &local).is_err());
+
+        assert_eq!(
+            split_remote("/tmp/dump/").unwrap(),
+            ("/tmp".into(), "dump".into())
+        );
+        assert_eq!(split_remote("/etc").unwrap(), ("/".into(), "etc".into()));
+        assert_eq!(split_remote("dump").unwrap(), (".".into(), "dump".into()));
+        assert!(split_remote("/").is_err());
+
+        let dir = std::env::temp_dir().join(format!("kubemc-cp-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("src/nested")).unwrap();
+        std::fs::write(dir.join("src/a.txt"), "a").unwrap();
+        std::fs::write(dir.join("src/nested/b.txt"), "b").unwrap();
+        let mut archive = Vec::new();
+        pack(&dir.join("src"), "data", &mut archive).unwrap();
+        unpack(archive.as_slice(), "data", &dir.join("copy")).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("copy/nested/b.txt")).unwrap(),
+            "b"
+        );
+        let mut archive = Vec::new();
+        pack(&dir.join("src/a.txt"), "renamed.txt", &mut archive).unwrap();
+        unpack(archive.as_slice(), "renamed.txt", &dir.join("single.txt")).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("single.txt")).unwrap(),
+            "a"
+        );
+
+        // an existing directory receives the copy under its own name
+        assert_eq!(local_destination(&dir, "dump"), dir.join("dump"));
+        assert_eq!(
+            local_destination(&dir.join("single.txt"), "dump"),
+            dir.join("single.txt")
+        );
+        assert_eq!(local_name(&dir.join("src/.")).unwrap(), "src");
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            target_path(Path::new("data/../../etc/passwd"), "data", Path::new("out")),
+            None
+        );
+        assert_eq!(
+            target_path(Path::new("other/a"), "data", Path::new("out")),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn stream_archives() {
+        let dir = std::env::temp_dir().join(format!("kubemc-cp-stream-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("src")).unwrap();
+        let contents = "x".repeat(CHUNK_SIZE * 3);
+        std::fs::write(dir.join("src/big.txt"), &contents).unwrap();
+
+        // a single chunk in flight still moves an archive many times its size
+        let (chunks, archive) = mpsc::channel(1);
+        let src = dir.join("src");
+        let packing = tokio::task::spawn_blocking(move || {
+            pack(&src, "data", BufWriter::new(ChannelWriter(chunks)))
+        });
+        let copy = dir.join("copy");
+        let unpacking =
+            tokio::task::spawn_blocking(move || unpack(ChannelReader::new(archive), "data", &copy));
+        packing.await.unwrap().unwrap();
+        unpacking.await.unwrap().unwrap();
+        assert_eq!(
+            std::fs::read_to_string(dir.join("copy/big.txt")).unwrap(),
+            contents
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
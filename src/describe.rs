@@ -0,0 +1,463 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use k8s_openapi::{
+    api::core::v1::{Container, Node, Pod, PodSpec, Taint},
+    apimachinery::pkg::api::resource::Quantity,
+};
+use kube::{api::ListParams, core::DynamicObject, Api, Client as KubeClient, ResourceExt};
+
+use crate::{
+    client::DEFAULT_PAGE_SIZE,
+    output::{create_table, AllocatedOutput, NodePodOutput},
+};
+
+/// Requests and limits of a pod, cpu in cores and memory in bytes
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PodResources {
+    pub cpu_requests: f64,
+    pub cpu_limits: f64,
+    pub memory_requests: f64,
+    pub memory_limits: f64,
+    /// Pods summed up, one for the resources of a single pod
+    pub pods: usize,
+}
+
+impl PodResources {
+    /// Computes what a pod reserves the way the scheduler does: app containers run together so
+    /// they are summed, init containers run one at a time so only the largest counts, and the
+    /// pod overhead comes on top.
+    pub fn of(spec: &PodSpec) -> Self {
+        let mut total = spec
+            .containers
+            .iter()
+            .map(Self::of_container)
+            .fold(Self::default(), |sum, c| sum.add(&c));
+        for init in spec.init_containers.iter().flatten() {
+            let init = Self::of_container(init);
+            total.cpu_requests = total.cpu_requests.max(init.cpu_requests);
+            total.cpu_limits = total.cpu_limits.max(init.cpu_limits);
+            total.memory_requests = total.memory_requests.max(init.memory_requests);
+            total.memory_limits = total.memory_limits.max(init.memory_limits);
+        }
+        if let Some(overhead) = &spec.overhead {
+            let overhead = Self {
+                cpu_requests: quantity(overhead, "cpu"),
+                cpu_limits: quantity(overhead, "cpu"),
+                memory_requests: quantity(overhead, "memory"),
+                memory_limits: quantity(overhead, "memory"),
+                pods: 0,
+            };
+            total = total.add(&overhead);
+        }
+        total.pods = 1;
+        total
+    }
+
+    fn of_container(container: &Container) -> Self {
+        let resources = container.resources.clone().unwrap_or_default();
+        let requests = resources.requests.unwrap_or_default();
+        let limits = resources.limits.unwrap_or_default();
+        Self {
+            cpu_requests: quantity(&requests, "cpu"),
+            cpu_limits: quantity(&limits, "cpu"),
+            memory_requests: quantity(&requests, "memory"),
+            memory_limits: quantity(&limits, "memory"),
+            pods: 0,
+        }
+    }
+
+    fn add(&self, other: &Self) -> Self {
+        Self {
+            cpu_requests: self.cpu_requests + other.cpu_requests,
+            cpu_limits: self.cpu_limits + other.cpu_limits,
+            memory_requests: self.memory_requests + other.memory_requests,
+            memory_limits: self.memory_limits + other.memory_limits,
+            pods: self.pods + other.pods,
+        }
+    }
+}
+
+/// A node on a single cluster along with the pods running on it
+pub struct NodeDescription {
+    pub clustername: String,
+    pub node: Node,
+    pub pods: Vec<Pod>,
+}
+
+impl NodeDescription {
+    /// Fetches the node and its non-terminated pods, or None when the cluster has no such node
+    pub async fn fetch(clustername: &str, client: KubeClient, name: &str) -> Result<Option<Self>> {
+        let nodes: Api<Node> = Api::all(client.clone());
+        let Some(node) = nodes.get_opt(name).await? else {
+            return Ok(None);
+        };
+        let pods: Api<Pod> = Api::all(client);
+        let lp = ListParams::default().fields(&format!(
+            "spec.nodeName={},status.phase!=Succeeded,status.phase!=Failed",
+            name
+        ));
+        let mut pods = pods.list(&lp).await?.items;
+        pods.sort_by_key(|pod| (pod.namespace(), pod.name_any()));
+        Ok(Some(Self {
+            clustername: clustername.to_owned(),
+            node,
+            pods,
+        }))
+    }
+
+    pub fn print(&self) {
+        let status = self.node.status.clone().unwrap_or_default();
+        let allocatable = status.allocatable.unwrap_or_default();
+        let cpu = quantity(&allocatable, "cpu");
+        let memory = quantity(&allocatable, "memory");
+
+        println!("Cluster:  {}", self.clustername);
+        println!("Name:     {}", self.node.name_any());
+        let conditions: Vec<String> = status
+            .conditions
+            .iter()
+            .flatten()
+            .map(|c| format!("{}={}", c.type_, c.status))
+            .collect();
+        println!("Conditions: {}", conditions.join(", "));
+        println!(
+            "Allocatable: cpu {}, memory {}, pods {}",
+            format_cpu(cpu),
+            format_memory(memory),
+            allocatable.get("pods").map_or("", |q| q.0.as_str())
+        );
+
+        println!();
+        println!("Non-terminated Pods: ({} in total)", self.pods.len());
+        let mut total = PodResources::default();
+        let rows: Vec<NodePodOutput> = self
+            .pods
+            .iter()
+            .map(|pod| {
+                let resources = pod.spec.as_ref().map(PodResources::of).unwrap_or_default();
+                total = total.add(&resources);
+                NodePodOutput {
+                    namespace: pod.namespace().unwrap_or_default(),
+                    name: pod.name_any(),
+                    cpu_requests: with_percent(format_cpu, resources.cpu_requests, cpu),
+                    cpu_limits: with_percent(format_cpu, resources.cpu_limits, cpu),
+                    memory_requests: with_percent(format_memory, resources.memory_requests, memory),
+                    memory_limits: with_percent(format_memory, resources.memory_limits, memory),
+                }
+            })
+            .collect();
+        create_table(rows);
+
+        println!("Allocated resources:");
+        create_table(vec![
+            AllocatedOutput {
+                resource: "cpu".into(),
+                requests: with_percent(format_cpu, total.cpu_requests, cpu),
+                limits: with_percent(format_cpu, total.cpu_limits, cpu),
+            },
+            AllocatedOutput {
+                resource: "memory".into(),
+                requests: with_percent(format_memory, total.memory_requests, memory),
+                limits: with_percent(format_memory, total.memory_limits, memory),
+            },
+        ]);
+    }
+}
+
+fn quantity(quantities: &BTreeMap<String, Quantity>, name: &str) -> f64 {
+    quantities
+        .get(name)
+        .and_then(|q| parse_quantity(&q.0))
+        .unwrap_or_default()
+}
+
+/// Parses a Kubernetes quantity such as `250m`, `2`, `128Mi`, `1G` or `1e3` into its base unit
+pub fn parse_quantity(quantity: &str) -> Option<f64> {
+    let quantity = quantity.trim();
+    let split = quantity
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-' || c == '+'))
+        .unwrap_or(quantity.len());
+    let (number, suffix) = quantity.split_at(split);
+    let number: f64 = number.parse().ok()?;
+    let multiplier = match suffix {
+        "" => 1.0,
+        "n" => 1e-9,
+        "u" => 1e-6,
+        "m" => 1e-3,
+        "k" => 1e3,
+        "M" => 1e6,
+        "G" => 1e9,
+        "T" => 1e12,
+        "P" => 1e15,
+        "E" => 1e18,
+        "Ki" => 1024.0,
+        "Mi" => 1024f64.powi(2),
+        "Gi" => 1024f64.powi(3),
+        "Ti" => 1024f64.powi(4),
+        "Pi" => 1024f64.powi(5),
+        "Ei" => 1024f64.powi(6),
+        exponent => 10f64.powi(exponent.strip_prefix(['e', 'E'])?.parse().ok()?),
+    };
+    Some(number * multiplier)
+}
+
+pub(crate) fn format_cpu(cores: f64) -> String {
+    if cores.fract() == 0.0 {
+        format!("{}", cores)
+    } else {
+        format!("{}m", (cores * 1000.0).round())
+    }
+}
+
+pub(crate) fn format_memory(bytes: f64) -> String {
+    let units = [
+        ("Gi", 1024f64.powi(3)),
+        ("Mi", 1024f64.powi(2)),
+        ("Ki", 1024.0),
+    ];
+    for (suffix, size) in units {
+        if bytes >= size && (bytes / size).fract() == 0.0 {
+            return format!("{}{}", bytes / size, suffix);
+        }
+    }
+    match units.iter().find(|(_, size)| bytes >= *size) {
+        Some((suffix, size)) => format!("{:.1}{}", bytes / size, suffix),
+        None => format!("{}", bytes),
+    }
+}
+
+fn with_percent(format: fn(f64) -> String, value: f64, allocatable: f64) -> String {
+    if allocatable > 0.0 {
+        format!(
+            "{} ({}%)",
+            format(value),
+            (value / allocatable * 100.0) as u64
+        )
+    } else {
+        format(value)
+    }
+}
+
+/// Headers of the columns `-o wide` adds to the table of a kind in an API group, none for most
+/// kinds
+pub fn wide_headers(group: &str, kind: &str) -> &'static [&'static str] {
+    match (group, kind) {
+        ("", "Pod") => &[
+            "CPU REQUESTS",
+            "CPU LIMITS",
+            "MEMORY REQUESTS",
+            "MEMORY LIMITS",
+        ],
+        ("", "Node") => &["CPU REQUESTS", "MEMORY REQUESTS"],
+        _ => &[],
+    }
+}
+
+/// Values of the `-o wide` columns of a listed object. Nodes compare the requests of their pods,
+/// looked up by node name in `requested`, to their allocatable capacity. Without `requested` the
+/// pods of the node could not be listed.
+pub fn wide_columns(
+    group: &str,
+    kind: &str,
+    object: &DynamicObject,
+    requested: Option<&BTreeMap<String, PodResources>>,
+) -> Vec<String> {
+    match (group, kind) {
+        ("", "Pod") => {
+            let spec: PodSpec = object
+                .data
+                .get("spec")
+                .and_then(|spec| serde_json::from_value(spec.to_owned()).ok())
+                .unwrap_or_default();
+            let resources = PodResources::of(&spec);
+            vec![
+                format_cpu(resources.cpu_requests),
+                format_cpu(resources.cpu_limits),
+                format_memory(resources.memory_requests),
+                format_memory(resources.memory_limits),
+            ]
+        }
+        ("", "Node") => {
+            let Some(requested) = requested else {
+                return vec!["<unknown>".to_string(); 2];
+            };
+            let allocatable: BTreeMap<String, Quantity> = object
+                .data
+                .pointer("/status/allocatable")
+                .and_then(|allocatable| serde_json::from_value(allocatable.to_owned()).ok())
+                .unwrap_or_default();
+            let resources = requested
+                .get(&object.name_any())
+                .copied()
+                .unwrap_or_default();
+            vec![
+                of_allocatable(
+                    format_cpu,
+                    resources.cpu_requests,
+                    quantity(&allocatable, "cpu"),
+                ),
+                of_allocatable(
+                    format_memory,
+                    resources.memory_requests,
+                    quantity(&allocatable, "memory"),
+                ),
+            ]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Header of the wide column `--show-taints` adds to nodes
+pub const TAINTS_HEADER: &str = "TAINT LIST";
+
+/// Taints of a listed node as `key=value:effect`, comma separated
+pub fn node_taints(object: &DynamicObject) -> String {
+    let taints: Vec<Taint> = object
+        .data
+        .pointer("/spec/taints")
+        .and_then(|taints| serde_json::from_value(taints.to_owned()).ok())
+        .unwrap_or_default();
+    if taints.is_empty() {
+        return "<none>".into();
+    }
+    taints
+        .iter()
+        .map(|taint| match &taint.value {
+            Some(value) => format!("{}={}:{}", taint.key, value, taint.effect),
+            None => format!("{}:{}", taint.key, taint.effect),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// Shows a request next to what the node has to give, such as 1500m/4 (37%)
+fn of_allocatable(format: fn(f64) -> String, value: f64, allocatable: f64) -> String {
+    let shown = format!("{}/{}", format(value), format(allocatable));
+    if allocatable > 0.0 {
+        format!("{} ({}%)", shown, (value / allocatable * 100.0) as u64)
+    } else {
+        shown
+    }
+}
+
+/// Sums what the non-terminated pods of a cluster reserve per node name, paging through the pods
+pub async fn node_requests(
+    client: KubeClient,
+    _: Option<String>,
+) -> Result<BTreeMap<String, PodResources>> {
+    let pods: Api<Pod> = Api::all(client);
+    let mut requested: BTreeMap<String, PodResources> = BTreeMap::new();
+    let mut lp = ListParams::default()
+        .fields("status.phase!=Succeeded,status.phase!=Failed")
+        .limit(DEFAULT_PAGE_SIZE);
+    loop {
+        let page = pods.list(&lp).await?;
+        for spec in page.items.into_iter().filter_map(|pod| pod.spec) {
+            if let Some(node) = &spec.node_name {
+                let total = requested.entry(node.clone()).or_default();
+                *total = total.add(&PodResources::of(&spec));
+            }
+        }
+        match page.metadata.continue_.filter(|token| !token.is_empty()) {
+            Some(token) => lp = lp.continue_token(&token),
+            None => return Ok(requested),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pod_resources_with_init_containers() {
+        assert_eq!(parse_quantity("250m"), Some(0.25));
+        assert_eq!(parse_quantity("128Mi"), Some(128.0 * 1024.0 * 1024.0));
+        assert_eq!(parse_quantity("1e3"), Some(1000.0));
+        assert_eq!(parse_quantity("lots"), None);
+
+        let spec: PodSpec = serde_json::from_value(serde_json::json!({
+            "containers": [
+                {"name": "a", "resources": {"requests": {"cpu": "100m", "memory": "64Mi"}}},
+                {"name": "b", "resources": {"requests": {"cpu": "200m"}, "limits": {"cpu": "1"}}},
+            ],
+            "initContainers": [
+                {"name": "init", "resources": {"requests": {"cpu": "500m"}}},
+            ],
+        }))
+        .unwrap();
+        let resources = PodResources::of(&spec);
+        assert_eq!(format_cpu(resources.cpu_requests), "500m");
+        assert_eq!(format_cpu(resources.cpu_limits), "1");
+        assert_eq!(format_memory(resources.memory_requests), "64Mi");
+        assert_eq!(
+            with_percent(format_cpu, resources.cpu_requests, 2.0),
+            "500m (25%)"
+        );
+    }
+
+    #[test]
+    fn wide_resource_columns() {
+        let object =
+            |data: serde_json::Value| -> DynamicObject { serde_json::from_value(data).unwrap() };
+        let pod = object(serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": {"name": "web"},
+            "spec": {"containers": [{
+                "name": "app",
+                "resources": {
+                    "requests": {"cpu": "250m", "memory": "128Mi"},
+                    "limits": {"cpu": "500m", "memory": "256Mi"},
+                },
+            }]},
+        }));
+        assert_eq!(
+            wide_columns("", "Pod", &pod, None),
+            ["250m", "500m", "128Mi", "256Mi"]
+        );
+        assert_eq!(wide_headers("", "Pod").len(), 4);
+
+        let node = object(serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "Node",
+            "metadata": {"name": "node-1"},
+            "status": {"allocatable": {"cpu": "4", "memory": "8Gi"}},
+        }));
+        let requested = BTreeMap::from([(
+            "node-1".to_string(),
+            PodResources {
+                cpu_requests: 1.5,
+                memory_requests: 2.0 * 1024f64.powi(3),
+                ..Default::default()
+            },
+        )]);
+        assert_eq!(
+            wide_columns("", "Node", &node, Some(&requested)),
+            ["1500m/4 (37%)", "2Gi/8Gi (25%)"]
+        );
+        assert_eq!(
+            wide_columns("", "Node", &node, None),
+            ["<unknown>", "<unknown>"]
+        );
+        assert!(wide_columns("", "Service", &node, None).is_empty());
+        // kinds of the same name in other groups have none of the columns
+        assert!(wide_columns("example.com", "Node", &node, None).is_empty());
+        assert_eq!(node_taints(&node), "<none>");
+
+        let tainted = object(serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "Node",
+            "metadata": {"name": "gpu-1"},
+            "spec": {"taints": [
+                {"key": "nvidia.com/gpu", "value": "true", "effect": "NoSchedule"},
+                {"key": "node.kubernetes.io/unreachable", "effect": "NoExecute"},
+            ]},
+        }));
+        assert_eq!(
+            node_taints(&tainted),
+            "nvidia.com/gpu=true:NoSchedule,node.kubernetes.io/unreachable:NoExecute"
+        );
+    }
+}
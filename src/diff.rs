@@ -0,0 +1,345 @@
+use serde_json::Value;
+
+/// Paths that change on every update and would drown out meaningful changes
+const IGNORED_PATHS: &[&str] = &[".metadata.resourceVersion", ".metadata.managedFields"];
+
+/// A single changed field. `old` is None for added fields and `new` is None for removed ones.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Change {
+    pub path: String,
+    pub old: Option<Value>,
+    pub new: Option<Value>,
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let show = |v: &Option<Value>| match v {
+            Some(v) => v.to_string(),
+            None => "<none>".into(),
+        };
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.path,
+            show(&self.old),
+            show(&self.new)
+        )
+    }
+}
+
+/// Returns the leaf level differences between two JSON values. Objects are compared key by key
+/// and arrays index by index, anything else is compared as a whole.
+pub fn diff(old: &Value, new: &Value) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_at(String::new(), Some(old), Some(new), &mut changes);
+    changes
+}
+
+fn diff_at(path: String, old: Option<&Value>, new: Option<&Value>, changes: &mut Vec<Change>) {
+    if IGNORED_PATHS.contains(&path.as_str()) {
+        return;
+    }
+    match (old, new) {
+        (Some(Value::Object(old)), Some(Value::Object(new))) => {
+            let mut keys: Vec<&String> = old.keys().chain(new.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                diff_at(
+                    format!("{}.{}", path, key),
+                    old.get(key),
+                    new.get(key),
+                    changes,
+                );
+            }
+        }
+        (Some(Value::Array(old)), Some(Value::Array(new))) => {
+            for i in 0..old.len().max(new.len()) {
+                diff_at(format!("{}[{}]", path, i), old.get(i), new.get(i), changes);
+            }
+        }
+        (old, new) if old != new => changes.push(Change {
+            path,
+            old: old.cloned(),
+            new: new.cloned(),
+        }),
+        _ => {}
+    }
+}
+
+/// Returns the fields of `desired` that `live` does not match. Fields only present on `live`,
+/// such as defaults and status filled in by the API server, are not differences.
+pub fn diff_desired(live: &Value, desired: &Value) -> Vec<Change> {
+    let mut changes = Vec::new();
+    diff_desired_at(String::new(), live, desired, &mut changes);
+    changes
+}
+
+fn diff_desired_at(path: String, live: &Value, desired: &Value, changes: &mut Vec<Change>) {
+    if IGNORED_PATHS.contains(&path.as_str()) {
+        return;
+    }
+    match (live, desired) {
+        (Value::Object(live), Value::Object(desired)) => {
+            for (key, value) in desired {
+                let path = format!("{}.{}", path, key);
+                match live.get(key) {
+                    Some(current) => diff_desired_at(path, current, value, changes),
+                    None if value.is_null() => {}
+                    None => changes.push(Change {
+                        path,
+                        old: None,
+                        new: Some(value.clone()),
+                    }),
+                }
+            }
+        }
+        (Value::Array(live), Value::Array(desired)) if live.len() == desired.len() => {
+            for (i, (current, value)) in live.iter().zip(desired).enumerate() {
+                diff_desired_at(format!("{}[{}]", path, i), current, value, changes);
+            }
+        }
+        (live, desired) if live != desired => changes.push(Change {
+            path,
+            old: Some(live.clone()),
+            new: Some(desired.clone()),
+        }),
+        _ => {}
+    }
+}
+
+/// Builds a JSON merge patch (RFC 7386) turning `old` into `new`, or None when they are equal.
+/// Removed keys become nulls and arrays are replaced as a whole.
+pub fn merge_patch(old: &Value, new: &Value) -> Option<Value> {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let mut patch = serde_json::Map::new();
+            for (key, value) in new {
+                let changed = match old.get(key) {
+                    Some(previous) => merge_patch(previous, value),
+                    None => Some(value.clone()),
+                };
+                if let Some(changed) = changed {
+                    patch.insert(key.clone(), changed);
+                }
+            }
+            for key in old.keys().filter(|key| !new.contains_key(*key)) {
+                patch.insert(key.clone(), Value::Null);
+            }
+            (!patch.is_empty()).then_some(Value::Object(patch))
+        }
+        (old, new) if old == new => None,
+        (_, new) => Some(new.clone()),
+    }
+}
+
+/// Applies a JSON merge patch (RFC 7386) to `target`
+pub fn apply_merge_patch(target: &Value, patch: &Value) -> Value {
+    let Value::Object(patch) = patch else {
+        return patch.clone();
+    };
+    let mut result = match target {
+        Value::Object(target) => target.clone(),
+        _ => serde_json::Map::new(),
+    };
+    for (key, value) in patch {
+        if value.is_null() {
+            result.remove(key);
+        } else {
+            let patched = apply_merge_patch(result.get(key).unwrap_or(&Value::Null), value);
+            result.insert(key.clone(), patched);
+        }
+    }
+    Value::Object(result)
+}
+
+/// Builds a strategic merge patch turning `old` into `new`, or None when they are equal. It is
+/// the merge patch with lists of objects that lost elements marked to be replaced as a whole,
+/// since the API server merges such lists by key and would otherwise keep the removed elements.
+pub fn strategic_merge_patch(old: &Value, new: &Value) -> Option<Value> {
+    let mut patch = merge_patch(old, new)?;
+    mark_replaced_lists(old, &mut patch);
+    Some(patch)
+}
+
+fn mark_replaced_lists(old: &Value, patch: &mut Value) {
+    match (old, patch) {
+        (Value::Object(old), Value::Object(patch)) => {
+            for (key, value) in patch.iter_mut() {
+                if let Some(previous) = old.get(key) {
+                    mark_replaced_lists(previous, value);
+                }
+            }
+        }
+        (Value::Array(old), Value::Array(new))
+            if new.len() < old.len() && old.iter().all(Value::is_object) =>
+        {
+            new.push(replace_directive());
+        }
+        _ => {}
+    }
+}
+
+fn replace_directive() -> Value {
+    serde_json::json!({"$patch": "replace"})
+}
+
+/// Applies a patch built by `strategic_merge_patch` to `target`, merging lists of objects by
+/// their `name` like the API server does for containers, volumes and most other built in lists
+pub fn apply_strategic_patch(target: &Value, patch: &Value) -> Value {
+    match (target, patch) {
+        (_, Value::Object(patch)) => {
+            let mut result = match target {
+                Value::Object(target) => target.clone(),
+                _ => serde_json::Map::new(),
+            };
+            for (key, value) in patch {
+                if value.is_null() {
+                    result.remove(key);
+                } else {
+                    let patched =
+                        apply_strategic_patch(result.get(key).unwrap_or(&Value::Null), value);
+                    result.insert(key.clone(), patched);
+                }
+            }
+            Value::Object(result)
+        }
+        (Value::Array(target), Value::Array(patch))
+            if !patch.contains(&replace_directive())
+                && target.iter().chain(patch).all(|e| e.get("name").is_some()) =>
+        {
+            let mut result = target.clone();
+            for element in patch {
+                match result
+                    .iter_mut()
+                    .find(|existing| existing.get("name") == element.get("name"))
+                {
+                    Some(existing) => *existing = apply_strategic_patch(existing, element),
+                    None => result.push(element.clone()),
+                }
+            }
+            Value::Array(result)
+        }
+        (_, Value::Array(patch)) => Value::Array(
+            patch
+                .iter()
+                .filter(|element| **element != replace_directive())
+                .cloned()
+                .collect(),
+        ),
+        (_, patch) => patch.clone(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn diff_nested_values() {
+        let old = json!({
+            "metadata": {"resourceVersion": "1", "labels": {"a": "1"}},
+            "status": {"phase": "Pending", "conditions": [{"type": "Ready"}]},
+        });
+        let new = json!({
+            "metadata": {"resourceVersion": "2", "labels": {"b": "2"}},
+            "status": {"phase": "Running", "conditions": [{"type": "Ready"}, {"type": "Init"}]},
+        });
+        let changes: Vec<String> = diff(&old, &new).iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            changes,
+            vec![
+                r#".metadata.labels.a: "1" -> <none>"#,
+                r#".metadata.labels.b: <none> -> "2""#,
+                r#".status.conditions[1]: <none> -> {"type":"Init"}"#,
+                r#".status.phase: "Pending" -> "Running""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn diff_against_desired_fields() {
+        let live = json!({
+            "metadata": {"name": "web", "uid": "1", "labels": {"app": "web"}},
+            "spec": {
+                "replicas": 2,
+                "template": {"spec": {"containers": [{"name": "web", "image": "web:1", "imagePullPolicy": "IfNotPresent"}]}},
+            },
+            "status": {"replicas": 2},
+        });
+        let desired = json!({
+            "metadata": {"name": "web", "labels": {"app": "web", "tier": "frontend"}},
+            "spec": {
+                "replicas": 2,
+                "template": {"spec": {"containers": [{"name": "web", "image": "web:2"}]}},
+            },
+        });
+        let changes: Vec<String> = diff_desired(&live, &desired)
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                r#".metadata.labels.tier: <none> -> "frontend""#,
+                r#".spec.template.spec.containers[0].image: "web:1" -> "web:2""#,
+            ]
+        );
+        assert!(diff_desired(&live, &live).is_empty());
+    }
+
+    #[test]
+    fn merge_patch_round_trip() {
+        let old = json!({
+            "metadata": {"name": "web", "labels": {"a": "1", "b": "2"}},
+            "spec": {"replicas": 2, "ports": [80]},
+        });
+        let new = json!({
+            "metadata": {"name": "web", "labels": {"a": "1", "c": "3"}},
+            "spec": {"replicas": 3, "ports": [80, 443]},
+        });
+        let patch = merge_patch(&old, &new).unwrap();
+        assert_eq!(
+            patch,
+            json!({
+                "metadata": {"labels": {"b": null, "c": "3"}},
+                "spec": {"replicas": 3, "ports": [80, 443]},
+            })
+        );
+        assert_eq!(apply_merge_patch(&old, &patch), new);
+        assert_eq!(merge_patch(&old, &old), None);
+    }
+
+    #[test]
+    fn strategic_patch_merges_lists_by_name() {
+        let edited = json!({"spec": {"containers": [
+            {"name": "app", "image": "app:1"},
+            {"name": "proxy", "image": "proxy:1"},
+        ]}});
+        let new = json!({"spec": {"containers": [{"name": "app", "image": "app:2"}]}});
+        let patch = strategic_merge_patch(&edited, &new).unwrap();
+        assert_eq!(
+            patch,
+            json!({"spec": {"containers": [
+                {"name": "app", "image": "app:2"},
+                {"$patch": "replace"},
+            ]}})
+        );
+        assert_eq!(apply_strategic_patch(&edited, &patch), new);
+
+        // containers only another cluster runs are kept
+        let patch = strategic_merge_patch(
+            &new,
+            &json!({"spec": {"containers": [{"name": "app", "image": "app:3"}]}}),
+        )
+        .unwrap();
+        assert_eq!(
+            apply_strategic_patch(&edited, &patch),
+            json!({"spec": {"containers": [
+                {"name": "app", "image": "app:3"},
+                {"name": "proxy", "image": "proxy:1"},
+            ]}})
+        );
+    }
+}
@@ -1,9 +1,23 @@
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{APIGroupList, APIResourceList};
 use serde::Deserialize;
-use std::path::{Path, PathBuf};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
 use tracing::log::debug;
 
-use kube::discovery::{ApiResource, Scope};
+use kube::{
+    discovery::{ApiResource, Scope},
+    Client as KubeClient,
+};
+
+/// How long discovery written by kubemc is trusted before it is refreshed
+pub const CACHE_TTL: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// File written last when refreshing the kubemc cache, its age is the age of the cache
+const CACHE_MARKER: &str = "servergroups.json";
 
 pub struct Discovery {
     resources: Vec<DiscoveryResource>,
@@ -19,40 +33,346 @@ pub struct DiscoveryResource {
     scope: Scope,
 }
 
+impl DiscoveryResource {
+    pub fn api_resource(&self) -> &ApiResource {
+        &self.api_resource
+    }
+
+    pub fn scope(&self) -> &Scope {
+        &self.scope
+    }
+
+    /// Short names such as `po` for pods. The kind and plural are always the first two matches.
+    pub fn short_names(&self) -> &[String] {
+        self.kind.get(2..).unwrap_or_default()
+    }
+}
+
 impl Discovery {
-    /// Creates a
+    /// Creates a Discovery from the discovery cache kubectl maintains
     pub fn new_from_default_cache(url: String) -> Result<Self> {
-        let mut resources = Vec::new();
         let host_path = parse_kube_url_to_discovery(url)?;
-        let paths = get_cache_files(
+        Self::new_from_dir(
             dirs::home_dir()
                 .unwrap()
                 .join(".kube")
                 .join("cache")
                 .join("discovery")
                 .join(host_path),
-        )?;
-        let files = read_cache_files(paths);
+        )
+    }
+
+    /// Creates a Discovery from the cache kubemc maintains, failing if it is older than `ttl`
+    pub fn new_from_kubemc_cache(url: String, ttl: Duration) -> Result<Self> {
+        let dir = kubemc_cache_dir(url)?;
+        let refreshed = std::fs::metadata(dir.join(CACHE_MARKER))
+            .and_then(|metadata| metadata.modified())
+            .context("kubemc discovery cache is missing")?;
+        let age = SystemTime::now()
+            .duration_since(refreshed)
+            .unwrap_or_default();
+        if age > ttl {
+            return Err(anyhow!(
+                "kubemc discovery cache expired {:?} ago",
+                age - ttl
+            ));
+        }
+        Self::new_from_dir(dir)
+    }
+
+    /// Runs discovery against the cluster and rewrites the kubemc cache with the result. Only
+    /// the preferred version of each group is kept so lookups by name are unambiguous.
+    pub async fn refresh(client: &KubeClient, url: String) -> Result<Self> {
+        let dir = kubemc_cache_dir(url)?;
+        let mut lists: Vec<(PathBuf, APIResourceList)> = Vec::new();
+
+        let core = client.list_core_api_versions().await?;
+        for version in &core.versions {
+            let list = client.list_core_api_resources(version).await?;
+            lists.push((PathBuf::from(version), list));
+        }
+
+        let groups = client.list_api_groups().await?;
+        let group_lists = futures::future::join_all(groups.groups.iter().filter_map(|group| {
+            let version = group
+                .preferred_version
+                .as_ref()
+                .or_else(|| group.versions.first())?;
+            let path = Path::new(&group.name).join(&version.version);
+            Some(async move {
+                (
+                    path,
+                    client
+                        .list_api_group_resources(&version.group_version)
+                        .await,
+                )
+            })
+        }))
+        .await;
+        for (path, list) in group_lists {
+            match list {
+                Ok(list) => lists.push((path, list)),
+                // an unavailable aggregated API should not prevent caching everything else
+                Err(e) => debug!("failed to discover {}: {}", path.display(), e),
+            }
+        }
+
+        // the new cache is written next to the old one and swapped in once complete, so a
+        // refresh cut short never leaves truncated files or a fresh marker over a partial cache
+        let mut staging = dir.clone().into_os_string();
+        staging.push(format!(".tmp-{}", std::process::id()));
+        let staging = PathBuf::from(staging);
+        let write = || -> Result<()> {
+            for (path, list) in &lists {
+                let path = staging.join(path);
+                std::fs::create_dir_all(&path)?;
+                std::fs::write(
+                    path.join("serverresources.json"),
+                    serde_json::to_string(list)?,
+                )?;
+            }
+            std::fs::write(staging.join(CACHE_MARKER), serde_json::to_string(&groups)?)?;
+            if dir.exists() {
+                std::fs::remove_dir_all(&dir)?;
+            }
+            std::fs::rename(&staging, &dir)?;
+            Ok(())
+        };
+        if let Err(e) = write() {
+            let _ = std::fs::remove_dir_all(&staging);
+            return Err(e.context("failed to write the discovery cache"));
+        }
+        Self::new_from_dir(dir)
+    }
+
+    /// Creates a Discovery from the group versions `resolve` cached, leaving out those older than
+    /// `ttl`. They live in the kubemc cache, which a refresh replaces with a full discovery.
+    pub fn new_from_resolved_cache(url: String, ttl: Duration) -> Result<Self> {
+        let paths = get_cache_files(kubemc_cache_dir(url)?)?
+            .into_iter()
+            .filter(|path| {
+                std::fs::metadata(path)
+                    .and_then(|metadata| metadata.modified())
+                    .is_ok_and(|modified| modified.elapsed().is_ok_and(|age| age <= ttl))
+            })
+            .collect();
+        Ok(Self::new_from_files(read_cache_files(paths)))
+    }
+
+    /// Looks up a single resource by fetching only the group version that may serve it instead
+    /// of discovering the whole cluster: the core group for plain names, or the preferred version
+    /// of the group in qualified names such as `certificates.cert-manager.io`. The group list is
+    /// fetched once into `groups` for every qualified name of a cluster, and what is fetched is
+    /// cached for later commands.
+    pub async fn resolve(
+        client: &KubeClient,
+        url: String,
+        groups: &mut Option<APIGroupList>,
+        name: &str,
+    ) -> Result<Self> {
+        let group_version = match name.split_once('.') {
+            None => Some("v1".to_owned()),
+            Some(_) => {
+                if groups.is_none() {
+                    *groups = Some(client.list_api_groups().await?);
+                }
+                groups
+                    .as_ref()
+                    .and_then(|groups| preferred_group_version(groups, name))
+            }
+        };
+        let Some(group_version) = group_version else {
+            return Ok(Self::new_from_files(Vec::new()));
+        };
+        let list = if group_version.contains('/') {
+            client.list_api_group_resources(&group_version).await?
+        } else {
+            client.list_core_api_resources(&group_version).await?
+        };
+        let list = serde_json::to_string(&list)?;
+        let path = kubemc_cache_dir(url)?.join(&group_version);
+        let cached = std::fs::create_dir_all(&path)
+            .and_then(|()| write_atomic(&path.join("serverresources.json"), &list));
+        if let Err(e) = cached {
+            debug!("failed to cache discovery of {}: {}", group_version, e);
+        }
+        Ok(Self::new_from_files(vec![list]))
+    }
+
+    fn new_from_dir(dir: PathBuf) -> Result<Self> {
+        let paths = get_cache_files(dir)?;
+        Ok(Self::new_from_files(read_cache_files(paths)))
+    }
+
+    fn new_from_files(files: Vec<String>) -> Self {
+        let mut resources = Vec::new();
         for file in &files {
             match ApiResourceList::try_from_str(file) {
                 Ok(arl) => resources.append(&mut arl.get_api_resources()),
                 Err(e) => debug!("failed to parse discovery {}", e),
             }
         }
-        let discovery = Discovery { resources };
-        Ok(discovery)
+        // prefer the core group for kinds that exist in several groups, e.g. events
+        resources.sort_by(|a, b| a.api_resource.group.cmp(&b.api_resource.group));
+        Discovery { resources }
+    }
+
+    /// All resources known to this discovery
+    pub fn resources(&self) -> &[DiscoveryResource] {
+        &self.resources
     }
 
+    /// Number of resources known to this discovery
+    pub fn len(&self) -> usize {
+        self.resources.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.resources.is_empty()
+    }
+
+    /// Finds a resource by kind, plural or short name. The name may be qualified with the API
+    /// group, as in `deployments.apps`, to pick between resources of the same name.
     pub fn get_resource_from_name(&self, name: &str) -> Result<(ApiResource, Scope)> {
-        for resource in &self.resources {
-            for k in &resource.kind {
-                if k.eq_ignore_ascii_case(name) {
-                    return Ok((resource.api_resource.clone(), resource.scope.clone()));
-                }
+        let matches = |resource: &DiscoveryResource, name: &str| {
+            resource.kind.iter().any(|k| k.eq_ignore_ascii_case(name))
+        };
+        if let Some(resource) = self.resources.iter().find(|r| matches(r, name)) {
+            return Ok((resource.api_resource.clone(), resource.scope.clone()));
+        }
+        if let Some((name, group)) = name.split_once('.') {
+            if let Some(resource) = self
+                .resources
+                .iter()
+                .find(|r| r.api_resource.group.eq_ignore_ascii_case(group) && matches(r, name))
+            {
+                return Ok((resource.api_resource.clone(), resource.scope.clone()));
             }
         }
-        Err(anyhow!("resource {} not found", name))
+        let suggestions = self.suggestions(name);
+        match suggestions.first() {
+            Some(suggestion) => Err(anyhow!(
+                "resource {} not found, did you mean {}?",
+                name,
+                suggestion
+            )),
+            None => Err(anyhow!("resource {} not found", name)),
+        }
     }
+
+    /// Plurals of the resources whose kind, plural or short name is the closest to a resource
+    /// name that was not found, for suggesting what a typo meant
+    pub fn suggestions(&self, name: &str) -> Vec<String> {
+        nearest_resources(
+            name,
+            self.resources.iter().flat_map(|r| {
+                r.kind
+                    .iter()
+                    .map(|candidate| (r.api_resource.plural.as_str(), candidate.as_str()))
+            }),
+        )
+    }
+}
+
+/// Resource names handed out as suggestions, each with the clusters serving it
+pub type Suggestions = BTreeMap<String, BTreeSet<String>>;
+
+/// A resource no discovery of a cluster knows, along with the resources it may be a typo of
+#[derive(Debug)]
+pub struct UnknownResource {
+    pub resource: String,
+    pub clustername: String,
+    pub suggestions: Vec<String>,
+}
+
+impl std::fmt::Display for UnknownResource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "discovery of resource {} failed for cluster {}",
+            self.resource, self.clustername
+        )
+    }
+}
+
+impl std::error::Error for UnknownResource {}
+
+/// Picks the resources closest to `name` by edit distance out of `(plural, candidate)` pairs,
+/// where the candidates are kinds, plurals and short names. Names too far off to be a typo are
+/// not suggested.
+pub fn nearest_resources<'a>(
+    name: &str,
+    candidates: impl Iterator<Item = (&'a str, &'a str)>,
+) -> Vec<String> {
+    // a group qualifier as in deploymnets.apps is not part of the typo
+    let name = name.split('.').next().unwrap_or(name).to_lowercase();
+    let max_distance = match name.chars().count() {
+        0..=4 => 1,
+        5..=9 => 2,
+        _ => 3,
+    };
+    let mut best: Option<usize> = None;
+    let mut nearest = BTreeSet::new();
+    for (plural, candidate) in candidates {
+        let distance = edit_distance(&name, &candidate.to_lowercase());
+        if distance > max_distance || best.is_some_and(|best| distance > best) {
+            continue;
+        }
+        if best.is_none_or(|best| distance < best) {
+            best = Some(distance);
+            nearest.clear();
+        }
+        nearest.insert(plural.to_owned());
+    }
+    nearest.into_iter().collect()
+}
+
+/// Describes a resource that no cluster knows, suggesting the closest resources and the
+/// clusters serving each of them
+pub fn unknown_resource_message(resource: &str, suggestions: &Suggestions) -> String {
+    let served: Vec<String> = suggestions
+        .iter()
+        .map(|(suggestion, clusters)| {
+            let clusters: Vec<&str> = clusters.iter().map(String::as_str).collect();
+            format!("{} (served by {})", suggestion, clusters.join(", "))
+        })
+        .collect();
+    match served.as_slice() {
+        [] => format!("resource {} not found on any cluster", resource),
+        [one] => format!(
+            "resource {} not found on any cluster, did you mean {}?",
+            resource, one
+        ),
+        many => format!(
+            "resource {} not found on any cluster, did you mean one of {}?",
+            resource,
+            many.join(", ")
+        ),
+    }
+}
+
+// Optimal string alignment distance, counting a swap of two neighbouring characters as one edit
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    rows[0] = (0..=b.len()).collect();
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            let mut distance = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = distance;
+        }
+    }
+    rows[a.len()][b.len()]
 }
 
 // Replacement taken from: https://github.com/kubernetes/kubernetes/blob/c4d752765b3bbac2237bf87cf0b1c2e307844666/staging/src/k8s.io/cli-runtime/pkg/genericclioptions/config_flags.go#L355-L365
@@ -65,6 +385,32 @@ pub fn parse_kube_url_to_discovery(url: String) -> Result<String> {
     Ok(re.replace_all(&hp, "_").to_string())
 }
 
+fn kubemc_cache_dir(url: String) -> Result<PathBuf> {
+    let host_path = parse_kube_url_to_discovery(url)?;
+    dirs::home_dir()
+        .map(|h| {
+            h.join(".kube")
+                .join("cache")
+                .join("kubemc-discovery")
+                .join(host_path)
+        })
+        .ok_or_else(|| anyhow!("failed to find home directory"))
+}
+
+// The preferred version of the group a qualified name such as `deployments.apps` is in
+fn preferred_group_version(groups: &APIGroupList, name: &str) -> Option<String> {
+    let (_, qualifier) = name.split_once('.')?;
+    let group = groups
+        .groups
+        .iter()
+        .find(|group| group.name.eq_ignore_ascii_case(qualifier))?;
+    group
+        .preferred_version
+        .as_ref()
+        .or_else(|| group.versions.first())
+        .map(|version| version.group_version.clone())
+}
+
 fn get_cache_files<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>> {
     let mut files: Vec<PathBuf> = Vec::new();
     let mut entries = std::fs::read_dir(path)?;
@@ -81,6 +427,15 @@ fn get_cache_files<P: AsRef<Path>>(path: P) -> Result<Vec<PathBuf>> {
     Ok(files)
 }
 
+// Writes next to the target and renames, so a reader never sees a truncated file
+fn write_atomic(path: &Path, contents: &str) -> std::io::Result<()> {
+    let tmp = path.with_extension(format!("tmp-{}", std::process::id()));
+    std::fs::write(&tmp, contents)?;
+    std::fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&tmp);
+    })
+}
+
 fn read_cache_files(paths: Vec<PathBuf>) -> Vec<String> {
     let mut file_outs: Vec<String> = Vec::new();
     for path in paths {
@@ -117,15 +472,19 @@ impl ApiResourceList {
         }
     }
     fn get_api_resources(&self) -> Vec<DiscoveryResource> {
-        let group = match self.group_version.split_once('/') {
-            Some(g) => g.0,
-            None => "",
+        let (group, version) = match self.group_version.split_once('/') {
+            Some(g) => g,
+            None => ("", self.group_version.as_str()),
         };
         let mut resource_list = Vec::new();
         for resource in &self.resources {
+            // subresources such as pods/log are not resources that can be listed
+            if resource.name.contains('/') {
+                continue;
+            }
             let api_resource = ApiResource {
                 group: group.to_string(),
-                version: self.api_version.clone(),
+                version: version.to_string(),
                 api_version: self.group_version.clone(),
                 kind: resource.kind.clone(),
                 plural: resource.name.clone(),
@@ -227,6 +586,102 @@ mod test {
         assert_eq!(ds.0.kind, "DaemonSet");
     }
 
+    #[test]
+    fn parse_group_resources() {
+        let arl = ApiResourceList::try_from_str(
+            r#"{"kind":"APIResourceList","apiVersion":"v1","groupVersion":"autoscaling/v2",
+            "resources":[
+              {"name":"horizontalpodautoscalers","singularName":"horizontalpodautoscaler",
+               "namespaced":true,"kind":"HorizontalPodAutoscaler","shortNames":["hpa"],
+               "verbs":["get","list"]},
+              {"name":"horizontalpodautoscalers/status","singularName":"","namespaced":true,
+               "kind":"HorizontalPodAutoscaler","verbs":["get"]}]}"#,
+        )
+        .unwrap();
+        let discovery = Discovery {
+            resources: arl.get_api_resources(),
+        };
+        assert_eq!(discovery.len(), 1);
+        let (ar, scope) = discovery.get_resource_from_name("hpa").unwrap();
+        assert_eq!(ar.group, "autoscaling");
+        assert_eq!(ar.version, "v2");
+        assert_eq!(ar.plural, "horizontalpodautoscalers");
+        assert_eq!(scope, Scope::Namespaced);
+    }
+
+    #[test]
+    fn suggest_typos() {
+        let arl = ApiResourceList::try_from_str(
+            r#"{"kind":"APIResourceList","apiVersion":"v1","groupVersion":"apps/v1",
+            "resources":[
+              {"name":"deployments","namespaced":true,"kind":"Deployment",
+               "shortNames":["deploy"],"verbs":["get","list"]},
+              {"name":"daemonsets","namespaced":true,"kind":"DaemonSet",
+               "shortNames":["ds"],"verbs":["get","list"]},
+              {"name":"statefulsets","namespaced":true,"kind":"StatefulSet",
+               "shortNames":["sts"],"verbs":["get","list"]}]}"#,
+        )
+        .unwrap();
+        let discovery = Discovery {
+            resources: arl.get_api_resources(),
+        };
+        assert_eq!(discovery.suggestions("deploymnets"), ["deployments"]);
+        assert_eq!(discovery.suggestions("Deplyoment"), ["deployments"]);
+        assert_eq!(discovery.suggestions("deploymnets.apps"), ["deployments"]);
+        assert_eq!(discovery.suggestions("stss"), ["statefulsets"]);
+        assert!(discovery.suggestions("configmaps").is_empty());
+        let (found, _) = discovery.get_resource_from_name("daemonset").unwrap();
+        assert_eq!(found.plural, "daemonsets");
+        let err = discovery.get_resource_from_name("deamonsets").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "resource deamonsets not found, did you mean daemonsets?"
+        );
+
+        let mut suggestions = Suggestions::new();
+        suggestions
+            .entry("deployments".into())
+            .or_default()
+            .extend(["prod-us".to_string(), "prod-eu".to_string()]);
+        assert_eq!(
+            unknown_resource_message("deploymnets", &suggestions),
+            "resource deploymnets not found on any cluster, did you mean deployments (served by \
+             prod-eu, prod-us)?"
+        );
+        suggestions.insert("daemonsets".into(), BTreeSet::from(["prod-us".into()]));
+        assert_eq!(
+            unknown_resource_message("dep", &suggestions),
+            "resource dep not found on any cluster, did you mean one of daemonsets (served by \
+             prod-us), deployments (served by prod-eu, prod-us)?"
+        );
+    }
+
+    #[test]
+    fn resolve_group_versions() {
+        let groups: APIGroupList = serde_json::from_str(
+            r#"{"kind":"APIGroupList","apiVersion":"v1","groups":[
+              {"name":"apps","versions":[{"groupVersion":"apps/v1","version":"v1"}],
+               "preferredVersion":{"groupVersion":"apps/v1","version":"v1"}},
+              {"name":"cert-manager.io","versions":[
+                {"groupVersion":"cert-manager.io/v1","version":"v1"},
+                {"groupVersion":"cert-manager.io/v1beta1","version":"v1beta1"}]}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            preferred_group_version(&groups, "deployments.apps").as_deref(),
+            Some("apps/v1")
+        );
+        assert_eq!(
+            preferred_group_version(&groups, "certificates.cert-manager.io").as_deref(),
+            Some("cert-manager.io/v1")
+        );
+        assert_eq!(
+            preferred_group_version(&groups, "widgets.example.com"),
+            None
+        );
+        assert_eq!(preferred_group_version(&groups, "pods"), None);
+    }
+
     #[test]
     fn build_host_path() {
         let hp = parse_kube_url_to_discovery("https://carson.cloud.gravitational.io:443".into())
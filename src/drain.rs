@@ -0,0 +1,160 @@
+use k8s_openapi::api::core::v1::Pod;
+use kube::ResourceExt;
+
+/// Annotation kubelet sets on the mirror pods of static pods, which cannot be evicted
+const MIRROR_ANNOTATION: &str = "kubernetes.io/config.mirror";
+
+/// Merge patch marking a node unschedulable, or schedulable again
+pub fn cordon_patch(unschedulable: bool) -> serde_json::Value {
+    serde_json::json!({"spec": {"unschedulable": unschedulable}})
+}
+
+/// What draining a node does with one of its pods
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Disposition {
+    Evict,
+    /// Left on the node, such as daemonset pods which would come right back
+    Skip,
+    /// Stops the drain of the node, with the flag that allows evicting it
+    Block(&'static str),
+}
+
+/// Which pods a drain evicts besides those a controller recreates elsewhere
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DrainPolicy {
+    /// Evict pods not managed by a controller, which are gone for good
+    pub force: bool,
+    /// Evict pods with emptyDir volumes, whose data is lost
+    pub delete_emptydir_data: bool,
+}
+
+impl DrainPolicy {
+    /// Decides what happens to a pod the way `kubectl drain --ignore-daemonsets` does
+    pub fn disposition(&self, pod: &Pod) -> Disposition {
+        if pod.annotations().contains_key(MIRROR_ANNOTATION) {
+            return Disposition::Skip;
+        }
+        // finished pods have nothing left to lose
+        let phase = pod
+            .status
+            .as_ref()
+            .and_then(|status| status.phase.as_deref());
+        if matches!(phase, Some("Succeeded" | "Failed")) {
+            return Disposition::Evict;
+        }
+        let controller = pod
+            .owner_references()
+            .iter()
+            .find(|owner| owner.controller == Some(true));
+        if controller.is_some_and(|owner| owner.kind == "DaemonSet") {
+            return Disposition::Skip;
+        }
+        if controller.is_none() && !self.force {
+            return Disposition::Block("--force");
+        }
+        let emptydir = pod
+            .spec
+            .iter()
+            .flat_map(|spec| spec.volumes.iter().flatten())
+            .any(|volume| volume.empty_dir.is_some());
+        if emptydir && !self.delete_emptydir_data {
+            return Disposition::Block("--delete-emptydir-data");
+        }
+        Disposition::Evict
+    }
+}
+
+/// Whether a node name matches a pattern in which `*` stands for any run of characters, such as
+/// `ip-10-0-*` or `*-pool-a-*`
+pub fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let Some(mut rest) = name.strip_prefix(first) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+    rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn drain_dispositions() {
+        let pod = |metadata: serde_json::Value, spec: serde_json::Value| -> Pod {
+            serde_json::from_value(serde_json::json!({
+                "metadata": metadata,
+                "spec": spec,
+                "status": {"phase": "Running"},
+            }))
+            .unwrap()
+        };
+        let owned_by = |kind: &str| {
+            serde_json::json!({"name": "web", "ownerReferences": [{
+                "apiVersion": "apps/v1", "kind": kind, "name": "web", "uid": "1",
+                "controller": true,
+            }]})
+        };
+        let containers = serde_json::json!({"containers": [{"name": "web"}]});
+        let with_emptydir = serde_json::json!({
+            "containers": [{"name": "web"}],
+            "volumes": [{"name": "scratch", "emptyDir": {}}],
+        });
+        let policy = DrainPolicy::default();
+
+        let replicated = pod(owned_by("ReplicaSet"), containers.clone());
+        assert_eq!(policy.disposition(&replicated), Disposition::Evict);
+        let daemon = pod(owned_by("DaemonSet"), containers.clone());
+        assert_eq!(policy.disposition(&daemon), Disposition::Skip);
+        let mirror = pod(
+            serde_json::json!({"name": "etcd", "annotations": {MIRROR_ANNOTATION: "abc"}}),
+            containers.clone(),
+        );
+        assert_eq!(policy.disposition(&mirror), Disposition::Skip);
+
+        let bare = pod(serde_json::json!({"name": "debug"}), containers);
+        assert_eq!(policy.disposition(&bare), Disposition::Block("--force"));
+        let forced = DrainPolicy {
+            force: true,
+            ..policy
+        };
+        assert_eq!(forced.disposition(&bare), Disposition::Evict);
+        let mut finished = bare.clone();
+        finished.status.as_mut().unwrap().phase = Some("Succeeded".into());
+        assert_eq!(policy.disposition(&finished), Disposition::Evict);
+
+        let scratch = pod(owned_by("ReplicaSet"), with_emptydir);
+        assert_eq!(
+            policy.disposition(&scratch),
+            Disposition::Block("--delete-emptydir-data")
+        );
+        let deleting = DrainPolicy {
+            delete_emptydir_data: true,
+            ..policy
+        };
+        assert_eq!(deleting.disposition(&scratch), Disposition::Evict);
+    }
+
+    #[test]
+    fn match_node_patterns() {
+        assert!(matches_pattern("node-1", "node-1"));
+        assert!(!matches_pattern("node-1", "node-10"));
+        assert!(matches_pattern("ip-10-0-*", "ip-10-0-3-17"));
+        assert!(!matches_pattern("ip-10-0-*", "ip-10-1-3-17"));
+        assert!(matches_pattern("*-pool-a-*", "gke-prod-pool-a-1f2e"));
+        assert!(!matches_pattern("*-pool-a-*", "gke-prod-pool-b-1f2e"));
+        assert!(matches_pattern("*", "anything"));
+        assert!(matches_pattern("a*b*b", "abb"));
+        assert!(!matches_pattern("a*b*b", "ab"));
+    }
+}
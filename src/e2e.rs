@@ -0,0 +1,440 @@
+use std::{
+    path::{Path, PathBuf},
+    process::{Output, Stdio},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::{api::DeleteParams, Api};
+use tokio::{
+    io::{AsyncBufReadExt, BufReader},
+    process::Command,
+};
+
+use crate::{
+    client::connect_all,
+    config::{Cluster, Clusterset, Config, API_VERSION},
+    output::{E2eOutput, Health, NamedGetReport, StatusCell},
+    telemetry::format_millis,
+};
+
+/// Prefix of the kind clusters the suite runs against, numbered from 1
+pub const KIND_PREFIX: &str = "kubemc-e2e";
+
+/// Kind clusters the suite runs against
+const CLUSTER_COUNT: usize = 2;
+
+/// Namespace the suite creates its objects in
+const NAMESPACE: &str = "kubemc-e2e";
+
+/// ConfigMap the suite applies, watches and deletes
+const PROBE: &str = "e2e-probe";
+
+/// How long the watch may take to report the objects and their changes
+const WATCH_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Kind clusters of the suite along with the config and kubeconfig pointing at them
+pub struct Environment {
+    dir: PathBuf,
+    clusters: Vec<String>,
+}
+
+impl Environment {
+    /// Creates the kind clusters of the suite, reusing those left running by an earlier run, and
+    /// writes a clusterset of them to `dir`
+    pub async fn create(dir: &Path) -> Result<Self> {
+        let environment = Self {
+            dir: dir.to_path_buf(),
+            clusters: (1..=CLUSTER_COUNT)
+                .map(|i| format!("{}-{}", KIND_PREFIX, i))
+                .collect(),
+        };
+        let running = kind(&["get", "clusters"]).await?;
+        let running = String::from_utf8_lossy(&running.stdout);
+        std::fs::create_dir_all(dir)?;
+        let kubeconfig = environment.kubeconfig();
+        let kubeconfig = kubeconfig.to_string_lossy();
+        for cluster in &environment.clusters {
+            if running.lines().any(|line| line == cluster) {
+                eprintln!("reusing kind cluster {}", cluster);
+                kind(&[
+                    "export",
+                    "kubeconfig",
+                    "--name",
+                    cluster,
+                    "--kubeconfig",
+                    &kubeconfig,
+                ])
+                .await?;
+            } else {
+                eprintln!("creating kind cluster {}", cluster);
+                kind(&[
+                    "create",
+                    "cluster",
+                    "--name",
+                    cluster,
+                    "--kubeconfig",
+                    &kubeconfig,
+                    "--wait",
+                    "120s",
+                ])
+                .await?;
+            }
+        }
+        let config = suite_config(&environment.clusters, &kubeconfig);
+        Config::create_file(&environment.config(), &config).await?;
+        Ok(environment)
+    }
+
+    /// Deletes the kind clusters
+    pub async fn delete(&self) -> Result<()> {
+        for cluster in &self.clusters {
+            kind(&["delete", "cluster", "--name", cluster]).await?;
+        }
+        Ok(())
+    }
+
+    fn kubeconfig(&self) -> PathBuf {
+        self.dir.join("kubeconfig")
+    }
+
+    /// The kubemc config of the suite
+    pub fn config(&self) -> PathBuf {
+        self.dir.join("kubemc")
+    }
+
+    // Runs kubemc against the clusterset of the suite
+    fn kubemc(&self, args: &[&str]) -> Command {
+        let exe = std::env::current_exe().unwrap_or_else(|_| "kubemc".into());
+        let mut command = Command::new(exe);
+        command.arg("--config-file").arg(self.config()).args(args);
+        command.stdin(Stdio::null()).kill_on_drop(true);
+        command
+    }
+
+    async fn run_kubemc(&self, args: &[&str]) -> Result<Output> {
+        let output = self.kubemc(args).output().await?;
+        Ok(output)
+    }
+
+    /// Runs the suite, stopping at the first step that fails as the later steps build on it
+    pub async fn run_suite(&self) -> Vec<E2eOutput> {
+        let mut rows = Vec::new();
+        let mut failed = false;
+        for step in STEPS {
+            let name = step.name();
+            if failed {
+                rows.push(E2eOutput {
+                    step: name.into(),
+                    result: StatusCell::new("skipped", Health::Unknown),
+                    took: String::new(),
+                });
+                continue;
+            }
+            let start = Instant::now();
+            let outcome = match step {
+                Step::Apply => self.apply_generation(1).await,
+                Step::Get => self.get_step().await,
+                Step::List => self.list_step().await,
+                Step::Watch => self.watch_step().await,
+                Step::Delete => self.delete_step().await,
+            };
+            let result = match outcome {
+                Ok(()) => StatusCell::new("passed", Health::Healthy),
+                Err(e) => {
+                    failed = true;
+                    StatusCell::new(format!("failed: {:#}", e), Health::Unhealthy)
+                }
+            };
+            rows.push(E2eOutput {
+                step: name.into(),
+                result,
+                took: format_millis(start.elapsed().as_millis() as u64),
+            });
+        }
+        rows
+    }
+
+    // Applies the manifest of the suite at a generation
+    async fn apply_generation(&self, generation: u32) -> Result<()> {
+        let path = self.dir.join("manifest.yaml");
+        std::fs::write(&path, manifest(generation))?;
+        let output = self
+            .run_kubemc(&["apply", "-f", &path.to_string_lossy()])
+            .await?;
+        expect_success(&output)
+    }
+
+    async fn get_step(&self) -> Result<()> {
+        let output = self.run_kubemc(&["get", "configmaps", PROBE]).await?;
+        expect_success(&output)?;
+        expect_clusters(
+            &self.clusternames(),
+            &String::from_utf8_lossy(&output.stdout),
+        )
+    }
+
+    async fn list_step(&self) -> Result<()> {
+        let output = self
+            .run_kubemc(&["get", "configmaps", "-o", "jsonl"])
+            .await?;
+        expect_success(&output)?;
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let probes: Vec<&str> = stdout.lines().filter(|line| line.contains(PROBE)).collect();
+        expect_clusters(&self.clusternames(), &probes.join("\n"))
+    }
+
+    // Watches the configmaps, waiting for the probe to show up on every cluster, then changes it
+    // and waits for every cluster to report the change
+    async fn watch_step(&self) -> Result<()> {
+        let mut watch = self
+            .kubemc(&["get", "configmaps", "--watch"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdout = watch
+            .stdout
+            .take()
+            .ok_or_else(|| anyhow!("no output of the watch"))?;
+        let mut lines = BufReader::new(stdout).lines();
+        let clusters = self.clusternames();
+        let watched = tokio::time::timeout(WATCH_TIMEOUT, async {
+            for (change, then_apply) in [("ADDED", Some(2)), ("MODIFIED", None)] {
+                let mut pending = clusters.clone();
+                while !pending.is_empty() {
+                    let line = lines
+                        .next_line()
+                        .await?
+                        .ok_or_else(|| anyhow!("watch ended before reporting {}", change))?;
+                    if let Some(cluster) = watch_event(&line, change, PROBE) {
+                        pending.retain(|c| c != cluster);
+                    }
+                }
+                if let Some(generation) = then_apply {
+                    self.apply_generation(generation).await?;
+                }
+            }
+            Ok::<(), anyhow::Error>(())
+        })
+        .await;
+        watch.kill().await.ok();
+        watched.map_err(|_| anyhow!("watch did not report every cluster in time"))?
+    }
+
+    // kubemc has no delete, the probe is deleted through the API and has to be gone from every
+    // cluster for `get` afterwards
+    async fn delete_step(&self) -> Result<()> {
+        for (clustername, client) in connect_all(&self.cluster_configs()).await? {
+            let configmaps: Api<ConfigMap> = Api::namespaced(client?, NAMESPACE);
+            configmaps
+                .delete(PROBE, &DeleteParams::default())
+                .await
+                .with_context(|| format!("cluster {}", clustername))?;
+        }
+        let deadline = Instant::now() + WATCH_TIMEOUT;
+        loop {
+            let output = self.run_kubemc(&["get", "configmaps", PROBE]).await?;
+            // any other code than not found on every cluster, such as a failed cluster, means
+            // the probe is not known to be gone
+            if output.status.code() == Some(NamedGetReport::NOT_FOUND.into()) {
+                return Ok(());
+            }
+            if Instant::now() > deadline {
+                return Err(anyhow!("{} still found after deleting it", PROBE));
+            }
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    fn cluster_configs(&self) -> Vec<Cluster> {
+        suite_config(&self.clusters, &self.kubeconfig().to_string_lossy()).clustersets[0]
+            .clusters
+            .clone()
+    }
+
+    fn clusternames(&self) -> Vec<String> {
+        self.cluster_configs().into_iter().map(|c| c.name).collect()
+    }
+}
+
+/// Steps of the suite, in the order they run
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Step {
+    Apply,
+    Get,
+    List,
+    Watch,
+    Delete,
+}
+
+const STEPS: [Step; 5] = [
+    Step::Apply,
+    Step::Get,
+    Step::List,
+    Step::Watch,
+    Step::Delete,
+];
+
+impl Step {
+    fn name(self) -> &'static str {
+        match self {
+            Step::Apply => "apply",
+            Step::Get => "get by name",
+            Step::List => "list",
+            Step::Watch => "watch",
+            Step::Delete => "delete",
+        }
+    }
+}
+
+// Runs kind, failing with its output when it fails
+async fn kind(args: &[&str]) -> Result<Output> {
+    let output = Command::new("kind")
+        .args(args)
+        .stdin(Stdio::null())
+        .output()
+        .await
+        .map_err(|e| {
+            anyhow!(
+                "failed to run kind, install it from https://kind.sigs.k8s.io: {}",
+                e
+            )
+        })?;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "kind {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(output)
+}
+
+fn expect_success(output: &Output) -> Result<()> {
+    if output.status.success() {
+        return Ok(());
+    }
+    Err(anyhow!(
+        "exited with {}: {}",
+        output.status,
+        String::from_utf8_lossy(&output.stderr).trim()
+    ))
+}
+
+// Checks every cluster shows up in the output
+fn expect_clusters(clusters: &[String], output: &str) -> Result<()> {
+    let missing: Vec<&str> = clusters
+        .iter()
+        .filter(|cluster| {
+            !output
+                .split(|c: char| !(c.is_alphanumeric() || c == '-'))
+                .any(|word| word == cluster.as_str())
+        })
+        .map(String::as_str)
+        .collect();
+    if missing.is_empty() {
+        Ok(())
+    } else {
+        Err(anyhow!("missing from the output: {}", missing.join(", ")))
+    }
+}
+
+/// The config of the suite, a clusterset named e2e holding a cluster per kind cluster
+pub fn suite_config(kind_clusters: &[String], kubeconfig: &str) -> Config {
+    let clusters = kind_clusters
+        .iter()
+        .map(|kind_cluster| Cluster {
+            name: kind_cluster
+                .strip_prefix(KIND_PREFIX)
+                .map(|number| format!("e2e{}", number))
+                .unwrap_or_else(|| kind_cluster.clone()),
+            context: Some(format!("kind-{}", kind_cluster)),
+            kubeconfig: Some(kubeconfig.to_owned()),
+            ..Default::default()
+        })
+        .collect();
+    Config {
+        api_version: API_VERSION.into(),
+        current_clusterset: "e2e".into(),
+        clustersets: vec![Clusterset {
+            name: "e2e".into(),
+            namespace: NAMESPACE.into(),
+            clusters,
+            include: Vec::new(),
+            pinned_cluster: None,
+            output: None,
+        }],
+        ..Default::default()
+    }
+}
+
+/// The manifest of the suite, its namespace and the probe at a generation
+pub fn manifest(generation: u32) -> String {
+    format!(
+        "apiVersion: v1
+kind: Namespace
+metadata:
+  name: {namespace}
+---
+apiVersion: v1
+kind: ConfigMap
+metadata:
+  name: {probe}
+  namespace: {namespace}
+data:
+  generation: \"{generation}\"
+",
+        namespace = NAMESPACE,
+        probe = PROBE,
+        generation = generation
+    )
+}
+
+// The cluster of a watch line reporting `change` to `name`
+fn watch_event<'a>(line: &'a str, change: &str, name: &str) -> Option<&'a str> {
+    let mut words = line.split_whitespace();
+    if words.next()? != change {
+        return None;
+    }
+    let cluster = words.next()?;
+    words.any(|word| word == name).then_some(cluster)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn suite_setup() {
+        let kind_clusters = vec!["kubemc-e2e-1".to_string(), "kubemc-e2e-2".to_string()];
+        let config = suite_config(&kind_clusters, "/tmp/e2e/kubeconfig");
+        let clusterset = config.active_clusterset().unwrap();
+        assert_eq!(clusterset.namespace, NAMESPACE);
+        let clusters: Vec<(&str, Option<&str>)> = clusterset
+            .clusters
+            .iter()
+            .map(|c| (c.name.as_str(), c.context.as_deref()))
+            .collect();
+        assert_eq!(
+            clusters,
+            [
+                ("e2e-1", Some("kind-kubemc-e2e-1")),
+                ("e2e-2", Some("kind-kubemc-e2e-2"))
+            ]
+        );
+
+        assert!(manifest(2).contains("generation: \"2\""));
+
+        assert_eq!(
+            watch_event("ADDED     e2e-1   e2e-probe   1   5s", "ADDED", PROBE),
+            Some("e2e-1")
+        );
+        assert_eq!(
+            watch_event("MODIFIED  e2e-1   e2e-probe   1   5s", "ADDED", PROBE),
+            None
+        );
+        assert!(expect_clusters(&["e2e-1".into(), "e2e-2".into()], "e2e-1 e2e-probe").is_err());
+        assert!(expect_clusters(&["e2e-1".into()], "e2e-1   e2e-probe").is_ok());
+    }
+}
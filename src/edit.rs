@@ -0,0 +1,176 @@
+use anyhow::{anyhow, Context, Result};
+use kube::core::DynamicObject;
+use serde_json::Value;
+use std::collections::hash_map::RandomState;
+use std::fs::{self, OpenOptions};
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::process::Command;
+
+/// Fields left out of the editor since they are managed by the API server
+const NOT_EDITABLE: &[&str] = &["managedFields"];
+
+/// The object as the user gets to edit it, without server managed fields and status
+pub fn editable(object: &DynamicObject) -> Result<Value> {
+    let mut value = serde_json::to_value(object)?;
+    if let Some(object) = value.as_object_mut() {
+        object.remove("status");
+        if let Some(Value::Object(metadata)) = object.get_mut("metadata") {
+            for field in NOT_EDITABLE {
+                metadata.remove(*field);
+            }
+        }
+    }
+    Ok(value)
+}
+
+/// Metadata fields the API server sets on every object, left out of cleaned objects
+const SERVER_METADATA: &[&str] = &[
+    "managedFields",
+    "resourceVersion",
+    "uid",
+    "creationTimestamp",
+    "generation",
+    "selfLink",
+];
+
+/// The object without status and server set metadata, ready to be saved as a manifest
+pub fn cleaned(object: &DynamicObject) -> Result<Value> {
+    let mut value = editable(object)?;
+    if let Some(Value::Object(metadata)) = value.get_mut("metadata") {
+        for field in SERVER_METADATA {
+            metadata.remove(*field);
+        }
+        if let Some(Value::Object(annotations)) = metadata.get_mut("annotations") {
+            annotations.remove("kubectl.kubernetes.io/last-applied-configuration");
+            if annotations.is_empty() {
+                metadata.remove("annotations");
+            }
+        }
+    }
+    Ok(value)
+}
+
+/// Opens `contents` in the editor from KUBE_EDITOR or EDITOR, falling back to vi as kubectl
+/// does, and returns the saved file. `name` ends up in the temporary file name so the editor
+/// shows what is being edited.
+pub async fn edit_in_editor(name: &str, contents: &str) -> Result<String> {
+    let editor = std::env::var("KUBE_EDITOR")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".into());
+    let file = PrivateFile::create(&format!("kubemc-edit-{}", name.replace(['/', ','], "-")))
+        .context("failed to create file for editing")?;
+    tokio::fs::write(&file.path, contents)
+        .await
+        .context("failed to write object for editing")?;
+    // the editor may come with arguments, e.g. `code --wait`
+    let status = Command::new("sh")
+        .arg("-c")
+        .arg(format!("{} \"$1\"", editor))
+        .arg("sh")
+        .arg(&file.path)
+        .status()
+        .await
+        .with_context(|| format!("failed to run editor {}", editor))?;
+    if !status.success() {
+        return Err(anyhow!("editor {} exited with {}", editor, status));
+    }
+    tokio::fs::read_to_string(&file.path)
+        .await
+        .context("failed to read edited object")
+}
+
+// A temporary yaml file only the current user can read, under a name that cannot be guessed
+// ahead of time, removed once dropped
+struct PrivateFile {
+    path: PathBuf,
+}
+
+impl PrivateFile {
+    fn create(prefix: &str) -> io::Result<Self> {
+        loop {
+            let path = std::env::temp_dir().join(format!("{}-{:016x}.yaml", prefix, random()));
+            let mut options = OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::OpenOptionsExt;
+                options.mode(0o600);
+            }
+            match options.open(&path) {
+                Ok(_) => return Ok(Self { path }),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for PrivateFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// Random bits from the randomly keyed std hasher
+fn random() -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos(),
+    );
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn clean_server_fields() {
+        let object: DynamicObject = serde_json::from_value(json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMap",
+            "metadata": {
+                "name": "settings",
+                "uid": "1234",
+                "resourceVersion": "7",
+                "managedFields": [{"manager": "kubectl"}],
+                "annotations": {"kubectl.kubernetes.io/last-applied-configuration": "{}"},
+                "labels": {"app": "web"},
+            },
+            "data": {"mode": "fast"},
+            "status": {},
+        }))
+        .unwrap();
+        assert_eq!(
+            cleaned(&object).unwrap(),
+            json!({
+                "apiVersion": "v1",
+                "kind": "ConfigMap",
+                "metadata": {"name": "settings", "labels": {"app": "web"}},
+                "data": {"mode": "fast"},
+            })
+        );
+    }
+
+    #[test]
+    fn private_files() {
+        let file = PrivateFile::create("kubemc-test").unwrap();
+        let path = file.path.clone();
+        assert!(PrivateFile::create("kubemc-test").unwrap().path != path);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        drop(file);
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,120 @@
+use kube::{core::ErrorResponse, Error as KubeError};
+use regex::Regex;
+
+/// Returns a concise, user facing description of an error returned while talking to a cluster.
+/// Kubernetes API errors are classified by status code, anything else is shown as is.
+pub fn describe_error(e: &anyhow::Error) -> String {
+    match e
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<KubeError>())
+    {
+        Some(kube_error) => describe_kube_error(kube_error),
+        None => e.to_string(),
+    }
+}
+
+pub fn describe_kube_error(e: &KubeError) -> String {
+    match e {
+        KubeError::Api(response) => describe_api_error(response),
+        e => e.to_string(),
+    }
+}
+
+/// Whether the error means the cluster rejected the credentials or they could not be obtained,
+/// which a login command may be able to fix
+pub fn is_auth_error(e: &anyhow::Error) -> bool {
+    matches!(
+        e.chain()
+            .find_map(|cause| cause.downcast_ref::<KubeError>()),
+        Some(KubeError::Api(ErrorResponse {
+            code: 401 | 403,
+            ..
+        })) | Some(KubeError::Auth(_))
+    )
+}
+
+fn describe_api_error(response: &ErrorResponse) -> String {
+    match response.code {
+        401 => "unauthorized: credentials were rejected".into(),
+        403 => describe_forbidden(&response.message),
+        404 => describe_not_found(&response.message),
+        410 => "expired: the list changed while paging, retry the request".into(),
+        429 => "throttled: too many requests".into(),
+        _ => format!("{}: {}", response.reason, response.message),
+    }
+}
+
+// RBAC denials look like:
+// pods is forbidden: User "alice" cannot list resource "pods" in API group "" in the namespace "x"
+fn describe_forbidden(message: &str) -> String {
+    let re = Regex::new(r#"cannot (\w+) resource "([^"]+)"(?: in API group "([^"]*)")?"#).unwrap();
+    match re.captures(message) {
+        Some(caps) => {
+            let resource = match caps.get(3).map(|g| g.as_str()) {
+                Some(group) if !group.is_empty() => format!("{}.{}", &caps[2], group),
+                _ => caps[2].to_string(),
+            };
+            format!("forbidden: missing {} {}", &caps[1], resource)
+        }
+        None => format!("forbidden: {}", message),
+    }
+}
+
+// A 404 on a list usually means the namespace itself is missing, e.g. `namespaces "x" not found`
+fn describe_not_found(message: &str) -> String {
+    let re = Regex::new(r#"^namespaces "([^"]+)" not found"#).unwrap();
+    match re.captures(message) {
+        Some(caps) => format!("namespace {} not found", &caps[1]),
+        None => format!("not found: {}", message),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn api_error(code: u16, reason: &str, message: &str) -> KubeError {
+        KubeError::Api(ErrorResponse {
+            status: "Failure".into(),
+            message: message.into(),
+            reason: reason.into(),
+            code,
+        })
+    }
+
+    #[test]
+    fn classify_api_errors() {
+        let forbidden = api_error(
+            403,
+            "Forbidden",
+            r#"pods is forbidden: User "alice" cannot list resource "pods" in API group "" in the namespace "default""#,
+        );
+        assert_eq!(
+            describe_kube_error(&forbidden),
+            "forbidden: missing list pods"
+        );
+
+        let forbidden_group = api_error(
+            403,
+            "Forbidden",
+            r#"deployments.apps is forbidden: User "alice" cannot get resource "deployments" in API group "apps" in the namespace "default""#,
+        );
+        assert_eq!(
+            describe_error(&anyhow::Error::from(forbidden_group)),
+            "forbidden: missing get deployments.apps"
+        );
+
+        let missing_ns = api_error(404, "NotFound", r#"namespaces "team-a" not found"#);
+        assert_eq!(
+            describe_kube_error(&missing_ns),
+            "namespace team-a not found"
+        );
+
+        let auth = |e: KubeError| is_auth_error(&anyhow::Error::from(e));
+        assert!(auth(api_error(401, "Unauthorized", "Unauthorized")));
+        assert!(auth(api_error(403, "Forbidden", "pods is forbidden")));
+        // only the status code counts, not what the message happens to say
+        assert!(!auth(api_error(500, "InternalError", "token expired")));
+        assert!(!auth(missing_ns));
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use k8s_openapi::{
+    api::{core::v1::Endpoints, discovery::v1::EndpointSlice},
+    chrono::{DateTime, Utc},
+};
+use kube::{
+    runtime::watcher::{self, Event},
+    ResourceExt,
+};
+
+/// Label EndpointSlices carry the name of their service in
+pub const SERVICE_NAME_LABEL: &str = "kubernetes.io/service-name";
+
+/// Annotation the endpoints controllers stamp EndpointSlices and Endpoints with when the pods
+/// behind them last changed
+const LAST_CHANGE_ANNOTATION: &str = "endpoints.kubernetes.io/last-change-trigger-time";
+
+/// Backends of a service on one cluster by readiness
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BackendCount {
+    pub ready: usize,
+    pub not_ready: usize,
+    /// Backends of pods being deleted, which stop receiving new traffic
+    pub terminating: usize,
+}
+
+/// An event of either watch of a service on one cluster
+pub enum BackendUpdate {
+    Slices(watcher::Result<Event<EndpointSlice>>),
+    Endpoints(watcher::Result<Event<Endpoints>>),
+}
+
+/// What is known of the backends of a service on one cluster, kept up to date from watches of
+/// its EndpointSlices and Endpoints
+#[derive(Default)]
+pub struct ServiceBackends {
+    slices: HashMap<String, EndpointSlice>,
+    endpoints: Option<Endpoints>,
+    count: BackendCount,
+    /// Whether either watch listed the backends yet
+    listed: bool,
+    /// When the number of ready backends last changed, unknown until it changes while watched
+    /// unless the listed objects tell
+    pub changed: Option<DateTime<Utc>>,
+    /// The last error of a watch, cleared by its next event
+    pub error: Option<String>,
+}
+
+impl ServiceBackends {
+    pub fn count(&self) -> BackendCount {
+        self.count
+    }
+
+    /// Applies an event of either watch, returning whether what is shown of the service changed.
+    /// The watches retry on their own, so an error is only kept until their next event.
+    pub fn apply(&mut self, update: BackendUpdate, now: DateTime<Utc>) -> bool {
+        match update {
+            BackendUpdate::Slices(Ok(event)) => self.apply_slices(event, now),
+            BackendUpdate::Endpoints(Ok(event)) => self.apply_endpoints(event, now),
+            BackendUpdate::Slices(Err(e)) | BackendUpdate::Endpoints(Err(e)) => {
+                self.error = Some(e.to_string());
+                true
+            }
+        }
+    }
+
+    /// Applies an event of the EndpointSlices watch, returning whether the count changed or an
+    /// error was cleared
+    pub fn apply_slices(&mut self, event: Event<EndpointSlice>, now: DateTime<Utc>) -> bool {
+        let at = match event {
+            Event::Applied(slice) => {
+                let at = last_change(slice.annotations()).unwrap_or(now);
+                self.slices.insert(slice.name_any(), slice);
+                Some(at)
+            }
+            Event::Deleted(slice) => {
+                self.slices.remove(&slice.name_any());
+                Some(now)
+            }
+            Event::Restarted(slices) => {
+                let at = self.listed_change(slices.iter().map(|s| s.annotations()), now);
+                self.slices = slices.into_iter().map(|s| (s.name_any(), s)).collect();
+                at
+            }
+        };
+        self.recount(at)
+    }
+
+    /// Like `apply_slices` for the Endpoints watch
+    pub fn apply_endpoints(&mut self, event: Event<Endpoints>, now: DateTime<Utc>) -> bool {
+        let at = match event {
+            Event::Applied(endpoints) => {
+                let at = last_change(endpoints.annotations()).unwrap_or(now);
+                self.endpoints = Some(endpoints);
+                Some(at)
+            }
+            Event::Deleted(_) => {
+                self.endpoints = None;
+                Some(now)
+            }
+            Event::Restarted(endpoints) => {
+                let at = self.listed_change(endpoints.iter().map(|e| e.annotations()), now);
+                self.endpoints = endpoints.into_iter().next();
+                at
+            }
+        };
+        self.recount(at)
+    }
+
+    // When the backends of a list last changed as far as their objects tell. What the first list
+    // shows may have changed long before the watch started, so without a time of its own that
+    // change is left unknown rather than taken to happen now.
+    fn listed_change<'a>(
+        &self,
+        annotations: impl Iterator<Item = &'a BTreeMap<String, String>>,
+        now: DateTime<Utc>,
+    ) -> Option<DateTime<Utc>> {
+        let listed = annotations.filter_map(last_change).max();
+        match self.listed {
+            true => listed.or(Some(now)),
+            false => listed,
+        }
+    }
+
+    // EndpointSlices are what kube-proxy routes by, Endpoints are only counted on clusters
+    // without them. `at` is when a change of the ready count happened.
+    fn recount(&mut self, at: Option<DateTime<Utc>>) -> bool {
+        let recovered = self.error.take().is_some();
+        let count = if self.slices.is_empty() {
+            self.endpoints
+                .as_ref()
+                .map(endpoints_count)
+                .unwrap_or_default()
+        } else {
+            slices_count(self.slices.values())
+        };
+        if count.ready != self.count.ready {
+            self.changed = at;
+        }
+        let changed = count != self.count || !self.listed;
+        self.count = count;
+        self.listed = true;
+        changed || recovered
+    }
+}
+
+// The time in the last change annotation of an EndpointSlice or Endpoints
+fn last_change(annotations: &BTreeMap<String, String>) -> Option<DateTime<Utc>> {
+    let time = annotations.get(LAST_CHANGE_ANNOTATION)?;
+    DateTime::parse_from_rfc3339(time)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// Counts the backends of a service across its EndpointSlices. A backend in the slices of both
+/// IPv4 and IPv6 is counted once, and one of unknown readiness is ready as for kube-proxy.
+pub fn slices_count<'a>(slices: impl Iterator<Item = &'a EndpointSlice>) -> BackendCount {
+    let mut ready = BTreeSet::new();
+    let mut not_ready = BTreeSet::new();
+    let mut terminating = BTreeSet::new();
+    for slice in slices {
+        for endpoint in &slice.endpoints {
+            let key = match &endpoint.target_ref {
+                Some(target) => target.name.clone().unwrap_or_default(),
+                None => endpoint.addresses.first().cloned().unwrap_or_default(),
+            };
+            let conditions = endpoint.conditions.clone().unwrap_or_default();
+            if conditions.terminating == Some(true) {
+                terminating.insert(key);
+            } else if conditions.ready != Some(false) {
+                ready.insert(key);
+            } else {
+                not_ready.insert(key);
+            }
+        }
+    }
+    BackendCount {
+        ready: ready.len(),
+        not_ready: not_ready.len(),
+        terminating: terminating.len(),
+    }
+}
+
+/// Counts the backends of a service in its Endpoints, which do not tell terminating backends
+/// apart from those not ready
+pub fn endpoints_count(endpoints: &Endpoints) -> BackendCount {
+    let mut ready = BTreeSet::new();
+    let mut not_ready = BTreeSet::new();
+    for subset in endpoints.subsets.iter().flatten() {
+        ready.extend(subset.addresses.iter().flatten().map(|a| a.ip.clone()));
+        not_ready.extend(
+            subset
+                .not_ready_addresses
+                .iter()
+                .flatten()
+                .map(|a| a.ip.clone()),
+        );
+    }
+    BackendCount {
+        ready: ready.len(),
+        not_ready: not_ready.len(),
+        terminating: 0,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use k8s_openapi::chrono::Duration;
+
+    #[test]
+    fn count_service_backends() {
+        let slice = |name: &str, endpoints: serde_json::Value| -> EndpointSlice {
+            serde_json::from_value(serde_json::json!({
+                "metadata": {"name": name, "labels": {SERVICE_NAME_LABEL: "web"}},
+                "addressType": "IPv4",
+                "endpoints": endpoints,
+            }))
+            .unwrap()
+        };
+        let ipv4 = slice(
+            "web-ipv4",
+            serde_json::json!([
+                {"addresses": ["10.0.0.1"], "conditions": {"ready": true},
+                 "targetRef": {"kind": "Pod", "name": "web-a"}},
+                {"addresses": ["10.0.0.2"], "conditions": {"ready": false},
+                 "targetRef": {"kind": "Pod", "name": "web-b"}},
+                {"addresses": ["10.0.0.3"], "conditions": {"ready": false, "terminating": true},
+                 "targetRef": {"kind": "Pod", "name": "web-c"}},
+                {"addresses": ["10.0.0.4"], "conditions": {}},
+            ]),
+        );
+        let ipv6 = slice(
+            "web-ipv6",
+            serde_json::json!([
+                {"addresses": ["fd00::1"], "conditions": {"ready": true},
+                 "targetRef": {"kind": "Pod", "name": "web-a"}},
+            ]),
+        );
+        let count = slices_count([&ipv4, &ipv6].into_iter());
+        assert_eq!(
+            count,
+            BackendCount {
+                ready: 2,
+                not_ready: 1,
+                terminating: 1
+            }
+        );
+
+        let endpoints: Endpoints = serde_json::from_value(serde_json::json!({
+            "metadata": {
+                "name": "web",
+                "annotations": {LAST_CHANGE_ANNOTATION: "2026-10-16T12:00:00Z"},
+            },
+            "subsets": [{
+                "addresses": [{"ip": "10.0.0.1"}, {"ip": "10.0.0.4"}],
+                "notReadyAddresses": [{"ip": "10.0.0.2"}],
+            }],
+        }))
+        .unwrap();
+        assert_eq!(
+            endpoints_count(&endpoints),
+            BackendCount {
+                ready: 2,
+                not_ready: 1,
+                terminating: 0
+            }
+        );
+
+        // slices take over from endpoints once there are any, and the time of the last change
+        // only moves with the ready count, taken from the first list rather than when it was
+        // listed
+        let start = Utc::now();
+        let mut backends = ServiceBackends::default();
+        assert!(backends.apply_endpoints(Event::Restarted(vec![endpoints.clone()]), start));
+        assert_eq!(backends.count().ready, 2);
+        let triggered = "2026-10-16T12:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert_eq!(backends.changed, Some(triggered));
+        let mut unannotated = endpoints;
+        unannotated.metadata.annotations = None;
+        let mut fresh = ServiceBackends::default();
+        assert!(fresh.apply_endpoints(Event::Restarted(vec![unannotated]), start));
+        assert_eq!(fresh.changed, None);
+
+        let start = triggered;
+        let later = start + Duration::seconds(5);
+        assert!(backends.apply_slices(Event::Restarted(vec![ipv4, ipv6.clone()]), later));
+        assert_eq!(backends.count().terminating, 1);
+        assert_eq!(backends.changed, Some(start));
+        let drained = slice("web-ipv4", serde_json::json!([]));
+        backends.apply_slices(Event::Applied(drained), later);
+        backends.apply_slices(Event::Deleted(ipv6), later);
+        assert_eq!(backends.count(), BackendCount::default());
+        assert_eq!(backends.changed, Some(later));
+    }
+}
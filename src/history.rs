@@ -0,0 +1,141 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+};
+
+use anyhow::{anyhow, Result};
+use k8s_openapi::chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::log::debug;
+
+/// One change kubemc made, or failed to make, to one cluster
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Entry {
+    pub time: DateTime<Utc>,
+    /// The command making the change, such as `scale` or `rollout restart`
+    pub command: String,
+    pub cluster: String,
+    /// What was changed, such as `deployment/web` or `node/ip-10-0-3-17`
+    pub resource: String,
+    /// What came of it, such as `scaled 3 -> 5` or why it failed
+    pub outcome: String,
+    pub success: bool,
+}
+
+impl Entry {
+    pub fn new(command: &str, cluster: &str, resource: &str, outcome: &str, success: bool) -> Self {
+        Self {
+            time: Utc::now(),
+            command: command.to_owned(),
+            cluster: cluster.to_owned(),
+            resource: resource.to_owned(),
+            outcome: outcome.to_owned(),
+            success,
+        }
+    }
+}
+
+/// Appends the changes of a command to the history file, one JSON object per line
+pub fn record(entries: &[Entry]) -> Result<()> {
+    if entries.is_empty() {
+        return Ok(());
+    }
+    let path = history_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut lines = String::new();
+    for entry in entries {
+        lines.push_str(&serde_json::to_string(entry)?);
+        lines.push('\n');
+    }
+    // a single write keeps the lines of commands running at once apart
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(lines.as_bytes())?;
+    Ok(())
+}
+
+/// Reads every entry from the history file, skipping lines that cannot be parsed
+pub fn read() -> Result<Vec<Entry>> {
+    let path = history_path()?;
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(contents
+        .lines()
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                debug!("skipping history entry: {}", e);
+                None
+            }
+        })
+        .collect())
+}
+
+/// The entries made since `since` on `cluster`, oldest first
+pub fn select(
+    mut entries: Vec<Entry>,
+    since: Option<DateTime<Utc>>,
+    cluster: Option<&str>,
+) -> Vec<Entry> {
+    entries.retain(|entry| {
+        since.is_none_or(|since| entry.time >= since)
+            && cluster.is_none_or(|cluster| entry.cluster == cluster)
+    });
+    entries.sort_by_key(|entry| entry.time);
+    entries
+}
+
+fn history_path() -> Result<PathBuf> {
+    dirs::home_dir()
+        .map(|h| h.join(".kube").join("kubemc-history.jsonl"))
+        .ok_or_else(|| anyhow!("failed to find home directory"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(time: &str, cluster: &str, outcome: &str) -> Entry {
+        Entry {
+            time: time.parse().unwrap(),
+            command: "scale".into(),
+            cluster: cluster.into(),
+            resource: "deployment/web".into(),
+            outcome: outcome.into(),
+            success: outcome.starts_with("scaled"),
+        }
+    }
+
+    #[test]
+    fn select_history() {
+        let entries = vec![
+            entry("2030-01-02T10:00:00Z", "prod-us", "scaled 3 -> 5"),
+            entry("2030-01-01T10:00:00Z", "prod-eu", "failed: forbidden"),
+            entry("2030-01-01T09:00:00Z", "prod-us", "scaled 2 -> 3"),
+        ];
+        let line = serde_json::to_string(&entries[1]).unwrap();
+        assert_eq!(serde_json::from_str::<Entry>(&line).unwrap(), entries[1]);
+
+        let outcomes = |entries: Vec<Entry>| -> Vec<String> {
+            entries.into_iter().map(|entry| entry.outcome).collect()
+        };
+        assert_eq!(
+            outcomes(select(entries.clone(), None, None)),
+            ["scaled 2 -> 3", "failed: forbidden", "scaled 3 -> 5"]
+        );
+        assert_eq!(
+            outcomes(select(entries.clone(), None, Some("prod-us"))),
+            ["scaled 2 -> 3", "scaled 3 -> 5"]
+        );
+        let since = "2030-01-01T10:00:00Z".parse().unwrap();
+        assert_eq!(
+            outcomes(select(entries, Some(since), Some("prod-us"))),
+            ["scaled 3 -> 5"]
+        );
+    }
+}
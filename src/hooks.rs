@@ -0,0 +1,68 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet},
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use tokio::process::Command;
+use tracing::log::{debug, warn};
+
+/// A pre-command that succeeded within this window is not run again, so a login that does not
+/// fix authentication cannot be triggered over and over
+const PRE_COMMAND_COOLDOWN: Duration = Duration::from_secs(60);
+
+/// Runs each distinct pre-command once. Commands run one at a time with the terminal attached
+/// since logins commonly prompt or open a browser.
+pub async fn run_pre_commands(commands: &[String]) {
+    let mut seen = HashSet::new();
+    for command in commands {
+        if !seen.insert(command) {
+            continue;
+        }
+        let marker = marker_path(command);
+        if marker.as_ref().is_some_and(|path| ran_recently(path)) {
+            debug!("skipping pre-command {}, it ran recently", command);
+            continue;
+        }
+        eprintln!("running pre-command: {}", command);
+        match Command::new("sh").arg("-c").arg(command).status().await {
+            Ok(status) if status.success() => {
+                if let Some(path) = marker {
+                    if let Err(e) = write_marker(&path) {
+                        debug!("failed to record pre-command run: {}", e);
+                    }
+                }
+            }
+            Ok(status) => warn!("pre-command {} exited with {}", command, status),
+            Err(e) => warn!("failed to run pre-command {}: {}", command, e),
+        }
+    }
+}
+
+fn marker_path(command: &str) -> Option<PathBuf> {
+    let mut hasher = DefaultHasher::new();
+    command.hash(&mut hasher);
+    dirs::home_dir().map(|h| {
+        h.join(".kube")
+            .join("cache")
+            .join("kubemc")
+            .join("hooks")
+            .join(format!("{:016x}", hasher.finish()))
+    })
+}
+
+fn ran_recently(path: &Path) -> bool {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age < PRE_COMMAND_COOLDOWN)
+}
+
+fn write_marker(path: &Path) -> std::io::Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(path, "")
+}
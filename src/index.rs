@@ -0,0 +1,477 @@
+use std::{
+    fmt::Debug,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use futures::{future::BoxFuture, FutureExt};
+use k8s_openapi::{
+    api::{
+        apps::v1::{DaemonSet, Deployment, StatefulSet},
+        batch::v1::{CronJob, Job},
+        core::v1::{ConfigMap, Namespace, Node, Pod, Secret, Service},
+        networking::v1::Ingress,
+    },
+    chrono::{DateTime, Utc},
+    NamespaceResourceScope,
+};
+use kube::{api::ListParams, Api, Client as KubeClient, Resource, ResourceExt};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::log::debug;
+
+use crate::{client::connect_all, config::Cluster};
+
+/// Objects requested per page while indexing
+const PAGE_SIZE: u32 = 500;
+
+/// How long names listed for completion are reused before asking the cluster again
+pub const COMPLETION_TTL: Duration = Duration::from_secs(60);
+
+/// How long completion waits for a cluster before falling back to older names
+const COMPLETION_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Lists the names of a kind, in one namespace or across all of them
+type Lister = fn(KubeClient, Option<String>) -> BoxFuture<'static, Result<Vec<IndexEntry>>>;
+
+/// A kind kept in the index, with the names it can be referred to by
+pub struct IndexedKind {
+    pub kind: &'static str,
+    /// API group, empty for the core group
+    pub group: &'static str,
+    pub plural: &'static str,
+    pub short: Option<&'static str>,
+    pub namespaced: bool,
+    list: Lister,
+}
+
+/// Kinds listed when building the index
+pub const INDEXED_KINDS: &[IndexedKind] = &[
+    IndexedKind {
+        kind: "Pod",
+        group: "",
+        plural: "pods",
+        short: Some("po"),
+        namespaced: true,
+        list: namespaced_names::<Pod>,
+    },
+    IndexedKind {
+        kind: "Deployment",
+        group: "apps",
+        plural: "deployments",
+        short: Some("deploy"),
+        namespaced: true,
+        list: namespaced_names::<Deployment>,
+    },
+    IndexedKind {
+        kind: "StatefulSet",
+        group: "apps",
+        plural: "statefulsets",
+        short: Some("sts"),
+        namespaced: true,
+        list: namespaced_names::<StatefulSet>,
+    },
+    IndexedKind {
+        kind: "DaemonSet",
+        group: "apps",
+        plural: "daemonsets",
+        short: Some("ds"),
+        namespaced: true,
+        list: namespaced_names::<DaemonSet>,
+    },
+    IndexedKind {
+        kind: "Job",
+        group: "batch",
+        plural: "jobs",
+        short: None,
+        namespaced: true,
+        list: namespaced_names::<Job>,
+    },
+    IndexedKind {
+        kind: "CronJob",
+        group: "batch",
+        plural: "cronjobs",
+        short: Some("cj"),
+        namespaced: true,
+        list: namespaced_names::<CronJob>,
+    },
+    IndexedKind {
+        kind: "Service",
+        group: "",
+        plural: "services",
+        short: Some("svc"),
+        namespaced: true,
+        list: namespaced_names::<Service>,
+    },
+    IndexedKind {
+        kind: "ConfigMap",
+        group: "",
+        plural: "configmaps",
+        short: Some("cm"),
+        namespaced: true,
+        list: namespaced_names::<ConfigMap>,
+    },
+    IndexedKind {
+        kind: "Secret",
+        group: "",
+        plural: "secrets",
+        short: None,
+        namespaced: true,
+        list: namespaced_names::<Secret>,
+    },
+    IndexedKind {
+        kind: "Ingress",
+        group: "networking.k8s.io",
+        plural: "ingresses",
+        short: Some("ing"),
+        namespaced: true,
+        list: namespaced_names::<Ingress>,
+    },
+    IndexedKind {
+        kind: "Namespace",
+        group: "",
+        plural: "namespaces",
+        short: Some("ns"),
+        namespaced: false,
+        list: names::<Namespace>,
+    },
+    IndexedKind {
+        kind: "Node",
+        group: "",
+        plural: "nodes",
+        short: Some("no"),
+        namespaced: false,
+        list: names::<Node>,
+    },
+];
+
+/// Finds an indexed kind by kind, plural or short name, ignoring case, optionally qualified
+/// with its API group as in `cronjobs.batch`. This is the one table of names commands that only
+/// act on certain kinds check their resource argument against.
+pub fn indexed_kind(resource: &str) -> Option<&'static IndexedKind> {
+    let resource = resource.to_lowercase();
+    INDEXED_KINDS.iter().find(|indexed| {
+        let name = match resource.split_once('.') {
+            Some((name, group)) if group == indexed.group => name,
+            Some(_) => return false,
+            None => resource.as_str(),
+        };
+        indexed.kind.to_lowercase() == name || indexed.plural == name || indexed.short == Some(name)
+    })
+}
+
+/// Whether `resource` names `kind`, such as `deploy` or `deployments.apps` naming `Deployment`
+pub fn names_kind(resource: &str, kind: &str) -> bool {
+    indexed_kind(resource).is_some_and(|indexed| indexed.kind == kind)
+}
+
+/// An object known to exist on a cluster when the index was built
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct IndexEntry {
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
+    pub name: String,
+}
+
+/// Names of the objects of one cluster, built from metadata-only lists so that searches and
+/// shell completion do not need to reach the cluster
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct ClusterIndex {
+    pub built: DateTime<Utc>,
+    pub objects: Vec<IndexEntry>,
+    /// Kinds that could not be listed, such as secrets without permission to list them
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unlisted: Vec<String>,
+}
+
+impl ClusterIndex {
+    /// Lists every indexed kind across all namespaces of the cluster
+    pub async fn build(client: KubeClient) -> Result<Self> {
+        let listings = futures::future::join_all(
+            INDEXED_KINDS
+                .iter()
+                .map(|indexed| (indexed.list)(client.clone(), None)),
+        )
+        .await;
+        let mut objects = Vec::new();
+        let mut unlisted = Vec::new();
+        for (indexed, listing) in INDEXED_KINDS.iter().zip(listings) {
+            match listing {
+                Ok(entries) => objects.extend(entries),
+                Err(e) => {
+                    debug!("failed to index {}: {}", indexed.plural, e);
+                    unlisted.push(indexed.plural.to_owned());
+                }
+            }
+        }
+        if objects.is_empty() && !unlisted.is_empty() {
+            return Err(anyhow!("failed to list any of {}", unlisted.join(", ")));
+        }
+        Ok(Self {
+            built: Utc::now(),
+            objects,
+            unlisted,
+        })
+    }
+
+    /// Loads the index of a cluster, or None when it has never been built
+    pub fn load(cluster: &str) -> Result<Option<Self>> {
+        let path = index_path(cluster)?;
+        let data = match fs::read_to_string(&path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e).with_context(|| format!("failed to read {}", path.display())),
+        };
+        serde_json::from_str(&data)
+            .map(Some)
+            .with_context(|| format!("failed to parse index {}", path.display()))
+    }
+
+    pub fn save(&self, cluster: &str) -> Result<()> {
+        let path = index_path(cluster)?;
+        save_json(&path, self)
+    }
+
+    /// Objects whose name contains `pattern`, optionally only of one kind
+    pub fn find<'a>(
+        &'a self,
+        pattern: &'a str,
+        kind: Option<&'a str>,
+    ) -> impl Iterator<Item = &'a IndexEntry> {
+        self.objects.iter().filter(move |entry| {
+            entry.name.contains(pattern) && kind.is_none_or(|kind| entry.kind == kind)
+        })
+    }
+
+    /// Names of a kind starting with `prefix`, in `namespace` for namespaced kinds
+    pub fn names<'a>(
+        &'a self,
+        kind: &'a str,
+        namespace: &'a str,
+        prefix: &'a str,
+    ) -> impl Iterator<Item = &'a str> {
+        self.objects
+            .iter()
+            .filter(move |entry| {
+                entry.kind == kind
+                    && entry.namespace.as_deref().is_none_or(|ns| ns == namespace)
+                    && entry.name.starts_with(prefix)
+            })
+            .map(|entry| entry.name.as_str())
+    }
+}
+
+/// Names of a kind listed live from one cluster, kept for completion
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct CompletionCache {
+    listed: DateTime<Utc>,
+    names: Vec<String>,
+}
+
+/// Names of a kind on one cluster for shell completion, in the cluster's own namespace or else
+/// `namespace` for namespaced kinds.
+/// Names listed within the last minute are reused, otherwise the cluster is asked. A cluster
+/// that does not answer within two seconds gets the last listed or indexed names instead.
+pub async fn completion_names(
+    cluster: &Cluster,
+    indexed: &IndexedKind,
+    namespace: &str,
+) -> Vec<String> {
+    let namespace = cluster.namespace_or(namespace);
+    let namespace = indexed.namespaced.then_some(namespace.as_str());
+    let path = completion_path(&cluster.name, indexed.plural, namespace);
+    let cached: Option<CompletionCache> = path
+        .as_ref()
+        .ok()
+        .and_then(|path| fs::read_to_string(path).ok())
+        .and_then(|data| serde_json::from_str(&data).ok());
+    let age = |cache: &CompletionCache| (Utc::now() - cache.listed).to_std().unwrap_or_default();
+    if let Some(cache) = cached.as_ref().filter(|cache| age(cache) < COMPLETION_TTL) {
+        return cache.names.clone();
+    }
+
+    let live = tokio::time::timeout(COMPLETION_TIMEOUT, async {
+        let (_, client) = connect_all(std::slice::from_ref(cluster))
+            .await?
+            .pop()
+            .ok_or_else(|| anyhow!("no client for cluster {}", cluster.name))?;
+        (indexed.list)(client?, namespace.map(str::to_owned)).await
+    })
+    .await;
+    match live {
+        Ok(Ok(entries)) => {
+            let cache = CompletionCache {
+                listed: Utc::now(),
+                names: entries.into_iter().map(|entry| entry.name).collect(),
+            };
+            if let Ok(path) = &path {
+                if let Err(e) = save_json(path, &cache) {
+                    debug!("failed to cache completion names: {}", e);
+                }
+            }
+            return cache.names;
+        }
+        Ok(Err(e)) => debug!(
+            "failed to list {} on {}: {}",
+            indexed.plural, cluster.name, e
+        ),
+        Err(_) => debug!("listing {} on {} timed out", indexed.plural, cluster.name),
+    }
+    if let Some(cache) = cached {
+        return cache.names;
+    }
+    match ClusterIndex::load(&cluster.name) {
+        Ok(Some(index)) => index
+            .names(indexed.kind, namespace.unwrap_or_default(), "")
+            .map(str::to_owned)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Lists the names of a cluster scoped kind
+fn names<K>(client: KubeClient, _: Option<String>) -> BoxFuture<'static, Result<Vec<IndexEntry>>>
+where
+    K: Resource<DynamicType = ()> + Clone + DeserializeOwned + Debug + Send + 'static,
+{
+    list_names(Api::<K>::all(client)).boxed()
+}
+
+// Lists the names of a namespaced kind, in one namespace or across all of them
+fn namespaced_names<K>(
+    client: KubeClient,
+    namespace: Option<String>,
+) -> BoxFuture<'static, Result<Vec<IndexEntry>>>
+where
+    K: Resource<DynamicType = (), Scope = NamespaceResourceScope>
+        + Clone
+        + DeserializeOwned
+        + Debug
+        + Send
+        + 'static,
+{
+    let api: Api<K> = match namespace {
+        Some(namespace) => Api::namespaced(client, &namespace),
+        None => Api::all(client),
+    };
+    list_names(api).boxed()
+}
+
+// Lists the names of every object of an api page by page
+async fn list_names<K>(api: Api<K>) -> Result<Vec<IndexEntry>>
+where
+    K: Resource<DynamicType = ()> + Clone + DeserializeOwned + Debug,
+{
+    let kind = K::kind(&()).to_string();
+    let mut params = ListParams::default().limit(PAGE_SIZE);
+    let mut entries = Vec::new();
+    loop {
+        let page = api.list_metadata(&params).await?;
+        entries.extend(page.items.iter().map(|object| IndexEntry {
+            kind: kind.clone(),
+            namespace: object.namespace(),
+            name: object.name_any(),
+        }));
+        match page.metadata.continue_ {
+            Some(token) if !token.is_empty() => params = params.continue_token(&token),
+            _ => return Ok(entries),
+        }
+    }
+}
+
+// Writes next to the target and renames, so a reader never sees a partial file
+fn save_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let tmp = path.with_extension("tmp");
+    fs::write(&tmp, serde_json::to_string(value)?)?;
+    fs::rename(&tmp, path).with_context(|| format!("failed to write {}", path.display()))
+}
+
+// `~/.kube/cache/kubemc/index/<cluster>.json`
+fn index_path(cluster: &str) -> Result<PathBuf> {
+    Ok(cache_dir()?
+        .join("index")
+        .join(format!("{}.json", file_name(cluster))))
+}
+
+// `~/.kube/cache/kubemc/completion/<cluster>/<plural>[.<namespace>].json`
+fn completion_path(cluster: &str, plural: &str, namespace: Option<&str>) -> Result<PathBuf> {
+    let file = match namespace {
+        Some(namespace) => format!("{}.{}.json", plural, namespace),
+        None => format!("{}.json", plural),
+    };
+    Ok(cache_dir()?
+        .join("completion")
+        .join(file_name(cluster))
+        .join(file))
+}
+
+fn cache_dir() -> Result<PathBuf> {
+    dirs::home_dir()
+        .map(|h| h.join(".kube").join("cache").join("kubemc"))
+        .ok_or_else(|| anyhow!("failed to find home directory"))
+}
+
+/// Cluster names may hold characters that are not safe in file names
+pub fn file_name(cluster: &str) -> String {
+    cluster
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn entry(kind: &str, namespace: Option<&str>, name: &str) -> IndexEntry {
+        IndexEntry {
+            kind: kind.into(),
+            namespace: namespace.map(str::to_owned),
+            name: name.into(),
+        }
+    }
+
+    #[test]
+    fn search_index() {
+        let index = ClusterIndex {
+            built: Utc::now(),
+            objects: vec![
+                entry("Pod", Some("default"), "web-abc-1"),
+                entry("Deployment", Some("default"), "web"),
+                entry("Pod", Some("other"), "web-xyz-1"),
+                entry("Node", None, "worker-1"),
+            ],
+            unlisted: Vec::new(),
+        };
+        assert_eq!(index.find("web", None).count(), 3);
+        assert_eq!(index.find("web", Some("Deployment")).count(), 1);
+
+        let names: Vec<&str> = index.names("Pod", "default", "web").collect();
+        assert_eq!(names, vec!["web-abc-1"]);
+        let names: Vec<&str> = index.names("Node", "default", "").collect();
+        assert_eq!(names, vec!["worker-1"]);
+
+        assert_eq!(indexed_kind("po").map(|k| k.kind), Some("Pod"));
+        assert_eq!(
+            indexed_kind("Deployment").map(|k| k.kind),
+            Some("Deployment")
+        );
+        assert_eq!(indexed_kind("ingresses").map(|k| k.kind), Some("Ingress"));
+        assert!(indexed_kind("widgets").is_none());
+        assert_eq!(
+            indexed_kind("cronjobs.batch").map(|k| k.kind),
+            Some("CronJob")
+        );
+        assert!(indexed_kind("cronjobs.example.com").is_none());
+        assert!(names_kind("svc", "Service"));
+        assert!(!names_kind("deployment", "Pod"));
+    }
+}
@@ -0,0 +1,135 @@
+use anyhow::{anyhow, Result};
+use k8s_openapi::{
+    api::batch::v1::{CronJob, Job},
+    apimachinery::pkg::apis::meta::v1::ObjectMeta,
+    chrono::{DateTime, Utc},
+};
+use kube::{Resource, ResourceExt};
+
+use crate::rollout::RolloutState;
+
+/// Annotation kubectl marks jobs created by hand from a cronjob with
+pub const INSTANTIATE_ANNOTATION: &str = "cronjob.kubernetes.io/instantiate";
+
+// Job names end up in pod labels, which hold 63 characters
+const MAX_JOB_NAME: usize = 63;
+
+/// Name of a job run by hand, `<cronjob>-manual-<unix time>`, with the cronjob name shortened
+/// to keep it a valid label value
+pub fn manual_job_name(cronjob: &str, now: DateTime<Utc>) -> String {
+    let suffix = format!("-manual-{}", now.timestamp());
+    let base: String = cronjob.chars().take(MAX_JOB_NAME - suffix.len()).collect();
+    format!("{}{}", base.trim_end_matches('-'), suffix)
+}
+
+/// Builds the job the cronjob runs on schedule, owned by the cronjob and marked as created by
+/// hand, the way `kubectl create job --from=cronjob/NAME` does
+pub fn job_from_cronjob(cronjob: &CronJob, name: &str) -> Result<Job> {
+    let spec = cronjob
+        .spec
+        .as_ref()
+        .ok_or_else(|| anyhow!("cronjob {} has no spec", cronjob.name_any()))?;
+    let template = spec.job_template.metadata.clone().unwrap_or_default();
+    let mut annotations = template.annotations.unwrap_or_default();
+    annotations.insert(INSTANTIATE_ANNOTATION.into(), "manual".into());
+    Ok(Job {
+        metadata: ObjectMeta {
+            name: Some(name.to_owned()),
+            namespace: cronjob.namespace(),
+            labels: template.labels,
+            annotations: Some(annotations),
+            owner_references: cronjob.controller_owner_ref(&()).map(|owner| vec![owner]),
+            ..Default::default()
+        },
+        spec: spec.job_template.spec.clone(),
+        ..Default::default()
+    })
+}
+
+/// Where a job stands, complete or failed once it has the matching condition
+pub fn job_state(job: &Job) -> RolloutState {
+    let status = job.status.clone().unwrap_or_default();
+    for condition in status.conditions.iter().flatten() {
+        if condition.status != "True" {
+            continue;
+        }
+        match condition.type_.as_str() {
+            "Complete" => return RolloutState::Complete("complete".into()),
+            "Failed" => {
+                let reason = condition.reason.as_deref().unwrap_or("unknown reason");
+                return RolloutState::Failed(format!("failed: {}", reason));
+            }
+            _ => {}
+        }
+    }
+    RolloutState::Progressing(format!(
+        "{} active, {} succeeded, {} failed",
+        status.active.unwrap_or_default(),
+        status.succeeded.unwrap_or_default(),
+        status.failed.unwrap_or_default()
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn trigger_cronjob() {
+        let now: DateTime<Utc> = "2030-01-31T00:00:00Z".parse().unwrap();
+        assert_eq!(
+            manual_job_name("backup", now),
+            format!("backup-manual-{}", now.timestamp())
+        );
+        let long = manual_job_name(&"nightly-".repeat(10), now);
+        assert!(long.len() <= MAX_JOB_NAME);
+        assert!(long.starts_with("nightly-nightly"));
+        assert!(!long.contains("--"));
+
+        let cronjob: CronJob = serde_json::from_value(json!({
+            "metadata": {"name": "backup", "namespace": "shop", "uid": "1234"},
+            "spec": {
+                "schedule": "0 3 * * *",
+                "jobTemplate": {
+                    "metadata": {"labels": {"app": "backup"}},
+                    "spec": {"backoffLimit": 2, "template": {"spec": {"containers": []}}},
+                },
+            },
+        }))
+        .unwrap();
+        let job = job_from_cronjob(&cronjob, "backup-manual-1").unwrap();
+        assert_eq!(job.metadata.namespace.as_deref(), Some("shop"));
+        assert_eq!(job.labels()["app"], "backup");
+        assert_eq!(job.annotations()[INSTANTIATE_ANNOTATION], "manual");
+        let owner = &job.owner_references()[0];
+        assert_eq!(
+            (owner.kind.as_str(), owner.uid.as_str()),
+            ("CronJob", "1234")
+        );
+        assert_eq!(job.spec.unwrap().backoff_limit, Some(2));
+
+        let job = |status: serde_json::Value| -> Job {
+            serde_json::from_value(json!({"metadata": {"name": "backup"}, "status": status}))
+                .unwrap()
+        };
+        assert_eq!(
+            job_state(&job(json!({"active": 1}))),
+            RolloutState::Progressing("1 active, 0 succeeded, 0 failed".into())
+        );
+        let complete = job(json!({"succeeded": 1, "conditions": [
+            {"type": "Complete", "status": "True"},
+        ]}));
+        assert_eq!(
+            job_state(&complete),
+            RolloutState::Complete("complete".into())
+        );
+        let failed = job(json!({"failed": 3, "conditions": [
+            {"type": "Failed", "status": "True", "reason": "BackoffLimitExceeded"},
+        ]}));
+        assert_eq!(
+            job_state(&failed),
+            RolloutState::Failed("failed: BackoffLimitExceeded".into())
+        );
+    }
+}
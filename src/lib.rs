@@ -1,5 +1,50 @@
+pub mod apply;
+pub mod args;
+pub mod audit;
+pub mod auth;
+pub mod cache;
 pub mod client;
 pub mod commands;
+pub mod completion;
 pub mod config;
+pub mod copy;
+pub mod describe;
+pub mod diff;
 pub mod discovery;
+pub mod drain;
+#[cfg(feature = "dev")]
+pub mod e2e;
+pub mod edit;
+pub mod errors;
+pub mod failover;
+pub mod history;
+pub mod hooks;
+pub mod index;
+pub mod job;
+pub mod logs;
+pub mod manifest;
+pub mod metadata;
+pub mod otlp;
 pub mod output;
+pub mod ownership;
+pub mod paging;
+pub mod plugin;
+pub mod portforward;
+pub mod printer;
+pub mod profile;
+pub mod redact;
+pub mod reload;
+pub mod report;
+pub mod responses;
+pub mod retry;
+pub mod rollout;
+pub mod serial;
+pub mod session;
+pub mod stats;
+pub mod table;
+pub mod telemetry;
+pub mod verify;
+pub mod wait;
+pub mod watch;
+pub mod wizard;
+pub mod writer;
@@ -0,0 +1,214 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    time::Instant,
+};
+
+use anyhow::Result;
+use futures::{stream::BoxStream, AsyncBufReadExt, StreamExt};
+use k8s_openapi::api::core::v1::Pod;
+use kube::{
+    api::{ListParams, LogParams},
+    Api, Client as KubeClient, ResourceExt,
+};
+use tracing::log::warn;
+
+/// A container whose log is shown, written as `cluster/pod/container` in front of its lines
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct LogSource {
+    pub clustername: String,
+    pub pod: String,
+    pub container: String,
+}
+
+impl Display for LogSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}/{}", self.clustername, self.pod, self.container)
+    }
+}
+
+/// A line of a container log
+pub struct LogLine {
+    pub source: LogSource,
+    pub line: String,
+}
+
+/// The pods logs are shown for on each cluster
+#[derive(Clone, Debug)]
+pub enum PodSelector {
+    Name(String),
+    Labels(String),
+}
+
+/// Opens the logs of the selected pods on one cluster, one stream per container. Only
+/// `container` is read when given, pods without it are skipped.
+pub async fn open_logs(
+    clustername: &str,
+    client: KubeClient,
+    ns: &str,
+    selector: &PodSelector,
+    container: Option<&str>,
+    params: &LogParams,
+) -> Result<Vec<BoxStream<'static, LogLine>>> {
+    let api: Api<Pod> = Api::namespaced(client, ns);
+    let pods = match selector {
+        PodSelector::Name(name) => api.get_opt(name).await?.into_iter().collect(),
+        PodSelector::Labels(labels) => api.list(&ListParams::default().labels(labels)).await?.items,
+    };
+    let mut streams = Vec::new();
+    for pod in pods {
+        let containers = pod
+            .spec
+            .iter()
+            .flat_map(|spec| &spec.containers)
+            .map(|c| c.name.clone())
+            .filter(|name| container.is_none_or(|container| container == name));
+        for name in containers {
+            let source = LogSource {
+                clustername: clustername.to_owned(),
+                pod: pod.name_any(),
+                container: name.clone(),
+            };
+            let params = LogParams {
+                container: Some(name),
+                ..params.clone()
+            };
+            let lines = match api.log_stream(&source.pod, &params).await {
+                Ok(log) => log.lines(),
+                Err(e) => {
+                    warn!("failed to read the log of {}: {}", source, e);
+                    continue;
+                }
+            };
+            let stream = lines
+                .take_while(move |line| {
+                    futures::future::ready(match line {
+                        Ok(_) => true,
+                        Err(e) => {
+                            warn!("log stream ended: {}", e);
+                            false
+                        }
+                    })
+                })
+                .filter_map(|line| futures::future::ready(line.ok()))
+                .map(move |line| LogLine {
+                    source: source.clone(),
+                    line,
+                })
+                .boxed();
+            streams.push(stream);
+        }
+    }
+    Ok(streams)
+}
+
+/// Token bucket letting through `rate` lines per second on average, with bursts of up to
+/// `rate` lines
+#[derive(Clone, Debug)]
+pub struct RateLimiter {
+    rate: f64,
+    tokens: f64,
+    last: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(rate: u32, now: Instant) -> Self {
+        Self {
+            rate: rate as f64,
+            tokens: rate as f64,
+            last: now,
+        }
+    }
+
+    /// Whether a line arriving at `now` fits in the rate
+    pub fn allow(&mut self, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.last).as_secs_f64();
+        self.last = now.max(self.last);
+        self.tokens = (self.tokens + elapsed * self.rate).min(self.rate);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// Interleaves the lines of many containers, rate limiting each container on its own so one
+/// noisy container cannot drown out the rest. Dropped lines are counted per cluster.
+#[derive(Debug, Default)]
+pub struct LogMultiplexer {
+    rate: Option<u32>,
+    limiters: HashMap<LogSource, RateLimiter>,
+    dropped: BTreeMap<String, u64>,
+}
+
+impl LogMultiplexer {
+    /// Limits every source to `rate` lines per second, or nothing when None
+    pub fn new(rate: Option<u32>) -> Self {
+        Self {
+            rate,
+            ..Default::default()
+        }
+    }
+
+    /// Whether a line of `source` arriving at `now` is shown, counting it as dropped if not
+    pub fn admit(&mut self, source: &LogSource, now: Instant) -> bool {
+        let Some(rate) = self.rate else {
+            return true;
+        };
+        let allowed = self
+            .limiters
+            .entry(source.clone())
+            .or_insert_with(|| RateLimiter::new(rate, now))
+            .allow(now);
+        if !allowed {
+            *self.dropped.entry(source.clustername.clone()).or_default() += 1;
+        }
+        allowed
+    }
+
+    /// Lines dropped so far per cluster, only clusters that had any
+    pub fn dropped(&self) -> &BTreeMap<String, u64> {
+        &self.dropped
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn rate_limit_noisy_sources() {
+        let source = |clustername: &str, pod: &str| LogSource {
+            clustername: clustername.into(),
+            pod: pod.into(),
+            container: "app".into(),
+        };
+        let noisy = source("prod-us", "web-1");
+        let quiet = source("prod-eu", "web-1");
+        assert_eq!(noisy.to_string(), "prod-us/web-1/app");
+
+        let start = Instant::now();
+        let mut mux = LogMultiplexer::new(Some(10));
+        // a burst of 100 lines only lets the first 10 through
+        let shown = (0..100).filter(|_| mux.admit(&noisy, start)).count();
+        assert_eq!(shown, 10);
+        // the quiet source has its own budget
+        assert!(mux.admit(&quiet, start));
+        // after half a second half of the budget is back
+        let later = start + Duration::from_millis(500);
+        let shown = (0..10).filter(|_| mux.admit(&noisy, later)).count();
+        assert_eq!(shown, 5);
+        assert_eq!(
+            mux.dropped().clone(),
+            BTreeMap::from([("prod-us".to_string(), 95)])
+        );
+
+        let mut unlimited = LogMultiplexer::new(None);
+        assert!((0..1000).all(|_| unlimited.admit(&noisy, start)));
+        assert!(unlimited.dropped().is_empty());
+    }
+}
@@ -1,22 +1,171 @@
+use std::{process::ExitCode, time::Instant};
+
 use anyhow::Result;
-use clap::Parser;
-use kubemc::commands::Cli;
-use kubemc::client::ListResponse;
+use clap::{CommandFactory, FromArgMatches};
+use kubemc::{
+    cache::CacheDirs,
+    commands::{Cli, ConfigAction, StatsAction},
+    metadata::MetadataField,
+    otlp::{self, SpanCollector, COMMAND_SPAN},
+    output::OutputFormat,
+    profile, redact, retry, serial, telemetry,
+};
+use tracing::{info_span, log::debug, Instrument};
+use tracing_subscriber::{
+    filter::{filter_fn, LevelFilter},
+    layer::SubscriberExt,
+    util::SubscriberInitExt,
+    Layer,
+};
 
 pub struct TestStruct {
     pub name: String,
 }
 
 #[tokio::main]
-async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
-    let cli = Cli::parse();
+async fn main() -> Result<ExitCode> {
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches)?;
+    // spans are only collected when there is a collector to send them to
+    let otlp_endpoint = cli.otlp_endpoint();
+    let collector = otlp_endpoint
+        .is_some()
+        .then(|| SpanCollector.with_filter(filter_fn(|metadata| metadata.is_span())));
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_filter(LevelFilter::INFO))
+        .with(collector)
+        .init();
+    cli.apply_output_settings(&matches);
+    if cli.serial {
+        serial::enable();
+    }
+    if cli.show_secret_values {
+        redact::show_secret_values();
+    }
+    kubemc::output::set_color(
+        cli.color_enabled() && matches!(cli.output, OutputFormat::Table | OutputFormat::Wide),
+    );
+
+    let start = Instant::now();
+    let command = matches.subcommand_name().unwrap_or_default();
+    let span = info_span!(
+        COMMAND_SPAN,
+        otel.name = %format!("kubemc {}", command),
+        command,
+        success = tracing::field::Empty
+    );
+    let result = run(&cli).instrument(span.clone()).await;
+    span.record(
+        "success",
+        matches!(result, Ok(code) if code == ExitCode::SUCCESS),
+    );
+    drop(span);
+    if let Some(endpoint) = &otlp_endpoint {
+        if let Err(e) = otlp::export(endpoint).await {
+            debug!("failed to export traces: {}", e);
+        }
+    }
+    match CacheDirs::home().and_then(|dirs| dirs.auto_prune()) {
+        Ok(()) => {}
+        Err(e) => debug!("failed to prune caches: {}", e),
+    }
+    if cli.verbose {
+        for (clustername, retries) in retry::retry_counts() {
+            eprintln!("{}: {} retries", clustername, retries);
+        }
+    }
+    if cli.profile && !profile::timings().is_empty() {
+        eprintln!("{}", profile::report());
+    }
+    if cli.telemetry_enabled() {
+        let success = matches!(result, Ok(code) if code == ExitCode::SUCCESS);
+        let record = telemetry::Record::new(command, start.elapsed(), success);
+        if let Err(e) = telemetry::append(&record) {
+            debug!("failed to record telemetry: {}", e);
+        }
+    }
+    result
+}
 
+async fn run(cli: &Cli) -> Result<ExitCode> {
+    cli.check_report()?;
     match &cli.action {
-        kubemc::commands::Action::Get { resource, name } => cli.get(resource, name).await?,
-        kubemc::commands::Action::GenerateConfig => cli.generate_config().await?,
-        kubemc::commands::Action::Namespace { namespace } => cli.namespace(namespace).await?,
+        kubemc::commands::Action::Get(args) => return cli.get(args).await,
+        kubemc::commands::Action::Describe(args) => return cli.describe(args).await,
+        kubemc::commands::Action::Apply(args) => return cli.apply(args).await,
+        kubemc::commands::Action::Scale(args) => return cli.scale(args).await,
+        kubemc::commands::Action::Label(args) => {
+            return cli.change_metadata(MetadataField::Labels, args).await
+        }
+        kubemc::commands::Action::Annotate(args) => {
+            return cli.change_metadata(MetadataField::Annotations, args).await
+        }
+        kubemc::commands::Action::Cordon(args) => return cli.cordon(args, true).await,
+        kubemc::commands::Action::Uncordon(args) => return cli.cordon(args, false).await,
+        kubemc::commands::Action::Drain(args) => return cli.drain(args).await,
+        kubemc::commands::Action::Edit(args) => return cli.edit(args).await,
+        kubemc::commands::Action::Yaml(args) => return cli.yaml(args).await,
+        kubemc::commands::Action::Audit(action) => return cli.audit(action).await,
+        kubemc::commands::Action::Stats { action: None } => return cli.stats().await,
+        kubemc::commands::Action::Stats {
+            action: Some(StatsAction::Telemetry { days }),
+        } => cli.telemetry_stats(*days)?,
+        kubemc::commands::Action::History { since, cluster } => {
+            cli.history(*since, cluster.as_deref())?
+        }
+        kubemc::commands::Action::Rollout(action) => return cli.rollout(action).await,
+        kubemc::commands::Action::Create(action) => return cli.create(action).await,
+        kubemc::commands::Action::Wait(args) => return cli.wait(args).await,
+        kubemc::commands::Action::PortForward(args) => cli.port_forward(args).await?,
+        kubemc::commands::Action::Logs(args) => return cli.logs(args).await,
+        kubemc::commands::Action::Exec(args) => return cli.exec(args).await,
+        kubemc::commands::Action::Attach(args) => return cli.attach(args).await,
+        kubemc::commands::Action::Cp(args) => cli.cp(args).await?,
+        kubemc::commands::Action::ApiResources { refresh, missing } => {
+            cli.api_resources(*refresh, *missing).await?
+        }
+        kubemc::commands::Action::Cache(action) => cli.cache(action)?,
+        kubemc::commands::Action::Leaders => return cli.leaders().await,
+        kubemc::commands::Action::ApiServices { unavailable } => {
+            return cli.api_services(*unavailable).await
+        }
+        kubemc::commands::Action::FailoverWatch { service } => {
+            return cli.failover_watch(service).await
+        }
+        kubemc::commands::Action::VerifyCluster { cluster, filename } => {
+            return cli.verify_cluster(cluster, filename).await
+        }
+        kubemc::commands::Action::Index(action) => return cli.index(action).await,
+        kubemc::commands::Action::Find { pattern, kind } => {
+            return cli.find(pattern, kind.as_deref()).await
+        }
+        kubemc::commands::Action::Completion { shell } => cli.completion(*shell).await?,
+        kubemc::commands::Action::GenerateConfig { dry_run, template } => {
+            cli.generate_config(*dry_run, *template).await?
+        }
+        kubemc::commands::Action::Config(ConfigAction::Validate) => {
+            return cli.validate_config().await
+        }
+        kubemc::commands::Action::Config(ConfigAction::Migrate) => cli.migrate_config().await?,
+        kubemc::commands::Action::Config(ConfigAction::AddCluster(args)) => {
+            return cli.add_cluster(args).await
+        }
+        kubemc::commands::Action::Cluster(action) => cli.cluster(action).await?,
+        kubemc::commands::Action::Pin { cluster, clear } => {
+            cli.pin(cluster.as_deref(), *clear).await?
+        }
+        kubemc::commands::Action::Namespace {
+            namespace,
+            interactive,
+            create_missing,
+            yes,
+        } => {
+            cli.namespace(namespace.as_deref(), *interactive, *create_missing, *yes)
+                .await?
+        }
+        #[cfg(feature = "dev")]
+        kubemc::commands::Action::Dev(action) => return cli.dev(action).await,
     }
 
-    Ok(())
+    Ok(ExitCode::SUCCESS)
 }
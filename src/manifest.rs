@@ -0,0 +1,159 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use kube::{core::DynamicObject, ResourceExt};
+use serde::Deserialize;
+
+use crate::diff::{diff_desired, Change};
+
+/// Annotation `kubectl apply` records the applied configuration of an object in
+pub const LAST_APPLIED_ANNOTATION: &str = "kubectl.kubernetes.io/last-applied-configuration";
+
+/// Reads every object of a multi-document yaml manifest, skipping empty documents
+pub fn read_manifest(path: &Path) -> Result<Vec<DynamicObject>> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("failed to read manifest {}", path.display()))?;
+    parse_manifest(&contents).with_context(|| format!("invalid manifest {}", path.display()))
+}
+
+fn parse_manifest(contents: &str) -> Result<Vec<DynamicObject>> {
+    let mut objects = Vec::new();
+    for (i, document) in serde_yaml::Deserializer::from_str(contents).enumerate() {
+        let value = serde_yaml::Value::deserialize(document)?;
+        if value.is_null() {
+            continue;
+        }
+        let object: DynamicObject = serde_yaml::from_value(value)
+            .with_context(|| format!("document {} is not a Kubernetes object", i + 1))?;
+        if object.types.is_none() {
+            return Err(anyhow!("document {} has no apiVersion or kind", i + 1));
+        }
+        if object.metadata.name.is_none() {
+            return Err(anyhow!("document {} has no metadata.name", i + 1));
+        }
+        objects.push(object);
+    }
+    Ok(objects)
+}
+
+/// The resource a manifest object is looked up by, qualified by its API group so kinds served
+/// by several groups resolve to the right one, e.g. `deployment.apps`
+pub fn resource_name(object: &DynamicObject) -> String {
+    let types = object.types.clone().unwrap_or_default();
+    match types.api_version.split_once('/') {
+        Some((group, _)) => format!("{}.{}", types.kind.to_lowercase(), group),
+        None => types.kind.to_lowercase(),
+    }
+}
+
+/// Manifest fields the live object does not match, ignoring everything the manifest leaves out
+pub fn manifest_changes(desired: &DynamicObject, live: &DynamicObject) -> Result<Vec<Change>> {
+    Ok(diff_desired(
+        &serde_json::to_value(live)?,
+        &serde_json::to_value(desired)?,
+    ))
+}
+
+/// Fields of the configuration last applied with `kubectl apply` that the live object no longer
+/// matches, meaning they were changed out of band. None when the object was never applied.
+pub fn last_applied_changes(live: &DynamicObject) -> Result<Option<Vec<Change>>> {
+    let Some(applied) = live.annotations().get(LAST_APPLIED_ANNOTATION) else {
+        return Ok(None);
+    };
+    let applied: serde_json::Value = serde_json::from_str(applied)
+        .with_context(|| format!("invalid {} annotation", LAST_APPLIED_ANNOTATION))?;
+    Ok(Some(diff_desired(&serde_json::to_value(live)?, &applied)))
+}
+
+/// Names a manifest object as KIND/NAME for output
+pub fn object_name(object: &DynamicObject) -> String {
+    let kind = object
+        .types
+        .as_ref()
+        .map(|t| t.kind.as_str())
+        .unwrap_or_default();
+    format!("{}/{}", kind.to_lowercase(), object.name_any())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_manifest_documents() {
+        let objects = parse_manifest(
+            r#"
+apiVersion: apps/v1
+kind: Deployment
+metadata:
+  name: web
+  namespace: shop
+---
+---
+apiVersion: v1
+kind: ConfigMap
+metadata:
+  name: settings
+data:
+  mode: fast
+"#,
+        )
+        .unwrap();
+        let names: Vec<(String, String)> = objects
+            .iter()
+            .map(|o| (resource_name(o), object_name(o)))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                ("deployment.apps".into(), "deployment/web".into()),
+                ("configmap".into(), "configmap/settings".into()),
+            ]
+        );
+        assert!(parse_manifest("kind: ConfigMap\nmetadata:\n  name: a\n").is_err());
+        assert!(parse_manifest("apiVersion: v1\nkind: ConfigMap\n").is_err());
+    }
+
+    #[test]
+    fn last_applied_drift() {
+        let applied = serde_json::json!({
+            "apiVersion": "apps/v1",
+            "kind": "Deployment",
+            "metadata": {"name": "web", "namespace": "shop"},
+            "spec": {"replicas": 2, "template": {"spec": {"containers": [{"name": "web", "image": "web:1"}]}}},
+        });
+        let live = |replicas: i64| -> DynamicObject {
+            serde_json::from_value(serde_json::json!({
+                "apiVersion": "apps/v1",
+                "kind": "Deployment",
+                "metadata": {
+                    "name": "web",
+                    "namespace": "shop",
+                    "uid": "1",
+                    "annotations": {LAST_APPLIED_ANNOTATION: applied.to_string()},
+                },
+                "spec": {
+                    "replicas": replicas,
+                    "template": {"spec": {"containers": [{"name": "web", "image": "web:1", "imagePullPolicy": "IfNotPresent"}]}},
+                },
+            }))
+            .unwrap()
+        };
+        assert_eq!(last_applied_changes(&live(2)).unwrap(), Some(Vec::new()));
+        let changes: Vec<String> = last_applied_changes(&live(5))
+            .unwrap()
+            .unwrap()
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        assert_eq!(changes, [".spec.replicas: 5 -> 2"]);
+
+        let mut unmanaged = live(2);
+        unmanaged.metadata.annotations = None;
+        assert_eq!(last_applied_changes(&unmanaged).unwrap(), None);
+        unmanaged
+            .annotations_mut()
+            .insert(LAST_APPLIED_ANNOTATION.into(), "{".into());
+        assert!(last_applied_changes(&unmanaged).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use kube::{api::DynamicObject, ResourceExt};
+use serde_json::{json, Map, Value};
+
+// Names of labels and annotations, and label values, hold 63 characters
+const MAX_NAME: usize = 63;
+
+// Prefixes of label and annotation keys are DNS subdomains
+const MAX_PREFIX: usize = 253;
+
+/// The metadata `label` and `annotate` change
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MetadataField {
+    Labels,
+    Annotations,
+}
+
+impl MetadataField {
+    /// What the field is called in object metadata
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::Labels => "labels",
+            Self::Annotations => "annotations",
+        }
+    }
+
+    fn singular(self) -> &'static str {
+        match self {
+            Self::Labels => "label",
+            Self::Annotations => "annotation",
+        }
+    }
+
+    fn current(self, object: &DynamicObject) -> &BTreeMap<String, String> {
+        match self {
+            Self::Labels => object.labels(),
+            Self::Annotations => object.annotations(),
+        }
+    }
+}
+
+/// A label or annotation to set, given as `KEY=VALUE`, or to remove, given as `KEY-`
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetadataChange {
+    pub key: String,
+    pub value: Option<String>,
+}
+
+/// Splits the arguments of `label` and `annotate` into the name of the object, the first
+/// argument when it is not a change, and the changes
+pub fn parse_metadata_args(
+    field: MetadataField,
+    args: &[String],
+) -> Result<(Option<String>, Vec<MetadataChange>)> {
+    let is_change = |arg: &String| arg.contains('=') || arg.ends_with('-');
+    let (name, args) = match args.split_first() {
+        Some((first, rest)) if !is_change(first) => (Some(first.clone()), rest),
+        _ => (None, args),
+    };
+    let mut changes: Vec<MetadataChange> = Vec::new();
+    for arg in args {
+        let change = parse_change(field, arg)?;
+        if changes.iter().any(|c| c.key == change.key) {
+            return Err(anyhow!(
+                "{} {} is changed twice",
+                field.singular(),
+                change.key
+            ));
+        }
+        changes.push(change);
+    }
+    if changes.is_empty() {
+        return Err(anyhow!(
+            "no {} to change, expected KEY=VALUE or KEY-",
+            field.key()
+        ));
+    }
+    Ok((name, changes))
+}
+
+fn parse_change(field: MetadataField, arg: &str) -> Result<MetadataChange> {
+    let (key, value) = match arg.split_once('=') {
+        Some((key, value)) => (key, Some(value.to_owned())),
+        None => match arg.strip_suffix('-') {
+            Some(key) => (key, None),
+            None => {
+                return Err(anyhow!(
+                    "invalid {} change {:?}, expected KEY=VALUE or KEY-",
+                    field.singular(),
+                    arg
+                ))
+            }
+        },
+    };
+    if !is_qualified_name(key) {
+        return Err(anyhow!(
+            "invalid {} key {:?}, expected an optional DNS prefix and a name of at most {} \
+             letters, digits, '-', '_' or '.', as in example.com/name",
+            field.singular(),
+            key,
+            MAX_NAME
+        ));
+    }
+    if let (MetadataField::Labels, Some(value)) = (field, &value) {
+        if !value.is_empty() && !is_name(value) {
+            return Err(anyhow!(
+                "invalid value {:?} of label {}, expected at most {} letters, digits, '-', '_' \
+                 or '.', starting and ending with a letter or digit",
+                value,
+                key,
+                MAX_NAME
+            ));
+        }
+    }
+    Ok(MetadataChange {
+        key: key.to_owned(),
+        value,
+    })
+}
+
+// A key such as `app` or `app.kubernetes.io/name`
+fn is_qualified_name(key: &str) -> bool {
+    match key.split_once('/') {
+        Some((prefix, name)) => is_dns_subdomain(prefix) && is_name(name),
+        None => is_name(key),
+    }
+}
+
+fn is_name(name: &str) -> bool {
+    let edge = |c: Option<char>| c.is_some_and(|c| c.is_ascii_alphanumeric());
+    name.len() <= MAX_NAME
+        && edge(name.chars().next())
+        && edge(name.chars().last())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+fn is_dns_subdomain(prefix: &str) -> bool {
+    prefix.len() <= MAX_PREFIX
+        && prefix.split('.').all(|part| {
+            let edge =
+                |c: Option<char>| c.is_some_and(|c| c.is_ascii_lowercase() || c.is_ascii_digit());
+            edge(part.chars().next())
+                && edge(part.chars().last())
+                && part
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        })
+}
+
+/// The merge patch making the changes to an object, None when the object already has them.
+/// Values that are set to something else are only replaced with `overwrite`, as with kubectl.
+/// The patch carries the version of the object it was made for, so it fails rather than
+/// overwrite a value that changed since.
+pub fn metadata_patch(
+    field: MetadataField,
+    object: &DynamicObject,
+    changes: &[MetadataChange],
+    overwrite: bool,
+) -> Result<Option<Value>> {
+    let current = field.current(object);
+    let mut patch = Map::new();
+    for change in changes {
+        match (&change.value, current.get(&change.key)) {
+            (Some(value), Some(existing)) if value == existing => {}
+            (Some(_), Some(existing)) if !overwrite => {
+                return Err(anyhow!(
+                    "{} {} is already {:?}, use --overwrite to replace it",
+                    field.singular(),
+                    change.key,
+                    existing
+                ))
+            }
+            (Some(value), _) => {
+                patch.insert(change.key.clone(), Value::String(value.clone()));
+            }
+            (None, Some(_)) => {
+                patch.insert(change.key.clone(), Value::Null);
+            }
+            (None, None) => {}
+        }
+    }
+    if patch.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(json!({
+        "metadata": {
+            field.key(): patch,
+            "resourceVersion": object.resource_version(),
+        }
+    })))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn change_metadata() {
+        let args = |args: &[&str]| -> Vec<String> { args.iter().map(|&a| a.into()).collect() };
+        let (name, changes) = parse_metadata_args(
+            MetadataField::Labels,
+            &args(&[
+                "web",
+                "tier=frontend",
+                "app.kubernetes.io/part-of=shop",
+                "legacy-",
+            ]),
+        )
+        .unwrap();
+        assert_eq!(name.as_deref(), Some("web"));
+        let keys: Vec<&str> = changes.iter().map(|c| c.key.as_str()).collect();
+        assert_eq!(keys, ["tier", "app.kubernetes.io/part-of", "legacy"]);
+        assert_eq!(changes[2].value, None);
+
+        let (name, _) = parse_metadata_args(MetadataField::Labels, &args(&["tier="])).unwrap();
+        assert_eq!(name, None);
+        for invalid in [
+            &["web"][..],
+            &["tier=a", "tier-"],
+            &["-tier=a"],
+            &["Example.com/tier=a"],
+            &["tier=not valid"],
+        ] {
+            assert!(parse_metadata_args(MetadataField::Labels, &args(invalid)).is_err());
+        }
+        // annotation values are free form
+        let (_, changes) =
+            parse_metadata_args(MetadataField::Annotations, &args(&["note=not valid"])).unwrap();
+        assert_eq!(changes[0].value.as_deref(), Some("not valid"));
+
+        let object: DynamicObject = serde_json::from_value(json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMap",
+            "metadata": {
+                "name": "web",
+                "resourceVersion": "42",
+                "labels": {"tier": "backend", "legacy": "true"},
+            },
+        }))
+        .unwrap();
+        let change = |arg: &str| parse_change(MetadataField::Labels, arg).unwrap();
+        let patch = |changes: &[MetadataChange], overwrite| {
+            metadata_patch(MetadataField::Labels, &object, changes, overwrite)
+        };
+        assert!(patch(&[change("tier=frontend")], false).is_err());
+        assert_eq!(
+            patch(&[change("tier=frontend"), change("legacy-")], true).unwrap(),
+            Some(json!({"metadata": {
+                "labels": {"tier": "frontend", "legacy": null},
+                "resourceVersion": "42",
+            }}))
+        );
+        assert_eq!(
+            patch(&[change("tier=backend"), change("missing-")], false).unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,350 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Debug,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use hyper_rustls::HttpsConnectorBuilder;
+use serde_json::{json, Value};
+use tracing::{
+    field::{Field, Visit},
+    span::{Attributes, Id, Record},
+    Subscriber,
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+/// Name of the span wrapping a whole command, which every other span of the trace hangs off
+pub const COMMAND_SPAN: &str = "command";
+
+/// Collector to send traces to, read the same way every OpenTelemetry SDK reads it
+pub const ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+const TRACES_ENDPOINT_ENV: &str = "OTEL_EXPORTER_OTLP_TRACES_ENDPOINT";
+const HEADERS_ENV: &str = "OTEL_EXPORTER_OTLP_HEADERS";
+const DISABLED_ENV: &str = "OTEL_SDK_DISABLED";
+
+/// How long the export may hold up the end of a command
+const EXPORT_TIMEOUT: Duration = Duration::from_secs(5);
+
+// OTLP span kinds
+const SPAN_KIND_INTERNAL: u8 = 1;
+const SPAN_KIND_CLIENT: u8 = 3;
+const STATUS_CODE_ERROR: u8 = 2;
+
+static SPANS: Mutex<Vec<FinishedSpan>> = Mutex::new(Vec::new());
+
+// Span ids of the registry are reused once a span closes, so spans get their own
+static NEXT_SPAN_ID: AtomicU64 = AtomicU64::new(1);
+
+/// A closed span waiting to be exported
+#[derive(Clone, Debug, PartialEq)]
+pub struct FinishedSpan {
+    pub id: u64,
+    pub parent: Option<u64>,
+    pub name: String,
+    pub start: SystemTime,
+    pub end: SystemTime,
+    pub fields: BTreeMap<String, String>,
+}
+
+// What is known about a span while it is open, kept in its registry extensions
+struct OpenSpan {
+    id: u64,
+    parent: Option<u64>,
+    name: String,
+    start: SystemTime,
+    fields: BTreeMap<String, String>,
+}
+
+struct FieldVisitor<'a>(&'a mut BTreeMap<String, String>);
+
+impl Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_owned(), value.to_owned());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0
+            .insert(field.name().to_owned(), format!("{:?}", value));
+    }
+}
+
+/// Layer keeping every span of the command, such as those of kube around each HTTP request and
+/// of kubemc around the requests it makes per cluster, for `export` to send once it finishes
+pub struct SpanCollector;
+
+impl<S> Layer<S> for SpanCollector
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = BTreeMap::new();
+        attrs.record(&mut FieldVisitor(&mut fields));
+        let mut parent = None;
+        if let Some(parent_span) = span.parent() {
+            if let Some(open) = parent_span.extensions().get::<OpenSpan>() {
+                parent = Some(open.id);
+                // requests kube makes for a cluster span are attributed to that cluster
+                if let Some(cluster) = open.fields.get("cluster") {
+                    fields
+                        .entry("cluster".to_owned())
+                        .or_insert_with(|| cluster.clone());
+                }
+            }
+        }
+        span.extensions_mut().insert(OpenSpan {
+            id: NEXT_SPAN_ID.fetch_add(1, Ordering::Relaxed),
+            parent,
+            name: attrs.metadata().name().to_owned(),
+            start: SystemTime::now(),
+            fields,
+        });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(open) = span.extensions_mut().get_mut::<OpenSpan>() {
+                values.record(&mut FieldVisitor(&mut open.fields));
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let Some(open) = span.extensions_mut().remove::<OpenSpan>() else {
+            return;
+        };
+        SPANS.lock().unwrap().push(FinishedSpan {
+            id: open.id,
+            parent: open.parent,
+            name: open.name,
+            start: open.start,
+            end: SystemTime::now(),
+            fields: open.fields,
+        });
+    }
+}
+
+/// Where traces go, None unless the environment or the config names a collector. A traces
+/// endpoint in the environment is used as is, other endpoints get the OTLP/HTTP traces path.
+pub fn traces_endpoint(configured: Option<&str>) -> Option<String> {
+    traces_endpoint_with(|name| std::env::var(name).ok(), configured)
+}
+
+fn traces_endpoint_with(
+    env: impl Fn(&str) -> Option<String>,
+    configured: Option<&str>,
+) -> Option<String> {
+    let env = |name: &str| env(name).filter(|value| !value.is_empty());
+    if env(DISABLED_ENV).is_some_and(|value| value.eq_ignore_ascii_case("true")) {
+        return None;
+    }
+    if let Some(endpoint) = env(TRACES_ENDPOINT_ENV) {
+        return Some(endpoint);
+    }
+    let base = env(ENDPOINT_ENV).or_else(|| configured.map(str::to_owned))?;
+    Some(format!("{}/v1/traces", base.trim_end_matches('/')))
+}
+
+/// Parses headers given as `key1=value1,key2=value2`, such as the credentials of a hosted
+/// collector, skipping malformed entries
+pub fn parse_headers(headers: &str) -> Vec<(String, String)> {
+    headers
+        .split(',')
+        .filter_map(|header| {
+            let (key, value) = header.split_once('=')?;
+            let (key, value) = (key.trim(), value.trim());
+            (!key.is_empty()).then(|| (key.to_owned(), value.to_owned()))
+        })
+        .collect()
+}
+
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
+
+/// Builds the OTLP/HTTP JSON request carrying the spans of one command as a single trace.
+/// Spans started in spawned tasks have no parent and are put under the command span.
+pub fn trace_request(spans: &[FinishedSpan], trace_id: &str) -> Value {
+    let root = spans
+        .iter()
+        .find(|span| span.name == COMMAND_SPAN && span.parent.is_none())
+        .map(|span| span.id);
+    let spans: Vec<Value> = spans
+        .iter()
+        .map(|span| {
+            let parent = span.parent.or(root.filter(|root| *root != span.id));
+            let name = span.fields.get("otel.name").unwrap_or(&span.name);
+            let kind = match span.fields.get("otel.kind").map(String::as_str) {
+                Some("client") => SPAN_KIND_CLIENT,
+                _ => SPAN_KIND_INTERNAL,
+            };
+            let attributes: Vec<Value> = span
+                .fields
+                .iter()
+                .filter(|(key, _)| !key.starts_with("otel."))
+                .map(|(key, value)| json!({"key": key, "value": {"stringValue": value}}))
+                .collect();
+            let mut value = json!({
+                "traceId": trace_id,
+                "spanId": format!("{:016x}", span.id),
+                "name": name,
+                "kind": kind,
+                "startTimeUnixNano": unix_nanos(span.start),
+                "endTimeUnixNano": unix_nanos(span.end),
+                "attributes": attributes,
+            });
+            if let Some(parent) = parent {
+                value["parentSpanId"] = json!(format!("{:016x}", parent));
+            }
+            if span.fields.get("otel.status_code").map(String::as_str) == Some("ERROR") {
+                value["status"] = json!({"code": STATUS_CODE_ERROR});
+            }
+            value
+        })
+        .collect();
+    json!({
+        "resourceSpans": [{
+            "resource": {"attributes": [
+                {"key": "service.name", "value": {"stringValue": "kubemc"}},
+                {"key": "service.version", "value": {"stringValue": env!("CARGO_PKG_VERSION")}},
+            ]},
+            "scopeSpans": [{"scope": {"name": "kubemc"}, "spans": spans}],
+        }]
+    })
+}
+
+// Trace ids only have to be unique, the start of the export and the process do
+fn trace_id() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64;
+    format!("{:016x}{:016x}", nanos, std::process::id())
+}
+
+/// Sends the spans collected so far to the collector at `endpoint`
+pub async fn export(endpoint: &str) -> Result<()> {
+    let spans = std::mem::take(&mut *SPANS.lock().unwrap());
+    if spans.is_empty() {
+        return Ok(());
+    }
+    let body = serde_json::to_vec(&trace_request(&spans, &trace_id()))?;
+    let mut request = hyper::Request::post(endpoint).header("content-type", "application/json");
+    let headers = std::env::var(HEADERS_ENV).unwrap_or_default();
+    for (key, value) in parse_headers(&headers) {
+        request = request.header(key, value);
+    }
+    let request = request.body(hyper::Body::from(body))?;
+    let https = HttpsConnectorBuilder::new()
+        .with_native_roots()
+        .https_or_http()
+        .enable_http1()
+        .build();
+    let client: hyper::Client<_, hyper::Body> = hyper::Client::builder().build(https);
+    let response = tokio::time::timeout(EXPORT_TIMEOUT, client.request(request))
+        .await
+        .map_err(|_| anyhow!("timed out sending traces to {}", endpoint))??;
+    if !response.status().is_success() {
+        return Err(anyhow!(
+            "{} rejected the traces with status {}",
+            endpoint,
+            response.status()
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tracing::{debug_span, info_span};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    #[test]
+    fn export_command_traces() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(key, _)| *key == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+        assert_eq!(traces_endpoint_with(env(&[]), None), None);
+        assert_eq!(
+            traces_endpoint_with(env(&[]), Some("http://collector:4318/")).as_deref(),
+            Some("http://collector:4318/v1/traces")
+        );
+        assert_eq!(
+            traces_endpoint_with(
+                env(&[(ENDPOINT_ENV, "https://otlp.example.com")]),
+                Some("http://collector:4318")
+            )
+            .as_deref(),
+            Some("https://otlp.example.com/v1/traces")
+        );
+        assert_eq!(
+            traces_endpoint_with(env(&[(TRACES_ENDPOINT_ENV, "http://x/traces")]), None).as_deref(),
+            Some("http://x/traces")
+        );
+        assert_eq!(
+            traces_endpoint_with(env(&[(DISABLED_ENV, "true")]), Some("http://collector")),
+            None
+        );
+        assert_eq!(
+            parse_headers("x-api-key=secret, tenant = platform,broken"),
+            [
+                ("x-api-key".into(), "secret".into()),
+                ("tenant".into(), "platform".into())
+            ]
+        );
+
+        let subscriber = tracing_subscriber::registry().with(SpanCollector);
+        tracing::subscriber::with_default(subscriber, || {
+            let command = info_span!(COMMAND_SPAN, otel.name = "kubemc get");
+            let _entered = command.enter();
+            let cluster = debug_span!("list_page", cluster = "prod-us", kind = "Pod");
+            let _cluster = cluster.enter();
+            let request = debug_span!(
+                "HTTP",
+                http.method = "GET",
+                otel.kind = "client",
+                otel.status_code = tracing::field::Empty
+            );
+            request.record("otel.status_code", "ERROR");
+            drop(request);
+            // spans of spawned tasks start without a parent
+            drop(debug_span!(parent: None, "create_clients", cluster = "prod-eu"));
+        });
+        let spans = std::mem::take(&mut *SPANS.lock().unwrap());
+        assert_eq!(spans.len(), 4);
+        let request = trace_request(&spans, "0123456789abcdef0123456789abcdef");
+        let exported = request["resourceSpans"][0]["scopeSpans"][0]["spans"]
+            .as_array()
+            .unwrap();
+        let find = |name: &str| exported.iter().find(|span| span["name"] == name).unwrap();
+        let (root, list, http) = (find("kubemc get"), find("list_page"), find("HTTP"));
+        assert!(root.get("parentSpanId").is_none());
+        assert_eq!(list["parentSpanId"], root["spanId"]);
+        assert_eq!(http["parentSpanId"], list["spanId"]);
+        assert_eq!(http["kind"], SPAN_KIND_CLIENT);
+        assert_eq!(http["status"]["code"], STATUS_CODE_ERROR);
+        assert!(http["attributes"]
+            .as_array()
+            .unwrap()
+            .contains(&json!({"key": "cluster", "value": {"stringValue": "prod-us"}})));
+        assert_eq!(find("create_clients")["parentSpanId"], root["spanId"]);
+    }
+}
@@ -1,18 +1,169 @@
-use std::fmt::Display;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    net::IpAddr,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+};
 
 use k8s_openapi::{
     api::{
-        apps::v1::DeploymentStatus,
+        apps::v1::{
+            DaemonSetSpec, DaemonSetStatus, DeploymentStatus, ReplicaSetSpec, ReplicaSetStatus,
+            StatefulSetSpec, StatefulSetStatus,
+        },
+        batch::v1::{JobSpec, JobStatus},
+        coordination::v1::Lease,
         core::v1::{ContainerStatus, NodeStatus, PodSpec, PodStatus, ServiceSpec, ServiceStatus},
+        networking::v1::{IngressSpec, IngressStatus},
     },
     apimachinery::pkg::apis::meta::v1::Time,
-    chrono::Utc,
+    chrono::{DateTime, Duration, SecondsFormat, Utc},
+    kube_aggregator::pkg::apis::apiregistration::v1::APIService,
+};
+use kube::{
+    core::DynamicObject,
+    discovery::{ApiResource, Scope},
+    ResourceExt,
+};
+use serde::{Deserialize, Serialize};
+use tabled::{
+    builder::Builder,
+    settings::{object::Cell, Modify, Span, Style},
+    Table, Tabled,
 };
-use kube::{core::DynamicObject, ResourceExt};
-use serde::Deserialize;
-use tabled::{settings::Style, Table, Tabled};
+use tracing::log::warn;
+
+use crate::{
+    args::parse_age,
+    audit::{ImageFinding, ImageIssue, SchedulingReport, SecurityFinding, SecurityIssue},
+    client::{
+        GetResponse, GetResult, ListFailure, ListResponse, Listing, ScaleResponse, ScaleResult,
+    },
+    config::{Cluster, Clusterset},
+    describe::{format_cpu, format_memory},
+    discovery::{Discovery, DiscoveryResource},
+    failover::ServiceBackends,
+    history::Entry,
+    index::{ClusterIndex, IndexEntry},
+    manifest::{last_applied_changes, manifest_changes, object_name},
+    ownership::descendants,
+    paging::Truncated,
+    redact::redact_object,
+    report::TestCase,
+    rollout::Revision,
+    stats::ClusterStats,
+};
+
+/// IP family kept in the address columns of pods and services
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum IpFamily {
+    Ipv4,
+    Ipv6,
+}
+
+impl IpFamily {
+    // Keeps the addresses of this family in a comma separated list. Anything that is not an
+    // address, such as a load balancer hostname or <none>, is kept as well
+    fn retain(self, addresses: &str) -> String {
+        addresses
+            .split(',')
+            .filter(|address| match address.parse::<IpAddr>() {
+                Ok(ip) => ip.is_ipv4() == (self == IpFamily::Ipv4),
+                Err(_) => true,
+            })
+            .collect::<Vec<_>>()
+            .join(",")
+    }
+}
+
+/// Format used to render command results
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    /// The table with extra columns, such as the cpu and memory requests of pods and nodes
+    Wide,
+    Json,
+    Yaml,
+    /// The table columns as comma separated values
+    Csv,
+    /// One JSON object per line, listed objects carrying their cluster and kind and printed
+    /// as each cluster responds
+    Jsonl,
+    /// The JSON output piped to an external `kubemc-format-<name>` executable, selected with
+    /// `-o plugin:<name>`
+    #[value(skip)]
+    #[serde(skip)]
+    Plugin,
+}
+
+impl OutputFormat {
+    /// Whether results are printed as table rows, as opposed to structured data
+    pub fn is_tabular(self) -> bool {
+        matches!(self, Self::Table | Self::Wide | Self::Csv)
+    }
+}
+
+static COLOR: AtomicBool = AtomicBool::new(false);
+
+/// Enables highlighting statuses with ANSI colors in table output
+pub fn set_color(enabled: bool) {
+    COLOR.store(enabled, Ordering::Relaxed)
+}
+
+/// How healthy a status is, which decides the color it is shown in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Health {
+    Healthy,
+    Progressing,
+    Unhealthy,
+    #[default]
+    Unknown,
+}
+
+/// A status column value along with its health
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct StatusCell {
+    pub text: String,
+    pub health: Health,
+}
+
+impl StatusCell {
+    pub fn new(text: impl Into<String>, health: Health) -> Self {
+        Self {
+            text: text.into(),
+            health,
+        }
+    }
+}
+
+// Structured output only carries the text, the health is only shown as color
+impl Serialize for StatusCell {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.text)
+    }
+}
 
-use crate::client::ListResponse;
+impl Display for StatusCell {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let color = match self.health {
+            Health::Healthy => "32",
+            Health::Progressing => "33",
+            Health::Unhealthy => "31",
+            Health::Unknown => return write!(f, "{}", self.text),
+        };
+        if COLOR.load(Ordering::Relaxed) {
+            write!(f, "\x1b[{}m{}\x1b[0m", color, self.text)
+        } else {
+            write!(f, "{}", self.text)
+        }
+    }
+}
 
 #[derive(Tabled, Clone, Debug)]
 #[tabled(rename_all = "UPPERCASE")]
@@ -37,6 +188,16 @@ pub enum KubeOutput {
     #[tabled(inline)]
     Service(#[tabled(inline)] ServiceOutput),
     #[tabled(inline)]
+    StatefulSet(#[tabled(inline)] StatefulSetOutput),
+    #[tabled(inline)]
+    DaemonSet(#[tabled(inline)] DaemonSetOutput),
+    #[tabled(inline)]
+    ReplicaSet(#[tabled(inline)] ReplicaSetOutput),
+    #[tabled(inline)]
+    Job(#[tabled(inline)] JobOutput),
+    #[tabled(inline)]
+    Ingress(#[tabled(inline)] IngressOutput),
+    #[tabled(inline)]
     Default_(#[tabled(inline)] DefaultOutput),
 }
 
@@ -45,20 +206,44 @@ pub enum KubeOutput {
 pub struct NodeOutput {
     pub clustername: String,
     pub name: String,
-    pub status: String,
+    pub status: StatusCell,
+    /// Running pods out of the pods the node can hold, the count is filled in once pods of the
+    /// cluster are listed
+    pub pods: String,
+    pub taints: usize,
     pub age: String,
     pub version: String,
     pub arch: String,
     pub kernel: String,
     pub container_runtime_version: String,
+    #[tabled(skip)]
+    pub pod_capacity: String,
+}
+
+impl NodeOutput {
+    /// Fills in how many pods run on the node
+    pub fn set_pod_count(&mut self, pods: usize) {
+        self.pods = format!("{}/{}", pods, self.pod_capacity);
+    }
 }
 
 impl From<DynamicObject> for NodeOutput {
     fn from(d: DynamicObject) -> Self {
+        let taints = d
+            .data
+            .pointer("/spec/taints")
+            .and_then(|taints| taints.as_array())
+            .map_or(0, Vec::len);
         if let Some(status) = d.data.get("status") {
             let status: NodeStatus = serde_json::from_value(status.to_owned()).unwrap_or_default();
             let node_info = status.node_info.clone().unwrap_or_default();
             let conditions = status.conditions.unwrap_or_default();
+            let pod_capacity = status
+                .allocatable
+                .as_ref()
+                .or(status.capacity.as_ref())
+                .and_then(|resources| resources.get("pods"))
+                .map_or_else(|| "?".into(), |pods| pods.0.clone());
             Self {
                 clustername: "".into(),
                 name: d.name_any(),
@@ -66,26 +251,30 @@ impl From<DynamicObject> for NodeOutput {
                     .iter()
                     .find(|condition| condition.type_ == "Ready")
                     .map_or_else(
-                        || "Unknown".to_string(),
+                        || StatusCell::new("Unknown", Health::Unknown),
                         |condition| {
                             if condition.status == "True" {
-                                "Ready".to_string()
+                                StatusCell::new("Ready", Health::Healthy)
                             } else {
-                                "NotReady".to_string()
+                                StatusCell::new("NotReady", Health::Unhealthy)
                             }
                         },
                     ),
+                pods: format!("?/{}", pod_capacity),
+                taints,
                 age: get_age(d.metadata.creation_timestamp),
                 version: node_info.kubelet_version,
                 arch: node_info.architecture,
                 kernel: node_info.kernel_version,
                 container_runtime_version: node_info.container_runtime_version,
+                pod_capacity,
             }
         } else {
             Self {
                 clustername: "".into(),
                 name: d.name_any(),
-                status: "Unknown".into(),
+                status: StatusCell::new("Unknown", Health::Unknown),
+                taints,
                 age: get_age(d.metadata.creation_timestamp),
                 ..Default::default()
             }
@@ -116,43 +305,55 @@ impl From<DynamicObject> for DefaultOutput {
 pub struct PodOutput {
     pub clustername: String,
     pub name: String,
-    pub status: String,
+    pub status: StatusCell,
     pub restarts: String,
     pub age: String,
     pub ip: String,
     pub node: String,
+    /// Whether the pod's Ready condition is true
+    #[tabled(skip)]
+    pub ready: bool,
 }
 
 impl From<DynamicObject> for PodOutput {
     fn from(d: DynamicObject) -> Self {
         if let (Some(status), Some(spec)) = (d.data.get("status"), d.data.get("spec")) {
+            // restartPolicy of init containers is newer than the Kubernetes API types in use
+            let sidecars: Vec<String> = spec["initContainers"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|c| c["restartPolicy"] == "Always")
+                .filter_map(|c| c["name"].as_str().map(str::to_owned))
+                .collect();
             let spec: PodSpec = serde_json::from_value(spec.to_owned()).unwrap_or_default();
             let status: PodStatus = serde_json::from_value(status.to_owned()).unwrap_or_default();
+            let deleting = d.metadata.deletion_timestamp.is_some();
+            let pod_status = get_pod_status(&spec, &status, &sidecars, deleting);
+            let ip = get_pod_ips(&status);
+            let ready = !deleting
+                && status
+                    .conditions
+                    .iter()
+                    .flatten()
+                    .any(|c| c.type_ == "Ready" && c.status == "True");
             let container_statuses = status.container_statuses.unwrap_or_default();
             let init_containers = status.init_container_statuses.unwrap_or_default();
             Self {
                 clustername: "".into(),
                 name: d.name_any(),
-                status: status.phase.unwrap_or_else(|| "Unknown".to_string()),
-                restarts: {
-                    let mut restart_count = 0;
-                    container_statuses
-                        .iter()
-                        .for_each(|cs| restart_count += cs.restart_count);
-                    init_containers
-                        .iter()
-                        .for_each(|cs| restart_count += cs.restart_count);
-                    restart_count.to_string()
-                },
+                status: pod_status,
+                restarts: get_restarts(&container_statuses, &init_containers),
                 age: get_age(d.metadata.creation_timestamp),
-                ip: status.pod_ip.unwrap_or_default(),
+                ip,
                 node: spec.node_name.unwrap_or_default(),
+                ready,
             }
         } else {
             Self {
                 clustername: "".into(),
                 name: d.name_any(),
-                status: "Unknown".into(),
+                status: StatusCell::new("Unknown", Health::Unknown),
                 age: get_age(d.metadata.creation_timestamp),
                 ..Default::default()
             }
@@ -165,7 +366,7 @@ impl From<DynamicObject> for PodOutput {
 pub struct DeploymentOutput {
     pub clustername: String,
     pub name: String,
-    pub ready: String,
+    pub ready: StatusCell,
     pub up_to_date: String,
     pub available: String,
     pub age: String,
@@ -173,14 +374,13 @@ pub struct DeploymentOutput {
 
 impl From<DynamicObject> for DeploymentOutput {
     fn from(d: DynamicObject) -> Self {
-        if let (Some(status), Some(spec)) = (d.data.get("status"), d.data.get("spec")) {
+        if let (Some(status), Some(_spec)) = (d.data.get("status"), d.data.get("spec")) {
             let status: DeploymentStatus =
                 serde_json::from_value(status.to_owned()).unwrap_or_default();
             Self {
                 clustername: "".into(),
                 name: d.name_any(),
-                ready: format!(
-                    "{}/{}",
+                ready: get_replicas_ready(
                     status.ready_replicas.unwrap_or_default(),
                     status.replicas.unwrap_or_default(),
                 ),
@@ -221,8 +421,8 @@ impl From<DynamicObject> for ServiceOutput {
             Self {
                 clustername: "".into(),
                 name: d.name_any(),
+                cluster_ip: get_cluster_ips(&spec),
                 type_: spec.type_.unwrap_or("Unknown".to_string()),
-                cluster_ip: spec.cluster_ip.unwrap_or("<none>".to_string()),
                 external_ip: get_external_ip(&status),
                 ports: spec
                     .ports
@@ -257,154 +457,3148 @@ impl From<DynamicObject> for ServiceOutput {
     }
 }
 
-pub fn convert_list_response_to_table(lr: ListResponse) -> Vec<KubeOutput> {
-    let mut kube_output = Vec::new();
-    for obj in &lr.object_list {
-        match lr.kind.as_str() {
-            "Node" => {
-                let mut output: NodeOutput = obj.clone().into();
-                output.clustername = lr.clustername.clone();
-                kube_output.push(KubeOutput::Node(output))
-            }
-            "Pod" => {
-                let mut output: PodOutput = obj.clone().into();
-                output.clustername = lr.clustername.clone();
-                kube_output.push(KubeOutput::Pod(output))
-            }
-            "Deployment" => {
-                let mut output: DeploymentOutput = obj.clone().into();
-                output.clustername = lr.clustername.clone();
-                kube_output.push(KubeOutput::Deployment(output))
-            }
-            "Service" => {
-                let mut output: ServiceOutput = obj.clone().into();
-                output.clustername = lr.clustername.clone();
-                kube_output.push(KubeOutput::Service(output))
-            }
-            _ => {
-                let mut default_output: DefaultOutput = obj.clone().into();
-                default_output.clustername = lr.clustername.clone();
-                kube_output.push(KubeOutput::Default_(default_output))
-            }
+#[derive(Tabled, Clone, Debug, Default)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct StatefulSetOutput {
+    pub clustername: String,
+    pub name: String,
+    pub ready: StatusCell,
+    pub age: String,
+}
+
+impl From<DynamicObject> for StatefulSetOutput {
+    fn from(d: DynamicObject) -> Self {
+        let spec: StatefulSetSpec = parse_field(&d, "spec");
+        let status: StatefulSetStatus = parse_field(&d, "status");
+        Self {
+            clustername: "".into(),
+            name: d.name_any(),
+            ready: get_replicas_ready(
+                status.ready_replicas.unwrap_or_default(),
+                spec.replicas.unwrap_or(1),
+            ),
+            age: get_age(d.metadata.creation_timestamp),
         }
     }
-    kube_output
 }
 
-pub(crate) fn create_table<T: Tabled>(outputs: Vec<T>) {
-    let mut builder = Table::builder(&outputs);
-    builder.clean();
-    let table = builder.build().with(Style::blank()).to_string();
-    println!("{}", table)
+#[derive(Tabled, Clone, Debug, Default)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct DaemonSetOutput {
+    pub clustername: String,
+    pub name: String,
+    pub desired: i32,
+    pub current: i32,
+    pub ready: StatusCell,
+    pub up_to_date: i32,
+    pub available: i32,
+    pub node_selector: String,
+    pub age: String,
 }
-//pub(crate) fn create_table<T: Tabled>(outputs: Vec<T>) {
-//    let mut table = Table::new(&outputs);
-//    table.with(Style::blank());
-//    println!("{}", table)
-//}
 
-#[derive(Clone, Debug, Deserialize, Default)]
-pub struct Status {
-    #[serde(rename = "containerStatuses")]
-    pub container_statuses: Option<Vec<ContainerStatus>>,
+impl From<DynamicObject> for DaemonSetOutput {
+    fn from(d: DynamicObject) -> Self {
+        let spec: DaemonSetSpec = parse_field(&d, "spec");
+        let status: DaemonSetStatus = parse_field(&d, "status");
+        let node_selector = spec
+            .template
+            .spec
+            .and_then(|spec| spec.node_selector)
+            .unwrap_or_default()
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<String>>()
+            .join(",");
+        Self {
+            clustername: "".into(),
+            name: d.name_any(),
+            desired: status.desired_number_scheduled,
+            current: status.current_number_scheduled,
+            ready: get_replicas_ready(status.number_ready, status.desired_number_scheduled),
+            up_to_date: status.updated_number_scheduled.unwrap_or_default(),
+            available: status.number_available.unwrap_or_default(),
+            node_selector: if node_selector.is_empty() {
+                "<none>".into()
+            } else {
+                node_selector
+            },
+            age: get_age(d.metadata.creation_timestamp),
+        }
+    }
+}
 
-    pub phase: Option<String>,
+#[derive(Tabled, Clone, Debug, Default)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct ReplicaSetOutput {
+    pub clustername: String,
+    pub name: String,
+    pub desired: i32,
+    pub current: i32,
+    pub ready: StatusCell,
+    pub age: String,
+}
 
-    pub replicas: Option<u16>,
+impl From<DynamicObject> for ReplicaSetOutput {
+    fn from(d: DynamicObject) -> Self {
+        let spec: ReplicaSetSpec = parse_field(&d, "spec");
+        let status: ReplicaSetStatus = parse_field(&d, "status");
+        let desired = spec.replicas.unwrap_or(1);
+        Self {
+            clustername: "".into(),
+            name: d.name_any(),
+            desired,
+            current: status.replicas,
+            ready: get_replicas_ready(status.ready_replicas.unwrap_or_default(), desired),
+            age: get_age(d.metadata.creation_timestamp),
+        }
+    }
+}
 
-    // Node conditions
-    pub conditions: Option<Vec<Condition>>,
+#[derive(Tabled, Clone, Debug, Default)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct JobOutput {
+    pub clustername: String,
+    pub name: String,
+    pub completions: StatusCell,
+    pub duration: String,
+    pub age: String,
+}
 
-    #[serde(rename = "readyReplicas")]
-    pub ready_replicas: Option<u16>,
+impl From<DynamicObject> for JobOutput {
+    fn from(d: DynamicObject) -> Self {
+        let spec: JobSpec = parse_field(&d, "spec");
+        let status: JobStatus = parse_field(&d, "status");
+        Self {
+            clustername: "".into(),
+            name: d.name_any(),
+            completions: get_job_completions(&spec, &status),
+            duration: get_job_duration(&status),
+            age: get_age(d.metadata.creation_timestamp),
+        }
+    }
 }
 
-#[derive(Clone, Debug, Deserialize, Default)]
-pub struct Condition {
-    #[serde(rename = "type")]
-    pub type_: String,
+#[derive(Tabled, Clone, Debug, Default)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct IngressOutput {
+    pub clustername: String,
+    pub name: String,
+    pub class: String,
+    pub hosts: String,
+    pub address: String,
+    pub ports: String,
+    pub age: String,
+}
 
-    pub status: String,
+impl From<DynamicObject> for IngressOutput {
+    fn from(d: DynamicObject) -> Self {
+        let spec: IngressSpec = parse_field(&d, "spec");
+        let status: IngressStatus = parse_field(&d, "status");
+        // the class annotation predates ingressClassName and is still set by older manifests
+        let class = spec
+            .ingress_class_name
+            .clone()
+            .or_else(|| d.annotations().get("kubernetes.io/ingress.class").cloned())
+            .unwrap_or("<none>".to_string());
+        let hosts: Vec<&str> = spec
+            .rules
+            .iter()
+            .flatten()
+            .filter_map(|rule| rule.host.as_deref())
+            .collect();
+        let addresses: Vec<&str> = status
+            .load_balancer
+            .iter()
+            .flat_map(|lb| lb.ingress.iter().flatten())
+            .filter_map(|ingress| ingress.ip.as_deref().or(ingress.hostname.as_deref()))
+            .collect();
+        let tls = spec.tls.as_ref().is_some_and(|tls| !tls.is_empty());
+        Self {
+            clustername: "".into(),
+            name: d.name_any(),
+            class,
+            hosts: if hosts.is_empty() {
+                "*".to_string()
+            } else {
+                hosts.join(",")
+            },
+            address: addresses.join(","),
+            ports: if tls { "80, 443" } else { "80" }.to_string(),
+            age: get_age(d.metadata.creation_timestamp.clone()),
+        }
+    }
 }
 
-impl Status {
-    pub fn get_ready(&self) -> String {
-        if let Some(cs) = &self.container_statuses {
-            let container_count = cs.len();
-            let containers_ready = cs.iter().filter(|cs| cs.ready).count();
-            return format!("{}/{}", containers_ready, container_count);
+/// How the objects of a kind are shown, picked by API group and kind so that a kind of another
+/// group sharing a name, such as the Service of Knative, keeps the default columns
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Formatter {
+    Node,
+    Pod,
+    Deployment,
+    Service,
+    StatefulSet,
+    DaemonSet,
+    ReplicaSet,
+    Job,
+    Ingress,
+    Default,
+}
+
+/// The kinds with columns of their own, by API group and kind
+const FORMATTERS: [(&str, &str, Formatter); 9] = [
+    ("", "Node", Formatter::Node),
+    ("", "Pod", Formatter::Pod),
+    ("", "Service", Formatter::Service),
+    ("apps", "Deployment", Formatter::Deployment),
+    ("apps", "StatefulSet", Formatter::StatefulSet),
+    ("apps", "DaemonSet", Formatter::DaemonSet),
+    ("apps", "ReplicaSet", Formatter::ReplicaSet),
+    ("batch", "Job", Formatter::Job),
+    ("networking.k8s.io", "Ingress", Formatter::Ingress),
+];
+
+impl Formatter {
+    /// The formatter of a kind served under an apiVersion such as `apps/v1`, whatever the
+    /// version. Kinds without columns of their own get the default ones
+    pub fn of(api_version: &str, kind: &str) -> Self {
+        let group = api_version.split_once('/').map_or("", |(group, _)| group);
+        FORMATTERS
+            .iter()
+            .find(|(g, k, _)| *g == group && *k == kind)
+            .map_or(Formatter::Default, |(_, _, formatter)| *formatter)
+    }
+
+    /// The formatter of a resource as discovery resolved it, from a plural, singular, short
+    /// name or kind
+    pub fn for_resource(resource: &ApiResource) -> Self {
+        Self::of(&resource.api_version, &resource.kind)
+    }
+
+    /// The formatter of an object by its own apiVersion and kind. Objects read by name or
+    /// watched carry them, the items of lists do not and get the default columns
+    pub fn for_object(object: &DynamicObject) -> Self {
+        object.types.as_ref().map_or(Formatter::Default, |types| {
+            Self::of(&types.api_version, &types.kind)
+        })
+    }
+
+    /// Whether the rows of the kind have a health to judge, see `KubeOutput::health`
+    pub fn has_health(self) -> bool {
+        !matches!(
+            self,
+            Formatter::Service | Formatter::Ingress | Formatter::Default
+        )
+    }
+
+    /// Converts an object to a row of the kind's own columns
+    pub fn format(self, clustername: &str, obj: DynamicObject) -> KubeOutput {
+        match self {
+            Formatter::Node => {
+                let mut output: NodeOutput = obj.into();
+                output.clustername = clustername.to_owned();
+                KubeOutput::Node(output)
+            }
+            Formatter::Pod => {
+                let mut output: PodOutput = obj.into();
+                output.clustername = clustername.to_owned();
+                KubeOutput::Pod(output)
+            }
+            Formatter::Deployment => {
+                let mut output: DeploymentOutput = obj.into();
+                output.clustername = clustername.to_owned();
+                KubeOutput::Deployment(output)
+            }
+            Formatter::Service => {
+                let mut output: ServiceOutput = obj.into();
+                output.clustername = clustername.to_owned();
+                KubeOutput::Service(output)
+            }
+            Formatter::StatefulSet => {
+                let mut output: StatefulSetOutput = obj.into();
+                output.clustername = clustername.to_owned();
+                KubeOutput::StatefulSet(output)
+            }
+            Formatter::DaemonSet => {
+                let mut output: DaemonSetOutput = obj.into();
+                output.clustername = clustername.to_owned();
+                KubeOutput::DaemonSet(output)
+            }
+            Formatter::ReplicaSet => {
+                let mut output: ReplicaSetOutput = obj.into();
+                output.clustername = clustername.to_owned();
+                KubeOutput::ReplicaSet(output)
+            }
+            Formatter::Job => {
+                let mut output: JobOutput = obj.into();
+                output.clustername = clustername.to_owned();
+                KubeOutput::Job(output)
+            }
+            Formatter::Ingress => {
+                let mut output: IngressOutput = obj.into();
+                output.clustername = clustername.to_owned();
+                KubeOutput::Ingress(output)
+            }
+            Formatter::Default => {
+                let mut output: DefaultOutput = obj.into();
+                output.clustername = clustername.to_owned();
+                KubeOutput::Default_(output)
+            }
         }
-        if let (Some(ready_rep), Some(rep)) = (&self.ready_replicas, &self.replicas) {
-            return format!("{}/{}", ready_rep, rep);
+    }
+}
+
+pub fn convert_list_response_to_table(lr: ListResponse) -> Vec<KubeOutput> {
+    let formatter = Formatter::of(&lr.api_version, &lr.kind);
+    lr.object_list
+        .into_iter()
+        .map(|obj| formatter.format(&lr.clustername, obj))
+        .collect()
+}
+
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct ScaleOutput {
+    pub clustername: String,
+    pub name: String,
+    pub replicas: String,
+    pub result: String,
+}
+
+impl ScaleOutput {
+    pub fn new(name: &str, response: ScaleResponse) -> Self {
+        let (replicas, result) = match response.result {
+            ScaleResult::Scaled { old, new } => (format!("{} -> {}", old, new), "scaled".into()),
+            ScaleResult::Skipped { current } => (
+                current.to_string(),
+                "skipped: current replicas do not match".into(),
+            ),
+            ScaleResult::Failed(e) => (String::new(), format!("failed: {}", e)),
+        };
+        Self {
+            clustername: response.clustername,
+            name: name.to_owned(),
+            replicas,
+            result,
         }
+    }
+}
 
-        String::default()
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct ApiResourceOutput {
+    pub name: String,
+    pub shortnames: String,
+    pub apiversion: String,
+    pub namespaced: bool,
+    pub kind: String,
+    pub clusters: String,
+    pub missing: String,
+}
+
+/// Builds one row per resource served by any cluster, flagging the clusters that do not serve
+/// it. Resources are matched by group and plural so differing versions count as present.
+pub fn api_resource_union(discoveries: &[(String, Discovery)]) -> Vec<ApiResourceOutput> {
+    let mut union: BTreeMap<(String, String), (&DiscoveryResource, BTreeSet<String>)> =
+        BTreeMap::new();
+    for (clustername, discovery) in discoveries {
+        for resource in discovery.resources() {
+            let ar = resource.api_resource();
+            union
+                .entry((ar.group.clone(), ar.plural.clone()))
+                .or_insert_with(|| (resource, BTreeSet::new()))
+                .1
+                .insert(clustername.clone());
+        }
     }
+    union
+        .into_values()
+        .map(|(resource, present)| {
+            let ar = resource.api_resource();
+            let missing: Vec<&str> = discoveries
+                .iter()
+                .map(|(clustername, _)| clustername.as_str())
+                .filter(|clustername| !present.contains(*clustername))
+                .collect();
+            ApiResourceOutput {
+                name: ar.plural.clone(),
+                shortnames: resource.short_names().join(","),
+                apiversion: ar.api_version.clone(),
+                namespaced: *resource.scope() == Scope::Namespaced,
+                kind: ar.kind.clone(),
+                clusters: format!("{}/{}", present.len(), discoveries.len()),
+                missing: missing.join(","),
+            }
+        })
+        .collect()
+}
 
-    pub fn get_status(&self) -> String {
-        if self.phase.is_some() {
-            return self.phase.clone().unwrap_or_default();
+impl KubeOutput {
+    /// Drops addresses of the other IP family from the pod, service and ingress address columns
+    pub fn retain_ip_family(&mut self, family: IpFamily) {
+        match self {
+            KubeOutput::Pod(pod) => pod.ip = family.retain(&pod.ip),
+            KubeOutput::Service(service) => {
+                service.cluster_ip = family.retain(&service.cluster_ip);
+                service.external_ip = family.retain(&service.external_ip);
+            }
+            KubeOutput::Ingress(ingress) => ingress.address = family.retain(&ingress.address),
+            _ => {}
         }
+    }
 
-        match &self.conditions {
-            Some(c) => {
-                let mut status = String::new();
-                for condition in c {
-                    if condition.type_.as_str() == "Ready" {
-                        status = match condition.status.as_str() {
-                            "True" => String::from("Ready"),
-                            "False" => String::from("NotReady"),
-                            _ => String::default(),
-                        }
-                    }
-                }
-                status
+    /// Health of the row's status, Unknown for kinds without a status to judge
+    pub fn health(&self) -> Health {
+        match self {
+            KubeOutput::Node(node) => node.status.health,
+            KubeOutput::Pod(pod) => pod.status.health,
+            KubeOutput::Deployment(deployment) => deployment.ready.health,
+            KubeOutput::StatefulSet(statefulset) => statefulset.ready.health,
+            KubeOutput::DaemonSet(daemonset) => daemonset.ready.health,
+            KubeOutput::ReplicaSet(replicaset) => replicaset.ready.health,
+            KubeOutput::Job(job) => job.completions.health,
+            KubeOutput::Service(_) | KubeOutput::Ingress(_) | KubeOutput::Default_(_) => {
+                Health::Unknown
             }
-            None => String::default(),
         }
     }
-}
 
-impl Display for Status {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "{}  {}  {}",
-            self.get_ready(),
-            self.phase.to_owned().unwrap_or_default(),
-            self.replicas.to_owned().unwrap_or_default(),
-        )
+    /// Whether the row counts as ready for `get --exit-on-notready`: pods with a true Ready
+    /// condition or that completed, and Ready nodes. None for kinds that are not judged.
+    pub fn ready(&self) -> Option<bool> {
+        match self {
+            KubeOutput::Node(node) => Some(node.status.health == Health::Healthy),
+            KubeOutput::Pod(pod) => {
+                Some(pod.ready || matches!(pod.status.text.as_str(), "Succeeded" | "Completed"))
+            }
+            _ => None,
+        }
+    }
+
+    /// The status a row is counted under by `get --summarize`
+    pub fn summary_status(&self) -> String {
+        let readiness = |cell: &StatusCell| match cell.health {
+            Health::Healthy => "Ready",
+            Health::Progressing => "Progressing",
+            Health::Unhealthy => "Unavailable",
+            Health::Unknown => "Unknown",
+        };
+        match self {
+            KubeOutput::Node(node) => node.status.text.clone(),
+            KubeOutput::Pod(pod) => pod.status.text.clone(),
+            KubeOutput::Deployment(deployment) => readiness(&deployment.ready).into(),
+            KubeOutput::StatefulSet(statefulset) => readiness(&statefulset.ready).into(),
+            KubeOutput::DaemonSet(daemonset) => readiness(&daemonset.ready).into(),
+            KubeOutput::ReplicaSet(replicaset) => readiness(&replicaset.ready).into(),
+            KubeOutput::Service(service) => service.type_.clone(),
+            KubeOutput::Job(job) => match job.completions.health {
+                Health::Healthy => "Complete".into(),
+                Health::Unhealthy => "Failed".into(),
+                _ => "Running".into(),
+            },
+            KubeOutput::Ingress(ingress) => ingress.class.clone(),
+            KubeOutput::Default_(_) => "Total".into(),
+        }
     }
 }
 
-fn get_age(creation: Option<Time>) -> String {
-    if creation.is_none() {
-        return String::default();
+/// Count of the objects of a kind on one cluster, or across all clusters, by status
+#[derive(Tabled, Clone, Debug, Default, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct SummaryOutput {
+    pub clustername: String,
+    pub kind: String,
+    pub total: usize,
+    #[tabled(display_with = "display_counts")]
+    pub statuses: BTreeMap<String, usize>,
+}
+
+impl SummaryOutput {
+    pub fn new(clustername: &str, kind: &str) -> Self {
+        Self {
+            clustername: clustername.to_owned(),
+            kind: kind.to_owned(),
+            ..Default::default()
+        }
     }
-    let duration = Utc::now().signed_duration_since(creation.unwrap().0);
-    match (
-        duration.num_days(),
-        duration.num_hours(),
-        duration.num_minutes(),
-        duration.num_seconds(),
-    ) {
-        (days, hours, _, _) if days > 2 => format!("{}d{}h", days, hours - 24 * days),
-        (_, hours, mins, _) if hours > 0 => format!("{}h{}m", hours, mins - 60 * hours),
-        (_, _, mins, secs) if mins > 0 => format!("{}m{}s", mins, secs - 60 * mins),
-        (_, _, _, secs) => format!("{}s", secs),
+
+    pub fn count(&mut self, status: String) {
+        self.total += 1;
+        *self.statuses.entry(status).or_default() += 1;
     }
-}
 
-fn get_external_ip(status: &ServiceStatus) -> String {
-    let default = "<none>".to_string();
-    let Some(lb) = &status.load_balancer else {return default};
-    let Some(ing) = &lb.ingress else {return default};
-    if let Some(first_ing) = ing.first() {
-        if let Some(ip) = &first_ing.ip {
-            return ip.to_owned();
-        } else if let Some(host) = &first_ing.hostname {
-            return host.to_owned();
+    pub fn add(&mut self, other: &SummaryOutput) {
+        self.total += other.total;
+        for (status, count) in &other.statuses {
+            *self.statuses.entry(status.clone()).or_default() += count;
         }
     }
-    default
+}
+
+// Most common statuses first, e.g. `Running 140, Pending 3, CrashLoopBackOff 1`
+fn display_counts(counts: &BTreeMap<String, usize>) -> String {
+    let mut counts: Vec<(&String, &usize)> = counts.iter().collect();
+    counts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+    counts
+        .iter()
+        .map(|(status, count)| format!("{} {}", status, count))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn display_option(value: &Option<String>) -> String {
+    value.clone().unwrap_or_else(|| "<none>".into())
+}
+
+/// Time spent on one cluster, printed by --profile
+#[derive(Tabled, Clone, Debug)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct ProfileOutput {
+    pub clustername: String,
+    pub connect: String,
+    pub list: String,
+    pub pages: u32,
+    pub objects: usize,
+}
+
+/// What the index holds for one cluster after building it
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct IndexOutput {
+    pub clustername: String,
+    pub objects: usize,
+    pub unlisted: String,
+}
+
+impl IndexOutput {
+    pub fn new(clustername: &str, index: &ClusterIndex) -> Self {
+        Self {
+            clustername: clustername.to_owned(),
+            objects: index.objects.len(),
+            unlisted: index.unlisted.join(","),
+        }
+    }
+}
+
+/// An object from the index whose name matched a search
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct FindOutput {
+    pub clustername: String,
+    pub kind: String,
+    pub namespace: String,
+    pub name: String,
+    pub indexed: String,
+}
+
+impl FindOutput {
+    pub fn new(clustername: &str, entry: &IndexEntry, built: DateTime<Utc>) -> Self {
+        Self {
+            clustername: clustername.to_owned(),
+            kind: entry.kind.clone(),
+            namespace: entry.namespace.clone().unwrap_or_default(),
+            name: entry.name.clone(),
+            indexed: get_age(Some(Time(built))),
+        }
+    }
+}
+
+/// A cluster of the clusterset and whether commands target it
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct ClusterOutput {
+    pub clustername: String,
+    pub context: String,
+    pub namespace: String,
+    pub state: StatusCell,
+}
+
+impl ClusterOutput {
+    pub fn new(cluster: &Cluster, clusterset: &Clusterset, now: DateTime<Utc>) -> Self {
+        let state = if cluster.disabled {
+            StatusCell::new("disabled", Health::Unhealthy)
+        } else if cluster.in_maintenance(now) {
+            StatusCell::new("maintenance", Health::Progressing)
+        } else if clusterset
+            .pinned_cluster
+            .as_ref()
+            .is_some_and(|pinned| pinned != &cluster.name)
+        {
+            StatusCell::new("not pinned", Health::Unknown)
+        } else {
+            StatusCell::new("enabled", Health::Healthy)
+        };
+        let context = match (&cluster.context, &cluster.cluster) {
+            (Some(context), _) => context.clone(),
+            (None, Some(cluster)) => cluster.clone(),
+            (None, None) => String::new(),
+        };
+        Self {
+            clustername: cluster.name.clone(),
+            context,
+            namespace: cluster.namespace_or(&clusterset.namespace),
+            state,
+        }
+    }
+}
+
+/// The holder of a leader election lease on one cluster. A lease not renewed within its
+/// duration is expired, meaning no instance is currently leading.
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct LeaderOutput {
+    pub clustername: String,
+    pub name: String,
+    pub holder: String,
+    pub state: StatusCell,
+    /// Time since the holder last renewed the lease
+    pub renewed: String,
+    pub transitions: i32,
+}
+
+impl LeaderOutput {
+    pub fn new(clustername: &str, lease: Lease, now: DateTime<Utc>) -> Self {
+        let spec = lease.spec.unwrap_or_default();
+        let renewed = spec.renew_time.as_ref().map(|time| time.0);
+        let expires = renewed.map(|renewed| {
+            renewed + Duration::seconds(spec.lease_duration_seconds.unwrap_or_default().into())
+        });
+        let holder = spec.holder_identity.filter(|holder| !holder.is_empty());
+        let state = match (&holder, expires) {
+            (None, _) => StatusCell::new("none", Health::Unknown),
+            (Some(_), Some(expires)) if expires >= now => StatusCell::new("held", Health::Healthy),
+            (Some(_), _) => StatusCell::new("expired", Health::Unhealthy),
+        };
+        Self {
+            clustername: clustername.to_owned(),
+            name: lease.metadata.name.unwrap_or_default(),
+            holder: holder.unwrap_or("<none>".to_string()),
+            state,
+            renewed: renewed.map_or(String::new(), |renewed| {
+                format_duration(now.signed_duration_since(renewed))
+            }),
+            transitions: spec.lease_transitions.unwrap_or_default(),
+        }
+    }
+}
+
+/// The backends of a service on one cluster while watching a failover. A cluster without ready
+/// backends gets no traffic for the service.
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct FailoverOutput {
+    pub clustername: String,
+    pub service: String,
+    pub ready: usize,
+    #[tabled(rename = "NOT READY")]
+    pub not_ready: usize,
+    pub terminating: usize,
+    pub status: StatusCell,
+    /// Time since the number of ready backends last changed
+    pub changed: String,
+}
+
+impl FailoverOutput {
+    pub fn new(
+        clustername: &str,
+        service: &str,
+        backends: &ServiceBackends,
+        now: DateTime<Utc>,
+    ) -> Self {
+        let count = backends.count();
+        let status = match &backends.error {
+            Some(error) => StatusCell::new(error.as_str(), Health::Unknown),
+            None if count.ready > 0 => StatusCell::new("serving", Health::Healthy),
+            None => StatusCell::new("no backends", Health::Unhealthy),
+        };
+        Self {
+            clustername: clustername.to_owned(),
+            service: service.to_owned(),
+            ready: count.ready,
+            not_ready: count.not_ready,
+            terminating: count.terminating,
+            status,
+            changed: backends.changed.map_or(String::new(), |changed| {
+                format_duration(now.signed_duration_since(changed))
+            }),
+        }
+    }
+}
+
+/// A namespace along with the clusters of the clusterset it exists on
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct NamespaceOutput {
+    pub current: String,
+    pub name: String,
+    pub clusters: String,
+    pub missing: String,
+}
+
+/// Builds one row per namespace found on any of `clusternames`, marking the configured one
+pub fn namespace_union(
+    clusternames: &[String],
+    responses: &[ListResponse],
+    current: &str,
+) -> Vec<NamespaceOutput> {
+    let mut union: BTreeMap<String, BTreeSet<&str>> = BTreeMap::new();
+    for lr in responses {
+        for ns in &lr.object_list.items {
+            union
+                .entry(ns.name_any())
+                .or_default()
+                .insert(lr.clustername.as_str());
+        }
+    }
+    union
+        .into_iter()
+        .map(|(name, present)| {
+            let missing: Vec<&str> = clusternames
+                .iter()
+                .map(String::as_str)
+                .filter(|clustername| !present.contains(clustername))
+                .collect();
+            NamespaceOutput {
+                current: if name == current {
+                    "*".into()
+                } else {
+                    "".into()
+                },
+                name,
+                clusters: present.into_iter().collect::<Vec<_>>().join(","),
+                missing: missing.join(","),
+            }
+        })
+        .collect()
+}
+
+/// Outcome of applying an edit on one cluster
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct EditOutput {
+    pub clustername: String,
+    pub name: String,
+    pub result: StatusCell,
+}
+
+/// Outcome of applying a document of a manifest on one cluster
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct ApplyOutput {
+    pub clustername: String,
+    /// Position of the document in the manifest
+    pub document: usize,
+    pub object: String,
+    pub result: StatusCell,
+}
+
+/// Outcome of changing the labels or annotations of an object on one cluster
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct MetadataOutput {
+    pub clustername: String,
+    pub object: String,
+    pub result: StatusCell,
+}
+
+/// Outcome of a step of the end-to-end suite of `kubemc dev e2e`
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct E2eOutput {
+    pub step: String,
+    pub result: StatusCell,
+    pub took: String,
+}
+
+/// A job created from a cronjob on one cluster, along with how it is doing
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct CreateJobOutput {
+    pub clustername: String,
+    pub job: String,
+    pub status: StatusCell,
+}
+
+/// Rollout progress of a workload on one cluster
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct RolloutOutput {
+    pub clustername: String,
+    pub name: String,
+    pub status: StatusCell,
+}
+
+/// A cache entry along with the cluster it belongs to
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct CacheOutput {
+    pub clustername: String,
+    pub cache: String,
+    pub name: String,
+    pub size: String,
+    pub age: String,
+    pub status: StatusCell,
+}
+
+/// Whether an object met the condition waited for on one cluster
+#[derive(Tabled, Clone, Debug)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct WaitOutput {
+    pub clustername: String,
+    pub name: String,
+    pub status: StatusCell,
+}
+
+/// A revision of a deployment on one cluster
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct RevisionOutput {
+    pub clustername: String,
+    pub revision: i64,
+    pub images: String,
+    pub change_cause: String,
+    pub age: String,
+}
+
+impl RevisionOutput {
+    pub fn new(clustername: &str, revision: &Revision) -> Self {
+        Self {
+            clustername: clustername.to_owned(),
+            revision: revision.revision,
+            images: revision.images.join(","),
+            change_cause: revision
+                .change_cause
+                .clone()
+                .unwrap_or_else(|| "<none>".into()),
+            age: get_age(revision.created.clone()),
+        }
+    }
+}
+
+/// A pod of a rollout on one cluster, placed on a timeline shared by every cluster
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct TimelineOutput {
+    pub clustername: String,
+    pub pod: String,
+    /// Since the rollout created the ReplicaSet of the pod
+    pub created: String,
+    pub ready: String,
+    pub timeline: String,
+}
+
+/// How fast the pods of a rollout became ready on one cluster
+#[derive(Tabled, Clone, Debug)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct ReadinessOutput {
+    pub clustername: String,
+    pub ready: String,
+    pub median: String,
+    pub max: String,
+    pub status: StatusCell,
+}
+
+/// Latency of one command on one day, from the local telemetry file
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct TelemetryOutput {
+    pub day: String,
+    pub command: String,
+    pub runs: usize,
+    pub failed: usize,
+    pub clusters: String,
+    pub p50: String,
+    pub p90: String,
+    pub p99: String,
+    pub max: String,
+}
+
+/// A change kubemc made to one cluster, from the local history file
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct HistoryOutput {
+    pub time: String,
+    pub clustername: String,
+    pub command: String,
+    pub resource: String,
+    pub outcome: StatusCell,
+}
+
+impl From<&Entry> for HistoryOutput {
+    fn from(entry: &Entry) -> Self {
+        let health = if entry.success {
+            Health::Healthy
+        } else {
+            Health::Unhealthy
+        };
+        Self {
+            time: entry.time.to_rfc3339_opts(SecondsFormat::Secs, true),
+            clustername: entry.cluster.clone(),
+            command: entry.command.clone(),
+            resource: entry.resource.clone(),
+            outcome: StatusCell::new(&entry.outcome, health),
+        }
+    }
+}
+
+/// Fleet capacity overview row for one cluster, or the footer holding the total across clusters
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct StatsOutput {
+    pub clustername: String,
+    #[tabled(inline)]
+    #[serde(flatten)]
+    pub counts: StatsCounts,
+}
+
+/// Object counts and capacity shown by `kubemc stats`
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct StatsCounts {
+    pub nodes: usize,
+    pub namespaces: usize,
+    pub pods: usize,
+    pub deployments: usize,
+    pub cpu: String,
+    pub memory: String,
+}
+
+/// `kubemc stats` in structured output, with the total kept apart from the clusters so it
+/// cannot be taken for a cluster
+#[derive(Clone, Debug, Serialize)]
+pub struct StatsReport {
+    pub clusters: Vec<StatsOutput>,
+    pub total: StatsCounts,
+}
+
+impl StatsOutput {
+    /// Name of the total in the table footer, which is no valid cluster name
+    pub const TOTAL: &'static str = "(total)";
+
+    pub fn new(clustername: &str, stats: &ClusterStats) -> Self {
+        Self {
+            clustername: clustername.to_owned(),
+            counts: StatsCounts::new(stats),
+        }
+    }
+}
+
+impl StatsCounts {
+    pub fn new(stats: &ClusterStats) -> Self {
+        Self {
+            nodes: stats.nodes,
+            namespaces: stats.namespaces,
+            pods: stats.pods,
+            deployments: stats.deployments,
+            cpu: format_cpu(stats.cpu),
+            memory: format_memory(stats.memory),
+        }
+    }
+}
+
+/// An image pull issue of one container, found by `audit image-pull`
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct ImageFindingOutput {
+    pub clustername: String,
+    pub namespace: String,
+    pub workload: String,
+    pub container: String,
+    pub image: String,
+    pub issue: ImageIssue,
+}
+
+impl ImageFindingOutput {
+    pub fn new(clustername: &str, finding: ImageFinding) -> Self {
+        Self {
+            clustername: clustername.to_owned(),
+            namespace: finding.namespace,
+            workload: finding.workload,
+            container: finding.container,
+            image: finding.image,
+            issue: finding.issue,
+        }
+    }
+}
+
+/// Image pull hygiene of one cluster: how many workloads were audited and the findings by issue
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct ImageAuditOutput {
+    pub clustername: String,
+    pub workloads: usize,
+    #[tabled(rename = "ALWAYS ON DIGEST")]
+    pub always_pull_digest: usize,
+    #[tabled(rename = "LATEST TAG")]
+    pub latest_tag: usize,
+    #[tabled(rename = "NO PULL SECRET")]
+    pub missing_pull_secret: usize,
+}
+
+impl ImageAuditOutput {
+    pub fn new(clustername: &str, workloads: usize, findings: &[ImageFinding]) -> Self {
+        let count = |issue| findings.iter().filter(|f| f.issue == issue).count();
+        Self {
+            clustername: clustername.to_owned(),
+            workloads,
+            always_pull_digest: count(ImageIssue::AlwaysPullDigest),
+            latest_tag: count(ImageIssue::LatestTag),
+            missing_pull_secret: count(ImageIssue::MissingPullSecret),
+        }
+    }
+}
+
+/// A security issue of a workload or container, found by `audit security`
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct SecurityFindingOutput {
+    pub clustername: String,
+    pub namespace: String,
+    pub workload: String,
+    #[tabled(display_with = "display_option")]
+    pub container: Option<String>,
+    pub issue: SecurityIssue,
+}
+
+impl SecurityFindingOutput {
+    pub fn new(clustername: &str, finding: SecurityFinding) -> Self {
+        Self {
+            clustername: clustername.to_owned(),
+            namespace: finding.namespace,
+            workload: finding.workload,
+            container: finding.container,
+            issue: finding.issue,
+        }
+    }
+}
+
+/// Security posture of one namespace of a cluster: how many workloads were audited and how
+/// many of them have each issue
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct SecurityAuditOutput {
+    pub clustername: String,
+    pub namespace: String,
+    pub workloads: usize,
+    pub privileged: usize,
+    #[tabled(rename = "HOST NETWORK")]
+    pub host_network: usize,
+    #[tabled(rename = "HOST PID")]
+    pub host_pid: usize,
+    #[tabled(rename = "RUN AS ROOT")]
+    pub run_as_root: usize,
+}
+
+impl SecurityAuditOutput {
+    /// One row per audited namespace, counting each workload once per issue
+    pub fn by_namespace(
+        clustername: &str,
+        workloads: &BTreeMap<String, usize>,
+        findings: &[SecurityFinding],
+    ) -> Vec<Self> {
+        workloads
+            .iter()
+            .map(|(namespace, workloads)| {
+                let count = |issue| {
+                    findings
+                        .iter()
+                        .filter(|f| &f.namespace == namespace && f.issue == issue)
+                        .map(|f| &f.workload)
+                        .collect::<BTreeSet<_>>()
+                        .len()
+                };
+                Self {
+                    clustername: clustername.to_owned(),
+                    namespace: namespace.clone(),
+                    workloads: *workloads,
+                    privileged: count(SecurityIssue::Privileged),
+                    host_network: count(SecurityIssue::HostNetwork),
+                    host_pid: count(SecurityIssue::HostPid),
+                    run_as_root: count(SecurityIssue::RunAsRoot),
+                }
+            })
+            .collect()
+    }
+}
+
+/// How the live object of a manifest compares to it on one cluster
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ManifestState {
+    Matches,
+    Differs,
+    Missing,
+    Failed,
+    /// Never applied with `kubectl apply`, so there is no last applied configuration
+    Unmanaged,
+}
+
+impl ManifestState {
+    pub fn status(&self) -> StatusCell {
+        match self {
+            ManifestState::Matches => StatusCell::new("matches", Health::Healthy),
+            ManifestState::Differs => StatusCell::new("differs", Health::Progressing),
+            ManifestState::Missing => StatusCell::new("missing", Health::Unhealthy),
+            ManifestState::Failed => StatusCell::new("failed", Health::Unknown),
+            ManifestState::Unmanaged => StatusCell::new("unmanaged", Health::Unknown),
+        }
+    }
+}
+
+impl Display for ManifestState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.status())
+    }
+}
+
+/// A manifest object checked against one cluster by `get -f`, or a live object checked against
+/// its last applied configuration by `get --against last-applied`. Details hold the differing
+/// fields, or the error when the cluster could not be checked.
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct ManifestOutput {
+    pub clustername: String,
+    pub namespace: String,
+    pub object: String,
+    pub state: ManifestState,
+    #[tabled(display_with = "display_details")]
+    pub details: Vec<String>,
+}
+
+impl ManifestOutput {
+    pub fn new(namespace: &str, desired: &DynamicObject, response: GetResponse) -> Self {
+        let (state, details) = match response.result {
+            GetResult::Found(live) => match manifest_changes(desired, &live) {
+                Ok(changes) if changes.is_empty() => (ManifestState::Matches, Vec::new()),
+                Ok(changes) => (
+                    ManifestState::Differs,
+                    changes.iter().map(|c| c.path.clone()).collect(),
+                ),
+                Err(e) => (ManifestState::Failed, vec![e.to_string()]),
+            },
+            GetResult::NotFound => (ManifestState::Missing, Vec::new()),
+            GetResult::Failed(e) => (ManifestState::Failed, vec![e]),
+        };
+        Self {
+            clustername: response.clustername,
+            namespace: namespace.to_owned(),
+            object: object_name(desired),
+            state,
+            details,
+        }
+    }
+
+    /// Compares the object `name` of one cluster to the configuration last applied to it. The
+    /// namespace is that of the object when found.
+    pub fn against_last_applied(
+        clustername: String,
+        kind: &str,
+        namespace: &str,
+        name: &str,
+        result: GetResult,
+    ) -> Self {
+        let mut namespace = namespace.to_owned();
+        let (state, details) = match result {
+            GetResult::Found(live) => {
+                namespace = live.namespace().unwrap_or_default();
+                match last_applied_changes(&live) {
+                    Ok(None) => (ManifestState::Unmanaged, Vec::new()),
+                    Ok(Some(changes)) if changes.is_empty() => (ManifestState::Matches, Vec::new()),
+                    Ok(Some(changes)) => (
+                        ManifestState::Differs,
+                        changes.iter().map(|c| c.path.clone()).collect(),
+                    ),
+                    Err(e) => (ManifestState::Failed, vec![e.to_string()]),
+                }
+            }
+            GetResult::NotFound => (ManifestState::Missing, Vec::new()),
+            GetResult::Failed(e) => (ManifestState::Failed, vec![e]),
+        };
+        Self {
+            clustername,
+            namespace,
+            object: format!("{}/{}", kind.to_lowercase(), name),
+            state,
+            details,
+        }
+    }
+
+    /// The check of the object as a test case of --report, failing with its state and details
+    /// unless it `passed`
+    pub fn test_case(&self, passed: bool) -> TestCase {
+        let name = if self.namespace.is_empty() {
+            self.object.clone()
+        } else {
+            format!("{}/{}", self.namespace, self.object)
+        };
+        let failure = (!passed).then(|| match self.details.as_slice() {
+            [] => self.state.status().text,
+            details => format!("{}: {}", self.state.status().text, details.join(", ")),
+        });
+        TestCase::new(&self.clustername, &name, failure)
+    }
+}
+
+fn display_details(details: &[String]) -> String {
+    details.join(", ")
+}
+
+/// Whether a deployment can be scheduled on the nodes of one cluster
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct SchedulingOutput {
+    pub clustername: String,
+    pub nodes: usize,
+    pub schedulable: StatusCell,
+    /// Reasons nodes are ruled out, with how many nodes each rules out
+    #[tabled(display_with = "display_counts")]
+    pub blockers: BTreeMap<String, usize>,
+}
+
+impl SchedulingOutput {
+    pub fn new(clustername: &str, report: Option<SchedulingReport>) -> Self {
+        let Some(report) = report else {
+            return Self {
+                clustername: clustername.to_owned(),
+                nodes: 0,
+                schedulable: StatusCell::new("not found", Health::Unknown),
+                blockers: BTreeMap::new(),
+            };
+        };
+        let health = if report.schedulable > 0 {
+            Health::Healthy
+        } else {
+            Health::Unhealthy
+        };
+        Self {
+            clustername: clustername.to_owned(),
+            nodes: report.nodes,
+            schedulable: StatusCell::new(report.schedulable.to_string(), health),
+            blockers: report.blockers,
+        }
+    }
+}
+
+/// Availability of an API service on one cluster. Aggregated APIs are served by a service in
+/// the cluster, the rest are served by the API server itself
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct ApiServiceOutput {
+    pub clustername: String,
+    pub name: String,
+    pub service: String,
+    pub available: StatusCell,
+    pub message: String,
+    pub age: String,
+}
+
+impl ApiServiceOutput {
+    pub fn new(clustername: &str, api_service: APIService) -> Self {
+        let service = match api_service.spec.and_then(|spec| spec.service) {
+            Some(service) => format!(
+                "{}/{}",
+                service.namespace.unwrap_or_default(),
+                service.name.unwrap_or_default()
+            ),
+            None => "Local".to_string(),
+        };
+        let condition = api_service
+            .status
+            .and_then(|status| status.conditions)
+            .unwrap_or_default()
+            .into_iter()
+            .find(|condition| condition.type_ == "Available");
+        let (available, message) = match condition {
+            Some(condition) if condition.status == "True" => {
+                (StatusCell::new("True", Health::Healthy), String::new())
+            }
+            Some(condition) => {
+                let text = match condition.reason {
+                    Some(reason) => format!("{} ({})", condition.status, reason),
+                    None => condition.status,
+                };
+                (
+                    StatusCell::new(text, Health::Unhealthy),
+                    condition.message.unwrap_or_default(),
+                )
+            }
+            None => (StatusCell::new("Unknown", Health::Unknown), String::new()),
+        };
+        Self {
+            clustername: clustername.to_owned(),
+            name: api_service.metadata.name.unwrap_or_default(),
+            service,
+            available,
+            message,
+            age: get_age(api_service.metadata.creation_timestamp),
+        }
+    }
+
+    /// Whether the API server reports the service as available
+    pub fn is_available(&self) -> bool {
+        self.available.health == Health::Healthy
+    }
+}
+
+/// Outcome of one check of `verify-cluster`
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct CheckOutput {
+    pub check: String,
+    pub result: StatusCell,
+    pub detail: String,
+}
+
+impl CheckOutput {
+    pub fn new(check: &str, passed: bool, detail: String) -> Self {
+        let result = if passed {
+            StatusCell::new("pass", Health::Healthy)
+        } else {
+            StatusCell::new("fail", Health::Unhealthy)
+        };
+        Self {
+            check: check.to_owned(),
+            result,
+            detail,
+        }
+    }
+}
+
+/// A node cordoned, uncordoned or drained on one cluster. The pod counts are only filled in by
+/// drains, and a blocked drain lists the pods stopping it as the detail.
+#[derive(Tabled, Clone, Debug, Serialize)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct NodeMaintenanceOutput {
+    pub clustername: String,
+    pub node: String,
+    pub result: StatusCell,
+    pub evicted: String,
+    pub skipped: String,
+    pub detail: String,
+}
+
+impl NodeMaintenanceOutput {
+    pub fn new(clustername: &str, node: &str, result: StatusCell) -> Self {
+        Self {
+            clustername: clustername.to_owned(),
+            node: node.to_owned(),
+            result,
+            evicted: String::new(),
+            skipped: String::new(),
+            detail: String::new(),
+        }
+    }
+
+    pub fn failed(clustername: &str, node: &str, error: String) -> Self {
+        Self::new(
+            clustername,
+            node,
+            StatusCell::new(format!("failed: {}", error), Health::Unhealthy),
+        )
+    }
+
+    pub fn succeeded(&self) -> bool {
+        self.result.health != Health::Unhealthy
+    }
+}
+
+/// A pod on a described node with what it reserves of the node
+#[derive(Tabled, Clone, Debug)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct NodePodOutput {
+    pub namespace: String,
+    pub name: String,
+    #[tabled(rename = "CPU REQUESTS")]
+    pub cpu_requests: String,
+    #[tabled(rename = "CPU LIMITS")]
+    pub cpu_limits: String,
+    #[tabled(rename = "MEMORY REQUESTS")]
+    pub memory_requests: String,
+    #[tabled(rename = "MEMORY LIMITS")]
+    pub memory_limits: String,
+}
+
+/// Totals reserved on a described node for one resource
+#[derive(Tabled, Clone, Debug)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct AllocatedOutput {
+    pub resource: String,
+    pub requests: String,
+    pub limits: String,
+}
+
+/// The cluster of a row whose other columns were rendered by the API server
+#[derive(Tabled, Clone, Debug)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct ClusterNameOutput {
+    pub clustername: String,
+}
+
+/// A cluster that failed to serve a request, shown below the results
+#[derive(Tabled, Clone, Debug)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct ErrorOutput {
+    pub clustername: String,
+    pub error: String,
+}
+
+impl From<&ListFailure> for ErrorOutput {
+    fn from(failure: &ListFailure) -> Self {
+        Self {
+            clustername: failure.clustername.clone(),
+            error: failure.error.clone(),
+        }
+    }
+}
+
+/// A listed object as a JSON line, the fields of a `ClusterObject` added to the object itself
+pub fn object_line(
+    cluster: &str,
+    kind: &str,
+    api_version: &str,
+    mut object: DynamicObject,
+) -> serde_json::Value {
+    redact_object(kind, &mut object);
+    let mut value = serde_json::to_value(object).unwrap_or_default();
+    if let Some(fields) = value.as_object_mut() {
+        fields.insert("cluster".into(), cluster.into());
+        fields.insert("kind".into(), kind.into());
+        fields.insert("apiVersion".into(), api_version.into());
+    }
+    value
+}
+
+/// An object along with the cluster, kind and apiVersion it was fetched as. This is the schema
+/// of every object printed as json or yaml, whether listed or fetched by name, and what output
+/// plugins read. Objects in lists carry no kind or apiVersion of their own, hence the fields.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterObject {
+    pub cluster: String,
+    pub kind: String,
+    pub api_version: String,
+    pub object: DynamicObject,
+}
+
+/// The objects of one kind listed from one cluster, for library consumers. Output prints each
+/// of its objects as a `ClusterObject` instead, so that its shape does not depend on how the
+/// listing was paged.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClusterList {
+    pub cluster: String,
+    pub kind: String,
+    pub api_version: String,
+    pub items: Vec<DynamicObject>,
+    /// Set when the listing stopped at the object cap
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub truncated: Option<Truncated>,
+}
+
+impl From<ListResponse> for ClusterList {
+    fn from(lr: ListResponse) -> Self {
+        Self {
+            cluster: lr.clustername,
+            kind: lr.kind,
+            api_version: lr.api_version,
+            items: lr.object_list.items,
+            truncated: lr.truncated,
+        }
+    }
+}
+
+impl ClusterList {
+    /// The objects of the list about to be printed, with the values of Secrets masked
+    pub fn objects(self) -> impl Iterator<Item = ClusterObject> {
+        let Self {
+            cluster,
+            kind,
+            api_version,
+            items,
+            ..
+        } = self;
+        items
+            .into_iter()
+            .map(move |object| ClusterObject::new(&cluster, &kind, &api_version, object))
+    }
+}
+
+impl ClusterObject {
+    /// An object of a kind about to be printed, with the values of Secrets masked
+    pub fn new(cluster: &str, kind: &str, api_version: &str, mut object: DynamicObject) -> Self {
+        redact_object(kind, &mut object);
+        Self {
+            cluster: cluster.to_owned(),
+            kind: kind.to_owned(),
+            api_version: api_version.to_owned(),
+            object,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct ClusterError {
+    pub cluster: String,
+    pub error: String,
+}
+
+/// Per-cluster outcome of fetching a single named object
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NamedGetReport {
+    pub kind: String,
+    pub name: String,
+    pub found: Vec<ClusterObject>,
+    pub not_found: Vec<String>,
+    pub failed: Vec<ClusterError>,
+    /// Objects owned by the found objects, only populated with `--with-children`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub children: Vec<OwnedObject>,
+    /// Clusters where the objects owned by the found objects could not be listed
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub child_failures: Vec<ClusterError>,
+}
+
+/// An object owned, directly or transitively, by a named object
+#[derive(Clone, Debug, Serialize)]
+pub struct OwnedObject {
+    pub cluster: String,
+    pub kind: String,
+    pub depth: usize,
+    pub object: DynamicObject,
+}
+
+#[derive(Tabled, Clone, Debug)]
+#[tabled(rename_all = "UPPERCASE")]
+pub struct OwnedOutput {
+    pub clustername: String,
+    pub kind: String,
+    pub name: String,
+    pub age: String,
+}
+
+impl OwnedOutput {
+    // children are indented below their owner by depth
+    fn new(cluster: &str, kind: &str, depth: usize, object: &DynamicObject) -> Self {
+        Self {
+            clustername: cluster.to_owned(),
+            kind: kind.to_owned(),
+            name: format!("{}{}", "  ".repeat(depth), object.name_any()),
+            age: get_age(object.metadata.creation_timestamp.clone()),
+        }
+    }
+}
+
+impl NamedGetReport {
+    /// Exit code when the object exists in every cluster
+    pub const FOUND_ALL: u8 = 0;
+    /// Exit code when at least one cluster could not be queried or there was no cluster to
+    /// query, the same as for any other error
+    pub const FAILED: u8 = 1;
+    /// Exit code when only a subset of clusters has the object
+    pub const FOUND_SUBSET: u8 = 2;
+    /// Exit code when no cluster has the object and none failed
+    pub const NOT_FOUND: u8 = 3;
+
+    pub fn new(name: &str, kind: &str, responses: Vec<GetResponse>) -> Self {
+        let mut report = Self {
+            kind: kind.to_owned(),
+            name: name.to_owned(),
+            found: Vec::new(),
+            not_found: Vec::new(),
+            failed: Vec::new(),
+            children: Vec::new(),
+            child_failures: Vec::new(),
+        };
+        for response in responses {
+            match response.result {
+                GetResult::Found(object) => {
+                    let api_version = object
+                        .types
+                        .as_ref()
+                        .map(|types| types.api_version.clone())
+                        .unwrap_or_default();
+                    report.found.push(ClusterObject::new(
+                        &response.clustername,
+                        kind,
+                        &api_version,
+                        *object,
+                    ))
+                }
+                GetResult::NotFound => report.not_found.push(response.clustername),
+                GetResult::Failed(error) => report.failed.push(ClusterError {
+                    cluster: response.clustername,
+                    error,
+                }),
+            }
+        }
+        report.found.sort_by(|a, b| a.cluster.cmp(&b.cluster));
+        report.not_found.sort();
+        report.failed.sort_by(|a, b| a.cluster.cmp(&b.cluster));
+        report
+    }
+
+    /// Finds the objects owned by each found object among the listed candidates of its cluster,
+    /// noting the clusters of found objects where candidates could not be listed
+    pub fn attach_children(&mut self, candidates: &Listing) {
+        for failure in &candidates.failures {
+            if self
+                .found
+                .iter()
+                .any(|found| found.cluster == failure.clustername)
+            {
+                self.child_failures.push(ClusterError {
+                    cluster: failure.clustername.clone(),
+                    error: format!("failed to list {}: {}", failure.kind, failure.error),
+                });
+            }
+        }
+        for found in &self.found {
+            let Some(uid) = found.object.uid() else {
+                continue;
+            };
+            let cluster_candidates = candidates
+                .responses
+                .iter()
+                .filter(|lr| lr.clustername == found.cluster)
+                .flat_map(|lr| {
+                    lr.object_list
+                        .items
+                        .iter()
+                        .map(|object| (lr.kind.clone(), object.clone()))
+                })
+                .collect();
+            for descendant in descendants(&uid, cluster_candidates) {
+                self.children.push(OwnedObject {
+                    cluster: found.cluster.clone(),
+                    kind: descendant.kind,
+                    depth: descendant.depth,
+                    object: descendant.object,
+                })
+            }
+        }
+    }
+
+    /// Rows for the found objects followed by their children, grouped by cluster
+    pub fn ownership_tables(&self) -> Vec<Vec<OwnedOutput>> {
+        self.found
+            .iter()
+            .map(|found| {
+                let mut rows = vec![OwnedOutput::new(
+                    &found.cluster,
+                    &self.kind,
+                    0,
+                    &found.object,
+                )];
+                rows.extend(
+                    self.children
+                        .iter()
+                        .filter(|child| child.cluster == found.cluster)
+                        .map(|child| {
+                            OwnedOutput::new(
+                                &child.cluster,
+                                &child.kind,
+                                child.depth,
+                                &child.object,
+                            )
+                        }),
+                );
+                rows
+            })
+            .collect()
+    }
+
+    pub fn total(&self) -> usize {
+        self.found.len() + self.not_found.len() + self.failed.len()
+    }
+
+    pub fn exit_code(&self) -> u8 {
+        if !self.failed.is_empty() || self.total() == 0 {
+            Self::FAILED
+        } else if self.not_found.is_empty() {
+            Self::FOUND_ALL
+        } else if self.found.is_empty() {
+            Self::NOT_FOUND
+        } else {
+            Self::FOUND_SUBSET
+        }
+    }
+
+    /// Exit code of a get of several kinds. A kind that failed wins over the others not being
+    /// found anywhere or only in some clusters, so errors are never hidden.
+    pub fn combined_exit_code(reports: &[NamedGetReport]) -> u8 {
+        let codes = reports.iter().map(NamedGetReport::exit_code);
+        if codes.clone().any(|code| code == Self::FAILED) {
+            Self::FAILED
+        } else {
+            codes.max().unwrap_or_default()
+        }
+    }
+
+    /// One line description of where the object was and was not found
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "{} {} found in {} of {} clusters",
+            self.kind,
+            self.name,
+            self.found.len(),
+            self.total()
+        );
+        if !self.not_found.is_empty() {
+            summary.push_str(&format!("; not found in: {}", self.not_found.join(", ")));
+        }
+        if !self.failed.is_empty() {
+            let failed = self
+                .failed
+                .iter()
+                .map(|f| format!("{} ({})", f.cluster, f.error))
+                .collect::<Vec<String>>()
+                .join(", ");
+            summary.push_str(&format!("; failed in: {}", failed));
+        }
+        summary
+    }
+}
+
+pub(crate) fn create_table<T: Tabled>(outputs: Vec<T>) {
+    println!("{}", table_string(outputs))
+}
+
+/// Renders rows the way `create_table` prints them, for output that is redrawn in place
+pub(crate) fn table_string<T: Tabled>(outputs: Vec<T>) -> String {
+    render_table(tabled_records(&outputs), &[])
+}
+
+// The header and rows of a table, without columns empty in every row. Should tabled fail to
+// build them the rows are taken as they are.
+fn tabled_records<T: Tabled>(outputs: &[T]) -> Vec<Vec<String>> {
+    let built = catch_quietly(|| {
+        let mut builder = Table::builder(outputs);
+        builder.clean();
+        Vec::<Vec<String>>::from(builder)
+    });
+    built.unwrap_or_else(|_| {
+        warn!("failed to build the table, keeping all of its columns");
+        let header = T::headers().into_iter().map(String::from).collect();
+        let rows = outputs
+            .iter()
+            .map(|output| output.fields().into_iter().map(String::from).collect());
+        std::iter::once(header).chain(rows).collect()
+    })
+}
+
+// Runs `f`, catching a panic without the panic hook printing it, since the caller reports
+// the failure itself
+fn catch_quietly<R>(f: impl FnOnce() -> R) -> std::thread::Result<R> {
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let result = panic::catch_unwind(AssertUnwindSafe(f));
+    panic::set_hook(hook);
+    result
+}
+
+/// Renders records, headed by the column names, as a table. The rows at `title_rows` span every
+/// column. Records tabled cannot lay out, with rows not matching the header or cells too large
+/// to line up, are rendered as plain text with a warning so the data is shown either way.
+pub(crate) fn render_table(records: Vec<Vec<String>>, title_rows: &[usize]) -> String {
+    let columns = records.first().map_or(0, Vec::len);
+    let problem = if records
+        .iter()
+        .enumerate()
+        .any(|(i, row)| row.len() != columns && !title_rows.contains(&i))
+    {
+        "its rows do not match the header".to_owned()
+    } else if records
+        .iter()
+        .flatten()
+        .any(|cell| cell.len() > MAX_CELL_LEN)
+    {
+        format!("it has cells over {} bytes", MAX_CELL_LEN)
+    } else {
+        let rendered = catch_quietly(|| {
+            let mut table = Builder::from(records.clone()).build();
+            table.with(Style::blank());
+            for &row in title_rows {
+                table.with(Modify::new(Cell::new(row, 0)).with(Span::column(columns)));
+            }
+            table.to_string()
+        });
+        match rendered {
+            Ok(table) => return table,
+            Err(_) => "tabled failed to lay it out".to_owned(),
+        }
+    };
+    warn!("printing the table as plain text, {}", problem);
+    plain_table(&records, title_rows)
+}
+
+/// Cells longer than this are not lined up in a table
+const MAX_CELL_LEN: usize = 4096;
+
+// Cells wider than this do not widen their column in plain text, they run over instead
+const MAX_PLAIN_WIDTH: usize = 64;
+
+// Lines up records in columns separated by two spaces, one line per record. Line breaks in
+// cells are escaped and title rows only show their first cell.
+fn plain_table(records: &[Vec<String>], title_rows: &[usize]) -> String {
+    let records: Vec<Vec<String>> = records
+        .iter()
+        .enumerate()
+        .map(|(i, row)| {
+            let row = if title_rows.contains(&i) {
+                &row[..row.len().min(1)]
+            } else {
+                &row[..]
+            };
+            row.iter()
+                .map(|cell| cell.replace('\n', "\\n").replace('\r', "\\r"))
+                .collect()
+        })
+        .collect();
+    let mut widths: Vec<usize> = Vec::new();
+    for (i, row) in records.iter().enumerate() {
+        if title_rows.contains(&i) {
+            continue;
+        }
+        for (column, cell) in row.iter().enumerate() {
+            let width = display_width(cell);
+            if column >= widths.len() {
+                widths.resize(column + 1, 0);
+            }
+            if width <= MAX_PLAIN_WIDTH {
+                widths[column] = widths[column].max(width);
+            }
+        }
+    }
+    let lines: Vec<String> = records
+        .iter()
+        .map(|row| {
+            let mut line = String::new();
+            for (column, cell) in row.iter().enumerate() {
+                line.push_str(cell);
+                if column + 1 < row.len() {
+                    let pad = widths[column].saturating_sub(display_width(cell));
+                    line.push_str(&" ".repeat(pad + 2));
+                }
+            }
+            line
+        })
+        .collect();
+    lines.join("\n")
+}
+
+// Width of a cell in characters, leaving out the ANSI color codes of status cells
+fn display_width(cell: &str) -> usize {
+    let mut width = 0;
+    let mut chars = cell.chars();
+    while let Some(c) = chars.next() {
+        if c == '\x1b' {
+            chars.by_ref().find(|c| c.is_ascii_alphabetic());
+        } else {
+            width += 1;
+        }
+    }
+    width
+}
+
+/// Prints rows as a table, or as CSV with the same columns when `format` is csv
+pub(crate) fn print_rows<T: Tabled>(format: OutputFormat, outputs: Vec<T>) {
+    print_labeled_rows(format, outputs, Vec::new())
+}
+
+/// Like `print_rows`, adding a column for every configured label column filled from `labels`,
+/// which holds the labels of each row in order
+pub(crate) fn print_labeled_rows<T: Tabled>(
+    format: OutputFormat,
+    outputs: Vec<T>,
+    labels: Vec<BTreeMap<String, String>>,
+) {
+    print_wide_rows(format, outputs, labels, Vec::new(), Vec::new())
+}
+
+/// Like `print_labeled_rows`, adding the columns named by `headers`, such as the `-o wide`
+/// columns, after the row's own columns. `wide` holds their values for each row in order.
+pub(crate) fn print_wide_rows<T: Tabled>(
+    format: OutputFormat,
+    outputs: Vec<T>,
+    labels: Vec<BTreeMap<String, String>>,
+    headers: Vec<String>,
+    wide: Vec<Vec<String>>,
+) {
+    let settings = table_settings();
+    let mut records = tabled_records(&outputs);
+    if let (false, Some((header, rows))) = (headers.is_empty(), records.split_first_mut()) {
+        header.extend(headers);
+        for (row, wide) in rows.iter_mut().zip(wide) {
+            row.extend(wide);
+        }
+    }
+    if !labels.is_empty() {
+        settings.add_label_columns(&mut records, &labels);
+    }
+    settings.apply(&mut records);
+    if format == OutputFormat::Csv {
+        for record in records {
+            let fields: Vec<String> = record.iter().map(|field| csv_field(field)).collect();
+            println!("{}", fields.join(","));
+        }
+    } else {
+        println!("{}", render_table(records, &[]))
+    }
+}
+
+/// Like `print_rows`, with `footer`, such as a total across the rows, below the rows of a table
+/// after a blank line, so it is neither sorted among them nor mistaken for one of them. Csv
+/// leaves it out, as it would read as one more row there.
+pub(crate) fn print_rows_with_footer<T: Tabled>(
+    format: OutputFormat,
+    mut outputs: Vec<T>,
+    footer: T,
+) {
+    if format == OutputFormat::Csv {
+        return print_rows(format, outputs);
+    }
+    outputs.push(footer);
+    let mut records = tabled_records(&outputs);
+    let settings = table_settings();
+    // hidden columns are hidden in the footer too, while only the rows are sorted
+    let footer = records.pop().unwrap_or_default();
+    let mut footer_records = vec![records[0].clone(), footer];
+    TableSettings {
+        sort_by: None,
+        ..settings.clone()
+    }
+    .apply(&mut footer_records);
+    settings.apply(&mut records);
+    let separator = records.len();
+    records.push(vec![String::new()]);
+    records.extend(footer_records.pop());
+    println!("{}", render_table(records, &[separator]))
+}
+
+/// Rows printed together under a header naming the group
+pub(crate) struct RowGroup<T> {
+    /// Value of the group column in csv output
+    pub name: String,
+    /// Text of the row shown above the group's rows in a table
+    pub header: String,
+    pub rows: Vec<T>,
+    pub labels: Vec<BTreeMap<String, String>>,
+    /// Values of the extra columns of each row, see `print_grouped_wide_rows`
+    pub wide: Vec<Vec<String>>,
+}
+
+/// Like `print_labeled_rows` for rows split into groups. Tables show each group below a row
+/// holding its header, csv gets a `column` column holding the group name instead. Rows are only
+/// sorted within their group.
+pub(crate) fn print_grouped_rows<T: Tabled>(
+    format: OutputFormat,
+    column: &str,
+    groups: Vec<RowGroup<T>>,
+) {
+    print_grouped_wide_rows(format, column, groups, Vec::new())
+}
+
+/// Like `print_grouped_rows`, adding the columns named by `headers` after the row's own columns
+/// the way `print_wide_rows` does, filled from the `wide` values of each group
+pub(crate) fn print_grouped_wide_rows<T: Tabled>(
+    format: OutputFormat,
+    column: &str,
+    groups: Vec<RowGroup<T>>,
+    headers: Vec<String>,
+) {
+    if groups.is_empty() {
+        return print_rows::<T>(format, Vec::new());
+    }
+    println!("{}", grouped_table(format, column, groups, headers))
+}
+
+// Renders the groups of `print_grouped_wide_rows` as a table, or as csv lines. A group without
+// rows still gets its header row, telling an empty cluster apart from one left out.
+fn grouped_table<T: Tabled>(
+    format: OutputFormat,
+    column: &str,
+    groups: Vec<RowGroup<T>>,
+    headers: Vec<String>,
+) -> String {
+    let settings = table_settings();
+    let sizes: Vec<usize> = groups.iter().map(|group| group.rows.len()).collect();
+    let mut names = Vec::new();
+    let mut rows = Vec::new();
+    let mut labels = Vec::new();
+    let mut wide = Vec::new();
+    for group in groups {
+        names.push((group.name, group.header));
+        rows.extend(group.rows);
+        labels.extend(group.labels);
+        wide.extend(group.wide);
+    }
+    // without any rows every column would be dropped as empty, header included
+    let mut records = match rows.is_empty() {
+        true => vec![T::headers().into_iter().map(String::from).collect()],
+        false => tabled_records(&rows),
+    };
+    if let (false, Some((header, rows))) = (headers.is_empty(), records.split_first_mut()) {
+        header.extend(headers);
+        for (row, wide) in rows.iter_mut().zip(wide) {
+            row.extend(wide);
+        }
+    }
+    if !settings.label_columns.is_empty() {
+        settings.add_label_columns(&mut records, &labels);
+    }
+    let full_header = records.remove(0);
+
+    // settings are applied per group so sorting keeps rows in their group, leaving every group
+    // with the same header
+    let mut header = Vec::new();
+    let mut grouped = Vec::new();
+    let mut rest = records.into_iter();
+    for (size, (name, title)) in sizes.into_iter().zip(names) {
+        let mut chunk = vec![full_header.clone()];
+        chunk.extend(rest.by_ref().take(size));
+        settings.apply(&mut chunk);
+        let rows = chunk.split_off(1);
+        header = chunk.remove(0);
+        grouped.push((name, title, rows));
+    }
+
+    if format == OutputFormat::Csv {
+        header.push(column.to_uppercase());
+        let mut lines = vec![header];
+        for (name, _, rows) in grouped {
+            for mut row in rows {
+                row.push(name.clone());
+                lines.push(row);
+            }
+        }
+        return lines
+            .iter()
+            .map(|row| {
+                let fields: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+                fields.join(",")
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+    }
+    let columns = header.len();
+    let mut records = vec![header];
+    let mut title_rows = Vec::new();
+    for (_, title, rows) in grouped {
+        title_rows.push(records.len());
+        let mut title_row = vec![String::new(); columns];
+        title_row[0] = title;
+        records.push(title_row);
+        records.extend(rows);
+    }
+    render_table(records, &title_rows)
+}
+
+static TABLE_SETTINGS: OnceLock<TableSettings> = OnceLock::new();
+
+/// Sets the column settings used by `print_rows` for the rest of the process
+pub fn set_table_settings(settings: TableSettings) {
+    let _ = TABLE_SETTINGS.set(settings);
+}
+
+fn table_settings() -> &'static TableSettings {
+    TABLE_SETTINGS.get_or_init(TableSettings::default)
+}
+
+/// Column changes applied to tables and csv. Columns are matched case-insensitively by header.
+#[derive(Clone, Debug, Default)]
+pub struct TableSettings {
+    pub label_columns: Vec<String>,
+    pub hide_columns: Vec<String>,
+    pub sort_by: Option<String>,
+}
+
+impl TableSettings {
+    // records start with the header row
+    fn add_label_columns(&self, records: &mut [Vec<String>], labels: &[BTreeMap<String, String>]) {
+        let Some((header, rows)) = records.split_first_mut() else {
+            return;
+        };
+        for label in &self.label_columns {
+            header.push(label.to_uppercase());
+            for (row, labels) in rows.iter_mut().zip(labels) {
+                row.push(labels.get(label).cloned().unwrap_or_default());
+            }
+        }
+    }
+
+    fn apply(&self, records: &mut [Vec<String>]) {
+        let Some((header, rows)) = records.split_first_mut() else {
+            return;
+        };
+        let column = |name: &str| header.iter().position(|h| h.eq_ignore_ascii_case(name));
+        if let Some(i) = self.sort_by.as_deref().and_then(column) {
+            rows.sort_by(|a, b| compare_fields(&a[i], &b[i]));
+        }
+        let mut hidden: Vec<usize> = self.hide_columns.iter().filter_map(|c| column(c)).collect();
+        hidden.sort_unstable();
+        hidden.dedup();
+        for record in records.iter_mut() {
+            for i in hidden.iter().rev() {
+                record.remove(*i);
+            }
+        }
+    }
+}
+
+// Orders ages such as 3d4h by duration and numbers numerically, anything else as text
+fn compare_fields(a: &str, b: &str) -> std::cmp::Ordering {
+    if let (Some(a), Some(b)) = (parse_age(a), parse_age(b)) {
+        return a.cmp(&b);
+    }
+    if let (Ok(a), Ok(b)) = (a.parse::<f64>(), b.parse::<f64>()) {
+        return a.total_cmp(&b);
+    }
+    a.cmp(b)
+}
+
+// Quotes a CSV field when it contains a separator, quote or line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+//pub(crate) fn create_table<T: Tabled>(outputs: Vec<T>) {
+//    let mut table = Table::new(&outputs);
+//    table.with(Style::blank());
+//    println!("{}", table)
+//}
+
+#[derive(Clone, Debug, Deserialize, Default)]
+pub struct Status {
+    #[serde(rename = "containerStatuses")]
+    pub container_statuses: Option<Vec<ContainerStatus>>,
+
+    pub phase: Option<String>,
+
+    pub replicas: Option<u16>,
+
+    // Node conditions
+    pub conditions: Option<Vec<Condition>>,
+
+    #[serde(rename = "readyReplicas")]
+    pub ready_replicas: Option<u16>,
+}
+
+#[derive(Clone, Debug, Deserialize, Default)]
+pub struct Condition {
+    #[serde(rename = "type")]
+    pub type_: String,
+
+    pub status: String,
+}
+
+impl Status {
+    pub fn get_ready(&self) -> String {
+        if let Some(cs) = &self.container_statuses {
+            let container_count = cs.len();
+            let containers_ready = cs.iter().filter(|cs| cs.ready).count();
+            return format!("{}/{}", containers_ready, container_count);
+        }
+        if let (Some(ready_rep), Some(rep)) = (&self.ready_replicas, &self.replicas) {
+            return format!("{}/{}", ready_rep, rep);
+        }
+
+        String::default()
+    }
+
+    pub fn get_status(&self) -> String {
+        if self.phase.is_some() {
+            return self.phase.clone().unwrap_or_default();
+        }
+
+        match &self.conditions {
+            Some(c) => {
+                let mut status = String::new();
+                for condition in c {
+                    if condition.type_.as_str() == "Ready" {
+                        status = match condition.status.as_str() {
+                            "True" => String::from("Ready"),
+                            "False" => String::from("NotReady"),
+                            _ => String::default(),
+                        }
+                    }
+                }
+                status
+            }
+            None => String::default(),
+        }
+    }
+}
+
+impl Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}  {}  {}",
+            self.get_ready(),
+            self.phase.to_owned().unwrap_or_default(),
+            self.replicas.to_owned().unwrap_or_default(),
+        )
+    }
+}
+
+pub(crate) fn get_age(creation: Option<Time>) -> String {
+    if creation.is_none() {
+        return String::default();
+    }
+    format_duration(Utc::now().signed_duration_since(creation.unwrap().0))
+}
+
+/// Formats a duration in the short form used for ages, e.g. 3d4h, 2h10m or 45s
+pub(crate) fn format_duration(duration: Duration) -> String {
+    match (
+        duration.num_days(),
+        duration.num_hours(),
+        duration.num_minutes(),
+        duration.num_seconds(),
+    ) {
+        (days, hours, _, _) if days > 2 => format!("{}d{}h", days, hours - 24 * days),
+        (_, hours, mins, _) if hours > 0 => format!("{}h{}m", hours, mins - 60 * hours),
+        (_, _, mins, secs) if mins > 0 => format!("{}m{}s", mins, secs - 60 * mins),
+        (_, _, _, secs) => format!("{}s", secs),
+    }
+}
+
+// Total restarts across all containers, followed by how long ago the most recent one finished,
+// e.g. `5 (2h10m ago)`
+fn get_restarts(containers: &[ContainerStatus], init_containers: &[ContainerStatus]) -> String {
+    let statuses = containers.iter().chain(init_containers);
+    let restart_count: i32 = statuses.clone().map(|cs| cs.restart_count).sum();
+    let last_restart = statuses
+        .filter_map(|cs| {
+            cs.last_state
+                .as_ref()?
+                .terminated
+                .as_ref()?
+                .finished_at
+                .clone()
+        })
+        .max_by_key(|finished_at| finished_at.0);
+    match last_restart {
+        Some(finished_at) if restart_count > 0 => format!(
+            "{} ({} ago)",
+            restart_count,
+            format_duration(Utc::now().signed_duration_since(finished_at.0))
+        ),
+        _ => restart_count.to_string(),
+    }
+}
+
+// Like kubectl, a waiting container's reason such as CrashLoopBackOff is shown instead of the
+// pod phase since it says more about what is wrong
+// The pod status as kubectl computes it: the phase unless the pod is being deleted, still
+// initializing or has a container waiting or terminated with a reason. `sidecars` names the
+// init containers that keep running next to the pod's containers.
+fn get_pod_status(
+    spec: &PodSpec,
+    status: &PodStatus,
+    sidecars: &[String],
+    deleting: bool,
+) -> StatusCell {
+    let mut reason = status
+        .reason
+        .clone()
+        .filter(|r| !r.is_empty())
+        .or_else(|| status.phase.clone())
+        .unwrap_or_else(|| "Unknown".into());
+
+    let init_containers = spec.init_containers.as_deref().unwrap_or_default();
+    let mut initializing = false;
+    for (i, container) in status.init_container_statuses.iter().flatten().enumerate() {
+        let state = container.state.clone().unwrap_or_default();
+        let sidecar = sidecars.contains(&container.name);
+        if let Some(terminated) = &state.terminated {
+            if terminated.exit_code == 0 {
+                continue;
+            }
+            reason = match &terminated.reason {
+                Some(r) if !r.is_empty() => format!("Init:{}", r),
+                _ => match terminated.signal {
+                    Some(signal) if signal != 0 => format!("Init:Signal:{}", signal),
+                    _ => format!("Init:ExitCode:{}", terminated.exit_code),
+                },
+            };
+        } else if sidecar && container.started == Some(true) {
+            continue;
+        } else {
+            reason = match state.waiting.and_then(|w| w.reason) {
+                Some(r) if !r.is_empty() && r != "PodInitializing" => format!("Init:{}", r),
+                _ => format!("Init:{}/{}", i, init_containers.len()),
+            };
+        }
+        initializing = true;
+        break;
+    }
+
+    let has_condition = |type_: &str| {
+        status
+            .conditions
+            .iter()
+            .flatten()
+            .any(|c| c.type_ == type_ && c.status == "True")
+    };
+    if !initializing || has_condition("Initialized") {
+        let mut has_running = false;
+        for container in status.container_statuses.iter().flatten().rev() {
+            let state = container.state.clone().unwrap_or_default();
+            let waiting = state
+                .waiting
+                .and_then(|w| w.reason)
+                .filter(|r| !r.is_empty());
+            if let Some(waiting) = waiting {
+                reason = waiting;
+            } else if let Some(terminated) = state.terminated {
+                reason = match terminated.reason.filter(|r| !r.is_empty()) {
+                    Some(r) => r,
+                    None => match terminated.signal {
+                        Some(signal) if signal != 0 => format!("Signal:{}", signal),
+                        _ => format!("ExitCode:{}", terminated.exit_code),
+                    },
+                };
+            } else if container.ready && state.running.is_some() {
+                has_running = true;
+            }
+        }
+        // a completed container next to one still running means the pod is still up
+        if reason == "Completed" && has_running {
+            reason = if has_condition("Ready") {
+                "Running".into()
+            } else {
+                "NotReady".into()
+            };
+        }
+    }
+
+    if deleting {
+        reason = if status.reason.as_deref() == Some("NodeLost") {
+            "Unknown".into()
+        } else {
+            "Terminating".into()
+        };
+    }
+
+    let health = match reason.as_str() {
+        "Running" | "Succeeded" | "Completed" => Health::Healthy,
+        "Pending" | "ContainerCreating" | "PodInitializing" | "Terminating" | "NotReady" => {
+            Health::Progressing
+        }
+        "Unknown" => Health::Unknown,
+        r if r.starts_with("Init:") && r[5..].contains('/') => Health::Progressing,
+        _ => Health::Unhealthy,
+    };
+    StatusCell::new(reason, health)
+}
+
+fn get_replicas_ready(ready: i32, replicas: i32) -> StatusCell {
+    let health = match (ready, replicas) {
+        (ready, replicas) if ready >= replicas => Health::Healthy,
+        (0, _) => Health::Unhealthy,
+        _ => Health::Progressing,
+    };
+    StatusCell::new(format!("{}/{}", ready, replicas), health)
+}
+
+// Deserializes a top level field such as spec or status, falling back to its default when the
+// field is missing or malformed
+fn parse_field<T: serde::de::DeserializeOwned + Default>(d: &DynamicObject, field: &str) -> T {
+    d.data
+        .get(field)
+        .and_then(|value| serde_json::from_value(value.to_owned()).ok())
+        .unwrap_or_default()
+}
+
+// Mirrors kubectl: `succeeded/completions`, or `succeeded/1 of parallelism` for work queue jobs
+// without a completion count
+fn get_job_completions(spec: &JobSpec, status: &JobStatus) -> StatusCell {
+    let succeeded = status.succeeded.unwrap_or_default();
+    let failed = status
+        .conditions
+        .iter()
+        .flatten()
+        .any(|c| c.type_ == "Failed" && c.status == "True");
+    let (text, complete) = match (spec.completions, spec.parallelism.unwrap_or(1)) {
+        (Some(completions), _) => (
+            format!("{}/{}", succeeded, completions),
+            succeeded >= completions,
+        ),
+        (None, parallelism) if parallelism > 1 => (
+            format!("{}/1 of {}", succeeded, parallelism),
+            succeeded >= 1,
+        ),
+        (None, _) => (format!("{}/1", succeeded), succeeded >= 1),
+    };
+    let health = match (complete, failed) {
+        (true, _) => Health::Healthy,
+        (false, true) => Health::Unhealthy,
+        (false, false) => Health::Progressing,
+    };
+    StatusCell::new(text, health)
+}
+
+// Time from start to completion, or until now for jobs still running
+fn get_job_duration(status: &JobStatus) -> String {
+    let Some(start) = &status.start_time else {
+        return String::default();
+    };
+    let end = status
+        .completion_time
+        .as_ref()
+        .map_or_else(Utc::now, |end| end.0);
+    format_duration(end.signed_duration_since(start.0))
+}
+
+// Every load balancer ingress, dual-stack load balancers have one per IP family
+fn get_external_ip(status: &ServiceStatus) -> String {
+    let ingresses: Vec<&str> = status
+        .load_balancer
+        .iter()
+        .flat_map(|lb| lb.ingress.iter().flatten())
+        .filter_map(|ingress| ingress.ip.as_deref().or(ingress.hostname.as_deref()))
+        .collect();
+    if ingresses.is_empty() {
+        return "<none>".to_string();
+    }
+    ingresses.join(",")
+}
+
+// Dual-stack pods list an address per family in podIPs, podIP only holds the first
+fn get_pod_ips(status: &PodStatus) -> String {
+    match &status.pod_ips {
+        Some(ips) if !ips.is_empty() => ips
+            .iter()
+            .filter_map(|ip| ip.ip.as_deref())
+            .collect::<Vec<_>>()
+            .join(","),
+        _ => status.pod_ip.clone().unwrap_or_default(),
+    }
+}
+
+// Dual-stack services list an address per family in clusterIPs, clusterIP only holds the first
+fn get_cluster_ips(spec: &ServiceSpec) -> String {
+    match &spec.cluster_ips {
+        Some(ips) if !ips.is_empty() => ips.join(","),
+        _ => spec.cluster_ip.clone().unwrap_or("<none>".to_string()),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn response(cluster: &str, result: GetResult) -> GetResponse {
+        GetResponse {
+            clustername: cluster.into(),
+            kind: "Pod".into(),
+            result,
+        }
+    }
+
+    fn found(name: &str) -> GetResult {
+        let ar = ApiResource::erase::<k8s_openapi::api::core::v1::Pod>(&());
+        GetResult::Found(Box::new(DynamicObject::new(name, &ar)))
+    }
+
+    #[test]
+    fn namespaces_across_clusters() {
+        let ar = ApiResource::erase::<k8s_openapi::api::core::v1::Namespace>(&());
+        let list = |cluster: &str, names: &[&str]| ListResponse {
+            clustername: cluster.into(),
+            kind: "Namespace".into(),
+            api_version: "v1".into(),
+            object_list: kube::core::ObjectList {
+                metadata: Default::default(),
+                items: names.iter().map(|n| DynamicObject::new(n, &ar)).collect(),
+            },
+            truncated: None,
+            cached: None,
+        };
+        let clusters = vec!["a".to_string(), "b".to_string()];
+        let rows = namespace_union(
+            &clusters,
+            &[list("a", &["default", "web"]), list("b", &["default"])],
+            "web",
+        );
+        let rows: Vec<_> = rows
+            .iter()
+            .map(|r| {
+                (
+                    r.current.as_str(),
+                    r.name.as_str(),
+                    r.clusters.as_str(),
+                    r.missing.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            rows,
+            vec![("", "default", "a,b", ""), ("*", "web", "a", "b")]
+        );
+    }
+
+    #[test]
+    fn restarts_with_last_restart_age() {
+        let finished = Utc::now() - Duration::minutes(130);
+        let restarted: ContainerStatus = serde_json::from_value(serde_json::json!({
+            "name": "app",
+            "image": "app",
+            "imageID": "",
+            "ready": true,
+            "restartCount": 5,
+            "lastState": {"terminated": {"exitCode": 1, "finishedAt": finished}},
+        }))
+        .unwrap();
+        assert_eq!(get_restarts(&[restarted], &[]), "5 (2h10m ago)");
+
+        let fresh = ContainerStatus {
+            restart_count: 0,
+            ..Default::default()
+        };
+        assert_eq!(get_restarts(&[fresh], &[]), "0");
+    }
+
+    #[test]
+    fn stats_total_apart_from_clusters() {
+        let stats = ClusterStats {
+            nodes: 3,
+            pods: 40,
+            cpu: 12.0,
+            memory: 48.0 * 1024.0 * 1024.0 * 1024.0,
+            ..Default::default()
+        };
+        let headers: Vec<String> = StatsOutput::headers()
+            .into_iter()
+            .map(String::from)
+            .collect();
+        assert_eq!(
+            headers,
+            [
+                "CLUSTERNAME",
+                "NODES",
+                "NAMESPACES",
+                "PODS",
+                "DEPLOYMENTS",
+                "CPU",
+                "MEMORY"
+            ]
+        );
+        // a cluster called total stays a cluster, the total is a field of its own
+        let report = StatsReport {
+            clusters: vec![StatsOutput::new("total", &stats)],
+            total: StatsCounts::new(&stats.add(&stats)),
+        };
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["clusters"][0]["clustername"], "total");
+        assert_eq!(json["clusters"][0]["nodes"], 3);
+        assert_eq!(json["total"]["nodes"], 6);
+        assert!(json["total"].get("clustername").is_none());
+    }
+
+    #[test]
+    fn named_get_exit_codes() {
+        let all = NamedGetReport::new("p", "Pod", vec![response("a", found("p"))]);
+        assert_eq!(all.exit_code(), NamedGetReport::FOUND_ALL);
+
+        let subset = NamedGetReport::new(
+            "p",
+            "Pod",
+            vec![
+                response("b", GetResult::NotFound),
+                response("a", found("p")),
+                response("c", GetResult::NotFound),
+            ],
+        );
+        assert_eq!(subset.exit_code(), NamedGetReport::FOUND_SUBSET);
+        assert_eq!(
+            subset.summary(),
+            "Pod p found in 1 of 3 clusters; not found in: b, c"
+        );
+
+        let none = NamedGetReport::new("p", "Pod", vec![response("a", GetResult::NotFound)]);
+        assert_eq!(none.exit_code(), NamedGetReport::NOT_FOUND);
+
+        // not finding the object anywhere is told apart from errors, and asking no cluster at
+        // all is not a success
+        assert_ne!(NamedGetReport::NOT_FOUND, NamedGetReport::FAILED);
+        let empty = NamedGetReport::new("p", "Pod", Vec::new());
+        assert_eq!(empty.exit_code(), NamedGetReport::FAILED);
+
+        let failed = NamedGetReport::new(
+            "p",
+            "Pod",
+            vec![
+                response("a", found("p")),
+                response("b", GetResult::Failed("timeout".into())),
+            ],
+        );
+        assert_eq!(failed.exit_code(), NamedGetReport::FAILED);
+
+        // a kind that failed on some cluster is not hidden by another kind missing everywhere
+        assert_eq!(
+            NamedGetReport::combined_exit_code(&[failed, none]),
+            NamedGetReport::FAILED
+        );
+        assert_eq!(
+            NamedGetReport::combined_exit_code(&[all, subset]),
+            NamedGetReport::FOUND_SUBSET
+        );
+    }
+
+    #[test]
+    fn api_service_availability() {
+        let api_service =
+            |data: serde_json::Value| -> APIService { serde_json::from_value(data).unwrap() };
+        let metrics = ApiServiceOutput::new(
+            "a",
+            api_service(serde_json::json!({
+                "metadata": {"name": "v1beta1.metrics.k8s.io"},
+                "spec": {
+                    "service": {"namespace": "kube-system", "name": "metrics-server"},
+                    "groupPriorityMinimum": 100,
+                    "versionPriority": 100,
+                },
+                "status": {"conditions": [{
+                    "type": "Available",
+                    "status": "False",
+                    "reason": "MissingEndpoints",
+                    "message": "endpoints for service/metrics-server have no addresses",
+                }]},
+            })),
+        );
+        assert_eq!(metrics.service, "kube-system/metrics-server");
+        assert_eq!(metrics.available.to_string(), "False (MissingEndpoints)");
+        assert_eq!(
+            metrics.message,
+            "endpoints for service/metrics-server have no addresses"
+        );
+        assert!(!metrics.is_available());
+
+        let apps = ApiServiceOutput::new(
+            "a",
+            api_service(serde_json::json!({
+                "metadata": {"name": "v1.apps"},
+                "spec": {"groupPriorityMinimum": 17800, "versionPriority": 15},
+                "status": {"conditions": [{"type": "Available", "status": "True", "reason": "Local"}]},
+            })),
+        );
+        assert_eq!(apps.service, "Local");
+        assert!(apps.is_available());
+    }
+
+    #[test]
+    fn lease_holders() {
+        let now: DateTime<Utc> = "2024-05-01T12:00:00Z".parse().unwrap();
+        let lease = |renewed: &str| -> Lease {
+            serde_json::from_value(serde_json::json!({
+                "metadata": {"name": "kube-controller-manager"},
+                "spec": {
+                    "holderIdentity": "cp-1_5f2c",
+                    "leaseDurationSeconds": 15,
+                    "renewTime": renewed,
+                    "leaseTransitions": 3,
+                },
+            }))
+            .unwrap()
+        };
+        let held = LeaderOutput::new("a", lease("2024-05-01T11:59:55.000000Z"), now);
+        assert_eq!(held.holder, "cp-1_5f2c");
+        assert_eq!(held.state.health, Health::Healthy);
+        assert_eq!(held.renewed, "5s");
+        assert_eq!(held.transitions, 3);
+
+        let expired = LeaderOutput::new("a", lease("2024-05-01T11:58:00.000000Z"), now);
+        assert_eq!(expired.state.text, "expired");
+
+        let released: Lease = serde_json::from_value(serde_json::json!({
+            "metadata": {"name": "scheduler"},
+            "spec": {"holderIdentity": ""},
+        }))
+        .unwrap();
+        let released = LeaderOutput::new("a", released, now);
+        assert_eq!(
+            (released.holder.as_str(), released.state.text.as_str()),
+            ("<none>", "none")
+        );
+    }
+
+    #[test]
+    fn dual_stack_addresses() {
+        let object = |data: serde_json::Value| -> DynamicObject {
+            let mut object: DynamicObject = serde_json::from_value(serde_json::json!({
+                "apiVersion": "v1",
+                "kind": "Unknown",
+                "metadata": {"name": "web"},
+            }))
+            .unwrap();
+            object.data = data;
+            object
+        };
+        let pod = PodOutput::from(object(serde_json::json!({
+            "spec": {"containers": []},
+            "status": {
+                "podIP": "10.0.0.5",
+                "podIPs": [{"ip": "10.0.0.5"}, {"ip": "fd00::5"}],
+            },
+        })));
+        assert_eq!(pod.ip, "10.0.0.5,fd00::5");
+
+        let service = ServiceOutput::from(object(serde_json::json!({
+            "spec": {
+                "type": "LoadBalancer",
+                "clusterIP": "10.96.0.10",
+                "clusterIPs": ["10.96.0.10", "fd00:10::a"],
+            },
+            "status": {"loadBalancer": {"ingress": [
+                {"ip": "203.0.113.7"},
+                {"ip": "2001:db8::7"},
+                {"hostname": "lb.example.com"},
+            ]}},
+        })));
+        assert_eq!(service.cluster_ip, "10.96.0.10,fd00:10::a");
+        assert_eq!(
+            service.external_ip,
+            "203.0.113.7,2001:db8::7,lb.example.com"
+        );
+
+        let mut output = KubeOutput::Service(service);
+        output.retain_ip_family(IpFamily::Ipv6);
+        let KubeOutput::Service(service) = output else {
+            unreachable!()
+        };
+        assert_eq!(service.cluster_ip, "fd00:10::a");
+        assert_eq!(service.external_ip, "2001:db8::7,lb.example.com");
+
+        let mut output = KubeOutput::Pod(pod);
+        output.retain_ip_family(IpFamily::Ipv4);
+        let KubeOutput::Pod(pod) = output else {
+            unreachable!()
+        };
+        assert_eq!(pod.ip, "10.0.0.5");
+
+        let ingress = IngressOutput::from(object(serde_json::json!({
+            "spec": {
+                "ingressClassName": "nginx",
+                "rules": [{"host": "a.example.com"}, {"host": "b.example.com"}],
+                "tls": [{"hosts": ["a.example.com"]}],
+            },
+            "status": {"loadBalancer": {"ingress": [{"ip": "203.0.113.9"}, {"ip": "2001:db8::9"}]}},
+        })));
+        assert_eq!(ingress.class, "nginx");
+        assert_eq!(ingress.hosts, "a.example.com,b.example.com");
+        assert_eq!(ingress.address, "203.0.113.9,2001:db8::9");
+        assert_eq!(ingress.ports, "80, 443");
+
+        let single = PodOutput::from(object(serde_json::json!({
+            "spec": {"containers": []},
+            "status": {"podIP": "10.0.0.6"},
+        })));
+        assert_eq!(single.ip, "10.0.0.6");
+    }
+
+    #[test]
+    fn pod_status_health() {
+        let crashing: PodStatus = serde_json::from_value(serde_json::json!({
+            "phase": "Running",
+            "containerStatuses": [{
+                "name": "app",
+                "image": "app",
+                "imageID": "",
+                "ready": false,
+                "restartCount": 3,
+                "state": {"waiting": {"reason": "CrashLoopBackOff"}},
+            }],
+        }))
+        .unwrap();
+        let spec = PodSpec::default();
+        assert_eq!(
+            get_pod_status(&spec, &crashing, &[], false),
+            StatusCell::new("CrashLoopBackOff", Health::Unhealthy)
+        );
+        assert_eq!(
+            get_pod_status(&spec, &crashing, &[], true),
+            StatusCell::new("Terminating", Health::Progressing)
+        );
+
+        let pending = PodStatus {
+            phase: Some("Pending".into()),
+            ..Default::default()
+        };
+        let status = get_pod_status(&spec, &pending, &[], false);
+        assert_eq!(status.health, Health::Progressing);
+        assert_eq!(status.to_string(), "Pending");
+
+        let spec: PodSpec = serde_json::from_value(serde_json::json!({
+            "initContainers": [{"name": "migrate"}, {"name": "seed"}],
+            "containers": [{"name": "app"}],
+        }))
+        .unwrap();
+        let init_status = |migrate: serde_json::Value| -> PodStatus {
+            serde_json::from_value(serde_json::json!({
+                "phase": "Pending",
+                "initContainerStatuses": [
+                    {"name": "migrate", "image": "m", "imageID": "", "ready": false, "restartCount": 0, "state": migrate},
+                    {"name": "seed", "image": "s", "imageID": "", "ready": false, "restartCount": 0, "state": {"waiting": {"reason": "PodInitializing"}}},
+                ],
+            }))
+            .unwrap()
+        };
+        let done = init_status(serde_json::json!({"terminated": {"exitCode": 0}}));
+        assert_eq!(
+            get_pod_status(&spec, &done, &[], false),
+            StatusCell::new("Init:1/2", Health::Progressing)
+        );
+        let failing = init_status(serde_json::json!({"waiting": {"reason": "ImagePullBackOff"}}));
+        assert_eq!(
+            get_pod_status(&spec, &failing, &[], false),
+            StatusCell::new("Init:ImagePullBackOff", Health::Unhealthy)
+        );
+        let killed = init_status(serde_json::json!({"terminated": {"exitCode": 137, "signal": 9}}));
+        assert_eq!(
+            get_pod_status(&spec, &killed, &[], false).text,
+            "Init:Signal:9"
+        );
+
+        assert_eq!(get_replicas_ready(1, 3).health, Health::Progressing);
+        assert_eq!(get_replicas_ready(0, 0).health, Health::Healthy);
+    }
+
+    #[test]
+    fn formatters_by_group_and_kind() {
+        assert_eq!(Formatter::of("v1", "Pod"), Formatter::Pod);
+        assert_eq!(
+            Formatter::of("apps/v1", "Deployment"),
+            Formatter::Deployment
+        );
+        assert_eq!(Formatter::of("batch/v1", "Job"), Formatter::Job);
+        // kinds sharing a name with a kind of another group keep the default columns
+        assert_eq!(
+            Formatter::of("serving.knative.dev/v1", "Service"),
+            Formatter::Default
+        );
+        assert_eq!(Formatter::of("v1", "Deployment"), Formatter::Default);
+        assert_eq!(Formatter::of("v1", "ConfigMap"), Formatter::Default);
+
+        let ingress = ApiResource::erase::<k8s_openapi::api::networking::v1::Ingress>(&());
+        assert_eq!(Formatter::for_resource(&ingress), Formatter::Ingress);
+        let statefulsets = ApiResource::erase::<k8s_openapi::api::apps::v1::StatefulSet>(&());
+        assert_eq!(
+            Formatter::for_resource(&statefulsets),
+            Formatter::StatefulSet
+        );
+
+        let knative: DynamicObject = serde_json::from_value(serde_json::json!({
+            "apiVersion": "serving.knative.dev/v1",
+            "kind": "Service",
+            "metadata": {"name": "web"},
+        }))
+        .unwrap();
+        assert_eq!(Formatter::for_object(&knative), Formatter::Default);
+        assert!(matches!(
+            Formatter::for_object(&knative).format("prod", knative.clone()),
+            KubeOutput::Default_(output) if output.clustername == "prod"
+        ));
+        let mut listed = knative;
+        listed.types = None;
+        assert_eq!(Formatter::for_object(&listed), Formatter::Default);
+        let pod = ApiResource::erase::<k8s_openapi::api::core::v1::Pod>(&());
+        assert_eq!(
+            Formatter::for_object(&DynamicObject::new("web", &pod)),
+            Formatter::Pod
+        );
+    }
+
+    #[test]
+    fn readiness_for_exit_codes() {
+        let pod = |phase: &str, ready: &str| {
+            let object: DynamicObject = serde_json::from_value(serde_json::json!({
+                "apiVersion": "v1",
+                "kind": "Pod",
+                "metadata": {"name": "web"},
+                "spec": {"containers": []},
+                "status": {"phase": phase, "conditions": [{"type": "Ready", "status": ready}]},
+            }))
+            .unwrap();
+            Formatter::of("v1", "Pod").format("prod", object).ready()
+        };
+        assert_eq!(pod("Running", "True"), Some(true));
+        assert_eq!(pod("Running", "False"), Some(false));
+        assert_eq!(pod("Pending", "False"), Some(false));
+        assert_eq!(pod("Succeeded", "False"), Some(true));
+
+        let node: DynamicObject = serde_json::from_value(serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "Node",
+            "metadata": {"name": "node-a"},
+            "status": {"conditions": [{"type": "Ready", "status": "False"}]},
+        }))
+        .unwrap();
+        assert_eq!(
+            Formatter::of("v1", "Node").format("prod", node).ready(),
+            Some(false)
+        );
+        let config: DynamicObject = serde_json::from_value(serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMap",
+            "metadata": {"name": "settings"},
+        }))
+        .unwrap();
+        assert_eq!(
+            Formatter::of("v1", "ConfigMap")
+                .format("prod", config)
+                .ready(),
+            None
+        );
+    }
+
+    #[test]
+    fn node_pods_and_taints() {
+        let node: DynamicObject = serde_json::from_value(serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "Node",
+            "metadata": {"name": "node-a"},
+            "spec": {"taints": [{"key": "dedicated", "value": "gpu", "effect": "NoSchedule"}]},
+            "status": {
+                "capacity": {"pods": "110"},
+                "allocatable": {"pods": "100"},
+                "conditions": [{"type": "Ready", "status": "True"}],
+            },
+        }))
+        .unwrap();
+        let mut output = NodeOutput::from(node);
+        assert_eq!((output.pods.as_str(), output.taints), ("?/100", 1));
+        output.set_pod_count(12);
+        assert_eq!(output.pods, "12/100");
+    }
+
+    #[test]
+    fn job_completions_and_duration() {
+        let ar = ApiResource::erase::<k8s_openapi::api::batch::v1::Job>(&());
+        let mut job = DynamicObject::new("backup", &ar);
+        job.data = serde_json::json!({
+            "spec": {"completions": 3, "template": {}},
+            "status": {
+                "succeeded": 3,
+                "startTime": "2024-01-01T00:00:00Z",
+                "completionTime": "2024-01-01T00:02:30Z",
+            },
+        });
+        let output = JobOutput::from(job);
+        assert_eq!(output.completions.to_string(), "3/3");
+        assert_eq!(output.completions.health, Health::Healthy);
+        assert_eq!(output.duration, "2m30s");
+
+        let queue = JobSpec {
+            parallelism: Some(4),
+            ..Default::default()
+        };
+        let running = JobStatus::default();
+        let completions = get_job_completions(&queue, &running);
+        assert_eq!(completions.to_string(), "0/1 of 4");
+        assert_eq!(completions.health, Health::Progressing);
+    }
+
+    #[test]
+    fn sort_and_hide_columns() {
+        let settings = TableSettings {
+            label_columns: vec!["app".into()],
+            hide_columns: vec!["status".into()],
+            sort_by: Some("age".into()),
+        };
+        let record = |fields: &[&str]| fields.iter().map(|f| f.to_string()).collect::<Vec<_>>();
+        let mut records = vec![
+            record(&["NAME", "STATUS", "AGE"]),
+            record(&["web", "Running", "2h10m"]),
+            record(&["db", "Running", "45s"]),
+            record(&["cache", "Pending", "3d4h"]),
+        ];
+        let labels = vec![
+            BTreeMap::from([("app".to_string(), "web".to_string())]),
+            BTreeMap::new(),
+            BTreeMap::from([("app".to_string(), "cache".to_string())]),
+        ];
+        settings.add_label_columns(&mut records, &labels);
+        settings.apply(&mut records);
+        assert_eq!(
+            records,
+            vec![
+                record(&["NAME", "AGE", "APP"]),
+                record(&["db", "45s", ""]),
+                record(&["web", "2h10m", "web"]),
+                record(&["cache", "3d4h", "cache"]),
+            ]
+        );
+        assert_eq!(compare_fields("10", "9"), std::cmp::Ordering::Greater);
+    }
+
+    #[test]
+    fn summarize_by_status() {
+        let mut a = SummaryOutput::new("a", "Pod");
+        for status in [
+            "Running",
+            "Pending",
+            "Running",
+            "CrashLoopBackOff",
+            "Running",
+        ] {
+            a.count(status.into());
+        }
+        let mut b = SummaryOutput::new("b", "Pod");
+        b.count("Pending".into());
+        let mut total = SummaryOutput::new("total", "Pod");
+        total.add(&a);
+        total.add(&b);
+        assert_eq!(
+            display_counts(&a.statuses),
+            "Running 3, CrashLoopBackOff 1, Pending 1"
+        );
+        assert_eq!(total.total, 6);
+        assert_eq!(
+            display_counts(&total.statuses),
+            "Running 3, Pending 2, CrashLoopBackOff 1"
+        );
+    }
+
+    #[test]
+    fn plain_table_fallback() {
+        let record = |cells: &[&str]| cells.iter().map(|&c| c.to_owned()).collect::<Vec<_>>();
+        let table = render_table(
+            vec![record(&["NAME", "READY"]), record(&["web-1", "1/1"])],
+            &[],
+        );
+        assert!(table.contains("web-1"));
+
+        // a row longer than the header is still shown
+        let ragged = vec![
+            record(&["NAME", "READY"]),
+            record(&["web-1", "1/1"]),
+            record(&["api", "0/1", "extra"]),
+        ];
+        assert_eq!(
+            render_table(ragged, &[]),
+            "NAME   READY\nweb-1  1/1\napi    0/1    extra"
+        );
+
+        let huge = "x".repeat(MAX_CELL_LEN + 1);
+        let records = vec![
+            record(&["CLUSTER", "NAME", "DATA"]),
+            record(&["east"]),
+            record(&["east", "big", &huge]),
+            record(&["east", "two\nlines", "b"]),
+        ];
+        let table = render_table(records, &[1]);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "CLUSTER  NAME        DATA");
+        assert_eq!(lines[1], "east");
+        assert_eq!(lines[2], format!("east     big         {}", huge));
+        assert_eq!(lines[3], "east     two\\nlines  b");
+
+        let status = StatusCell::new("Running", Health::Healthy);
+        assert_eq!(display_width(&format!("\x1b[32m{}\x1b[0m", status.text)), 7);
+    }
+
+    #[test]
+    fn chunk_per_cluster() {
+        #[derive(Tabled)]
+        struct Row {
+            name: &'static str,
+        }
+        let group = |name: &str, rows: Vec<Row>| RowGroup {
+            name: name.into(),
+            header: format!("{} ({} pods)", name, rows.len()),
+            labels: vec![BTreeMap::new(); rows.len()],
+            wide: vec![Vec::new(); rows.len()],
+            rows,
+        };
+        let groups = || {
+            vec![
+                group(
+                    "prod-eu",
+                    vec![Row { name: "web-1" }, Row { name: "web-2" }],
+                ),
+                group("prod-us", Vec::new()),
+            ]
+        };
+        let table = grouped_table(OutputFormat::Table, "cluster", groups(), Vec::new());
+        let lines: Vec<&str> = table.lines().map(str::trim_end).collect();
+        assert_eq!(lines.len(), 5, "{}", table);
+        assert!(lines[1].trim().starts_with("prod-eu (2 pods)"));
+        assert!(lines[2].contains("web-1") && lines[3].contains("web-2"));
+        assert!(lines[4].trim().starts_with("prod-us (0 pods)"));
+
+        let csv = grouped_table(OutputFormat::Csv, "cluster", groups(), Vec::new());
+        assert_eq!(csv, "name,CLUSTER\nweb-1,prod-eu\nweb-2,prod-eu");
+
+        // a section per cluster even when no cluster has any objects
+        let empty = grouped_table(
+            OutputFormat::Table,
+            "cluster",
+            vec![group("prod-eu", Vec::new()), group("prod-us", Vec::new())],
+            Vec::new(),
+        );
+        assert!(
+            empty.contains("prod-eu (0 pods)") && empty.contains("prod-us (0 pods)"),
+            "{:?}",
+            empty
+        );
+    }
+
+    #[test]
+    fn cluster_list_round_trip() {
+        let ar = kube::core::ApiResource::erase::<k8s_openapi::api::core::v1::Secret>(&());
+        let lr = ListResponse {
+            clustername: "east".into(),
+            kind: "Secret".into(),
+            api_version: "v1".into(),
+            object_list: kube::core::ObjectList {
+                metadata: Default::default(),
+                items: vec![DynamicObject::new("db", &ar).data(serde_json::json!({
+                    "data": {"password": "aHVudGVyMg=="},
+                }))],
+            },
+            truncated: Some(Truncated::More(3)),
+            cached: None,
+        };
+        let list = ClusterList::from(lr);
+        let json = serde_json::to_value(&list).unwrap();
+        assert_eq!(json["cluster"], "east");
+        assert_eq!(json["apiVersion"], "v1");
+        assert_eq!(json["items"][0]["metadata"]["name"], "db");
+        assert_eq!(json["truncated"], serde_json::json!({"more": 3}));
+        let read: ClusterList = serde_json::from_value(json).unwrap();
+        assert_eq!(read, list);
+
+        let json = serde_json::to_value(ClusterList {
+            truncated: None,
+            ..list.clone()
+        })
+        .unwrap();
+        assert!(json.get("truncated").is_none());
+
+        // output prints every object with where it came from, whatever the page size
+        let objects: Vec<ClusterObject> = list.objects().collect();
+        assert_eq!(objects.len(), 1);
+        assert_eq!(objects[0].object.data["data"]["password"], "<redacted>");
+        let json = serde_json::to_value(&objects[0]).unwrap();
+        assert_eq!(json["cluster"], "east");
+        assert_eq!(json["kind"], "Secret");
+        assert_eq!(json["apiVersion"], "v1");
+        assert_eq!(json["object"]["metadata"]["name"], "db");
+        let read: ClusterObject = serde_json::from_value(json).unwrap();
+        assert_eq!(read, objects[0]);
+    }
+
+    #[test]
+    fn quote_csv_fields() {
+        assert_eq!(csv_field("east"), "east");
+        assert_eq!(csv_field("80/TCP,443/TCP"), "\"80/TCP,443/TCP\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
 }
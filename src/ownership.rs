@@ -0,0 +1,193 @@
+use std::{collections::HashMap, fmt::Display};
+
+use kube::{core::DynamicObject, ResourceExt};
+
+/// Resources searched for objects owned by another object. Covers the controllers shipped with
+/// Kubernetes, e.g. Deployment -> ReplicaSet -> Pod and CronJob -> Job -> Pod.
+pub const CHILD_RESOURCES: &str = "replicasets,pods,jobs,controllerrevisions";
+
+/// An object found by following ownerReferences down from a root object
+#[derive(Clone, Debug)]
+pub struct Descendant {
+    pub kind: String,
+    /// Distance from the root, direct children have a depth of 1
+    pub depth: usize,
+    pub object: DynamicObject,
+}
+
+/// Returns every candidate owned directly or transitively by the object with `root_uid`, in
+/// depth first order so that each object is followed by its own descendants. Candidates are
+/// `(kind, object)` pairs from the same cluster.
+pub fn descendants(root_uid: &str, candidates: Vec<(String, DynamicObject)>) -> Vec<Descendant> {
+    let mut by_owner: HashMap<String, Vec<(String, DynamicObject)>> = HashMap::new();
+    for (kind, object) in candidates {
+        for owner in object.owner_references() {
+            by_owner
+                .entry(owner.uid.clone())
+                .or_default()
+                .push((kind.clone(), object.clone()));
+        }
+    }
+
+    let mut found = Vec::new();
+    walk_children(root_uid, 0, &mut by_owner, &mut found);
+    found
+}
+
+// Adds the children of the object with `uid` to `found`, each followed by its own descendants.
+// Owners are removed from `by_owner` once visited, so ownership cycles cannot recurse forever.
+fn walk_children(
+    uid: &str,
+    depth: usize,
+    by_owner: &mut HashMap<String, Vec<(String, DynamicObject)>>,
+    found: &mut Vec<Descendant>,
+) {
+    let Some(mut children) = by_owner.remove(uid) else {
+        return;
+    };
+    children.sort_by_key(|(kind, object)| (kind.clone(), object.name_any()));
+    for (kind, object) in children {
+        let child_uid = object.uid();
+        found.push(Descendant {
+            kind,
+            depth: depth + 1,
+            object,
+        });
+        if let Some(child_uid) = child_uid {
+            walk_children(&child_uid, depth + 1, by_owner, found);
+        }
+    }
+}
+
+/// The workload a pod belongs to
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Owner {
+    pub kind: String,
+    pub name: String,
+}
+
+impl Display for Owner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.kind, self.name)
+    }
+}
+
+/// Returns the controller of a pod from its ownerReferences. Pods of a Deployment are owned by
+/// one of its ReplicaSets, named after the Deployment and the pod-template-hash label of the
+/// pods, so those resolve to the Deployment without looking up the ReplicaSet.
+pub fn pod_owner(pod: &DynamicObject) -> Option<Owner> {
+    let owner = pod
+        .owner_references()
+        .iter()
+        .find(|owner| owner.controller == Some(true))?;
+    let deployment = pod
+        .labels()
+        .get("pod-template-hash")
+        .filter(|_| owner.kind == "ReplicaSet")
+        .and_then(|hash| owner.name.strip_suffix(&format!("-{}", hash)));
+    Some(match deployment {
+        Some(deployment) => Owner {
+            kind: "Deployment".into(),
+            name: deployment.to_owned(),
+        },
+        None => Owner {
+            kind: owner.kind.clone(),
+            name: owner.name.clone(),
+        },
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::OwnerReference;
+    use kube::discovery::ApiResource;
+
+    fn object(name: &str, uid: &str, owner: Option<&str>) -> DynamicObject {
+        let ar = ApiResource::erase::<k8s_openapi::api::core::v1::Pod>(&());
+        let mut object = DynamicObject::new(name, &ar);
+        object.metadata.uid = Some(uid.into());
+        if let Some(owner) = owner {
+            object.metadata.owner_references = Some(vec![OwnerReference {
+                uid: owner.into(),
+                ..Default::default()
+            }]);
+        }
+        object
+    }
+
+    #[test]
+    fn transitive_descendants() {
+        let candidates = vec![
+            ("Pod".to_string(), object("web-abc-1", "p1", Some("rs1"))),
+            (
+                "ReplicaSet".to_string(),
+                object("web-abc", "rs1", Some("d1")),
+            ),
+            ("Pod".to_string(), object("other", "p2", Some("rs2"))),
+        ];
+        let found = descendants("d1", candidates);
+        let names: Vec<(String, usize)> = found
+            .iter()
+            .map(|d| (d.object.name_any(), d.depth))
+            .collect();
+        assert_eq!(
+            names,
+            vec![("web-abc".to_string(), 1), ("web-abc-1".to_string(), 2)]
+        );
+
+        // children are listed right below their parent rather than after all of its siblings
+        let candidates = vec![
+            ("Pod".to_string(), object("web-new-1", "p3", Some("rs2"))),
+            ("Pod".to_string(), object("web-old-1", "p1", Some("rs1"))),
+            (
+                "ReplicaSet".to_string(),
+                object("web-new", "rs2", Some("d1")),
+            ),
+            (
+                "ReplicaSet".to_string(),
+                object("web-old", "rs1", Some("d1")),
+            ),
+            ("Pod".to_string(), object("web-old-2", "p2", Some("rs1"))),
+        ];
+        let names: Vec<String> = descendants("d1", candidates)
+            .iter()
+            .map(|d| d.object.name_any())
+            .collect();
+        assert_eq!(
+            names,
+            ["web-new", "web-new-1", "web-old", "web-old-1", "web-old-2"]
+        );
+    }
+
+    #[test]
+    fn owner_of_pods() {
+        let owned = |kind: &str, name: &str, hash: Option<&str>| {
+            let mut pod = object("pod", "p1", None);
+            pod.metadata.owner_references = Some(vec![OwnerReference {
+                kind: kind.into(),
+                name: name.into(),
+                controller: Some(true),
+                ..Default::default()
+            }]);
+            if let Some(hash) = hash {
+                pod.labels_mut()
+                    .insert("pod-template-hash".into(), hash.into());
+            }
+            pod_owner(&pod).map(|owner| owner.to_string())
+        };
+        assert_eq!(
+            owned("ReplicaSet", "web-5d9f7", Some("5d9f7")).as_deref(),
+            Some("Deployment/web")
+        );
+        assert_eq!(
+            owned("ReplicaSet", "standalone", None).as_deref(),
+            Some("ReplicaSet/standalone")
+        );
+        assert_eq!(
+            owned("StatefulSet", "db", None).as_deref(),
+            Some("StatefulSet/db")
+        );
+        assert_eq!(pod_owner(&object("bare", "p2", None)), None);
+    }
+}
@@ -0,0 +1,261 @@
+use std::{collections::HashSet, fmt::Display};
+
+use kube::{core::DynamicObject, Error as KubeError, ResourceExt};
+use serde::{Deserialize, Serialize};
+
+/// Times a list restarts from the beginning after its continue token expired before giving up
+pub const MAX_LIST_RESTARTS: u32 = 3;
+
+/// Whether a list failed because its continue token expired, which happens when paging takes
+/// longer than the API server keeps the snapshot around. The list can only start over.
+pub fn is_expired_continue(e: &KubeError) -> bool {
+    matches!(e, KubeError::Api(response) if response.code == 410)
+}
+
+/// Annotations larger than this are dropped from objects listed only to be shown as tables,
+/// such as the last applied configuration kubectl keeps
+pub const MAX_TRIMMED_ANNOTATION: usize = 1024;
+
+/// Drops what tables never show but can make up most of an object, its managed fields and
+/// large annotations, so lists of huge objects take less memory while they are held
+pub fn trim_object(object: &mut DynamicObject) {
+    object.metadata.managed_fields = None;
+    if let Some(annotations) = object.metadata.annotations.as_mut() {
+        annotations.retain(|_, value| value.len() <= MAX_TRIMMED_ANNOTATION);
+    }
+}
+
+/// Identity of a listed object, its UID or its namespace and name when it has none
+pub fn object_key(object: &DynamicObject) -> String {
+    match object.uid() {
+        Some(uid) => uid,
+        None => format!(
+            "{}/{}",
+            object.namespace().unwrap_or_default(),
+            object.name_any()
+        ),
+    }
+}
+
+/// Sort key matching the order API servers list objects in, so rows of a list that had to
+/// start over still come out in namespace and name order
+pub fn sort_key(object: &DynamicObject) -> (String, String) {
+    (object.namespace().unwrap_or_default(), object.name_any())
+}
+
+/// The objects a paginated list of one kind on one cluster has yielded so far. A list starting
+/// over after its continue token expired yields objects of earlier pages again, possibly
+/// modified in between, and those are dropped so every object is yielded exactly once.
+#[derive(Debug, Default)]
+pub struct PageTracker {
+    seen: HashSet<String>,
+}
+
+impl PageTracker {
+    /// Drops the objects of a page that were already yielded, returning how many were dropped
+    pub fn reconcile(&mut self, items: &mut Vec<DynamicObject>) -> usize {
+        let before = items.len();
+        items.retain(|object| self.seen.insert(object_key(object)));
+        before - items.len()
+    }
+}
+
+/// Objects a cluster still had to list when its list stopped at the object cap
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Truncated {
+    /// The API server reported how many objects remain
+    More(i64),
+    /// The API server did not say how many remain, as with label selectors
+    Unknown,
+}
+
+impl Display for Truncated {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Truncated::More(more) => write!(f, "truncated, {} more", more),
+            Truncated::Unknown => write!(f, "truncated, more not listed"),
+        }
+    }
+}
+
+/// Caps the objects a paginated list of one kind on one cluster yields, so that listing a huge
+/// kind such as events on many clusters does not exhaust memory
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ObjectCap {
+    max: Option<usize>,
+    listed: usize,
+}
+
+impl ObjectCap {
+    pub fn new(max: Option<usize>) -> Self {
+        Self { max, listed: 0 }
+    }
+
+    /// The page size to request, never asking for more objects than the cap leaves
+    pub fn limit(&self, page_size: u32) -> u32 {
+        match self.max {
+            Some(max) => {
+                let left = max.saturating_sub(self.listed).max(1);
+                page_size.min(u32::try_from(left).unwrap_or(u32::MAX))
+            }
+            None => page_size,
+        }
+    }
+
+    /// Counts the objects of a page. Once the cap is reached while pages are left the list is
+    /// truncated, with `remaining` being the count of objects left the API server reported.
+    pub fn count(
+        &mut self,
+        objects: usize,
+        more_pages: bool,
+        remaining: Option<i64>,
+    ) -> Option<Truncated> {
+        self.listed += objects;
+        let max = self.max?;
+        let truncated = match remaining {
+            Some(more) => Truncated::More(more),
+            None => Truncated::Unknown,
+        };
+        (more_pages && self.listed >= max).then_some(truncated)
+    }
+
+    /// Counts the objects of a page read back from the response cache, which may hold more than
+    /// the cap leaves, dropping those over it. `remaining` is the count of objects on the pages
+    /// after it.
+    pub fn take<T>(&mut self, items: &mut Vec<T>, remaining: usize) -> Option<Truncated> {
+        let left = self
+            .max
+            .map_or(usize::MAX, |max| max.saturating_sub(self.listed));
+        let dropped = items.len().saturating_sub(left);
+        items.truncate(left);
+        let more = dropped + remaining;
+        self.count(items.len(), more > 0, i64::try_from(more).ok())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use kube::core::ErrorResponse;
+
+    fn object(name: &str, uid: Option<&str>, version: &str) -> DynamicObject {
+        serde_json::from_value(serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMap",
+            "metadata": {"name": name, "namespace": "shop", "uid": uid, "resourceVersion": version},
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn reconcile_restarted_pages() {
+        let mut tracker = PageTracker::default();
+        let mut yielded = Vec::new();
+        let mut first = vec![object("a", Some("1"), "10"), object("c", Some("3"), "10")];
+        assert_eq!(tracker.reconcile(&mut first), 0);
+        yielded.extend(first);
+
+        // the continue token expired, meanwhile c was modified, b created and d recreated under
+        // a new UID, so the restarted list repeats a and c next to the new objects
+        let mut restarted = vec![
+            object("a", Some("1"), "10"),
+            object("b", Some("2"), "11"),
+            object("c", Some("3"), "12"),
+        ];
+        assert_eq!(tracker.reconcile(&mut restarted), 2);
+        yielded.extend(restarted);
+        let mut last = vec![object("d", Some("4"), "13"), object("d", Some("5"), "14")];
+        assert_eq!(tracker.reconcile(&mut last), 0);
+        yielded.extend(last);
+
+        yielded.sort_by_key(sort_key);
+        let names: Vec<(String, String)> = yielded
+            .iter()
+            .map(|o| (o.name_any(), o.resource_version().unwrap()))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("a".into(), "10".into()),
+                ("b".into(), "11".into()),
+                ("c".into(), "10".into()),
+                ("d".into(), "13".into()),
+                ("d".into(), "14".into()),
+            ]
+        );
+
+        // objects without a UID fall back to their namespace and name
+        let mut without_uid = vec![object("e", None, "1"), object("e", None, "2")];
+        assert_eq!(tracker.reconcile(&mut without_uid), 1);
+        assert_eq!(object_key(&without_uid[0]), "shop/e");
+
+        let expired = KubeError::Api(ErrorResponse {
+            status: "Failure".into(),
+            message: "The provided continue parameter is too old".into(),
+            reason: "Expired".into(),
+            code: 410,
+        });
+        assert!(is_expired_continue(&expired));
+    }
+
+    #[test]
+    fn trim_listed_objects() {
+        let mut object: DynamicObject = serde_json::from_value(serde_json::json!({
+            "apiVersion": "apps/v1",
+            "kind": "Deployment",
+            "metadata": {
+                "name": "web",
+                "namespace": "shop",
+                "annotations": {
+                    "deployment.kubernetes.io/revision": "4",
+                    "kubectl.kubernetes.io/last-applied-configuration": "x".repeat(4096),
+                },
+                "managedFields": [{"manager": "kubectl", "operation": "Apply"}],
+            },
+            "spec": {"replicas": 3},
+        }))
+        .unwrap();
+        trim_object(&mut object);
+        assert!(object.metadata.managed_fields.is_none());
+        let annotations: Vec<&String> = object.annotations().keys().collect();
+        assert_eq!(annotations, ["deployment.kubernetes.io/revision"]);
+        assert_eq!(object.data["spec"]["replicas"], 3);
+    }
+
+    #[test]
+    fn cap_objects_per_cluster() {
+        let mut uncapped = ObjectCap::new(None);
+        assert_eq!(uncapped.limit(500), 500);
+        assert_eq!(uncapped.count(500, true, Some(10_000)), None);
+
+        let mut cap = ObjectCap::new(Some(1200));
+        assert_eq!(cap.limit(500), 500);
+        assert_eq!(cap.count(500, true, Some(2000)), None);
+        assert_eq!(cap.count(500, true, Some(1500)), None);
+        // the last page only asks for what the cap leaves
+        assert_eq!(cap.limit(500), 200);
+        let truncated = cap.count(200, true, Some(1300));
+        assert_eq!(truncated, Some(Truncated::More(1300)));
+        assert_eq!(truncated.unwrap().to_string(), "truncated, 1300 more");
+
+        // a list ending right at the cap is complete
+        let mut cap = ObjectCap::new(Some(100));
+        assert_eq!(cap.count(100, false, None), None);
+
+        let mut cap = ObjectCap::new(Some(100));
+        assert_eq!(cap.count(100, true, None), Some(Truncated::Unknown));
+
+        // cached pages were listed without the cap and are cut down to it
+        let mut cap = ObjectCap::new(Some(3));
+        let mut page = vec![1, 2];
+        assert_eq!(cap.take(&mut page, 4), None);
+        let mut page = vec![3, 4];
+        assert_eq!(cap.take(&mut page, 2), Some(Truncated::More(3)));
+        assert_eq!(page, [3]);
+        let mut uncapped = ObjectCap::new(None);
+        let mut page = vec![1, 2];
+        assert_eq!(uncapped.take(&mut page, 2), None);
+        assert_eq!(page.len(), 2);
+    }
+}
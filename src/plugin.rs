@@ -0,0 +1,131 @@
+use std::{
+    io::ErrorKind,
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::OnceLock,
+};
+
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use serde::Serialize;
+
+use crate::output::OutputFormat;
+
+/// Output plugins are executables on PATH named with this prefix followed by the plugin name
+pub const PLUGIN_PREFIX: &str = "kubemc-format-";
+
+static OUTPUT_PLUGIN: OnceLock<String> = OnceLock::new();
+
+/// Parses an --output value, either one of the built in formats or `plugin:<name>`
+pub fn parse_output_format(format: &str) -> Result<OutputFormat> {
+    if let Some(name) = plugin_name(format) {
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            bail!("invalid output plugin name {:?}", name);
+        }
+        return Ok(OutputFormat::Plugin);
+    }
+    OutputFormat::from_str(format, true).map_err(|_| {
+        anyhow!(
+            "invalid output format {}, expected table, wide, json, yaml, csv, jsonl or plugin:<name>",
+            format
+        )
+    })
+}
+
+/// Name of the plugin in an --output value of the form `plugin:<name>`
+pub fn plugin_name(format: &str) -> Option<&str> {
+    format.strip_prefix("plugin:")
+}
+
+/// Selects the plugin that plugin output is piped to
+pub fn set_output_plugin(name: &str) {
+    let _ = OUTPUT_PLUGIN.set(name.to_owned());
+}
+
+/// A running output plugin. It reads the results as JSON on stdin, the same JSON `-o json`
+/// prints, and writes whatever it likes to the terminal.
+pub struct OutputPlugin {
+    program: String,
+    child: Child,
+}
+
+impl OutputPlugin {
+    /// Starts the plugin selected with `-o plugin:<name>`
+    pub fn spawn() -> Result<Self> {
+        let name = OUTPUT_PLUGIN
+            .get()
+            .ok_or_else(|| anyhow!("no output plugin selected"))?;
+        Self::spawn_program(format!("{}{}", PLUGIN_PREFIX, name))
+    }
+
+    fn spawn_program(program: String) -> Result<Self> {
+        let child = match Command::new(&program).stdin(Stdio::piped()).spawn() {
+            Ok(child) => child,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                bail!("output plugin {} not found on PATH", program)
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("failed to run output plugin {}", program))
+            }
+        };
+        Ok(Self { program, child })
+    }
+
+    /// Where the JSON for the plugin is written
+    pub fn stdin(&mut self) -> &mut ChildStdin {
+        self.child.stdin.as_mut().expect("plugin stdin is piped")
+    }
+
+    /// Closes the plugin's stdin and waits for it, failing when it does not exit successfully
+    pub fn finish(mut self) -> Result<()> {
+        drop(self.child.stdin.take());
+        let status = self
+            .child
+            .wait()
+            .with_context(|| format!("failed to wait for output plugin {}", self.program))?;
+        if !status.success() {
+            bail!("output plugin {} exited with {}", self.program, status);
+        }
+        Ok(())
+    }
+}
+
+/// Pipes `value` as JSON to the selected output plugin
+pub fn print_plugin<T: Serialize>(value: &T) -> Result<()> {
+    let mut plugin = OutputPlugin::spawn()?;
+    let written = serde_json::to_writer_pretty(plugin.stdin(), value);
+    // a plugin exiting early breaks the pipe, its exit status explains more than the write error
+    plugin.finish()?;
+    Ok(written?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn output_plugins() {
+        assert_eq!(parse_output_format("csv").unwrap(), OutputFormat::Csv);
+        assert_eq!(
+            parse_output_format("plugin:team-view").unwrap(),
+            OutputFormat::Plugin
+        );
+        assert_eq!(plugin_name("plugin:team-view"), Some("team-view"));
+        assert!(parse_output_format("plugin").is_err());
+        assert!(parse_output_format("plugin:").is_err());
+        assert!(parse_output_format("plugin:../bin/sh").is_err());
+
+        let missing = OutputPlugin::spawn_program(format!("{}missing-for-test", PLUGIN_PREFIX));
+        assert_eq!(
+            missing.err().unwrap().to_string(),
+            "output plugin kubemc-format-missing-for-test not found on PATH"
+        );
+
+        let mut failing = OutputPlugin::spawn_program("false".into()).unwrap();
+        let _ = serde_json::to_writer(failing.stdin(), &["a"]);
+        assert!(failing.finish().is_err());
+    }
+}
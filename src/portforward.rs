@@ -0,0 +1,272 @@
+use std::{net::Ipv4Addr, str::FromStr};
+
+use anyhow::{anyhow, Context, Result};
+use k8s_openapi::{
+    api::core::v1::{Pod, Service},
+    apimachinery::pkg::util::intstr::IntOrString,
+};
+use kube::{api::ListParams, Api, Client as KubeClient, ResourceExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::log::{debug, warn};
+
+use crate::index::names_kind;
+
+/// A local port forwarded to a port of the target, written as `LOCAL:REMOTE` or just `PORT` when
+/// both are the same
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PortMapping {
+    pub local: u16,
+    pub remote: u16,
+}
+
+impl FromStr for PortMapping {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let parse = |port: &str| {
+            port.parse::<u16>()
+                .map_err(|_| anyhow!("invalid port {} in {}", port, s))
+        };
+        match s.split_once(':') {
+            Some((local, remote)) => Ok(PortMapping {
+                local: parse(local)?,
+                remote: parse(remote)?,
+            }),
+            None => {
+                let port = parse(s)?;
+                Ok(PortMapping {
+                    local: port,
+                    remote: port,
+                })
+            }
+        }
+    }
+}
+
+impl PortMapping {
+    /// Shifts the local port, used to give every cluster its own range of local ports
+    pub fn offset(self, offset: u16) -> Result<Self> {
+        let local = self
+            .local
+            .checked_add(offset)
+            .ok_or_else(|| anyhow!("local port {} + {} is out of range", self.local, offset))?;
+        Ok(PortMapping { local, ..self })
+    }
+}
+
+/// What to forward to, written as `pod/NAME`, `svc/NAME` or a bare pod name
+#[derive(Clone, Debug, PartialEq)]
+pub enum Target {
+    Pod(String),
+    Service(String),
+}
+
+impl FromStr for Target {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.split_once('/') {
+            None => Ok(Target::Pod(s.to_owned())),
+            Some((kind, name)) if names_kind(kind, "Pod") => Ok(Target::Pod(name.to_owned())),
+            Some((kind, name)) if names_kind(kind, "Service") => {
+                Ok(Target::Service(name.to_owned()))
+            }
+            Some((kind, _)) => Err(anyhow!(
+                "cannot port-forward to {}, only pods and services are supported",
+                kind
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::Pod(name) => write!(f, "pod/{}", name),
+            Target::Service(name) => write!(f, "svc/{}", name),
+        }
+    }
+}
+
+/// A pod on a single cluster along with the pod ports to forward to
+pub struct Forward {
+    pub clustername: String,
+    pub pod: String,
+    pub ports: Vec<PortMapping>,
+    api: Api<Pod>,
+}
+
+impl Forward {
+    /// Finds the pod backing `target` on a cluster. Returns None when the target does not exist
+    /// there. Service ports are translated to the target ports of the chosen pod.
+    pub async fn resolve(
+        clustername: &str,
+        client: KubeClient,
+        namespace: &str,
+        target: &Target,
+        ports: &[PortMapping],
+    ) -> Result<Option<Self>> {
+        let api: Api<Pod> = Api::namespaced(client.clone(), namespace);
+        let (pod, ports) = match target {
+            Target::Pod(name) => match api.get_opt(name).await? {
+                Some(pod) => (pod, ports.to_vec()),
+                None => return Ok(None),
+            },
+            Target::Service(name) => {
+                let services: Api<Service> = Api::namespaced(client, namespace);
+                let Some(service) = services.get_opt(name).await? else {
+                    return Ok(None);
+                };
+                let pod = service_pod(&api, &service).await?;
+                let ports = ports
+                    .iter()
+                    .map(|mapping| {
+                        Ok(PortMapping {
+                            remote: service_target_port(&service, &pod, mapping.remote)?,
+                            ..*mapping
+                        })
+                    })
+                    .collect::<Result<_>>()?;
+                (pod, ports)
+            }
+        };
+        Ok(Some(Forward {
+            clustername: clustername.to_owned(),
+            pod: pod.name_any(),
+            ports,
+            api,
+        }))
+    }
+
+    /// Listens on the local ports and forwards every accepted connection to the pod in the
+    /// background. Forwarding stops when the runtime shuts down.
+    pub async fn start(&self) -> Result<()> {
+        for mapping in &self.ports {
+            let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, mapping.local))
+                .await
+                .with_context(|| format!("failed to listen on port {}", mapping.local))?;
+            println!(
+                "Forwarding from 127.0.0.1:{} -> {}/{}:{}",
+                mapping.local, self.clustername, self.pod, mapping.remote
+            );
+            let api = self.api.clone();
+            let pod = self.pod.clone();
+            let remote = mapping.remote;
+            tokio::spawn(async move {
+                loop {
+                    let connection = match listener.accept().await {
+                        Ok((connection, _)) => connection,
+                        Err(e) => {
+                            warn!("failed to accept connection: {}", e);
+                            continue;
+                        }
+                    };
+                    let (api, pod) = (api.clone(), pod.clone());
+                    tokio::spawn(async move {
+                        if let Err(e) = forward_connection(&api, &pod, remote, connection).await {
+                            warn!("failed to forward to {}:{}: {}", pod, remote, e);
+                        }
+                    });
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+async fn forward_connection(
+    api: &Api<Pod>,
+    pod: &str,
+    port: u16,
+    mut connection: TcpStream,
+) -> Result<()> {
+    let mut forwarder = api.portforward(pod, &[port]).await?;
+    let mut upstream = forwarder
+        .take_stream(port)
+        .ok_or_else(|| anyhow!("port {} was not forwarded", port))?;
+    let (sent, received) = tokio::io::copy_bidirectional(&mut connection, &mut upstream).await?;
+    debug!(
+        "connection to {}:{} closed after {} bytes sent, {} received",
+        pod, port, sent, received
+    );
+    drop(upstream);
+    forwarder.join().await?;
+    Ok(())
+}
+
+// Picks a running pod selected by the service
+async fn service_pod(api: &Api<Pod>, service: &Service) -> Result<Pod> {
+    let selector = service
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.selector.as_ref())
+        .filter(|selector| !selector.is_empty())
+        .ok_or_else(|| anyhow!("service {} has no selector", service.name_any()))?;
+    let selector = selector
+        .iter()
+        .map(|(k, v)| format!("{}={}", k, v))
+        .collect::<Vec<_>>()
+        .join(",");
+    let pods = api.list(&ListParams::default().labels(&selector)).await?;
+    pods.items
+        .into_iter()
+        .find(|pod| {
+            pod.metadata.deletion_timestamp.is_none()
+                && pod.status.as_ref().and_then(|s| s.phase.as_deref()) == Some("Running")
+        })
+        .ok_or_else(|| anyhow!("no running pods back service {}", service.name_any()))
+}
+
+// Translates a service port to the container port it targets on the pod
+fn service_target_port(service: &Service, pod: &Pod, port: u16) -> Result<u16> {
+    let service_port = service
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.ports.as_ref())
+        .and_then(|ports| ports.iter().find(|p| p.port == i32::from(port)))
+        .ok_or_else(|| anyhow!("service {} has no port {}", service.name_any(), port))?;
+    let target = match &service_port.target_port {
+        None => i32::from(port),
+        Some(IntOrString::Int(target)) => *target,
+        Some(IntOrString::String(name)) => pod
+            .spec
+            .iter()
+            .flat_map(|spec| &spec.containers)
+            .flat_map(|container| container.ports.iter().flatten())
+            .find(|p| p.name.as_deref() == Some(name.as_str()))
+            .map(|p| p.container_port)
+            .ok_or_else(|| anyhow!("pod {} has no port named {}", pod.name_any(), name))?,
+    };
+    u16::try_from(target).map_err(|_| anyhow!("invalid target port {}", target))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_port_mappings() {
+        assert_eq!(
+            "8080:80".parse::<PortMapping>().unwrap(),
+            PortMapping {
+                local: 8080,
+                remote: 80
+            }
+        );
+        let same: PortMapping = "5432".parse().unwrap();
+        assert_eq!((same.local, same.remote), (5432, 5432));
+        assert_eq!(same.offset(10).unwrap().local, 5442);
+        assert!(same.offset(u16::MAX).is_err());
+        assert!("http:80".parse::<PortMapping>().is_err());
+
+        assert_eq!(
+            "svc/web".parse::<Target>().unwrap(),
+            Target::Service("web".into())
+        );
+        assert_eq!(
+            "web-0".parse::<Target>().unwrap(),
+            Target::Pod("web-0".into())
+        );
+        assert!("deploy/web".parse::<Target>().is_err());
+    }
+}
@@ -0,0 +1,255 @@
+use anyhow::Result;
+use k8s_openapi::{
+    apiextensions_apiserver::pkg::apis::apiextensions::v1::{
+        CustomResourceColumnDefinition, CustomResourceDefinition,
+    },
+    apimachinery::pkg::apis::meta::v1::Time,
+    chrono::{DateTime, Utc},
+};
+use kube::{core::DynamicObject, Api, Client as KubeClient};
+use serde_json::Value;
+
+use crate::output::get_age;
+
+/// Columns of CRD objects that tables already show, so printer columns for them are left out
+const SHOWN_PATHS: &[&str] = &[".metadata.name", ".metadata.creationTimestamp"];
+
+/// Fetches the additionalPrinterColumns of the CRD serving the resource behind `resource_url`,
+/// such as `/apis/cert-manager.io/v1/namespaces/default/certificates`. Columns with a priority
+/// above 0 are only included for wide output. None when the resource is not served by a CRD.
+pub async fn fetch_printer_columns(
+    client: KubeClient,
+    resource_url: &str,
+    wide: bool,
+) -> Result<Option<Vec<CustomResourceColumnDefinition>>> {
+    let Some((crd_name, version)) = crd_name(resource_url) else {
+        return Ok(None);
+    };
+    let crds: Api<CustomResourceDefinition> = Api::all(client);
+    let Some(crd) = crds.get_opt(&crd_name).await? else {
+        return Ok(None);
+    };
+    Ok(Some(printer_columns(&crd, &version, wide)))
+}
+
+/// Printer columns of one version of a CRD, leaving out the name and age shown anyway
+pub fn printer_columns(
+    crd: &CustomResourceDefinition,
+    version: &str,
+    wide: bool,
+) -> Vec<CustomResourceColumnDefinition> {
+    crd.spec
+        .versions
+        .iter()
+        .find(|v| v.name == version)
+        .and_then(|v| v.additional_printer_columns.clone())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|column| wide || column.priority.unwrap_or_default() == 0)
+        .filter(|column| !SHOWN_PATHS.contains(&column.json_path.as_str()))
+        .collect()
+}
+
+/// Whether a printer column reads annotations, which trimming drops from listed objects when
+/// they are large
+pub fn reads_annotations(columns: &[CustomResourceColumnDefinition]) -> bool {
+    columns
+        .iter()
+        .any(|column| column.json_path.starts_with(".metadata.annotations"))
+}
+
+// The CRD name, `<plural>.<group>`, and version of a resource url. Built in resources are never
+// served by CRDs and neither are groups without a dot such as `apps`.
+fn crd_name(resource_url: &str) -> Option<(String, String)> {
+    let mut segments = resource_url.strip_prefix("/apis/")?.split('/');
+    let group = segments.next()?;
+    let version = segments.next()?;
+    let plural = segments.next_back()?;
+    group
+        .contains('.')
+        .then(|| (format!("{}.{}", plural, group), version.to_owned()))
+}
+
+/// Renders a printer column of an object the way kubectl does, dates as ages and several
+/// matches separated by commas
+pub fn column_value(column: &CustomResourceColumnDefinition, object: &DynamicObject) -> String {
+    let object = serde_json::to_value(object).unwrap_or_default();
+    json_path(&object, &column.json_path)
+        .into_iter()
+        .map(|value| match value {
+            Value::String(s) if column.type_ == "date" => match s.parse::<DateTime<Utc>>() {
+                Ok(time) => get_age(Some(Time(time))),
+                Err(_) => s.clone(),
+            },
+            Value::String(s) => s.clone(),
+            Value::Null => String::new(),
+            value => value.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// Evaluates the JSONPath subset printer columns use: fields such as `.spec.replicas`, with `\.`
+// escaping dots in keys, indexes such as `[0]`, wildcards `[*]` and filters such as
+// `[?(@.type=="Ready")]`
+fn json_path<'a>(value: &'a Value, path: &str) -> Vec<&'a Value> {
+    let mut current = vec![value];
+    let mut rest = path.trim().trim_start_matches('{').trim_end_matches('}');
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix('.') {
+            let (field, after) = split_field(after);
+            current = current.into_iter().filter_map(|v| v.get(&field)).collect();
+            rest = after;
+        } else if let Some((selector, after)) = rest
+            .strip_prefix('[')
+            .and_then(|after| after.split_once(']'))
+        {
+            current = current
+                .into_iter()
+                .flat_map(|v| select(v, selector))
+                .collect();
+            rest = after;
+        } else {
+            return Vec::new();
+        }
+    }
+    current
+}
+
+// Splits the leading field name off a path, up to the next unescaped dot or bracket
+fn split_field(path: &str) -> (String, &str) {
+    let mut field = String::new();
+    let mut chars = path.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => field.extend(chars.next().map(|(_, c)| c)),
+            '.' | '[' => return (field, &path[i..]),
+            c => field.push(c),
+        }
+    }
+    (field, "")
+}
+
+fn select<'a>(value: &'a Value, selector: &str) -> Vec<&'a Value> {
+    if selector == "*" {
+        return match value {
+            Value::Array(items) => items.iter().collect(),
+            Value::Object(fields) => fields.values().collect(),
+            _ => Vec::new(),
+        };
+    }
+    if let Some(filter) = selector
+        .strip_prefix("?(@")
+        .and_then(|filter| filter.strip_suffix(')'))
+    {
+        let Some((path, expected)) = filter.split_once("==") else {
+            return Vec::new();
+        };
+        let expected = unquote(expected);
+        return value
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|item| {
+                json_path(item, path.trim())
+                    .first()
+                    .is_some_and(|v| v.as_str().map_or(v.to_string(), str::to_owned) == expected)
+            })
+            .collect();
+    }
+    if let Ok(index) = selector.parse::<i64>() {
+        let items = value.as_array().map(Vec::as_slice).unwrap_or_default();
+        let index = if index < 0 {
+            items.len() as i64 + index
+        } else {
+            index
+        };
+        return usize::try_from(index)
+            .ok()
+            .and_then(|i| items.get(i))
+            .into_iter()
+            .collect();
+    }
+    value.get(unquote(selector)).into_iter().collect()
+}
+
+fn unquote(s: &str) -> &str {
+    s.trim().trim_matches(|c| c == '"' || c == '\'')
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn crd_printer_columns() {
+        assert_eq!(
+            crd_name("/apis/cert-manager.io/v1/namespaces/default/certificates"),
+            Some(("certificates.cert-manager.io".into(), "v1".into()))
+        );
+        assert_eq!(
+            crd_name("/apis/apps/v1/namespaces/default/deployments"),
+            None
+        );
+        assert_eq!(crd_name("/api/v1/pods"), None);
+
+        let crd: CustomResourceDefinition = serde_json::from_value(serde_json::json!({
+            "metadata": {"name": "certificates.cert-manager.io"},
+            "spec": {
+                "group": "cert-manager.io",
+                "names": {"kind": "Certificate", "plural": "certificates"},
+                "scope": "Namespaced",
+                "versions": [{
+                    "name": "v1",
+                    "served": true,
+                    "storage": true,
+                    "additionalPrinterColumns": [
+                        {"name": "Ready", "type": "string", "jsonPath": ".status.conditions[?(@.type==\"Ready\")].status"},
+                        {"name": "Secret", "type": "string", "jsonPath": ".spec.secretName"},
+                        {"name": "Issuer", "type": "string", "jsonPath": ".spec.issuerRef.name", "priority": 1},
+                        {"name": "Hosts", "type": "string", "jsonPath": ".spec.dnsNames[*]"},
+                        {"name": "Age", "type": "date", "jsonPath": ".metadata.creationTimestamp"},
+                    ],
+                }],
+            },
+        }))
+        .unwrap();
+        let columns = printer_columns(&crd, "v1", false);
+        let names: Vec<&str> = columns.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["Ready", "Secret", "Hosts"]);
+        assert_eq!(printer_columns(&crd, "v1", true).len(), 4);
+        assert!(printer_columns(&crd, "v1beta1", true).is_empty());
+        assert!(!reads_annotations(&columns));
+        let mut team = columns[0].clone();
+        team.json_path = r".metadata.annotations.example\.com/team".into();
+        assert!(reads_annotations(&[team]));
+
+        let certificate: DynamicObject = serde_json::from_value(serde_json::json!({
+            "apiVersion": "cert-manager.io/v1",
+            "kind": "Certificate",
+            "metadata": {"name": "web", "annotations": {"example.com/team": "edge"}},
+            "spec": {"secretName": "web-tls", "dnsNames": ["a.example.com", "b.example.com"]},
+            "status": {"conditions": [
+                {"type": "Issuing", "status": "False"},
+                {"type": "Ready", "status": "True"},
+            ]},
+        }))
+        .unwrap();
+        let values: Vec<String> = columns
+            .iter()
+            .map(|c| column_value(c, &certificate))
+            .collect();
+        assert_eq!(values, ["True", "web-tls", "a.example.com,b.example.com"]);
+
+        let value = serde_json::to_value(&certificate).unwrap();
+        assert_eq!(
+            json_path(&value, r".metadata.annotations.example\.com/team"),
+            [&Value::from("edge")]
+        );
+        assert_eq!(
+            json_path(&value, ".spec.dnsNames[-1]"),
+            [&Value::from("b.example.com")]
+        );
+        assert!(json_path(&value, ".spec.missing").is_empty());
+    }
+}
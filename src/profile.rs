@@ -0,0 +1,89 @@
+use std::{collections::BTreeMap, sync::Mutex, time::Duration};
+
+use crate::{
+    output::{table_string, ProfileOutput},
+    telemetry::format_millis,
+};
+
+static TIMINGS: Mutex<BTreeMap<String, ClusterTimings>> = Mutex::new(BTreeMap::new());
+
+/// Time spent on one cluster during the command
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ClusterTimings {
+    /// Connecting, authenticating and resolving resources
+    pub connect: Duration,
+    /// Waiting for list pages, summed over every page
+    pub list: Duration,
+    /// List pages requested
+    pub pages: u32,
+    /// Objects listed
+    pub objects: usize,
+}
+
+/// Records how long creating the clients of a cluster took
+pub fn record_connect(clustername: &str, elapsed: Duration) {
+    TIMINGS
+        .lock()
+        .unwrap()
+        .entry(clustername.to_owned())
+        .or_default()
+        .connect += elapsed;
+}
+
+/// Records a list page fetched from a cluster
+pub fn record_page(clustername: &str, elapsed: Duration, objects: usize) {
+    let mut timings = TIMINGS.lock().unwrap();
+    let timing = timings.entry(clustername.to_owned()).or_default();
+    timing.list += elapsed;
+    timing.pages += 1;
+    timing.objects += objects;
+}
+
+/// Timings recorded so far per cluster
+pub fn timings() -> BTreeMap<String, ClusterTimings> {
+    TIMINGS.lock().unwrap().clone()
+}
+
+/// Table of the recorded timings, slowest cluster first
+pub fn report() -> String {
+    let mut timings: Vec<(String, ClusterTimings)> = timings().into_iter().collect();
+    timings.sort_by_key(|(_, timing)| std::cmp::Reverse(timing.connect + timing.list));
+    let rows: Vec<ProfileOutput> = timings
+        .into_iter()
+        .map(|(clustername, timing)| ProfileOutput {
+            clustername,
+            connect: format_millis(millis(timing.connect)),
+            list: format_millis(millis(timing.list)),
+            pages: timing.pages,
+            objects: timing.objects,
+        })
+        .collect();
+    table_string(rows)
+}
+
+fn millis(duration: Duration) -> u64 {
+    u64::try_from(duration.as_millis()).unwrap_or(u64::MAX)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn record_cluster_timings() {
+        record_connect("slow", Duration::from_millis(1500));
+        record_page("slow", Duration::from_millis(200), 500);
+        record_page("slow", Duration::from_millis(300), 20);
+        record_connect("fast", Duration::from_millis(40));
+
+        let slow = timings()["slow"];
+        assert_eq!(slow.list, Duration::from_millis(500));
+        assert_eq!((slow.pages, slow.objects), (2, 520));
+
+        let report = report();
+        let slow_line = report.lines().position(|line| line.contains("slow"));
+        let fast_line = report.lines().position(|line| line.contains("fast"));
+        assert!(slow_line < fast_line);
+        assert!(report.contains("1.5s"));
+    }
+}
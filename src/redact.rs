@@ -0,0 +1,129 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use kube::core::DynamicObject;
+use serde_json::Value;
+
+use crate::diff::Change;
+
+/// Shown in place of the values of Secrets
+pub const REDACTED: &str = "<redacted>";
+
+/// Fields of a Secret holding its values
+const SECRET_FIELDS: &[&str] = &["data", "stringData"];
+
+/// Annotation kubectl apply keeps the whole applied object in, values included
+const LAST_APPLIED: &str = "kubectl.kubernetes.io/last-applied-configuration";
+
+static SHOW_SECRET_VALUES: AtomicBool = AtomicBool::new(false);
+
+/// Leaves the values of Secrets in output, for `--show-secret-values`
+pub fn show_secret_values() {
+    SHOW_SECRET_VALUES.store(true, Ordering::Relaxed);
+}
+
+fn shown() -> bool {
+    SHOW_SECRET_VALUES.load(Ordering::Relaxed)
+}
+
+/// Masks the values of a Secret about to be printed, keeping their keys. Listed objects may lack
+/// their kind, so it is given along.
+pub fn redact(kind: &str, object: &mut Value) {
+    if shown() || kind != "Secret" {
+        return;
+    }
+    for field in SECRET_FIELDS {
+        if let Some(Value::Object(values)) = object.get_mut(*field) {
+            values
+                .values_mut()
+                .for_each(|value| *value = REDACTED.into());
+        }
+    }
+    if let Some(Value::Object(annotations)) = object.pointer_mut("/metadata/annotations") {
+        if let Some(applied) = annotations.get_mut(LAST_APPLIED) {
+            *applied = REDACTED.into();
+        }
+    }
+}
+
+/// Like `redact` for an object as fetched from a cluster
+pub fn redact_object(kind: &str, object: &mut DynamicObject) {
+    if shown() || kind != "Secret" {
+        return;
+    }
+    redact(kind, &mut object.data);
+    if let Some(applied) = object
+        .metadata
+        .annotations
+        .as_mut()
+        .and_then(|a| a.get_mut(LAST_APPLIED))
+    {
+        *applied = REDACTED.into();
+    }
+}
+
+/// Masks the values of a Secret in changes to it, such as those shown before an edit
+pub fn redact_changes(kind: &str, changes: &mut [Change]) {
+    if shown() || kind != "Secret" {
+        return;
+    }
+    for change in changes {
+        let path = change.path.as_str();
+        let secret = SECRET_FIELDS.iter().any(|field| {
+            path.strip_prefix('.')
+                .and_then(|path| path.strip_prefix(field))
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+        }) || path.contains(LAST_APPLIED);
+        if secret {
+            for value in [&mut change.old, &mut change.new].into_iter().flatten() {
+                *value = REDACTED.into();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::diff::diff;
+
+    #[test]
+    fn redact_secret_values() {
+        let mut secret = serde_json::json!({
+            "apiVersion": "v1",
+            "kind": "Secret",
+            "metadata": {
+                "name": "db",
+                "annotations": {LAST_APPLIED: "{\"data\":{\"password\":\"aHVudGVyMg==\"}}"},
+            },
+            "type": "Opaque",
+            "data": {"password": "aHVudGVyMg==", "user": "YWRtaW4="},
+            "stringData": {"token": "abc"},
+        });
+        let original = secret.clone();
+        redact("Secret", &mut secret);
+        assert_eq!(secret["data"]["password"], REDACTED);
+        assert_eq!(secret["data"]["user"], REDACTED);
+        assert_eq!(secret["stringData"]["token"], REDACTED);
+        assert_eq!(secret["metadata"]["annotations"][LAST_APPLIED], REDACTED);
+        assert_eq!(secret["type"], "Opaque");
+
+        // other kinds keep their data
+        let mut config_map = serde_json::json!({"data": {"mode": "fast"}});
+        redact("ConfigMap", &mut config_map);
+        assert_eq!(config_map["data"]["mode"], "fast");
+
+        let mut edited = original.clone();
+        edited["data"]["password"] = "bmV3".into();
+        edited["metadata"]["labels"] = serde_json::json!({"app": "db"});
+        let mut changes = diff(&original, &edited);
+        redact_changes("Secret", &mut changes);
+        let changes: Vec<String> = changes.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            changes,
+            [
+                r#".data.password: "<redacted>" -> "<redacted>""#,
+                r#".metadata.labels: <none> -> {"app":"db"}"#,
+            ]
+        );
+    }
+}
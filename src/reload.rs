@@ -0,0 +1,186 @@
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::Result;
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use tokio::sync::mpsc;
+use tracing::log::debug;
+
+use crate::config::{kubeconfig_paths, Cluster};
+
+/// How long writes to the watched files have to stop before they are reloaded, so a file
+/// rewritten in several steps is read once it is complete
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// Watches the kubemc config and kubeconfig files of a long running command for changes
+pub struct FileWatcher {
+    // watching stops when the watcher is dropped
+    _watcher: RecommendedWatcher,
+    changes: mpsc::UnboundedReceiver<PathBuf>,
+}
+
+impl FileWatcher {
+    /// Watches `files` through their directories, as kubectl and editors replace files rather
+    /// than write to them, which a watch on the file itself would lose track of
+    pub fn new(files: &[PathBuf]) -> Result<Self> {
+        let files: BTreeSet<PathBuf> = files.iter().map(|file| absolute(file)).collect();
+        let (sender, changes) = mpsc::unbounded_channel();
+        let watched = files.clone();
+        let mut watcher =
+            notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+                let Ok(event) = event else {
+                    return;
+                };
+                if event.kind.is_access() {
+                    return;
+                }
+                for path in event.paths {
+                    if watched.contains(&path) {
+                        let _ = sender.send(path);
+                    }
+                }
+            })?;
+        let dirs: BTreeSet<&Path> = files.iter().filter_map(|file| file.parent()).collect();
+        for dir in dirs {
+            if let Err(e) = watcher.watch(dir, RecursiveMode::NonRecursive) {
+                debug!("failed to watch {}: {}", dir.display(), e);
+            }
+        }
+        Ok(Self {
+            _watcher: watcher,
+            changes,
+        })
+    }
+
+    /// Waits until watched files changed and writes to them settled, returning the files
+    pub async fn changed(&mut self) -> BTreeSet<PathBuf> {
+        let mut changed = BTreeSet::new();
+        match self.changes.recv().await {
+            Some(path) => changed.insert(path),
+            None => std::future::pending().await,
+        };
+        while let Ok(Some(path)) = tokio::time::timeout(SETTLE_TIME, self.changes.recv()).await {
+            changed.insert(path);
+        }
+        changed
+    }
+}
+
+// The path with its directory resolved, matching the paths of file events
+fn absolute(path: &Path) -> PathBuf {
+    let (Some(dir), Some(name)) = (path.parent(), path.file_name()) else {
+        return path.to_path_buf();
+    };
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    match fs::canonicalize(dir) {
+        Ok(dir) => dir.join(name),
+        Err(_) => path.to_path_buf(),
+    }
+}
+
+/// The kubeconfig files the clusters are read from
+pub fn cluster_kubeconfigs(clusters: &[Cluster]) -> BTreeSet<PathBuf> {
+    clusters
+        .iter()
+        .filter(|cluster| cluster.uses_kubeconfig())
+        .flat_map(|cluster| kubeconfig_paths(cluster.kubeconfig.as_deref()))
+        .map(|path| absolute(&path))
+        .collect()
+}
+
+/// What a long running command does about a change to its files
+#[derive(Debug, Default, PartialEq)]
+pub struct Reload {
+    /// Clusters no longer in the clusterset
+    pub removed: Vec<String>,
+    /// Clusters that are new, changed in the config or read from a changed kubeconfig, to
+    /// connect to again
+    pub reconnect: Vec<Cluster>,
+}
+
+impl Reload {
+    /// Compares the clusters before and after `changed` files changed
+    pub fn plan(previous: &[Cluster], current: &[Cluster], changed: &BTreeSet<PathBuf>) -> Self {
+        let removed = previous
+            .iter()
+            .filter(|cluster| !current.iter().any(|c| c.name == cluster.name))
+            .map(|cluster| cluster.name.clone())
+            .collect();
+        let reconnect = current
+            .iter()
+            .filter(|cluster| {
+                previous.iter().all(|c| *c != **cluster)
+                    || !cluster_kubeconfigs(std::slice::from_ref(cluster)).is_disjoint(changed)
+            })
+            .cloned()
+            .collect();
+        Self { removed, reconnect }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.reconnect.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn plan_reloads() {
+        let dir = std::env::temp_dir().join(format!("kubemc-reload-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let kubeconfig = |name: &str| dir.join(name).to_string_lossy().into_owned();
+        let cluster = |name: &str, context: &str, file: &str| Cluster {
+            name: name.into(),
+            context: Some(context.into()),
+            kubeconfig: Some(kubeconfig(file)),
+            ..Default::default()
+        };
+        let previous = vec![
+            cluster("prod-us", "us", "prod"),
+            cluster("prod-eu", "eu", "prod"),
+            cluster("staging", "staging", "staging"),
+        ];
+
+        // nothing changed
+        assert!(Reload::plan(&previous, &previous, &BTreeSet::new()).is_empty());
+
+        // a cluster added, one removed and one pointed at another context
+        let current = vec![
+            cluster("prod-us", "us", "prod"),
+            cluster("prod-eu", "eu-2", "prod"),
+            cluster("dev", "dev", "staging"),
+        ];
+        let reload = Reload::plan(&previous, &current, &BTreeSet::new());
+        assert_eq!(reload.removed, ["staging"]);
+        let names: Vec<&str> = reload.reconnect.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["prod-eu", "dev"]);
+
+        // rotated credentials in a kubeconfig reconnect the clusters read from it
+        let changed = BTreeSet::from([absolute(Path::new(&kubeconfig("prod")))]);
+        let reload = Reload::plan(&previous, &previous, &changed);
+        assert!(reload.removed.is_empty());
+        let names: Vec<&str> = reload.reconnect.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, ["prod-us", "prod-eu"]);
+
+        // replacing a file is seen through its directory
+        let file = PathBuf::from(kubeconfig("prod"));
+        let mut watcher = FileWatcher::new(std::slice::from_ref(&file)).unwrap();
+        fs::write(dir.join("prod.tmp"), "rotated").unwrap();
+        fs::rename(dir.join("prod.tmp"), &file).unwrap();
+        let changed = tokio::time::timeout(Duration::from_secs(5), watcher.changed())
+            .await
+            .unwrap();
+        assert_eq!(changed, BTreeSet::from([absolute(&file)]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,175 @@
+use std::{collections::BTreeMap, fmt::Write, path::PathBuf};
+
+use anyhow::{anyhow, Context, Result};
+
+/// Formats `--report` writes results in
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    Junit,
+}
+
+/// Where `--report` writes the results of a command for CI, and in which format
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Report {
+    pub format: ReportFormat,
+    pub path: PathBuf,
+}
+
+/// Parses a --report value of the form `FORMAT=PATH`, such as `junit=report.xml`
+pub fn parse_report(value: &str) -> Result<Report> {
+    let (format, path) = value
+        .split_once('=')
+        .filter(|(_, path)| !path.is_empty())
+        .ok_or_else(|| anyhow!("invalid report {}, expected FORMAT=PATH", value))?;
+    let format = match format {
+        "junit" => ReportFormat::Junit,
+        format => return Err(anyhow!("invalid report format {}, expected junit", format)),
+    };
+    Ok(Report {
+        format,
+        path: path.into(),
+    })
+}
+
+/// One assertion a command made on one cluster, a test case of the report
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestCase {
+    pub cluster: String,
+    pub name: String,
+    /// Why the assertion failed, none when it passed
+    pub failure: Option<String>,
+}
+
+impl TestCase {
+    pub fn new(cluster: &str, name: &str, failure: Option<String>) -> Self {
+        Self {
+            cluster: cluster.to_owned(),
+            name: name.to_owned(),
+            failure,
+        }
+    }
+}
+
+impl Report {
+    /// Writes the test cases of a command such as `wait` to the report file
+    pub fn write(&self, command: &str, cases: &[TestCase]) -> Result<()> {
+        let report = match self.format {
+            ReportFormat::Junit => junit_xml(command, cases),
+        };
+        std::fs::write(&self.path, report)
+            .with_context(|| format!("failed to write report {}", self.path.display()))
+    }
+}
+
+/// Renders test cases as JUnit XML with a test suite per cluster, which CI systems show like
+/// the results of unit tests
+pub fn junit_xml(command: &str, cases: &[TestCase]) -> String {
+    let mut suites: BTreeMap<&str, Vec<&TestCase>> = BTreeMap::new();
+    for case in cases {
+        suites.entry(&case.cluster).or_default().push(case);
+    }
+    let failures = |cases: &[&TestCase]| cases.iter().filter(|c| c.failure.is_some()).count();
+    let all: Vec<&TestCase> = cases.iter().collect();
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    let _ = writeln!(
+        xml,
+        "<testsuites name=\"kubemc {}\" tests=\"{}\" failures=\"{}\">",
+        escape(command),
+        cases.len(),
+        failures(&all)
+    );
+    for (cluster, cases) in &suites {
+        let _ = writeln!(
+            xml,
+            "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\">",
+            escape(cluster),
+            cases.len(),
+            failures(cases)
+        );
+        for case in cases {
+            let _ = write!(
+                xml,
+                "    <testcase classname=\"{}\" name=\"{}\"",
+                escape(cluster),
+                escape(&case.name)
+            );
+            match &case.failure {
+                Some(failure) => {
+                    let _ = writeln!(
+                        xml,
+                        ">\n      <failure message=\"{}\"/>\n    </testcase>",
+                        escape(failure)
+                    );
+                }
+                None => xml.push_str("/>\n"),
+            }
+        }
+        xml.push_str("  </testsuite>\n");
+    }
+    xml.push_str("</testsuites>\n");
+    xml
+}
+
+// Escapes text for an attribute value, dropping control characters XML 1.0 does not allow
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\n' => escaped.push_str("&#10;"),
+            '\t' => escaped.push_str("&#9;"),
+            c if c.is_control() => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn junit_report() {
+        assert_eq!(
+            parse_report("junit=out/report.xml").unwrap(),
+            Report {
+                format: ReportFormat::Junit,
+                path: "out/report.xml".into(),
+            }
+        );
+        assert!(parse_report("junit").is_err());
+        assert!(parse_report("junit=").is_err());
+        assert!(parse_report("tap=report.tap").is_err());
+
+        let cases = [
+            TestCase::new("prod-us", "deployment/web", None),
+            TestCase::new(
+                "prod-eu",
+                "deployment/web",
+                Some("not \"ready\" & <late>".into()),
+            ),
+            TestCase::new("prod-us", "service/web", None),
+        ];
+        assert_eq!(
+            junit_xml("wait", &cases),
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<testsuites name="kubemc wait" tests="3" failures="1">
+  <testsuite name="prod-eu" tests="1" failures="1">
+    <testcase classname="prod-eu" name="deployment/web">
+      <failure message="not &quot;ready&quot; &amp; &lt;late&gt;"/>
+    </testcase>
+  </testsuite>
+  <testsuite name="prod-us" tests="2" failures="0">
+    <testcase classname="prod-us" name="deployment/web"/>
+    <testcase classname="prod-us" name="service/web"/>
+  </testsuite>
+</testsuites>
+"#
+        );
+    }
+}
@@ -0,0 +1,225 @@
+use std::{
+    collections::hash_map::DefaultHasher,
+    fs::{self, DirBuilder, File, OpenOptions},
+    hash::{Hash, Hasher},
+    io::{BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::{anyhow, Context, Result};
+use kube::core::{DynamicObject, ObjectList};
+use tracing::log::debug;
+
+use crate::index::file_name;
+
+/// A list of a kind on one cluster as `get` listed it, reused by the next `get` within the TTL
+/// so that exploring clusters over slow links does not list the same objects again and again.
+/// Lists are kept apart per cluster, target, resource url and query, the target covering the
+/// kubeconfig, context and impersonated user, the resource url the group, version, namespace and
+/// plural and the query the selectors. Only the user running kubemc can read them.
+pub struct ResponseCache {
+    path: PathBuf,
+}
+
+impl ResponseCache {
+    /// The cache of a list in `~/.kube/cache/kubemc/responses/<cluster>/`
+    pub fn new(cluster: &str, target: u64, resource_url: &str, query: impl Hash) -> Result<Self> {
+        let dir = dirs::home_dir()
+            .map(|h| {
+                h.join(".kube")
+                    .join("cache")
+                    .join("kubemc")
+                    .join("responses")
+            })
+            .ok_or_else(|| anyhow!("failed to find home directory"))?;
+        Ok(Self::in_dir(&dir, cluster, target, resource_url, query))
+    }
+
+    /// Like `new`, keeping the lists of every cluster in `dir`
+    pub fn in_dir(
+        dir: &Path,
+        cluster: &str,
+        target: u64,
+        resource_url: &str,
+        query: impl Hash,
+    ) -> Self {
+        let mut hasher = DefaultHasher::new();
+        target.hash(&mut hasher);
+        resource_url.hash(&mut hasher);
+        query.hash(&mut hasher);
+        Self {
+            path: dir
+                .join(file_name(cluster))
+                .join(format!("{:016x}.jsonl", hasher.finish())),
+        }
+    }
+
+    /// The pages of the list when it was listed less than `ttl` ago, along with how long ago
+    pub fn read(&self, ttl: Duration) -> Option<(Vec<ObjectList<DynamicObject>>, Duration)> {
+        let modified = fs::metadata(&self.path).and_then(|m| m.modified()).ok()?;
+        let age = SystemTime::now()
+            .duration_since(modified)
+            .unwrap_or_default();
+        if age >= ttl {
+            return None;
+        }
+        let pages = File::open(&self.path)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| {
+                BufReader::new(file)
+                    .lines()
+                    .map(|line| Ok(serde_json::from_str(&line?)?))
+                    .collect::<Result<Vec<_>>>()
+            });
+        match pages {
+            Ok(pages) if !pages.is_empty() => Some((pages, age)),
+            Ok(_) => None,
+            Err(e) => {
+                debug!("failed to read cached list {}: {}", self.path.display(), e);
+                None
+            }
+        }
+    }
+
+    /// Starts writing a list page by page, which only replaces the cached list once finished
+    pub fn writer(&self) -> Result<ResponseWriter> {
+        if let Some(dir) = self.path.parent() {
+            let mut builder = DirBuilder::new();
+            builder.recursive(true);
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::DirBuilderExt;
+                builder.mode(0o700);
+            }
+            builder.create(dir)?;
+        }
+        // other kubemc processes may be writing the same list
+        let tmp = self
+            .path
+            .with_extension(format!("{}.tmp", std::process::id()));
+        let mut options = OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let file = options
+            .open(&tmp)
+            .with_context(|| format!("failed to create {}", tmp.display()))?;
+        Ok(ResponseWriter {
+            file: BufWriter::new(file),
+            tmp,
+            path: self.path.clone(),
+        })
+    }
+}
+
+/// A list being written to the response cache, a page per line. Dropping it before it is
+/// finished, such as when the list failed part way through, leaves the cached list as it was.
+pub struct ResponseWriter {
+    file: BufWriter<File>,
+    tmp: PathBuf,
+    path: PathBuf,
+}
+
+impl ResponseWriter {
+    pub fn page(&mut self, page: &ObjectList<DynamicObject>) -> Result<()> {
+        serde_json::to_writer(&mut self.file, page)?;
+        self.file.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Replaces the cached list with the pages written
+    pub fn finish(mut self) -> Result<()> {
+        self.file.flush()?;
+        fs::rename(&self.tmp, &self.path)
+            .with_context(|| format!("failed to write {}", self.path.display()))
+    }
+}
+
+impl Drop for ResponseWriter {
+    fn drop(&mut self) {
+        // gone already once finished
+        let _ = fs::remove_file(&self.tmp);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use kube::discovery::ApiResource;
+
+    #[test]
+    fn cache_responses() {
+        let dir = std::env::temp_dir().join(format!("kubemc-responses-{}", std::process::id()));
+        let url = "/api/v1/namespaces/shop/pods";
+        let cache = ResponseCache::in_dir(&dir, "prod/us", 1, url, Some("app=web"));
+        let ttl = Duration::from_secs(60);
+        assert!(cache.read(ttl).is_none());
+
+        let ar = ApiResource::erase::<k8s_openapi::api::core::v1::Pod>(&());
+        let page = |names: &[&str]| ObjectList {
+            metadata: Default::default(),
+            items: names.iter().map(|n| DynamicObject::new(n, &ar)).collect(),
+        };
+        let mut writer = cache.writer().unwrap();
+        writer.page(&page(&["web-a", "web-b"])).unwrap();
+        writer.page(&page(&["web-c"])).unwrap();
+        // a list that did not finish leaves nothing behind
+        drop(writer);
+        assert!(cache.read(ttl).is_none());
+
+        let mut writer = cache.writer().unwrap();
+        writer.page(&page(&["web-a", "web-b"])).unwrap();
+        writer.page(&page(&["web-c"])).unwrap();
+        writer.finish().unwrap();
+        let (pages, age) = cache.read(ttl).unwrap();
+        let names: Vec<Vec<String>> = pages
+            .iter()
+            .map(|page| {
+                page.items
+                    .iter()
+                    .map(|o| o.metadata.name.clone().unwrap())
+                    .collect()
+            })
+            .collect();
+        assert_eq!(names, [vec!["web-a", "web-b"], vec!["web-c"]]);
+        assert!(age < ttl);
+        assert!(cache.read(Duration::ZERO).is_none());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = |path: &Path| fs::metadata(path).unwrap().permissions().mode() & 0o777;
+            assert_eq!(mode(&cache.path), 0o600);
+            assert_eq!(mode(&dir.join("prod_us")), 0o700);
+        }
+
+        // lists of other namespaces, selectors and credentials are kept apart
+        assert!(ResponseCache::in_dir(&dir, "prod/us", 1, url, None::<&str>)
+            .read(ttl)
+            .is_none());
+        assert!(
+            ResponseCache::in_dir(&dir, "prod/us", 2, url, Some("app=web"))
+                .read(ttl)
+                .is_none()
+        );
+        let other = ResponseCache::in_dir(
+            &dir,
+            "prod/us",
+            1,
+            "/api/v1/namespaces/web/pods",
+            None::<&str>,
+        );
+        assert!(other.read(ttl).is_none());
+        // as are lists narrowed by more selectors
+        let query = (Some("app=web"), Some("status.phase=Running"));
+        assert!(ResponseCache::in_dir(&dir, "prod/us", 1, url, query)
+            .read(ttl)
+            .is_none());
+        assert_eq!(fs::read_dir(dir.join("prod_us")).unwrap().count(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+use std::{collections::BTreeMap, future::Future, sync::Mutex, time::Duration};
+
+use kube::Error as KubeError;
+use tracing::log::debug;
+
+use crate::config::Connection;
+
+/// Retries after the first attempt unless the cluster's connection settings say otherwise
+const DEFAULT_RETRIES: u32 = 3;
+
+/// Delay before the first retry, doubled on every further retry
+const DEFAULT_BACKOFF: Duration = Duration::from_millis(200);
+
+/// Upper bound for the delay between two attempts
+const MAX_BACKOFF: Duration = Duration::from_secs(5);
+
+static RETRIES: Mutex<BTreeMap<String, u32>> = Mutex::new(BTreeMap::new());
+
+/// How often and how patiently requests to a cluster are retried after transient errors
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            retries: DEFAULT_RETRIES,
+            backoff: DEFAULT_BACKOFF,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new(connection: Option<&Connection>) -> Self {
+        let default = Self::default();
+        let Some(connection) = connection else {
+            return default;
+        };
+        Self {
+            retries: connection.retries.unwrap_or(default.retries),
+            backoff: connection
+                .retry_backoff
+                .and_then(|secs| Duration::try_from_secs_f64(secs).ok())
+                .unwrap_or(default.backoff),
+        }
+    }
+
+    // Delay before retry number `retry`, counting from zero
+    fn delay(&self, retry: u32) -> Duration {
+        self.backoff
+            .saturating_mul(2u32.saturating_pow(retry))
+            .min(MAX_BACKOFF)
+    }
+}
+
+/// Whether an error is likely to go away on its own: throttling, server errors and failed
+/// connections
+pub fn is_transient(e: &KubeError) -> bool {
+    match e {
+        KubeError::Api(response) => response.code == 429 || response.code >= 500,
+        KubeError::HyperError(_) | KubeError::Service(_) => true,
+        _ => false,
+    }
+}
+
+/// Runs `request` against a cluster, retrying transient errors with exponential backoff
+pub async fn with_retries<T, F, Fut>(
+    clustername: &str,
+    policy: RetryPolicy,
+    mut request: F,
+) -> kube::Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = kube::Result<T>>,
+{
+    let mut retry = 0;
+    loop {
+        match request().await {
+            Err(e) if retry < policy.retries && is_transient(&e) => {
+                let delay = policy.delay(retry);
+                debug!(
+                    "retrying request to cluster {} in {:?}: {}",
+                    clustername, delay, e
+                );
+                *RETRIES
+                    .lock()
+                    .unwrap()
+                    .entry(clustername.to_owned())
+                    .or_default() += 1;
+                tokio::time::sleep(delay).await;
+                retry += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+/// Number of retries made so far per cluster, for clusters that needed any
+pub fn retry_counts() -> BTreeMap<String, u32> {
+    RETRIES.lock().unwrap().clone()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use kube::core::ErrorResponse;
+
+    fn api_error(code: u16) -> KubeError {
+        KubeError::Api(ErrorResponse {
+            status: "Failure".into(),
+            message: String::new(),
+            reason: String::new(),
+            code,
+        })
+    }
+
+    #[tokio::test]
+    async fn retry_transient_errors() {
+        assert!(is_transient(&api_error(503)));
+        assert!(is_transient(&api_error(429)));
+        assert!(!is_transient(&api_error(404)));
+
+        let policy = RetryPolicy {
+            retries: 2,
+            backoff: Duration::from_millis(1),
+        };
+        assert_eq!(policy.delay(3), Duration::from_millis(8));
+
+        let mut attempts = 0;
+        let result = with_retries("flaky", policy, || {
+            attempts += 1;
+            let attempt = attempts;
+            async move {
+                if attempt < 3 {
+                    Err(api_error(503))
+                } else {
+                    Ok(attempt)
+                }
+            }
+        })
+        .await;
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(retry_counts().get("flaky"), Some(&2));
+
+        let mut attempts = 0;
+        let result: kube::Result<()> = with_retries("missing", policy, || {
+            attempts += 1;
+            async { Err(api_error(404)) }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(attempts, 1);
+    }
+}
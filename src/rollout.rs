@@ -0,0 +1,395 @@
+use k8s_openapi::{
+    api::{
+        apps::v1::{Deployment, ReplicaSet},
+        core::v1::Pod,
+    },
+    apimachinery::pkg::apis::meta::v1::Time,
+    chrono::{DateTime, Duration, Utc},
+};
+use kube::ResourceExt;
+use serde_json::json;
+
+use crate::output::{Health, StatusCell};
+
+/// Pod template annotation `kubectl rollout restart` sets to replace every pod
+pub const RESTARTED_AT_ANNOTATION: &str = "kubectl.kubernetes.io/restartedAt";
+
+/// Kinds whose pod template `rollout restart` can annotate
+pub const RESTARTABLE_KINDS: [&str; 3] = ["Deployment", "StatefulSet", "DaemonSet"];
+
+const REVISION_ANNOTATION: &str = "deployment.kubernetes.io/revision";
+const CHANGE_CAUSE_ANNOTATION: &str = "kubernetes.io/change-cause";
+
+/// Clusters whose pods take this many times the fleet median to become ready are slow
+pub const SLOW_READINESS_FACTOR: f64 = 2.0;
+
+/// Where a deployment rollout stands, with a kubectl style message
+#[derive(Clone, Debug, PartialEq)]
+pub enum RolloutState {
+    Progressing(String),
+    Complete(String),
+    Failed(String),
+}
+
+impl RolloutState {
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, RolloutState::Progressing(_))
+    }
+
+    pub fn status(&self) -> StatusCell {
+        match self {
+            RolloutState::Progressing(message) => StatusCell::new(message, Health::Progressing),
+            RolloutState::Complete(message) => StatusCell::new(message, Health::Healthy),
+            RolloutState::Failed(message) => StatusCell::new(message, Health::Unhealthy),
+        }
+    }
+}
+
+/// Works out rollout progress the same way `kubectl rollout status` does
+pub fn deployment_rollout_state(deployment: &Deployment) -> RolloutState {
+    let status = deployment.status.clone().unwrap_or_default();
+    let generation = deployment.metadata.generation.unwrap_or_default();
+    if generation > status.observed_generation.unwrap_or_default() {
+        return RolloutState::Progressing(
+            "waiting for deployment spec update to be observed".into(),
+        );
+    }
+    let deadline_exceeded = status.conditions.iter().flatten().any(|c| {
+        c.type_ == "Progressing" && c.reason.as_deref() == Some("ProgressDeadlineExceeded")
+    });
+    if deadline_exceeded {
+        return RolloutState::Failed("exceeded its progress deadline".into());
+    }
+    let replicas = deployment
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.replicas)
+        .unwrap_or(1);
+    let updated = status.updated_replicas.unwrap_or_default();
+    let available = status.available_replicas.unwrap_or_default();
+    let total = status.replicas.unwrap_or_default();
+    if updated < replicas {
+        RolloutState::Progressing(format!(
+            "{} out of {} new replicas have been updated",
+            updated, replicas
+        ))
+    } else if total > updated {
+        RolloutState::Progressing(format!(
+            "{} old replicas are pending termination",
+            total - updated
+        ))
+    } else if available < updated {
+        RolloutState::Progressing(format!(
+            "{} of {} updated replicas are available",
+            available, updated
+        ))
+    } else {
+        RolloutState::Complete("successfully rolled out".into())
+    }
+}
+
+/// Merge patch restarting a workload's pods by stamping its pod template
+pub fn restart_patch(now: DateTime<Utc>) -> serde_json::Value {
+    json!({
+        "spec": {
+            "template": {
+                "metadata": {
+                    "annotations": {
+                        RESTARTED_AT_ANNOTATION: now.to_rfc3339(),
+                    }
+                }
+            }
+        }
+    })
+}
+
+/// A revision of a deployment, backed by one of its ReplicaSets
+#[derive(Clone, Debug)]
+pub struct Revision {
+    pub revision: i64,
+    pub images: Vec<String>,
+    pub change_cause: Option<String>,
+    pub created: Option<Time>,
+}
+
+/// Returns the revisions of the deployment with `deployment_uid`, oldest first
+pub fn revisions(deployment_uid: &str, replicasets: &[ReplicaSet]) -> Vec<Revision> {
+    let mut revisions: Vec<Revision> = replicasets
+        .iter()
+        .filter(|rs| {
+            rs.owner_references()
+                .iter()
+                .any(|owner| owner.uid == deployment_uid)
+        })
+        .filter_map(|rs| {
+            let annotations = rs.annotations();
+            let revision = annotations.get(REVISION_ANNOTATION)?.parse().ok()?;
+            let images = rs
+                .spec
+                .iter()
+                .filter_map(|spec| spec.template.as_ref()?.spec.as_ref())
+                .flat_map(|spec| &spec.containers)
+                .filter_map(|container| container.image.clone())
+                .collect();
+            Some(Revision {
+                revision,
+                images,
+                change_cause: annotations.get(CHANGE_CAUSE_ANNOTATION).cloned(),
+                created: rs.metadata.creation_timestamp.clone(),
+            })
+        })
+        .collect();
+    revisions.sort_by_key(|revision| revision.revision);
+    revisions
+}
+
+/// The newest ReplicaSet of the deployment with `deployment_uid`, which the rollout creates
+pub fn newest_replicaset<'a>(
+    deployment_uid: &str,
+    replicasets: &'a [ReplicaSet],
+) -> Option<&'a ReplicaSet> {
+    replicasets
+        .iter()
+        .filter(|rs| {
+            rs.owner_references()
+                .iter()
+                .any(|owner| owner.uid == deployment_uid)
+        })
+        .max_by_key(|rs| {
+            rs.annotations()
+                .get(REVISION_ANNOTATION)
+                .and_then(|revision| revision.parse::<i64>().ok())
+                .unwrap_or_default()
+        })
+}
+
+/// When a pod of a rollout was created and, going by its Ready condition, became ready
+#[derive(Clone, Debug, PartialEq)]
+pub struct PodReadiness {
+    pub pod: String,
+    pub created: DateTime<Utc>,
+    pub ready: Option<DateTime<Utc>>,
+}
+
+impl PodReadiness {
+    pub fn new(pod: &Pod) -> Option<Self> {
+        let ready = pod
+            .status
+            .iter()
+            .flat_map(|status| status.conditions.iter().flatten())
+            .find(|c| c.type_ == "Ready" && c.status == "True")
+            .and_then(|c| c.last_transition_time.as_ref())
+            .map(|time| time.0);
+        Some(Self {
+            pod: pod.name_any(),
+            created: pod.metadata.creation_timestamp.as_ref()?.0,
+            ready,
+        })
+    }
+
+    pub fn time_to_ready(&self) -> Option<Duration> {
+        self.ready.map(|ready| ready - self.created)
+    }
+}
+
+/// Readiness of the pods of a replicaset, in the order they were created
+pub fn replicaset_readiness(replicaset: &ReplicaSet, pods: &[Pod]) -> Vec<PodReadiness> {
+    let uid = replicaset.uid().unwrap_or_default();
+    let mut readiness: Vec<PodReadiness> = pods
+        .iter()
+        .filter(|pod| pod.owner_references().iter().any(|owner| owner.uid == uid))
+        .filter_map(PodReadiness::new)
+        .collect();
+    readiness.sort_by_key(|pod| pod.created);
+    readiness
+}
+
+/// Median of durations, None without any
+pub fn median(durations: &[Duration]) -> Option<Duration> {
+    let mut sorted = durations.to_vec();
+    sorted.sort();
+    sorted.get(sorted.len().saturating_sub(1) / 2).copied()
+}
+
+/// How many times slower than `fleet` a cluster's median time to ready is, when it is at
+/// least `SLOW_READINESS_FACTOR` times slower
+pub fn slowdown(cluster: Duration, fleet: Duration) -> Option<f64> {
+    let fleet = fleet.num_milliseconds().max(1) as f64;
+    let factor = cluster.num_milliseconds() as f64 / fleet;
+    (factor >= SLOW_READINESS_FACTOR).then_some(factor)
+}
+
+/// Draws a pod on a timeline `width` characters wide covering `span` from the start of the
+/// rollout: dots before it was created, `=` until it became ready and `|` once ready. Pods that
+/// are not ready yet run to the end with `>`.
+pub fn timeline_bar(
+    created: Duration,
+    ready: Option<Duration>,
+    span: Duration,
+    width: usize,
+) -> String {
+    let span = span.num_milliseconds().max(1);
+    let column = |offset: Duration| {
+        let offset = offset.num_milliseconds().clamp(0, span);
+        ((offset as f64 / span as f64) * (width - 1) as f64).round() as usize
+    };
+    let start = column(created);
+    let end = ready.map(column).unwrap_or(width - 1).max(start);
+    (0..width)
+        .map(|i| match i {
+            i if i < start => '.',
+            i if i == end && ready.is_some() => '|',
+            i if i == end => '>',
+            i if i < end => '=',
+            _ => ' ',
+        })
+        .collect::<String>()
+        .trim_end()
+        .to_owned()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn deployment(generation: i64, status: serde_json::Value) -> Deployment {
+        serde_json::from_value(json!({
+            "metadata": {"name": "web", "generation": generation},
+            "spec": {"replicas": 3, "selector": {}, "template": {}},
+            "status": status,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn rollout_states() {
+        let stale = deployment(2, json!({"observedGeneration": 1}));
+        assert!(!deployment_rollout_state(&stale).is_finished());
+
+        let updating = deployment(
+            2,
+            json!({"observedGeneration": 2, "replicas": 4, "updatedReplicas": 1}),
+        );
+        assert_eq!(
+            deployment_rollout_state(&updating),
+            RolloutState::Progressing("1 out of 3 new replicas have been updated".into())
+        );
+
+        let done = deployment(
+            2,
+            json!({
+                "observedGeneration": 2,
+                "replicas": 3,
+                "updatedReplicas": 3,
+                "availableReplicas": 3,
+            }),
+        );
+        assert_eq!(
+            deployment_rollout_state(&done),
+            RolloutState::Complete("successfully rolled out".into())
+        );
+
+        let stuck = deployment(
+            2,
+            json!({
+                "observedGeneration": 2,
+                "conditions": [{
+                    "type": "Progressing",
+                    "status": "False",
+                    "reason": "ProgressDeadlineExceeded",
+                }],
+            }),
+        );
+        assert!(matches!(
+            deployment_rollout_state(&stuck),
+            RolloutState::Failed(_)
+        ));
+    }
+
+    #[test]
+    fn readiness_timeline() {
+        let replicaset = |name: &str, uid: &str, revision: &str| -> ReplicaSet {
+            serde_json::from_value(json!({
+                "metadata": {
+                    "name": name,
+                    "uid": uid,
+                    "annotations": {REVISION_ANNOTATION: revision},
+                    "ownerReferences": [{
+                        "apiVersion": "apps/v1", "kind": "Deployment", "name": "web", "uid": "d1",
+                    }],
+                },
+            }))
+            .unwrap()
+        };
+        let replicasets = [
+            replicaset("web-1", "rs1", "9"),
+            replicaset("web-2", "rs2", "10"),
+        ];
+        let newest = newest_replicaset("d1", &replicasets).unwrap();
+        assert_eq!(newest.name_any(), "web-2");
+        assert!(newest_replicaset("other", &replicasets).is_none());
+
+        let pod = |name: &str, owner: &str, created: &str, ready: Option<&str>| -> Pod {
+            let conditions = match ready {
+                Some(time) => {
+                    json!([{"type": "Ready", "status": "True", "lastTransitionTime": time}])
+                }
+                None => json!([{"type": "Ready", "status": "False"}]),
+            };
+            serde_json::from_value(json!({
+                "metadata": {
+                    "name": name,
+                    "creationTimestamp": created,
+                    "ownerReferences": [{
+                        "apiVersion": "apps/v1", "kind": "ReplicaSet", "name": "web", "uid": owner,
+                    }],
+                },
+                "status": {"conditions": conditions},
+            }))
+            .unwrap()
+        };
+        let pods = [
+            pod("web-2-b", "rs2", "2030-01-01T00:00:20Z", None),
+            pod(
+                "web-2-a",
+                "rs2",
+                "2030-01-01T00:00:00Z",
+                Some("2030-01-01T00:00:30Z"),
+            ),
+            pod(
+                "web-1-a",
+                "rs1",
+                "2029-12-01T00:00:00Z",
+                Some("2029-12-01T00:00:05Z"),
+            ),
+        ];
+        let readiness = replicaset_readiness(newest, &pods);
+        let names: Vec<&str> = readiness.iter().map(|p| p.pod.as_str()).collect();
+        assert_eq!(names, ["web-2-a", "web-2-b"]);
+        assert_eq!(readiness[0].time_to_ready(), Some(Duration::seconds(30)));
+        assert_eq!(readiness[1].time_to_ready(), None);
+
+        let seconds = |s: i64| Duration::seconds(s);
+        assert_eq!(
+            median(&[seconds(30), seconds(10), seconds(20)]),
+            Some(seconds(20))
+        );
+        assert_eq!(median(&[seconds(10), seconds(20)]), Some(seconds(10)));
+        assert_eq!(median(&[]), None);
+        assert_eq!(slowdown(seconds(90), seconds(30)), Some(3.0));
+        assert_eq!(slowdown(seconds(40), seconds(30)), None);
+
+        assert_eq!(
+            timeline_bar(seconds(0), Some(seconds(30)), seconds(60), 11),
+            "=====|"
+        );
+        assert_eq!(
+            timeline_bar(seconds(20), None, seconds(60), 11),
+            "...=======>"
+        );
+        assert_eq!(
+            timeline_bar(seconds(60), Some(seconds(60)), seconds(60), 11),
+            "..........|"
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use std::{
+    future::Future,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use futures::{Stream, StreamExt};
+use tokio::sync::Mutex;
+
+use crate::telemetry::format_millis;
+
+static SERIAL: AtomicBool = AtomicBool::new(false);
+
+// Held by the cluster whose turn it is while running serially
+static TURN: Mutex<()> = Mutex::const_new(());
+
+/// Makes clusters take turns instead of being worked on in parallel, for `--serial`
+pub fn enable() {
+    SERIAL.store(true, Ordering::Relaxed);
+}
+
+pub fn enabled() -> bool {
+    SERIAL.load(Ordering::Relaxed)
+}
+
+/// Awaits the work of every cluster, all of it at once unless `--serial` is set: then the work
+/// of a cluster is only started once the cluster before it is done. Every fan-out over clusters
+/// goes through here, so that `--serial` leaves nothing running in parallel.
+pub async fn join_all<I>(work: I) -> Vec<<I::Item as Future>::Output>
+where
+    I: IntoIterator,
+    I::Item: Future,
+{
+    if !enabled() {
+        return futures::future::join_all(work).await;
+    }
+    let mut outputs = Vec::new();
+    // the iterator is only advanced once the previous work is done, so work spawned as it is
+    // created starts one cluster at a time too
+    for work in work {
+        outputs.push(work.await);
+    }
+    outputs
+}
+
+/// Merges the streams of every cluster as their items arrive, or goes through the clusters one
+/// after the other with `--serial`
+pub fn interleave<S: Stream + Unpin>(
+    clusters: impl Iterator<Item = S>,
+) -> impl Stream<Item = S::Item> {
+    if enabled() {
+        futures::stream::iter(clusters.collect::<Vec<_>>())
+            .flatten()
+            .left_stream()
+    } else {
+        futures::stream::select_all(clusters).right_stream()
+    }
+}
+
+/// Runs a phase of the work on one cluster. Phases run as they come, unless `--serial` is set:
+/// then only one phase of one cluster runs at a time, and each is reported on stderr as it
+/// starts and ends so a cluster that hangs is the last one named.
+pub async fn phase<F: Future>(clustername: &str, phase: &str, work: F) -> F::Output {
+    if !enabled() {
+        return work.await;
+    }
+    let _turn = TURN.lock().await;
+    eprintln!("{}", started(clustername, phase));
+    let start = Instant::now();
+    let output = work.await;
+    eprintln!("{}", finished(clustername, phase, start.elapsed()));
+    output
+}
+
+fn started(clustername: &str, phase: &str) -> String {
+    format!("{}: {}...", clustername, phase)
+}
+
+fn finished(clustername: &str, phase: &str, elapsed: Duration) -> String {
+    format!(
+        "{}: {} took {}",
+        clustername,
+        phase,
+        format_millis(elapsed.as_millis() as u64)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    // Turns serial mode back off when dropped, so it does not outlive the test that enabled it
+    struct SerialGuard;
+
+    impl Drop for SerialGuard {
+        fn drop(&mut self) {
+            SERIAL.store(false, Ordering::Relaxed);
+        }
+    }
+
+    #[tokio::test]
+    async fn take_turns() {
+        assert_eq!(started("prod-us", "connect"), "prod-us: connect...");
+        assert_eq!(
+            finished("prod-us", "list pods", Duration::from_millis(1500)),
+            "prod-us: list pods took 1.5s"
+        );
+
+        enable();
+        let _serial = SerialGuard;
+        let running = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let handles = (0..4).map(|i| {
+            let running = running.clone();
+            tokio::spawn(async move {
+                phase(&format!("cluster-{}", i), "sleep", async {
+                    let overlapping = running.fetch_add(1, Ordering::SeqCst);
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                    running.fetch_sub(1, Ordering::SeqCst);
+                    overlapping
+                })
+                .await
+            })
+        });
+        for overlapping in futures::future::join_all(handles).await {
+            assert_eq!(overlapping.unwrap(), 0);
+        }
+
+        // work spawned for each cluster without phases of its own takes turns as well
+        let handles = (0..4).map(|_| {
+            let running = running.clone();
+            tokio::spawn(async move {
+                let overlapping = running.fetch_add(1, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(5)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                overlapping
+            })
+        });
+        for overlapping in join_all(handles).await {
+            assert_eq!(overlapping.unwrap(), 0);
+        }
+    }
+}
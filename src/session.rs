@@ -0,0 +1,386 @@
+use std::{
+    collections::BTreeMap,
+    fs::{File, OpenOptions},
+    io::{BufWriter, Read, Write},
+    path::Path,
+    process::{Command, Stdio},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use k8s_openapi::{api::core::v1::Pod, apimachinery::pkg::apis::meta::v1::Status, chrono::Utc};
+use kube::{api::AttachParams, Api};
+use serde::Serialize;
+use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+    sync::mpsc,
+};
+use tracing::log::debug;
+
+/// Terminal size written to recordings when the terminal does not tell
+const DEFAULT_SIZE: (u16, u16) = (80, 24);
+
+/// What a session runs in the container
+#[derive(Clone, Debug)]
+pub enum SessionKind {
+    Exec(Vec<String>),
+    Attach,
+}
+
+/// Where a session ran, kept in the header of its recording
+#[derive(Clone, Debug, Serialize)]
+pub struct SessionInfo {
+    pub cluster: String,
+    pub namespace: String,
+    pub pod: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+    pub user: String,
+}
+
+// Header line of an asciicast v2 file. Players ignore keys they do not know, such as kubemc.
+#[derive(Serialize)]
+struct CastHeader<'a> {
+    version: u8,
+    width: u16,
+    height: u16,
+    timestamp: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    command: Option<String>,
+    title: String,
+    env: BTreeMap<&'static str, String>,
+    kubemc: &'a SessionInfo,
+}
+
+/// Stream an event of a recording belongs to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CastEvent {
+    /// What the container printed
+    Output,
+    /// What was typed into the session
+    Input,
+}
+
+/// Writes a session as an asciicast v2 recording, playable with `asciinema play`: a JSON
+/// header followed by one `[seconds, "o" or "i", data]` line per chunk of output or input
+pub struct CastRecorder<W: Write> {
+    out: W,
+    start: Instant,
+    // bytes of a character split across chunks, per event stream
+    pending: [Vec<u8>; 2],
+}
+
+impl CastRecorder<BufWriter<File>> {
+    /// Creates the recording file for a session, refusing to overwrite an earlier recording
+    pub fn create(path: &Path, info: &SessionInfo, kind: &SessionKind) -> Result<Self> {
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .with_context(|| format!("failed to create recording {}", path.display()))?;
+        CastRecorder::new(BufWriter::new(file), info, kind)
+    }
+}
+
+impl<W: Write> CastRecorder<W> {
+    pub fn new(mut out: W, info: &SessionInfo, kind: &SessionKind) -> Result<Self> {
+        let (width, height) = terminal_size();
+        let (command, action) = match kind {
+            SessionKind::Exec(command) => (Some(command.join(" ")), "exec"),
+            SessionKind::Attach => (None, "attach"),
+        };
+        let header = CastHeader {
+            version: 2,
+            width,
+            height,
+            timestamp: Utc::now().timestamp(),
+            command,
+            title: format!(
+                "kubemc {} {}/{}/{}",
+                action, info.cluster, info.namespace, info.pod
+            ),
+            env: ["SHELL", "TERM"]
+                .into_iter()
+                .filter_map(|name| std::env::var(name).ok().map(|value| (name, value)))
+                .collect(),
+            kubemc: info,
+        };
+        serde_json::to_writer(&mut out, &header)?;
+        writeln!(out)?;
+        Ok(Self {
+            out,
+            start: Instant::now(),
+            pending: Default::default(),
+        })
+    }
+
+    /// Records a chunk of output or input at the current time
+    pub fn record(&mut self, event: CastEvent, data: &[u8]) -> Result<()> {
+        self.record_at(event, data, self.start.elapsed())
+    }
+
+    fn record_at(&mut self, event: CastEvent, data: &[u8], elapsed: Duration) -> Result<()> {
+        let (code, pending) = match event {
+            CastEvent::Output => ("o", &mut self.pending[0]),
+            CastEvent::Input => ("i", &mut self.pending[1]),
+        };
+        pending.extend_from_slice(data);
+        // a character cut off at the end of the chunk waits for the rest of it
+        let valid = match std::str::from_utf8(pending) {
+            Ok(_) => pending.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(_) => pending.len(),
+        };
+        if valid == 0 {
+            return Ok(());
+        }
+        let rest = pending.split_off(valid);
+        let text = String::from_utf8_lossy(pending).into_owned();
+        *pending = rest;
+        serde_json::to_writer(&mut self.out, &(elapsed.as_secs_f64(), code, text))?;
+        writeln!(self.out)?;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<()> {
+        self.out.flush()?;
+        Ok(())
+    }
+}
+
+fn terminal_size() -> (u16, u16) {
+    let size = |name: &str| std::env::var(name).ok().and_then(|v| v.parse().ok());
+    (
+        size("COLUMNS").unwrap_or(DEFAULT_SIZE.0),
+        size("LINES").unwrap_or(DEFAULT_SIZE.1),
+    )
+}
+
+/// Puts the local terminal in raw mode for a session with a TTY, so that keys such as Ctrl-C
+/// reach the container instead of kubemc, and restores it once dropped
+pub struct RawMode {
+    saved: String,
+}
+
+impl RawMode {
+    pub fn enable() -> Result<Self> {
+        let saved = stty(&["-g"])?.trim().to_owned();
+        stty(&["raw", "-echo"])?;
+        Ok(Self { saved })
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        if let Err(e) = stty(&[&self.saved]) {
+            debug!("failed to restore the terminal: {}", e);
+        }
+    }
+}
+
+// Runs stty against the terminal on stdin, returning what it printed
+fn stty(args: &[&str]) -> Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()
+        .context("failed to run stty")?;
+    if !output.status.success() {
+        bail!(
+            "stty {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+type Recorder = Arc<Mutex<Option<CastRecorder<BufWriter<File>>>>>;
+
+/// Runs a command in a container, or attaches to it, wiring the session to the local stdin,
+/// stdout and stderr and recording it when a recorder is given. What is typed is only recorded
+/// with `record_input`, since it may hold passwords. Returns the exit code of the command, 0
+/// for attach sessions that ended normally.
+pub async fn run_session(
+    pods: Api<Pod>,
+    pod: &str,
+    kind: &SessionKind,
+    params: AttachParams,
+    recorder: Option<CastRecorder<BufWriter<File>>>,
+    record_input: bool,
+) -> Result<i32> {
+    let mut process = match kind {
+        SessionKind::Exec(command) => pods.exec(pod, command.clone(), &params).await?,
+        SessionKind::Attach => pods.attach(pod, &params).await?,
+    };
+    let recorder: Recorder = Arc::new(Mutex::new(recorder));
+
+    let input = process.stdin().map(|remote| {
+        let recorder = match record_input {
+            true => recorder.clone(),
+            false => Arc::new(Mutex::new(None)),
+        };
+        tokio::spawn(async move {
+            if let Err(e) = pump_input(stdin_chunks(), remote, &recorder).await {
+                debug!("session input ended: {}", e);
+            }
+        })
+    });
+    let (remote_stdout, remote_stderr) = (process.stdout(), process.stderr());
+    let stdout = async {
+        match remote_stdout {
+            Some(remote) => pump(remote, tokio::io::stdout(), CastEvent::Output, &recorder).await,
+            None => Ok(()),
+        }
+    };
+    let stderr = async {
+        match remote_stderr {
+            Some(remote) => pump(remote, tokio::io::stderr(), CastEvent::Output, &recorder).await,
+            None => Ok(()),
+        }
+    };
+    let (stdout, stderr) = tokio::join!(stdout, stderr);
+    if let Some(input) = input {
+        input.abort();
+    }
+    let status = match process.take_status() {
+        Some(status) => status.await,
+        None => None,
+    };
+    if let Some(recorder) = recorder.lock().unwrap().take() {
+        recorder.finish()?;
+    }
+    stdout?;
+    stderr?;
+    Ok(exit_code(status))
+}
+
+// Copies one side of the session to the other until it ends, recording what passes through
+async fn pump(
+    mut from: impl AsyncRead + Unpin,
+    mut to: impl AsyncWrite + Unpin,
+    event: CastEvent,
+    recorder: &Recorder,
+) -> Result<()> {
+    let mut buf = vec![0; 8192];
+    loop {
+        let n = from.read(&mut buf).await?;
+        if n == 0 {
+            to.shutdown().await.ok();
+            return Ok(());
+        }
+        to.write_all(&buf[..n]).await?;
+        to.flush().await?;
+        if let Some(recorder) = recorder.lock().unwrap().as_mut() {
+            recorder.record(event, &buf[..n])?;
+        }
+    }
+}
+
+// Reads the local stdin on a thread of its own. Unlike the blocking threads tokio reads stdin
+// on, it does not hold up exiting after the session ended while it waits for input.
+fn stdin_chunks() -> mpsc::Receiver<Vec<u8>> {
+    let (chunks, receiver) = mpsc::channel(1);
+    std::thread::spawn(move || {
+        let mut stdin = std::io::stdin();
+        let mut buf = vec![0; 8192];
+        loop {
+            match stdin.read(&mut buf) {
+                Ok(0) | Err(_) => return,
+                Ok(n) => {
+                    if chunks.blocking_send(buf[..n].to_vec()).is_err() {
+                        return;
+                    }
+                }
+            }
+        }
+    });
+    receiver
+}
+
+// Copies what is typed into the session until stdin closes, recording it
+async fn pump_input(
+    mut chunks: mpsc::Receiver<Vec<u8>>,
+    mut to: impl AsyncWrite + Unpin,
+    recorder: &Recorder,
+) -> Result<()> {
+    while let Some(chunk) = chunks.recv().await {
+        to.write_all(&chunk).await?;
+        to.flush().await?;
+        if let Some(recorder) = recorder.lock().unwrap().as_mut() {
+            recorder.record(CastEvent::Input, &chunk)?;
+        }
+    }
+    to.shutdown().await.ok();
+    Ok(())
+}
+
+// The exit code reported in the status of a finished command
+fn exit_code(status: Option<Status>) -> i32 {
+    let Some(status) = status else {
+        return 0;
+    };
+    if status.status.as_deref() == Some("Success") {
+        return 0;
+    }
+    status
+        .details
+        .and_then(|details| details.causes)
+        .into_iter()
+        .flatten()
+        .find(|cause| cause.reason.as_deref() == Some("ExitCode"))
+        .and_then(|cause| cause.message?.parse().ok())
+        .unwrap_or(1)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn record_sessions() {
+        let info = SessionInfo {
+            cluster: "prod-us".into(),
+            namespace: "shop".into(),
+            pod: "web-1".into(),
+            container: None,
+            user: "oncall".into(),
+        };
+        let kind = SessionKind::Exec(vec!["sh".into(), "-c".into(), "date".into()]);
+        let mut recorder = CastRecorder::new(Vec::new(), &info, &kind).unwrap();
+        recorder
+            .record_at(CastEvent::Input, b"ls\n", Duration::from_millis(500))
+            .unwrap();
+        // é split across two chunks is recorded once complete
+        recorder
+            .record_at(CastEvent::Output, b"caf\xc3", Duration::from_secs(1))
+            .unwrap();
+        recorder
+            .record_at(CastEvent::Output, b"\xa9\n", Duration::from_secs(2))
+            .unwrap();
+        let cast = String::from_utf8(recorder.out).unwrap();
+        let lines: Vec<serde_json::Value> = cast
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines[0]["version"], 2);
+        assert_eq!(lines[0]["command"], "sh -c date");
+        assert_eq!(lines[0]["title"], "kubemc exec prod-us/shop/web-1");
+        assert_eq!(lines[0]["kubemc"]["user"], "oncall");
+        assert_eq!(lines[1], serde_json::json!([0.5, "i", "ls\n"]));
+        assert_eq!(lines[2], serde_json::json!([1.0, "o", "caf"]));
+        assert_eq!(lines[3], serde_json::json!([2.0, "o", "é\n"]));
+
+        let failed: Status = serde_json::from_value(serde_json::json!({
+            "status": "Failure",
+            "reason": "NonZeroExitCode",
+            "details": {"causes": [{"reason": "ExitCode", "message": "3"}]},
+        }))
+        .unwrap();
+        assert_eq!(exit_code(Some(failed)), 3);
+        let succeeded: Status =
+            serde_json::from_value(serde_json::json!({"status": "Success"})).unwrap();
+        assert_eq!(exit_code(Some(succeeded)), 0);
+    }
+}
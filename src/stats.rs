@@ -0,0 +1,77 @@
+use std::fmt::Debug;
+
+use anyhow::Result;
+use k8s_openapi::api::{
+    apps::v1::Deployment,
+    core::v1::{Namespace, Node, Pod},
+};
+use kube::{api::ListParams, Api, Client as KubeClient, Resource};
+use serde::de::DeserializeOwned;
+
+use crate::{client::DEFAULT_PAGE_SIZE, describe::parse_quantity};
+
+/// Object counts and capacity of a single cluster, cpu in cores and memory in bytes
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ClusterStats {
+    pub nodes: usize,
+    pub namespaces: usize,
+    pub pods: usize,
+    pub deployments: usize,
+    pub cpu: f64,
+    pub memory: f64,
+}
+
+impl ClusterStats {
+    pub async fn fetch(client: KubeClient) -> Result<Self> {
+        let nodes: Api<Node> = Api::all(client.clone());
+        let nodes = nodes.list(&ListParams::default()).await?.items;
+        let (cpu, memory) = nodes
+            .iter()
+            .filter_map(|node| node.status.as_ref()?.allocatable.as_ref())
+            .fold((0.0, 0.0), |(cpu, memory), allocatable| {
+                let get = |name: &str| {
+                    allocatable
+                        .get(name)
+                        .and_then(|q| parse_quantity(&q.0))
+                        .unwrap_or_default()
+                };
+                (cpu + get("cpu"), memory + get("memory"))
+            });
+        Ok(Self {
+            nodes: nodes.len(),
+            namespaces: count(Api::<Namespace>::all(client.clone())).await?,
+            pods: count(Api::<Pod>::all(client.clone())).await?,
+            deployments: count(Api::<Deployment>::all(client)).await?,
+            cpu,
+            memory,
+        })
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        Self {
+            nodes: self.nodes + other.nodes,
+            namespaces: self.namespaces + other.namespaces,
+            pods: self.pods + other.pods,
+            deployments: self.deployments + other.deployments,
+            cpu: self.cpu + other.cpu,
+            memory: self.memory + other.memory,
+        }
+    }
+}
+
+// Counts objects by paging through their metadata only, so large clusters stay cheap to count
+async fn count<K>(api: Api<K>) -> Result<usize>
+where
+    K: Resource + Clone + DeserializeOwned + Debug,
+{
+    let mut count = 0;
+    let mut lp = ListParams::default().limit(DEFAULT_PAGE_SIZE);
+    loop {
+        let page = api.list_metadata(&lp).await?;
+        count += page.items.len();
+        match page.metadata.continue_.filter(|token| !token.is_empty()) {
+            Some(token) => lp = lp.continue_token(&token),
+            None => return Ok(count),
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{ListMeta, ObjectMeta};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::output::get_age;
+
+/// Accept header asking the API server to render a list as a Table. Servers that cannot render
+/// tables, such as some aggregated APIs, answer with the plain list instead.
+pub const TABLE_ACCEPT: &str = "application/json;as=Table;v=v1;g=meta.k8s.io,application/json";
+
+/// A page of a list rendered by the API server through the Table protocol
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerTable {
+    #[serde(default)]
+    pub column_definitions: Vec<TableColumn>,
+    #[serde(default)]
+    pub rows: Vec<TableRow>,
+    #[serde(default)]
+    pub metadata: ListMeta,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct TableColumn {
+    pub name: String,
+    /// Columns above priority 0 are only shown in wide output
+    #[serde(default)]
+    pub priority: i32,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct TableRow {
+    #[serde(default)]
+    pub cells: Vec<Value>,
+    /// The object of the row, holding only its metadata
+    #[serde(default)]
+    pub object: Option<RowObject>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct RowObject {
+    #[serde(default)]
+    pub metadata: ObjectMeta,
+}
+
+impl TableRow {
+    pub fn metadata(&self) -> Option<&ObjectMeta> {
+        self.object.as_ref().map(|object| &object.metadata)
+    }
+}
+
+impl ServerTable {
+    /// Reads the response to a table request. A plain list gets the name and age columns
+    /// kubectl shows for kinds without columns of their own.
+    pub fn from_response(response: Value) -> Result<Self> {
+        if response["kind"] == "Table" {
+            return Ok(serde_json::from_value(response)?);
+        }
+        #[derive(Deserialize)]
+        struct List {
+            #[serde(default)]
+            metadata: ListMeta,
+            #[serde(default)]
+            items: Vec<RowObject>,
+        }
+        let list: List = serde_json::from_value(response)?;
+        let column = |name: &str| TableColumn {
+            name: name.into(),
+            priority: 0,
+        };
+        let rows = list
+            .items
+            .into_iter()
+            .map(|object| TableRow {
+                cells: vec![
+                    object.metadata.name.clone().unwrap_or_default().into(),
+                    get_age(object.metadata.creation_timestamp.clone()).into(),
+                ],
+                object: Some(object),
+            })
+            .collect();
+        Ok(Self {
+            column_definitions: vec![column("Name"), column("Age")],
+            rows,
+            metadata: list.metadata,
+        })
+    }
+
+    /// Names of the columns to show, leaving out those above priority 0 unless wide
+    pub fn columns(&self, wide: bool) -> Vec<String> {
+        self.column_definitions
+            .iter()
+            .filter(|column| wide || column.priority == 0)
+            .map(|column| column.name.clone())
+            .collect()
+    }
+
+    /// Cells of a row under the named columns. Tables of different clusters may not have the
+    /// same columns, those a table lacks are left empty.
+    pub fn cells(&self, row: &TableRow, columns: &[String]) -> Vec<String> {
+        let positions: BTreeMap<&str, usize> = self
+            .column_definitions
+            .iter()
+            .enumerate()
+            .map(|(i, column)| (column.name.as_str(), i))
+            .collect();
+        columns
+            .iter()
+            .map(|column| {
+                positions
+                    .get(column.as_str())
+                    .and_then(|&i| row.cells.get(i))
+                    .map_or(String::new(), cell_text)
+            })
+            .collect()
+    }
+}
+
+fn cell_text(cell: &Value) -> String {
+    match cell {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        cell => cell.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn merge_server_tables() {
+        let table = ServerTable::from_response(serde_json::json!({
+            "kind": "Table",
+            "apiVersion": "meta.k8s.io/v1",
+            "metadata": {"continue": "next"},
+            "columnDefinitions": [
+                {"name": "Name", "type": "string", "format": "name", "priority": 0},
+                {"name": "Ready", "type": "string", "priority": 0},
+                {"name": "Restarts", "type": "integer", "priority": 0},
+                {"name": "Node", "type": "string", "priority": 1},
+            ],
+            "rows": [{
+                "cells": ["web-1", "1/1", 0, "node-a"],
+                "object": {"kind": "PartialObjectMetadata", "metadata": {"name": "web-1", "labels": {"app": "web"}}},
+            }],
+        }))
+        .unwrap();
+        assert_eq!(table.metadata.continue_.as_deref(), Some("next"));
+        assert_eq!(table.columns(false), ["Name", "Ready", "Restarts"]);
+        let columns = table.columns(true);
+        assert_eq!(
+            table.cells(&table.rows[0], &columns),
+            ["web-1", "1/1", "0", "node-a"]
+        );
+        let labels = table.rows[0].metadata().unwrap().labels.clone().unwrap();
+        assert_eq!(labels["app"], "web");
+
+        // an older cluster without the Node column leaves it empty
+        let older = ServerTable::from_response(serde_json::json!({
+            "kind": "Table",
+            "columnDefinitions": [{"name": "Name"}, {"name": "Ready"}],
+            "rows": [{"cells": ["web-2", "0/1"]}],
+        }))
+        .unwrap();
+        assert_eq!(
+            older.cells(&older.rows[0], &columns),
+            ["web-2", "0/1", "", ""]
+        );
+
+        let list = ServerTable::from_response(serde_json::json!({
+            "kind": "WidgetList",
+            "metadata": {},
+            "items": [{"metadata": {"name": "gear"}}],
+        }))
+        .unwrap();
+        assert_eq!(list.columns(false), ["Name", "Age"]);
+        assert_eq!(
+            list.cells(&list.rows[0], &list.columns(false)),
+            ["gear", ""]
+        );
+    }
+}
@@ -0,0 +1,173 @@
+use std::{
+    collections::BTreeMap,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::PathBuf,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use anyhow::{anyhow, Result};
+use k8s_openapi::chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::log::debug;
+
+use crate::output::TelemetryOutput;
+
+static CLUSTERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Remembers how many clusters the running command targets, for its telemetry record
+pub fn set_cluster_count(clusters: usize) {
+    CLUSTERS.store(clusters, Ordering::Relaxed);
+}
+
+/// One finished command. Only the subcommand name is kept, never cluster or resource names.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Record {
+    pub time: DateTime<Utc>,
+    pub command: String,
+    pub clusters: usize,
+    pub millis: u64,
+    pub success: bool,
+}
+
+impl Record {
+    pub fn new(command: &str, elapsed: Duration, success: bool) -> Self {
+        Self {
+            time: Utc::now(),
+            command: command.to_owned(),
+            clusters: CLUSTERS.load(Ordering::Relaxed),
+            millis: u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX),
+            success,
+        }
+    }
+}
+
+/// Appends a record to the local telemetry file, one JSON object per line
+pub fn append(record: &Record) -> Result<()> {
+    let path = telemetry_path()?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+    Ok(())
+}
+
+/// Reads every record from the telemetry file, skipping lines that cannot be parsed
+pub fn read() -> Result<Vec<Record>> {
+    let path = telemetry_path()?;
+    let contents = match fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    Ok(contents
+        .lines()
+        .filter_map(|line| match serde_json::from_str(line) {
+            Ok(record) => Some(record),
+            Err(e) => {
+                debug!("skipping telemetry record: {}", e);
+                None
+            }
+        })
+        .collect())
+}
+
+/// Groups records made since `since` by day and command, with latency percentiles per group
+pub fn summarize(records: &[Record], since: DateTime<Utc>) -> Vec<TelemetryOutput> {
+    let mut groups: BTreeMap<(NaiveDate, &str), Vec<&Record>> = BTreeMap::new();
+    for record in records.iter().filter(|r| r.time >= since) {
+        groups
+            .entry((record.time.date_naive(), record.command.as_str()))
+            .or_default()
+            .push(record);
+    }
+    groups
+        .into_iter()
+        .map(|((day, command), records)| {
+            let mut millis: Vec<u64> = records.iter().map(|r| r.millis).collect();
+            millis.sort_unstable();
+            let clusters =
+                records.iter().map(|r| r.clusters).sum::<usize>() as f64 / records.len() as f64;
+            TelemetryOutput {
+                day: day.to_string(),
+                command: command.to_owned(),
+                runs: records.len(),
+                failed: records.iter().filter(|r| !r.success).count(),
+                clusters: format!("{:.1}", clusters),
+                p50: format_millis(percentile(&millis, 50)),
+                p90: format_millis(percentile(&millis, 90)),
+                p99: format_millis(percentile(&millis, 99)),
+                max: format_millis(millis.last().copied().unwrap_or_default()),
+            }
+        })
+        .collect()
+}
+
+// Nearest-rank percentile of sorted values
+fn percentile(sorted: &[u64], p: usize) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+    let rank = (p * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+pub(crate) fn format_millis(millis: u64) -> String {
+    if millis < 1000 {
+        format!("{}ms", millis)
+    } else {
+        format!("{:.1}s", millis as f64 / 1000.0)
+    }
+}
+
+fn telemetry_path() -> Result<PathBuf> {
+    dirs::home_dir()
+        .map(|h| h.join(".kube").join("cache").join("kubemc-telemetry.jsonl"))
+        .ok_or_else(|| anyhow!("failed to find home directory"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record(time: &str, command: &str, millis: u64) -> Record {
+        Record {
+            time: time.parse().unwrap(),
+            command: command.into(),
+            clusters: 3,
+            millis,
+            success: millis < 1000,
+        }
+    }
+
+    #[test]
+    fn latency_percentiles_per_day() {
+        assert_eq!(percentile(&[], 50), 0);
+        let sorted: Vec<u64> = (1..=10).collect();
+        assert_eq!(percentile(&sorted, 50), 5);
+        assert_eq!(percentile(&sorted, 90), 9);
+        assert_eq!(percentile(&sorted, 99), 10);
+
+        let records = vec![
+            record("2030-01-01T10:00:00Z", "get", 200),
+            record("2030-01-01T11:00:00Z", "get", 1500),
+            record("2030-01-01T12:00:00Z", "get", 300),
+            record("2030-01-02T10:00:00Z", "get", 100),
+            record("2029-12-01T10:00:00Z", "get", 100),
+        ];
+        let since = "2030-01-01T00:00:00Z".parse().unwrap();
+        let rows = summarize(&records, since);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(
+            (rows[0].day.as_str(), rows[0].runs, rows[0].failed),
+            ("2030-01-01", 3, 1)
+        );
+        assert_eq!(
+            (rows[0].p50.as_str(), rows[0].max.as_str()),
+            ("300ms", "1.5s")
+        );
+        assert_eq!(rows[1].clusters, "3.0");
+    }
+}
@@ -0,0 +1,173 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{Context, Result};
+use kube::{core::DynamicObject, ResourceExt};
+use serde::Deserialize;
+
+use crate::output::{Formatter, Health};
+
+/// Checks a cluster has to pass before it joins the fleet, read from a yaml checklist
+#[derive(Debug, Clone, Deserialize)]
+pub struct Checklist {
+    pub checks: Vec<Check>,
+}
+
+/// Expects objects of a resource to exist and optionally to be ready, e.g. the CNI pods in
+/// kube-system or a default storageclass
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Check {
+    /// Shown in the report
+    pub name: String,
+
+    /// Kubernetes resource to look for, such as `pods` or `storageclasses`
+    pub resource: String,
+
+    /// Namespace of namespaced resources, `default` if not set
+    #[serde(default)]
+    pub namespace: Option<String>,
+
+    /// Name of a single object to look for instead of listing the resource
+    #[serde(default)]
+    pub object: Option<String>,
+
+    /// Labels the objects must carry
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+
+    /// Annotations the objects must carry, e.g.
+    /// `storageclass.kubernetes.io/is-default-class: "true"`
+    #[serde(default)]
+    pub annotations: BTreeMap<String, String>,
+
+    /// Least number of matching objects
+    #[serde(default = "default_min")]
+    pub min: usize,
+
+    /// Whether every matching object must also be ready, pods going by their Ready condition.
+    /// Kinds without a status to judge, such as services, cannot be checked for readiness
+    #[serde(default)]
+    pub ready: bool,
+}
+
+fn default_min() -> usize {
+    1
+}
+
+impl Checklist {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read checklist {}", path.display()))?;
+        serde_yaml::from_str(&contents)
+            .with_context(|| format!("invalid checklist {}", path.display()))
+    }
+}
+
+impl Check {
+    /// Judges the objects of the check's resource found on the cluster, returning whether the
+    /// check passed along with what was found
+    pub fn evaluate(&self, formatter: Formatter, objects: Vec<DynamicObject>) -> (bool, String) {
+        let matching: Vec<DynamicObject> = objects
+            .into_iter()
+            .filter(|o| contains_all(o.labels(), &self.labels))
+            .filter(|o| contains_all(o.annotations(), &self.annotations))
+            .collect();
+        if matching.len() < self.min {
+            return (
+                false,
+                format!("found {} of at least {}", matching.len(), self.min),
+            );
+        }
+        if !self.ready {
+            return (true, format!("found {}", matching.len()));
+        }
+        let total = matching.len();
+        let not_ready: Vec<String> = matching
+            .into_iter()
+            .filter_map(|object| {
+                let name = object.name_any();
+                // pods are ready by their Ready condition, other kinds by their health
+                let output = formatter.format("", object);
+                let ready = output.ready().unwrap_or(output.health() == Health::Healthy);
+                (!ready).then_some(name)
+            })
+            .collect();
+        if not_ready.is_empty() {
+            (true, format!("{} ready", total))
+        } else {
+            (false, format!("not ready: {}", not_ready.join(", ")))
+        }
+    }
+}
+
+fn contains_all(actual: &BTreeMap<String, String>, wanted: &BTreeMap<String, String>) -> bool {
+    wanted
+        .iter()
+        .all(|(key, value)| actual.get(key) == Some(value))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use kube::discovery::ApiResource;
+
+    #[test]
+    fn evaluate_checks() {
+        let checklist: Checklist = serde_yaml::from_str(
+            r#"
+checks:
+- name: default storageclass
+  resource: storageclasses
+  annotations:
+    storageclass.kubernetes.io/is-default-class: "true"
+- name: cni ready
+  resource: pods
+  namespace: kube-system
+  labels:
+    k8s-app: cilium
+  min: 2
+  ready: true
+"#,
+        )
+        .unwrap();
+        let [storage, cni] = &checklist.checks[..] else {
+            panic!("expected two checks");
+        };
+
+        let sc = ApiResource::erase::<k8s_openapi::api::storage::v1::StorageClass>(&());
+        let mut standard = DynamicObject::new("standard", &sc);
+        standard.metadata.annotations = Some(BTreeMap::from([(
+            "storageclass.kubernetes.io/is-default-class".into(),
+            "true".into(),
+        )]));
+        assert_eq!(
+            storage.evaluate(Formatter::Default, vec![DynamicObject::new("fast", &sc)]),
+            (false, "found 0 of at least 1".into())
+        );
+        assert_eq!(
+            storage.evaluate(Formatter::Default, vec![standard]),
+            (true, "found 1".into())
+        );
+
+        let pod = |name: &str, ready: &str| {
+            let ar = ApiResource::erase::<k8s_openapi::api::core::v1::Pod>(&());
+            let mut pod = DynamicObject::new(name, &ar).data(serde_json::json!({
+                "spec": {"containers": []},
+                "status": {
+                    "phase": "Running",
+                    "conditions": [{"type": "Ready", "status": ready}],
+                },
+            }));
+            pod.metadata.labels = Some(BTreeMap::from([("k8s-app".into(), "cilium".into())]));
+            pod
+        };
+        assert_eq!(
+            cni.evaluate(Formatter::Pod, vec![pod("a", "True"), pod("b", "False")]),
+            (false, "not ready: b".into())
+        );
+        assert_eq!(
+            cni.evaluate(Formatter::Pod, vec![pod("a", "True"), pod("b", "True")]),
+            (true, "2 ready".into())
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use kube::core::DynamicObject;
+
+use crate::{
+    client::GetResult,
+    output::{Health, StatusCell},
+};
+
+/// What `kubemc wait` waits for, written as `--for=condition=Available`,
+/// `--for=condition=Ready=False` or `--for=delete`
+#[derive(Clone, Debug, PartialEq)]
+pub enum WaitFor {
+    Condition { type_: String, status: String },
+    Delete,
+}
+
+impl FromStr for WaitFor {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s == "delete" {
+            return Ok(WaitFor::Delete);
+        }
+        let Some(condition) = s.strip_prefix("condition=") else {
+            return Err(anyhow!(
+                "invalid condition {}, expected condition=TYPE, condition=TYPE=STATUS or delete",
+                s
+            ));
+        };
+        let (type_, status) = condition.split_once('=').unwrap_or((condition, "True"));
+        if type_.is_empty() || status.is_empty() {
+            return Err(anyhow!("invalid condition {}", s));
+        }
+        Ok(WaitFor::Condition {
+            type_: type_.to_owned(),
+            status: status.to_owned(),
+        })
+    }
+}
+
+/// Where the wait on one cluster stands
+#[derive(Clone, Debug, PartialEq)]
+pub enum WaitState {
+    Waiting(String),
+    Met(String),
+    /// The object could not be fetched, which is retried until the timeout
+    Failed(String),
+}
+
+impl WaitState {
+    pub fn is_met(&self) -> bool {
+        matches!(self, WaitState::Met(_))
+    }
+
+    pub fn status(&self) -> StatusCell {
+        match self {
+            WaitState::Waiting(message) => StatusCell::new(message, Health::Progressing),
+            WaitState::Met(message) => StatusCell::new(message, Health::Healthy),
+            WaitState::Failed(message) => StatusCell::new(message, Health::Unhealthy),
+        }
+    }
+}
+
+impl WaitFor {
+    /// Checks the object fetched from one cluster against the condition
+    pub fn state(&self, result: GetResult) -> WaitState {
+        match (self, result) {
+            (WaitFor::Delete, GetResult::NotFound) => WaitState::Met("deleted".into()),
+            (WaitFor::Delete, GetResult::Found(_)) => WaitState::Waiting("exists".into()),
+            (WaitFor::Condition { .. }, GetResult::NotFound) => {
+                WaitState::Waiting("not found".into())
+            }
+            (WaitFor::Condition { type_, status }, GetResult::Found(object)) => {
+                condition_state(&object, type_, status)
+            }
+            (_, GetResult::Failed(e)) => WaitState::Failed(e),
+        }
+    }
+}
+
+// Looks the condition up in status.conditions, where every kind with conditions keeps them
+fn condition_state(object: &DynamicObject, type_: &str, status: &str) -> WaitState {
+    let condition = object.data["status"]["conditions"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|c| {
+            c["type"]
+                .as_str()
+                .is_some_and(|t| t.eq_ignore_ascii_case(type_))
+        });
+    let Some(condition) = condition else {
+        return WaitState::Waiting(format!("no {} condition", type_));
+    };
+    let current = condition["status"].as_str().unwrap_or("Unknown");
+    if current.eq_ignore_ascii_case(status) {
+        return WaitState::Met(format!("{}={}", type_, current));
+    }
+    match condition["reason"].as_str() {
+        Some(reason) => WaitState::Waiting(format!("{}={} ({})", type_, current, reason)),
+        None => WaitState::Waiting(format!("{}={}", type_, current)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn wait_for_conditions() {
+        assert_eq!(
+            "condition=Available".parse::<WaitFor>().unwrap(),
+            WaitFor::Condition {
+                type_: "Available".into(),
+                status: "True".into()
+            }
+        );
+        assert_eq!(
+            "condition=Ready=False".parse::<WaitFor>().unwrap(),
+            WaitFor::Condition {
+                type_: "Ready".into(),
+                status: "False".into()
+            }
+        );
+        assert_eq!("delete".parse::<WaitFor>().unwrap(), WaitFor::Delete);
+        assert!("jsonpath={.status}".parse::<WaitFor>().is_err());
+        assert!("condition=".parse::<WaitFor>().is_err());
+
+        let deployment = |status: &str| {
+            let object: DynamicObject = serde_json::from_value(serde_json::json!({
+                "apiVersion": "apps/v1",
+                "kind": "Deployment",
+                "metadata": {"name": "web"},
+                "status": {"conditions": [
+                    {"type": "Progressing", "status": "True"},
+                    {"type": "Available", "status": status, "reason": "MinimumReplicasUnavailable"},
+                ]},
+            }))
+            .unwrap();
+            GetResult::Found(Box::new(object))
+        };
+        let available: WaitFor = "condition=available".parse().unwrap();
+        assert_eq!(
+            available.state(deployment("True")),
+            WaitState::Met("available=True".into())
+        );
+        assert_eq!(
+            available.state(deployment("False")),
+            WaitState::Waiting("available=False (MinimumReplicasUnavailable)".into())
+        );
+        assert!(!available.state(GetResult::NotFound).is_met());
+        assert!(WaitFor::Delete.state(GetResult::NotFound).is_met());
+        assert!(!WaitFor::Delete.state(deployment("True")).is_met());
+    }
+}
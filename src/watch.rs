@@ -0,0 +1,145 @@
+use std::collections::HashMap;
+
+use kube::{core::DynamicObject, runtime::watcher::Event, ResourceExt};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeType {
+    Added,
+    Modified,
+    Deleted,
+}
+
+impl std::fmt::Display for ChangeType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ChangeType::Added => write!(f, "ADDED"),
+            ChangeType::Modified => write!(f, "MODIFIED"),
+            ChangeType::Deleted => write!(f, "DELETED"),
+        }
+    }
+}
+
+/// A change to a single object observed while watching
+#[derive(Clone, Debug)]
+pub struct ObjectEvent {
+    pub change: ChangeType,
+    pub clustername: String,
+    pub kind: String,
+    pub object: DynamicObject,
+    /// The last seen state of a modified object
+    pub previous: Option<DynamicObject>,
+}
+
+/// Last seen state of every watched object, used to tell additions from modifications and to
+/// find deletions missed while a watch was restarting
+#[derive(Default)]
+pub struct WatchState {
+    objects: HashMap<(String, String), HashMap<String, DynamicObject>>,
+}
+
+impl WatchState {
+    /// Applies a watcher event from a cluster and returns the resulting object changes
+    pub fn apply(
+        &mut self,
+        clustername: &str,
+        kind: &str,
+        event: Event<DynamicObject>,
+    ) -> Vec<ObjectEvent> {
+        let store = self
+            .objects
+            .entry((clustername.to_owned(), kind.to_owned()))
+            .or_default();
+        let to_event = |change, object, previous| ObjectEvent {
+            change,
+            clustername: clustername.to_owned(),
+            kind: kind.to_owned(),
+            object,
+            previous,
+        };
+        match event {
+            Event::Applied(object) => {
+                let previous = store.insert(object_key(&object), object.clone());
+                let change = match previous {
+                    Some(_) => ChangeType::Modified,
+                    None => ChangeType::Added,
+                };
+                vec![to_event(change, object, previous)]
+            }
+            Event::Deleted(object) => {
+                store.remove(&object_key(&object));
+                vec![to_event(ChangeType::Deleted, object, None)]
+            }
+            Event::Restarted(objects) => {
+                let mut previous_objects = std::mem::take(store);
+                let mut events = Vec::new();
+                for object in objects {
+                    let key = object_key(&object);
+                    store.insert(key.clone(), object.clone());
+                    match previous_objects.remove(&key) {
+                        None => events.push(to_event(ChangeType::Added, object, None)),
+                        Some(previous)
+                            if previous.resource_version() != object.resource_version() =>
+                        {
+                            events.push(to_event(ChangeType::Modified, object, Some(previous)))
+                        }
+                        Some(_) => {}
+                    }
+                }
+                for (_, object) in previous_objects {
+                    events.push(to_event(ChangeType::Deleted, object, None));
+                }
+                events
+            }
+        }
+    }
+
+    /// Drops what was seen on a cluster no longer watched
+    pub fn forget(&mut self, clustername: &str) {
+        self.objects
+            .retain(|(cluster, _), _| cluster != clustername);
+    }
+}
+
+fn object_key(object: &DynamicObject) -> String {
+    object.uid().unwrap_or_else(|| {
+        format!(
+            "{}/{}",
+            object.namespace().unwrap_or_default(),
+            object.name_any()
+        )
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use kube::discovery::ApiResource;
+
+    fn pod(name: &str, version: &str) -> DynamicObject {
+        let ar = ApiResource::erase::<k8s_openapi::api::core::v1::Pod>(&());
+        let mut object = DynamicObject::new(name, &ar);
+        object.metadata.resource_version = Some(version.into());
+        object
+    }
+
+    #[test]
+    fn track_changes_across_restarts() {
+        let mut state = WatchState::default();
+        let events = state.apply(
+            "c1",
+            "Pod",
+            Event::Restarted(vec![pod("a", "1"), pod("b", "1")]),
+        );
+        assert!(events.iter().all(|e| e.change == ChangeType::Added));
+
+        let events = state.apply("c1", "Pod", Event::Applied(pod("a", "2")));
+        assert_eq!(events[0].change, ChangeType::Modified);
+        assert!(events[0].previous.is_some());
+
+        // b vanished while the watch was down and a is unchanged
+        let events = state.apply("c1", "Pod", Event::Restarted(vec![pod("a", "2")]));
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].change, ChangeType::Deleted);
+        assert_eq!(events[0].object.name_any(), "b");
+    }
+}
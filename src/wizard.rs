@@ -0,0 +1,245 @@
+use std::io::{BufRead, Write};
+
+use anyhow::{anyhow, Result};
+use kube::config::Kubeconfig;
+
+use crate::config::{is_dns1123_label, Cluster, Clusterset, Config};
+
+/// Builds a config by asking which kubeconfig contexts go into which clustersets. Questions are
+/// written to `prompt` and answers read from `input`, one per line.
+pub fn config_wizard(
+    kubeconfig: &Kubeconfig,
+    input: &mut impl BufRead,
+    prompt: &mut impl Write,
+) -> Result<Config> {
+    let contexts = list_contexts(kubeconfig, prompt)?;
+    let mut clustersets: Vec<Clusterset> = Vec::new();
+    loop {
+        let name = ask(input, prompt, "clusterset name (empty to finish): ")?;
+        if name.is_empty() {
+            if clustersets.is_empty() {
+                writeln!(prompt, "at least one clusterset is needed")?;
+                continue;
+            }
+            break;
+        }
+        if clustersets.iter().any(|cs| cs.name == name) {
+            writeln!(prompt, "clusterset {} already exists", name)?;
+            continue;
+        }
+        let selected = loop {
+            let answer = ask(
+                input,
+                prompt,
+                &format!("contexts in {}, e.g. 1,3-4 or all: ", name),
+            )?;
+            match parse_selection(&answer, &contexts) {
+                Ok(selected) => break selected,
+                Err(e) => writeln!(prompt, "{}", e)?,
+            }
+        };
+        let namespace = loop {
+            let answer = ask(input, prompt, &format!("namespace of {} [default]: ", name))?;
+            if answer.is_empty() {
+                break "default".to_owned();
+            }
+            if is_dns1123_label(&answer) {
+                break answer;
+            }
+            writeln!(prompt, "{} is not a valid namespace name", answer)?;
+        };
+        clustersets.push(Clusterset {
+            name,
+            namespace,
+            clusters: selected
+                .into_iter()
+                .map(|i| Cluster {
+                    name: contexts[i].to_owned(),
+                    context: Some(contexts[i].to_owned()),
+                    ..Default::default()
+                })
+                .collect(),
+            include: Vec::new(),
+            pinned_cluster: None,
+            output: None,
+        });
+    }
+
+    let names: Vec<&str> = clustersets.iter().map(|cs| cs.name.as_str()).collect();
+    let current = if names.len() == 1 {
+        names[0].to_owned()
+    } else {
+        loop {
+            let answer = ask(
+                input,
+                prompt,
+                &format!(
+                    "current clusterset [1-{}, default {}]: ",
+                    names.len(),
+                    names[0]
+                ),
+            )?;
+            match parse_selection(&answer, &names).as_deref() {
+                _ if answer.is_empty() => break names[0].to_owned(),
+                Ok([i]) => break names[*i].to_owned(),
+                _ => writeln!(prompt, "pick one of the clustersets")?,
+            }
+        }
+    };
+    Ok(Config {
+        current_clusterset: current,
+        clustersets,
+        ..Default::default()
+    })
+}
+
+/// Asks which kubeconfig context a cluster added to the config connects through
+pub fn context_wizard(
+    kubeconfig: &Kubeconfig,
+    input: &mut impl BufRead,
+    prompt: &mut impl Write,
+) -> Result<String> {
+    let contexts = list_contexts(kubeconfig, prompt)?;
+    loop {
+        let answer = ask(input, prompt, "context of the cluster: ")?;
+        match parse_selection(&answer, &contexts).as_deref() {
+            Ok([i]) => return Ok(contexts[*i].to_owned()),
+            Ok(_) => writeln!(prompt, "pick a single context")?,
+            Err(e) => writeln!(prompt, "{}", e)?,
+        }
+    }
+}
+
+// Writes the numbered kubeconfig contexts to pick from, returning their names
+fn list_contexts<'a>(kubeconfig: &'a Kubeconfig, prompt: &mut impl Write) -> Result<Vec<&'a str>> {
+    if kubeconfig.contexts.is_empty() {
+        return Err(anyhow!("the kubeconfig has no contexts to pick from"));
+    }
+    writeln!(prompt, "kubeconfig contexts:")?;
+    for (i, context) in kubeconfig.contexts.iter().enumerate() {
+        match context.context.as_ref().map(|c| c.cluster.as_str()) {
+            Some(cluster) if cluster != context.name => writeln!(
+                prompt,
+                "{:>3}) {} (cluster {})",
+                i + 1,
+                context.name,
+                cluster
+            )?,
+            _ => writeln!(prompt, "{:>3}) {}", i + 1, context.name)?,
+        }
+    }
+    Ok(kubeconfig
+        .contexts
+        .iter()
+        .map(|context| context.name.as_str())
+        .collect())
+}
+
+// Asks a question and reads the trimmed answer, failing when the input ended
+fn ask(input: &mut impl BufRead, prompt: &mut impl Write, question: &str) -> Result<String> {
+    write!(prompt, "{}", question)?;
+    prompt.flush()?;
+    let mut answer = String::new();
+    if input.read_line(&mut answer)? == 0 {
+        return Err(anyhow!("input ended before the config was complete"));
+    }
+    Ok(answer.trim().to_owned())
+}
+
+/// Parses a selection of listed items such as `1,3-4`, `all` or item names into their indices,
+/// in the order given and without repeats
+pub fn parse_selection(answer: &str, items: &[&str]) -> Result<Vec<usize>> {
+    if answer.trim() == "all" {
+        return Ok((0..items.len()).collect());
+    }
+    let index = |part: &str| -> Result<usize> {
+        let i = match part.parse::<usize>() {
+            Ok(n) => n.wrapping_sub(1),
+            Err(_) => items
+                .iter()
+                .position(|item| *item == part)
+                .unwrap_or(usize::MAX),
+        };
+        if i < items.len() {
+            Ok(i)
+        } else {
+            Err(anyhow!("{} is not one of the listed items", part))
+        }
+    };
+    let mut selected = Vec::new();
+    for part in answer.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let range = match part.split_once('-') {
+            Some((start, end)) if start.parse::<usize>().is_ok() => index(start)?..=index(end)?,
+            _ => index(part)?..=index(part)?,
+        };
+        if range.is_empty() {
+            return Err(anyhow!("{} is not a valid range", part));
+        }
+        for i in range {
+            if !selected.contains(&i) {
+                selected.push(i);
+            }
+        }
+    }
+    if selected.is_empty() {
+        return Err(anyhow!("nothing selected"));
+    }
+    Ok(selected)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn build_config_from_answers() {
+        let contexts = ["prod-us", "prod-eu", "staging", "kind-local"];
+        assert_eq!(parse_selection("1,3-4", &contexts).unwrap(), [0, 2, 3]);
+        assert_eq!(parse_selection("all", &contexts).unwrap(), [0, 1, 2, 3]);
+        assert_eq!(parse_selection("staging, 1, 3", &contexts).unwrap(), [2, 0]);
+        // names containing dashes are not ranges
+        assert_eq!(parse_selection("kind-local", &contexts).unwrap(), [3]);
+        assert!(parse_selection("5", &contexts).is_err());
+        assert!(parse_selection("3-1", &contexts).is_err());
+        assert!(parse_selection("", &contexts).is_err());
+
+        let kubeconfig: Kubeconfig = serde_yaml::from_str(
+            r#"
+contexts:
+- name: prod-us
+  context: {cluster: us-east-1, user: admin}
+- name: prod-eu
+  context: {cluster: eu-west-1, user: admin}
+- name: staging
+  context: {cluster: staging, user: admin}
+"#,
+        )
+        .unwrap();
+        // an invalid selection and namespace are asked again, and an empty name finishes
+        let answers = "prod\n9\n1-2\nShop\nshop\nstaging\n3\n\n\n2\n";
+        let mut prompt = Vec::new();
+        let config = config_wizard(&kubeconfig, &mut answers.as_bytes(), &mut prompt).unwrap();
+        let prompt = String::from_utf8(prompt).unwrap();
+        assert!(prompt.contains("  1) prod-us (cluster us-east-1)\n"));
+        assert!(prompt.contains("  3) staging\n"));
+        assert!(prompt.contains("9 is not one of the listed items"));
+        assert_eq!(config.current_clusterset, "staging");
+        assert_eq!(config.clustersets.len(), 2);
+        let prod = &config.clustersets[0];
+        assert_eq!(prod.namespace, "shop");
+        let clusters: Vec<_> = prod.clusters.iter().map(|c| c.context.as_deref()).collect();
+        assert_eq!(clusters, [Some("prod-us"), Some("prod-eu")]);
+        assert_eq!(config.clustersets[1].namespace, "default");
+        assert!(config.validate(&kubeconfig).is_empty());
+
+        // giving up halfway does not produce a config
+        assert!(config_wizard(&kubeconfig, &mut "prod\n".as_bytes(), &mut Vec::new()).is_err());
+
+        let mut prompt = Vec::new();
+        let context = context_wizard(&kubeconfig, &mut "1-2\nstaging\n".as_bytes(), &mut prompt);
+        assert_eq!(context.unwrap(), "staging");
+        assert!(String::from_utf8(prompt)
+            .unwrap()
+            .contains("pick a single context"));
+    }
+}
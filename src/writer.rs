@@ -0,0 +1,150 @@
+use std::io::Write;
+
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+use tokio::io::{AsyncWrite, AsyncWriteExt, BufWriter};
+
+/// Writes a stream of YAML documents, each preceded by a `---` separator, as they are produced.
+/// Only a single document is ever serialized in memory at a time.
+pub struct YamlDocumentWriter<W: AsyncWrite + Unpin> {
+    writer: BufWriter<W>,
+    written: usize,
+    max_objects: Option<usize>,
+}
+
+impl<W: AsyncWrite + Unpin> YamlDocumentWriter<W> {
+    pub fn new(writer: W, max_objects: Option<usize>) -> Self {
+        Self {
+            writer: BufWriter::new(writer),
+            written: 0,
+            max_objects,
+        }
+    }
+
+    /// Serializes and writes a single document. Fails without writing once `max_objects`
+    /// documents have already been written.
+    pub async fn write<T: Serialize>(&mut self, document: &T) -> Result<()> {
+        if let Some(max) = self.max_objects {
+            if self.written >= max {
+                return Err(anyhow!("output exceeds the limit of {} objects", max));
+            }
+        }
+        let yaml = serde_yaml::to_string(document)?;
+        self.writer.write_all(b"---\n").await?;
+        self.writer.write_all(yaml.as_bytes()).await?;
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Flushes remaining output and returns the number of documents written
+    pub async fn finish(mut self) -> Result<usize> {
+        self.writer.flush().await?;
+        Ok(self.written)
+    }
+}
+
+/// Writes a JSON array one element at a time so that elements can be dropped once written
+pub struct JsonArrayWriter<W: Write> {
+    writer: std::io::BufWriter<W>,
+    written: usize,
+}
+
+impl<W: Write> JsonArrayWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: std::io::BufWriter::new(writer),
+            written: 0,
+        }
+    }
+
+    pub fn write<T: Serialize>(&mut self, element: &T) -> Result<()> {
+        let separator: &[u8] = if self.written == 0 { b"[\n" } else { b",\n" };
+        self.writer.write_all(separator)?;
+        serde_json::to_writer_pretty(&mut self.writer, element)?;
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Closes the array and returns the number of elements written
+    pub fn finish(mut self) -> Result<usize> {
+        let end: &[u8] = if self.written == 0 { b"[]\n" } else { b"\n]\n" };
+        self.writer.write_all(end)?;
+        self.writer.flush()?;
+        Ok(self.written)
+    }
+}
+
+/// Writes one compact JSON value per line. Lines are buffered until `flush`, so callers flush
+/// whenever a batch of results is complete to pass it on without waiting for the rest.
+pub struct JsonLinesWriter<W: Write> {
+    writer: std::io::BufWriter<W>,
+    written: usize,
+}
+
+impl<W: Write> JsonLinesWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer: std::io::BufWriter::new(writer),
+            written: 0,
+        }
+    }
+
+    pub fn write<T: Serialize>(&mut self, line: &T) -> Result<()> {
+        serde_json::to_writer(&mut self.writer, line)?;
+        self.writer.write_all(b"\n")?;
+        self.written += 1;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.writer.flush()?)
+    }
+
+    /// Flushes remaining lines and returns the number of lines written
+    pub fn finish(mut self) -> Result<usize> {
+        self.writer.flush()?;
+        Ok(self.written)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn write_documents_with_limit() {
+        let mut out = Vec::new();
+        let mut writer = YamlDocumentWriter::new(&mut out, Some(2));
+        writer.write(&serde_json::json!({"a": 1})).await.unwrap();
+        writer.write(&serde_json::json!({"b": 2})).await.unwrap();
+        assert!(writer.write(&serde_json::json!({"c": 3})).await.is_err());
+        assert_eq!(writer.finish().await.unwrap(), 2);
+        assert_eq!(String::from_utf8(out).unwrap(), "---\na: 1\n---\nb: 2\n");
+    }
+
+    #[test]
+    fn write_json_array() {
+        let mut out = Vec::new();
+        let mut writer = JsonArrayWriter::new(&mut out);
+        writer.write(&1).unwrap();
+        writer.write(&serde_json::json!({"a": 1})).unwrap();
+        assert_eq!(writer.finish().unwrap(), 2);
+        let written = String::from_utf8(out).unwrap();
+        assert_eq!(written, "[\n1,\n{\n  \"a\": 1\n}\n]\n");
+        serde_json::from_str::<serde_json::Value>(&written).unwrap();
+
+        let mut empty = Vec::new();
+        JsonArrayWriter::new(&mut empty).finish().unwrap();
+        assert_eq!(empty, b"[]\n");
+    }
+
+    #[test]
+    fn write_json_lines() {
+        let mut out = Vec::new();
+        let mut writer = JsonLinesWriter::new(&mut out);
+        writer.write(&serde_json::json!({"a": {"b": 1}})).unwrap();
+        writer.write(&2).unwrap();
+        assert_eq!(writer.finish().unwrap(), 2);
+        assert_eq!(String::from_utf8(out).unwrap(), "{\"a\":{\"b\":1}}\n2\n");
+    }
+}